@@ -0,0 +1,69 @@
+//! Interpreter throughput on a tight arithmetic loop, in instructions per second.
+//! Run with `cargo bench --bench dispatch`.
+
+use std::rc::Rc;
+use std::time::Instant;
+use iris_vm::asm::assemble;
+use iris_vm::optimize::quicken_function;
+use iris_vm::vm::register::translate_function;
+use iris_vm::vm::vm::IrisVM;
+
+const ITERATIONS: i32 = 2_000_000;
+
+fn count_up(quicken: bool, registers: bool) -> Rc<iris_vm::vm::function::Function> {
+    let mut function = Rc::new(assemble(&format!(r#"
+        .function count_up 0
+        .const one i32 1
+
+                LoadImmediateI32 0
+                LoadImmediateI32 {}
+                LoadImmediateI32 0
+        loop:   GetLocalVariable8 0
+                GetLocalVariable8 1
+                LessThanInt32
+                JumpIfFalse done
+                GetLocalVariable8 2
+                PushConstant8 one
+                AddInt32
+                SetLocalVariable8 2
+                PopStack
+                GetLocalVariable8 0
+                PushConstant8 one
+                AddInt32
+                SetLocalVariable8 0
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 2
+                ReturnFromFunction
+    "#, ITERATIONS)).unwrap());
+    if quicken {
+        quicken_function(&mut function).unwrap();
+    }
+    if registers {
+        translate_function(&mut function).unwrap();
+    }
+    function
+}
+
+fn bench(name: &str, quicken: bool, registers: bool, tiering: bool) {
+    // Counting instructions times each one, so count in a separate run. Stats turn the
+    // register form off, so this counts bytecode instructions either way.
+    let mut counter = IrisVM::builder().stats(true).build();
+    counter.call(count_up(quicken, false), &[]).unwrap();
+    let instructions = counter.stats().unwrap().total_instructions();
+
+    let mut vm = IrisVM::builder().tiering(tiering).build();
+    let started = Instant::now();
+    vm.call(count_up(quicken, registers), &[]).unwrap();
+    let elapsed = started.elapsed();
+    println!("{:<10} {:>10} instructions in {:>8.1?} ({:.1} M/s)",
+        name, instructions, elapsed, instructions as f64 / elapsed.as_secs_f64() / 1e6);
+}
+
+fn main() {
+    bench("plain", false, false, false);
+    bench("quickened", true, false, false);
+    bench("registers", true, true, false);
+    // Moves to register form partway through the loop.
+    bench("tiered", false, false, true);
+}
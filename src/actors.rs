@@ -0,0 +1,155 @@
+//! Actors: isolated VMs on their own threads that process messages one at a time.
+//!
+//! `ActorSystem::spawn` starts a thread with a fresh `IrisVM` and a mailbox, and calls the
+//! handler, a bytecode function taking one argument, with each message sent to it. Actors
+//! share nothing: messages and the handler are copied like `vm::channel` messages, so only
+//! immutable values can be sent. Send a channel along to get replies. Each actor VM has the
+//! `actor_spawn(handler)`, `actor_send(id, message)` and `actor_stop(id)` natives, so actors
+//! can start and talk to other actors of the same system by id.
+//!
+//! A handler error stops its actor; `join` reports it.
+
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use crate::data::shared::SharedValue;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub type ActorId = i64;
+
+enum Envelope {
+    Message(SharedValue),
+    Stop,
+}
+
+struct Actor {
+    mailbox: Sender<Envelope>,
+    thread: JoinHandle<Result<(), String>>,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: ActorId,
+    actors: HashMap<ActorId, Actor>,
+}
+
+type Setup = Arc<dyn Fn(&mut IrisVM) + Send + Sync>;
+
+/// The actors that can reach each other by id. Clones share the same actors.
+#[derive(Clone, Default)]
+pub struct ActorSystem {
+    registry: Arc<Mutex<Registry>>,
+    setup: Option<Setup>,
+}
+
+impl ActorSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `setup` on each actor's VM before its first message, e.g. to register natives.
+    pub fn with_setup(mut self, setup: impl Fn(&mut IrisVM) + Send + Sync + 'static) -> Self {
+        self.setup = Some(Arc::new(setup));
+        self
+    }
+
+    pub fn spawn(&self, handler: &Value) -> Result<ActorId, VMError> {
+        let handler = match handler {
+            Value::Function(function) if function.bytecode.is_some() && function.arity == 1 => {
+                SharedValue::from_value(handler).map_err(|e| VMError::TypeMismatch(e.to_string()))?
+            }
+            _ => return Err(VMError::TypeMismatch("Actor handlers must be bytecode functions taking one message".to_string())),
+        };
+        let (mailbox, messages) = mpsc::channel();
+        let system = self.clone();
+        let thread = thread::spawn(move || {
+            let mut vm = IrisVM::new();
+            register(&mut vm, &system);
+            if let Some(setup) = &system.setup {
+                setup(&mut vm);
+            }
+            let Value::Function(handler) = handler.to_value() else { unreachable!() };
+            for envelope in messages {
+                match envelope {
+                    Envelope::Message(message) => {
+                        vm.call(handler.clone(), &[message.to_value()]).map_err(|e| e.to_string())?;
+                    }
+                    Envelope::Stop => break,
+                }
+            }
+            Ok(())
+        });
+        let mut registry = self.registry();
+        registry.next_id += 1;
+        let id = registry.next_id;
+        registry.actors.insert(id, Actor { mailbox, thread, stopped: false });
+        Ok(id)
+    }
+
+    pub fn send(&self, id: ActorId, message: &Value) -> Result<(), VMError> {
+        let message = SharedValue::from_value(message).map_err(|e| VMError::TypeMismatch(e.to_string()))?;
+        let registry = self.registry();
+        let actor = registry.actors.get(&id).filter(|actor| !actor.stopped).ok_or_else(|| no_actor(id))?;
+        // A failed send means the actor has stopped on an error, which `join` reports.
+        let _ = actor.mailbox.send(Envelope::Message(message));
+        Ok(())
+    }
+
+    /// Stops the actor once it has handled the messages already in its mailbox.
+    pub fn stop(&self, id: ActorId) -> Result<(), VMError> {
+        let mut registry = self.registry();
+        let actor = registry.actors.get_mut(&id).ok_or_else(|| no_actor(id))?;
+        actor.stopped = true;
+        let _ = actor.mailbox.send(Envelope::Stop);
+        Ok(())
+    }
+
+    /// Stops the actor and waits for it, returning the error that stopped it, if any.
+    pub fn join(&self, id: ActorId) -> Result<(), String> {
+        self.stop(id).map_err(|e| e.to_string())?;
+        let actor = self.registry().actors.remove(&id).ok_or_else(|| no_actor(id).to_string())?;
+        actor.thread.join().unwrap_or_else(|_| Err(format!("Actor {} panicked", id)))
+    }
+
+    /// Joins every actor, returning the first error.
+    pub fn shutdown(&self) -> Result<(), String> {
+        let ids: Vec<ActorId> = self.registry().actors.keys().copied().collect();
+        ids.into_iter().map(|id| self.join(id)).fold(Ok(()), Result::and)
+    }
+
+    fn registry(&self) -> MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn no_actor(id: ActorId) -> VMError {
+    VMError::InvalidOperand(format!("No running actor {}", id))
+}
+
+/// Defines the actor natives on `vm`, spawning into `system`.
+pub fn register(vm: &mut IrisVM, system: &ActorSystem) {
+    let spawner = system.clone();
+    vm.register_native("actor_spawn", move |args| {
+        let handler = args.first().ok_or(VMError::ArityMismatch { expected: 1, found: 0 })?;
+        Ok(Value::I64(spawner.spawn(handler)?))
+    });
+    let sender = system.clone();
+    vm.register_native("actor_send", move |args| {
+        let message = args.get(1).ok_or(VMError::ArityMismatch { expected: 2, found: args.len() })?;
+        sender.send(actor_id(args)?, message)?;
+        Ok(Value::Null)
+    });
+    let stopper = system.clone();
+    vm.register_native("actor_stop", move |args| {
+        stopper.stop(actor_id(args)?)?;
+        Ok(Value::Null)
+    });
+}
+
+fn actor_id(args: &[Value]) -> Result<ActorId, VMError> {
+    i64::from_value(args.first().ok_or(VMError::ArityMismatch { expected: 1, found: 0 })?)
+}
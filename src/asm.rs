@@ -0,0 +1,488 @@
+//! Textual assembler for Iris bytecode.
+//!
+//! ```text
+//! .function countdown 0          ; name and arity of the assembled function
+//! .const greeting str "liftoff"  ; named constant-pool entry
+//!
+//!         LoadImmediateI32 3
+//! loop:   DuplicateTop
+//!         PrintTopOfStack
+//!         LoadImmediateI32 1
+//!         SubtractInt32
+//!         DuplicateTop
+//!         JumpIfFalse done
+//!         LoopJump loop
+//! done:   PushConstant8 greeting
+//!         ReturnFromFunction
+//! ```
+//!
+//! Mnemonics are `OpCode` variant names (case-insensitive). Operands are numbers, labels
+//! for jump and switch targets, `.const` names, or string literals, which are added to
+//! the constant pool. `.byte` emits raw bytes, e.g. for custom opcodes. `.line 42` (or
+//! `.line 42 7` with a column) attributes the instructions that follow to a source line.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use crate::debug::lines::Span;
+use crate::vm::bigint::BigInt;
+use crate::vm::chunk::{Chunk, ChunkWriter};
+use crate::vm::function::Function;
+use crate::vm::opcode::OpCode;
+use crate::vm::intern::intern;
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, AsmError> {
+    Err(AsmError { line, message: message.into() })
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Word(String),
+    Str(String),
+}
+
+enum Item {
+    Instruction { line: usize, opcode: OpCode, operands: Vec<Token> },
+    Bytes(Vec<u8>),
+    Span(Span),
+}
+
+/// Assembles `source` into a function. The name and arity come from the `.function`
+/// directive and default to `main` and 0.
+pub fn assemble(source: &str) -> Result<Function, AsmError> {
+    let mut assembler = Assembler::default();
+    let chunk = assembler.assemble(source)?;
+    let name = assembler.name.unwrap_or_else(|| String::from("main"));
+    Ok(Function::new_bytecode(name, assembler.arity, chunk.code, chunk.constants).with_lines(chunk.lines))
+}
+
+/// Assembles `source` into a bare chunk, ignoring any `.function` directive.
+pub fn assemble_chunk(source: &str) -> Result<Chunk, AsmError> {
+    Assembler::default().assemble(source)
+}
+
+#[derive(Default)]
+struct Assembler {
+    name: Option<String>,
+    arity: usize,
+    chunk: Chunk,
+    constants: HashMap<String, usize>,
+    labels: HashMap<String, usize>,
+}
+
+impl Assembler {
+    fn assemble(&mut self, source: &str) -> Result<Chunk, AsmError> {
+        let mut items = Vec::new();
+        let mut offset = 0;
+
+        // First pass: parse every line and assign label offsets.
+        for (index, text) in source.lines().enumerate() {
+            let line = index + 1;
+            let mut tokens = tokenize(text, line)?;
+            while let Some(Token::Word(word)) = tokens.first() {
+                let Some(label) = word.strip_suffix(':') else { break };
+                if self.labels.insert(label.to_string(), offset).is_some() {
+                    return error(line, format!("label '{}' is defined twice", label));
+                }
+                tokens.remove(0);
+            }
+            let Some(first) = tokens.first().cloned() else { continue };
+            let Token::Word(head) = first else {
+                return error(line, "expected a mnemonic or directive");
+            };
+            let operands = tokens.split_off(1);
+
+            if let Some(directive) = head.strip_prefix('.') {
+                if directive == "line" {
+                    items.push(Item::Span(parse_span(&operands, line)?));
+                    continue;
+                }
+                if let Some(bytes) = self.directive(directive, &operands, line)? {
+                    offset += bytes.len();
+                    items.push(Item::Bytes(bytes));
+                }
+                continue;
+            }
+
+            let opcode = OpCode::from_mnemonic(&head)
+                .ok_or_else(|| AsmError { line, message: format!("unknown mnemonic '{}'", head) })?;
+            offset += instruction_size(opcode, operands.len(), line)?;
+            items.push(Item::Instruction { line, opcode, operands });
+        }
+
+        // Second pass: encode with every label known.
+        for item in items {
+            match item {
+                Item::Bytes(bytes) => self.chunk.code.extend(bytes),
+                Item::Span(span) => self.chunk.set_span(span),
+                Item::Instruction { line, opcode, operands } => self.encode(opcode, &operands, line)?,
+            }
+        }
+        Ok(std::mem::take(&mut self.chunk))
+    }
+
+    fn directive(&mut self, directive: &str, operands: &[Token], line: usize) -> Result<Option<Vec<u8>>, AsmError> {
+        match (directive, operands) {
+            ("function", [Token::Word(name), Token::Word(arity)]) => {
+                self.name = Some(name.clone());
+                self.arity = parse_int(arity, line)? as usize;
+                Ok(None)
+            }
+            ("const", [Token::Word(name), rest @ ..]) if !rest.is_empty() => {
+                let value = parse_constant(rest, line)?;
+                let index = self.chunk.find_or_add_constant(value);
+                if self.constants.insert(name.clone(), index).is_some() {
+                    return error(line, format!("constant '{}' is defined twice", name));
+                }
+                Ok(None)
+            }
+            ("byte", bytes) if !bytes.is_empty() => {
+                bytes.iter().map(|token| self.number(token, 0, u8::MAX as i128, line).map(|b| b as u8))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Some)
+            }
+            _ => error(line, format!("malformed directive '.{}'", directive)),
+        }
+    }
+
+    fn encode(&mut self, opcode: OpCode, operands: &[Token], line: usize) -> Result<(), AsmError> {
+        use OpCode::*;
+        let start = self.chunk.code.len();
+        let next = start + instruction_size(opcode, operands.len(), line)?;
+        self.chunk.write(opcode);
+
+        match opcode {
+            UnconditionalJump | BeginTryBlock | FinallyBlock => {
+                let distance = self.target(&operands[0], line)? as i128 - next as i128;
+                let distance = checked(distance, 0, u8::MAX as i128, line, "forward jump")?;
+                self.chunk.write(distance as u8);
+            }
+            ShortJump => {
+                let distance = self.target(&operands[0], line)? as i128 - next as i128;
+                let distance = checked(distance, i8::MIN as i128, i8::MAX as i128, line, "short jump")?;
+                self.chunk.write(distance as i8 as u8);
+            }
+            LoopJump => {
+                let distance = next as i128 - self.target(&operands[0], line)? as i128;
+                let distance = checked(distance, 0, u16::MAX as i128, line, "loop jump")?;
+                self.chunk.write(distance as u16);
+            }
+            JumpIfTrue | JumpIfFalse | JumpIfNull | JumpIfNonNull | CompareAndBranchEqualInt32
+            | CompareAndBranchNotEqualInt32 | CompareAndBranchLessThanInt32 | CompareAndBranchGreaterThanInt32 => {
+                let distance = self.target(&operands[0], line)? as i128 - next as i128;
+                let distance = checked(distance, 0, u16::MAX as i128, line, "conditional jump")?;
+                self.chunk.write(distance as u16);
+            }
+            TableSwitch => {
+                self.write_switch_offset(start, &operands[0], line)?;
+                let low = self.number(&operands[1], i32::MIN as i128, i32::MAX as i128, line)? as i32;
+                let high = self.number(&operands[2], i32::MIN as i128, i32::MAX as i128, line)? as i32;
+                if high as i64 - low as i64 + 1 != operands.len() as i64 - 3 {
+                    return error(line, "TableSwitch needs one target per case from low to high");
+                }
+                self.chunk.write(low);
+                self.chunk.write(high);
+                for target in &operands[3..] {
+                    self.write_switch_offset(start, target, line)?;
+                }
+            }
+            LookupSwitch | RangeSwitch => {
+                let entry = if opcode == LookupSwitch { 2 } else { 3 };
+                self.write_switch_offset(start, &operands[0], line)?;
+                self.chunk.write(((operands.len() - 1) / entry) as u16);
+                for case in operands[1..].chunks(entry) {
+                    for key in &case[..entry - 1] {
+                        let key = self.number(key, i32::MIN as i128, i32::MAX as i128, line)? as i32;
+                        self.chunk.write(key);
+                    }
+                    self.write_switch_offset(start, &case[entry - 1], line)?;
+                }
+            }
+            LoadImmediateI8 => {
+                let value = self.number(&operands[0], i8::MIN as i128, u8::MAX as i128, line)?;
+                self.chunk.write(value as u8);
+            }
+            LoadImmediateI16 => {
+                let value = self.number(&operands[0], i16::MIN as i128, u16::MAX as i128, line)?;
+                self.chunk.write(value as u16);
+            }
+            LoadImmediateI32 => {
+                let value = self.number(&operands[0], i32::MIN as i128, u32::MAX as i128, line)?;
+                self.chunk.write(value as i32);
+            }
+            LoadImmediateI64 => {
+                let value = self.number(&operands[0], i64::MIN as i128, u64::MAX as i128, line)?;
+                self.chunk.code.extend((value as i64).to_be_bytes());
+            }
+            LoadImmediateF32 => {
+                let value = parse_float(word(&operands[0], line)?, line)? as f32;
+                self.chunk.code.extend(value.to_be_bytes());
+            }
+            LoadImmediateF64 => {
+                let value = parse_float(word(&operands[0], line)?, line)?;
+                self.chunk.code.extend(value.to_be_bytes());
+            }
+            AddInt32WithConstant | AddInt64WithConstant | MultiplyInt32WithConstant | MultiplyInt64WithConstant => {
+                let value = self.number(&operands[0], i8::MIN as i128, i8::MAX as i128, line)?;
+                self.chunk.write(value as i8 as u8);
+            }
+            InvokeMethod8 => {
+                let method = self.number(&operands[0], 0, u8::MAX as i128, line)?;
+                self.chunk.write(method as u8);
+                let args = self.number(&operands[1], 0, u8::MAX as i128, line)?;
+                self.chunk.write(args as u8);
+            }
+            AddLocalInt32WithConstant => {
+                let slot = self.number(&operands[0], 0, u8::MAX as i128, line)?;
+                self.chunk.write(slot as u8);
+                let value = self.number(&operands[1], i8::MIN as i128, i8::MAX as i128, line)?;
+                self.chunk.write(value as i8 as u8);
+            }
+            JumpIfLocalsNotLessInt32 => {
+                for operand in &operands[..2] {
+                    let slot = self.number(operand, 0, u8::MAX as i128, line)?;
+                    self.chunk.write(slot as u8);
+                }
+                let distance = self.target(&operands[2], line)? as i128 - next as i128;
+                let distance = checked(distance, 0, u16::MAX as i128, line, "forward jump")?;
+                self.chunk.write(distance as u16);
+            }
+            CaptureUpvalue => {
+                let local = self.number(&operands[0], 0, 1, line)?;
+                self.chunk.write(local as u8);
+                let index = self.number(&operands[1], 0, u8::MAX as i128, line)?;
+                self.chunk.write(index as u8);
+            }
+            InvokeMethod16 | CallWithInlineCache | MegamorphicMethodCall => {
+                let method = self.number(&operands[0], 0, u16::MAX as i128, line)?;
+                self.chunk.write(method as u16);
+                let args = self.number(&operands[1], 0, u8::MAX as i128, line)?;
+                self.chunk.write(args as u8);
+            }
+            _ => match opcode.operand_len() {
+                Some(1) => {
+                    let value = self.number(&operands[0], 0, u8::MAX as i128, line)?;
+                    self.chunk.write(value as u8);
+                }
+                Some(2) => {
+                    let value = self.number(&operands[0], 0, u16::MAX as i128, line)?;
+                    self.chunk.write(value as u16);
+                }
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    fn write_switch_offset(&mut self, start: usize, token: &Token, line: usize) -> Result<(), AsmError> {
+        let distance = self.target(token, line)? as i128 - start as i128;
+        let distance = checked(distance, 0, u16::MAX as i128, line, "switch target")?;
+        self.chunk.write(distance as u16);
+        Ok(())
+    }
+
+    fn target(&self, token: &Token, line: usize) -> Result<usize, AsmError> {
+        let name = word(token, line)?;
+        match self.labels.get(name) {
+            Some(offset) => Ok(*offset),
+            None => error(line, format!("undefined label '{}'", name)),
+        }
+    }
+
+    /// Resolves a numeric operand: a literal, a `.const` name (its pool index), or a string
+    /// literal, which is added to the constant pool.
+    fn number(&mut self, token: &Token, min: i128, max: i128, line: usize) -> Result<i128, AsmError> {
+        let value = match token {
+            Token::Str(text) => {
+                self.chunk.find_or_add_constant(Value::Str(intern(text))) as i128
+            }
+            Token::Word(word) => match self.constants.get(word) {
+                Some(index) => *index as i128,
+                None => parse_int(word, line)?,
+            },
+        };
+        checked(value, min, max, line, "operand")
+    }
+}
+
+fn checked(value: i128, min: i128, max: i128, line: usize, what: &str) -> Result<i128, AsmError> {
+    if value < min || value > max {
+        return error(line, format!("{} {} does not fit in {}..={}", what, value, min, max));
+    }
+    Ok(value)
+}
+
+fn instruction_size(opcode: OpCode, operands: usize, line: usize) -> Result<usize, AsmError> {
+    let (expected, size) = match opcode {
+        OpCode::TableSwitch if operands >= 3 => (operands, 11 + (operands - 3) * 2),
+        OpCode::LookupSwitch if operands % 2 == 1 => (operands, 5 + (operands / 2) * 6),
+        OpCode::RangeSwitch if operands % 3 == 1 => (operands, 5 + (operands / 3) * 10),
+        OpCode::TableSwitch => return error(line, "expected: TableSwitch default, low, high, targets..."),
+        OpCode::LookupSwitch => return error(line, "expected: LookupSwitch default, (key, target)..."),
+        OpCode::RangeSwitch => return error(line, "expected: RangeSwitch default, (start, end, target)..."),
+        OpCode::InvokeMethod8 | OpCode::InvokeMethod16 | OpCode::CallWithInlineCache
+        | OpCode::MegamorphicMethodCall | OpCode::CaptureUpvalue | OpCode::AddLocalInt32WithConstant => {
+            (2, opcode.opcode_len() + opcode.operand_len().unwrap_or(0))
+        }
+        OpCode::JumpIfLocalsNotLessInt32 => (3, opcode.opcode_len() + 4),
+        _ => {
+            let len = opcode.operand_len().unwrap_or(0);
+            (usize::from(len > 0), opcode.opcode_len() + len)
+        }
+    };
+    if operands != expected {
+        return error(line, format!("{:?} takes {} operand(s), found {}", opcode, expected, operands));
+    }
+    Ok(size)
+}
+
+fn tokenize(text: &str, line: usize) -> Result<Vec<Token>, AsmError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ';' => break,
+            c if c.is_whitespace() || c == ',' => {
+                chars.next();
+            }
+            '"' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => text.push('\n'),
+                            Some('t') => text.push('\t'),
+                            Some('0') => text.push('\0'),
+                            Some(c @ ('"' | '\\')) => text.push(c),
+                            other => return error(line, format!("invalid escape '\\{}'", other.unwrap_or(' '))),
+                        },
+                        Some(c) => text.push(c),
+                        None => return error(line, "unterminated string literal"),
+                    }
+                }
+                tokens.push(Token::Str(text));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == ',' || c == ';' || c == '"' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                    if c == ':' {
+                        break;
+                    }
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn word(token: &Token, line: usize) -> Result<&str, AsmError> {
+    match token {
+        Token::Word(word) => Ok(word),
+        Token::Str(_) => error(line, "unexpected string literal"),
+    }
+}
+
+fn parse_span(operands: &[Token], line: usize) -> Result<Span, AsmError> {
+    let number = |token: &Token| match token {
+        Token::Word(word) => checked(parse_int(word, line)?, 0, u32::MAX as i128, line, "line number").map(|n| n as u32),
+        Token::Str(_) => error(line, "expected a line number"),
+    };
+    match operands {
+        [source_line] => Ok(Span::line(number(source_line)?)),
+        [source_line, column] => Ok(Span { line: number(source_line)?, column: number(column)? }),
+        _ => error(line, "malformed directive '.line'"),
+    }
+}
+
+fn parse_int(text: &str, line: usize) -> Result<i128, AsmError> {
+    let cleaned = text.replace('_', "");
+    let (negative, digits) = match cleaned.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, cleaned.as_str()),
+    };
+    let parsed = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16),
+        None => digits.parse::<i128>(),
+    };
+    match parsed {
+        Ok(value) if negative => Ok(-value),
+        Ok(value) => Ok(value),
+        Err(_) => error(line, format!("expected a number, label or constant, found '{}'", text)),
+    }
+}
+
+fn parse_float(text: &str, line: usize) -> Result<f64, AsmError> {
+    text.parse::<f64>().or_else(|_| error(line, format!("expected a float, found '{}'", text)))
+}
+
+fn parse_constant(tokens: &[Token], line: usize) -> Result<Value, AsmError> {
+    let ty = word(&tokens[0], line)?;
+    let literal = || -> Result<&Token, AsmError> {
+        match tokens {
+            [_, literal] => Ok(literal),
+            _ => error(line, format!("constant of type '{}' needs exactly one value", ty)),
+        }
+    };
+    let int = |min: i128, max: i128| -> Result<i128, AsmError> {
+        checked(parse_int(word(literal()?, line)?, line)?, min, max, line, ty)
+    };
+
+    let value = match ty {
+        "null" if tokens.len() == 1 => Value::Null,
+        "bool" => match word(literal()?, line)? {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            other => return error(line, format!("expected true or false, found '{}'", other)),
+        },
+        "i8" => Value::I8(int(i8::MIN as i128, i8::MAX as i128)? as i8),
+        "i16" => Value::I16(int(i16::MIN as i128, i16::MAX as i128)? as i16),
+        "i32" => Value::I32(int(i32::MIN as i128, i32::MAX as i128)? as i32),
+        "i64" => Value::I64(int(i64::MIN as i128, i64::MAX as i128)? as i64),
+        "i128" => Value::I128(int(i128::MIN, i128::MAX)?),
+        "u8" => Value::U8(int(0, u8::MAX as i128)? as u8),
+        "u16" => Value::U16(int(0, u16::MAX as i128)? as u16),
+        "u32" => Value::U32(int(0, u32::MAX as i128)? as u32),
+        "u64" => Value::U64(int(0, u64::MAX as i128)? as u64),
+        "u128" => Value::U128(int(0, i128::MAX)? as u128),
+        "f32" => Value::F32(parse_float(word(literal()?, line)?, line)? as f32),
+        "f64" => Value::F64(parse_float(word(literal()?, line)?, line)?),
+        "bigint" => match word(literal()?, line)?.parse::<BigInt>() {
+            Ok(value) => Value::BigInt(Rc::new(value)),
+            Err(_) => return error(line, format!("expected an integer, found '{}'", word(literal()?, line)?)),
+        },
+        "char" => match literal()? {
+            Token::Str(text) if text.chars().count() == 1 => Value::Char(text.chars().next().unwrap_or_default()),
+            _ => return error(line, "char constants need a quoted single character"),
+        },
+        "str" => match literal()? {
+            Token::Str(text) => Value::Str(intern(text)),
+            Token::Word(_) => return error(line, "str constants need a quoted string"),
+        },
+        _ => return error(line, format!("unknown constant type '{}'", ty)),
+    };
+    Ok(value)
+}
@@ -0,0 +1,3 @@
+//! Compilers from verified bytecode to other targets.
+
+pub mod wasm;
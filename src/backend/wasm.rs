@@ -0,0 +1,287 @@
+//! WebAssembly modules compiled from Iris functions.
+//!
+//! Compiles the register form (see `vm::register`), so the same functions qualify: Int32 code
+//! without calls. Every register becomes an `i32` local, the arguments being the parameters,
+//! and every function returns an `i32`; `Bool`s are 0 or 1. `SubtractInt32` and
+//! `MultiplyInt32` are rejected, as the interpreter widens their results to `I64`.
+//!
+//! WebAssembly only has structured control flow, so the blocks of the register form become
+//! the arms of a `br_table` in a loop: a jump sets the block to run next and branches back to
+//! the loop, and a block that doesn't end in a jump falls through to the next one. Each
+//! function is exported under its name.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use crate::vm::function::Function;
+use crate::vm::opcode::OpCode;
+use crate::vm::register::{translate, RegInstr, RegisterCode, TranslateError};
+use crate::vm::value::Value;
+use crate::vm::verifier::verify;
+
+const I32: u8 = 0x7F;
+const EMPTY_BLOCK: u8 = 0x40;
+
+#[derive(Debug)]
+pub enum WasmError {
+    /// The function has no register form.
+    Translate { function: String, error: TranslateError },
+    /// A constant that isn't an Int32 or a Bool.
+    NonInt32 { function: String, value: Value },
+    /// An instruction whose result the interpreter widens to `I64`.
+    Widening { function: String, opcode: OpCode },
+    Native(String),
+    DuplicateName(String),
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WasmError::Translate { function, error } => write!(f, "Cannot compile '{}': {}", function, error),
+            WasmError::NonInt32 { function, value } => write!(f, "Cannot compile '{}': {:?} is not an Int32", function, value),
+            WasmError::Widening { function, opcode } => {
+                write!(f, "Cannot compile '{}': {:?} widens its result to an Int64", function, opcode)
+            }
+            WasmError::Native(function) => write!(f, "Cannot compile native function '{}'", function),
+            WasmError::DuplicateName(function) => write!(f, "More than one function is called '{}'", function),
+        }
+    }
+}
+
+impl Error for WasmError {}
+
+/// A module exporting `function`.
+pub fn compile_function(function: &Rc<Function>) -> Result<Vec<u8>, WasmError> {
+    compile_module(std::slice::from_ref(function))
+}
+
+/// A module exporting each of `functions` under its name.
+pub fn compile_module(functions: &[Rc<Function>]) -> Result<Vec<u8>, WasmError> {
+    let mut names = HashSet::new();
+    let mut types = Vec::new();
+    let mut exports = Vec::new();
+    let mut bodies = Vec::new();
+    for (index, function) in functions.iter().enumerate() {
+        if function.bytecode.is_none() {
+            return Err(WasmError::Native(function.name.clone()));
+        }
+        if !names.insert(function.name.as_str()) {
+            return Err(WasmError::DuplicateName(function.name.clone()));
+        }
+        let translate_error = |error| WasmError::Translate { function: function.name.clone(), error };
+        let verified = verify(function).map_err(|error| translate_error(error.into()))?;
+        let registers = translate(&verified).map_err(translate_error)?;
+
+        let mut signature = vec![0x60];
+        vector(&mut signature, function.arity, |out| out.extend(std::iter::repeat_n(I32, function.arity)));
+        vector(&mut signature, 1, |out| out.push(I32));
+        types.push(signature);
+
+        let mut export = Vec::new();
+        name(&mut export, &function.name);
+        export.push(0x00);
+        unsigned(&mut export, index as u64);
+        exports.push(export);
+
+        let mut body = Vec::new();
+        FunctionBody { function, code: &registers, out: &mut body }.compile()?;
+        let mut sized = Vec::new();
+        unsigned(&mut sized, body.len() as u64);
+        sized.extend(body);
+        bodies.push(sized);
+    }
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    section(&mut module, 1, &types);
+    let indices: Vec<Vec<u8>> = (0..functions.len()).map(|index| {
+        let mut out = Vec::new();
+        unsigned(&mut out, index as u64);
+        out
+    }).collect();
+    section(&mut module, 3, &indices);
+    section(&mut module, 7, &exports);
+    section(&mut module, 10, &bodies);
+    Ok(module)
+}
+
+struct FunctionBody<'a> {
+    function: &'a Function,
+    code: &'a RegisterCode,
+    out: &'a mut Vec<u8>,
+}
+
+impl FunctionBody<'_> {
+    fn compile(mut self) -> Result<(), WasmError> {
+        // Registers the parameters don't cover, then the block to run next.
+        let locals = self.code.registers.max(self.function.arity);
+        let state = locals as u64;
+        vector(self.out, 1, |out| {
+            unsigned(out, (locals - self.function.arity) as u64 + 1);
+            out.push(I32);
+        });
+
+        // Block 0 is the code before the first jump target, block i + 1 starts at
+        // `blocks[i]`. The innermost wasm block is exited to run block 0.
+        let segments = self.code.blocks.len() + 1;
+        self.out.extend([0x03, EMPTY_BLOCK]);
+        for _ in 0..segments {
+            self.out.extend([0x02, EMPTY_BLOCK]);
+        }
+        self.local(0x20, state);
+        self.out.push(0x0E);
+        vector(self.out, segments - 1, |out| (0..segments as u64 - 1).for_each(|label| unsigned(out, label)));
+        unsigned(self.out, segments as u64 - 1);
+
+        let starts: Vec<usize> = std::iter::once(0).chain(self.code.blocks.iter().map(|block| block.pc)).collect();
+        for (segment, start) in starts.iter().enumerate() {
+            self.out.push(0x0B);
+            let end = starts.get(segment + 1).copied().unwrap_or(self.code.instructions.len());
+            // Wasm blocks left around this segment's code before the loop.
+            let depth = (segments - 1 - segment) as u64;
+            for instruction in &self.code.instructions[*start..end] {
+                self.instruction(instruction, depth, state)?;
+            }
+        }
+        self.out.extend([0x0B, 0x00, 0x0B]);
+        Ok(())
+    }
+
+    fn instruction(&mut self, instruction: &RegInstr, depth: u64, state: u64) -> Result<(), WasmError> {
+        match instruction {
+            RegInstr::LoadConstant { dst, index } => {
+                let value = self.function.constants[*index].clone();
+                self.constant(&value)?;
+                self.local(0x21, *dst as u64);
+            }
+            RegInstr::LoadValue { dst, value } => {
+                self.constant(value)?;
+                self.local(0x21, *dst as u64);
+            }
+            RegInstr::Move { dst, src } => {
+                self.local(0x20, *src as u64);
+                self.local(0x21, *dst as u64);
+            }
+            RegInstr::Binary { op: op @ (OpCode::SubtractInt32 | OpCode::MultiplyInt32), .. } => {
+                return Err(WasmError::Widening { function: self.function.name.clone(), opcode: *op });
+            }
+            RegInstr::Binary { op, dst, a, b } => {
+                self.local(0x20, *a as u64);
+                self.local(0x20, *b as u64);
+                self.out.push(binary_opcode(*op));
+                self.local(0x21, *dst as u64);
+            }
+            RegInstr::AddConstant { dst, src, constant } => {
+                self.local(0x20, *src as u64);
+                self.out.push(0x41);
+                signed(self.out, *constant as i64);
+                self.out.push(0x6A);
+                self.local(0x21, *dst as u64);
+            }
+            RegInstr::Jump { target } => self.jump(*target, depth, state),
+            RegInstr::JumpIfFalse { condition, target } => {
+                self.local(0x20, *condition as u64);
+                self.out.extend([0x45, 0x04, EMPTY_BLOCK]);
+                self.jump(*target, depth + 1, state);
+                self.out.push(0x0B);
+            }
+            RegInstr::JumpIfNotLess { a, b, target } => {
+                self.local(0x20, *a as u64);
+                self.local(0x20, *b as u64);
+                self.out.extend([0x48, 0x45, 0x04, EMPTY_BLOCK]);
+                self.jump(*target, depth + 1, state);
+                self.out.push(0x0B);
+            }
+            RegInstr::Return { src } => {
+                self.local(0x20, *src as u64);
+                self.out.push(0x0F);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs block `target + 1` next, see `compile`.
+    fn jump(&mut self, target: usize, depth: u64, state: u64) {
+        self.out.push(0x41);
+        signed(self.out, target as i64 + 1);
+        self.local(0x21, state);
+        self.out.push(0x0C);
+        unsigned(self.out, depth);
+    }
+
+    fn constant(&mut self, value: &Value) -> Result<(), WasmError> {
+        let constant = match value {
+            Value::I8(i) => *i as i64,
+            Value::I16(i) => *i as i64,
+            Value::I32(i) => *i as i64,
+            Value::Bool(b) => *b as i64,
+            _ => return Err(WasmError::NonInt32 { function: self.function.name.clone(), value: value.clone() }),
+        };
+        self.out.push(0x41);
+        signed(self.out, constant);
+        Ok(())
+    }
+
+    fn local(&mut self, opcode: u8, index: u64) {
+        self.out.push(opcode);
+        unsigned(self.out, index);
+    }
+}
+
+fn binary_opcode(op: OpCode) -> u8 {
+    match op {
+        OpCode::AddInt32 => 0x6A,
+        OpCode::EqualInt32 => 0x46,
+        OpCode::NotEqualInt32 => 0x47,
+        OpCode::LessThanInt32 => 0x48,
+        OpCode::GreaterThanInt32 => 0x4A,
+        OpCode::LessOrEqualInt32 => 0x4C,
+        OpCode::GreaterOrEqualInt32 => 0x4E,
+        _ => unreachable!("{:?} has no register form", op),
+    }
+}
+
+fn section(module: &mut Vec<u8>, id: u8, entries: &[Vec<u8>]) {
+    let mut contents = Vec::new();
+    vector(&mut contents, entries.len(), |out| entries.iter().for_each(|entry| out.extend(entry)));
+    module.push(id);
+    unsigned(module, contents.len() as u64);
+    module.extend(contents);
+}
+
+/// A count of `len` followed by what `items` writes.
+fn vector(out: &mut Vec<u8>, len: usize, items: impl FnOnce(&mut Vec<u8>)) {
+    unsigned(out, len as u64);
+    items(out);
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    unsigned(out, name.len() as u64);
+    out.extend(name.as_bytes());
+}
+
+/// LEB128.
+fn unsigned(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Signed LEB128.
+fn signed(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
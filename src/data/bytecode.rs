@@ -1,11 +1,137 @@
 use std::fs::File;
 use std::io::{Read, Write};
+use std::rc::Rc;
 use bincode::serde::{encode_to_vec, decode_from_slice};
 use bincode::config::standard;
+use crate::debug::lines::LineTable;
 use crate::vm::function::Function;
+use crate::vm::value::Value;
+use crate::vm::vm::VMError;
+
+/// Leading bytes of every versioned bytecode file. 0xFF can never start a headerless
+/// (format 0) file, because it is not a valid first byte of a bincode varint.
+pub const BYTECODE_MAGIC: [u8; 4] = [0xFF, b'I', b'R', b'S'];
+pub const BYTECODE_VERSION: u16 = 1;
+/// Set when line tables for the function and every function nested in its constants follow
+/// the payload.
+pub const FLAG_LINE_TABLES: u16 = 1;
+/// Flag bits this crate understands. Files with any other bit set were written by a newer version.
+pub const KNOWN_FLAGS: u16 = FLAG_LINE_TABLES;
+const HEADER_LEN: usize = BYTECODE_MAGIC.len() + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytecodeHeader {
+    pub version: u16,
+    pub flags: u16,
+}
+
+/// Reads the header at the start of `encoded` and returns it along with the payload.
+/// Headerless files from before versioning are reported as version 0.
+pub fn read_header(encoded: &[u8]) -> Result<(BytecodeHeader, &[u8]), VMError> {
+    if !encoded.starts_with(&BYTECODE_MAGIC) {
+        return Ok((BytecodeHeader { version: 0, flags: 0 }, encoded));
+    }
+    if encoded.len() < HEADER_LEN {
+        return Err(VMError::BytecodeVersionError {
+            expected: format!("a {} byte header", HEADER_LEN),
+            found: format!("{} bytes", encoded.len()),
+        });
+    }
+
+    let version = u16::from_be_bytes([encoded[4], encoded[5]]);
+    let flags = u16::from_be_bytes([encoded[6], encoded[7]]);
+    if version > BYTECODE_VERSION {
+        return Err(VMError::BytecodeVersionError {
+            expected: format!("format version {} or older", BYTECODE_VERSION),
+            found: format!("format version {}", version),
+        });
+    }
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(VMError::BytecodeVersionError {
+            expected: format!("flags within {:#06x}", KNOWN_FLAGS),
+            found: format!("flags {:#06x}", flags),
+        });
+    }
+    Ok((BytecodeHeader { version, flags }, &encoded[HEADER_LEN..]))
+}
+
+fn write_header(out: &mut Vec<u8>, flags: u16) {
+    out.extend_from_slice(&BYTECODE_MAGIC);
+    out.extend_from_slice(&BYTECODE_VERSION.to_be_bytes());
+    out.extend_from_slice(&flags.to_be_bytes());
+}
+
+pub fn encode_function(function: &Function) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut tables = Vec::new();
+    collect_line_tables(function, &mut tables);
+    let has_lines = tables.iter().any(|table| !table.is_empty());
+
+    let mut encoded = Vec::new();
+    write_header(&mut encoded, if has_lines { FLAG_LINE_TABLES } else { 0 });
+    encoded.extend(encode_to_vec(function, standard())?);
+    if has_lines {
+        encoded.extend(encode_to_vec(&tables, standard())?);
+    }
+    Ok(encoded)
+}
+
+pub fn decode_function(encoded: &[u8]) -> Result<Function, Box<dyn std::error::Error>> {
+    // Format 0 and 1 share the same payload layout, so older files only need their header skipped.
+    let (header, payload) = read_header(encoded)?;
+    let (mut decoded, read): (Function, usize) = decode_from_slice(payload, standard())?;
+    if header.flags & FLAG_LINE_TABLES != 0 {
+        let (tables, _): (Vec<LineTable>, usize) = decode_from_slice(&payload[read..], standard())?;
+        attach_line_tables(&mut decoded, &mut tables.into_iter());
+    }
+    Ok(decoded)
+}
+
+/// Line tables are kept out of the `Function` payload so that files without them keep the
+/// format 1 layout. They are stored in the order this walk visits functions.
+pub(crate) fn collect_line_tables(function: &Function, tables: &mut Vec<LineTable>) {
+    tables.push(function.lines.clone());
+    for constant in &function.constants {
+        match constant {
+            Value::Function(nested) => collect_line_tables(nested, tables),
+            Value::Class(class) => class.methods.iter().for_each(|method| collect_line_tables(method, tables)),
+            _ => {}
+        }
+    }
+}
+
+/// Inverse of `collect_line_tables`. Functions that are already shared can't be updated
+/// and keep an empty table.
+pub(crate) fn attach_line_tables(function: &mut Function, tables: &mut impl Iterator<Item = LineTable>) {
+    function.lines = tables.next().unwrap_or_default();
+    for constant in &mut function.constants {
+        let nested: Vec<&mut Rc<Function>> = match constant {
+            Value::Function(nested) => vec![nested],
+            Value::Class(class) => match Rc::get_mut(class) {
+                Some(class) => class.methods.iter_mut().collect(),
+                None => {
+                    class.methods.iter().for_each(|method| skip_line_tables(method, tables));
+                    continue;
+                }
+            },
+            _ => continue,
+        };
+        for nested in nested {
+            match Rc::get_mut(nested) {
+                Some(nested) => attach_line_tables(nested, tables),
+                None => skip_line_tables(nested, tables),
+            }
+        }
+    }
+}
+
+fn skip_line_tables(function: &Function, tables: &mut impl Iterator<Item = LineTable>) {
+    let mut skipped = Vec::new();
+    collect_line_tables(function, &mut skipped);
+    tables.take(skipped.len()).for_each(drop);
+}
 
 pub fn save_function(function: &Function, path: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let encoded: Vec<u8> = encode_to_vec(function, standard())?;
+    let encoded: Vec<u8> = encode_function(function)?;
     let mut file = File::create(path)?;
     file.write_all(&encoded)?;
     Ok(())
@@ -15,6 +141,5 @@ pub fn load_function(path: &str) -> Result<Function, Box<dyn std::error::Error>>
     let mut file = File::open(path)?;
     let mut encoded = Vec::new();
     file.read_to_end(&mut encoded)?;
-    let (decoded, _): (Function, usize) = decode_from_slice(&encoded, standard())?;
-    Ok(decoded)
+    decode_function(&encoded)
 }
@@ -0,0 +1,596 @@
+//! Converts between Rust values and VM values through serde, so embedders can hand their own
+//! types to bytecode and read results back without writing converters by hand.
+//!
+//! `to_value` maps anything `Serialize`, and `from_value` reads into anything `Deserialize`:
+//!
+//! - Integers, floats, bools, chars and strings map to the matching variants, `None` and `()`
+//!   to `Null`, and byte buffers to `Bytes`.
+//! - Sequences and tuples become `Array`s. `Tuple`s, `Set`s and typed arrays read as sequences.
+//! - Structs and maps become `Map`s, with keys turned into strings. Instances read as maps of
+//!   their properties.
+//! - A unit enum variant is its name as a `Str`, any other a one-entry `Map` from its name to
+//!   its contents.
+//! - A `BigInt` reads as its decimal string.
+//!
+//! Functions, classes, closures and the other runtime values carry no data, so converting
+//! them fails. This is separate from `Value`'s own serde derive, which is the bytecode file
+//! format for constants: a `Value` field in a converted struct goes through the derive.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::value::Value;
+
+/// How deep `from_value` follows nested arrays and maps, so a cycle fails instead of
+/// overflowing the stack.
+pub const MAX_DEPTH: usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConvertError(pub String);
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for ConvertError {}
+
+impl ser::Error for ConvertError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ConvertError(message.to_string())
+    }
+}
+
+impl de::Error for ConvertError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        ConvertError(message.to_string())
+    }
+}
+
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, ConvertError> {
+    value.serialize(ValueSerializer)
+}
+
+pub fn from_value<T: DeserializeOwned>(value: &Value) -> Result<T, ConvertError> {
+    T::deserialize(Reader { value: value.clone(), depth: 0 })
+}
+
+fn array(items: Vec<Value>) -> Value {
+    Value::Array(Gc::new(items))
+}
+
+fn map(entries: HashMap<String, Value>) -> Value {
+    Value::Map(Gc::new(entries))
+}
+
+/// A one-entry map from an enum variant's name to its contents.
+fn variant(name: &str, contents: Value) -> Value {
+    map(HashMap::from([(name.to_string(), contents)]))
+}
+
+fn key(value: Value) -> Result<String, ConvertError> {
+    Ok(match value {
+        Value::Str(s) => s.to_string(),
+        Value::Char(c) => c.to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::I8(i) => i.to_string(),
+        Value::I16(i) => i.to_string(),
+        Value::I32(i) => i.to_string(),
+        Value::I64(i) => i.to_string(),
+        Value::I128(i) => i.to_string(),
+        Value::U8(i) => i.to_string(),
+        Value::U16(i) => i.to_string(),
+        Value::U32(i) => i.to_string(),
+        Value::U64(i) => i.to_string(),
+        Value::U128(i) => i.to_string(),
+        other => return Err(ConvertError(format!("Map keys must be strings or integers, not {:?}", other))),
+    })
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ConvertError;
+    type SerializeSeq = SeqBuilder;
+    type SerializeTuple = SeqBuilder;
+    type SerializeTupleStruct = SeqBuilder;
+    type SerializeTupleVariant = SeqBuilder;
+    type SerializeMap = MapBuilder;
+    type SerializeStruct = MapBuilder;
+    type SerializeStructVariant = MapBuilder;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ConvertError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ConvertError> {
+        Ok(Value::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ConvertError> {
+        Ok(Value::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ConvertError> {
+        Ok(Value::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ConvertError> {
+        Ok(Value::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, ConvertError> {
+        Ok(Value::I128(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ConvertError> {
+        Ok(Value::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ConvertError> {
+        Ok(Value::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ConvertError> {
+        Ok(Value::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ConvertError> {
+        Ok(Value::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, ConvertError> {
+        Ok(Value::U128(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ConvertError> {
+        Ok(Value::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ConvertError> {
+        Ok(Value::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ConvertError> {
+        Ok(Value::Char(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ConvertError> {
+        Ok(Value::Str(intern(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ConvertError> {
+        Ok(Value::Bytes(Gc::new(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Value, ConvertError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, ConvertError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ConvertError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Value, ConvertError> {
+        Ok(Value::Str(intern(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<Value, ConvertError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, ConvertError> {
+        Ok(variant(name, to_value(value)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqBuilder, ConvertError> {
+        Ok(SeqBuilder { variant: None, items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqBuilder, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqBuilder, ConvertError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SeqBuilder, ConvertError> {
+        Ok(SeqBuilder { variant: Some(name), items: Vec::with_capacity(len) })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapBuilder, ConvertError> {
+        Ok(MapBuilder { variant: None, entries: HashMap::new(), key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<MapBuilder, ConvertError> {
+        self.serialize_map(None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<MapBuilder, ConvertError> {
+        Ok(MapBuilder { variant: Some(name), entries: HashMap::new(), key: None })
+    }
+}
+
+/// Collects an array, wrapped in a one-entry map for a tuple variant.
+struct SeqBuilder {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl SeqBuilder {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.items.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConvertError> {
+        let items = array(self.items);
+        Ok(match self.variant {
+            Some(name) => variant(name, items),
+            None => items,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SeqBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+/// Collects a map, wrapped in a one-entry map for a struct variant.
+struct MapBuilder {
+    variant: Option<&'static str>,
+    entries: HashMap<String, Value>,
+    key: Option<String>,
+}
+
+impl MapBuilder {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), ConvertError> {
+        self.entries.insert(key.to_string(), to_value(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Value, ConvertError> {
+        let entries = map(self.entries);
+        Ok(match self.variant {
+            Some(name) => variant(name, entries),
+            None => entries,
+        })
+    }
+}
+
+impl ser::SerializeMap for MapBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ConvertError> {
+        self.key = Some(self::key(to_value(key)?)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ConvertError> {
+        let key = self.key.take().ok_or_else(|| ConvertError("Map value without a key".to_string()))?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for MapBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ConvertError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for MapBuilder {
+    type Ok = Value;
+    type Error = ConvertError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ConvertError> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, ConvertError> {
+        self.finish()
+    }
+}
+
+/// Reads a value `depth` arrays or maps down from where `from_value` started.
+struct Reader {
+    value: Value,
+    depth: usize,
+}
+
+impl Reader {
+    fn nested(&self, value: Value) -> Result<Reader, ConvertError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ConvertError(format!("Value nested more than {} deep", MAX_DEPTH)));
+        }
+        Ok(Reader { value, depth: self.depth + 1 })
+    }
+
+    fn sequence<'de, V: Visitor<'de>>(self, items: Vec<Value>, visitor: V) -> Result<V::Value, ConvertError> {
+        visitor.visit_seq(SeqReader { items: items.into_iter(), parent: self })
+    }
+
+    fn entries<'de, V: Visitor<'de>>(self, entries: Vec<(String, Value)>, visitor: V) -> Result<V::Value, ConvertError> {
+        visitor.visit_map(MapReader { entries: entries.into_iter(), value: None, parent: self })
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Reader {
+    type Error = ConvertError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self.value.clone() {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::I8(i) => visitor.visit_i8(i),
+            Value::I16(i) => visitor.visit_i16(i),
+            Value::I32(i) => visitor.visit_i32(i),
+            Value::I64(i) => visitor.visit_i64(i),
+            Value::I128(i) => visitor.visit_i128(i),
+            Value::U8(i) => visitor.visit_u8(i),
+            Value::U16(i) => visitor.visit_u16(i),
+            Value::U32(i) => visitor.visit_u32(i),
+            Value::U64(i) => visitor.visit_u64(i),
+            Value::U128(i) => visitor.visit_u128(i),
+            Value::F32(f) => visitor.visit_f32(f),
+            Value::F64(f) => visitor.visit_f64(f),
+            Value::Char(c) => visitor.visit_char(c),
+            Value::Str(s) => visitor.visit_str(&s),
+            Value::BigInt(n) => visitor.visit_string(n.to_string()),
+            Value::Bytes(bytes) | Value::ByteArray(bytes) => visitor.visit_byte_buf(bytes.borrow().clone()),
+            Value::Array(items) => {
+                let items = items.borrow().clone();
+                self.sequence(items, visitor)
+            }
+            Value::Tuple(items) => self.sequence(items.to_vec(), visitor),
+            Value::Set(set) => {
+                let items = set.borrow().iter().cloned().collect();
+                self.sequence(items, visitor)
+            }
+            Value::Int32Array(items) => {
+                let items = items.borrow().iter().map(|i| Value::I32(*i)).collect();
+                self.sequence(items, visitor)
+            }
+            Value::Float64Array(items) => {
+                let items = items.borrow().iter().map(|f| Value::F64(*f)).collect();
+                self.sequence(items, visitor)
+            }
+            Value::Map(entries) => {
+                let entries = entries.borrow().iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+                self.entries(entries, visitor)
+            }
+            Value::Object(instance) => {
+                let instance = instance.borrow();
+                let entries = instance.shape.names().into_iter().zip(&instance.fields)
+                    .filter_map(|(name, value)| Some((name?.to_string(), value.clone())))
+                    .collect();
+                drop(instance);
+                self.entries(entries, visitor)
+            }
+            other => Err(ConvertError(format!("{} has no data to convert", runtime_kind(&other)))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, ConvertError> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, ConvertError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, ConvertError> {
+        match &self.value {
+            Value::Str(name) => visitor.visit_enum(name.to_string().into_deserializer()),
+            Value::Map(entries) if entries.borrow().len() == 1 => {
+                let (name, contents) = entries.borrow().iter().map(|(key, value)| (key.clone(), value.clone())).next().unwrap();
+                visitor.visit_enum(EnumReader { name, contents: self.nested(contents)? })
+            }
+            other => Err(ConvertError(format!("Expected a variant name or a one-entry map, found {:?}", other))),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// What a value that only exists at runtime is, for errors.
+fn runtime_kind(value: &Value) -> &'static str {
+    match value {
+        Value::Function(_) | Value::NativeFunction(_) | Value::Closure(_) | Value::BoundMethod(_) => "A function",
+        Value::Class(_) => "A class",
+        Value::Range(_) => "A range",
+        Value::WeakRef(_) => "A weak reference",
+        Value::Coroutine(_) => "A coroutine",
+        Value::Future(_) => "A future",
+        Value::Fiber(_) => "A fiber",
+        Value::Channel(_) => "A channel",
+        _ => "The value",
+    }
+}
+
+struct SeqReader {
+    items: std::vec::IntoIter<Value>,
+    parent: Reader,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqReader {
+    type Error = ConvertError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, ConvertError> {
+        match self.items.next() {
+            Some(item) => seed.deserialize(self.parent.nested(item)?).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapReader {
+    entries: std::vec::IntoIter<(String, Value)>,
+    value: Option<Value>,
+    parent: Reader,
+}
+
+impl<'de> de::MapAccess<'de> for MapReader {
+    type Error = ConvertError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, ConvertError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, ConvertError> {
+        let value = self.value.take().ok_or_else(|| ConvertError("Map value without a key".to_string()))?;
+        seed.deserialize(self.parent.nested(value)?)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumReader {
+    name: String,
+    contents: Reader,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumReader {
+    type Error = ConvertError;
+    type Variant = Reader;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Reader), ConvertError> {
+        let name = seed.deserialize(self.name.into_deserializer())?;
+        Ok((name, self.contents))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Reader {
+    type Error = ConvertError;
+
+    fn unit_variant(self) -> Result<(), ConvertError> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, ConvertError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, ConvertError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, ConvertError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
@@ -1,2 +1,10 @@
 pub mod bytecode;
-pub mod archive;
\ No newline at end of file
+pub mod archive;
+pub mod module;
+pub mod json;
+pub mod shared;
+pub mod convert;
+pub mod valuecodec;
+pub mod package;
+#[cfg(feature = "regex")]
+pub mod regex;
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::rc::Rc;
+use bincode::serde::{encode_to_vec, decode_from_slice};
+use bincode::config::standard;
+use serde::{Serialize, Deserialize};
+use crate::data::bytecode::{attach_line_tables, collect_line_tables};
+use crate::debug::lines::LineTable;
+use crate::vm::function::Function;
+use crate::vm::object::Class;
+use crate::vm::opcode::{instruction_len, is_custom_opcode, OpCode};
+use crate::vm::value::Value;
+
+/// A set of named functions saved together in one file, with an optional entry point.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Module {
+    pub name: String,
+    pub functions: Vec<Rc<Function>>,
+    pub entry_point: Option<String>,
+}
+
+impl Module {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            functions: Vec::new(),
+            entry_point: None,
+        }
+    }
+
+    /// Adds a function and returns its index, which is also its global slot once loaded.
+    pub fn add_function(&mut self, function: Function) -> usize {
+        self.functions.push(Rc::new(function));
+        self.functions.len() - 1
+    }
+
+    pub fn set_entry_point(&mut self, name: &str) {
+        self.entry_point = Some(name.to_string());
+    }
+
+    pub fn function(&self, name: &str) -> Option<Rc<Function>> {
+        self.functions.iter().find(|function| function.name == name).cloned()
+    }
+
+    pub fn entry(&self) -> Option<Rc<Function>> {
+        self.function(self.entry_point.as_ref()?)
+    }
+
+    /// The module as loaded at global slot `base`. Global operands below its function count
+    /// name its own functions, so they are moved up by `base`, in its functions and in the
+    /// functions and class methods among their constants; other slots are left alone.
+    /// Functions that don't change are shared. Fails if a moved slot doesn't fit its operand.
+    pub fn relocated(&self, base: usize) -> Result<Module, Box<dyn Error>> {
+        if base == 0 {
+            return Ok(self.clone());
+        }
+        let mut relocator = Relocator { base, count: self.functions.len(), functions: HashMap::new(), classes: HashMap::new() };
+        let functions = self.functions.iter().map(|function| relocator.function(function)).collect::<Result<_, _>>()?;
+        Ok(Module { name: self.name.clone(), functions, entry_point: self.entry_point.clone() })
+    }
+}
+
+struct Relocator {
+    base: usize,
+    count: usize,
+    functions: HashMap<*const Function, Rc<Function>>,
+    classes: HashMap<*const Class, Rc<Class>>,
+}
+
+impl Relocator {
+    fn function(&mut self, function: &Rc<Function>) -> Result<Rc<Function>, Box<dyn Error>> {
+        if let Some(relocated) = self.functions.get(&Rc::as_ptr(function)) {
+            return Ok(relocated.clone());
+        }
+        let Some(code) = &function.bytecode else { return Ok(function.clone()) };
+        let mut code = code.clone();
+        let mut offset = 0;
+        while offset < code.len() {
+            let len = instruction_len(&code, offset).ok_or_else(|| format!("Truncated instruction at {} in '{}'", offset, function.name))?;
+            let global = !is_custom_opcode(code[offset]) && matches!(
+                OpCode::decode(&code, offset),
+                Some(OpCode::GetGlobalVariable8 | OpCode::DefineGlobalVariable8 | OpCode::SetGlobalVariable8)
+            );
+            if global && (code[offset + 1] as usize) < self.count {
+                let slot = code[offset + 1] as usize + self.base;
+                code[offset + 1] = u8::try_from(slot)
+                    .map_err(|_| format!("Global slot {} used at {} in '{}' does not fit in a byte", slot, offset, function.name))?;
+            }
+            offset += len;
+        }
+        let constants = function.constants.iter().map(|constant| self.constant(constant)).collect::<Result<Vec<_>, _>>()?;
+        let unchanged = Some(&code) == function.bytecode.as_ref()
+            && constants.iter().zip(&function.constants).all(|(new, old)| match (new, old) {
+                (Value::Function(new), Value::Function(old)) => Rc::ptr_eq(new, old),
+                (Value::Class(new), Value::Class(old)) => Rc::ptr_eq(new, old),
+                _ => true,
+            });
+        let relocated = if unchanged {
+            function.clone()
+        } else {
+            let mut relocated = Function::new_bytecode(function.name.clone(), function.arity, code, constants);
+            relocated.lines = function.lines.clone();
+            Rc::new(relocated)
+        };
+        self.functions.insert(Rc::as_ptr(function), relocated.clone());
+        Ok(relocated)
+    }
+
+    fn class(&mut self, class: &Rc<Class>) -> Result<Rc<Class>, Box<dyn Error>> {
+        if let Some(relocated) = self.classes.get(&Rc::as_ptr(class)) {
+            return Ok(relocated.clone());
+        }
+        let superclass = class.superclass.as_ref().map(|superclass| self.class(superclass)).transpose()?;
+        let methods = class.methods.iter().map(|method| self.function(method)).collect::<Result<Vec<_>, _>>()?;
+        let unchanged = superclass.as_ref().zip(class.superclass.as_ref()).is_none_or(|(new, old)| Rc::ptr_eq(new, old))
+            && methods.iter().zip(&class.methods).all(|(new, old)| Rc::ptr_eq(new, old));
+        let relocated = if unchanged {
+            class.clone()
+        } else {
+            let mut relocated = Class::new(class.name.clone(), class.type_id, superclass);
+            relocated.methods = methods;
+            relocated.properties = class.properties.clone();
+            relocated.extensible = class.extensible;
+            Rc::new(relocated)
+        };
+        self.classes.insert(Rc::as_ptr(class), relocated.clone());
+        Ok(relocated)
+    }
+
+    fn constant(&mut self, constant: &Value) -> Result<Value, Box<dyn Error>> {
+        Ok(match constant {
+            Value::Function(function) => Value::Function(self.function(function)?),
+            Value::Class(class) => Value::Class(self.class(class)?),
+            other => other.clone(),
+        })
+    }
+}
+
+/// Line tables, if any function has one, follow the module payload.
+pub fn encode_module(module: &Module) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut tables = Vec::new();
+    module.functions.iter().for_each(|function| collect_line_tables(function, &mut tables));
+    let mut encoded = encode_to_vec(module, standard())?;
+    if tables.iter().any(|table| !table.is_empty()) {
+        encoded.extend(encode_to_vec(&tables, standard())?);
+    }
+    Ok(encoded)
+}
+
+pub fn decode_module(encoded: &[u8]) -> Result<Module, Box<dyn Error>> {
+    let (mut decoded, read): (Module, usize) = decode_from_slice(encoded, standard())?;
+    if read < encoded.len() {
+        let (tables, _): (Vec<LineTable>, usize) = decode_from_slice(&encoded[read..], standard())?;
+        let mut tables = tables.into_iter();
+        for function in decoded.functions.iter_mut().filter_map(Rc::get_mut) {
+            attach_line_tables(function, &mut tables);
+        }
+    }
+    if let Some(entry) = &decoded.entry_point {
+        if decoded.function(entry).is_none() {
+            return Err(format!("Entry point '{}' is not defined in module '{}'", entry, decoded.name).into());
+        }
+    }
+    Ok(decoded)
+}
+
+pub fn save_module(module: &Module, path: &str) -> Result<(), Box<dyn Error>> {
+    let encoded = encode_module(module)?;
+    let mut file = File::create(path)?;
+    file.write_all(&encoded)?;
+    Ok(())
+}
+
+pub fn load_module(path: &str) -> Result<Module, Box<dyn Error>> {
+    let mut file = File::open(path)?;
+    let mut encoded = Vec::new();
+    file.read_to_end(&mut encoded)?;
+    decode_module(&encoded)
+}
@@ -0,0 +1,207 @@
+//! Packages (`.icpkg`): a program's modules, metadata and resource files in one zip archive.
+//!
+//! The archive holds `manifest.json`, each module at `modules/<name>.icm` and each resource
+//! at `resources/<name>`. The manifest names the package, its version, the VM features it
+//! requires (see `supported_features`), its modules and resources, and optionally the module
+//! whose entry point runs the program:
+//!
+//! ```text
+//! {"format": 1, "name": "wordcount", "version": "1.0.0", "entry": "main",
+//!  "requires": ["regex"], "modules": ["main", "text.split"], "resources": ["stopwords.txt"]}
+//! ```
+//!
+//! `pack_manifest` builds a package from a manifest of the same shape whose `modules` and
+//! `resources` are file paths instead, which is what `iris pack` takes.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+use zip::read::ZipArchive;
+use zip::write::{FileOptions, ZipWriter};
+use crate::data::json::Json;
+use crate::data::module::{decode_module, encode_module, load_module, Module};
+use crate::vm::import::MemoryResolver;
+
+pub const PACKAGE_FORMAT: i64 = 1;
+const MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Clone, Default)]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// Module whose entry point runs the package, if it is a program.
+    pub entry: Option<String>,
+    /// VM features the package needs, see `supported_features`.
+    pub requires: Vec<String>,
+    pub modules: Vec<Module>,
+    pub resources: BTreeMap<String, Vec<u8>>,
+}
+
+impl Package {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self { name: name.to_string(), version: version.to_string(), ..Self::default() }
+    }
+
+    pub fn add_module(&mut self, module: Module) {
+        self.modules.push(module);
+    }
+
+    pub fn add_resource(&mut self, name: &str, contents: Vec<u8>) {
+        self.resources.insert(name.to_string(), contents);
+    }
+
+    pub fn set_entry(&mut self, module: &str) {
+        self.entry = Some(module.to_string());
+    }
+
+    pub fn require(&mut self, feature: &str) {
+        self.requires.push(feature.to_string());
+    }
+
+    pub fn module(&self, name: &str) -> Option<&Module> {
+        self.modules.iter().find(|module| module.name == name)
+    }
+
+    pub fn entry_module(&self) -> Option<&Module> {
+        self.module(self.entry.as_ref()?)
+    }
+
+    pub fn resource(&self, name: &str) -> Option<&[u8]> {
+        self.resources.get(name).map(Vec::as_slice)
+    }
+
+    /// Required features this build of the VM lacks.
+    pub fn missing_features(&self) -> Vec<&str> {
+        let supported = supported_features();
+        self.requires.iter().map(String::as_str).filter(|feature| !supported.contains(feature)).collect()
+    }
+
+    /// Resolves imports of the package's modules, see `vm::import`.
+    pub fn resolver(&self) -> MemoryResolver {
+        self.modules.iter().cloned().fold(MemoryResolver::new(), MemoryResolver::with)
+    }
+
+    fn manifest(&self) -> Json {
+        let strings = |names: &mut dyn Iterator<Item = &String>| Json::Array(names.map(|name| Json::String(name.clone())).collect());
+        Json::object([
+            ("format", Json::Number(PACKAGE_FORMAT as f64)),
+            ("name", Json::String(self.name.clone())),
+            ("version", Json::String(self.version.clone())),
+            ("entry", self.entry.clone().map_or(Json::Null, Json::String)),
+            ("requires", strings(&mut self.requires.iter())),
+            ("modules", strings(&mut self.modules.iter().map(|module| &module.name))),
+            ("resources", strings(&mut self.resources.keys())),
+        ])
+    }
+}
+
+/// Features `Package::requires` may name that this build of the VM has.
+pub fn supported_features() -> Vec<&'static str> {
+    let mut features = vec!["modules"];
+    if cfg!(feature = "regex") {
+        features.push("regex");
+    }
+    if cfg!(feature = "nan-boxing") {
+        features.push("nan-boxing");
+    }
+    if crate::stdlib::ffi::SUPPORTED {
+        features.push("ffi");
+    }
+    features
+}
+
+pub fn encode_package(package: &Package) -> Result<Vec<u8>, Box<dyn Error>> {
+    if let Some(entry) = &package.entry {
+        if package.module(entry).is_none() {
+            return Err(format!("Entry module '{}' is not in package '{}'", entry, package.name).into());
+        }
+    }
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file(MANIFEST, options)?;
+    zip.write_all(package.manifest().pretty(2).as_bytes())?;
+    for module in &package.modules {
+        zip.start_file(format!("modules/{}.icm", module.name), options)?;
+        zip.write_all(&encode_module(module)?)?;
+    }
+    for (name, contents) in &package.resources {
+        zip.start_file(format!("resources/{}", name), options)?;
+        zip.write_all(contents)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+pub fn decode_package(encoded: &[u8]) -> Result<Package, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(Cursor::new(encoded))?;
+    let mut read = |name: &str| -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut file = archive.by_name(name).map_err(|_| format!("Package has no '{}'", name))?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        Ok(contents)
+    };
+    let manifest = Json::parse(std::str::from_utf8(&read(MANIFEST)?)?)?;
+    if manifest.get("format").and_then(Json::as_i64) != Some(PACKAGE_FORMAT) {
+        return Err(format!("Unsupported package format, expected {}", PACKAGE_FORMAT).into());
+    }
+    let mut package = Package::new(string(&manifest, "name")?, string(&manifest, "version")?);
+    package.entry = manifest.get("entry").and_then(Json::as_str).map(str::to_string);
+    package.requires = strings(&manifest, "requires")?.into_iter().map(str::to_string).collect();
+    for name in strings(&manifest, "modules")? {
+        let module = decode_module(&read(&format!("modules/{}.icm", name))?)?;
+        if module.name != name {
+            return Err(format!("Module file for '{}' holds module '{}'", name, module.name).into());
+        }
+        package.add_module(module);
+    }
+    for name in strings(&manifest, "resources")? {
+        let contents = read(&format!("resources/{}", name))?;
+        package.add_resource(name, contents);
+    }
+    if let Some(entry) = &package.entry {
+        if package.module(entry).is_none() {
+            return Err(format!("Entry module '{}' is not in package '{}'", entry, package.name).into());
+        }
+    }
+    Ok(package)
+}
+
+pub fn save_package(package: &Package, path: &str) -> Result<(), Box<dyn Error>> {
+    std::fs::write(path, encode_package(package)?)?;
+    Ok(())
+}
+
+pub fn load_package(path: &str) -> Result<Package, Box<dyn Error>> {
+    decode_package(&std::fs::read(path)?)
+}
+
+/// Builds a package from the manifest at `path`, whose `modules` and `resources` are paths
+/// relative to it. Resources keep their path as their name.
+pub fn pack_manifest(path: &str) -> Result<Package, Box<dyn Error>> {
+    let manifest = Json::parse(&std::fs::read_to_string(path)?)?;
+    let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+    let mut package = Package::new(string(&manifest, "name")?, string(&manifest, "version")?);
+    package.entry = manifest.get("entry").and_then(Json::as_str).map(str::to_string);
+    package.requires = strings(&manifest, "requires")?.into_iter().map(str::to_string).collect();
+    for file in strings(&manifest, "modules")? {
+        package.add_module(load_module(&directory.join(file).to_string_lossy())?);
+    }
+    for file in strings(&manifest, "resources")? {
+        if file.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+            return Err(format!("Resource path '{}' must be relative and stay below the manifest", file).into());
+        }
+        package.add_resource(file, std::fs::read(directory.join(file))?);
+    }
+    Ok(package)
+}
+
+fn string<'a>(manifest: &'a Json, key: &str) -> Result<&'a str, Box<dyn Error>> {
+    Ok(manifest.get(key).and_then(Json::as_str).ok_or_else(|| format!("Manifest needs a string '{}'", key))?)
+}
+
+/// The strings in array `key`, empty if it is missing.
+fn strings<'a>(manifest: &'a Json, key: &str) -> Result<Vec<&'a str>, Box<dyn Error>> {
+    let Some(values) = manifest.get(key) else { return Ok(Vec::new()) };
+    let values = values.as_array().ok_or_else(|| format!("Manifest '{}' must be an array", key))?;
+    Ok(values.iter().map(|value| value.as_str().ok_or_else(|| format!("Manifest '{}' must hold strings", key))).collect::<Result<_, _>>()?)
+}
@@ -0,0 +1,730 @@
+//! Regular expressions for the `regex` natives, enabled with the `regex` feature.
+//!
+//! The syntax is the common subset: literals, `.`, classes like `[a-z_]` and `[^0-9]`, the
+//! escapes `\d \w \s` (ASCII digits, alphanumerics and `_`, whitespace), their negations and
+//! `\b \B`, anchors `^` and `$`, groups `(...)`, `(?:...)` and `(?<name>...)`, alternation
+//! and the quantifiers `* + ? {n} {n,} {n,m}`, lazy with a trailing `?`. Matching is
+//! leftmost-first, like Perl, but runs a Pike VM over all alternatives at once, so time is
+//! linear in the text whatever the pattern; there are no backreferences. Positions are byte
+//! offsets into the text.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// Instructions a compiled pattern may take, after expanding counted repetition.
+pub const MAX_PROGRAM_LEN: usize = 100_000;
+/// Groups a pattern may nest inside one another.
+pub const MAX_NESTING: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    /// In characters into the pattern.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at character {}", self.message, self.offset)
+    }
+}
+
+impl Error for RegexError {}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    program: Vec<Inst>,
+    /// Name of each group, the whole match first.
+    names: Vec<Option<String>>,
+}
+
+/// Where each group matched, `None` for groups that took no part.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    groups: Vec<Option<Range<usize>>>,
+}
+
+impl Captures {
+    pub fn get(&self, group: usize) -> Option<Range<usize>> {
+        self.groups.get(group).cloned().flatten()
+    }
+
+    /// The whole match.
+    pub fn range(&self) -> Range<usize> {
+        self.get(0).unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        let mut parser = Parser { pattern: pattern.chars().collect(), pos: 0, depth: 0, names: vec![None] };
+        let node = parser.alternation()?;
+        if parser.pos < parser.pattern.len() {
+            return Err(parser.error("unmatched ')'"));
+        }
+        let mut compiler = Compiler { program: Vec::new() };
+        compiler.push(Inst::Save(0))?;
+        compiler.node(&node)?;
+        compiler.push(Inst::Save(1))?;
+        compiler.push(Inst::Match)?;
+        Ok(Regex { program: compiler.program, names: parser.names })
+    }
+
+    /// Groups including the whole match, which is group 0.
+    pub fn group_count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The name of each group, `None` for unnamed ones.
+    pub fn group_names(&self) -> &[Option<String>] {
+        &self.names
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.captures(text).is_some()
+    }
+
+    /// The leftmost match.
+    pub fn captures(&self, text: &str) -> Option<Captures> {
+        let text = Text::new(text);
+        self.search(&text, 0).map(|slots| text.captures(&slots))
+    }
+
+    /// Every match, left to right, not overlapping. After an empty match the next one starts
+    /// a character further on.
+    pub fn captures_all(&self, text: &str) -> Vec<Captures> {
+        let text = Text::new(text);
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while start <= text.chars.len() {
+            let Some(slots) = self.search(&text, start) else { break };
+            let (from, to) = (slots[0].unwrap_or(start), slots[1].unwrap_or(start));
+            start = if to == from { to + 1 } else { to };
+            matches.push(text.captures(&slots));
+        }
+        matches
+    }
+
+    /// `text` with every match replaced. In `replacement`, `$1` or `${1}` stands for a group,
+    /// `${name}` for a named one and `$$` for `$`; groups that didn't match give nothing.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        let mut replaced = String::new();
+        let mut last = 0;
+        for captures in self.captures_all(text) {
+            let range = captures.range();
+            replaced.push_str(&text[last..range.start]);
+            self.expand(text, &captures, replacement, &mut replaced);
+            last = range.end;
+        }
+        replaced.push_str(&text[last..]);
+        replaced
+    }
+
+    fn expand(&self, text: &str, captures: &Captures, replacement: &str, out: &mut String) {
+        let mut rest = replacement;
+        while let Some(dollar) = rest.find('$') {
+            out.push_str(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+            let (reference, after) = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => (&braced[..end], &braced[end + 1..]),
+                    None => ("", rest),
+                }
+            } else if rest.starts_with('$') {
+                out.push('$');
+                rest = &rest[1..];
+                continue;
+            } else {
+                let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            };
+            if reference.is_empty() {
+                out.push('$');
+                continue;
+            }
+            let group = reference.parse().ok().or_else(|| self.names.iter().position(|name| name.as_deref() == Some(reference)));
+            if let Some(range) = group.and_then(|group| captures.get(group)) {
+                out.push_str(&text[range]);
+            }
+            rest = after;
+        }
+        out.push_str(rest);
+    }
+
+    /// Runs the program over `text` from character `start`, returning the capture slots of
+    /// the leftmost-first match.
+    fn search(&self, text: &Text, start: usize) -> Option<Vec<Option<usize>>> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut scratch = vec![None; self.names.len() * 2];
+        let mut matched = None;
+        let mut pos = start;
+        loop {
+            if matched.is_none() {
+                scratch.fill(None);
+                self.add(&mut current, 0, text, pos, &mut scratch);
+            }
+            if current.threads.is_empty() && matched.is_some() {
+                break;
+            }
+            let ch = text.chars.get(pos).copied();
+            for (pc, slots) in current.threads.drain(..) {
+                let consumed = match (&self.program[pc], ch) {
+                    (Inst::Match, _) => {
+                        matched = Some(slots);
+                        // Threads after this one have lower priority.
+                        break;
+                    }
+                    (Inst::Char(expected), Some(ch)) => *expected == ch,
+                    (Inst::Any, Some(ch)) => ch != '\n',
+                    (Inst::Class(class), Some(ch)) => class.matches(ch),
+                    _ => false,
+                };
+                if consumed {
+                    let mut slots = slots;
+                    self.add(&mut next, pc + 1, text, pos + 1, &mut slots);
+                }
+            }
+            current.clear();
+            if ch.is_none() {
+                break;
+            }
+            std::mem::swap(&mut current, &mut next);
+            pos += 1;
+        }
+        matched
+    }
+
+    /// Adds the thread at `pc` to `threads`, following jumps, splits, saves and assertions
+    /// in priority order.
+    fn add(&self, threads: &mut Threads, pc: usize, text: &Text, pos: usize, slots: &mut [Option<usize>]) {
+        enum Job {
+            Visit(usize),
+            Restore(usize, Option<usize>),
+        }
+        let mut jobs = vec![Job::Visit(pc)];
+        while let Some(job) = jobs.pop() {
+            let pc = match job {
+                Job::Visit(pc) => pc,
+                Job::Restore(slot, old) => {
+                    slots[slot] = old;
+                    continue;
+                }
+            };
+            if !threads.visit(pc) {
+                continue;
+            }
+            match &self.program[pc] {
+                Inst::Jump(target) => jobs.push(Job::Visit(*target)),
+                Inst::Split(first, second) => {
+                    jobs.push(Job::Visit(*second));
+                    jobs.push(Job::Visit(*first));
+                }
+                Inst::Save(slot) => {
+                    jobs.push(Job::Restore(*slot, slots[*slot]));
+                    slots[*slot] = Some(pos);
+                    jobs.push(Job::Visit(pc + 1));
+                }
+                Inst::Assert(assertion) => {
+                    if assertion.holds(text, pos) {
+                        jobs.push(Job::Visit(pc + 1));
+                    }
+                }
+                _ => threads.threads.push((pc, slots.to_vec())),
+            }
+        }
+    }
+}
+
+/// The text as characters, with the byte offset of each.
+struct Text {
+    chars: Vec<char>,
+    offsets: Vec<usize>,
+}
+
+impl Text {
+    fn new(text: &str) -> Self {
+        let (mut offsets, chars): (Vec<usize>, Vec<char>) = text.char_indices().unzip();
+        offsets.push(text.len());
+        Text { chars, offsets }
+    }
+
+    fn captures(&self, slots: &[Option<usize>]) -> Captures {
+        let groups = slots.chunks(2).map(|pair| match pair {
+            [Some(start), Some(end)] => Some(self.offsets[*start]..self.offsets[*end]),
+            _ => None,
+        });
+        Captures { groups: groups.collect() }
+    }
+}
+
+/// Threads at one position, each program counter at most once.
+struct Threads {
+    visited: Vec<bool>,
+    threads: Vec<(usize, Vec<Option<usize>>)>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Self {
+        Threads { visited: vec![false; len], threads: Vec::new() }
+    }
+
+    /// Whether `pc` is new at this position.
+    fn visit(&mut self, pc: usize) -> bool {
+        !std::mem::replace(&mut self.visited[pc], true)
+    }
+
+    fn clear(&mut self) {
+        self.visited.fill(false);
+        self.threads.clear();
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Save(usize),
+    /// Tries the first target before the second.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+impl Assertion {
+    fn holds(self, text: &Text, pos: usize) -> bool {
+        let boundary = || {
+            let before = pos > 0 && is_word(text.chars[pos - 1]);
+            let after = text.chars.get(pos).is_some_and(|&ch| is_word(ch));
+            before != after
+        };
+        match self {
+            Assertion::Start => pos == 0,
+            Assertion::End => pos == text.chars.len(),
+            Assertion::WordBoundary => boundary(),
+            Assertion::NotWordBoundary => !boundary(),
+        }
+    }
+}
+
+fn is_word(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ClassItem {
+    Range(char, char),
+    Digit,
+    Word,
+    Space,
+    NotDigit,
+    NotWord,
+    NotSpace,
+}
+
+impl ClassItem {
+    fn matches(self, ch: char) -> bool {
+        match self {
+            ClassItem::Range(low, high) => (low..=high).contains(&ch),
+            ClassItem::Digit => ch.is_ascii_digit(),
+            ClassItem::Word => is_word(ch),
+            ClassItem::Space => ch.is_whitespace(),
+            ClassItem::NotDigit => !ch.is_ascii_digit(),
+            ClassItem::NotWord => !is_word(ch),
+            ClassItem::NotSpace => !ch.is_whitespace(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+impl Class {
+    fn matches(&self, ch: char) -> bool {
+        self.items.iter().any(|item| item.matches(ch)) != self.negated
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat { node: Box<Node>, min: u32, max: Option<u32>, greedy: bool },
+}
+
+struct Parser {
+    pattern: Vec<char>,
+    pos: usize,
+    depth: usize,
+    names: Vec<Option<String>>,
+}
+
+impl Parser {
+    fn error(&self, message: &str) -> RegexError {
+        RegexError { offset: self.pos, message: message.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.pattern.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, ch: char) -> bool {
+        let found = self.peek() == Some(ch);
+        self.pos += found as usize;
+        found
+    }
+
+    fn alternation(&mut self) -> Result<Node, RegexError> {
+        let mut alternatives = vec![self.concat()?];
+        while self.eat('|') {
+            alternatives.push(self.concat()?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.pop().unwrap() } else { Node::Alternation(alternatives) })
+    }
+
+    fn concat(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = Vec::new();
+        while let Some(ch) = self.peek() {
+            if ch == '|' || ch == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, RegexError> {
+        let ch = self.peek().ok_or_else(|| self.error("unexpected end of pattern"))?;
+        self.pos += 1;
+        Ok(match ch {
+            '(' => self.group()?,
+            '[' => Node::Class(self.class()?),
+            '.' => Node::Any,
+            '^' => Node::Assert(Assertion::Start),
+            '$' => Node::Assert(Assertion::End),
+            '*' | '+' | '?' => {
+                self.pos -= 1;
+                return Err(self.error("nothing to repeat"));
+            }
+            '\\' => match self.escape()? {
+                Escape::Char(ch) => Node::Char(ch),
+                Escape::Item(item) => Node::Class(Class { negated: false, items: vec![item] }),
+                Escape::Assert(assertion) => Node::Assert(assertion),
+            },
+            ch => Node::Char(ch),
+        })
+    }
+
+    fn group(&mut self) -> Result<Node, RegexError> {
+        if self.depth == MAX_NESTING {
+            return Err(self.error("groups nested too deeply"));
+        }
+        let index = if self.eat('?') {
+            if self.eat(':') {
+                None
+            } else if self.eat('<') || (self.eat('P') && self.eat('<')) {
+                let name = self.name()?;
+                if self.names.iter().any(|taken| taken.as_deref() == Some(&name)) {
+                    return Err(self.error("duplicate group name"));
+                }
+                self.names.push(Some(name));
+                Some(self.names.len() - 1)
+            } else {
+                return Err(self.error("unsupported group flag"));
+            }
+        } else {
+            self.names.push(None);
+            Some(self.names.len() - 1)
+        };
+        self.depth += 1;
+        let inner = self.alternation()?;
+        self.depth -= 1;
+        if !self.eat(')') {
+            return Err(self.error("unclosed group"));
+        }
+        Ok(Node::Group(Box::new(inner), index))
+    }
+
+    fn name(&mut self) -> Result<String, RegexError> {
+        let start = self.pos;
+        while self.peek().is_some_and(is_word) {
+            self.pos += 1;
+        }
+        let name: String = self.pattern[start..self.pos].iter().collect();
+        if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) || !self.eat('>') {
+            return Err(self.error("invalid group name"));
+        }
+        Ok(name)
+    }
+
+    fn class(&mut self) -> Result<Class, RegexError> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let ch = self.peek().ok_or_else(|| self.error("unclosed character class"))?;
+            self.pos += 1;
+            if ch == ']' && !first {
+                break;
+            }
+            first = false;
+            let low = match ch {
+                '\\' => match self.escape()? {
+                    Escape::Char(ch) => ch,
+                    Escape::Item(item) => {
+                        items.push(item);
+                        continue;
+                    }
+                    Escape::Assert(_) => return Err(self.error("assertion in a character class")),
+                },
+                ch => ch,
+            };
+            if self.peek() == Some('-') && self.pattern.get(self.pos + 1).is_some_and(|&next| next != ']') {
+                self.pos += 1;
+                let high = match self.peek() {
+                    Some('\\') => {
+                        self.pos += 1;
+                        match self.escape()? {
+                            Escape::Char(ch) => ch,
+                            _ => return Err(self.error("invalid class range")),
+                        }
+                    }
+                    Some(ch) => {
+                        self.pos += 1;
+                        ch
+                    }
+                    None => return Err(self.error("unclosed character class")),
+                };
+                if high < low {
+                    return Err(self.error("invalid class range"));
+                }
+                items.push(ClassItem::Range(low, high));
+            } else {
+                items.push(ClassItem::Range(low, low));
+            }
+        }
+        Ok(Class { negated, items })
+    }
+
+    /// What follows a `\`.
+    fn escape(&mut self) -> Result<Escape, RegexError> {
+        let ch = self.peek().ok_or_else(|| self.error("trailing backslash"))?;
+        self.pos += 1;
+        Ok(match ch {
+            'd' => Escape::Item(ClassItem::Digit),
+            'w' => Escape::Item(ClassItem::Word),
+            's' => Escape::Item(ClassItem::Space),
+            'D' => Escape::Item(ClassItem::NotDigit),
+            'W' => Escape::Item(ClassItem::NotWord),
+            'S' => Escape::Item(ClassItem::NotSpace),
+            'b' => Escape::Assert(Assertion::WordBoundary),
+            'B' => Escape::Assert(Assertion::NotWordBoundary),
+            'n' => Escape::Char('\n'),
+            't' => Escape::Char('\t'),
+            'r' => Escape::Char('\r'),
+            ch if ch.is_ascii_alphanumeric() => {
+                self.pos -= 1;
+                return Err(self.error("unknown escape"));
+            }
+            ch => Escape::Char(ch),
+        })
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, RegexError> {
+        let start = self.pos;
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => match self.counts() {
+                Some(counts) => counts,
+                None => {
+                    self.pos = start;
+                    return Ok(atom);
+                }
+            },
+            _ => return Ok(atom),
+        };
+        if self.pos == start {
+            self.pos += 1;
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("invalid repetition count"));
+        }
+        if matches!(atom, Node::Assert(_) | Node::Empty) {
+            return Err(RegexError { offset: start, message: "nothing to repeat".to_string() });
+        }
+        let greedy = !self.eat('?');
+        if matches!(self.peek(), Some('*' | '+' | '?')) {
+            return Err(self.error("nothing to repeat"));
+        }
+        if self.peek() == Some('{') {
+            let brace = self.pos;
+            let counted = self.counts().is_some();
+            self.pos = brace;
+            if counted {
+                return Err(self.error("nothing to repeat"));
+            }
+        }
+        Ok(Node::Repeat { node: Box::new(atom), min, max, greedy })
+    }
+
+    /// `{n}`, `{n,}` or `{n,m}`, leaving the position after it; `None` if it is a literal `{`.
+    fn counts(&mut self) -> Option<(u32, Option<u32>)> {
+        self.pos += 1;
+        let min = self.number()?;
+        let max = if self.eat(',') {
+            if self.peek() == Some('}') { None } else { Some(self.number()?) }
+        } else {
+            Some(min)
+        };
+        self.eat('}').then_some((min, max))
+    }
+
+    fn number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        self.pattern[start..self.pos].iter().collect::<String>().parse().ok()
+    }
+}
+
+enum Escape {
+    Char(char),
+    Item(ClassItem),
+    Assert(Assertion),
+}
+
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.program.len() == MAX_PROGRAM_LEN {
+            return Err(RegexError { offset: 0, message: "pattern is too large".to_string() });
+        }
+        self.program.push(inst);
+        Ok(self.program.len() - 1)
+    }
+
+    fn node(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Empty => {}
+            Node::Char(ch) => {
+                self.push(Inst::Char(*ch))?;
+            }
+            Node::Any => {
+                self.push(Inst::Any)?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Assert(assertion) => {
+                self.push(Inst::Assert(*assertion))?;
+            }
+            Node::Group(inner, index) => {
+                if let Some(index) = index {
+                    self.push(Inst::Save(index * 2))?;
+                }
+                self.node(inner)?;
+                if let Some(index) = index {
+                    self.push(Inst::Save(index * 2 + 1))?;
+                }
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.node(node)?;
+                }
+            }
+            Node::Alternation(alternatives) => {
+                let mut jumps = Vec::new();
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i + 1 < alternatives.len() {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.node(alternative)?;
+                        jumps.push(self.push(Inst::Jump(0))?);
+                        self.program[split] = Inst::Split(split + 1, self.program.len());
+                    } else {
+                        self.node(alternative)?;
+                    }
+                }
+                let end = self.program.len();
+                for jump in jumps {
+                    self.program[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat { node, min, max, greedy } => {
+                let start = self.program.len();
+                self.node(node)?;
+                if self.program.len() == start {
+                    // Repeating nothing matches nothing.
+                    return Ok(());
+                }
+                self.program.truncate(start);
+                for _ in 0..*min {
+                    self.node(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.node(node)?;
+                        self.push(Inst::Jump(split))?;
+                        self.program[split] = self.split(split + 1, self.program.len(), *greedy);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.push(Inst::Split(0, 0))?);
+                            self.node(node)?;
+                        }
+                        let end = self.program.len();
+                        for split in splits {
+                            self.program[split] = self.split(split + 1, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prefers `more` when greedy.
+    fn split(&self, more: usize, done: usize, greedy: bool) -> Inst {
+        if greedy { Inst::Split(more, done) } else { Inst::Split(done, more) }
+    }
+}
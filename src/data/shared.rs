@@ -0,0 +1,247 @@
+//! `Send + Sync` copies of compiled modules for multi-threaded embedding.
+//!
+//! Values and functions are `Rc`-based, so an `IrisVM` and everything it loaded stay on one
+//! thread. To run a VM per worker thread, load or assemble the module once, convert it to a
+//! `SharedModule`, hand the `Arc` to the workers, and have each call `instantiate` to get a
+//! `Module` of its own to load. Bytecode is shared; each instantiation only rebuilds the
+//! `Rc` wrappers. Constants must be immutable: scalars, strings, chars, big integers,
+//! tuples and ranges of those, functions, classes and channels. Host functions aren't shared;
+//! register natives on each VM instead.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::data::module::Module;
+use crate::debug::lines::LineTable;
+use crate::vm::bigint::BigInt;
+use crate::vm::channel::Channel;
+use crate::vm::function::{Function, FunctionKind};
+use crate::vm::object::Class;
+use crate::vm::range::Range;
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedValue {
+    Null,
+    Bool(bool),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    F32(f32),
+    F64(f64),
+    Str(Arc<str>),
+    Char(char),
+    BigInt(Arc<BigInt>),
+    Tuple(Arc<[SharedValue]>),
+    Range(Range),
+    Function(Arc<SharedFunction>),
+    Class(Arc<SharedClass>),
+    Channel(Channel),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SharedFunction {
+    pub name: String,
+    pub arity: usize,
+    pub bytecode: Arc<[u8]>,
+    pub constants: Vec<SharedValue>,
+    pub lines: LineTable,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SharedClass {
+    pub name: String,
+    pub type_id: usize,
+    pub superclass: Option<Arc<SharedClass>>,
+    pub methods: Vec<Arc<SharedFunction>>,
+    pub properties: HashMap<String, usize>,
+    pub extensible: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SharedModule {
+    pub name: String,
+    pub functions: Vec<Arc<SharedFunction>>,
+    pub entry_point: Option<String>,
+}
+
+impl SharedModule {
+    /// Copies `module` into thread-safe form, failing on host functions and mutable constants.
+    pub fn new(module: &Module) -> Result<Self, Box<dyn Error>> {
+        let mut sharing = Sharing::default();
+        let functions = module.functions.iter().map(|function| sharing.function(function)).collect::<Result<_, _>>()?;
+        Ok(Self { name: module.name.clone(), functions, entry_point: module.entry_point.clone() })
+    }
+
+    /// A module of this thread's own to load into an `IrisVM`. Functions and classes referred
+    /// to from several places stay shared within the result.
+    pub fn instantiate(&self) -> Module {
+        let mut instances = Instances::default();
+        Module {
+            name: self.name.clone(),
+            functions: self.functions.iter().map(|function| instances.function(function)).collect(),
+            entry_point: self.entry_point.clone(),
+        }
+    }
+}
+
+impl SharedValue {
+    /// `value` in thread-safe form, failing on host functions and mutable values.
+    pub fn from_value(value: &Value) -> Result<Self, Box<dyn Error>> {
+        Sharing::default().value(value)
+    }
+
+    /// A value of this thread's own.
+    pub fn to_value(&self) -> Value {
+        Instances::default().value(self)
+    }
+}
+
+/// Converts to shared form, keeping functions and classes that are referred to more than
+/// once shared.
+#[derive(Default)]
+struct Sharing {
+    functions: HashMap<*const Function, Arc<SharedFunction>>,
+    classes: HashMap<*const Class, Arc<SharedClass>>,
+}
+
+impl Sharing {
+    fn function(&mut self, function: &Rc<Function>) -> Result<Arc<SharedFunction>, Box<dyn Error>> {
+        if let Some(shared) = self.functions.get(&Rc::as_ptr(function)) {
+            return Ok(shared.clone());
+        }
+        let bytecode = match (&function.kind, &function.bytecode) {
+            (FunctionKind::Bytecode, Some(bytecode)) => bytecode.as_slice().into(),
+            _ => return Err(format!("Native function '{}' can't be shared between threads", function.name).into()),
+        };
+        let constants = function.constants.iter().map(|constant| self.value(constant)).collect::<Result<_, _>>()?;
+        let shared = Arc::new(SharedFunction {
+            name: function.name.clone(),
+            arity: function.arity,
+            bytecode,
+            constants,
+            lines: function.lines.clone(),
+        });
+        self.functions.insert(Rc::as_ptr(function), shared.clone());
+        Ok(shared)
+    }
+
+    fn class(&mut self, class: &Rc<Class>) -> Result<Arc<SharedClass>, Box<dyn Error>> {
+        if let Some(shared) = self.classes.get(&Rc::as_ptr(class)) {
+            return Ok(shared.clone());
+        }
+        let superclass = class.superclass.as_ref().map(|superclass| self.class(superclass)).transpose()?;
+        let methods = class.methods.iter().map(|method| self.function(method)).collect::<Result<_, _>>()?;
+        let shared = Arc::new(SharedClass {
+            name: class.name.clone(),
+            type_id: class.type_id,
+            superclass,
+            methods,
+            properties: class.properties.clone(),
+            extensible: class.extensible,
+        });
+        self.classes.insert(Rc::as_ptr(class), shared.clone());
+        Ok(shared)
+    }
+
+    fn value(&mut self, value: &Value) -> Result<SharedValue, Box<dyn Error>> {
+        let shared = match value {
+            Value::Null => SharedValue::Null,
+            Value::Bool(v) => SharedValue::Bool(*v),
+            Value::I8(v) => SharedValue::I8(*v),
+            Value::I16(v) => SharedValue::I16(*v),
+            Value::I32(v) => SharedValue::I32(*v),
+            Value::I64(v) => SharedValue::I64(*v),
+            Value::I128(v) => SharedValue::I128(*v),
+            Value::U8(v) => SharedValue::U8(*v),
+            Value::U16(v) => SharedValue::U16(*v),
+            Value::U32(v) => SharedValue::U32(*v),
+            Value::U64(v) => SharedValue::U64(*v),
+            Value::U128(v) => SharedValue::U128(*v),
+            Value::F32(v) => SharedValue::F32(*v),
+            Value::F64(v) => SharedValue::F64(*v),
+            Value::Str(s) => SharedValue::Str(Arc::from(&**s)),
+            Value::Char(c) => SharedValue::Char(*c),
+            Value::BigInt(v) => SharedValue::BigInt(Arc::new((**v).clone())),
+            Value::Tuple(elements) => {
+                SharedValue::Tuple(elements.iter().map(|element| self.value(element)).collect::<Result<_, _>>()?)
+            }
+            Value::Range(range) => SharedValue::Range(*range),
+            Value::Function(function) => SharedValue::Function(self.function(function)?),
+            Value::Class(class) => SharedValue::Class(self.class(class)?),
+            Value::Channel(channel) => SharedValue::Channel((**channel).clone()),
+            other => return Err(format!("Constant {:?} can't be shared between threads", other).into()),
+        };
+        Ok(shared)
+    }
+}
+
+/// Rebuilds `Rc` values from shared ones, one instance per shared function or class.
+#[derive(Default)]
+struct Instances {
+    functions: HashMap<*const SharedFunction, Rc<Function>>,
+    classes: HashMap<*const SharedClass, Rc<Class>>,
+}
+
+impl Instances {
+    fn function(&mut self, shared: &Arc<SharedFunction>) -> Rc<Function> {
+        if let Some(function) = self.functions.get(&Arc::as_ptr(shared)) {
+            return function.clone();
+        }
+        let constants = shared.constants.iter().map(|constant| self.value(constant)).collect();
+        let function = Function::new_bytecode(shared.name.clone(), shared.arity, shared.bytecode.to_vec(), constants)
+            .with_lines(shared.lines.clone());
+        let function = Rc::new(function);
+        self.functions.insert(Arc::as_ptr(shared), function.clone());
+        function
+    }
+
+    fn class(&mut self, shared: &Arc<SharedClass>) -> Rc<Class> {
+        if let Some(class) = self.classes.get(&Arc::as_ptr(shared)) {
+            return class.clone();
+        }
+        let superclass = shared.superclass.as_ref().map(|superclass| self.class(superclass));
+        let mut class = Class::new(shared.name.clone(), shared.type_id, superclass);
+        class.methods = shared.methods.iter().map(|method| self.function(method)).collect();
+        class.properties = shared.properties.clone();
+        class.extensible = shared.extensible;
+        let class = Rc::new(class);
+        self.classes.insert(Arc::as_ptr(shared), class.clone());
+        class
+    }
+
+    fn value(&mut self, shared: &SharedValue) -> Value {
+        match shared {
+            SharedValue::Null => Value::Null,
+            SharedValue::Bool(v) => Value::Bool(*v),
+            SharedValue::I8(v) => Value::I8(*v),
+            SharedValue::I16(v) => Value::I16(*v),
+            SharedValue::I32(v) => Value::I32(*v),
+            SharedValue::I64(v) => Value::I64(*v),
+            SharedValue::I128(v) => Value::I128(*v),
+            SharedValue::U8(v) => Value::U8(*v),
+            SharedValue::U16(v) => Value::U16(*v),
+            SharedValue::U32(v) => Value::U32(*v),
+            SharedValue::U64(v) => Value::U64(*v),
+            SharedValue::U128(v) => Value::U128(*v),
+            SharedValue::F32(v) => Value::F32(*v),
+            SharedValue::F64(v) => Value::F64(*v),
+            SharedValue::Str(s) => Value::Str(crate::vm::intern::intern(s)),
+            SharedValue::Char(c) => Value::Char(*c),
+            SharedValue::BigInt(v) => Value::BigInt(Rc::new((**v).clone())),
+            SharedValue::Tuple(elements) => Value::Tuple(elements.iter().map(|element| self.value(element)).collect()),
+            SharedValue::Range(range) => Value::Range(*range),
+            SharedValue::Function(function) => Value::Function(self.function(function)),
+            SharedValue::Class(class) => Value::Class(self.class(class)),
+            SharedValue::Channel(channel) => Value::Channel(Rc::new(channel.clone())),
+        }
+    }
+}
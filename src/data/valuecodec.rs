@@ -0,0 +1,693 @@
+//! A compact binary format for value graphs, for passing values between processes and
+//! keeping them on disk.
+//!
+//! `encode` writes a value and everything it reaches; `decode` rebuilds it. Each heap value
+//! is written once and referred to by index after that, so sharing survives the round
+//! trip and so do cycles through arrays, maps, sets, instances and closures' upvalues. A
+//! cycle through a tuple can't be rebuilt, as a tuple is made from its finished elements,
+//! and fails to encode.
+//!
+//! Integers are LEB128 varints, zigzagged when signed. Functions are written as their
+//! bytecode, constants and line table; classes with their methods and properties. Host
+//! functions, weak references, coroutines, futures, fibers, channels and bound methods are
+//! tied to the running process and fail to encode. `encode_with_natives` writes native
+//! functions by name instead, for `decode_with_natives` to look up in the decoding process.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use bincode::config::standard;
+use bincode::serde::{decode_from_slice, encode_to_vec};
+use crate::vm::bigint::BigInt;
+use crate::vm::closure::{Closure, Upvalue, UpvalueRef};
+use crate::vm::function::{Function, FunctionKind};
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::object::{Class, Instance};
+use crate::vm::range::Range;
+use crate::vm::set::ValueSet;
+use crate::vm::value::Value;
+
+pub const MAGIC: [u8; 4] = [0xFF, b'I', b'R', b'V'];
+pub const VERSION: u8 = 1;
+/// How deep values may nest, so a long chain fails instead of overflowing the stack.
+pub const MAX_DEPTH: usize = 512;
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const I8: u8 = 3;
+const I16: u8 = 4;
+const I32: u8 = 5;
+const I64: u8 = 6;
+const I128: u8 = 7;
+const U8: u8 = 8;
+const U16: u8 = 9;
+const U32: u8 = 10;
+const U64: u8 = 11;
+const U128: u8 = 12;
+const F32: u8 = 13;
+const F64: u8 = 14;
+const STR: u8 = 15;
+const CHAR: u8 = 16;
+const BIG_INT: u8 = 17;
+const RANGE: u8 = 18;
+const TUPLE: u8 = 19;
+const ARRAY: u8 = 20;
+const MAP: u8 = 21;
+const SET: u8 = 22;
+const INT32_ARRAY: u8 = 23;
+const FLOAT64_ARRAY: u8 = 24;
+const BYTE_ARRAY: u8 = 25;
+const BYTES: u8 = 26;
+const OBJECT: u8 = 27;
+const FUNCTION: u8 = 28;
+const CLASS: u8 = 29;
+const CLOSURE: u8 = 30;
+/// A value written earlier, by index.
+const REF: u8 = 31;
+/// A native function, by name.
+const NATIVE: u8 = 32;
+
+/// Finds the native function a name stands for, see `decode_with_natives`.
+pub type NativeLookup<'a> = &'a dyn Fn(&str) -> Option<Value>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodecError(pub String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for CodecError {}
+
+fn error<T>(message: impl Into<String>) -> Result<T, CodecError> {
+    Err(CodecError(message.into()))
+}
+
+pub fn encode(value: &Value) -> Result<Vec<u8>, CodecError> {
+    encode_value(value, false)
+}
+
+/// Like `encode`, but native functions are written as their name.
+pub fn encode_with_natives(value: &Value) -> Result<Vec<u8>, CodecError> {
+    encode_value(value, true)
+}
+
+fn encode_value(value: &Value, natives: bool) -> Result<Vec<u8>, CodecError> {
+    let mut encoder = Encoder { natives, ..Encoder::default() };
+    encoder.out.extend(MAGIC);
+    encoder.out.push(VERSION);
+    encoder.value(value)?;
+    Ok(encoder.out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Value, CodecError> {
+    decode_value(bytes, None)
+}
+
+/// Decodes what `encode_with_natives` wrote, replacing each native function with what
+/// `natives` returns for its name.
+pub fn decode_with_natives(bytes: &[u8], natives: NativeLookup) -> Result<Value, CodecError> {
+    decode_value(bytes, Some(natives))
+}
+
+fn decode_value(bytes: &[u8], natives: Option<NativeLookup>) -> Result<Value, CodecError> {
+    let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+        return error("Not an encoded value");
+    };
+    match rest.first() {
+        Some(&VERSION) => {}
+        Some(version) => return error(format!("Unsupported value format version {}", version)),
+        None => return error("Unexpected end of encoded value"),
+    }
+    let mut decoder = Decoder { input: &rest[1..], values: Vec::new(), upvalues: Vec::new(), depth: 0, natives };
+    let value = decoder.value()?;
+    if !decoder.input.is_empty() {
+        return error(format!("{} trailing byte(s) after encoded value", decoder.input.len()));
+    }
+    Ok(value)
+}
+
+/// The allocation behind a heap value, which identifies it while encoding.
+fn address(value: &Value) -> Option<*const ()> {
+    Some(match value {
+        Value::Str(s) => Rc::as_ptr(s) as *const u8 as *const (),
+        Value::BigInt(b) => Rc::as_ptr(b) as *const (),
+        Value::Tuple(items) => Rc::as_ptr(items) as *const Value as *const (),
+        Value::Array(items) => Gc::addr(items),
+        Value::Map(entries) => Gc::addr(entries),
+        Value::Set(set) => Gc::addr(set),
+        Value::Int32Array(items) => Gc::addr(items),
+        Value::Float64Array(items) => Gc::addr(items),
+        Value::ByteArray(bytes) => Gc::addr(bytes),
+        Value::Bytes(bytes) => Gc::addr(bytes),
+        Value::Object(instance) => Gc::addr(instance),
+        Value::Function(function) => Rc::as_ptr(function) as *const (),
+        Value::Class(class) => Rc::as_ptr(class) as *const (),
+        Value::Closure(closure) => Rc::as_ptr(closure) as *const (),
+        _ => return None,
+    })
+}
+
+/// Heap values are numbered in the order the decoder can first refer to them: containers
+/// that can be part of a cycle as soon as their tag is written, everything else once
+/// finished.
+#[derive(Default)]
+struct Encoder {
+    out: Vec<u8>,
+    ids: HashMap<*const (), usize>,
+    upvalues: HashMap<*const RefCell<Upvalue>, usize>,
+    /// Tuples being written, to catch a cycle through one.
+    unfinished: HashSet<*const ()>,
+    depth: usize,
+    /// Whether native functions are written by name rather than failing.
+    natives: bool,
+}
+
+impl Encoder {
+    fn define(&mut self, address: *const ()) {
+        let id = self.ids.len();
+        self.ids.insert(address, id);
+    }
+
+    fn value(&mut self, value: &Value) -> Result<(), CodecError> {
+        let address = address(value);
+        if let Some(&id) = address.and_then(|address| self.ids.get(&address)) {
+            self.out.push(REF);
+            unsigned(&mut self.out, id as u128);
+            return Ok(());
+        }
+        if self.depth == MAX_DEPTH {
+            return error(format!("Value is nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        self.contents(value, address.unwrap_or(std::ptr::null()))?;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn contents(&mut self, value: &Value, address: *const ()) -> Result<(), CodecError> {
+        let out = &mut self.out;
+        match value {
+            Value::Null => out.push(NULL),
+            Value::Bool(false) => out.push(FALSE),
+            Value::Bool(true) => out.push(TRUE),
+            Value::I8(v) => tagged(out, I8, |out| signed(out, *v as i128)),
+            Value::I16(v) => tagged(out, I16, |out| signed(out, *v as i128)),
+            Value::I32(v) => tagged(out, I32, |out| signed(out, *v as i128)),
+            Value::I64(v) => tagged(out, I64, |out| signed(out, *v as i128)),
+            Value::I128(v) => tagged(out, I128, |out| signed(out, *v)),
+            Value::U8(v) => tagged(out, U8, |out| unsigned(out, *v as u128)),
+            Value::U16(v) => tagged(out, U16, |out| unsigned(out, *v as u128)),
+            Value::U32(v) => tagged(out, U32, |out| unsigned(out, *v as u128)),
+            Value::U64(v) => tagged(out, U64, |out| unsigned(out, *v as u128)),
+            Value::U128(v) => tagged(out, U128, |out| unsigned(out, *v)),
+            Value::F32(v) => tagged(out, F32, |out| out.extend(v.to_le_bytes())),
+            Value::F64(v) => tagged(out, F64, |out| out.extend(v.to_le_bytes())),
+            Value::Char(c) => tagged(out, CHAR, |out| unsigned(out, *c as u128)),
+            Value::Range(range) => tagged(out, RANGE, |out| {
+                signed(out, range.start as i128);
+                signed(out, range.end as i128);
+                signed(out, range.step as i128);
+            }),
+            Value::Str(s) => {
+                tagged(out, STR, |out| string(out, s));
+                self.define(address);
+            }
+            Value::BigInt(b) => {
+                tagged(out, BIG_INT, |out| string(out, &b.to_string()));
+                self.define(address);
+            }
+            Value::Int32Array(items) => {
+                tagged(out, INT32_ARRAY, |out| {
+                    let items = items.borrow();
+                    unsigned(out, items.len() as u128);
+                    items.iter().for_each(|item| signed(out, *item as i128));
+                });
+                self.define(address);
+            }
+            Value::Float64Array(items) => {
+                tagged(out, FLOAT64_ARRAY, |out| {
+                    let items = items.borrow();
+                    unsigned(out, items.len() as u128);
+                    items.iter().for_each(|item| out.extend(item.to_le_bytes()));
+                });
+                self.define(address);
+            }
+            Value::ByteArray(bytes) => {
+                tagged(out, BYTE_ARRAY, |out| byte_string(out, &bytes.borrow()));
+                self.define(address);
+            }
+            Value::Bytes(bytes) => {
+                tagged(out, BYTES, |out| byte_string(out, &bytes.borrow()));
+                self.define(address);
+            }
+            Value::Tuple(items) => {
+                if !self.unfinished.insert(address) {
+                    return error("Cannot encode a cycle through a tuple");
+                }
+                out.push(TUPLE);
+                unsigned(out, items.len() as u128);
+                items.iter().try_for_each(|item| self.value(item))?;
+                self.unfinished.remove(&address);
+                self.define(address);
+            }
+            Value::Array(items) => {
+                out.push(ARRAY);
+                self.define(address);
+                let items = items.borrow().clone();
+                unsigned(&mut self.out, items.len() as u128);
+                items.iter().try_for_each(|item| self.value(item))?;
+            }
+            Value::Map(entries) => {
+                out.push(MAP);
+                self.define(address);
+                let mut entries: Vec<(String, Value)> = entries.borrow().iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                unsigned(&mut self.out, entries.len() as u128);
+                for (key, value) in &entries {
+                    string(&mut self.out, key);
+                    self.value(value)?;
+                }
+            }
+            Value::Set(set) => {
+                out.push(SET);
+                self.define(address);
+                let items: Vec<Value> = set.borrow().iter().cloned().collect();
+                unsigned(&mut self.out, items.len() as u128);
+                items.iter().try_for_each(|item| self.value(item))?;
+            }
+            Value::Object(instance) => {
+                out.push(OBJECT);
+                let (class, fields) = {
+                    let instance = instance.borrow();
+                    let names = instance.shape.names();
+                    let fields: Vec<(Option<String>, Value)> = instance.fields.iter().enumerate()
+                        .map(|(offset, value)| (names.get(offset).copied().flatten().map(str::to_string), value.clone()))
+                        .collect();
+                    (instance.class.clone(), fields)
+                };
+                self.value(&Value::Class(class))?;
+                self.define(address);
+                unsigned(&mut self.out, fields.len() as u128);
+                for (name, value) in &fields {
+                    match name {
+                        Some(name) => tagged(&mut self.out, 1, |out| string(out, name)),
+                        None => self.out.push(0),
+                    }
+                    self.value(value)?;
+                }
+            }
+            Value::Function(function) if self.natives && matches!(function.kind, FunctionKind::Native) => {
+                tagged(out, NATIVE, |out| string(out, &function.name));
+                self.define(address);
+            }
+            Value::Function(function) => {
+                self.function(function)?;
+                self.define(address);
+            }
+            Value::Class(class) => {
+                out.push(CLASS);
+                string(out, &class.name);
+                unsigned(out, class.type_id as u128);
+                match &class.superclass {
+                    Some(superclass) => {
+                        self.out.push(1);
+                        self.value(&Value::Class(superclass.clone()))?;
+                    }
+                    None => self.out.push(0),
+                }
+                unsigned(&mut self.out, class.methods.len() as u128);
+                for method in &class.methods {
+                    self.value(&Value::Function(method.clone()))?;
+                }
+                let mut properties: Vec<_> = class.properties.iter().collect();
+                properties.sort();
+                unsigned(&mut self.out, properties.len() as u128);
+                for (name, offset) in properties {
+                    string(&mut self.out, name);
+                    unsigned(&mut self.out, *offset as u128);
+                }
+                self.out.push(class.extensible as u8);
+                self.define(address);
+            }
+            Value::Closure(closure) => {
+                out.push(CLOSURE);
+                self.value(&Value::Function(closure.function.clone()))?;
+                // The upvalues are numbered before the closure and filled in after it, so a
+                // closure can capture itself.
+                unsigned(&mut self.out, closure.upvalues.len() as u128);
+                let mut fresh = Vec::new();
+                for upvalue in &closure.upvalues {
+                    match self.upvalues.get(&Rc::as_ptr(upvalue)) {
+                        Some(&id) => tagged(&mut self.out, 0, |out| unsigned(out, id as u128)),
+                        None => {
+                            self.upvalues.insert(Rc::as_ptr(upvalue), self.upvalues.len());
+                            self.out.push(1);
+                            fresh.push(upvalue.borrow().clone());
+                        }
+                    }
+                }
+                self.define(address);
+                for upvalue in fresh {
+                    match upvalue {
+                        Upvalue::Open(slot) => tagged(&mut self.out, 0, |out| unsigned(out, slot as u128)),
+                        Upvalue::Closed(value) => {
+                            self.out.push(1);
+                            self.value(&value)?;
+                        }
+                    }
+                }
+            }
+            other => return error(format!("Cannot encode {:?}", other)),
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, function: &Function) -> Result<(), CodecError> {
+        let bytecode = match (&function.kind, &function.bytecode) {
+            (FunctionKind::Bytecode, Some(bytecode)) => bytecode,
+            _ => return error(format!("Cannot encode native function '{}'", function.name)),
+        };
+        let lines = encode_to_vec(&function.lines, standard()).map_err(|e| CodecError(e.to_string()))?;
+        self.out.push(FUNCTION);
+        string(&mut self.out, &function.name);
+        unsigned(&mut self.out, function.arity as u128);
+        byte_string(&mut self.out, bytecode);
+        unsigned(&mut self.out, function.constants.len() as u128);
+        function.constants.iter().try_for_each(|constant| self.value(constant))?;
+        byte_string(&mut self.out, &lines);
+        Ok(())
+    }
+}
+
+fn tagged(out: &mut Vec<u8>, tag: u8, contents: impl FnOnce(&mut Vec<u8>)) {
+    out.push(tag);
+    contents(out);
+}
+
+/// LEB128.
+fn unsigned(out: &mut Vec<u8>, mut value: u128) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Zigzag LEB128, so small negative numbers stay short.
+fn signed(out: &mut Vec<u8>, value: i128) {
+    unsigned(out, ((value << 1) ^ (value >> 127)) as u128);
+}
+
+fn byte_string(out: &mut Vec<u8>, bytes: &[u8]) {
+    unsigned(out, bytes.len() as u128);
+    out.extend(bytes);
+}
+
+fn string(out: &mut Vec<u8>, s: &str) {
+    byte_string(out, s.as_bytes());
+}
+
+struct Decoder<'a> {
+    input: &'a [u8],
+    values: Vec<Value>,
+    upvalues: Vec<UpvalueRef>,
+    depth: usize,
+    natives: Option<NativeLookup<'a>>,
+}
+
+impl Decoder<'_> {
+    fn byte(&mut self) -> Result<u8, CodecError> {
+        let (&byte, rest) = self.input.split_first().ok_or_else(|| CodecError("Unexpected end of encoded value".to_string()))?;
+        self.input = rest;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&[u8], CodecError> {
+        if len > self.input.len() {
+            return error("Unexpected end of encoded value");
+        }
+        let (bytes, rest) = self.input.split_at(len);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    fn unsigned(&mut self) -> Result<u128, CodecError> {
+        let mut value = 0u128;
+        for shift in (0..128).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7F) as u128) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        error("Varint is too long")
+    }
+
+    fn signed(&mut self) -> Result<i128, CodecError> {
+        let zigzag = self.unsigned()?;
+        Ok((zigzag >> 1) as i128 ^ -((zigzag & 1) as i128))
+    }
+
+    fn int<T: TryFrom<i128>>(&mut self) -> Result<T, CodecError> {
+        let value = self.signed()?;
+        T::try_from(value).map_err(|_| CodecError(format!("Integer {} is out of range", value)))
+    }
+
+    fn uint<T: TryFrom<u128>>(&mut self) -> Result<T, CodecError> {
+        let value = self.unsigned()?;
+        T::try_from(value).map_err(|_| CodecError(format!("Integer {} is out of range", value)))
+    }
+
+    /// A count of items that each take at least a byte, checked against what's left so a
+    /// corrupt count can't allocate more than the input could hold.
+    fn len(&mut self) -> Result<usize, CodecError> {
+        let len = self.uint::<usize>()?;
+        if len > self.input.len() {
+            return error("Unexpected end of encoded value");
+        }
+        Ok(len)
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, CodecError> {
+        let len = self.uint::<usize>()?;
+        Ok(self.bytes(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, CodecError> {
+        String::from_utf8(self.byte_string()?).map_err(|_| CodecError("String is not valid UTF-8".to_string()))
+    }
+
+    fn define(&mut self, value: Value) -> Value {
+        self.values.push(value.clone());
+        value
+    }
+
+    fn value(&mut self) -> Result<Value, CodecError> {
+        if self.depth == MAX_DEPTH {
+            return error(format!("Value is nested more than {} deep", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let value = self.contents();
+        self.depth -= 1;
+        value
+    }
+
+    fn contents(&mut self) -> Result<Value, CodecError> {
+        Ok(match self.byte()? {
+            NULL => Value::Null,
+            FALSE => Value::Bool(false),
+            TRUE => Value::Bool(true),
+            I8 => Value::I8(self.int()?),
+            I16 => Value::I16(self.int()?),
+            I32 => Value::I32(self.int()?),
+            I64 => Value::I64(self.int()?),
+            I128 => Value::I128(self.signed()?),
+            U8 => Value::U8(self.uint()?),
+            U16 => Value::U16(self.uint()?),
+            U32 => Value::U32(self.uint()?),
+            U64 => Value::U64(self.uint()?),
+            U128 => Value::U128(self.unsigned()?),
+            F32 => Value::F32(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap())),
+            F64 => Value::F64(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap())),
+            CHAR => {
+                let code = self.uint::<u32>()?;
+                Value::Char(char::from_u32(code).ok_or_else(|| CodecError(format!("{:#x} is not a char", code)))?)
+            }
+            RANGE => {
+                let (start, end, step) = (self.int()?, self.int()?, self.int()?);
+                Value::Range(Range::new(start, end, step).ok_or_else(|| CodecError("Range has a step of zero".to_string()))?)
+            }
+            STR => {
+                let s = self.string()?;
+                self.define(Value::Str(intern(&s)))
+            }
+            BIG_INT => {
+                let digits = self.string()?;
+                let big = digits.parse::<BigInt>().map_err(|_| CodecError(format!("Invalid big integer '{}'", digits)))?;
+                self.define(Value::BigInt(Rc::new(big)))
+            }
+            INT32_ARRAY => {
+                let len = self.len()?;
+                let items = (0..len).map(|_| self.int()).collect::<Result<_, _>>()?;
+                self.define(Value::Int32Array(Gc::new(items)))
+            }
+            FLOAT64_ARRAY => {
+                let len = self.len()?;
+                let items = (0..len).map(|_| Ok(f64::from_le_bytes(self.bytes(8)?.try_into().unwrap()))).collect::<Result<_, _>>()?;
+                self.define(Value::Float64Array(Gc::new(items)))
+            }
+            BYTE_ARRAY => {
+                let bytes = self.byte_string()?;
+                self.define(Value::ByteArray(Gc::new(bytes)))
+            }
+            BYTES => {
+                let bytes = self.byte_string()?;
+                self.define(Value::Bytes(Gc::new(bytes)))
+            }
+            TUPLE => {
+                let len = self.len()?;
+                let items: Vec<Value> = (0..len).map(|_| self.value()).collect::<Result<_, _>>()?;
+                self.define(Value::Tuple(items.into()))
+            }
+            ARRAY => {
+                let items = Gc::new(Vec::new());
+                self.define(Value::Array(items.clone()));
+                for _ in 0..self.len()? {
+                    let item = self.value()?;
+                    items.borrow_mut().push(item);
+                }
+                Value::Array(items)
+            }
+            MAP => {
+                let entries = Gc::new(HashMap::new());
+                self.define(Value::Map(entries.clone()));
+                for _ in 0..self.len()? {
+                    let key = self.string()?;
+                    let value = self.value()?;
+                    entries.borrow_mut().insert(key, value);
+                }
+                Value::Map(entries)
+            }
+            SET => {
+                let set = Gc::new(ValueSet::new());
+                self.define(Value::Set(set.clone()));
+                for _ in 0..self.len()? {
+                    let item = self.value()?;
+                    set.borrow_mut().insert(item).map_err(|e| CodecError(e.to_string()))?;
+                }
+                Value::Set(set)
+            }
+            OBJECT => {
+                let Value::Class(class) = self.value()? else {
+                    return error("Instance without a class");
+                };
+                let instance = Gc::new(Instance::new(class));
+                self.define(Value::Object(instance.clone()));
+                for offset in 0..self.len()? {
+                    let name = match self.byte()? {
+                        0 => None,
+                        _ => Some(self.string()?),
+                    };
+                    let value = self.value()?;
+                    match name {
+                        Some(name) => instance.borrow_mut().set_property(&name, value),
+                        None => instance.borrow_mut().set_at(offset, value),
+                    }
+                }
+                Value::Object(instance)
+            }
+            FUNCTION => {
+                let name = self.string()?;
+                let arity = self.uint()?;
+                let bytecode = self.byte_string()?;
+                let len = self.len()?;
+                let constants = (0..len).map(|_| self.value()).collect::<Result<_, _>>()?;
+                let (lines, _) = decode_from_slice(&self.byte_string()?, standard()).map_err(|e| CodecError(e.to_string()))?;
+                let mut function = Function::new_bytecode(name, arity, bytecode, constants);
+                function.lines = lines;
+                self.define(Value::Function(Rc::new(function)))
+            }
+            CLASS => {
+                let name = self.string()?;
+                let type_id = self.uint()?;
+                let superclass = match self.byte()? {
+                    0 => None,
+                    _ => Some(self.class()?),
+                };
+                let mut class = Class::new(name, type_id, superclass);
+                for _ in 0..self.len()? {
+                    match self.value()? {
+                        Value::Function(method) => class.methods.push(method),
+                        other => return error(format!("Method {:?} is not a function", other)),
+                    }
+                }
+                for _ in 0..self.len()? {
+                    let name = self.string()?;
+                    let offset = self.uint()?;
+                    class.properties.insert(name, offset);
+                }
+                class.extensible = self.byte()? != 0;
+                self.define(Value::Class(Rc::new(class)))
+            }
+            CLOSURE => {
+                let Value::Function(function) = self.value()? else {
+                    return error("Closure without a function");
+                };
+                let mut closure = Closure::new(function);
+                let mut fresh = Vec::new();
+                for _ in 0..self.len()? {
+                    let upvalue = match self.byte()? {
+                        0 => {
+                            let id = self.uint::<usize>()?;
+                            self.upvalues.get(id).cloned().ok_or_else(|| CodecError(format!("No upvalue {}", id)))?
+                        }
+                        _ => {
+                            let upvalue = Rc::new(RefCell::new(Upvalue::Closed(Value::Null)));
+                            self.upvalues.push(upvalue.clone());
+                            fresh.push(upvalue.clone());
+                            upvalue
+                        }
+                    };
+                    closure.upvalues.push(upvalue);
+                }
+                let closure = self.define(Value::Closure(Rc::new(closure)));
+                for upvalue in fresh {
+                    *upvalue.borrow_mut() = match self.byte()? {
+                        0 => Upvalue::Open(self.uint()?),
+                        _ => Upvalue::Closed(self.value()?),
+                    };
+                }
+                closure
+            }
+            NATIVE => {
+                let name = self.string()?;
+                let native = self.natives.and_then(|natives| natives(&name));
+                let native = native.ok_or_else(|| CodecError(format!("No native function '{}'", name)))?;
+                self.define(native)
+            }
+            REF => {
+                let id = self.uint::<usize>()?;
+                self.values.get(id).cloned().ok_or_else(|| CodecError(format!("No value {}", id)))?
+            }
+            tag => return error(format!("Unknown value tag {}", tag)),
+        })
+    }
+
+    fn class(&mut self) -> Result<Rc<Class>, CodecError> {
+        match self.value()? {
+            Value::Class(class) => Ok(class),
+            other => error(format!("Superclass {:?} is not a class", other)),
+        }
+    }
+}
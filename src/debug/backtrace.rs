@@ -0,0 +1,32 @@
+use std::fmt;
+use crate::debug::lines::SourceLocation;
+
+/// The call frames that were active when an error escaped `run()`, innermost first. The
+/// innermost frame points at the failing instruction, the others at their pending call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Backtrace {
+    frames: Vec<SourceLocation>,
+}
+
+impl Backtrace {
+    pub fn new(frames: Vec<SourceLocation>) -> Self {
+        Self { frames }
+    }
+
+    pub fn frames(&self) -> &[SourceLocation] {
+        &self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (depth, frame) in self.frames.iter().enumerate() {
+            writeln!(f, "{:>4}: {}", depth, frame)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use std::collections::{BTreeSet, HashMap};
+use std::rc::Rc;
+use crate::debug::lines::SourceLocation;
+use crate::vm::vm::IrisVM;
+
+/// What `run()` does after a breakpoint handler returns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakpointAction {
+    /// Stop and return `VMError::Breakpoint`.
+    Pause,
+    /// Run the instruction and keep going.
+    Continue,
+}
+
+/// Called when `run()` reaches a breakpoint, before the instruction executes.
+pub type BreakpointHandler = Rc<dyn Fn(&mut IrisVM, &SourceLocation) -> BreakpointAction>;
+
+/// Breakpoints keyed by function name and bytecode offset.
+#[derive(Default)]
+pub struct Breakpoints {
+    offsets: HashMap<String, BTreeSet<usize>>,
+    pub(crate) handler: Option<BreakpointHandler>,
+}
+
+impl Breakpoints {
+    /// Returns `false` if the breakpoint was already set.
+    pub fn insert(&mut self, function: &str, offset: usize) -> bool {
+        self.offsets.entry(function.to_string()).or_default().insert(offset)
+    }
+
+    /// Returns `false` if there was no such breakpoint.
+    pub fn remove(&mut self, function: &str, offset: usize) -> bool {
+        let Some(offsets) = self.offsets.get_mut(function) else { return false };
+        let removed = offsets.remove(&offset);
+        if offsets.is_empty() {
+            self.offsets.remove(function);
+        }
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.offsets.clear();
+    }
+
+    pub fn contains(&self, function: &str, offset: usize) -> bool {
+        self.offsets.get(function).is_some_and(|offsets| offsets.contains(&offset))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Every breakpoint as `(function, offset)`, in no particular function order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> + '_ {
+        self.offsets.iter().flat_map(|(function, offsets)| offsets.iter().map(move |offset| (function.as_str(), *offset)))
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::rc::Rc;
+use crate::disasm::listing;
+use crate::vm::function::Function;
+use crate::vm::opcode::instruction_len;
+
+/// Which instruction offsets of one function have executed.
+#[derive(Debug, Clone)]
+pub struct FunctionCoverage {
+    function: Rc<Function>,
+    /// One bit per byte of bytecode, set for the offsets of executed instructions.
+    bitmap: Vec<u64>,
+}
+
+impl FunctionCoverage {
+    fn new(function: Rc<Function>) -> Self {
+        let len = function.bytecode.as_ref().map_or(0, Vec::len);
+        Self { function, bitmap: vec![0; len.div_ceil(64)] }
+    }
+
+    pub fn function(&self) -> &Rc<Function> {
+        &self.function
+    }
+
+    pub fn name(&self) -> &str {
+        &self.function.name
+    }
+
+    pub fn bitmap(&self) -> &[u64] {
+        &self.bitmap
+    }
+
+    pub fn is_executed(&self, offset: usize) -> bool {
+        self.bitmap.get(offset / 64).is_some_and(|word| word & (1 << (offset % 64)) != 0)
+    }
+
+    /// Offset of every instruction in the function, executed or not.
+    pub fn instructions(&self) -> Vec<usize> {
+        let code = self.function.bytecode.as_deref().unwrap_or_default();
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        while let Some(len) = instruction_len(code, offset) {
+            offsets.push(offset);
+            offset += len;
+        }
+        offsets
+    }
+
+    pub fn executed_instructions(&self) -> usize {
+        self.bitmap.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    /// Whether any instruction on each line ran. Lines come from the function's line table,
+    /// or from its disassembly listing when it has none.
+    pub fn lines(&self) -> BTreeMap<usize, bool> {
+        let mut lines = BTreeMap::new();
+        if self.function.lines.is_empty() {
+            let (_, offsets) = listing(&self.function);
+            for (index, offset) in offsets.into_iter().enumerate() {
+                if let Some(offset) = offset {
+                    lines.insert(index + 1, self.is_executed(offset));
+                }
+            }
+        } else {
+            for offset in self.instructions() {
+                if let Some(line) = self.function.lines.line_at(offset) {
+                    *lines.entry(line as usize).or_insert(false) |= self.is_executed(offset);
+                }
+            }
+        }
+        lines
+    }
+
+    fn mark(&mut self, offset: usize) {
+        if let Some(word) = self.bitmap.get_mut(offset / 64) {
+            *word |= 1 << (offset % 64);
+        }
+    }
+}
+
+/// Executed instructions per function, see `IrisVM::set_coverage_enabled`.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    functions: HashMap<*const Function, FunctionCoverage>,
+}
+
+impl Coverage {
+    pub(crate) fn record(&mut self, function: &Rc<Function>, offset: usize) {
+        self.functions
+            .entry(Rc::as_ptr(function))
+            .or_insert_with(|| FunctionCoverage::new(function.clone()))
+            .mark(offset);
+    }
+
+    /// Every function that ran at least one instruction, by name.
+    pub fn functions(&self) -> Vec<&FunctionCoverage> {
+        let mut functions: Vec<_> = self.functions.values().collect();
+        functions.sort_by(|a, b| a.name().cmp(b.name()));
+        functions
+    }
+
+    /// The first function named `name` that ran.
+    pub fn function(&self, name: &str) -> Option<&FunctionCoverage> {
+        self.functions().into_iter().find(|coverage| coverage.name() == name)
+    }
+
+    /// The report in lcov tracefile format, one record per function with the function name
+    /// as its source file.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::from("TN:\n");
+        for coverage in self.functions() {
+            let lines = coverage.lines();
+            let first = lines.keys().next().copied().unwrap_or(1);
+            let _ = writeln!(out, "SF:{}", coverage.name());
+            let _ = writeln!(out, "FN:{},{}", first, coverage.name());
+            let _ = writeln!(out, "FNDA:{},{}", u8::from(coverage.is_executed(0)), coverage.name());
+            let _ = writeln!(out, "FNF:1\nFNH:{}", u8::from(coverage.is_executed(0)));
+            for (line, hit) in &lines {
+                let _ = writeln!(out, "DA:{},{}", line, u8::from(*hit));
+            }
+            let _ = writeln!(out, "LF:{}\nLH:{}", lines.len(), lines.values().filter(|hit| **hit).count());
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+}
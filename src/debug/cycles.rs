@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::vm::gc::Gc;
+use crate::vm::value::Value;
+
+/// One object in a reference cycle and the references it holds to other members.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CycleMember {
+    /// `array`, `map` or the instance's class name.
+    pub kind: String,
+    /// `(label, member)` per reference into the cycle, labelled `[index]` for array elements,
+    /// `.key` for map entries and `#slot` for instance fields.
+    pub references: Vec<(String, usize)>,
+}
+
+/// A strongly connected group of arrays, maps and instances. Reference counting alone can
+/// never free one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub members: Vec<CycleMember>,
+    /// Whether the stack or globals can still reach the cycle. Unreachable cycles are leaked
+    /// until the garbage collector runs.
+    pub reachable: bool,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = if self.reachable { "reachable" } else { "unreachable" };
+        writeln!(f, "{} cycle of {} object(s):", state, self.members.len())?;
+        for (index, member) in self.members.iter().enumerate() {
+            for (label, target) in &member.references {
+                writeln!(f, "  #{} {} {} -> #{} {}", index, member.kind, label, target, self.members[*target].kind)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Node {
+    value: Value,
+    edges: Vec<(String, usize)>,
+    reachable: bool,
+}
+
+fn identity(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Array(gc) => Some(Gc::addr(gc)),
+        Value::Map(gc) => Some(Gc::addr(gc)),
+        Value::Object(gc) => Some(Gc::addr(gc)),
+        Value::Set(gc) => Some(Gc::addr(gc)),
+        _ => None,
+    }
+}
+
+fn references(value: &Value) -> Vec<(String, Value)> {
+    match value {
+        Value::Array(array) => array.borrow().iter().enumerate().map(|(i, v)| (format!("[{}]", i), v.clone())).collect(),
+        Value::Map(map) => {
+            let mut entries: Vec<_> = map.borrow().iter().map(|(k, v)| (format!(".{}", k), v.clone())).collect();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            entries
+        }
+        Value::Object(instance) => instance.borrow().fields.iter().enumerate().map(|(i, v)| (format!("#{}", i), v.clone())).collect(),
+        Value::Set(set) => set.borrow().iter().enumerate().map(|(i, v)| (format!("{{{}}}", i), v.clone())).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn kind(value: &Value) -> String {
+    match value {
+        Value::Array(_) => "array".to_string(),
+        Value::Map(_) => "map".to_string(),
+        Value::Set(_) => "set".to_string(),
+        Value::Object(instance) => instance.borrow().class.name.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Finds every cycle among the objects reachable from `roots` and `others`. Cycles only
+/// reachable through `others` are reported as unreachable.
+pub fn find_cycles<'a>(roots: impl IntoIterator<Item = &'a Value>, others: impl IntoIterator<Item = Value>) -> Vec<Cycle> {
+    let mut nodes: Vec<Node> = Vec::new();
+    let mut index: HashMap<*const (), usize> = HashMap::new();
+    let starts: Vec<(Value, bool)> = roots.into_iter().map(|v| (v.clone(), true)).chain(others.into_iter().map(|v| (v, false))).collect();
+
+    // Build the object graph breadth first, so roots claim the objects they reach first.
+    for (start, reachable) in starts {
+        let Some(id) = identity(&start) else { continue };
+        if index.contains_key(&id) {
+            continue;
+        }
+        index.insert(id, nodes.len());
+        nodes.push(Node { value: start, edges: Vec::new(), reachable });
+        let mut next = nodes.len() - 1;
+        while next < nodes.len() {
+            let mut edges = Vec::new();
+            for (label, child) in references(&nodes[next].value) {
+                let Some(id) = identity(&child) else { continue };
+                let target = *index.entry(id).or_insert_with(|| {
+                    nodes.push(Node { value: child, edges: Vec::new(), reachable });
+                    nodes.len() - 1
+                });
+                edges.push((label, target));
+            }
+            nodes[next].edges = edges;
+            next += 1;
+        }
+    }
+
+    strongly_connected(&nodes)
+        .into_iter()
+        .filter(|component| component.len() > 1 || nodes[component[0]].edges.iter().any(|(_, t)| *t == component[0]))
+        .map(|component| {
+            let position: HashMap<usize, usize> = component.iter().enumerate().map(|(i, n)| (*n, i)).collect();
+            let members = component.iter().map(|node| CycleMember {
+                kind: kind(&nodes[*node].value),
+                references: nodes[*node].edges.iter()
+                    .filter_map(|(label, target)| position.get(target).map(|t| (label.clone(), *t)))
+                    .collect(),
+            }).collect();
+            Cycle { members, reachable: component.iter().any(|node| nodes[*node].reachable) }
+        })
+        .collect()
+}
+
+/// Tarjan's algorithm, iteratively. Components come out with members in discovery order.
+fn strongly_connected(nodes: &[Node]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut order = vec![UNVISITED; nodes.len()];
+    let mut low = vec![0; nodes.len()];
+    let mut on_stack = vec![false; nodes.len()];
+    let mut stack = Vec::new();
+    let mut components = Vec::new();
+    let mut counter = 0;
+
+    for start in 0..nodes.len() {
+        if order[start] != UNVISITED {
+            continue;
+        }
+        let mut work = vec![(start, 0)];
+        while let Some((node, edge)) = work.pop() {
+            if edge == 0 {
+                order[node] = counter;
+                low[node] = counter;
+                counter += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            }
+            if let Some((_, target)) = nodes[node].edges.get(edge) {
+                work.push((node, edge + 1));
+                if order[*target] == UNVISITED {
+                    work.push((*target, 0));
+                } else if on_stack[*target] {
+                    low[node] = low[node].min(order[*target]);
+                }
+                continue;
+            }
+            if let Some((parent, _)) = work.last() {
+                low[*parent] = low[*parent].min(low[node]);
+            }
+            if low[node] == order[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                component.reverse();
+                components.push(component);
+            }
+        }
+    }
+    components
+}
@@ -0,0 +1,376 @@
+//! Debug Adapter Protocol server, so editors such as VS Code can debug Iris bytecode.
+//!
+//! Every function is shown as a virtual source holding its disassembly; breakpoints and
+//! stack frames refer to lines of that listing. The server drives the VM with `run()` and
+//! `step()` and answers one request at a time, so a running program can't be paused except
+//! by a breakpoint.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use crate::data::bytecode::load_function;
+use crate::data::json::Json;
+use crate::data::module::{load_module, Module};
+use crate::disasm::listing;
+use crate::vm::function::Function;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+/// Variables reference of the globals scope. Frame scopes use their frame id.
+const GLOBALS_REFERENCE: i64 = 1 << 20;
+
+/// A function's disassembly, served as a source with `sourceReference` = index + 1.
+struct DebugSource {
+    name: String,
+    text: String,
+    /// The bytecode offset shown on each line, see `disasm::listing`.
+    offsets: Vec<Option<usize>>,
+}
+
+impl DebugSource {
+    fn new(function: &Function) -> Self {
+        let (text, offsets) = listing(function);
+        Self { name: function.name.clone(), text, offsets }
+    }
+
+    /// The first instruction on or after the 1-based `line`, with the line it is on.
+    fn offset_at_line(&self, line: usize) -> Option<(usize, usize)> {
+        let start = line.checked_sub(1)?;
+        self.offsets.iter().enumerate().skip(start).find_map(|(index, offset)| offset.map(|offset| (offset, index + 1)))
+    }
+
+    /// The 1-based line showing the instruction at `offset`.
+    fn line_of(&self, offset: usize) -> usize {
+        let index = self.offsets.iter().rposition(|line| line.is_some_and(|line| line <= offset));
+        index.map_or(1, |index| index + 1)
+    }
+}
+
+/// Reads one `Content-Length` framed message. Returns `None` at end of input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing Content-Length header"))?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Json::parse(&body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn write_message(writer: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+pub struct DapServer {
+    vm: IrisVM,
+    program: Option<Rc<Function>>,
+    sources: Vec<DebugSource>,
+    stop_on_entry: bool,
+    seq: i64,
+    done: bool,
+}
+
+impl DapServer {
+    pub fn new(vm: IrisVM) -> Self {
+        Self { vm, program: None, sources: Vec::new(), stop_on_entry: false, seq: 0, done: false }
+    }
+
+    /// Debugs `program` without the client having to name a file in `launch`.
+    pub fn with_program(vm: IrisVM, program: Rc<Function>) -> Self {
+        let mut server = Self::new(vm);
+        server.load(program);
+        server
+    }
+
+    /// Debugs a module's entry point, with the module's functions loaded as globals.
+    pub fn with_module(mut vm: IrisVM, module: &Module) -> Result<Self, VMError> {
+        let module = vm.load_module(module)?;
+        let entry = module.entry().ok_or(VMError::InvalidOperand(format!("module '{}' has no entry point", module.name)))?;
+        let mut server = Self::new(vm);
+        server.load_all(entry, &module.functions);
+        Ok(server)
+    }
+
+    pub fn vm(&self) -> &IrisVM {
+        &self.vm
+    }
+
+    /// Answers requests from `reader` until the client disconnects or the input ends.
+    pub fn serve(&mut self, reader: &mut impl BufRead, writer: &mut impl Write) -> io::Result<()> {
+        while !self.done {
+            let Some(request) = read_message(reader)? else { break };
+            for message in self.handle(&request) {
+                write_message(writer, &message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Handles one request, returning its response followed by any events it caused.
+    pub fn handle(&mut self, request: &Json) -> Vec<Json> {
+        let command = request.get("command").and_then(Json::as_str).unwrap_or_default().to_string();
+        let request_seq = request.get("seq").and_then(Json::as_i64).unwrap_or(0);
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Object(Vec::new()));
+        let mut events = Vec::new();
+        let result = self.dispatch(&command, &arguments, &mut events);
+        let mut response = vec![
+            ("type", Json::from("response")),
+            ("request_seq", Json::from(request_seq)),
+            ("command", Json::from(command.as_str())),
+        ];
+        match result {
+            Ok(body) => {
+                response.push(("success", Json::Bool(true)));
+                response.push(("body", body));
+            }
+            Err(message) => {
+                response.push(("success", Json::Bool(false)));
+                response.push(("message", Json::from(message)));
+            }
+        }
+        let mut messages = vec![self.message(response)];
+        messages.extend(events.into_iter().map(|(event, body)| {
+            self.message(vec![("type", Json::from("event")), ("event", Json::from(event)), ("body", body)])
+        }));
+        messages
+    }
+
+    fn message(&mut self, mut fields: Vec<(&str, Json)>) -> Json {
+        self.seq += 1;
+        fields.insert(0, ("seq", Json::from(self.seq)));
+        Json::object(fields)
+    }
+
+    fn dispatch(&mut self, command: &str, arguments: &Json, events: &mut Vec<(&'static str, Json)>) -> Result<Json, String> {
+        let empty = Json::Object(Vec::new());
+        match command {
+            "initialize" => {
+                events.push(("initialized", empty));
+                Ok(Json::object([("supportsConfigurationDoneRequest", Json::Bool(true))]))
+            }
+            "launch" => {
+                if let Some(path) = arguments.get("program").and_then(Json::as_str) {
+                    self.launch(path).map_err(|e| format!("could not load '{}': {}", path, e))?;
+                }
+                let program = self.program.clone().ok_or("no program to debug")?;
+                self.stop_on_entry = arguments.get("stopOnEntry").and_then(Json::as_bool).unwrap_or(false);
+                self.vm.reset();
+                self.vm.push_frame(program, 0).map_err(|e| e.to_string())?;
+                Ok(empty)
+            }
+            "setBreakpoints" => self.set_breakpoints(arguments),
+            "configurationDone" => {
+                if self.stop_on_entry {
+                    events.push(stopped("entry"));
+                } else {
+                    self.resume(events, |vm| vm.run().map(|_| true));
+                }
+                Ok(empty)
+            }
+            "threads" => Ok(Json::object([(
+                "threads",
+                Json::Array(vec![Json::object([("id", Json::from(1i64)), ("name", Json::from("main"))])]),
+            )])),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => {
+                let frame = arguments.get("frameId").and_then(Json::as_i64).unwrap_or(0);
+                let scope = |name: &str, reference: i64| {
+                    Json::object([("name", Json::from(name)), ("variablesReference", Json::from(reference)), ("expensive", Json::Bool(false))])
+                };
+                Ok(Json::object([("scopes", Json::Array(vec![scope("Locals", frame), scope("Globals", GLOBALS_REFERENCE)]))]))
+            }
+            "variables" => {
+                let reference = arguments.get("variablesReference").and_then(Json::as_i64).unwrap_or(0);
+                Ok(Json::object([("variables", Json::Array(self.variables(reference)))]))
+            }
+            "source" => {
+                let reference = arguments.get("sourceReference").and_then(Json::as_i64).unwrap_or(0);
+                let source = usize::try_from(reference - 1).ok().and_then(|index| self.sources.get(index)).ok_or("unknown source")?;
+                Ok(Json::object([("content", Json::from(source.text.as_str()))]))
+            }
+            "continue" => {
+                self.resume(events, |vm| {
+                    // Get off a breakpoint a step stopped on, or run() would pause there again.
+                    let location = vm.current_location();
+                    if location.is_some_and(|at| vm.breakpoints().contains(&at.function, at.offset)) && vm.step()?.finished {
+                        return Ok(true);
+                    }
+                    vm.run().map(|_| true)
+                });
+                Ok(Json::object([("allThreadsContinued", Json::Bool(true))]))
+            }
+            "stepIn" => {
+                self.resume(events, |vm| vm.step().map(|outcome| outcome.finished));
+                Ok(empty)
+            }
+            "next" | "stepOut" => {
+                // Keep stepping until control is back in this frame (next) or its caller (stepOut).
+                let depth = self.vm.call_stack().frames().len();
+                let target = if command == "next" { depth } else { depth.saturating_sub(1) };
+                self.resume(events, move |vm| loop {
+                    let outcome = vm.step()?;
+                    if outcome.finished || outcome.depth <= target {
+                        return Ok(outcome.finished);
+                    }
+                });
+                Ok(empty)
+            }
+            "disconnect" => {
+                self.done = true;
+                Ok(empty)
+            }
+            _ => Err(format!("unsupported request '{}'", command)),
+        }
+    }
+
+    fn launch(&mut self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if path.ends_with(".icm") {
+            let module = load_module(path)?;
+            let module = self.vm.load_module(&module)?;
+            let entry = module.entry().ok_or("module has no entry point")?;
+            self.load_all(entry, &module.functions);
+        } else {
+            self.load(Rc::new(load_function(path)?));
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, program: Rc<Function>) {
+        self.load_all(program, &[]);
+    }
+
+    /// Makes a source for `program`, `others` and every function nested in their constants.
+    fn load_all(&mut self, program: Rc<Function>, others: &[Rc<Function>]) {
+        self.sources.clear();
+        let mut pending: Vec<Rc<Function>> = others.iter().rev().cloned().collect();
+        pending.push(program.clone());
+        while let Some(function) = pending.pop() {
+            if function.bytecode.is_none() || self.sources.iter().any(|source| source.name == function.name) {
+                continue;
+            }
+            self.sources.push(DebugSource::new(&function));
+            for constant in function.constants.iter().rev() {
+                match constant {
+                    Value::Function(nested) => pending.push(nested.clone()),
+                    Value::Class(class) => pending.extend(class.methods.iter().rev().cloned()),
+                    _ => {}
+                }
+            }
+        }
+        self.program = Some(program);
+    }
+
+    fn source_index(&self, source: &Json) -> Option<usize> {
+        if let Some(reference) = source.get("sourceReference").and_then(Json::as_i64).filter(|reference| *reference > 0) {
+            return usize::try_from(reference - 1).ok().filter(|index| *index < self.sources.len());
+        }
+        let name = source.get("name").and_then(Json::as_str)?;
+        self.sources.iter().position(|source| source.name == name)
+    }
+
+    fn source_json(&self, index: usize) -> Json {
+        Json::object([("name", Json::from(self.sources[index].name.as_str())), ("sourceReference", Json::from(index + 1))])
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Json) -> Result<Json, String> {
+        let index = arguments.get("source").and_then(|source| self.source_index(source)).ok_or("unknown source")?;
+        let name = self.sources[index].name.clone();
+        let existing: Vec<usize> = self.vm.breakpoints().iter().filter(|(function, _)| *function == name).map(|(_, offset)| offset).collect();
+        for offset in existing {
+            self.vm.remove_breakpoint(&name, offset);
+        }
+        let requested = arguments.get("breakpoints").and_then(Json::as_array).unwrap_or_default();
+        let breakpoints = requested.iter().map(|breakpoint| {
+            let line = breakpoint.get("line").and_then(Json::as_i64).unwrap_or(0);
+            match usize::try_from(line).ok().and_then(|line| self.sources[index].offset_at_line(line)) {
+                Some((offset, line)) => {
+                    self.vm.add_breakpoint(&name, offset);
+                    Json::object([("verified", Json::Bool(true)), ("line", Json::from(line)), ("source", self.source_json(index))])
+                }
+                None => Json::object([("verified", Json::Bool(false)), ("line", Json::from(line))]),
+            }
+        }).collect();
+        Ok(Json::object([("breakpoints", Json::Array(breakpoints))]))
+    }
+
+    fn stack_trace(&self) -> Json {
+        let call_stack = self.vm.call_stack();
+        let frames: Vec<Json> = call_stack.frames().iter().enumerate().map(|(depth, location)| {
+            let mut frame = vec![
+                ("id", Json::from(depth + 1)),
+                ("name", Json::from(location.function.as_str())),
+                ("column", Json::from(1i64)),
+                ("instructionPointerReference", Json::from(location.offset.to_string())),
+            ];
+            match self.sources.iter().position(|source| source.name == location.function) {
+                Some(index) => {
+                    frame.push(("line", Json::from(self.sources[index].line_of(location.offset))));
+                    frame.push(("source", self.source_json(index)));
+                }
+                None => frame.push(("line", Json::from(0i64))),
+            }
+            Json::object(frame)
+        }).collect();
+        Json::object([("totalFrames", Json::from(frames.len())), ("stackFrames", Json::Array(frames))])
+    }
+
+    fn variables(&self, reference: i64) -> Vec<Json> {
+        let variable = |name: String, value: &Value| {
+            Json::object([("name", Json::from(name)), ("value", Json::from(format!("{:?}", value))), ("variablesReference", Json::from(0i64))])
+        };
+        if reference == GLOBALS_REFERENCE {
+            let names: HashMap<usize, &str> = self.vm.global_names().iter().map(|(name, slot)| (*slot, name.as_str())).collect();
+            return self.vm.globals().iter().enumerate()
+                .filter(|(_, value)| !matches!(value, Value::Null))
+                .map(|(slot, value)| variable(names.get(&slot).map_or_else(|| format!("global {}", slot), |name| name.to_string()), value))
+                .collect();
+        }
+        let frame = usize::try_from(reference - 1).ok().and_then(|depth| self.vm.frames().nth(depth));
+        let slots = frame.map(|frame| frame.locals()).unwrap_or_default();
+        slots.iter().enumerate().map(|(slot, value)| variable(slot.to_string(), value)).collect()
+    }
+
+    /// Runs `action` and reports where the program stopped.
+    fn resume(&mut self, events: &mut Vec<(&'static str, Json)>, action: impl FnOnce(&mut IrisVM) -> Result<bool, VMError>) {
+        match action(&mut self.vm) {
+            Ok(false) => events.push(stopped("step")),
+            Ok(true) => {
+                if let Some(result) = self.vm.stack.last() {
+                    events.push(output(format!("{:?}\n", result)));
+                }
+                events.push(("terminated", Json::Object(Vec::new())));
+            }
+            Err(VMError::Breakpoint(_)) => events.push(stopped("breakpoint")),
+            Err(error) => {
+                let message = match error.backtrace() {
+                    Some(backtrace) => format!("{}\nbacktrace:\n{}", error, backtrace),
+                    None => format!("{}\n", error),
+                };
+                events.push(output(message));
+                events.push(("terminated", Json::Object(Vec::new())));
+            }
+        }
+    }
+}
+
+fn stopped(reason: &str) -> (&'static str, Json) {
+    ("stopped", Json::object([("reason", Json::from(reason)), ("threadId", Json::from(1i64)), ("allThreadsStopped", Json::Bool(true))]))
+}
+
+fn output(text: String) -> (&'static str, Json) {
+    ("output", Json::object([("category", Json::from("stderr")), ("output", Json::from(text))]))
+}
@@ -0,0 +1,89 @@
+use std::fmt;
+use serde::{Serialize, Deserialize};
+
+/// A position in the source a piece of bytecode was compiled from. `column` is 0 when
+/// only the line is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    pub fn line(line: u32) -> Self {
+        Self { line, column: 0 }
+    }
+}
+
+/// Maps bytecode offsets to source spans. Each entry covers every offset from its own up
+/// to the next entry's, so a run of instructions from the same line costs one entry.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineTable {
+    entries: Vec<(u32, Span)>,
+}
+
+impl LineTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records that code from `offset` on came from `span`. Offsets must not go backwards.
+    pub fn record(&mut self, offset: usize, span: Span) {
+        let offset = offset as u32;
+        match self.entries.last_mut() {
+            Some((_, last)) if *last == span => {}
+            Some((start, last)) if *start == offset => *last = span,
+            Some((start, _)) => {
+                assert!(*start < offset, "line table offsets must increase");
+                self.entries.push((offset, span));
+            }
+            None => self.entries.push((offset, span)),
+        }
+    }
+
+    pub fn span_at(&self, offset: usize) -> Option<Span> {
+        let index = self.entries.partition_point(|(start, _)| *start as usize <= offset);
+        index.checked_sub(1).map(|index| self.entries[index].1)
+    }
+
+    pub fn line_at(&self, offset: usize) -> Option<u32> {
+        self.span_at(offset).map(|span| span.line)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (usize, Span)> + '_ {
+        self.entries.iter().map(|(offset, span)| (*offset as usize, *span))
+    }
+
+    /// Moves every entry through `map`, for passes that rewrite the code. `map` must not
+    /// reorder offsets; entries that land on the same offset keep the last span.
+    pub fn remap(&mut self, map: impl Fn(usize) -> usize) {
+        let mut remapped = LineTable::new();
+        for (offset, span) in self.entries() {
+            remapped.record(map(offset), span);
+        }
+        *self = remapped;
+    }
+}
+
+/// Where a runtime error happened: the function, the offset of the failing instruction,
+/// and its source span if the function carries a line table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub function: String,
+    pub offset: usize,
+    pub span: Option<Span>,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(Span { line, column: 0 }) => write!(f, "in {} at line {}", self.function, line),
+            Some(Span { line, column }) => write!(f, "in {} at line {}:{}", self.function, line, column),
+            None => write!(f, "in {} at offset {}", self.function, self.offset),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::{BTreeSet, HashMap};
+use serde::{Serialize, Deserialize};
+use crate::vm::function::Function;
+use crate::vm::opcode::{instruction_len, successors, OpCode};
+
+/// Bytecode offsets `[start, end)` over which a local slot holds a value that is still going to be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LiveRange {
+    pub slot: u16,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// Per-function liveness of local slots, compressed to one range per contiguous live stretch.
+/// Lets a debugger tell live locals apart from dead slots and operand-stack temporaries.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LivenessTable {
+    ranges: Vec<LiveRange>,
+}
+
+struct Instruction {
+    offset: usize,
+    len: usize,
+    uses: Option<u16>,
+    defs: Option<u16>,
+    successors: Vec<usize>,
+}
+
+impl LivenessTable {
+    /// Returns `None` if the function has no bytecode or the bytecode can't be decoded.
+    pub fn compute(function: &Function) -> Option<Self> {
+        Self::compute_code(function.bytecode.as_ref()?)
+    }
+
+    pub fn compute_code(code: &[u8]) -> Option<Self> {
+        let instructions = decode(code)?;
+
+        // Classic backward dataflow: live_in = uses ∪ (live_out − defs).
+        let mut live_in: Vec<BTreeSet<u16>> = vec![BTreeSet::new(); instructions.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (index, instruction) in instructions.iter().enumerate().rev() {
+                let mut live: BTreeSet<u16> = instruction.successors.iter()
+                    .flat_map(|&succ| live_in[succ].iter().copied())
+                    .collect();
+                if let Some(slot) = instruction.defs {
+                    live.remove(&slot);
+                }
+                if let Some(slot) = instruction.uses {
+                    live.insert(slot);
+                }
+                if live != live_in[index] {
+                    live_in[index] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut ranges: Vec<LiveRange> = Vec::new();
+        let mut open: HashMap<u16, LiveRange> = HashMap::new();
+        for (index, instruction) in instructions.iter().enumerate() {
+            let start = instruction.offset as u32;
+            let end = (instruction.offset + instruction.len) as u32;
+            open.retain(|slot, range| {
+                if live_in[index].contains(slot) {
+                    return true;
+                }
+                ranges.push(*range);
+                false
+            });
+            for &slot in &live_in[index] {
+                open.entry(slot).or_insert(LiveRange { slot, start, end }).end = end;
+            }
+        }
+        ranges.extend(open.into_values());
+        ranges.sort_by_key(|range| (range.slot, range.start));
+        Some(Self { ranges })
+    }
+
+    pub fn ranges(&self) -> &[LiveRange] {
+        &self.ranges
+    }
+
+    pub fn is_live(&self, slot: u16, ip: usize) -> bool {
+        self.ranges.iter().any(|range| range.slot == slot && (range.start as usize..range.end as usize).contains(&ip))
+    }
+
+    /// Local slots holding a live value when execution is paused before the instruction at `ip`.
+    pub fn live_locals_at(&self, ip: usize) -> Vec<u16> {
+        let mut slots: Vec<u16> = self.ranges.iter()
+            .filter(|range| (range.start as usize..range.end as usize).contains(&ip))
+            .map(|range| range.slot)
+            .collect();
+        slots.dedup();
+        slots
+    }
+}
+
+fn local_slot(code: &[u8], offset: usize, opcode: OpCode) -> u16 {
+    match opcode {
+        OpCode::GetLocalVariable8 | OpCode::SetLocalVariable8 => code[offset + 1] as u16,
+        _ => u16::from_be_bytes([code[offset + 1], code[offset + 2]]),
+    }
+}
+
+fn decode(code: &[u8]) -> Option<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    let mut index_of = HashMap::new();
+    // Instructions between BeginTryBlock and its EndTryBlock may transfer control to the handler.
+    let mut handlers: Vec<usize> = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let len = instruction_len(code, offset)?;
+        let opcode = OpCode::decode(code, offset)?;
+        let (uses, defs) = match opcode {
+            OpCode::GetLocalVariable8 | OpCode::GetLocalVariable16 => (Some(local_slot(code, offset, opcode)), None),
+            OpCode::SetLocalVariable8 | OpCode::SetLocalVariable16 => (None, Some(local_slot(code, offset, opcode))),
+            _ => (None, None),
+        };
+        let mut targets = successors(code, offset)?;
+        targets.extend(handlers.iter().copied());
+        match opcode {
+            OpCode::BeginTryBlock => handlers.push(offset + len + code[offset + 1] as usize),
+            OpCode::EndTryBlock => {
+                handlers.pop();
+            }
+            _ => {}
+        }
+        index_of.insert(offset, instructions.len());
+        instructions.push(Instruction { offset, len, uses, defs, successors: targets });
+        offset += len;
+    }
+
+    // Targets that don't land on an instruction (including the end of the code) leave the function.
+    for instruction in &mut instructions {
+        instruction.successors = instruction.successors.iter()
+            .filter_map(|target| index_of.get(target).copied())
+            .collect();
+    }
+    Some(instructions)
+}
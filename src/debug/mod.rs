@@ -0,0 +1,9 @@
+pub mod liveness;
+pub mod lines;
+pub mod backtrace;
+pub mod breakpoints;
+pub mod dap;
+pub mod profiler;
+pub mod stats;
+pub mod coverage;
+pub mod cycles;
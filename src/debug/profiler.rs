@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Samples the call stack every `interval` instructions, see `IrisVM::start_profiling`.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    interval: u64,
+    countdown: u64,
+    samples: u64,
+    /// Sample counts per call stack, outermost function first.
+    stacks: HashMap<Vec<String>, u64>,
+    /// Sample counts per innermost function and offset.
+    locations: HashMap<(String, usize), u64>,
+}
+
+impl Profile {
+    pub fn new(interval: u64) -> Self {
+        let interval = interval.max(1);
+        Self { interval, countdown: interval, ..Self::default() }
+    }
+
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn samples(&self) -> u64 {
+        self.samples
+    }
+
+    /// Counts one instruction and reports whether it should be sampled.
+    pub(crate) fn tick(&mut self) -> bool {
+        self.countdown -= 1;
+        if self.countdown > 0 {
+            return false;
+        }
+        self.countdown = self.interval;
+        true
+    }
+
+    /// Records one sample of `frames`, innermost first.
+    pub(crate) fn record(&mut self, frames: &[(String, usize)]) {
+        let Some((function, offset)) = frames.first() else { return };
+        self.samples += 1;
+        *self.locations.entry((function.clone(), *offset)).or_default() += 1;
+        let stack = frames.iter().rev().map(|(function, _)| function.clone()).collect();
+        *self.stacks.entry(stack).or_default() += 1;
+    }
+
+    /// Samples per function: `(name, self, total)`, where `self` counts samples with the
+    /// function innermost and `total` those with it anywhere on the stack. Hottest first.
+    pub fn functions(&self) -> Vec<(&str, u64, u64)> {
+        let mut counts: HashMap<&str, (u64, u64)> = HashMap::new();
+        for (stack, count) in &self.stacks {
+            let mut seen: Vec<&str> = Vec::new();
+            for function in stack {
+                if !seen.contains(&function.as_str()) {
+                    seen.push(function);
+                    counts.entry(function).or_default().1 += count;
+                }
+            }
+            if let Some(innermost) = stack.last() {
+                counts.entry(innermost).or_default().0 += count;
+            }
+        }
+        let mut functions: Vec<_> = counts.into_iter().map(|(name, (own, total))| (name, own, total)).collect();
+        functions.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(b.0)));
+        functions
+    }
+
+    /// Samples per `(function, offset)`, hottest first.
+    pub fn hot_spots(&self) -> Vec<(&str, usize, u64)> {
+        let mut spots: Vec<_> = self.locations.iter().map(|((function, offset), count)| (function.as_str(), *offset, *count)).collect();
+        spots.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)).then(a.1.cmp(&b.1)));
+        spots
+    }
+
+    /// The samples in collapsed-stack format (`main;inner 42` per line), as read by
+    /// flamegraph tools.
+    pub fn collapsed(&self) -> String {
+        let mut lines: Vec<String> = self.stacks.iter().map(|(stack, count)| format!("{} {}", stack.join(";"), count)).collect();
+        lines.sort();
+        lines.iter().map(|line| format!("{}\n", line)).collect()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} sample(s), one every {} instruction(s)", self.samples, self.interval)?;
+        if self.samples == 0 {
+            return Ok(());
+        }
+        let percent = |count: u64| count as f64 * 100.0 / self.samples as f64;
+        writeln!(f, "{:>7} {:>7}  function", "self", "total")?;
+        for (name, own, total) in self.functions() {
+            writeln!(f, "{:>6.1}% {:>6.1}%  {}", percent(own), percent(total), name)?;
+        }
+        writeln!(f, "{:>7}  location", "self")?;
+        for (name, offset, count) in self.hot_spots().into_iter().take(10) {
+            writeln!(f, "{:>6.1}%  {}@{:04}", percent(count), name, offset)?;
+        }
+        Ok(())
+    }
+}
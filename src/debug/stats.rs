@@ -0,0 +1,87 @@
+use std::fmt;
+use std::time::Duration;
+use crate::vm::opcode::{is_custom_opcode, OpCode};
+
+/// Slots for the one-byte opcodes followed by the extended page.
+const SLOTS: usize = 512;
+
+/// Execution counts and cumulative time per opcode, see `IrisVM::set_stats_enabled`.
+/// Time covers the instruction's dispatch, including any native it calls.
+#[derive(Debug, Clone)]
+pub struct ExecutionStats {
+    counts: Box<[u64; SLOTS]>,
+    times: Box<[Duration; SLOTS]>,
+}
+
+impl Default for ExecutionStats {
+    fn default() -> Self {
+        Self { counts: Box::new([0; SLOTS]), times: Box::new([Duration::ZERO; SLOTS]) }
+    }
+}
+
+/// Slot for an opcode number as reported by `StepOutcome::opcode`.
+fn slot(number: u16) -> usize {
+    if number > 0xFF { 0x100 | (number & 0xFF) as usize } else { number as usize }
+}
+
+fn number(slot: usize) -> u16 {
+    if slot > 0xFF { 0xFF00 | (slot & 0xFF) as u16 } else { slot as u16 }
+}
+
+impl ExecutionStats {
+    pub(crate) fn record(&mut self, number: u16, elapsed: Duration) {
+        self.counts[slot(number)] += 1;
+        self.times[slot(number)] += elapsed;
+    }
+
+    pub fn count(&self, opcode: OpCode) -> u64 {
+        self.counts[slot(opcode as u16)]
+    }
+
+    pub fn time(&self, opcode: OpCode) -> Duration {
+        self.times[slot(opcode as u16)]
+    }
+
+    /// Count and time for a raw opcode byte, for custom opcodes.
+    pub fn for_byte(&self, byte: u8) -> (u64, Duration) {
+        (self.counts[byte as usize], self.times[byte as usize])
+    }
+
+    pub fn total_instructions(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.times.iter().sum()
+    }
+
+    /// `(opcode, count, time)` for every opcode that ran, most executed first. Opcodes are
+    /// numbered as in `StepOutcome::opcode`.
+    pub fn entries(&self) -> Vec<(u16, u64, Duration)> {
+        let mut entries: Vec<_> = (0..SLOTS)
+            .filter(|slot| self.counts[*slot] > 0)
+            .map(|slot| (number(slot), self.counts[slot], self.times[slot]))
+            .collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        entries
+    }
+}
+
+fn opcode_name(number: u16) -> String {
+    match number {
+        0x100.. => format!("{:?}", OpCode::extended(number as u8)),
+        byte if is_custom_opcode(byte as u8) => format!("custom {:#04x}", byte),
+        byte => format!("{:?}", OpCode::from(byte as u8)),
+    }
+}
+
+impl fmt::Display for ExecutionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>12} {:>12} {:>10}  opcode", "count", "time", "avg")?;
+        for (number, count, time) in self.entries() {
+            let average = time / count.min(u32::MAX as u64) as u32;
+            writeln!(f, "{:>12} {:>12.3?} {:>10.1?}  {}", count, time, average, opcode_name(number))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,288 @@
+//! Disassembler producing the syntax accepted by `iris_vm::asm`. Byte offsets, jump
+//! destinations and resolved constants are added as trailing comments.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use crate::vm::function::Function;
+use crate::vm::opcode::{instruction_len, is_custom_opcode, successors, OpCode};
+use crate::vm::value::Value;
+
+const COMMENT_COLUMN: usize = 44;
+
+pub fn disassemble(function: &Function) -> String {
+    listing(function).0
+}
+
+/// The disassembly of `function` together with the bytecode offset each of its lines
+/// shows, `None` for directives, labels and blank lines.
+pub fn listing(function: &Function) -> (String, Vec<Option<usize>>) {
+    let mut marks = Vec::new();
+    let out = render(function, &mut marks);
+    let mut marks = marks.into_iter().peekable();
+    let mut start = 0;
+    let offsets = out.lines().map(|text| {
+        let offset = marks.next_if(|(position, _)| *position == start).map(|(_, offset)| offset);
+        start += text.len() + 1;
+        offset
+    }).collect();
+    (out, offsets)
+}
+
+/// Renders the disassembly, noting the position in the output where each instruction starts.
+fn render(function: &Function, marks: &mut Vec<(usize, usize)>) -> String {
+    let mut out = String::new();
+    let Some(code) = &function.bytecode else {
+        let _ = writeln!(out, "; native function {}/{}", function.name, function.arity);
+        return out;
+    };
+
+    let _ = writeln!(out, ".function {} {}", function.name, function.arity);
+    for (index, constant) in function.constants.iter().enumerate() {
+        match constant_literal(constant) {
+            Some(literal) => { let _ = writeln!(out, ".const k{} {}", index, literal); }
+            None => { let _ = writeln!(out, "; k{} = {}", index, describe(constant)); }
+        }
+    }
+    out.push('\n');
+
+    let labels = labels(code);
+    let mut offset = 0;
+    let mut span = None;
+    while offset < code.len() {
+        if let Some(current) = function.lines.span_at(offset).filter(|current| Some(*current) != span) {
+            match current.column {
+                0 => { let _ = writeln!(out, "{:8}.line {}", "", current.line); }
+                column => { let _ = writeln!(out, "{:8}.line {} {}", "", current.line, column); }
+            }
+            span = Some(current);
+        }
+        let Some(len) = instruction_len(code, offset) else {
+            let bytes = code[offset..].iter().map(|b| format!("{:#04x}", b)).collect::<Vec<_>>().join(" ");
+            line(&mut out, &labels, offset, format!(".byte {}", bytes), "truncated instruction".to_string());
+            offset = code.len();
+            break;
+        };
+        let (text, note) = instruction(function, code, offset, len, &labels);
+        marks.push((out.len(), offset));
+        line(&mut out, &labels, offset, text, note);
+        offset += len;
+    }
+    if let Some(label) = labels.get(&offset) {
+        let _ = writeln!(out, "{}:", label);
+    }
+    out
+}
+
+/// One instruction of `function` in assembler syntax, with jump targets shown as offsets
+/// and resolved constants as a trailing comment.
+pub fn disassemble_instruction(function: &Function, offset: usize) -> Option<String> {
+    let code = function.bytecode.as_ref()?;
+    let len = instruction_len(code, offset)?;
+    let labels = jump_targets(code, offset).into_iter().map(|target| (target, format!("{:04}", target))).collect();
+    let (text, note) = instruction(function, code, offset, len, &labels);
+    Some(if note.is_empty() || note.starts_with("->") { text } else { format!("{} ; {}", text, note) })
+}
+
+fn line(out: &mut String, labels: &BTreeMap<usize, String>, offset: usize, text: String, note: String) {
+    let label = labels.get(&offset).map(|label| format!("{}:", label)).unwrap_or_default();
+    let text = format!("{:<8}{}", label, text);
+    let comment = if note.is_empty() { format!("{:04}", offset) } else { format!("{:04} {}", offset, note) };
+    let _ = writeln!(out, "{:<width$}; {}", text, comment, width = COMMENT_COLUMN);
+}
+
+/// Offsets that are reached by a jump, switch or try handler, named in address order.
+fn labels(code: &[u8]) -> BTreeMap<usize, String> {
+    let mut targets = BTreeMap::new();
+    let mut offset = 0;
+    while let Some(len) = instruction_len(code, offset) {
+        for target in jump_targets(code, offset) {
+            targets.insert(target, String::new());
+        }
+        offset += len;
+    }
+    // Only boundaries can carry a label; anything else is rendered as raw bytes.
+    let boundaries = boundaries(code);
+    targets.retain(|target, _| boundaries.contains(target));
+    for (index, name) in targets.values_mut().enumerate() {
+        *name = format!("L{}", index);
+    }
+    targets
+}
+
+fn boundaries(code: &[u8]) -> Vec<usize> {
+    let mut boundaries = Vec::new();
+    let mut offset = 0;
+    while let Some(len) = instruction_len(code, offset) {
+        boundaries.push(offset);
+        offset += len;
+    }
+    boundaries.push(offset);
+    boundaries
+}
+
+fn jump_targets(code: &[u8], offset: usize) -> Vec<usize> {
+    use OpCode::*;
+    let Some(targets) = successors(code, offset) else { return Vec::new() };
+    if is_custom_opcode(code[offset]) {
+        return Vec::new();
+    }
+    match OpCode::decode(code, offset).unwrap_or(Unknown) {
+        UnconditionalJump | ShortJump | LoopJump | TableSwitch | LookupSwitch | RangeSwitch => targets,
+        JumpIfTrue | JumpIfFalse | JumpIfNull | JumpIfNonNull | CompareAndBranchEqualInt32
+        | CompareAndBranchNotEqualInt32 | CompareAndBranchLessThanInt32 | CompareAndBranchGreaterThanInt32
+        | BeginTryBlock | FinallyBlock | JumpIfLocalsNotLessInt32 => targets[1..].to_vec(),
+        _ => Vec::new(),
+    }
+}
+
+fn instruction(function: &Function, code: &[u8], offset: usize, len: usize, labels: &BTreeMap<usize, String>) -> (String, String) {
+    use OpCode::*;
+    let bytes = &code[offset..offset + len];
+    let raw = || bytes.iter().map(|b| format!("{:#04x}", b)).collect::<Vec<_>>().join(" ");
+    let u16_at = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]);
+    let i32_at = |at: usize| i32::from_be_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+
+    if is_custom_opcode(bytes[0]) {
+        return (format!(".byte {}", raw()), "custom opcode".to_string());
+    }
+    let opcode = OpCode::decode(bytes, 0).unwrap_or(Unknown);
+    if opcode == Unknown {
+        return (format!(".byte {}", raw()), "unknown opcode".to_string());
+    }
+
+    let targets = jump_targets(code, offset);
+    if targets.iter().any(|target| !labels.contains_key(target)) {
+        return (format!(".byte {}", raw()), format!("{:?} to a non-instruction offset", opcode));
+    }
+    let label = |target: &usize| labels[target].clone();
+    let mnemonic = format!("{:?}", opcode);
+
+    let (operands, note) = match opcode {
+        UnconditionalJump | ShortJump | LoopJump | JumpIfTrue | JumpIfFalse | JumpIfNull | JumpIfNonNull
+        | CompareAndBranchEqualInt32 | CompareAndBranchNotEqualInt32 | CompareAndBranchLessThanInt32
+        | CompareAndBranchGreaterThanInt32 | BeginTryBlock | FinallyBlock => {
+            (label(&targets[0]), format!("-> {:04}", targets[0]))
+        }
+        TableSwitch => {
+            let cases = targets[1..].iter().map(label).collect::<Vec<_>>().join(", ");
+            (format!("{}, {}, {}, {}", label(&targets[0]), i32_at(3), i32_at(7), cases), String::new())
+        }
+        LookupSwitch => {
+            let cases = (0..u16_at(3) as usize)
+                .map(|case| format!("{}, {}", i32_at(5 + case * 6), label(&targets[case + 1])))
+                .collect::<Vec<_>>();
+            (format!("{}, {}", label(&targets[0]), cases.join(", ")), String::new())
+        }
+        RangeSwitch => {
+            let cases = (0..u16_at(3) as usize)
+                .map(|case| format!("{}, {}, {}", i32_at(5 + case * 10), i32_at(9 + case * 10), label(&targets[case + 1])))
+                .collect::<Vec<_>>();
+            (format!("{}, {}", label(&targets[0]), cases.join(", ")), String::new())
+        }
+        PushConstant8 | DefineClass8 | GetObjectField8 | SetObjectField8 | GetObjectProperty8 | SetObjectProperty8 => {
+            constant_operand(function, bytes[1] as usize)
+        }
+        PushConstant16 | DefineClass16 | GetObjectField16 | SetObjectField16 | GetObjectProperty16
+        | SetObjectProperty16 | GetPropertyWithInlineCache | SetPropertyWithInlineCache | LoadMethodHandle
+        | CatchException => {
+            constant_operand(function, u16_at(1) as usize)
+        }
+        LoadModule | ImportSymbol => constant_operand(function, u16_at(2) as usize),
+        LoadImmediateI8 => ((bytes[1] as i8).to_string(), String::new()),
+        LoadImmediateI16 => ((u16_at(1) as i16).to_string(), String::new()),
+        LoadImmediateI32 => (i32_at(1).to_string(), String::new()),
+        LoadImmediateI64 => {
+            let value = i64::from_be_bytes(bytes[1..9].try_into().unwrap());
+            (value.to_string(), String::new())
+        }
+        LoadImmediateF32 => (format!("{:?}", f32::from_be_bytes(bytes[1..5].try_into().unwrap())), String::new()),
+        LoadImmediateF64 => (format!("{:?}", f64::from_be_bytes(bytes[1..9].try_into().unwrap())), String::new()),
+        AddInt32WithConstant | AddInt64WithConstant | MultiplyInt32WithConstant | MultiplyInt64WithConstant => {
+            ((bytes[1] as i8).to_string(), String::new())
+        }
+        InvokeMethod8 => (format!("{}, {}", bytes[1], bytes[2]), String::new()),
+        InvokeMethod16 => (format!("{}, {}", u16_at(1), bytes[3]), String::new()),
+        CallWithInlineCache | MegamorphicMethodCall => {
+            let (name, note) = constant_operand(function, u16_at(1) as usize);
+            (format!("{}, {}", name, bytes[3]), note)
+        }
+        CaptureUpvalue => (format!("{}, {}", bytes[2], bytes[3]), String::new()),
+        AddLocalInt32WithConstant => (format!("{}, {}", bytes[2], bytes[3] as i8), String::new()),
+        JumpIfLocalsNotLessInt32 => {
+            (format!("{}, {}, {}", bytes[2], bytes[3], label(&targets[0])), format!("-> {:04}", targets[0]))
+        }
+        _ => match len - opcode.opcode_len() {
+            1 => (bytes[opcode.opcode_len()].to_string(), String::new()),
+            2 => (u16_at(opcode.opcode_len()).to_string(), String::new()),
+            _ => (String::new(), String::new()),
+        },
+    };
+
+    let text = if operands.is_empty() { mnemonic } else { format!("{} {}", mnemonic, operands) };
+    (text, note)
+}
+
+fn constant_operand(function: &Function, index: usize) -> (String, String) {
+    match function.constants.get(index) {
+        Some(constant) if constant_literal(constant).is_some() => (format!("k{}", index), describe(constant)),
+        Some(constant) => (index.to_string(), describe(constant)),
+        None => (index.to_string(), "missing constant".to_string()),
+    }
+}
+
+/// `.const` syntax for constants the assembler can express.
+fn constant_literal(value: &Value) -> Option<String> {
+    let literal = match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => format!("bool {}", b),
+        Value::I8(v) => format!("i8 {}", v),
+        Value::I16(v) => format!("i16 {}", v),
+        Value::I32(v) => format!("i32 {}", v),
+        Value::I64(v) => format!("i64 {}", v),
+        Value::I128(v) => format!("i128 {}", v),
+        Value::U8(v) => format!("u8 {}", v),
+        Value::U16(v) => format!("u16 {}", v),
+        Value::U32(v) => format!("u32 {}", v),
+        Value::U64(v) => format!("u64 {}", v),
+        Value::U128(v) => format!("u128 {}", v),
+        Value::BigInt(v) => format!("bigint {}", v),
+        Value::F32(v) => format!("f32 {:?}", v),
+        Value::F64(v) => format!("f64 {:?}", v),
+        Value::Str(s) => format!("str {}", quote(s)),
+        Value::Char(c) => format!("char {}", quote(&c.to_string())),
+        _ => return None,
+    };
+    Some(literal)
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Str(s) => quote(s),
+        Value::Function(function) => format!("<fn {}/{}>", function.name, function.arity),
+        Value::Class(class) => format!("<class {}>", class.name),
+        Value::Object(_) => "<object>".to_string(),
+        Value::Array(array) => format!("<array len {}>", array.borrow().len()),
+        Value::Map(map) => format!("<map len {}>", map.borrow().len()),
+        Value::Bytes(bytes) => format!("<bytes len {}>", bytes.borrow().len()),
+        Value::Tuple(elements) => format!("<tuple len {}>", elements.len()),
+        Value::Set(set) => format!("<set len {}>", set.borrow().len()),
+        Value::NativeFunction(_) => "<native fn>".to_string(),
+        other => constant_literal(other).unwrap_or_default(),
+    }
+}
+
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            '\0' => quoted.push_str("\\0"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -1,2 +1,10 @@
 pub mod vm;
-pub mod data;
\ No newline at end of file
+pub mod asm;
+pub mod disasm;
+pub mod optimize;
+pub mod data;
+pub mod debug;
+pub mod pool;
+pub mod actors;
+pub mod stdlib;
+pub mod backend;
\ No newline at end of file
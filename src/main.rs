@@ -1,26 +1,311 @@
-use iris_vm::vm::chunk::{Chunk, ChunkWriter};
-use iris_vm::vm::function::Function;
-use iris_vm::vm::vm::IrisVM;
+use std::process::ExitCode;
 use std::rc::Rc;
-use iris_vm::data::bytecode::{load_function, save_function};
-use iris_vm::vm::opcode::OpCode::{PrintTopOfStack, PushConstant8};
-use iris_vm::vm::value::Value;
+use std::time::Instant;
+use iris_vm::backend::wasm::compile_module;
+use iris_vm::data::bytecode::load_function;
+use iris_vm::data::module::{load_module, Module};
+use iris_vm::data::package::{load_package, pack_manifest, save_package, Package};
+use iris_vm::debug::dap::DapServer;
+use iris_vm::disasm::disassemble;
+use iris_vm::optimize::{peephole_function, quicken_function, PeepholeStats};
+use iris_vm::stdlib::{package as package_natives, process};
+use iris_vm::vm::capability::Capability;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::import::FsResolver;
+use iris_vm::vm::register::translate_function;
+use iris_vm::vm::verifier::verify;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+const USAGE: &str = "\
+usage: iris <command> [options] <file> [-- <program arguments>]
+
+commands:
+  run      execute a function (.ic), a module's entry point (.icm) or a package (.icpkg)
+  disasm   print the disassembly of every function in the file
+  check    verify the bytecode without running it
+  dap      debug the file over the Debug Adapter Protocol on stdin/stdout
+  wasm     compile every function to a WebAssembly module written next to the file
+  pack     bundle the modules and resources a package manifest (.json) lists into a
+           .icpkg written next to it
+
+options:
+  --allow-env  let the program read environment variables
+  --jit        run with the JIT compiler (not available in this build)
+  --optimize   run the peephole optimizer, quicken every function and translate what it
+               can to register form after loading
+  --profile    sample the call stack while running and print a profile to stderr
+  --stats      print load, verification and execution statistics
+  --trace      log every executed instruction and the top of the stack to stderr
+  --verify     verify bytecode before running it (always on for check)
+
+Programs run with the process natives (iris_vm::stdlib::process) and may read their
+arguments and exit with a status. LoadModule finds a.b in a/b.icm next to the file.";
+
+struct Options {
+    command: String,
+    path: String,
+    /// Everything after `--`, for the program.
+    program_args: Vec<String>,
+    allow_env: bool,
+    jit: bool,
+    optimize: bool,
+    profile: bool,
+    stats: bool,
+    trace: bool,
+    verify: bool,
+}
 
-fn main() {
-    let mut chunk = Chunk::new();
+/// Instructions between call stack samples for `--profile`. Prime, so samples don't keep
+/// landing on the same instruction of a loop.
+const PROFILE_INTERVAL: u64 = 97;
 
-    let content = chunk.add_constant(Value::Str("Hello, World!".to_string()));
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut positional = Vec::new();
+    let mut options = Options { command: String::new(), path: String::new(), program_args: Vec::new(), allow_env: false, jit: false, optimize: false, profile: false, stats: false, trace: false, verify: false };
+    let (args, program_args) = match args.iter().position(|arg| arg == "--") {
+        Some(separator) => (&args[..separator], &args[separator + 1..]),
+        None => (args, &[][..]),
+    };
+    options.program_args = program_args.to_vec();
+    for arg in args {
+        match arg.as_str() {
+            "--allow-env" => options.allow_env = true,
+            "--jit" => options.jit = true,
+            "--optimize" => options.optimize = true,
+            "--profile" => options.profile = true,
+            "--stats" => options.stats = true,
+            "--trace" => options.trace = true,
+            "--verify" => options.verify = true,
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            _ => positional.push(arg.clone()),
+        }
+    }
+    match positional.as_slice() {
+        [command, path] => {
+            options.command = command.clone();
+            options.path = path.clone();
+            Ok(options)
+        }
+        _ => Err("expected a command and a file".to_string()),
+    }
+}
 
-    chunk.write(PushConstant8); chunk.write(content);
-    chunk.write(PrintTopOfStack);
+/// Loads a package, or wraps a module in a package with it as the entry module, or a single
+/// function file in a module with that function as entry point.
+fn load(path: &str) -> Result<Package, Box<dyn std::error::Error>> {
+    if path.ends_with(".icpkg") {
+        let package = load_package(path)?;
+        if package.entry.is_none() {
+            return Err(format!("package '{}' has no entry module", package.name).into());
+        }
+        return Ok(package);
+    }
+    let module = if path.ends_with(".icm") {
+        let module = load_module(path)?;
+        if module.entry().is_none() {
+            return Err(format!("module '{}' has no entry point", module.name).into());
+        }
+        module
+    } else {
+        let function = load_function(path)?;
+        let mut module = Module::new(path.to_string());
+        module.set_entry_point(&function.name);
+        module.add_function(function);
+        module
+    };
+    let mut package = Package::new(&module.name, "");
+    package.set_entry(&module.name);
+    package.add_module(module);
+    Ok(package)
+}
 
-    let function = Rc::new(Function::new_bytecode(String::from("test_func"), 1, chunk.code, chunk.constants));
+fn functions(package: &Package) -> Vec<Rc<Function>> {
+    package.modules.iter().flat_map(|module| module.functions.iter().cloned()).collect()
+}
 
-    save_function(&function, "func1.ic").unwrap();
+fn pack(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let package = pack_manifest(path)?;
+    let output = std::path::Path::new(path).with_extension("icpkg");
+    save_package(&package, &output.to_string_lossy())?;
+    eprintln!("wrote {} ({} module(s), {} resource(s))", output.display(), package.modules.len(), package.resources.len());
+    Ok(())
+}
 
-    let loaded_function = Rc::new(load_function("func1.ic").unwrap());
+/// Optimizes every function the module owns outright; shared functions are left as loaded.
+/// Functions that fail verification aren't quickened, and `check` or `run` reports them.
+/// Returns the peephole stats, superinstructions and functions translated to register form.
+fn optimize(module: &mut Module) -> (PeepholeStats, usize, usize) {
+    let mut total = PeepholeStats::default();
+    let (mut quickened, mut translated) = (0, 0);
+    for function in module.functions.iter_mut() {
+        if let Some(function) = Rc::get_mut(function) {
+            let stats = peephole_function(function);
+            total.fused += stats.fused;
+            total.removed_pairs += stats.removed_pairs;
+            total.threaded_jumps += stats.threaded_jumps;
+        }
+        quickened += quicken_function(function).unwrap_or(0);
+        translated += translate_function(function).unwrap_or(false) as usize;
+    }
+    (total, quickened, translated)
+}
 
+fn check(functions: &[Rc<Function>], stats: bool) -> Result<(), Box<dyn std::error::Error>> {
+    for function in functions {
+        let verified = verify(function).map_err(|e| format!("{}: {}", function.name, e))?;
+        if stats {
+            eprintln!("{}: {} bytes, {} constants, max stack depth {}",
+                function.name, function.bytecode.as_ref().map_or(0, Vec::len), function.constants.len(),
+                verified.max_stack_depth());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the status the program exited with, if it called `process_exit`.
+fn run(package: &Package, options: &Options) -> Result<Option<i32>, Box<dyn std::error::Error>> {
+    if options.jit && !IrisVM::jit_available() {
+        return Err("this build of iris has no JIT support".into());
+    }
     let mut vm = IrisVM::new();
-    let _ = vm.push_frame(loaded_function, 0);
-    let _ = vm.run();
-}
\ No newline at end of file
+    vm.set_require_verification(options.verify);
+    process::register(&mut vm);
+    package_natives::register(&mut vm);
+    let directory = std::path::Path::new(&options.path).parent().unwrap_or(std::path::Path::new(""));
+    vm.add_module_resolver(FsResolver::new(directory));
+    vm.set_args(options.program_args.clone());
+    vm.grant(Capability::Args);
+    vm.grant(Capability::Exit);
+    if options.allow_env {
+        vm.grant(Capability::Env);
+    }
+    // A lone function isn't defined as a global.
+    let entry = if options.path.ends_with(".ic") {
+        package.entry_module().and_then(Module::entry)
+    } else {
+        vm.load_package(package)?
+    };
+    let entry = entry.ok_or("no entry point")?;
+    let started = Instant::now();
+    vm.push_frame(entry.clone(), 0)?;
+    if options.profile {
+        vm.start_profiling(PROFILE_INTERVAL);
+    }
+    vm.set_stats_enabled(options.stats);
+    let result = if options.trace { vm.run_traced(&mut std::io::stderr().lock()) } else { vm.run() };
+    if let Some(profile) = vm.stop_profiling() {
+        eprint!("{}", profile);
+    }
+    if let Err(VMError::Exit(status)) = result.as_ref().map_err(VMError::root) {
+        return Ok(Some(*status));
+    }
+    result.map_err(|e| match e.backtrace() {
+        Some(backtrace) => format!("{}\nbacktrace:\n{}", e, backtrace.to_string().trim_end()),
+        None => e.to_string(),
+    })?;
+    if options.stats {
+        eprintln!("ran {} in {:?}, {} value(s) left on the stack, {} global(s)",
+            entry.name, started.elapsed(), vm.stack.len(), vm.globals().len());
+        let high_water = vm.high_water();
+        eprintln!("stack high water: {} value(s), {} frame(s)", high_water.stack, high_water.frames);
+        if let Some(stats) = vm.stats() {
+            eprint!("{}", stats);
+        }
+    }
+    if let Some(result) = vm.stack.last() {
+        println!("{:?}", result);
+    }
+    Ok(None)
+}
+
+fn debug(module: &Module) -> Result<(), Box<dyn std::error::Error>> {
+    let mut server = DapServer::with_module(IrisVM::new(), module)?;
+    server.serve(&mut std::io::stdin().lock(), &mut std::io::stdout().lock())?;
+    Ok(())
+}
+
+/// Writes `path` with a `.wasm` extension; every function must have a register form.
+fn wasm(functions: &[Rc<Function>], path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let output = std::path::Path::new(path).with_extension("wasm");
+    let bytes = compile_module(functions)?;
+    std::fs::write(&output, &bytes)?;
+    eprintln!("wrote {} ({} bytes)", output.display(), bytes.len());
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("iris: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let started = Instant::now();
+    if options.command == "pack" {
+        return match pack(&options.path) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("iris: cannot pack {}: {}", options.path, e);
+                ExitCode::FAILURE
+            }
+        };
+    }
+    let mut package = match load(&options.path) {
+        Ok(package) => package,
+        Err(e) => {
+            eprintln!("iris: cannot load {}: {}", options.path, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if options.stats {
+        eprintln!("loaded {} function(s) from {} in {:?}", functions(&package).len(), options.path, started.elapsed());
+    }
+    if options.optimize {
+        let (mut stats, mut quickened, mut translated) = (PeepholeStats::default(), 0, 0);
+        for module in package.modules.iter_mut() {
+            let (module_stats, module_quickened, module_translated) = optimize(module);
+            stats.fused += module_stats.fused;
+            stats.removed_pairs += module_stats.removed_pairs;
+            stats.threaded_jumps += module_stats.threaded_jumps;
+            quickened += module_quickened;
+            translated += module_translated;
+        }
+        if options.stats {
+            eprintln!("peephole: {} fused, {} push/pop pair(s) removed, {} jump(s) threaded",
+                stats.fused, stats.removed_pairs, stats.threaded_jumps);
+            eprintln!("quicken: {} superinstruction(s)", quickened);
+            eprintln!("register form: {} function(s)", translated);
+        }
+    }
+
+    let result = match options.command.as_str() {
+        "run" => match run(&package, &options) {
+            // Statuses wrap around as they do on Unix.
+            Ok(Some(status)) => return ExitCode::from(status as u8),
+            Ok(None) => Ok(()),
+            Err(e) => Err(e),
+        },
+        "check" => check(&functions(&package), options.stats),
+        "dap" => debug(package.entry_module().expect("loaded packages have an entry module")),
+        "wasm" => wasm(&functions(&package), &options.path),
+        "disasm" => {
+            let listings: Vec<String> = functions(&package).iter().map(|f| disassemble(f)).collect();
+            print!("{}", listings.join("\n"));
+            Ok(())
+        }
+        other => {
+            eprintln!("iris: unknown command '{}'\n\n{}", other, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("iris: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
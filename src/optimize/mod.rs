@@ -0,0 +1,5 @@
+pub mod peephole;
+pub mod quicken;
+
+pub use peephole::{peephole, peephole_code, peephole_function, PeepholeStats};
+pub use quicken::quicken_function;
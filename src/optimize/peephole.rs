@@ -0,0 +1,299 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use crate::debug::lines::LineTable;
+use crate::vm::chunk::Chunk;
+use crate::vm::function::Function;
+use crate::vm::opcode::{instruction_len, is_custom_opcode, OpCode};
+use crate::vm::value::Value;
+
+/// What a `peephole` run changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PeepholeStats {
+    pub fused: usize,
+    pub removed_pairs: usize,
+    pub threaded_jumps: usize,
+}
+
+impl PeepholeStats {
+    pub fn total(&self) -> usize {
+        self.fused + self.removed_pairs + self.threaded_jumps
+    }
+}
+
+/// How a jump operand is encoded, relative to the instruction that holds it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// u8 forward from the next instruction.
+    ForwardU8,
+    /// i8 from the next instruction.
+    RelativeI8,
+    /// u16 forward from the next instruction.
+    ForwardU16,
+    /// u16 backward from the next instruction.
+    BackwardU16,
+    /// u16 forward from the start of this instruction (switch tables).
+    SwitchU16,
+}
+
+pub(super) struct JumpOperand {
+    at: usize,
+    encoding: Encoding,
+}
+
+pub(super) struct Instruction {
+    pub(super) offset: usize,
+    pub(super) bytes: Vec<u8>,
+    pub(super) jumps: Vec<JumpOperand>,
+}
+
+pub fn peephole(chunk: &mut Chunk) -> PeepholeStats {
+    optimize(&mut chunk.code, &chunk.constants, &mut chunk.lines)
+}
+
+pub fn peephole_function(function: &mut Function) -> PeepholeStats {
+    match function.bytecode.as_mut() {
+        Some(code) => optimize(code, &function.constants, &mut function.lines),
+        None => PeepholeStats::default(),
+    }
+}
+
+/// Fuses a small integer `PushConstant8`/`LoadImmediateI8` followed by `AddInt32` or
+/// `MultiplyInt32` into the `*WithConstant` form, drops pushes that are immediately popped,
+/// and points jumps that land on an unconditional jump straight at its destination. Code
+/// that doesn't decode is left untouched.
+pub fn peephole_code(code: &mut Vec<u8>, constants: &[Value]) -> PeepholeStats {
+    optimize(code, constants, &mut LineTable::new())
+}
+
+fn optimize(code: &mut Vec<u8>, constants: &[Value], lines: &mut LineTable) -> PeepholeStats {
+    let mut stats = PeepholeStats::default();
+    loop {
+        let Some(instructions) = decode(code) else { return stats };
+        let targets: BTreeSet<usize> = instructions.iter()
+            .flat_map(|insn| insn.jumps.iter().map(|jump| read_target(&insn.bytes, insn.offset, jump)))
+            .collect();
+
+        let mut rewritten = Vec::with_capacity(instructions.len());
+        let mut changed = false;
+        let mut iter = instructions.into_iter().peekable();
+        while let Some(insn) = iter.next() {
+            let next = iter.peek().filter(|next| !targets.contains(&next.offset));
+            if let Some(next) = next {
+                if is_pure_push(&insn.bytes) && next.bytes == [OpCode::PopStack as u8] {
+                    iter.next();
+                    stats.removed_pairs += 1;
+                    changed = true;
+                    continue;
+                }
+                if let Some(fused) = fuse(&insn.bytes, &next.bytes, constants) {
+                    iter.next();
+                    rewritten.push(Instruction { offset: insn.offset, bytes: fused, jumps: Vec::new() });
+                    stats.fused += 1;
+                    changed = true;
+                    continue;
+                }
+            }
+            rewritten.push(insn);
+        }
+        if !changed {
+            break;
+        }
+        let (relaid, new_offsets) = relayout(code.len(), rewritten);
+        lines.remap(|old| remap(&new_offsets, old));
+        *code = relaid;
+    }
+    stats.threaded_jumps += thread_jumps(code);
+    stats
+}
+
+pub(super) fn decode(code: &[u8]) -> Option<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while offset < code.len() {
+        let len = instruction_len(code, offset)?;
+        let bytes = code[offset..offset + len].to_vec();
+        let jumps = jump_operands(&bytes);
+        instructions.push(Instruction { offset, bytes, jumps });
+        offset += len;
+    }
+    Some(instructions)
+}
+
+pub(super) fn jump_operands(bytes: &[u8]) -> Vec<JumpOperand> {
+    use OpCode::*;
+    if is_custom_opcode(bytes[0]) {
+        return Vec::new();
+    }
+    let jump = |at, encoding| JumpOperand { at, encoding };
+    let u16_at = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]) as usize;
+    match OpCode::decode(bytes, 0).unwrap_or(Unknown) {
+        UnconditionalJump | BeginTryBlock | FinallyBlock => vec![jump(1, Encoding::ForwardU8)],
+        ShortJump => vec![jump(1, Encoding::RelativeI8)],
+        LoopJump => vec![jump(1, Encoding::BackwardU16)],
+        JumpIfTrue | JumpIfFalse | JumpIfNull | JumpIfNonNull | CompareAndBranchEqualInt32
+        | CompareAndBranchNotEqualInt32 | CompareAndBranchLessThanInt32 | CompareAndBranchGreaterThanInt32 => {
+            vec![jump(1, Encoding::ForwardU16)]
+        }
+        JumpIfLocalsNotLessInt32 => vec![jump(4, Encoding::ForwardU16)],
+        TableSwitch => {
+            let cases = (11..bytes.len()).step_by(2).map(|at| jump(at, Encoding::SwitchU16));
+            std::iter::once(jump(1, Encoding::SwitchU16)).chain(cases).collect()
+        }
+        LookupSwitch => {
+            let cases = (0..u16_at(3)).map(|case| jump(5 + case * 6 + 4, Encoding::SwitchU16));
+            std::iter::once(jump(1, Encoding::SwitchU16)).chain(cases).collect()
+        }
+        RangeSwitch => {
+            let cases = (0..u16_at(3)).map(|case| jump(5 + case * 10 + 8, Encoding::SwitchU16));
+            std::iter::once(jump(1, Encoding::SwitchU16)).chain(cases).collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+pub(super) fn read_target(bytes: &[u8], offset: usize, jump: &JumpOperand) -> usize {
+    let next = offset + bytes.len();
+    let u16_at = |at: usize| u16::from_be_bytes([bytes[at], bytes[at + 1]]) as usize;
+    match jump.encoding {
+        Encoding::ForwardU8 => next + bytes[jump.at] as usize,
+        Encoding::RelativeI8 => next.wrapping_add_signed(bytes[jump.at] as i8 as isize),
+        Encoding::ForwardU16 => next + u16_at(jump.at),
+        Encoding::BackwardU16 => next.wrapping_sub(u16_at(jump.at)),
+        Encoding::SwitchU16 => offset + u16_at(jump.at),
+    }
+}
+
+/// Writes `target` into the operand, returning false if the encoding can't reach it.
+fn write_target(bytes: &mut [u8], offset: usize, jump: &JumpOperand, target: usize) -> bool {
+    let next = (offset + bytes.len()) as isize;
+    let target = target as isize;
+    let value = match jump.encoding {
+        Encoding::ForwardU8 => target - next,
+        Encoding::RelativeI8 => target - next,
+        Encoding::ForwardU16 => target - next,
+        Encoding::BackwardU16 => next - target,
+        Encoding::SwitchU16 => target - offset as isize,
+    };
+    match jump.encoding {
+        Encoding::ForwardU8 if (0..=u8::MAX as isize).contains(&value) => bytes[jump.at] = value as u8,
+        Encoding::RelativeI8 if (i8::MIN as isize..=i8::MAX as isize).contains(&value) => {
+            bytes[jump.at] = value as i8 as u8
+        }
+        Encoding::ForwardU16 | Encoding::BackwardU16 | Encoding::SwitchU16
+            if (0..=u16::MAX as isize).contains(&value) =>
+        {
+            bytes[jump.at..jump.at + 2].copy_from_slice(&(value as u16).to_be_bytes())
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// Lays the surviving instructions out again and re-encodes every jump. Targets of removed
+/// instructions move to the next surviving one. Code only shrinks, so every jump still fits.
+pub(super) fn relayout(old_len: usize, instructions: Vec<Instruction>) -> (Vec<u8>, BTreeMap<usize, usize>) {
+    let mut new_offsets = BTreeMap::new();
+    let mut offset = 0;
+    for insn in &instructions {
+        new_offsets.insert(insn.offset, offset);
+        offset += insn.bytes.len();
+    }
+    new_offsets.insert(old_len, offset);
+
+    let mut code = Vec::with_capacity(offset);
+    for mut insn in instructions {
+        let new_offset = code.len();
+        for jump in &insn.jumps {
+            let target = read_target(&insn.bytes, insn.offset, jump);
+            let written = write_target(&mut insn.bytes, new_offset, jump, remap(&new_offsets, target));
+            debug_assert!(written, "jump grew while shrinking code");
+        }
+        code.extend(insn.bytes);
+    }
+    (code, new_offsets)
+}
+
+/// Removed instructions map to whatever follows them. The end of the code is always mapped.
+pub(super) fn remap(new_offsets: &BTreeMap<usize, usize>, old: usize) -> usize {
+    new_offsets.range(old..).next().map_or(old, |(_, new)| *new)
+}
+
+/// Retargets jumps whose destination is an unconditional jump, following chains.
+fn thread_jumps(code: &mut [u8]) -> usize {
+    let Some(instructions) = decode(code) else { return 0 };
+    let gotos: HashMap<usize, usize> = instructions.iter()
+        .filter(|insn| {
+            !is_custom_opcode(insn.bytes[0])
+                && matches!(OpCode::from(insn.bytes[0]), OpCode::UnconditionalJump | OpCode::ShortJump | OpCode::LoopJump)
+        })
+        .map(|insn| (insn.offset, read_target(&insn.bytes, insn.offset, &insn.jumps[0])))
+        .collect();
+
+    let mut threaded = 0;
+    for mut insn in instructions {
+        let mut changed = false;
+        for jump in &insn.jumps {
+            let original = read_target(&insn.bytes, insn.offset, jump);
+            let mut target = original;
+            let mut steps = 0;
+            while let Some(next) = gotos.get(&target) {
+                if *next == target || steps > gotos.len() {
+                    break;
+                }
+                target = *next;
+                steps += 1;
+            }
+            if target != original && write_target(&mut insn.bytes, insn.offset, jump, target) {
+                threaded += 1;
+                changed = true;
+            }
+        }
+        if changed {
+            code[insn.offset..insn.offset + insn.bytes.len()].copy_from_slice(&insn.bytes);
+        }
+    }
+    threaded
+}
+
+fn is_pure_push(bytes: &[u8]) -> bool {
+    use OpCode::*;
+    !is_custom_opcode(bytes[0])
+        && matches!(
+            OpCode::from(bytes[0]),
+            PushConstant8 | PushConstant16 | PushNull | PushTrue | PushFalse | LoadImmediateI8 | LoadImmediateI16
+                | LoadImmediateI32 | LoadImmediateI64 | LoadImmediateF32 | LoadImmediateF64 | GetLocalVariable8
+                | GetLocalVariable16
+        )
+}
+
+/// `AddInt32` only accepts two I32s, so it fuses with I32 constants alone; `MultiplyInt32`
+/// widens any integer operand and fuses with all of them.
+pub(super) fn fuse(push: &[u8], op: &[u8], constants: &[Value]) -> Option<Vec<u8>> {
+    if op.len() != 1 || is_custom_opcode(op[0]) || is_custom_opcode(push[0]) {
+        return None;
+    }
+    let pushed = match OpCode::from(push[0]) {
+        OpCode::LoadImmediateI8 => Value::I8(push[1] as i8),
+        OpCode::PushConstant8 => constants.get(push[1] as usize)?.clone(),
+        _ => return None,
+    };
+    let (fused, value) = match (OpCode::from(op[0]), pushed) {
+        (OpCode::AddInt32, Value::I32(v)) => (OpCode::AddInt32WithConstant, v as i128),
+        (OpCode::MultiplyInt32, value) => {
+            let value = match value {
+                Value::I8(v) => v as i128,
+                Value::I16(v) => v as i128,
+                Value::I32(v) => v as i128,
+                Value::I64(v) => v as i128,
+                Value::U8(v) => v as i128,
+                Value::U16(v) => v as i128,
+                Value::U32(v) => v as i128,
+                Value::U64(v) => v as i128,
+                _ => return None,
+            };
+            (OpCode::MultiplyInt32WithConstant, value)
+        }
+        _ => return None,
+    };
+    let constant = i8::try_from(value).ok()?;
+    Some(vec![fused as u8, constant as u8])
+}
@@ -0,0 +1,111 @@
+//! Quickening: rewrites verified bytecode to use superinstructions for the sequences that
+//! dominate tight loops, so they take one dispatch instead of three or four.
+//!
+//! - `GetLocalVariable8 s; AddInt32WithConstant c; SetLocalVariable8 s`, or the same with
+//!   the constant still pushed separately, becomes `AddLocalInt32WithConstant s, c`.
+//! - `GetLocalVariable8 a; GetLocalVariable8 b; LessThanInt32; JumpIfFalse` becomes
+//!   `JumpIfLocalsNotLessInt32 a, b, target`.
+//!
+//! Sequences something jumps into the middle of are left alone. The superinstructions fail
+//! with the same errors as the instructions they replace.
+
+use std::collections::BTreeSet;
+use std::rc::Rc;
+use crate::debug::lines::LineTable;
+use crate::optimize::peephole::{decode, fuse, jump_operands, read_target, relayout, remap, Instruction};
+use crate::vm::function::Function;
+use crate::vm::opcode::{is_custom_opcode, OpCode};
+use crate::vm::value::Value;
+use crate::vm::verifier::{verify, VerifyError};
+
+/// Verifies `function` and quickens it, returning how many superinstructions it now has.
+/// A function that is shared, or has no bytecode, is left as it is.
+pub fn quicken_function(function: &mut Rc<Function>) -> Result<usize, VerifyError> {
+    verify(function)?;
+    let Some(function) = Rc::get_mut(function) else { return Ok(0) };
+    match function.bytecode.as_mut() {
+        Some(code) => Ok(quicken(code, &function.constants, &mut function.lines)),
+        None => Ok(0),
+    }
+}
+
+fn quicken(code: &mut Vec<u8>, constants: &[Value], lines: &mut LineTable) -> usize {
+    let Some(instructions) = decode(code) else { return 0 };
+    let targets: BTreeSet<usize> = instructions.iter()
+        .flat_map(|insn| insn.jumps.iter().map(|jump| read_target(&insn.bytes, insn.offset, jump)))
+        .collect();
+
+    let mut rewritten = Vec::with_capacity(instructions.len());
+    let mut quickened = 0;
+    let mut at = 0;
+    while at < instructions.len() {
+        let window = &instructions[at..];
+        let superinstruction = add_local(window, constants).or_else(|| locals_less_branch(window))
+            .filter(|(len, _)| window[1..*len].iter().all(|insn| !targets.contains(&insn.offset)));
+        let insn = &window[0];
+        match superinstruction {
+            Some((len, bytes)) => {
+                let jumps = jump_operands(&bytes);
+                rewritten.push(Instruction { offset: insn.offset, bytes, jumps });
+                quickened += 1;
+                at += len;
+            }
+            None => {
+                rewritten.push(Instruction { offset: insn.offset, bytes: insn.bytes.clone(), jumps: jump_operands(&insn.bytes) });
+                at += 1;
+            }
+        }
+    }
+    if quickened > 0 {
+        let (relaid, new_offsets) = relayout(code.len(), rewritten);
+        lines.remap(|old| remap(&new_offsets, old));
+        *code = relaid;
+    }
+    quickened
+}
+
+fn opcode(insn: &Instruction) -> OpCode {
+    if is_custom_opcode(insn.bytes[0]) {
+        return OpCode::Unknown;
+    }
+    OpCode::decode(&insn.bytes, 0).unwrap_or(OpCode::Unknown)
+}
+
+fn extended(opcode: OpCode) -> [u8; 2] {
+    (opcode as u16).to_be_bytes()
+}
+
+/// `AddLocalInt32WithConstant` and how many instructions it replaces.
+fn add_local(window: &[Instruction], constants: &[Value]) -> Option<(usize, Vec<u8>)> {
+    let [get, rest @ ..] = window else { return None };
+    if opcode(get) != OpCode::GetLocalVariable8 {
+        return None;
+    }
+    let (len, constant, set) = match rest {
+        [add, set, ..] if opcode(add) == OpCode::AddInt32WithConstant => (3, add.bytes[1], set),
+        [push, add, set, ..] => {
+            let fused = fuse(&push.bytes, &add.bytes, constants)?;
+            (4, (fused[0] == OpCode::AddInt32WithConstant as u8).then_some(fused[1])?, set)
+        }
+        _ => return None,
+    };
+    if opcode(set) != OpCode::SetLocalVariable8 || set.bytes[1] != get.bytes[1] {
+        return None;
+    }
+    let [prefix, byte] = extended(OpCode::AddLocalInt32WithConstant);
+    Some((len, vec![prefix, byte, get.bytes[1], constant]))
+}
+
+/// `JumpIfLocalsNotLessInt32`, with its target still relative to the original layout.
+fn locals_less_branch(window: &[Instruction]) -> Option<(usize, Vec<u8>)> {
+    let [a, b, less, jump, ..] = window else { return None };
+    let matches = opcode(a) == OpCode::GetLocalVariable8 && opcode(b) == OpCode::GetLocalVariable8
+        && opcode(less) == OpCode::LessThanInt32 && opcode(jump) == OpCode::JumpIfFalse;
+    if !matches {
+        return None;
+    }
+    let target = read_target(&jump.bytes, jump.offset, &jump.jumps[0]);
+    let [prefix, byte] = extended(OpCode::JumpIfLocalsNotLessInt32);
+    let [hi, lo] = u16::try_from(target.checked_sub(a.offset + 6)?).ok()?.to_be_bytes();
+    Some((4, vec![prefix, byte, a.bytes[1], b.bytes[1], hi, lo]))
+}
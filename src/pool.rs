@@ -0,0 +1,285 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+use crate::vm::closure::{Closure, Upvalue, UpvalueRef};
+use crate::vm::gc::Gc;
+use crate::vm::object::Instance;
+use crate::vm::set::ValueSet;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+/// Health thresholds for pooled VMs. A VM crossing any of them is dropped on return
+/// and replaced with a freshly warmed one.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub size: usize,
+    pub max_uses: Option<usize>,
+    pub max_errors: Option<usize>,
+    /// Bytes the stack and globals may still reach when a VM is returned, see
+    /// `IrisVM::recount_heap`.
+    pub max_heap_bytes: Option<usize>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            max_uses: None,
+            max_errors: Some(8),
+            max_heap_bytes: Some(64 << 20),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    pub created: usize,
+    pub checkouts: usize,
+    pub returns: usize,
+    pub recycled: usize,
+    pub idle: usize,
+    pub in_use: usize,
+}
+
+struct PooledVm {
+    vm: IrisVM,
+    /// A copy of the warm globals no script has touched, copied again on every return.
+    baseline_globals: Vec<Value>,
+    uses: usize,
+    errors: usize,
+    heap_bytes: usize,
+}
+
+/// Keeps a set of pre-warmed VMs built by a factory (stdlib loaded, modules linked, ...)
+/// and hands them out through guards that restore the warm state on return.
+pub struct VmPool {
+    factory: Box<dyn Fn() -> IrisVM>,
+    config: PoolConfig,
+    idle: RefCell<Vec<PooledVm>>,
+    metrics: RefCell<PoolMetrics>,
+}
+
+impl VmPool {
+    pub fn new(config: PoolConfig, factory: impl Fn() -> IrisVM + 'static) -> Self {
+        let pool = Self {
+            factory: Box::new(factory),
+            config,
+            idle: RefCell::new(Vec::new()),
+            metrics: RefCell::new(PoolMetrics::default()),
+        };
+        for _ in 0..pool.config.size {
+            let vm = pool.warm();
+            pool.idle.borrow_mut().push(vm);
+        }
+        pool
+    }
+
+    pub fn config(&self) -> &PoolConfig {
+        &self.config
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let mut metrics = self.metrics.borrow().clone();
+        metrics.idle = self.idle.borrow().len();
+        metrics
+    }
+
+    /// Checks out an idle VM, warming a new one if the pool is exhausted.
+    pub fn get(&self) -> PooledVmGuard<'_> {
+        let pooled = self.idle.borrow_mut().pop().unwrap_or_else(|| self.warm());
+        let mut metrics = self.metrics.borrow_mut();
+        metrics.checkouts += 1;
+        metrics.in_use += 1;
+        PooledVmGuard { pool: self, pooled: Some(pooled) }
+    }
+
+    fn warm(&self) -> PooledVm {
+        let vm = (self.factory)();
+        let baseline_globals = deep_copy_all(vm.globals());
+        self.metrics.borrow_mut().created += 1;
+        PooledVm { vm, baseline_globals, uses: 0, errors: 0, heap_bytes: 0 }
+    }
+
+    fn is_healthy(&self, pooled: &PooledVm) -> bool {
+        let within = |value: usize, limit: Option<usize>| limit.is_none_or(|limit| value < limit);
+        within(pooled.uses, self.config.max_uses)
+            && within(pooled.errors, self.config.max_errors)
+            && within(pooled.heap_bytes, self.config.max_heap_bytes)
+    }
+
+    fn check_in(&self, mut pooled: PooledVm) {
+        {
+            let mut metrics = self.metrics.borrow_mut();
+            metrics.returns += 1;
+            metrics.in_use -= 1;
+        }
+        pooled.uses += 1;
+        pooled.heap_bytes = pooled.vm.recount_heap();
+        pooled.vm.reset();
+        pooled.vm.replace_globals(deep_copy_all(&pooled.baseline_globals));
+
+        let pooled = if self.is_healthy(&pooled) {
+            pooled
+        } else {
+            self.metrics.borrow_mut().recycled += 1;
+            self.warm()
+        };
+        let mut idle = self.idle.borrow_mut();
+        if idle.len() < self.config.size {
+            idle.push(pooled);
+        }
+    }
+}
+
+/// A checked-out VM. Dereferences to `IrisVM` and goes back to its pool when dropped.
+pub struct PooledVmGuard<'a> {
+    pool: &'a VmPool,
+    pooled: Option<PooledVm>,
+}
+
+impl PooledVmGuard<'_> {
+    /// Runs the VM, counting failures towards the error threshold.
+    pub fn run(&mut self) -> Result<(), VMError> {
+        let result = self.vm_mut().run();
+        if result.is_err() {
+            self.record_error();
+        }
+        result
+    }
+
+    pub fn record_error(&mut self) {
+        if let Some(pooled) = self.pooled.as_mut() {
+            pooled.errors += 1;
+        }
+    }
+
+    fn vm_mut(&mut self) -> &mut IrisVM {
+        &mut self.pooled.as_mut().expect("pooled VM already returned").vm
+    }
+}
+
+impl Deref for PooledVmGuard<'_> {
+    type Target = IrisVM;
+
+    fn deref(&self) -> &IrisVM {
+        &self.pooled.as_ref().expect("pooled VM already returned").vm
+    }
+}
+
+impl DerefMut for PooledVmGuard<'_> {
+    fn deref_mut(&mut self) -> &mut IrisVM {
+        self.vm_mut()
+    }
+}
+
+impl Drop for PooledVmGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(pooled) = self.pooled.take() {
+            self.pool.check_in(pooled);
+        }
+    }
+}
+
+fn deep_copy_all(values: &[Value]) -> Vec<Value> {
+    let mut copier = Copier::default();
+    values.iter().map(|value| copier.copy(value)).collect()
+}
+
+/// Copies values and every mutable container they reach, so changes to a copy never show in
+/// the original. Containers shared among the values stay shared among the copies, and cycles
+/// are kept. Functions, classes and strings can't change and are shared; coroutines, fibers,
+/// channels and futures can't be copied and are shared too.
+#[derive(Default)]
+struct Copier {
+    copies: HashMap<*const (), Value>,
+    upvalues: HashMap<*const RefCell<Upvalue>, UpvalueRef>,
+}
+
+impl Copier {
+    fn copy(&mut self, value: &Value) -> Value {
+        let address = match value {
+            Value::Array(gc) => Gc::addr(gc),
+            Value::Map(gc) => Gc::addr(gc),
+            Value::Set(gc) => Gc::addr(gc),
+            Value::Object(gc) => Gc::addr(gc),
+            Value::Int32Array(gc) => Gc::addr(gc),
+            Value::Float64Array(gc) => Gc::addr(gc),
+            Value::ByteArray(gc) => Gc::addr(gc),
+            Value::Bytes(gc) => Gc::addr(gc),
+            Value::Tuple(elements) => elements.as_ptr() as *const (),
+            Value::Closure(closure) => Rc::as_ptr(closure) as *const (),
+            other => return other.clone(),
+        };
+        if let Some(copy) = self.copies.get(&address) {
+            return copy.clone();
+        }
+        // Containers are registered empty and filled afterwards, so a cycle finds its copy.
+        let copy = match value {
+            Value::Array(items) => {
+                let copy = Gc::new(Vec::new());
+                self.copies.insert(address, Value::Array(copy.clone()));
+                let items: Vec<Value> = items.borrow().iter().map(|item| self.copy(item)).collect();
+                *copy.borrow_mut() = items;
+                Value::Array(copy)
+            }
+            Value::Map(entries) => {
+                let copy = Gc::new(HashMap::new());
+                self.copies.insert(address, Value::Map(copy.clone()));
+                let entries: HashMap<String, Value> = entries.borrow().iter().map(|(key, value)| (key.clone(), self.copy(value))).collect();
+                *copy.borrow_mut() = entries;
+                Value::Map(copy)
+            }
+            Value::Set(set) => {
+                let copy = Gc::new(ValueSet::new());
+                self.copies.insert(address, Value::Set(copy.clone()));
+                let elements: Vec<Value> = set.borrow().iter().map(|element| self.copy(element)).collect();
+                for element in elements {
+                    // Copies hash like the elements they were made from.
+                    copy.borrow_mut().insert(element).expect("copied set elements are hashable");
+                }
+                Value::Set(copy)
+            }
+            Value::Object(instance) => {
+                let (class, shape) = {
+                    let instance = instance.borrow();
+                    (instance.class.clone(), instance.shape.clone())
+                };
+                let copy = Gc::new(Instance { class, shape, fields: Vec::new() });
+                self.copies.insert(address, Value::Object(copy.clone()));
+                let fields: Vec<Value> = instance.borrow().fields.iter().map(|field| self.copy(field)).collect();
+                copy.borrow_mut().fields = fields;
+                Value::Object(copy)
+            }
+            Value::Int32Array(items) => Value::Int32Array(Gc::new(items.borrow().clone())),
+            Value::Float64Array(items) => Value::Float64Array(Gc::new(items.borrow().clone())),
+            Value::ByteArray(bytes) => Value::ByteArray(Gc::new(bytes.borrow().clone())),
+            Value::Bytes(bytes) => Value::Bytes(Gc::new(bytes.borrow().clone())),
+            Value::Tuple(elements) => Value::Tuple(elements.iter().map(|element| self.copy(element)).collect()),
+            Value::Closure(closure) => {
+                let mut fresh = Vec::new();
+                let upvalues = closure.upvalues.iter().map(|upvalue| {
+                    self.upvalues.entry(Rc::as_ptr(upvalue)).or_insert_with(|| {
+                        let copy = Rc::new(RefCell::new(Upvalue::Closed(Value::Null)));
+                        fresh.push((upvalue.clone(), copy.clone()));
+                        copy
+                    }).clone()
+                }).collect();
+                let copy = Value::Closure(Rc::new(Closure { function: closure.function.clone(), upvalues }));
+                self.copies.insert(address, copy.clone());
+                for (upvalue, copy) in fresh {
+                    let contents = match &*upvalue.borrow() {
+                        Upvalue::Closed(value) => Upvalue::Closed(self.copy(value)),
+                        // Only while a frame runs, which isn't the case between checkouts.
+                        Upvalue::Open(slot) => Upvalue::Open(*slot),
+                    };
+                    *copy.borrow_mut() = contents;
+                }
+                copy
+            }
+            other => other.clone(),
+        };
+        self.copies.insert(address, copy.clone());
+        copy
+    }
+}
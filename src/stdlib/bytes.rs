@@ -0,0 +1,67 @@
+//! Natives for `Value::Bytes` buffers.
+//!
+//! `bytes_new(len)`, `bytes_len(b)`, `bytes_get(b, i)`, `bytes_set(b, i, byte)`,
+//! `bytes_slice(b, start, end)`, `bytes_concat(a, b)`, `bytes_from_str(s)` and
+//! `bytes_to_str(b)`. Slices and concatenations are copies; `bytes_to_str` fails on invalid
+//! UTF-8.
+
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    vm.register_native("bytes_new", |args| {
+        let len = index(args, 0)?;
+        Ok(Value::Bytes(Gc::new(vec![0; len])))
+    });
+    vm.register_native("bytes_len", |args| Ok(Value::I64(bytes(args, 0)?.borrow().len() as i64)));
+    vm.register_native("bytes_get", |args| {
+        let buffer = bytes(args, 0)?;
+        let byte = buffer.borrow().get(index(args, 1)?).copied().ok_or(VMError::IndexOutOfBounds)?;
+        Ok(Value::U8(byte))
+    });
+    vm.register_native("bytes_set", |args| {
+        let buffer = bytes(args, 0)?;
+        let byte = u8::try_from(i64::from_value(arg(args, 2)?)?)
+            .map_err(|_| VMError::TypeMismatch("bytes_set expects a byte between 0 and 255".to_string()))?;
+        *buffer.borrow_mut().get_mut(index(args, 1)?).ok_or(VMError::IndexOutOfBounds)? = byte;
+        Ok(Value::Null)
+    });
+    vm.register_native("bytes_slice", |args| {
+        let buffer = bytes(args, 0)?;
+        let (start, end) = (index(args, 1)?, index(args, 2)?);
+        let slice = buffer.borrow().get(start..end).ok_or(VMError::IndexOutOfBounds)?.to_vec();
+        Ok(Value::Bytes(Gc::new(slice)))
+    });
+    vm.register_native("bytes_concat", |args| {
+        let joined = [bytes(args, 0)?.borrow().as_slice(), bytes(args, 1)?.borrow().as_slice()].concat();
+        Ok(Value::Bytes(Gc::new(joined)))
+    });
+    vm.register_native("bytes_from_str", |args| {
+        let text = String::from_value(arg(args, 0)?)?;
+        Ok(Value::Bytes(Gc::new(text.into_bytes())))
+    });
+    vm.register_native("bytes_to_str", |args| {
+        let buffer = bytes(args, 0)?;
+        let buffer = buffer.borrow();
+        let text = std::str::from_utf8(&buffer).map_err(|e| VMError::TypeMismatch(format!("Bytes are not valid UTF-8: {}", e)))?;
+        Ok(Value::Str(intern(text)))
+    });
+}
+
+fn arg(args: &[Value], at: usize) -> Result<&Value, VMError> {
+    args.get(at).ok_or(VMError::ArityMismatch { expected: at + 1, found: args.len() })
+}
+
+fn bytes(args: &[Value], at: usize) -> Result<&Gc<Vec<u8>>, VMError> {
+    match arg(args, at)? {
+        Value::Bytes(buffer) => Ok(buffer),
+        other => Err(VMError::TypeMismatch(format!("Expected bytes, got {:?}", other))),
+    }
+}
+
+fn index(args: &[Value], at: usize) -> Result<usize, VMError> {
+    usize::try_from(i64::from_value(arg(args, at)?)?).map_err(|_| VMError::IndexOutOfBounds)
+}
@@ -0,0 +1,30 @@
+//! Natives for `Value::Channel`, see `vm::channel`.
+//!
+//! `channel_new()`, `channel_send(c, value)`, `channel_receive(c)` and
+//! `channel_try_receive(c)`. `channel_receive` blocks the thread until a message arrives,
+//! without running other fibers; use the `ChannelReceive` opcode for that.
+//! `channel_try_receive` returns null when no message is waiting.
+
+use std::rc::Rc;
+use crate::vm::channel::Channel;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    vm.register_native("channel_new", |_| Ok(Value::Channel(Rc::new(Channel::new()))));
+    vm.register_native("channel_send", |args| {
+        let message = args.get(1).ok_or(VMError::ArityMismatch { expected: 2, found: args.len() })?;
+        channel(args)?.send(message)?;
+        Ok(Value::Null)
+    });
+    vm.register_native("channel_receive", |args| channel(args)?.receive());
+    vm.register_native("channel_try_receive", |args| Ok(channel(args)?.try_receive().unwrap_or(Value::Null)));
+}
+
+fn channel(args: &[Value]) -> Result<&Channel, VMError> {
+    match args.first() {
+        Some(Value::Channel(channel)) => Ok(channel),
+        Some(other) => Err(VMError::TypeMismatch(format!("Expected a channel, got {:?}", other))),
+        None => Err(VMError::ArityMismatch { expected: 1, found: 0 }),
+    }
+}
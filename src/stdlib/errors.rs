@@ -0,0 +1,12 @@
+//! The built-in exception classes as globals, so bytecode can create and throw them.
+
+use crate::vm::value::Value;
+use crate::vm::vm::IrisVM;
+
+pub fn register(vm: &mut IrisVM) {
+    let classes: Vec<_> = vm.exception_classes().all().collect();
+    for class in classes {
+        let name = class.name.clone();
+        vm.define_named_global(&name, Value::Class(class));
+    }
+}
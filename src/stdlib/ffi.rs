@@ -0,0 +1,295 @@
+//! `ffi_function(library, symbol, params, returns)`: a native that calls a C function in a
+//! dynamic library. Declaring one needs `Capability::Ffi`.
+//!
+//! `library` is a path for `dlopen`, or null for what the process has loaded already, and
+//! stays open while the native is alive. `params` is an array of type names and `returns`
+//! one type name:
+//!
+//! - `"i32"`, `"i64"` and `"f64"` are `int32_t`, `int64_t` and `double`.
+//! - `"str"` is a NUL-terminated `const char *`, copied for the call. Returned, it is copied
+//!   into a `Str`, or is null for a null pointer.
+//! - `"bytes"` points at a `Bytes` buffer's contents, which the function may write to but
+//!   not past. Parameters only.
+//! - `"void"` returns null. Return type only.
+//!
+//! Each call goes through a function pointer typed after the declared signature, so a
+//! function takes at most `MAX_ARGS` parameters and can't be variadic. Declaring one fails
+//! on targets without `dlopen`. Nothing checks the signature against the library, so a
+//! wrong one is undefined behaviour.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::rc::Rc;
+use crate::vm::capability::Capability;
+use crate::vm::dylib::Library;
+use crate::vm::function::Function;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+/// Whether foreign calls work on this platform.
+pub const SUPPORTED: bool = cfg!(unix);
+/// Every parameter list is its own function pointer type, `4^n` of them for `n` parameters.
+pub const MAX_ARGS: usize = 4;
+
+pub fn register(vm: &mut IrisVM) {
+    let declare = Function::new_native("ffi_function".to_string(), 4, |vm, args| {
+        vm.require_capability(Capability::Ffi)?;
+        if args.len() != 4 {
+            return Err(VMError::ArityMismatch { expected: 4, found: args.len() });
+        }
+        let library = match &args[0] {
+            Value::Null => None,
+            path => Some(String::from_value(path)?),
+        };
+        let symbol = String::from_value(&args[1])?;
+        let params = match &args[2] {
+            Value::Array(params) => params.borrow().iter().map(|param| Type::parse(param, false)).collect::<Result<Vec<_>, _>>()?,
+            other => return Err(VMError::TypeMismatch(format!("Expected an array of parameter types, got {:?}", other))),
+        };
+        let returns = Type::parse(&args[3], true)?;
+        if params.len() > MAX_ARGS {
+            return Err(VMError::InvalidOperand(format!("'{}' takes more than {} argument(s)", symbol, MAX_ARGS)));
+        }
+        if !SUPPORTED {
+            return Err(VMError::InvalidOperand("FFI is not supported on this platform".to_string()));
+        }
+        let library = Rc::new(Library::open(library.as_deref())?);
+        let address = library.symbol(&symbol)?;
+        let foreign = Foreign { _library: library, address, params, returns };
+        let arity = foreign.params.len();
+        Ok(Value::Function(Rc::new(Function::new_native(symbol, arity, move |_, args| foreign.call(args)))))
+    });
+    vm.define_named_global("ffi_function", Value::Function(Rc::new(declare)));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    I32,
+    I64,
+    F64,
+    Str,
+    Bytes,
+    Void,
+}
+
+impl Type {
+    fn parse(name: &Value, returned: bool) -> Result<Type, VMError> {
+        let name = String::from_value(name)?;
+        match (name.as_str(), returned) {
+            ("i32", _) => Ok(Type::I32),
+            ("i64", _) => Ok(Type::I64),
+            ("f64", _) => Ok(Type::F64),
+            ("str", _) => Ok(Type::Str),
+            ("bytes", false) => Ok(Type::Bytes),
+            ("void", true) => Ok(Type::Void),
+            _ => Err(VMError::InvalidOperand(format!(
+                "'{}' is not an FFI {} type", name, if returned { "return" } else { "parameter" }
+            ))),
+        }
+    }
+}
+
+struct Foreign {
+    /// Keeps `address` valid.
+    _library: Rc<Library>,
+    address: *const c_void,
+    params: Vec<Type>,
+    returns: Type,
+}
+
+impl Foreign {
+    fn call(&self, args: &[Value]) -> Result<Value, VMError> {
+        if args.len() != self.params.len() {
+            return Err(VMError::ArityMismatch { expected: self.params.len(), found: args.len() });
+        }
+        let mut values = Vec::with_capacity(args.len());
+        // Hold the copied strings and borrowed buffers until the call returns.
+        let mut strings = Vec::new();
+        let mut buffers = Vec::new();
+        for (param, arg) in self.params.iter().zip(args) {
+            values.push(match param {
+                Type::I32 => {
+                    let value = i64::from_value(arg)?;
+                    Arg::I32(i32::try_from(value).map_err(|_| VMError::TypeMismatch(format!("{} does not fit in an i32", value)))?)
+                }
+                Type::I64 => Arg::I64(i64::from_value(arg)?),
+                Type::F64 => Arg::F64(f64::from_value(arg)?),
+                Type::Str => {
+                    let string = CString::new(String::from_value(arg)?)
+                        .map_err(|_| VMError::InvalidOperand("FFI strings cannot contain NUL".to_string()))?;
+                    strings.push(string);
+                    Arg::Pointer(strings.last().unwrap().as_ptr() as *const c_void)
+                }
+                Type::Bytes => match arg {
+                    Value::Bytes(bytes) => {
+                        let mut buffer = bytes.try_borrow_mut()
+                            .map_err(|_| VMError::InvalidOperand("A bytes buffer is passed twice".to_string()))?;
+                        let pointer = buffer.as_mut_ptr() as *const c_void;
+                        buffers.push(buffer);
+                        Arg::Pointer(pointer)
+                    }
+                    other => return Err(VMError::TypeMismatch(format!("Expected a bytes argument, got {:?}", other))),
+                },
+                Type::Void => unreachable!(),
+            });
+        }
+        // SAFETY: the declared signature is the caller's promise, see the module docs.
+        let result = unsafe { dispatch::<()>(self.address, &self.params, self.returns, &values) };
+        drop(buffers);
+        Ok(match result {
+            Returned::Void => Value::Null,
+            Returned::I32(value) => Value::I32(value),
+            Returned::I64(value) => Value::I64(value),
+            Returned::F64(value) => Value::F64(value),
+            Returned::Str(pointer) if pointer.is_null() => Value::Null,
+            // SAFETY: as above, a `str` return is a NUL-terminated string.
+            Returned::Str(pointer) => Value::Str(intern(&unsafe { CStr::from_ptr(pointer) }.to_string_lossy())),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arg {
+    I32(i32),
+    I64(i64),
+    F64(f64),
+    Pointer(*const c_void),
+}
+
+#[derive(Debug)]
+enum Returned {
+    Void,
+    I32(i32),
+    I64(i64),
+    F64(f64),
+    Str(*const c_char),
+}
+
+/// A C parameter type, read from the `Arg` its `Type` converts to.
+trait Param: Copy {
+    fn take(arg: Arg) -> Self;
+}
+
+macro_rules! param {
+    ($type:ty, $variant:ident) => {
+        impl Param for $type {
+            fn take(arg: Arg) -> Self {
+                match arg {
+                    Arg::$variant(value) => value,
+                    other => unreachable!("{:?} passed as {}", other, stringify!($type)),
+                }
+            }
+        }
+    };
+}
+
+param!(i32, I32);
+param!(i64, I64);
+param!(f64, F64);
+param!(*const c_void, Pointer);
+
+/// A C return type.
+trait Return {
+    fn returned(self) -> Returned;
+}
+
+impl Return for () {
+    fn returned(self) -> Returned {
+        Returned::Void
+    }
+}
+
+impl Return for i32 {
+    fn returned(self) -> Returned {
+        Returned::I32(self)
+    }
+}
+
+impl Return for i64 {
+    fn returned(self) -> Returned {
+        Returned::I64(self)
+    }
+}
+
+impl Return for f64 {
+    fn returned(self) -> Returned {
+        Returned::F64(self)
+    }
+}
+
+impl Return for *const c_char {
+    fn returned(self) -> Returned {
+        Returned::Str(self)
+    }
+}
+
+/// A parameter list as a tuple of `Param`s, or `TooMany` past `MAX_ARGS`.
+trait Params {
+    type Push<P: Param>: Params;
+
+    /// # Safety
+    ///
+    /// `address` must be a C function taking these parameters and returning `R`, and `args`
+    /// must hold one argument of the matching variant for each.
+    unsafe fn call<R: Return>(address: *const c_void, args: &[Arg]) -> Returned;
+}
+
+struct TooMany;
+
+impl Params for TooMany {
+    type Push<P: Param> = TooMany;
+
+    unsafe fn call<R: Return>(_: *const c_void, _: &[Arg]) -> Returned {
+        unreachable!("declarations take at most {} parameters", MAX_ARGS)
+    }
+}
+
+macro_rules! params {
+    ($next:ty; $($param:ident),*) => {
+        impl<$($param: Param),*> Params for ($($param,)*) {
+            type Push<P: Param> = $next;
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            unsafe fn call<R: Return>(address: *const c_void, args: &[Arg]) -> Returned {
+                let function: unsafe extern "C" fn($($param),*) -> R = std::mem::transmute(address);
+                let mut args = args.iter();
+                $(let $param = $param::take(*args.next().unwrap());)*
+                function($($param),*).returned()
+            }
+        }
+    };
+}
+
+params!((P,););
+params!((A, P); A);
+params!((A, B, P); A, B);
+params!((A, B, C, P); A, B, C);
+params!(TooMany; A, B, C, D);
+
+/// Calls `address` through the function pointer type of `params` and `returns`, built one
+/// parameter at a time onto `P`.
+///
+/// # Safety
+///
+/// As `Params::call`, with `params` the C function's signature after `P`'s parameters.
+unsafe fn dispatch<P: Params>(address: *const c_void, params: &[Type], returns: Type, args: &[Arg]) -> Returned {
+    let Some((param, rest)) = params.split_first() else {
+        return match returns {
+            Type::Void => P::call::<()>(address, args),
+            Type::I32 => P::call::<i32>(address, args),
+            Type::I64 => P::call::<i64>(address, args),
+            Type::F64 => P::call::<f64>(address, args),
+            Type::Str => P::call::<*const c_char>(address, args),
+            Type::Bytes => unreachable!(),
+        };
+    };
+    match param {
+        Type::I32 => dispatch::<P::Push<i32>>(address, rest, returns, args),
+        Type::I64 => dispatch::<P::Push<i64>>(address, rest, returns, args),
+        Type::F64 => dispatch::<P::Push<f64>>(address, rest, returns, args),
+        Type::Str | Type::Bytes => dispatch::<P::Push<*const c_void>>(address, rest, returns, args),
+        Type::Void => unreachable!(),
+    }
+}
+
@@ -0,0 +1,57 @@
+//! File natives, checked against the VM's `FsPolicy`, see `vm::sandbox`.
+//!
+//! `fs_read_file(path)` returns a file's contents as a string, `fs_write_file(path, contents)`
+//! writes a string or bytes, replacing the file, `fs_list_dir(path)` returns the sorted
+//! names in a directory and `fs_exists(path)` whether the path exists. With the default
+//! policy every one of them fails with `AccessDenied`; failures of the file system itself
+//! are `Io` errors.
+
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+use super::define;
+
+pub fn register(vm: &mut IrisVM) {
+    define(vm, "fs_read_file", 1, |vm, args| {
+        let name = String::from_value(&args[0])?;
+        let path = vm.fs_policy().check(&name, false)?;
+        let contents = std::fs::read_to_string(&path).map_err(|e| io_error(&name, e))?;
+        Ok(Value::Str(intern(&contents)))
+    });
+    define(vm, "fs_write_file", 2, |vm, args| {
+        let name = String::from_value(&args[0])?;
+        let path = vm.fs_policy().check(&name, true)?;
+        let contents = match &args[1] {
+            Value::Str(s) => s.as_bytes().to_vec(),
+            Value::Bytes(bytes) => bytes.borrow().clone(),
+            other => return Err(VMError::TypeMismatch(format!("Expected a string or bytes, got {:?}", other))),
+        };
+        std::fs::write(&path, contents).map_err(|e| io_error(&name, e))?;
+        Ok(Value::Null)
+    });
+    define(vm, "fs_list_dir", 1, |vm, args| {
+        let name = String::from_value(&args[0])?;
+        let path = vm.fs_policy().check(&name, false)?;
+        let mut names = Vec::new();
+        for entry in std::fs::read_dir(&path).map_err(|e| io_error(&name, e))? {
+            let entry = entry.map_err(|e| io_error(&name, e))?;
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+        names.sort();
+        Ok(Value::Array(Gc::new(names.iter().map(|name| Value::Str(intern(name))).collect())))
+    });
+    define(vm, "fs_exists", 1, |vm, args| {
+        match vm.fs_policy().check(&String::from_value(&args[0])?, false) {
+            Ok(path) => Ok(Value::Bool(path.exists())),
+            // The parent directory is missing too.
+            Err(VMError::Io(_)) => Ok(Value::Bool(false)),
+            Err(error) => Err(error),
+        }
+    });
+}
+
+fn io_error(path: &str, error: std::io::Error) -> VMError {
+    VMError::Io(format!("'{}': {}", path, error))
+}
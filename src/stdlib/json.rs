@@ -0,0 +1,117 @@
+//! `json_parse(text[, max_depth])` and `json_stringify(value[, indent[, max_depth]])`.
+//!
+//! Parsing gives `Map`s for objects, `Array`s for arrays, and `I64`s for integral numbers
+//! and `F64`s for the rest. Stringifying takes those back, along with the other numbers,
+//! `Char`s, `Tuple`s, `Set`s, typed arrays and instances' properties. Map keys come out
+//! sorted. An `indent` above zero pretty-prints with that many spaces per level. Both fail
+//! on arrays and objects nested more than `max_depth` deep, `DEFAULT_MAX_DEPTH` unless given,
+//! which also stops a cycle.
+
+use std::collections::HashMap;
+use crate::data::json::{Json, DEFAULT_MAX_DEPTH};
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    vm.register_native("json_parse", |args| {
+        let text = String::from_value(args.first().ok_or(VMError::ArityMismatch { expected: 1, found: 0 })?)?;
+        let json = Json::parse_with_max_depth(&text, max_depth(args, 1)?)
+            .map_err(|e| VMError::InvalidOperand(format!("Invalid JSON: {}", e)))?;
+        Ok(to_value(json))
+    });
+    vm.register_native("json_stringify", |args| {
+        let value = args.first().ok_or(VMError::ArityMismatch { expected: 1, found: 0 })?;
+        let indent = match args.get(1) {
+            None | Some(Value::Null) => 0,
+            Some(indent) => usize::try_from(i64::from_value(indent)?)
+                .map_err(|_| VMError::InvalidOperand("json_stringify indent must not be negative".to_string()))?,
+        };
+        let json = to_json(value, max_depth(args, 2)?)?;
+        let text = if indent > 0 { json.pretty(indent) } else { json.to_string() };
+        Ok(Value::Str(intern(&text)))
+    });
+}
+
+fn max_depth(args: &[Value], at: usize) -> Result<usize, VMError> {
+    match args.get(at) {
+        None | Some(Value::Null) => Ok(DEFAULT_MAX_DEPTH),
+        Some(depth) => usize::try_from(i64::from_value(depth)?)
+            .map_err(|_| VMError::InvalidOperand("JSON max depth must not be negative".to_string())),
+    }
+}
+
+pub fn to_value(json: Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Bool(b) => Value::Bool(b),
+        Json::Number(n) => match Json::Number(n).as_i64() {
+            Some(i) => Value::I64(i),
+            None => Value::F64(n),
+        },
+        Json::String(s) => Value::Str(intern(&s)),
+        Json::Array(items) => Value::Array(Gc::new(items.into_iter().map(to_value).collect())),
+        Json::Object(entries) => {
+            let entries: HashMap<String, Value> = entries.into_iter().map(|(key, value)| (key, to_value(value))).collect();
+            Value::Map(Gc::new(entries))
+        }
+    }
+}
+
+/// `value` as JSON, with arrays and objects nested no more than `max_depth` deep.
+pub fn to_json(value: &Value, max_depth: usize) -> Result<Json, VMError> {
+    let inner = || max_depth.checked_sub(1).ok_or_else(|| VMError::InvalidOperand("Value is nested too deeply for JSON".to_string()));
+    let nested = |items: &mut dyn Iterator<Item = Value>| -> Result<Json, VMError> {
+        let depth = inner()?;
+        items.map(|item| to_json(&item, depth)).collect::<Result<Vec<_>, _>>().map(Json::Array)
+    };
+    let object = |entries: Vec<(String, Value)>| -> Result<Json, VMError> {
+        let depth = inner()?;
+        entries.into_iter().map(|(key, value)| Ok((key, to_json(&value, depth)?))).collect::<Result<Vec<_>, _>>().map(Json::Object)
+    };
+    Ok(match value {
+        Value::Null => Json::Null,
+        Value::Bool(b) => Json::Bool(*b),
+        Value::I8(i) => Json::Number(*i as f64),
+        Value::I16(i) => Json::Number(*i as f64),
+        Value::I32(i) => Json::Number(*i as f64),
+        Value::I64(i) => Json::Number(*i as f64),
+        Value::I128(i) => Json::Number(*i as f64),
+        Value::U8(i) => Json::Number(*i as f64),
+        Value::U16(i) => Json::Number(*i as f64),
+        Value::U32(i) => Json::Number(*i as f64),
+        Value::U64(i) => Json::Number(*i as f64),
+        Value::U128(i) => Json::Number(*i as f64),
+        Value::F32(f) => Json::Number(*f as f64),
+        Value::F64(f) => Json::Number(*f),
+        Value::Str(s) => Json::String(s.to_string()),
+        Value::Char(c) => Json::String(c.to_string()),
+        Value::Array(items) => {
+            let items = items.borrow().clone();
+            nested(&mut items.into_iter())?
+        }
+        Value::Tuple(items) => nested(&mut items.iter().cloned())?,
+        Value::Set(set) => {
+            let items: Vec<Value> = set.borrow().iter().cloned().collect();
+            nested(&mut items.into_iter())?
+        }
+        Value::Int32Array(items) => Json::Array(items.borrow().iter().map(|i| Json::Number(*i as f64)).collect()),
+        Value::Float64Array(items) => Json::Array(items.borrow().iter().map(|f| Json::Number(*f)).collect()),
+        Value::Map(entries) => {
+            let mut entries: Vec<(String, Value)> = entries.borrow().iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            object(entries)?
+        }
+        Value::Object(instance) => {
+            let instance = instance.borrow();
+            let entries = instance.shape.names().into_iter().zip(&instance.fields)
+                .filter_map(|(name, value)| Some((name?.to_string(), value.clone())))
+                .collect();
+            drop(instance);
+            object(entries)?
+        }
+        other => return Err(VMError::TypeMismatch(format!("Cannot convert {:?} to JSON", other))),
+    })
+}
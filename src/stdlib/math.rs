@@ -0,0 +1,92 @@
+//! Math as the `math` global: a map of natives and constants.
+//!
+//! `sin`, `cos`, `tan`, `exp`, `log` (natural), `pow(x, y)` and `atan2(y, x)` take any numbers
+//! and return an `F64`. `min(a, ...)` and `max(a, ...)` take one or more numbers and
+//! `clamp(x, low, high)` three; given only integers they return the chosen argument as it
+//! is, otherwise an `F64`. `PI` and `E` are `F64` constants.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::vm::function::Function;
+use crate::vm::gc::Gc;
+use crate::vm::native::{FromValue, NativeFn, TypedNative};
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    let mut math = HashMap::new();
+    typed(&mut math, "sin", |x: f64| Ok(x.sin()));
+    typed(&mut math, "cos", |x: f64| Ok(x.cos()));
+    typed(&mut math, "tan", |x: f64| Ok(x.tan()));
+    typed(&mut math, "exp", |x: f64| Ok(x.exp()));
+    typed(&mut math, "log", |x: f64| Ok(x.ln()));
+    typed(&mut math, "pow", |x: f64, y: f64| Ok(x.powf(y)));
+    typed(&mut math, "atan2", |y: f64, x: f64| Ok(y.atan2(x)));
+    native(&mut math, "min", 0, |args| extreme(args, std::cmp::Ordering::Less));
+    native(&mut math, "max", 0, |args| extreme(args, std::cmp::Ordering::Greater));
+    native(&mut math, "clamp", 3, |args| {
+        let [x, low, high] = args else {
+            return Err(VMError::ArityMismatch { expected: 3, found: args.len() });
+        };
+        if compare(low, high)?.is_gt() {
+            return Err(VMError::InvalidOperand(format!("clamp range {:?} to {:?} is empty", low, high)));
+        }
+        let floats = [x, low, high].into_iter().any(is_float);
+        let clamped = if compare(x, low)?.is_lt() {
+            low
+        } else if compare(x, high)?.is_gt() {
+            high
+        } else {
+            x
+        };
+        number(clamped, floats)
+    });
+    math.insert("PI".to_string(), Value::F64(std::f64::consts::PI));
+    math.insert("E".to_string(), Value::F64(std::f64::consts::E));
+    vm.define_named_global("math", Value::Map(Gc::new(math)));
+}
+
+fn typed<Args>(math: &mut HashMap<String, Value>, name: &str, native: impl TypedNative<Args>) {
+    let function = Function::from_native(name.to_string(), native.arity(), native.into_native());
+    math.insert(name.to_string(), Value::Function(Rc::new(function)));
+}
+
+fn native(math: &mut HashMap<String, Value>, name: &str, arity: usize, native: impl Fn(&[Value]) -> Result<Value, VMError> + 'static) {
+    let function = Function::from_native(name.to_string(), arity, NativeFn::new(move |_, args| native(args)));
+    math.insert(name.to_string(), Value::Function(Rc::new(function)));
+}
+
+fn is_float(value: &Value) -> bool {
+    matches!(value, Value::F32(_) | Value::F64(_))
+}
+
+/// Orders two numbers, as integers if both are.
+fn compare(a: &Value, b: &Value) -> Result<std::cmp::Ordering, VMError> {
+    if is_float(a) || is_float(b) {
+        let (a, b) = (f64::from_value(a)?, f64::from_value(b)?);
+        return a.partial_cmp(&b).ok_or_else(|| VMError::InvalidOperand("Cannot order NaN".to_string()));
+    }
+    Ok(i64::from_value(a)?.cmp(&i64::from_value(b)?))
+}
+
+/// `value` as returned by `min`, `max` and `clamp`: as is, or as an `F64` if any argument
+/// was a float.
+fn number(value: &Value, floats: bool) -> Result<Value, VMError> {
+    if floats {
+        f64::from_value(value).map(Value::F64)
+    } else {
+        i64::from_value(value)?;
+        Ok(value.clone())
+    }
+}
+
+fn extreme(args: &[Value], wanted: std::cmp::Ordering) -> Result<Value, VMError> {
+    let (first, rest) = args.split_first().ok_or(VMError::ArityMismatch { expected: 1, found: 0 })?;
+    let mut best = first;
+    for arg in rest {
+        if compare(arg, best)? == wanted {
+            best = arg;
+        }
+    }
+    number(best, args.iter().any(is_float))
+}
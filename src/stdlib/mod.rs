@@ -0,0 +1,33 @@
+//! Natives a host can install into a VM. Each module's `register` defines its functions as
+//! named globals, see `IrisVM::register_native`.
+
+use std::rc::Rc;
+use crate::vm::function::Function;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub mod bytes;
+pub mod channel;
+pub mod errors;
+pub mod ffi;
+pub mod fs;
+pub mod json;
+pub mod math;
+pub mod package;
+pub mod process;
+pub mod random;
+#[cfg(feature = "regex")]
+pub mod regex;
+pub mod string;
+pub mod time;
+
+/// Defines a native that gets the VM, failing calls with the wrong number of arguments.
+fn define(vm: &mut IrisVM, name: &str, arity: usize, native: impl Fn(&mut IrisVM, &[Value]) -> Result<Value, VMError> + 'static) {
+    let function = Function::new_native(name.to_string(), arity, move |vm, args| {
+        if args.len() != arity {
+            return Err(VMError::ArityMismatch { expected: arity, found: args.len() });
+        }
+        native(vm, args)
+    });
+    vm.define_named_global(name, Value::Function(Rc::new(function)));
+}
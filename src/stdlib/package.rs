@@ -0,0 +1,15 @@
+//! `package_resource(name)`: a resource file of a loaded package as `Bytes`, or `Null` if no
+//! package has one by that name. See `IrisVM::load_package`.
+
+use crate::vm::gc::Gc;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::IrisVM;
+use super::define;
+
+pub fn register(vm: &mut IrisVM) {
+    define(vm, "package_resource", 1, |vm, args| {
+        let name = String::from_value(&args[0])?;
+        Ok(vm.resource(&name).map_or(Value::Null, |contents| Value::Bytes(Gc::new(contents.to_vec()))))
+    });
+}
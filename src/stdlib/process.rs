@@ -0,0 +1,45 @@
+//! Natives for programs run from the command line, each behind a capability, see
+//! `vm::capability`.
+//!
+//! `process_args()` returns the program arguments set with `IrisVM::set_args` as an `Array`
+//! of strings (`Args`). `env_get(name)` returns an environment variable, or `Null` if it is
+//! unset or not Unicode, and `env_vars()` a `Map` of all of them (`Env`). `process_exit(status)`
+//! stops the program with `VMError::Exit`, which guest code can't catch; the host decides
+//! what to do with the status (`Exit`).
+
+use std::collections::HashMap;
+use crate::vm::capability::Capability;
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+use super::define;
+
+pub fn register(vm: &mut IrisVM) {
+    define(vm, "process_args", 0, |vm, _| {
+        vm.require_capability(Capability::Args)?;
+        Ok(Value::Array(Gc::new(vm.args().iter().map(|arg| Value::Str(intern(arg))).collect())))
+    });
+    define(vm, "env_get", 1, |vm, args| {
+        vm.require_capability(Capability::Env)?;
+        let name = String::from_value(&args[0])?;
+        if name.is_empty() || name.contains(['=', '\0']) {
+            return Err(VMError::InvalidOperand(format!("'{}' is not a valid environment variable name", name)));
+        }
+        Ok(std::env::var(&name).map_or(Value::Null, |value| Value::Str(intern(&value))))
+    });
+    define(vm, "env_vars", 0, |vm, _| {
+        vm.require_capability(Capability::Env)?;
+        let vars: HashMap<String, Value> = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, Value::Str(intern(value.to_str()?)))))
+            .collect();
+        Ok(Value::Map(Gc::new(vars)))
+    });
+    define(vm, "process_exit", 1, |vm, args| {
+        vm.require_capability(Capability::Exit)?;
+        let status = i64::from_value(&args[0])?;
+        let status = i32::try_from(status).map_err(|_| VMError::InvalidOperand(format!("Exit status {} is out of range", status)))?;
+        Err(VMError::Exit(status))
+    });
+}
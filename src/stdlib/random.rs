@@ -0,0 +1,24 @@
+//! Random numbers from the VM's generator, see `vm::random`.
+//!
+//! `random_f64()` returns an `F64` in `[0, 1)`, `random_range(low, high)` an `I64` in
+//! `[low, high)`, and `shuffle(array)` reorders an array in place.
+
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+use super::define;
+
+pub fn register(vm: &mut IrisVM) {
+    define(vm, "random_f64", 0, |vm, _| Ok(Value::F64(vm.rng().next_f64())));
+    define(vm, "random_range", 2, |vm, args| {
+        let (low, high) = (i64::from_value(&args[0])?, i64::from_value(&args[1])?);
+        vm.rng().range(low, high).map(Value::I64).ok_or_else(|| VMError::InvalidOperand(format!("random_range({}, {}) is empty", low, high)))
+    });
+    define(vm, "shuffle", 1, |vm, args| {
+        let Value::Array(array) = &args[0] else {
+            return Err(VMError::TypeMismatch(format!("Expected an array, got {:?}", args[0])));
+        };
+        vm.rng().shuffle(&mut array.borrow_mut());
+        Ok(Value::Null)
+    });
+}
@@ -0,0 +1,45 @@
+//! `regex_match(pattern, text)`, `regex_find_all(pattern, text)`,
+//! `regex_replace(pattern, text, replacement)` and `regex_captures(pattern, text)`, see
+//! `data::regex` for the syntax. Only built with the `regex` feature.
+//!
+//! `regex_match` tells whether the pattern matches anywhere in the text and `regex_find_all`
+//! returns an `Array` of every match. `regex_replace` replaces every match, with `$1` or
+//! `${name}` in the replacement standing for a group. `regex_captures` returns a `Map` of the
+//! first match, with the whole match under `"0"`, each group under its number and named
+//! groups under their name too, `Null` for groups that didn't match; or `Null` if nothing did.
+
+use std::collections::HashMap;
+use crate::data::regex::Regex;
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    vm.register_typed_native("regex_match", |pattern: String, text: String| Ok(compile(&pattern)?.is_match(&text)));
+    vm.register_typed_native("regex_find_all", |pattern: String, text: String| {
+        let matches = compile(&pattern)?.captures_all(&text).into_iter().map(|captures| Value::Str(intern(&text[captures.range()])));
+        Ok(Value::Array(Gc::new(matches.collect())))
+    });
+    vm.register_typed_native("regex_replace", |pattern: String, text: String, replacement: String| {
+        Ok(compile(&pattern)?.replace_all(&text, &replacement))
+    });
+    vm.register_typed_native("regex_captures", |pattern: String, text: String| {
+        let regex = compile(&pattern)?;
+        let Some(captures) = regex.captures(&text) else { return Ok(Value::Null) };
+        let mut groups = HashMap::new();
+        for (group, name) in regex.group_names().iter().enumerate() {
+            let value = captures.get(group).map_or(Value::Null, |range| Value::Str(intern(&text[range])));
+            if let Some(name) = name {
+                groups.insert(name.clone(), value.clone());
+            }
+            groups.insert(group.to_string(), value);
+        }
+        Ok(Value::Map(Gc::new(groups)))
+    });
+}
+
+fn compile(pattern: &str) -> Result<Regex, VMError> {
+    Regex::new(pattern).map_err(|e| VMError::InvalidOperand(format!("Invalid regex: {}", e)))
+}
+
@@ -0,0 +1,212 @@
+//! Unicode-aware natives for strings and `Value::Char`.
+//!
+//! `string_len(s)` counts chars, `string_char_at(s, i)` returns the char at char index `i`
+//! (or null past the end), `string_chars(s)` and `string_graphemes(s)` split a string into
+//! an array of chars or of grapheme strings. `string_to_upper` and `string_to_lower` take a
+//! string or a char; a char whose mapping is longer than one char becomes a string.
+//! `char_code(c)` and `char_from_code(n)` convert to and from code points.
+//!
+//! `string_split(s[, separator])` splits on a separator, or on runs of whitespace without
+//! one, and `string_join(items, separator)` joins an array or tuple. `string_trim(s)` strips
+//! whitespace from both ends, `string_replace(s, from, to)` replaces every occurrence, and
+//! `string_find(s, needle)` returns the char index of the first one or null.
+//! `string_starts_with` and `string_ends_with` test a prefix or suffix.
+//!
+//! `string_format(template, args...)` interpolates: `{}` takes the next argument, `{n}`
+//! argument `n`, `{:.p}` or `{n:.p}` a number with `p` decimals, and `{{` and `}}` are
+//! literal braces. Strings and chars go in as they are, other values as they print.
+
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub fn register(vm: &mut IrisVM) {
+    vm.register_typed_native("string_len", |s: String| Ok(s.chars().count() as i64));
+    vm.register_typed_native("string_char_at", |s: String, index: i64| {
+        Ok(usize::try_from(index).ok().and_then(|index| s.chars().nth(index)))
+    });
+    vm.register_typed_native("string_chars", |s: String| {
+        Ok(Value::Array(Gc::new(s.chars().map(Value::Char).collect())))
+    });
+    vm.register_typed_native("string_graphemes", |s: String| {
+        Ok(Value::Array(Gc::new(graphemes(&s).into_iter().map(|g| Value::Str(intern(g))).collect())))
+    });
+    vm.register_native("string_to_upper", |args| convert_case(arg(args, 0)?, true));
+    vm.register_native("string_to_lower", |args| convert_case(arg(args, 0)?, false));
+    vm.register_native("string_split", |args| {
+        let s = String::from_value(arg(args, 0)?)?;
+        let parts: Vec<Value> = match args.get(1) {
+            None | Some(Value::Null) => s.split_whitespace().map(|part| Value::Str(intern(part))).collect(),
+            Some(separator) => {
+                let separator = non_empty(String::from_value(separator)?)?;
+                s.split(separator.as_str()).map(|part| Value::Str(intern(part))).collect()
+            }
+        };
+        Ok(Value::Array(Gc::new(parts)))
+    });
+    vm.register_typed_native("string_join", |items: Value, separator: String| {
+        let items = match &items {
+            Value::Array(items) => items.borrow().clone(),
+            Value::Tuple(items) => items.to_vec(),
+            other => return Err(VMError::TypeMismatch(format!("Expected an array or tuple, got {:?}", other))),
+        };
+        Ok(items.iter().map(display).collect::<Vec<_>>().join(&separator))
+    });
+    vm.register_typed_native("string_trim", |s: String| Ok(s.trim().to_string()));
+    vm.register_typed_native("string_replace", |s: String, from: String, to: String| {
+        Ok(s.replace(non_empty(from)?.as_str(), &to))
+    });
+    vm.register_typed_native("string_find", |s: String, needle: String| {
+        Ok(s.find(&needle).map(|byte| s[..byte].chars().count() as i64))
+    });
+    vm.register_typed_native("string_starts_with", |s: String, prefix: String| Ok(s.starts_with(&prefix)));
+    vm.register_typed_native("string_ends_with", |s: String, suffix: String| Ok(s.ends_with(&suffix)));
+    vm.register_native("string_format", |args| {
+        let template = String::from_value(arg(args, 0)?)?;
+        Ok(Value::Str(intern(&format(&template, &args[1..])?)))
+    });
+    vm.register_typed_native("char_code", |c: char| Ok(c as i64));
+    vm.register_typed_native("char_from_code", |code: i64| {
+        u32::try_from(code).ok().and_then(char::from_u32)
+            .ok_or_else(|| VMError::TypeMismatch(format!("{} is not a Unicode scalar value", code)))
+    });
+}
+
+fn arg(args: &[Value], at: usize) -> Result<&Value, VMError> {
+    args.get(at).ok_or(VMError::ArityMismatch { expected: at + 1, found: args.len() })
+}
+
+fn non_empty(separator: String) -> Result<String, VMError> {
+    if separator.is_empty() {
+        return Err(VMError::InvalidOperand("Separator must not be empty".to_string()));
+    }
+    Ok(separator)
+}
+
+/// How `string_join` and `string_format` show a value.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::I8(v) => v.to_string(),
+        Value::I16(v) => v.to_string(),
+        Value::I32(v) => v.to_string(),
+        Value::I64(v) => v.to_string(),
+        Value::I128(v) => v.to_string(),
+        Value::U8(v) => v.to_string(),
+        Value::U16(v) => v.to_string(),
+        Value::U32(v) => v.to_string(),
+        Value::U64(v) => v.to_string(),
+        Value::U128(v) => v.to_string(),
+        Value::F32(v) => v.to_string(),
+        Value::F64(v) => v.to_string(),
+        Value::Str(s) => s.to_string(),
+        Value::Char(c) => c.to_string(),
+        Value::BigInt(b) => b.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+/// `template` with its placeholders replaced, see the module docs.
+pub fn format(template: &str, args: &[Value]) -> Result<String, VMError> {
+    let invalid = |message: String| VMError::InvalidOperand(format!("Format string {:?}: {}", template, message));
+    let mut out = String::new();
+    let mut next = 0;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                out.push('}');
+            }
+            '}' => return Err(invalid("unmatched '}'".to_string())),
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or_else(|| invalid("unclosed '{'".to_string()))?;
+                let (index, precision) = match rest[..end].split_once(':') {
+                    Some((index, spec)) => {
+                        let precision = spec.strip_prefix('.').and_then(|p| p.parse::<usize>().ok())
+                            .ok_or_else(|| invalid(format!("unsupported format spec '{}'", spec)))?;
+                        (index, Some(precision))
+                    }
+                    None => (&rest[..end], None),
+                };
+                let index = if index.is_empty() {
+                    next += 1;
+                    next - 1
+                } else {
+                    index.parse().map_err(|_| invalid(format!("'{}' is not an argument index", index)))?
+                };
+                let arg = args.get(index).ok_or_else(|| invalid(format!("no argument {}", index)))?;
+                match precision {
+                    Some(precision) => out.push_str(&format!("{:.*}", precision, f64::from_value(arg)?)),
+                    None => out.push_str(&display(arg)),
+                }
+                chars = rest[end + 1..].chars();
+            }
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+fn convert_case(value: &Value, upper: bool) -> Result<Value, VMError> {
+    let converted: String = match value {
+        Value::Str(s) if upper => s.to_uppercase(),
+        Value::Str(s) => s.to_lowercase(),
+        Value::Char(c) if upper => c.to_uppercase().collect(),
+        Value::Char(c) => c.to_lowercase().collect(),
+        other => return Err(VMError::TypeMismatch(format!("Expected a string or char, got {:?}", other))),
+    };
+    let mut chars = converted.chars();
+    Ok(match (value, chars.next(), chars.next()) {
+        (Value::Char(_), Some(c), None) => Value::Char(c),
+        _ => Value::Str(intern(&converted)),
+    })
+}
+
+/// Splits `text` into user-perceived characters. This covers the common cases of the
+/// Unicode segmentation rules rather than all of them: CR LF, combining marks, variation
+/// selectors, emoji modifiers, zero-width-joiner sequences and regional indicator pairs.
+pub fn graphemes(text: &str) -> Vec<&str> {
+    let mut graphemes = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    let mut regional_run = 0;
+    for (offset, c) in text.char_indices() {
+        let joins = match previous {
+            None => true,
+            Some('\r') => c == '\n',
+            Some('\u{200D}') => true,
+            Some(p) if is_regional_indicator(p) && is_regional_indicator(c) => regional_run % 2 == 1,
+            Some(_) => is_extend(c) || c == '\u{200D}',
+        };
+        if !joins {
+            graphemes.push(&text[start..offset]);
+            start = offset;
+        }
+        regional_run = if is_regional_indicator(c) { regional_run + 1 } else { 0 };
+        previous = Some(c);
+    }
+    if start < text.len() {
+        graphemes.push(&text[start..]);
+    }
+    graphemes
+}
+
+fn is_extend(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}' | '\u{FE00}'..='\u{FE0F}'
+        | '\u{1F3FB}'..='\u{1F3FF}' | '\u{E0020}'..='\u{E007F}')
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
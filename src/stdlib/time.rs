@@ -0,0 +1,45 @@
+//! Time natives, reading the VM's clock, see `vm::clock`.
+//!
+//! Times are `F64` seconds. `time_now()` is monotonic, for measuring intervals, and
+//! `time_wall()` the time since the Unix epoch. `time_sleep(seconds)` blocks, while
+//! `time_sleep_async(seconds)` returns a future to `Await`, which `IrisVM::run_async` waits
+//! on without blocking. `time_stopwatch()` returns a function giving the seconds since the
+//! stopwatch was made.
+
+use std::rc::Rc;
+use std::time::Duration;
+use crate::vm::function::Function;
+use crate::vm::future::HostFuture;
+use crate::vm::gc::Gc;
+use crate::vm::native::FromValue;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+use super::define;
+
+pub fn register(vm: &mut IrisVM) {
+    define(vm, "time_now", 0, |vm, _| Ok(Value::F64(vm.clock().monotonic().as_secs_f64())));
+    define(vm, "time_wall", 0, |vm, _| Ok(Value::F64(vm.clock().wall().as_secs_f64())));
+    define(vm, "time_sleep", 1, |vm, args| {
+        vm.clock().sleep(duration(&args[0])?);
+        Ok(Value::Null)
+    });
+    define(vm, "time_sleep_async", 1, |vm, args| {
+        let sleep = vm.clock().sleep_async(duration(&args[0])?);
+        let future = HostFuture::new(async move {
+            sleep.await;
+            Ok(Value::Null)
+        });
+        Ok(Value::Future(Gc::new(future)))
+    });
+    define(vm, "time_stopwatch", 0, |vm, _| {
+        let clock = vm.clock().clone();
+        let started = clock.monotonic();
+        let elapsed = Function::new_native("stopwatch".to_string(), 0, move |_, _| Ok(Value::F64((clock.monotonic() - started).as_secs_f64())));
+        Ok(Value::Function(Rc::new(elapsed)))
+    });
+}
+
+fn duration(seconds: &Value) -> Result<Duration, VMError> {
+    let seconds = f64::from_value(seconds)?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| VMError::InvalidOperand(format!("Cannot sleep for {} seconds", seconds)))
+}
@@ -0,0 +1,308 @@
+//! Arbitrary-precision signed integers for `Value::BigInt`.
+//!
+//! Stored as a sign and a little-endian magnitude of 32-bit limbs with no trailing zero
+//! limbs, so zero is an empty, non-negative magnitude and every number has one
+//! representation. Division truncates toward zero, like Rust's integer division.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> Self {
+        while magnitude.last() == Some(&0) {
+            magnitude.pop();
+        }
+        let negative = negative && !magnitude.is_empty();
+        Self { negative, magnitude }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The value as an `i64`, or `None` if it doesn't fit.
+    pub fn to_i64(&self) -> Option<i64> {
+        i64::try_from(self.to_i128()?).ok()
+    }
+
+    /// The value as an `i128`, or `None` if it doesn't fit.
+    pub fn to_i128(&self) -> Option<i128> {
+        if self.magnitude.len() > 4 {
+            return None;
+        }
+        let magnitude = self.magnitude.iter().rev().fold(0u128, |acc, limb| acc << 32 | *limb as u128);
+        if self.negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    /// The nearest `f64`, infinite if out of range.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self.magnitude.iter().rev().fold(0.0, |acc, limb| acc * 4294967296.0 + *limb as f64);
+        if self.negative { -magnitude } else { magnitude }
+    }
+
+    pub fn abs(&self) -> BigInt {
+        Self::new(false, self.magnitude.clone())
+    }
+
+    /// Quotient and remainder, or `None` when dividing by zero. The remainder takes the sign
+    /// of the dividend.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &divisor.magnitude);
+        Some((Self::new(self.negative != divisor.negative, quotient), Self::new(self.negative, remainder)))
+    }
+
+    fn add_signed(&self, other: &BigInt, other_negative: bool) -> BigInt {
+        if self.negative == other_negative {
+            return Self::new(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+        match compare_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => Self::new(other_negative, sub_magnitude(&other.magnitude, &self.magnitude)),
+            _ => Self::new(self.negative, sub_magnitude(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        Self::from(value as i128)
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(value: i128) -> Self {
+        let mut magnitude = value.unsigned_abs();
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push(magnitude as u32);
+            magnitude >>= 32;
+        }
+        Self::new(value < 0, limbs)
+    }
+}
+
+impl From<u128> for BigInt {
+    fn from(mut value: u128) -> Self {
+        let mut limbs = Vec::new();
+        while value > 0 {
+            limbs.push(value as u32);
+            value >>= 32;
+        }
+        Self::new(false, limbs)
+    }
+}
+
+impl std::ops::Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        self.add_signed(other, other.negative)
+    }
+}
+
+impl std::ops::Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self.add_signed(other, !other.negative && !other.is_zero())
+    }
+}
+
+impl std::ops::Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        let mut product = vec![0u32; self.magnitude.len() + other.magnitude.len()];
+        for (i, a) in self.magnitude.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, b) in other.magnitude.iter().enumerate() {
+                let sum = product[i + j] as u64 + *a as u64 * *b as u64 + carry;
+                product[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            product[i + other.magnitude.len()] = carry as u32;
+        }
+        BigInt::new(self.negative != other.negative, product)
+    }
+}
+
+impl std::ops::Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_zero() {
+            return f.write_str("0");
+        }
+        // Peel off nine decimal digits at a time, least significant first.
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            chunks.push(div_rem_small(&mut magnitude, 1_000_000_000));
+        }
+        let mut text = String::from(if self.negative { "-" } else { "" });
+        text.push_str(&chunks.pop().unwrap_or_default().to_string());
+        for chunk in chunks.iter().rev() {
+            text.push_str(&format!("{:09}", chunk));
+        }
+        f.pad(&text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+impl fmt::Display for ParseBigIntError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid integer literal")
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    /// Parses an optionally signed decimal integer.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseBigIntError);
+        }
+        let mut magnitude: Vec<u32> = Vec::new();
+        for digit in digits.bytes() {
+            let mut carry = (digit - b'0') as u64;
+            for limb in magnitude.iter_mut() {
+                let value = *limb as u64 * 10 + carry;
+                *limb = value as u32;
+                carry = value >> 32;
+            }
+            if carry > 0 {
+                magnitude.push(carry as u32);
+            }
+        }
+        Ok(Self::new(negative, magnitude))
+    }
+}
+
+fn compare_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len()).then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut sum = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, limb) in long.iter().enumerate() {
+        let value = *limb as u64 + short.get(i).copied().unwrap_or(0) as u64 + carry;
+        sum.push(value as u32);
+        carry = value >> 32;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+/// `a - b` for `a >= b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, limb) in a.iter().enumerate() {
+        let mut value = *limb as i64 - b.get(i).copied().unwrap_or(0) as i64 - borrow;
+        borrow = (value < 0) as i64;
+        value += borrow << 32;
+        difference.push(value as u32);
+    }
+    difference
+}
+
+/// Divides `magnitude` in place by a single limb and returns the remainder.
+fn div_rem_small(magnitude: &mut Vec<u32>, divisor: u32) -> u32 {
+    let mut remainder = 0u64;
+    for limb in magnitude.iter_mut().rev() {
+        let value = remainder << 32 | *limb as u64;
+        *limb = (value / divisor as u64) as u32;
+        remainder = value % divisor as u64;
+    }
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+    remainder as u32
+}
+
+/// Schoolbook binary long division. `divisor` must be non-zero.
+fn div_rem_magnitude(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if let [single] = divisor {
+        let mut quotient = dividend.to_vec();
+        let remainder = div_rem_small(&mut quotient, *single);
+        return (quotient, vec![remainder]);
+    }
+    let mut quotient = vec![0u32; dividend.len()];
+    let mut remainder: Vec<u32> = Vec::new();
+    for bit in (0..dividend.len() * 32).rev() {
+        // remainder = remainder << 1 | next bit of the dividend
+        let mut carry = (dividend[bit / 32] >> (bit % 32)) & 1;
+        for limb in remainder.iter_mut() {
+            let next = *limb >> 31;
+            *limb = *limb << 1 | carry;
+            carry = next;
+        }
+        if carry > 0 {
+            remainder.push(carry);
+        }
+        if compare_magnitude(&remainder, divisor) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, divisor);
+            while remainder.last() == Some(&0) {
+                remainder.pop();
+            }
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    (quotient, remainder)
+}
@@ -0,0 +1,242 @@
+use std::rc::Rc;
+use crate::vm::capability::Capability;
+use crate::vm::clock::Clock;
+use crate::vm::exception::CatchPolicy;
+use crate::vm::function::Function;
+use crate::vm::import::ModuleResolver;
+use crate::vm::sandbox::FsPolicy;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError, DEFAULT_TIER_UP_THRESHOLD};
+
+/// Resource limits enforced while running. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VMLimits {
+    /// Most values the operand stack may hold, checked after every instruction.
+    pub max_stack_size: Option<usize>,
+    /// Most call frames that may be active at once.
+    pub max_call_depth: Option<usize>,
+    /// Most bytes of strings, arrays, maps and instances the guest may hold, see `vm::memory`.
+    pub max_heap_bytes: Option<usize>,
+}
+
+/// Configures an `IrisVM` before it runs anything.
+///
+/// ```
+/// use iris_vm::vm::builder::IrisVMBuilder;
+/// use iris_vm::vm::value::Value;
+///
+/// let vm = IrisVMBuilder::new()
+///     .initial_stack_capacity(256)
+///     .initial_frame_capacity(32)
+///     .max_stack_size(4096)
+///     .max_call_depth(64)
+///     .global("answer", Value::I64(42))
+///     .build();
+/// assert_eq!(vm.limits().max_call_depth, Some(64));
+/// ```
+#[derive(Default)]
+pub struct IrisVMBuilder {
+    initial_stack_capacity: usize,
+    initial_frame_capacity: usize,
+    limits: VMLimits,
+    fuel: Option<u64>,
+    tier_up_threshold: Option<Option<u32>>,
+    jit: bool,
+    stats: bool,
+    coverage: bool,
+    require_verification: bool,
+    deadlock_detection: Option<bool>,
+    catch_policy: Option<CatchPolicy>,
+    capabilities: Vec<Capability>,
+    fs_policy: Option<FsPolicy>,
+    clock: Option<Rc<dyn Clock>>,
+    random_seed: Option<u64>,
+    deterministic: Option<u64>,
+    args: Vec<String>,
+    module_resolvers: Vec<Box<dyn ModuleResolver>>,
+    globals: Vec<(String, Value)>,
+}
+
+impl IrisVMBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn initial_stack_capacity(mut self, capacity: usize) -> Self {
+        self.initial_stack_capacity = capacity;
+        self
+    }
+
+    /// Call frames to reserve room for, so recursion this deep doesn't reallocate.
+    pub fn initial_frame_capacity(mut self, capacity: usize) -> Self {
+        self.initial_frame_capacity = capacity;
+        self
+    }
+
+    pub fn max_stack_size(mut self, size: usize) -> Self {
+        self.limits.max_stack_size = Some(size);
+        self
+    }
+
+    pub fn max_call_depth(mut self, depth: usize) -> Self {
+        self.limits.max_call_depth = Some(depth);
+        self
+    }
+
+    pub fn max_heap_bytes(mut self, bytes: usize) -> Self {
+        self.limits.max_heap_bytes = Some(bytes);
+        self
+    }
+
+    /// Starts the VM with an instruction budget, see `IrisVM::set_fuel`.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Tiers functions up to register form after this many calls plus loop back edges, see
+    /// `IrisVM::set_tier_up_threshold`.
+    pub fn tier_up_threshold(mut self, threshold: u32) -> Self {
+        self.tier_up_threshold = Some(Some(threshold));
+        self
+    }
+
+    /// Turns tiering up off, or back on at `DEFAULT_TIER_UP_THRESHOLD`.
+    pub fn tiering(mut self, enabled: bool) -> Self {
+        self.tier_up_threshold = Some(enabled.then_some(DEFAULT_TIER_UP_THRESHOLD));
+        self
+    }
+
+    /// Records whether the embedder wants JIT compilation. There is no JIT backend yet, so
+    /// this only shows up in `IrisVM::jit_enabled`.
+    pub fn jit(mut self, enabled: bool) -> Self {
+        self.jit = enabled;
+        self
+    }
+
+    /// Collects per-opcode counts and timings, see `IrisVM::set_stats_enabled`.
+    pub fn stats(mut self, enabled: bool) -> Self {
+        self.stats = enabled;
+        self
+    }
+
+    /// Records executed instructions, see `IrisVM::set_coverage_enabled`.
+    pub fn coverage(mut self, enabled: bool) -> Self {
+        self.coverage = enabled;
+        self
+    }
+
+    pub fn require_verification(mut self, required: bool) -> Self {
+        self.require_verification = required;
+        self
+    }
+
+    /// Checks `EnterMonitor` for deadlocks, see `IrisVM::set_deadlock_detection`.
+    pub fn deadlock_detection(mut self, enabled: bool) -> Self {
+        self.deadlock_detection = Some(enabled);
+        self
+    }
+
+    pub fn catch_policy(mut self, policy: CatchPolicy) -> Self {
+        self.catch_policy = Some(policy);
+        self
+    }
+
+    /// Lets guest code do what `capability` covers, see `IrisVM::grant`.
+    pub fn grant(mut self, capability: Capability) -> Self {
+        self.capabilities.push(capability);
+        self
+    }
+
+    /// What the `fs` natives may touch, see `IrisVM::set_fs_policy`.
+    pub fn fs_policy(mut self, policy: FsPolicy) -> Self {
+        self.fs_policy = Some(policy);
+        self
+    }
+
+    /// Where the `time` natives read the time, see `IrisVM::set_clock`.
+    pub fn clock(mut self, clock: Rc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Fixes the seed of the `random` natives, see `IrisVM::seed_random`.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+
+    /// Makes runs reproducible, with `seed` for the `random` natives, see
+    /// `IrisVM::make_deterministic`. Overrides `clock` and `random_seed`.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.deterministic = Some(seed);
+        self
+    }
+
+    /// The program arguments, see `IrisVM::set_args`.
+    pub fn args(mut self, args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.args = args.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Adds a place `LoadModule` looks for modules, see `IrisVM::add_module_resolver`.
+    pub fn module_resolver(mut self, resolver: impl ModuleResolver + 'static) -> Self {
+        self.module_resolvers.push(Box::new(resolver));
+        self
+    }
+
+    /// Defines a named global, in the order given, before anything runs.
+    pub fn global(mut self, name: &str, value: Value) -> Self {
+        self.globals.push((name.to_string(), value));
+        self
+    }
+
+    /// Registers a native as a named global, see `IrisVM::register_native`.
+    pub fn native(self, name: &str, native: impl Fn(&[Value]) -> Result<Value, VMError> + 'static) -> Self {
+        let function = Function::new_native(name.to_string(), 0, move |_, args| native(args));
+        self.global(name, Value::Function(Rc::new(function)))
+    }
+
+    pub fn build(self) -> IrisVM {
+        let mut vm = IrisVM::new();
+        vm.preallocate(self.initial_stack_capacity, self.initial_frame_capacity);
+        vm.set_limits(self.limits);
+        vm.set_fuel(self.fuel);
+        if let Some(threshold) = self.tier_up_threshold {
+            vm.set_tier_up_threshold(threshold);
+        }
+        vm.set_jit_enabled(self.jit);
+        vm.set_stats_enabled(self.stats);
+        vm.set_coverage_enabled(self.coverage);
+        vm.set_require_verification(self.require_verification);
+        if let Some(enabled) = self.deadlock_detection {
+            vm.set_deadlock_detection(enabled);
+        }
+        if let Some(policy) = self.catch_policy {
+            vm.set_catch_policy(policy);
+        }
+        for capability in self.capabilities {
+            vm.grant(capability);
+        }
+        if let Some(policy) = self.fs_policy {
+            vm.set_fs_policy(policy);
+        }
+        if let Some(clock) = self.clock {
+            vm.set_clock(clock);
+        }
+        if let Some(seed) = self.random_seed {
+            vm.seed_random(seed);
+        }
+        if let Some(seed) = self.deterministic {
+            vm.make_deterministic(seed);
+        }
+        vm.set_args(self.args);
+        for resolver in self.module_resolvers {
+            vm.add_module_resolver(move |name: &str| resolver.resolve(name));
+        }
+        for (name, value) in self.globals {
+            vm.define_named_global(&name, value);
+        }
+        vm
+    }
+}
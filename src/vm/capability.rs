@@ -0,0 +1,39 @@
+//! Capabilities: what guest code may do beyond computing, such as calling into native
+//! libraries.
+//!
+//! A VM starts with none. The host grants the ones it wants to allow with `IrisVM::grant` or
+//! `IrisVMBuilder::grant`, and natives that need one fail with `VMError::CapabilityDenied`
+//! until it is granted.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// Calling C functions in dynamic libraries, see `stdlib::ffi`.
+    Ffi,
+    /// Reading the program's arguments, see `stdlib::process`.
+    Args,
+    /// Reading environment variables.
+    Env,
+    /// Ending the program with an exit status.
+    Exit,
+}
+
+impl Capability {
+    /// Whether what the capability allows gives the same results on every run, see
+    /// `IrisVM::make_deterministic`.
+    pub fn is_deterministic(self) -> bool {
+        !matches!(self, Capability::Ffi | Capability::Env)
+    }
+}
+
+impl fmt::Display for Capability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Capability::Ffi => write!(f, "ffi"),
+            Capability::Args => write!(f, "args"),
+            Capability::Env => write!(f, "env"),
+            Capability::Exit => write!(f, "exit"),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! `Value::Channel`: a message queue between fibers, or between VMs on different threads.
+//!
+//! Messages are copied into `SharedValue`s on send and back into values of the receiving
+//! VM's own on receive, so only immutable values can be sent: scalars, strings, chars, big
+//! integers, tuples, ranges, functions, classes and other channels. Each handle can both
+//! send and receive. To connect VMs on different threads, clone a `Channel` on the host and
+//! define it as a global in each VM.
+//!
+//! `ChannelReceive` on an empty channel lets other fibers run until a message arrives. When
+//! no other fiber can run, it blocks the thread, unless no other handle to the channel
+//! exists, since then nothing could ever send.
+
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use crate::data::shared::SharedValue;
+use crate::vm::value::Value;
+use crate::vm::vm::VMError;
+
+#[derive(Clone)]
+pub struct Channel {
+    sender: Sender<SharedValue>,
+    receiver: Arc<Mutex<Receiver<SharedValue>>>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self { sender, receiver: Arc::new(Mutex::new(receiver)) }
+    }
+
+    pub fn send(&self, value: &Value) -> Result<(), VMError> {
+        let message = SharedValue::from_value(value).map_err(|e| VMError::TypeMismatch(e.to_string()))?;
+        // Every handle holds a receiver, so the channel can't be disconnected while we hold one.
+        self.sender.send(message).map_err(|_| VMError::InvalidOperand("Channel is closed".to_string()))
+    }
+
+    /// The next message, if one is waiting.
+    pub fn try_receive(&self) -> Option<Value> {
+        match self.receiver.lock().ok()?.try_recv() {
+            Ok(message) => Some(message.to_value()),
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Waits for the next message. Fails instead of waiting forever when this is the only
+    /// handle to the channel.
+    pub fn receive(&self) -> Result<Value, VMError> {
+        if let Some(message) = self.try_receive() {
+            return Ok(message);
+        }
+        if !self.is_shared() {
+            return Err(VMError::InvalidOperand("Receiving from an empty channel nothing else can send to".to_string()));
+        }
+        let receiver = self.receiver.lock().map_err(|_| VMError::InvalidOperand("Channel is poisoned".to_string()))?;
+        let message = receiver.recv().map_err(|_| VMError::InvalidOperand("Channel is closed".to_string()))?;
+        Ok(message.to_value())
+    }
+
+    /// Whether another handle, on this thread's host side or another thread, exists.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.receiver) > 1
+    }
+
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        Arc::ptr_eq(&a.receiver, &b.receiver)
+    }
+
+    pub(crate) fn addr(&self) -> *const () {
+        Arc::as_ptr(&self.receiver) as *const ()
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Channel {
+    fn eq(&self, other: &Self) -> bool {
+        Channel::ptr_eq(self, other)
+    }
+}
+
+impl fmt::Debug for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Channel")
+    }
+}
@@ -1,3 +1,8 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use crate::debug::lines::{LineTable, Span};
+use crate::vm::bigint::BigInt;
 use crate::vm::value::Value;
 use serde::{Serialize, Deserialize};
 
@@ -11,6 +16,106 @@ pub trait ChunkWriter<T> {
 pub struct Chunk {
     pub code: Vec<u8>,
     pub constants: Vec<Value>,
+    pub lines: LineTable,
+    #[serde(skip)]
+    current_span: Option<Span>,
+    #[serde(skip)]
+    constant_indices: HashMap<ConstantKey, usize>,
+    #[serde(skip)]
+    labels: Vec<Option<usize>>,
+    #[serde(skip)]
+    jumps: Vec<LabelJump>,
+}
+
+/// Identity of a constant for deduplication. Floats compare by bits so that `0.0` and
+/// `-0.0` stay distinct and equal NaNs are shared; reference values are never merged.
+#[derive(PartialEq, Eq, Hash)]
+enum ConstantKey {
+    Null,
+    Bool(bool),
+    Int(u8, i128),
+    UInt(u8, u128),
+    F32(u32),
+    F64(u64),
+    Str(String),
+    BigInt(BigInt),
+}
+
+impl ConstantKey {
+    fn of(value: &Value) -> Option<Self> {
+        let key = match value {
+            Value::Null => ConstantKey::Null,
+            Value::Bool(b) => ConstantKey::Bool(*b),
+            Value::I8(v) => ConstantKey::Int(8, *v as i128),
+            Value::I16(v) => ConstantKey::Int(16, *v as i128),
+            Value::I32(v) => ConstantKey::Int(32, *v as i128),
+            Value::I64(v) => ConstantKey::Int(64, *v as i128),
+            Value::I128(v) => ConstantKey::Int(128, *v),
+            Value::U8(v) => ConstantKey::UInt(8, *v as u128),
+            Value::U16(v) => ConstantKey::UInt(16, *v as u128),
+            Value::U32(v) => ConstantKey::UInt(32, *v as u128),
+            Value::U64(v) => ConstantKey::UInt(64, *v as u128),
+            Value::U128(v) => ConstantKey::UInt(128, *v),
+            Value::F32(v) => ConstantKey::F32(v.to_bits()),
+            Value::F64(v) => ConstantKey::F64(v.to_bits()),
+            Value::Str(s) => ConstantKey::Str(s.to_string()),
+            Value::BigInt(v) => ConstantKey::BigInt((**v).clone()),
+            _ => return None,
+        };
+        Some(key)
+    }
+}
+
+/// A jump target created by `Chunk::create_label` and placed with `Chunk::bind_label`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Label(usize);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpError {
+    NotAJump(OpCode),
+    /// Conditional jumps, BeginTryBlock and FinallyBlock can't target a bound label.
+    Backward(OpCode),
+    OutOfRange { opcode: OpCode, at: usize, target: usize },
+    LabelBoundTwice,
+}
+
+impl fmt::Display for JumpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JumpError::NotAJump(opcode) => write!(f, "{:?} is not a jump", opcode),
+            JumpError::Backward(opcode) => write!(f, "{:?} can only jump forward", opcode),
+            JumpError::OutOfRange { opcode, at, target } => {
+                write!(f, "{:?} from {} to {} is out of range", opcode, at, target)
+            }
+            JumpError::LabelBoundTwice => write!(f, "Label bound twice"),
+        }
+    }
+}
+
+impl Error for JumpError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JumpKind {
+    /// Encoded as ShortJump, UnconditionalJump, LoopJump or `PushFalse; JumpIfFalse`
+    /// depending on direction and distance.
+    Goto,
+    /// A u16 forward jump such as JumpIfFalse.
+    Conditional(OpCode),
+    /// BeginTryBlock or FinallyBlock, with a u8 forward handler offset.
+    BeginTry(OpCode),
+}
+
+struct LabelJump {
+    at: usize,
+    size: usize,
+    label: usize,
+    kind: JumpKind,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Chunk {
@@ -18,19 +123,142 @@ impl Chunk {
         Self {
             code: Vec::new(),
             constants: Vec::new(),
+            lines: LineTable::new(),
+            current_span: None,
+            constant_indices: HashMap::new(),
+            labels: Vec::new(),
+            jumps: Vec::new(),
+        }
+    }
+
+    /// Attributes every instruction written from now on to `line`.
+    pub fn set_line(&mut self, line: u32) {
+        self.set_span(Span::line(line));
+    }
+
+    pub fn set_span(&mut self, span: Span) {
+        self.current_span = Some(span);
+    }
+
+    fn mark_instruction(&mut self) {
+        if let Some(span) = self.current_span {
+            self.lines.record(self.code.len(), span);
+        }
+    }
+
+    pub fn create_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    /// Binds `label` to the current end of the code and patches every jump to it.
+    pub fn bind_label(&mut self, label: Label) -> Result<(), JumpError> {
+        if self.labels[label.0].is_some() {
+            return Err(JumpError::LabelBoundTwice);
+        }
+        self.labels[label.0] = Some(self.code.len());
+        self.relax_jumps()
+    }
+
+    /// Emits a jump to `label`, which may be bound before or after this call.
+    ///
+    /// ShortJump, UnconditionalJump and LoopJump are interchangeable here: the shortest
+    /// encoding that reaches the label is picked and widened in place if later code pushes
+    /// the label out of range. Conditional jumps, BeginTryBlock and FinallyBlock only jump forward.
+    /// Jumps written with raw offsets are not adjusted when a label jump grows, so don't
+    /// mix the two across a label jump.
+    ///
+    /// Fails if a jump can't reach its label; the jumps are then left unpatched and the code
+    /// shouldn't be run.
+    pub fn emit_jump(&mut self, opcode: OpCode, label: Label) -> Result<(), JumpError> {
+        let kind = match opcode {
+            OpCode::ShortJump | OpCode::UnconditionalJump | OpCode::LoopJump => JumpKind::Goto,
+            OpCode::BeginTryBlock | OpCode::FinallyBlock => JumpKind::BeginTry(opcode),
+            OpCode::JumpIfTrue | OpCode::JumpIfFalse | OpCode::JumpIfNull | OpCode::JumpIfNonNull
+            | OpCode::CompareAndBranchEqualInt32 | OpCode::CompareAndBranchNotEqualInt32
+            | OpCode::CompareAndBranchLessThanInt32 | OpCode::CompareAndBranchGreaterThanInt32 => {
+                JumpKind::Conditional(opcode)
+            }
+            _ => return Err(JumpError::NotAJump(opcode)),
+        };
+        if kind != JumpKind::Goto && self.labels[label.0].is_some() {
+            return Err(JumpError::Backward(opcode));
         }
+        let size = if let JumpKind::Conditional(_) = kind { 3 } else { 2 };
+        self.mark_instruction();
+        let at = self.code.len();
+        self.code.resize(at + size, 0);
+        self.jumps.push(LabelJump { at, size, label: label.0, kind });
+        self.relax_jumps()
+    }
+
+    /// Grows jumps whose target moved out of range until every label jump fits, then
+    /// rewrites all of them.
+    fn relax_jumps(&mut self) -> Result<(), JumpError> {
+        loop {
+            let mut grown = None;
+            for (index, jump) in self.jumps.iter().enumerate() {
+                if let Some(target) = self.labels[jump.label] {
+                    let needed = jump_size(jump, target);
+                    if needed > jump.size {
+                        grown = Some((index, needed));
+                        break;
+                    }
+                }
+            }
+            let Some((index, needed)) = grown else { break };
+
+            let at = self.jumps[index].at;
+            let extra = needed - self.jumps[index].size;
+            let end = at + self.jumps[index].size;
+            self.code.splice(end..end, std::iter::repeat_n(0, extra));
+            self.jumps[index].size = needed;
+            for bound in self.labels.iter_mut().flatten() {
+                if *bound > at {
+                    *bound += extra;
+                }
+            }
+            for jump in self.jumps.iter_mut() {
+                if jump.at > at {
+                    jump.at += extra;
+                }
+            }
+            self.lines.remap(|offset| if offset > at { offset + extra } else { offset });
+        }
+
+        let patches = self.jumps.iter().map(|jump| {
+            let target = self.labels[jump.label].unwrap_or(jump.at + jump.size);
+            Ok((jump.at, encode_jump(jump.kind, jump.at, jump.size, target)?))
+        }).collect::<Result<Vec<_>, JumpError>>()?;
+        for (at, bytes) in patches {
+            self.code[at..at + bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(())
     }
 
     pub fn add_constant(&mut self, value: Value) -> u8 {
+        self.find_or_add_constant(value) as u8
+    }
+
+    /// Returns the index of an equal scalar or string constant already in the pool, adding
+    /// `value` only if there is none.
+    pub fn find_or_add_constant(&mut self, value: Value) -> usize {
+        let key = ConstantKey::of(&value);
+        if let Some(index) = key.as_ref().and_then(|key| self.constant_indices.get(key)) {
+            return *index;
+        }
         self.constants.push(value);
-        (self.constants.len() - 1) as u8
+        let index = self.constants.len() - 1;
+        if let Some(key) = key {
+            self.constant_indices.insert(key, index);
+        }
+        index
     }
 
     pub fn write_constant(&mut self, value: Value) {
-        self.constants.push(value);
-        let current_index = self.constants.len() - 1;
-        if current_index > u16::max_value() as usize {todo!("Handle this error.");}
-        if current_index <= u8::max_value() as usize {
+        let current_index = self.find_or_add_constant(value);
+        if current_index > u16::MAX as usize {todo!("Handle this error.");}
+        if current_index <= u8::MAX as usize {
             self.write(OpCode::PushConstant8);
             self.write(current_index as u8);
             return;
@@ -41,6 +269,52 @@ impl Chunk {
     }
 }
 
+fn jump_size(jump: &LabelJump, target: usize) -> usize {
+    match jump.kind {
+        JumpKind::Goto if target > jump.at => {
+            if target - (jump.at + 2) <= u8::MAX as usize { 2 } else { 4 }
+        }
+        JumpKind::Goto => {
+            if jump.at + 2 - target <= 128 { 2 } else { 3 }
+        }
+        JumpKind::Conditional(_) => 3,
+        JumpKind::BeginTry(_) => 2,
+    }
+}
+
+fn encode_jump(kind: JumpKind, at: usize, size: usize, target: usize) -> Result<Vec<u8>, JumpError> {
+    let forward = |opcode: OpCode, next: usize, max: usize| {
+        let distance = target.checked_sub(next).filter(|d| *d <= max);
+        distance.ok_or(JumpError::OutOfRange { opcode, at, target })
+    };
+    let bytes = match (kind, size) {
+        (JumpKind::Goto, 2) if target >= at + 2 => {
+            vec![OpCode::UnconditionalJump as u8, forward(OpCode::UnconditionalJump, at + 2, u8::MAX as usize)? as u8]
+        }
+        (JumpKind::Goto, 2) => vec![OpCode::ShortJump as u8, (target as isize - (at + 2) as isize) as i8 as u8],
+        (JumpKind::Goto, 3) => {
+            let distance = at + 3 - target;
+            if distance > u16::MAX as usize {
+                return Err(JumpError::OutOfRange { opcode: OpCode::LoopJump, at, target });
+            }
+            let [hi, lo] = (distance as u16).to_be_bytes();
+            vec![OpCode::LoopJump as u8, hi, lo]
+        }
+        (JumpKind::Goto, _) => {
+            let [hi, lo] = (forward(OpCode::JumpIfFalse, at + 4, u16::MAX as usize)? as u16).to_be_bytes();
+            vec![OpCode::PushFalse as u8, OpCode::JumpIfFalse as u8, hi, lo]
+        }
+        (JumpKind::Conditional(opcode), _) => {
+            let [hi, lo] = (forward(opcode, at + 3, u16::MAX as usize)? as u16).to_be_bytes();
+            vec![opcode as u8, hi, lo]
+        }
+        (JumpKind::BeginTry(opcode), _) => {
+            vec![opcode as u8, forward(opcode, at + 2, u8::MAX as usize)? as u8]
+        }
+    };
+    Ok(bytes)
+}
+
 impl ChunkWriter<u8> for Chunk {
     fn write(&mut self, value: u8) {
         self.code.push(value);
@@ -49,7 +323,9 @@ impl ChunkWriter<u8> for Chunk {
 
 impl ChunkWriter<OpCode> for Chunk {
     fn write(&mut self, value: OpCode) {
-        self.code.push(value as u8);
+        self.mark_instruction();
+        let bytes = (value as u16).to_be_bytes();
+        self.code.extend_from_slice(&bytes[2 - value.opcode_len()..]);
     }
 }
 
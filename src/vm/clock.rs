@@ -0,0 +1,133 @@
+//! Where the `time` natives get the time from, see `IrisVM::set_clock`.
+//!
+//! `SystemClock`, the default, reads the host's clocks and really sleeps. A `VirtualClock`
+//! only moves when told to, or when guest code sleeps on it, so runs that read the time
+//! repeat exactly.
+
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub trait Clock {
+    /// Time since a fixed point, such as the clock's creation. Never goes backwards.
+    fn monotonic(&self) -> Duration;
+
+    /// Time since the Unix epoch.
+    fn wall(&self) -> Duration;
+
+    /// Blocks the thread for `duration`.
+    fn sleep(&self, duration: Duration);
+
+    /// Completes after `duration` without blocking the executor.
+    fn sleep_async(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>>;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { started: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn monotonic(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn wall(&self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+
+    fn sleep_async(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        Box::pin(Timer { deadline: Instant::now() + duration, waker: None })
+    }
+}
+
+/// Waits on a thread of its own, so it works under any executor.
+struct Timer {
+    deadline: Instant,
+    waker: Option<Arc<Mutex<Waker>>>,
+}
+
+impl Future for Timer {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        match &self.waker {
+            Some(waker) => waker.lock().unwrap().clone_from(cx.waker()),
+            None => {
+                let waker = Arc::new(Mutex::new(cx.waker().clone()));
+                let (shared, remaining) = (waker.clone(), self.deadline - now);
+                std::thread::spawn(move || {
+                    std::thread::sleep(remaining);
+                    shared.lock().unwrap().wake_by_ref();
+                });
+                self.waker = Some(waker);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// A clock that stands still until `advance` or a sleep moves it.
+#[derive(Debug, Default)]
+pub struct VirtualClock {
+    elapsed: Cell<Duration>,
+    epoch: Duration,
+}
+
+impl VirtualClock {
+    /// Starts at zero, with the wall clock at the Unix epoch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts with the wall clock `since_epoch` after the Unix epoch.
+    pub fn starting_at(since_epoch: Duration) -> Self {
+        Self { elapsed: Cell::new(Duration::ZERO), epoch: since_epoch }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.set(self.elapsed.get() + duration);
+    }
+}
+
+impl Clock for VirtualClock {
+    fn monotonic(&self) -> Duration {
+        self.elapsed.get()
+    }
+
+    fn wall(&self) -> Duration {
+        self.epoch + self.elapsed.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+
+    fn sleep_async(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()>>> {
+        self.advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
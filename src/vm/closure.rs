@@ -0,0 +1,43 @@
+//! Closures: functions paired with the variables they captured from enclosing frames.
+//!
+//! A captured local is shared through an `Upvalue`. While the frame that owns the local is
+//! still running the upvalue is open and refers to the local's stack slot, so reads and
+//! writes through the closure and through the frame see each other. When the slot goes
+//! away, via `CloseUpvalue` or the frame returning, the upvalue is closed and keeps the
+//! value itself. Closures aren't tracked by the collector, so a closure that captures
+//! itself is never freed.
+//!
+//! A closure is built by `CaptureUpvalue`, which takes a function or closure and returns a
+//! closure with one more upvalue:
+//!
+//! ```text
+//!         PushConstant8 inner
+//!         CaptureUpvalue 1, 0   ; local 0 of this frame
+//!         CaptureUpvalue 0, 2   ; upvalue 2 of the running closure
+//! ```
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::vm::function::Function;
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone)]
+pub enum Upvalue {
+    /// Refers to a live stack slot, by absolute index.
+    Open(usize),
+    Closed(Value),
+}
+
+pub type UpvalueRef = Rc<RefCell<Upvalue>>;
+
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+impl Closure {
+    pub fn new(function: Rc<Function>) -> Self {
+        Self { function, upvalues: Vec::new() }
+    }
+}
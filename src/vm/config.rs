@@ -0,0 +1,114 @@
+use crate::vm::function::Function;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+/// Config values are published into read-only global slots allocated downwards from here,
+/// so they stay reachable by the 8-bit global opcodes.
+pub const CONFIG_SLOT_TOP: usize = u8::MAX as usize;
+
+#[derive(Debug)]
+pub struct ConfigEntry {
+    pub name: String,
+    pub slot: usize,
+    pub value: Value,
+    watchers: Vec<Value>,
+}
+
+/// Host-published configuration values mirrored into reserved global slots.
+#[derive(Debug, Default)]
+pub struct ConfigStore {
+    entries: Vec<ConfigEntry>,
+    pending: Vec<usize>,
+}
+
+/// A watcher callback waiting to be invoked at the next safepoint.
+pub(crate) struct ConfigNotification {
+    pub callback: Value,
+    pub name: String,
+    pub value: Value,
+}
+
+impl ConfigStore {
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.entry(name).map(|entry| &entry.value)
+    }
+
+    pub fn slot_of(&self, name: &str) -> Option<usize> {
+        self.entry(name).map(|entry| entry.slot)
+    }
+
+    pub fn is_reserved(&self, slot: usize) -> bool {
+        self.entries.iter().any(|entry| entry.slot == slot)
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    fn entry(&self, name: &str) -> Option<&ConfigEntry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    /// Stores `value` under `name` and returns its global slot. Watchers are queued only
+    /// when an existing value actually changes.
+    pub(crate) fn publish(&mut self, name: &str, value: Value) -> Result<usize, VMError> {
+        if let Some(index) = self.entries.iter().position(|entry| entry.name == name) {
+            let entry = &mut self.entries[index];
+            if entry.value != value {
+                entry.value = value;
+                if !entry.watchers.is_empty() && !self.pending.contains(&index) {
+                    self.pending.push(index);
+                }
+            }
+            return Ok(entry.slot);
+        }
+
+        let slot = CONFIG_SLOT_TOP.checked_sub(self.entries.len())
+            .ok_or(VMError::InvalidOperand(format!("No global slot left for config value '{}'", name)))?;
+        self.entries.push(ConfigEntry { name: name.to_string(), slot, value, watchers: Vec::new() });
+        Ok(slot)
+    }
+
+    pub(crate) fn watch(&mut self, name: &str, callback: Value) -> Result<(), VMError> {
+        if !matches!(callback, Value::Function(_)) {
+            return Err(VMError::NonCallableValue);
+        }
+        let entry = self.entries.iter_mut().find(|entry| entry.name == name)
+            .ok_or(VMError::UndefinedVariable(name.to_string()))?;
+        entry.watchers.push(callback);
+        Ok(())
+    }
+
+    pub(crate) fn take_pending(&mut self) -> Vec<ConfigNotification> {
+        let mut notifications = Vec::new();
+        for index in std::mem::take(&mut self.pending) {
+            let entry = &self.entries[index];
+            for callback in &entry.watchers {
+                notifications.push(ConfigNotification {
+                    callback: callback.clone(),
+                    name: entry.name.clone(),
+                    value: entry.value.clone(),
+                });
+            }
+        }
+        notifications
+    }
+}
+
+/// Native `watch_config(name, callback)` for guest code. Define it as a global to let scripts
+/// subscribe to config changes; it pushes `true` on success and `false` otherwise.
+pub fn watch_config_native() -> Function {
+    Function::new_native(String::from("watch_config"), 2, watch_config)
+}
+
+fn watch_config(vm: &mut IrisVM, args: &[Value]) -> Result<Value, VMError> {
+    let registered = match args {
+        [Value::Str(name), callback] => vm.watch_config(name, callback.clone()).is_ok(),
+        _ => false,
+    };
+    Ok(Value::Bool(registered))
+}
@@ -0,0 +1,55 @@
+//! Coroutines: calls that can suspend themselves with `Yield` and be resumed later.
+//!
+//! `CreateCoroutine` wraps a function or closure taking at most one argument. The first
+//! `ResumeCoroutine` (or `IrisVM::resume`) starts it, passing the resumed value as that
+//! argument. `Yield` hands a value back to the resumer and saves the coroutine's frames and
+//! stack; the next resume restores them and pushes the resumed value as the result of
+//! `Yield`. Returning finishes the coroutine, and its return value is the result of the last
+//! resume.
+//!
+//! Suspending closes the upvalues pointing into the coroutine's stack, so closures made
+//! inside it keep the values they saw at the `Yield`. Coroutines hold `Rc<Function>`s and
+//! live stack state, so unlike most values they aren't serialized, and they aren't tracked
+//! by the collector.
+
+use std::fmt;
+use crate::vm::value::Value;
+use crate::vm::vm::CallFrame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoroutineState {
+    /// Created or stopped at a `Yield`, ready to resume.
+    Suspended,
+    Running,
+    Finished,
+}
+
+pub struct Coroutine {
+    /// The function or closure to start on the first resume.
+    pub(crate) callee: Option<Value>,
+    /// Saved while suspended, with stack bases relative to `stack`.
+    pub(crate) frames: Vec<CallFrame>,
+    pub(crate) stack: Vec<Value>,
+    pub(crate) state: CoroutineState,
+}
+
+impl Coroutine {
+    pub(crate) fn new(callee: Value) -> Self {
+        Self { callee: Some(callee), frames: Vec::new(), stack: Vec::new(), state: CoroutineState::Suspended }
+    }
+
+    pub fn state(&self) -> CoroutineState {
+        self.state
+    }
+
+    /// Values the suspended coroutine holds: its saved stack and, before it starts, its callee.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.callee.iter().chain(&self.stack)
+    }
+}
+
+impl fmt::Debug for Coroutine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Coroutine({:?})", self.state)
+    }
+}
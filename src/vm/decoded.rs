@@ -0,0 +1,92 @@
+//! Pre-decoded bytecode: each instruction's opcode and operands read once, up front.
+//!
+//! `IrisVM::push_frame` decodes a function the first time it runs and keeps the result, so
+//! executing an instruction is a lookup by offset instead of decoding its opcode and parsing
+//! its operands byte by byte. Offsets stay byte offsets into the original code, so jumps,
+//! try blocks, line tables and breakpoints are unaffected. An offset that isn't the start of
+//! a decoded instruction, such as a jump into the middle of one, is decoded when it's reached.
+
+use crate::vm::opcode::{instruction_len, is_custom_opcode, OpCode, CUSTOM_OPCODES_WITH_OPERAND};
+
+/// The most operands an instruction of fixed length has, see `OpCode::operand_widths`.
+pub const MAX_OPERANDS: usize = 3;
+
+/// One instruction with its operands already read.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DecodedInstr {
+    pub opcode: OpCode,
+    /// The first byte, which tells custom opcodes apart; their `opcode` is `Unknown`.
+    pub byte: u8,
+    /// Offset of the instruction after this one.
+    pub next: usize,
+    operands: [u64; MAX_OPERANDS],
+    operand_count: u8,
+}
+
+impl DecodedInstr {
+    /// Operands as unsigned big-endian numbers of their width; a truncated instruction has
+    /// only those that fit.
+    pub fn operands(&self) -> &[u64] {
+        &self.operands[..self.operand_count as usize]
+    }
+}
+
+impl Default for DecodedInstr {
+    fn default() -> Self {
+        Self { opcode: OpCode::Unknown, byte: 0, next: 0, operands: [0; MAX_OPERANDS], operand_count: 0 }
+    }
+}
+
+/// Decodes the instruction starting at `offset`, or `None` past the end of the code.
+pub fn decode_instruction(code: &[u8], offset: usize) -> Option<DecodedInstr> {
+    let byte = *code.get(offset)?;
+    let (opcode, widths): (OpCode, &[usize]) = match byte {
+        byte if CUSTOM_OPCODES_WITH_OPERAND.contains(&byte) => (OpCode::Unknown, &[1]),
+        byte if is_custom_opcode(byte) => (OpCode::Unknown, &[]),
+        _ => {
+            let opcode = OpCode::decode(code, offset).unwrap_or(OpCode::Unknown);
+            (opcode, opcode.operand_widths())
+        }
+    };
+    let mut instruction = DecodedInstr { opcode, byte, ..DecodedInstr::default() };
+    let mut at = offset + opcode.opcode_len();
+    for width in widths {
+        let Some(bytes) = code.get(at..at + width) else { break };
+        instruction.operands[instruction.operand_count as usize] =
+            bytes.iter().fold(0, |value, byte| value << 8 | *byte as u64);
+        instruction.operand_count += 1;
+        at += width;
+    }
+    instruction.next = instruction_len(code, offset).map_or(code.len(), |len| offset + len);
+    Some(instruction)
+}
+
+/// A function's code decoded from start to end.
+#[derive(Debug, Default)]
+pub struct DecodedCode {
+    instructions: Vec<DecodedInstr>,
+    /// Index into `instructions` by byte offset; `u32::MAX` inside an instruction.
+    index: Vec<u32>,
+}
+
+impl DecodedCode {
+    pub fn new(code: &[u8]) -> Self {
+        let mut decoded = DecodedCode { instructions: Vec::new(), index: vec![u32::MAX; code.len()] };
+        let mut offset = 0;
+        while let Some(instruction) = decode_instruction(code, offset) {
+            decoded.index[offset] = decoded.instructions.len() as u32;
+            decoded.instructions.push(instruction);
+            offset = instruction.next;
+        }
+        decoded
+    }
+
+    pub fn instructions(&self) -> &[DecodedInstr] {
+        &self.instructions
+    }
+
+    /// The instruction starting at `offset`, if decoding from the start reached one there.
+    pub fn at(&self, offset: usize) -> Option<&DecodedInstr> {
+        self.instructions.get(*self.index.get(offset)? as usize)
+    }
+}
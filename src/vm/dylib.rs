@@ -0,0 +1,86 @@
+//! Dynamic libraries, for `stdlib::ffi` and `vm::extension`.
+
+#[cfg(unix)]
+pub use self::unix::Library;
+#[cfg(not(unix))]
+pub use self::unsupported::Library;
+
+#[cfg(unix)]
+mod unix {
+    use std::ffi::{c_char, c_int, c_void, CStr, CString};
+    use crate::vm::vm::VMError;
+
+    const RTLD_NOW: c_int = 2;
+
+    #[cfg_attr(target_os = "linux", link(name = "dl"))]
+    extern "C" {
+        fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlclose(handle: *mut c_void) -> c_int;
+        fn dlerror() -> *const c_char;
+    }
+
+    fn error(what: &str) -> VMError {
+        // SAFETY: dlerror returns null or a NUL-terminated message.
+        let message = unsafe { dlerror() };
+        let reason = if message.is_null() {
+            "unknown error".into()
+        } else {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy()
+        };
+        VMError::InvalidOperand(format!("{}: {}", what, reason))
+    }
+
+    /// An open library, closed on drop.
+    pub struct Library(*mut c_void);
+
+    impl Library {
+        /// `dlopen`s `path`, or the process itself for `None`.
+        pub fn open(path: Option<&str>) -> Result<Library, VMError> {
+            let path = path.map(CString::new).transpose()
+                .map_err(|_| VMError::InvalidOperand("Library paths cannot contain NUL".to_string()))?;
+            // SAFETY: the path is NUL-terminated or null, which dlopen takes as the process.
+            let handle = unsafe { dlopen(path.as_ref().map_or(std::ptr::null(), |path| path.as_ptr()), RTLD_NOW) };
+            if handle.is_null() {
+                return Err(error("Cannot open library"));
+            }
+            Ok(Library(handle))
+        }
+
+        pub fn symbol(&self, name: &str) -> Result<*const c_void, VMError> {
+            let symbol = CString::new(name).map_err(|_| VMError::InvalidOperand("Symbols cannot contain NUL".to_string()))?;
+            // SAFETY: the handle is open and the name NUL-terminated.
+            let address = unsafe { dlsym(self.0, symbol.as_ptr()) };
+            if address.is_null() {
+                return Err(error(&format!("Cannot find '{}'", name)));
+            }
+            Ok(address)
+        }
+    }
+
+    impl Drop for Library {
+        fn drop(&mut self) {
+            // SAFETY: the handle came from dlopen and is closed once.
+            unsafe { dlclose(self.0) };
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod unsupported {
+    use std::ffi::c_void;
+    use crate::vm::vm::VMError;
+
+    pub struct Library;
+
+    impl Library {
+        pub fn open(_: Option<&str>) -> Result<Library, VMError> {
+            Err(VMError::InvalidOperand("Dynamic libraries are not supported on this platform".to_string()))
+        }
+
+        pub fn symbol(&self, _: &str) -> Result<*const c_void, VMError> {
+            unreachable!()
+        }
+    }
+}
+
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use crate::vm::intern::intern;
+use crate::vm::object::{Class, Instance};
+use crate::vm::value::Value;
+use crate::vm::vm::VMErrorKind;
+
+/// The root of the built-in exception classes; the others extend it. Guest exception
+/// classes can extend any of them.
+pub const RUNTIME_ERROR: &str = "RuntimeError";
+
+/// Built-in exception classes besides `RuntimeError`, see `ExceptionClasses::for_kind`.
+pub const ERROR_CLASSES: [&str; 6] = ["TypeError", "IndexError", "DivisionByZero", "NameError", "ArgumentError", "ArithmeticError"];
+
+/// The built-in exception classes of one VM. Every instance has a `message` and a
+/// `stack_trace` property; the VM fills in the trace when it throws the instance.
+pub struct ExceptionClasses {
+    root: Rc<Class>,
+    classes: HashMap<&'static str, Rc<Class>>,
+}
+
+impl ExceptionClasses {
+    pub fn new() -> Self {
+        let mut root = Class::new(RUNTIME_ERROR.to_string(), 0, None);
+        root.properties.insert("message".to_string(), 0);
+        root.properties.insert("stack_trace".to_string(), 1);
+        let root = Rc::new(root);
+        let classes = ERROR_CLASSES.into_iter()
+            .map(|name| (name, Rc::new(Class::new(name.to_string(), 0, Some(root.clone())))))
+            .chain([(RUNTIME_ERROR, root.clone())])
+            .collect();
+        Self { root, classes }
+    }
+
+    pub fn get(&self, name: &str) -> Option<Rc<Class>> {
+        self.classes.get(name).cloned()
+    }
+
+    /// Every built-in class, `RuntimeError` first.
+    pub fn all(&self) -> impl Iterator<Item = Rc<Class>> + '_ {
+        [self.root.clone()].into_iter().chain(ERROR_CLASSES.iter().filter_map(|name| self.get(name)))
+    }
+
+    /// The class a runtime error of `kind` is thrown as.
+    pub fn for_kind(&self, kind: VMErrorKind) -> Rc<Class> {
+        let name = match kind {
+            VMErrorKind::TypeMismatch | VMErrorKind::NonCallableValue | VMErrorKind::NonObjectValue
+            | VMErrorKind::NonClassValue | VMErrorKind::NonStringKey => "TypeError",
+            VMErrorKind::IndexOutOfBounds => "IndexError",
+            VMErrorKind::DivisionByZero => "DivisionByZero",
+            VMErrorKind::IntegerOverflow => "ArithmeticError",
+            VMErrorKind::UndefinedVariable | VMErrorKind::UndefinedProperty | VMErrorKind::MethodNotFound => "NameError",
+            VMErrorKind::ArityMismatch => "ArgumentError",
+            _ => RUNTIME_ERROR,
+        };
+        self.get(name).unwrap_or_else(|| self.root.clone())
+    }
+
+    /// Whether `class` is `RuntimeError` or extends it.
+    pub fn is_exception_class(&self, class: &Class) -> bool {
+        std::ptr::eq(class, Rc::as_ptr(&self.root)) || class.superclass.as_deref().is_some_and(|superclass| self.is_exception_class(superclass))
+    }
+}
+
+impl Default for ExceptionClasses {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A new instance of `class` with `message` and no stack trace yet.
+pub fn new_exception(class: Rc<Class>, message: &str) -> Instance {
+    let mut exception = Instance::new(class);
+    exception.set_property("message", Value::Str(intern(message)));
+    exception.set_property("stack_trace", Value::Null);
+    exception
+}
+
+/// Whether `class`, or one of its superclasses, is named `name`. `CatchException` filters
+/// by name so bytecode can name built-in classes.
+pub fn is_named_or_extends(class: &Class, name: &str) -> bool {
+    class.name == name || class.superclass.as_deref().is_some_and(|superclass| is_named_or_extends(superclass, name))
+}
+
+/// Decides which runtime `VMError`s the interpreter may turn into guest exceptions
+/// that an in-language try block can catch. Anything not allowed here, or raised outside
+/// any try block, aborts `run()`. The default is `all_recoverable`.
+#[derive(Debug, Clone)]
+pub struct CatchPolicy {
+    catchable: HashSet<VMErrorKind>,
+}
+
+impl CatchPolicy {
+    /// Nothing is catchable; every runtime error aborts `run()`.
+    pub fn none() -> Self {
+        Self { catchable: HashSet::new() }
+    }
+
+    /// Every non-fatal error kind is catchable.
+    pub fn all_recoverable() -> Self {
+        let catchable = [
+            VMErrorKind::StackUnderflow,
+            VMErrorKind::TypeMismatch,
+            VMErrorKind::UndefinedVariable,
+            VMErrorKind::UndefinedProperty,
+            VMErrorKind::MethodNotFound,
+            VMErrorKind::NonCallableValue,
+            VMErrorKind::NonObjectValue,
+            VMErrorKind::NonClassValue,
+            VMErrorKind::NonStringKey,
+            VMErrorKind::IndexOutOfBounds,
+            VMErrorKind::DivisionByZero,
+            VMErrorKind::IntegerOverflow,
+            VMErrorKind::CoroutineFinished,
+            VMErrorKind::ReadOnlyGlobal,
+            VMErrorKind::ArityMismatch,
+            VMErrorKind::OutOfMemory,
+            VMErrorKind::CapabilityDenied,
+            VMErrorKind::AccessDenied,
+            VMErrorKind::Io,
+            VMErrorKind::Import,
+        ]
+        .into_iter()
+        .collect();
+        Self { catchable }
+    }
+
+    pub fn allow(mut self, kind: VMErrorKind) -> Self {
+        self.catchable.insert(kind);
+        self
+    }
+
+    pub fn deny(mut self, kind: VMErrorKind) -> Self {
+        self.catchable.remove(&kind);
+        self
+    }
+
+    /// Fatal kinds are never catchable, even if they were explicitly allowed.
+    pub fn is_catchable(&self, kind: VMErrorKind) -> bool {
+        !kind.is_fatal() && self.catchable.contains(&kind)
+    }
+}
+
+impl Default for CatchPolicy {
+    fn default() -> Self {
+        Self::all_recoverable()
+    }
+}
@@ -0,0 +1,291 @@
+//! Native extensions: libraries that register natives and classes into a VM at runtime.
+//!
+//! `IrisVM::load_extension(path)` opens a library and calls its `iris_module_init`, an
+//! `IrisModuleInit`, with an `IrisRegistrar`. Everything crossing the boundary is
+//! `#[repr(C)]`, so an extension can be written in C or in Rust built by any compiler:
+//!
+//! ```c
+//! int iris_module_init(IrisRegistrar *registrar) {
+//!     if (registrar->abi_version != 1) return 1;
+//!     return registrar->register_native(registrar, "add", 2, add, NULL);
+//! }
+//! ```
+//!
+//! An init function returns 0 on success; anything else fails the load, along with whatever
+//! it registered so far staying registered. Natives get their arguments as `IrisValue`s:
+//! null, bools, integers as `int`, floats as `float`, and strings and bytes as a pointer and
+//! length valid for the call. Any other value is an opaque `IRIS_VALUE` handle, also valid
+//! for the call, that can be passed back or to the `IrisCall` functions. A native returns 0
+//! and sets `result`, or nonzero with `result` optionally a message. Natives registered as
+//! class methods get the instance as their first argument, on top of their arity.
+//!
+//! The library stays open while anything it registered is alive.
+
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr;
+use std::rc::Rc;
+use crate::vm::dylib::Library;
+use crate::vm::function::Function;
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::object::Class;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+pub const IRIS_ABI_VERSION: u32 = 1;
+/// The symbol `IrisVM::load_extension` calls.
+pub const IRIS_INIT_SYMBOL: &str = "iris_module_init";
+
+pub const IRIS_NULL: u32 = 0;
+pub const IRIS_BOOL: u32 = 1;
+pub const IRIS_INT: u32 = 2;
+pub const IRIS_FLOAT: u32 = 3;
+pub const IRIS_STR: u32 = 4;
+pub const IRIS_BYTES: u32 = 5;
+pub const IRIS_VALUE: u32 = 6;
+
+/// A value crossing the boundary. `tag` says which fields hold it: `int` for bools and
+/// integers, `float`, `ptr` and `len` for UTF-8 strings and bytes, `ptr` for handles.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct IrisValue {
+    pub tag: u32,
+    pub int: i64,
+    pub float: f64,
+    pub ptr: *const c_void,
+    pub len: usize,
+}
+
+impl IrisValue {
+    pub const NULL: IrisValue = IrisValue { tag: IRIS_NULL, int: 0, float: 0.0, ptr: ptr::null(), len: 0 };
+
+    pub fn int(value: i64) -> Self {
+        Self { tag: IRIS_INT, int: value, ..Self::NULL }
+    }
+
+    pub fn float(value: f64) -> Self {
+        Self { tag: IRIS_FLOAT, float: value, ..Self::NULL }
+    }
+
+    /// Borrows `value`, which must outlive the use of the result.
+    pub fn str(value: &str) -> Self {
+        Self { tag: IRIS_STR, ptr: value.as_ptr() as *const c_void, len: value.len(), ..Self::NULL }
+    }
+}
+
+pub type IrisNative = unsafe extern "C" fn(call: *mut IrisCall, args: *const IrisValue, argc: usize, result: *mut IrisValue) -> i32;
+
+/// What a native is called with, besides its arguments.
+#[repr(C)]
+pub struct IrisCall {
+    /// The pointer the native was registered with.
+    pub data: *mut c_void,
+    /// Reads a property of an instance handle into `out`. Nonzero if it has none.
+    pub get_property: unsafe extern "C" fn(call: *mut IrisCall, object: *const IrisValue, name: *const c_char, out: *mut IrisValue) -> i32,
+    pub set_property: unsafe extern "C" fn(call: *mut IrisCall, object: *const IrisValue, name: *const c_char, value: *const IrisValue) -> i32,
+    /// Copies a string into a handle valid for the call, so a native can return one it built.
+    pub new_string: unsafe extern "C" fn(call: *mut IrisCall, ptr: *const c_char, len: usize, out: *mut IrisValue),
+    /// Values handed out during the call, boxed so handles to them stay put.
+    #[allow(clippy::vec_box)]
+    values: Vec<Box<Value>>,
+}
+
+#[repr(C)]
+pub struct IrisMethod {
+    pub name: *const c_char,
+    pub arity: u32,
+    pub native: IrisNative,
+    pub data: *mut c_void,
+}
+
+#[repr(C)]
+pub struct IrisRegistrar {
+    pub abi_version: u32,
+    /// Defines a native as a named global. Nonzero if `name` isn't UTF-8.
+    pub register_native: unsafe extern "C" fn(registrar: *mut IrisRegistrar, name: *const c_char, arity: u32, native: IrisNative, data: *mut c_void) -> i32,
+    /// Defines a class as a named global, with the given properties and native methods.
+    pub register_class: unsafe extern "C" fn(
+        registrar: *mut IrisRegistrar,
+        name: *const c_char,
+        properties: *const *const c_char,
+        property_count: usize,
+        methods: *const IrisMethod,
+        method_count: usize,
+    ) -> i32,
+    vm: *mut IrisVM,
+    library: Option<Rc<Library>>,
+}
+
+pub type IrisModuleInit = unsafe extern "C" fn(registrar: *mut IrisRegistrar) -> i32;
+
+/// Opens the library at `path` and runs its `iris_module_init`.
+pub fn load(vm: &mut IrisVM, path: &str) -> Result<(), VMError> {
+    let library = Rc::new(Library::open(Some(path))?);
+    let address = library.symbol(IRIS_INIT_SYMBOL)?;
+    // SAFETY: an extension exports its init function under this name.
+    let init: IrisModuleInit = unsafe { std::mem::transmute(address) };
+    run_init(vm, init, Some(library), path)
+}
+
+/// Runs the init function of an extension linked into the program.
+pub fn init(vm: &mut IrisVM, init: IrisModuleInit) -> Result<(), VMError> {
+    run_init(vm, init, None, "built-in extension")
+}
+
+fn run_init(vm: &mut IrisVM, init: IrisModuleInit, library: Option<Rc<Library>>, name: &str) -> Result<(), VMError> {
+    let mut registrar = IrisRegistrar { abi_version: IRIS_ABI_VERSION, register_native, register_class, vm, library };
+    // SAFETY: the registrar and the VM outlive the call.
+    match unsafe { init(&mut registrar) } {
+        0 => Ok(()),
+        status => Err(VMError::InvalidOperand(format!("Extension '{}' failed to initialize with status {}", name, status))),
+    }
+}
+
+unsafe fn c_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    CStr::from_ptr(ptr).to_str().ok().map(str::to_string)
+}
+
+/// A native registered by an extension.
+struct Extern {
+    name: String,
+    arity: usize,
+    native: IrisNative,
+    data: *mut c_void,
+    _library: Option<Rc<Library>>,
+}
+
+impl Extern {
+    fn function(self) -> Function {
+        Function::new_native(self.name.clone(), self.arity, move |_, args| self.call(args))
+    }
+
+    fn call(&self, args: &[Value]) -> Result<Value, VMError> {
+        if args.len() != self.arity {
+            return Err(VMError::ArityMismatch { expected: self.arity, found: args.len() });
+        }
+        let mut call = IrisCall { data: self.data, get_property, set_property, new_string, values: Vec::new() };
+        let buffers: Vec<_> = args.iter().filter_map(|arg| match arg {
+            Value::Bytes(bytes) => Some(bytes.borrow()),
+            _ => None,
+        }).collect();
+        let marshalled: Vec<IrisValue> = args.iter().map(to_iris).collect();
+        let mut result = IrisValue::NULL;
+        // SAFETY: the arguments, and what they point at, live until the native returns.
+        let status = unsafe { (self.native)(&mut call, marshalled.as_ptr(), marshalled.len(), &mut result) };
+        drop(buffers);
+        // SAFETY: `result` is the native's, with handles from this call.
+        let result = unsafe { from_iris(&result) };
+        if status != 0 {
+            let message = match result {
+                Ok(Value::Str(message)) => message.to_string(),
+                _ => format!("status {}", status),
+            };
+            return Err(VMError::InvalidOperand(format!("'{}' failed: {}", self.name, message)));
+        }
+        result
+    }
+}
+
+/// `value` for a native, borrowing it: strings and byte buffers point into it, anything else
+/// becomes a handle to it.
+fn to_iris(value: &Value) -> IrisValue {
+    match value {
+        Value::Null => IrisValue::NULL,
+        Value::Bool(b) => IrisValue { tag: IRIS_BOOL, int: *b as i64, ..IrisValue::NULL },
+        Value::I8(v) => IrisValue::int(*v as i64),
+        Value::I16(v) => IrisValue::int(*v as i64),
+        Value::I32(v) => IrisValue::int(*v as i64),
+        Value::I64(v) => IrisValue::int(*v),
+        Value::U8(v) => IrisValue::int(*v as i64),
+        Value::U16(v) => IrisValue::int(*v as i64),
+        Value::U32(v) => IrisValue::int(*v as i64),
+        Value::F32(v) => IrisValue::float(*v as f64),
+        Value::F64(v) => IrisValue::float(*v),
+        Value::Str(s) => IrisValue::str(s),
+        Value::Bytes(bytes) => {
+            // SAFETY: the caller holds a borrow of the buffer for as long as this is used.
+            let bytes = unsafe { &*bytes.as_ptr() };
+            IrisValue { tag: IRIS_BYTES, ptr: bytes.as_ptr() as *const c_void, len: bytes.len(), ..IrisValue::NULL }
+        }
+        other => IrisValue { tag: IRIS_VALUE, ptr: other as *const Value as *const c_void, ..IrisValue::NULL },
+    }
+}
+
+unsafe fn from_iris(value: &IrisValue) -> Result<Value, VMError> {
+    let bytes = || std::slice::from_raw_parts(value.ptr as *const u8, value.len);
+    Ok(match value.tag {
+        IRIS_NULL => Value::Null,
+        IRIS_BOOL => Value::Bool(value.int != 0),
+        IRIS_INT => Value::I64(value.int),
+        IRIS_FLOAT => Value::F64(value.float),
+        IRIS_STR => {
+            let s = std::str::from_utf8(bytes()).map_err(|_| VMError::InvalidOperand("Extension string is not UTF-8".to_string()))?;
+            Value::Str(intern(s))
+        }
+        IRIS_BYTES => Value::Bytes(Gc::new(bytes().to_vec())),
+        IRIS_VALUE => (*(value.ptr as *const Value)).clone(),
+        tag => return Err(VMError::InvalidOperand(format!("Unknown extension value tag {}", tag))),
+    })
+}
+
+unsafe extern "C" fn register_native(registrar: *mut IrisRegistrar, name: *const c_char, arity: u32, native: IrisNative, data: *mut c_void) -> i32 {
+    let registrar = &mut *registrar;
+    let Some(name) = c_string(name) else { return 1 };
+    let function = Extern { name: name.clone(), arity: arity as usize, native, data, _library: registrar.library.clone() }.function();
+    (*registrar.vm).define_named_global(&name, Value::Function(Rc::new(function)));
+    0
+}
+
+unsafe extern "C" fn register_class(
+    registrar: *mut IrisRegistrar,
+    name: *const c_char,
+    properties: *const *const c_char,
+    property_count: usize,
+    methods: *const IrisMethod,
+    method_count: usize,
+) -> i32 {
+    let registrar = &mut *registrar;
+    let Some(name) = c_string(name) else { return 1 };
+    let mut class = Class::new(name.clone(), 0, None);
+    for offset in 0..property_count {
+        let Some(property) = c_string(*properties.add(offset)) else { return 1 };
+        class.properties.insert(property, offset);
+    }
+    for index in 0..method_count {
+        let method = &*methods.add(index);
+        let Some(method_name) = c_string(method.name) else { return 1 };
+        let arity = method.arity as usize + 1;
+        let function = Extern { name: method_name, arity, native: method.native, data: method.data, _library: registrar.library.clone() }.function();
+        class.add_method(index, Rc::new(function));
+    }
+    (*registrar.vm).define_named_global(&name, Value::Class(Rc::new(class)));
+    0
+}
+
+unsafe extern "C" fn get_property(call: *mut IrisCall, object: *const IrisValue, name: *const c_char, out: *mut IrisValue) -> i32 {
+    let (Ok(Value::Object(instance)), Some(name)) = (from_iris(&*object), c_string(name)) else { return 1 };
+    let Some(value) = instance.borrow().get_property(&name) else { return 1 };
+    let value = Box::new(value);
+    // The box keeps strings and handles valid for the rest of the call.
+    *out = to_iris(&value);
+    (*call).values.push(value);
+    0
+}
+
+unsafe extern "C" fn set_property(_: *mut IrisCall, object: *const IrisValue, name: *const c_char, value: *const IrisValue) -> i32 {
+    let (Ok(Value::Object(instance)), Some(name), Ok(value)) = (from_iris(&*object), c_string(name), from_iris(&*value)) else {
+        return 1;
+    };
+    instance.borrow_mut().set_property(&name, value);
+    0
+}
+
+unsafe extern "C" fn new_string(call: *mut IrisCall, ptr: *const c_char, len: usize, out: *mut IrisValue) {
+    let bytes = std::slice::from_raw_parts(ptr as *const u8, len);
+    let value = Box::new(Value::Str(intern(&String::from_utf8_lossy(bytes))));
+    *out = to_iris(&value);
+    (*call).values.push(value);
+}
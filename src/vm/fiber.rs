@@ -0,0 +1,114 @@
+//! Green threads: fibers multiplexed onto the interpreter by a round-robin scheduler.
+//!
+//! `SpawnFiber` turns a function or closure taking no arguments into a fiber and queues it.
+//! Only the running fiber's frames and stack are live in the VM; the others keep theirs in
+//! their `Fiber`. The scheduler switches fibers at safepoints, the boundaries between
+//! top-level instructions: on `YieldFiber`, on a `JoinFiber` whose fiber hasn't finished
+//! yet, when a fiber finishes, and every `time_slice` instructions. It never switches inside
+//! a host `call`, `resume` or running coroutine.
+//!
+//! The program `run()` started is the main fiber. `run()` returns once it finishes, like a
+//! process exiting, even if spawned fibers haven't; join the ones whose results matter.
+//! A parked fiber's captured locals live in their upvalues until it resumes, so closures and
+//! the frames owning those locals see each other's writes across switches.
+
+use std::collections::VecDeque;
+use std::fmt;
+use crate::vm::closure::UpvalueRef;
+use crate::vm::gc::Gc;
+use crate::vm::value::Value;
+use crate::vm::vm::{CallFrame, TryFrame};
+
+/// Instructions a fiber runs before the scheduler preempts it.
+pub const DEFAULT_TIME_SLICE: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiberState {
+    Ready,
+    Running,
+    Finished,
+}
+
+pub struct Fiber {
+    /// The function or closure to start the first time the fiber runs.
+    pub(crate) callee: Option<Value>,
+    pub(crate) frames: Vec<CallFrame>,
+    pub(crate) stack: Vec<Value>,
+    pub(crate) try_frames: Vec<TryFrame>,
+    /// Upvalues open on `stack` when the fiber was parked, with their slots. They stay
+    /// closed until it resumes.
+    pub(crate) upvalues: Vec<(UpvalueRef, usize)>,
+    pub(crate) state: FiberState,
+    pub(crate) result: Option<Value>,
+}
+
+impl Fiber {
+    pub(crate) fn new(callee: Option<Value>) -> Self {
+        Self {
+            callee,
+            frames: Vec::new(),
+            stack: Vec::new(),
+            try_frames: Vec::new(),
+            upvalues: Vec::new(),
+            state: FiberState::Ready,
+            result: None,
+        }
+    }
+
+    pub fn state(&self) -> FiberState {
+        self.state
+    }
+
+    /// What the fiber returned, once it has finished.
+    pub fn result(&self) -> Option<&Value> {
+        self.result.as_ref()
+    }
+
+    /// Values the fiber holds while it isn't running.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.callee.iter().chain(&self.stack).chain(&self.result)
+    }
+}
+
+impl fmt::Debug for Fiber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fiber({:?})", self.state)
+    }
+}
+
+pub(crate) struct Scheduler {
+    /// The running fiber; `None` until the first spawn, while only the main program runs.
+    pub(crate) current: Option<Gc<Fiber>>,
+    pub(crate) main: Option<Gc<Fiber>>,
+    pub(crate) ready: VecDeque<Gc<Fiber>>,
+    pub(crate) time_slice: u32,
+    pub(crate) ticks: u32,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self { current: None, main: None, ready: VecDeque::new(), time_slice: DEFAULT_TIME_SLICE, ticks: 0 }
+    }
+}
+
+impl Scheduler {
+    /// Queues `fiber`, making the running program the main fiber if nothing was spawned yet.
+    pub(crate) fn spawn(&mut self, fiber: Gc<Fiber>) {
+        if self.current.is_none() {
+            let mut main = Fiber::new(None);
+            main.state = FiberState::Running;
+            let main = Gc::new(main);
+            self.main = Some(main.clone());
+            self.current = Some(main);
+        }
+        self.ready.push_back(fiber);
+    }
+
+    /// Whether the running fiber is the main program.
+    pub(crate) fn in_main(&self) -> bool {
+        match (&self.current, &self.main) {
+            (Some(current), Some(main)) => Gc::ptr_eq(current, main),
+            _ => true,
+        }
+    }
+}
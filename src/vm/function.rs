@@ -1,6 +1,11 @@
+use crate::debug::lines::LineTable;
+use crate::vm::native::NativeFn;
+use crate::vm::register::RegisterCode;
 use crate::vm::value::Value;
-use crate::vm::vm::IrisVM;
+use crate::vm::vm::{IrisVM, VMError};
 use serde::{Serialize, Deserialize};
+use std::cell::Cell;
+use std::rc::Rc;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub enum FunctionKind {
@@ -8,6 +13,37 @@ pub enum FunctionKind {
     Native,
 }
 
+/// How often a function has been called and has jumped back to loop, counted by the VM to
+/// decide when to tier it up, see `IrisVM::set_tier_up_threshold`.
+#[derive(Debug, Default)]
+pub struct Hotness {
+    invocations: Cell<u32>,
+    back_edges: Cell<u32>,
+}
+
+impl Hotness {
+    pub fn invocations(&self) -> u32 {
+        self.invocations.get()
+    }
+
+    pub fn back_edges(&self) -> u32 {
+        self.back_edges.get()
+    }
+
+    /// Invocations and back edges together.
+    pub fn total(&self) -> u32 {
+        self.invocations().saturating_add(self.back_edges())
+    }
+
+    pub(crate) fn record_invocation(&self) {
+        self.invocations.set(self.invocations().saturating_add(1));
+    }
+
+    pub(crate) fn record_back_edge(&self) {
+        self.back_edges.set(self.back_edges().saturating_add(1));
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Function {
     pub name: String,
@@ -15,8 +51,16 @@ pub struct Function {
     pub arity: usize,
     pub bytecode: Option<Vec<u8>>,
     pub constants: Vec<Value>, // Added constants field
+    /// Written after the function payload by `encode_function`, see `data::bytecode`.
+    #[serde(skip)]
+    pub lines: LineTable,
+    #[serde(skip)]
+    pub native: Option<NativeFn>,
+    /// Run instead of `bytecode` when set, see `vm::register`.
     #[serde(skip)]
-    pub native: Option<fn(*mut IrisVM)>,
+    pub registers: Option<Rc<RegisterCode>>,
+    #[serde(skip)]
+    pub hotness: Hotness,
 }
 
 impl Function {
@@ -27,26 +71,45 @@ impl Function {
             arity,
             bytecode: Some(bytecode),
             constants, // Initialize constants
-            native: None
+            lines: LineTable::new(),
+            native: None,
+            registers: None,
+            hotness: Hotness::default(),
         }
     }
 
-    pub fn new_native(name: String, arity: usize, native: fn(*mut IrisVM)) -> Self {
+    pub fn new_native(
+        name: String,
+        arity: usize,
+        native: impl Fn(&mut IrisVM, &[Value]) -> Result<Value, VMError> + 'static,
+    ) -> Self {
+        Self::from_native(name, arity, NativeFn::new(native))
+    }
+
+    pub fn from_native(name: String, arity: usize, native: NativeFn) -> Self {
         Self {
             name,
             kind: FunctionKind::Native,
             arity,
             bytecode: None,
             constants: Vec::new(),
-            native: Some(native)
+            lines: LineTable::new(),
+            native: Some(native),
+            registers: None,
+            hotness: Hotness::default(),
         }
     }
 
+    pub fn with_lines(mut self, lines: LineTable) -> Self {
+        self.lines = lines;
+        self
+    }
+
     pub fn constants(&self) -> &[Value] {
         &self.constants
     }
 
-    pub fn switch_native(&mut self, native: fn(*mut IrisVM)){
+    pub fn switch_native(&mut self, native: NativeFn) {
         self.native = Some(native);
         self.kind = FunctionKind::Native;
     }
@@ -0,0 +1,66 @@
+//! `Value::Future`: a host future bytecode can wait on with `Await`.
+//!
+//! Natives return futures wrapping host work such as network requests. `Await` pushes the
+//! result of a completed future, or throws its rejection value as a guest exception. On a
+//! pending future `run()` stops with `VMError::Pending` before the `Await`, and
+//! `IrisVM::run_async` waits for the future and carries on. The VM is `!Send`, so on tokio
+//! drive it from a `LocalSet` or a current-thread runtime.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use crate::vm::value::Value;
+
+enum FutureState {
+    Pending(Pin<Box<dyn Future<Output = Result<Value, Value>>>>),
+    Ready(Result<Value, Value>),
+}
+
+pub struct HostFuture {
+    state: FutureState,
+}
+
+impl HostFuture {
+    pub fn new(future: impl Future<Output = Result<Value, Value>> + 'static) -> Self {
+        Self { state: FutureState::Pending(Box::pin(future)) }
+    }
+
+    /// An already completed future.
+    pub fn ready(result: Result<Value, Value>) -> Self {
+        Self { state: FutureState::Ready(result) }
+    }
+
+    /// The result once the future has completed.
+    pub fn result(&self) -> Option<&Result<Value, Value>> {
+        match &self.state {
+            FutureState::Ready(result) => Some(result),
+            FutureState::Pending(_) => None,
+        }
+    }
+
+    /// Completes the future from the host, dropping the work it was waiting on.
+    pub fn complete(&mut self, result: Result<Value, Value>) {
+        self.state = FutureState::Ready(result);
+    }
+
+    /// Polls the wrapped future, keeping its result once it completes.
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let FutureState::Pending(future) = &mut self.state {
+            match future.as_mut().poll(cx) {
+                Poll::Ready(result) => self.state = FutureState::Ready(result),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(())
+    }
+}
+
+impl fmt::Debug for HostFuture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.state {
+            FutureState::Pending(_) => f.write_str("Future(pending)"),
+            FutureState::Ready(result) => write!(f, "Future({:?})", result),
+        }
+    }
+}
@@ -0,0 +1,290 @@
+//! Garbage-collected handles for arrays, maps, sets and instances.
+//!
+//! A `Gc<T>` is reference counted, so acyclic garbage is freed as soon as the last handle
+//! goes away. Cycles are left to `Heap::collect`, a mark-sweep pass over every object the VM
+//! allocated. Anything holding a handle from outside the heap, whether the operand stack,
+//! globals, frames' constants or the embedder, keeps an object alive: roots are the objects
+//! with more strong references than the heap itself accounts for. Unreachable objects are
+//! swept by clearing their contents, which breaks the cycles and lets the counts drop to zero.
+//! Tuples are plain `Rc`s the collector doesn't look inside, so a cycle that runs through a
+//! tuple counts as externally referenced and is never collected.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::rc::{Rc, Weak};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::vm::object::Instance;
+use crate::vm::set::ValueSet;
+use crate::vm::value::Value;
+
+pub struct Gc<T>(Rc<RefCell<T>>);
+
+impl<T> Gc<T> {
+    pub fn new(value: T) -> Self {
+        Gc(Rc::new(RefCell::new(value)))
+    }
+
+    pub fn ptr_eq(a: &Gc<T>, b: &Gc<T>) -> bool {
+        Rc::ptr_eq(&a.0, &b.0)
+    }
+
+    /// Identity of the object, for hashing and cycle detection.
+    pub fn addr(this: &Gc<T>) -> *const () {
+        Rc::as_ptr(&this.0) as *const ()
+    }
+
+    pub fn downgrade(this: &Gc<T>) -> Weak<RefCell<T>> {
+        Rc::downgrade(&this.0)
+    }
+
+    pub fn strong_count(this: &Gc<T>) -> usize {
+        Rc::strong_count(&this.0)
+    }
+}
+
+impl<T> Clone for Gc<T> {
+    fn clone(&self) -> Self {
+        Gc(self.0.clone())
+    }
+}
+
+impl<T> Deref for Gc<T> {
+    type Target = RefCell<T>;
+
+    fn deref(&self) -> &RefCell<T> {
+        &self.0
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Gc<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.try_borrow() {
+            Ok(value) => value.fmt(f),
+            Err(_) => f.write_str("<borrowed>"),
+        }
+    }
+}
+
+impl<T: Serialize> Serialize for Gc<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Gc<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Rc::<RefCell<T>>::deserialize(deserializer).map(Gc)
+    }
+}
+
+/// A reference to an array, map, set or instance that doesn't keep it alive.
+#[derive(Clone)]
+pub enum WeakRef {
+    Array(Weak<RefCell<Vec<Value>>>),
+    Map(Weak<RefCell<HashMap<String, Value>>>),
+    Object(Weak<RefCell<Instance>>),
+    Set(Weak<RefCell<ValueSet>>),
+}
+
+impl WeakRef {
+    /// `None` unless `value` is an array, map, set or instance.
+    pub fn new(value: &Value) -> Option<WeakRef> {
+        match value {
+            Value::Array(gc) => Some(WeakRef::Array(Gc::downgrade(gc))),
+            Value::Map(gc) => Some(WeakRef::Map(Gc::downgrade(gc))),
+            Value::Object(gc) => Some(WeakRef::Object(Gc::downgrade(gc))),
+            Value::Set(gc) => Some(WeakRef::Set(Gc::downgrade(gc))),
+            _ => None,
+        }
+    }
+
+    /// The referenced value, or `None` once it has been freed.
+    pub fn upgrade(&self) -> Option<Value> {
+        match self {
+            WeakRef::Array(weak) => weak.upgrade().map(|rc| Value::Array(Gc(rc))),
+            WeakRef::Map(weak) => weak.upgrade().map(|rc| Value::Map(Gc(rc))),
+            WeakRef::Object(weak) => weak.upgrade().map(|rc| Value::Object(Gc(rc))),
+            WeakRef::Set(weak) => weak.upgrade().map(|rc| Value::Set(Gc(rc))),
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        match self {
+            WeakRef::Array(weak) => weak.strong_count() > 0,
+            WeakRef::Map(weak) => weak.strong_count() > 0,
+            WeakRef::Object(weak) => weak.strong_count() > 0,
+            WeakRef::Set(weak) => weak.strong_count() > 0,
+        }
+    }
+
+    pub fn ptr_eq(a: &WeakRef, b: &WeakRef) -> bool {
+        match (a, b) {
+            (WeakRef::Array(a), WeakRef::Array(b)) => a.ptr_eq(b),
+            (WeakRef::Map(a), WeakRef::Map(b)) => a.ptr_eq(b),
+            (WeakRef::Object(a), WeakRef::Object(b)) => a.ptr_eq(b),
+            (WeakRef::Set(a), WeakRef::Set(b)) => a.ptr_eq(b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for WeakRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if self.is_alive() { "WeakRef(alive)" } else { "WeakRef(freed)" })
+    }
+}
+
+/// A live tracked object during a collection.
+enum Live {
+    Array(Gc<Vec<Value>>),
+    Map(Gc<HashMap<String, Value>>),
+    Object(Gc<Instance>),
+    Set(Gc<ValueSet>),
+}
+
+impl Live {
+    fn addr(&self) -> *const () {
+        match self {
+            Live::Array(gc) => Gc::addr(gc),
+            Live::Map(gc) => Gc::addr(gc),
+            Live::Object(gc) => Gc::addr(gc),
+            Live::Set(gc) => Gc::addr(gc),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Live::Array(gc) => Gc::strong_count(gc),
+            Live::Map(gc) => Gc::strong_count(gc),
+            Live::Object(gc) => Gc::strong_count(gc),
+            Live::Set(gc) => Gc::strong_count(gc),
+        }
+    }
+
+    fn children(&self) -> Vec<*const ()> {
+        let values: Vec<Value> = match self {
+            Live::Array(gc) => gc.borrow().clone(),
+            Live::Map(gc) => gc.borrow().values().cloned().collect(),
+            Live::Object(gc) => gc.borrow().fields.clone(),
+            Live::Set(gc) => gc.borrow().iter().cloned().collect(),
+        };
+        values.iter().filter_map(value_addr).collect()
+    }
+
+    /// Empties the object, handing back its contents so they are dropped after every borrow
+    /// has been released.
+    fn clear(&self) -> Vec<Value> {
+        match self {
+            Live::Array(gc) => std::mem::take(&mut *gc.borrow_mut()),
+            Live::Map(gc) => std::mem::take(&mut *gc.borrow_mut()).into_values().collect(),
+            Live::Object(gc) => std::mem::take(&mut gc.borrow_mut().fields),
+            Live::Set(gc) => gc.borrow_mut().take(),
+        }
+    }
+}
+
+fn value_addr(value: &Value) -> Option<*const ()> {
+    match value {
+        Value::Array(gc) => Some(Gc::addr(gc)),
+        Value::Map(gc) => Some(Gc::addr(gc)),
+        Value::Object(gc) => Some(Gc::addr(gc)),
+        Value::Set(gc) => Some(Gc::addr(gc)),
+        _ => None,
+    }
+}
+
+/// Allocations between automatic collections until the heap has grown past it.
+const DEFAULT_THRESHOLD: usize = 10_000;
+
+/// Every array, map, set and instance the VM allocated, see the module docs.
+pub struct Heap {
+    tracked: Vec<WeakRef>,
+    allocated: usize,
+    threshold: usize,
+    collections: usize,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self { tracked: Vec::new(), allocated: 0, threshold: DEFAULT_THRESHOLD, collections: 0 }
+    }
+}
+
+impl Heap {
+    /// Starts tracking `value` if it is an array, map, set or instance.
+    pub fn track(&mut self, value: &Value) {
+        if let Some(tracked) = WeakRef::new(value) {
+            self.tracked.push(tracked);
+            self.allocated += 1;
+        }
+    }
+
+    /// Whether enough has been allocated since the last collection to run another.
+    pub fn should_collect(&self) -> bool {
+        self.allocated >= self.threshold
+    }
+
+    /// Objects currently tracked that haven't been freed.
+    pub fn live_objects(&self) -> usize {
+        self.tracked.iter().filter(|tracked| tracked.is_alive()).count()
+    }
+
+    /// Handles to every tracked object that is still alive.
+    pub fn objects(&self) -> Vec<Value> {
+        self.tracked.iter().filter_map(WeakRef::upgrade).collect()
+    }
+
+    pub fn collections(&self) -> usize {
+        self.collections
+    }
+
+    /// Frees unreachable cycles and returns how many objects were swept.
+    pub fn collect(&mut self) -> usize {
+        let live: Vec<Live> = self.tracked.iter().filter_map(|tracked| match tracked {
+            WeakRef::Array(weak) => weak.upgrade().map(|rc| Live::Array(Gc(rc))),
+            WeakRef::Map(weak) => weak.upgrade().map(|rc| Live::Map(Gc(rc))),
+            WeakRef::Object(weak) => weak.upgrade().map(|rc| Live::Object(Gc(rc))),
+            WeakRef::Set(weak) => weak.upgrade().map(|rc| Live::Set(Gc(rc))),
+        }).collect();
+        let index: HashMap<*const (), usize> = live.iter().enumerate().map(|(i, object)| (object.addr(), i)).collect();
+        let children: Vec<Vec<usize>> = live.iter()
+            .map(|object| object.children().iter().filter_map(|addr| index.get(addr).copied()).collect())
+            .collect();
+
+        // References from outside the heap: strong count minus our own handle and the
+        // references from other tracked objects.
+        let mut external: Vec<isize> = live.iter().map(|object| object.strong_count() as isize - 1).collect();
+        for edges in &children {
+            for child in edges {
+                external[*child] -= 1;
+            }
+        }
+
+        // Mark everything reachable from an externally referenced object.
+        let mut marked = vec![false; live.len()];
+        let mut pending: Vec<usize> = (0..live.len()).filter(|i| external[*i] > 0).collect();
+        while let Some(i) = pending.pop() {
+            if !std::mem::replace(&mut marked[i], true) {
+                pending.extend(&children[i]);
+            }
+        }
+
+        // Sweep.
+        let mut garbage = Vec::new();
+        let mut swept = 0;
+        for (object, _) in live.iter().zip(&marked).filter(|(_, marked)| !**marked) {
+            garbage.extend(object.clear());
+            swept += 1;
+        }
+        drop(garbage);
+        drop(live);
+
+        self.tracked.retain(WeakRef::is_alive);
+        self.allocated = 0;
+        self.threshold = DEFAULT_THRESHOLD.max(self.tracked.len());
+        self.collections += 1;
+        swept
+    }
+}
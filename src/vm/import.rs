@@ -0,0 +1,136 @@
+//! Modules bytecode loads by name. `LoadModule name` pushes the exports of the module called
+//! `name`: a new `Map` from the name of each of its functions to the function. `ImportSymbol
+//! name` pops such a map and pushes one export.
+//!
+//! The VM asks its resolvers in the order they were added, see `IrisVM::add_module_resolver`,
+//! and keeps every module it loads, so importing a name again neither resolves it again nor
+//! makes new functions. An imported module's functions are placed in free global slots,
+//! relocated like `IrisVM::load_module` so they reach each other by slot, but get no global
+//! names; other modules they reach through imports. A module no resolver knows, or a missing
+//! export, fails with `VMError::Import`.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use crate::data::module::{load_module, Module};
+use crate::vm::function::Function;
+use crate::vm::value::Value;
+use crate::vm::vm::VMError;
+
+pub trait ModuleResolver {
+    /// The module called `name`, or `None` to let the next resolver try.
+    fn resolve(&self, name: &str) -> Result<Option<Module>, VMError>;
+}
+
+impl<F: Fn(&str) -> Result<Option<Module>, VMError>> ModuleResolver for F {
+    fn resolve(&self, name: &str) -> Result<Option<Module>, VMError> {
+        self(name)
+    }
+}
+
+/// Loads `a.b` from `<root>/a/b.icm`. Names are identifiers separated by dots.
+#[derive(Debug, Clone)]
+pub struct FsResolver {
+    root: PathBuf,
+}
+
+impl FsResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+}
+
+impl ModuleResolver for FsResolver {
+    fn resolve(&self, name: &str) -> Result<Option<Module>, VMError> {
+        let valid = name.split('.').all(|part| {
+            part.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_') && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+        });
+        if !valid {
+            return Err(VMError::Import(format!("'{}' is not a valid module name", name)));
+        }
+        let mut path = self.root.join(name.replace('.', "/"));
+        path.set_extension("icm");
+        if !path.is_file() {
+            return Ok(None);
+        }
+        load_module(&path.to_string_lossy()).map(Some).map_err(|e| VMError::Import(format!("Cannot load module '{}': {}", name, e)))
+    }
+}
+
+/// Modules the host built or loaded itself, by name.
+#[derive(Debug, Default)]
+pub struct MemoryResolver {
+    modules: HashMap<String, Module>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `module` under its name, replacing any module of that name.
+    pub fn insert(&mut self, module: Module) {
+        self.modules.insert(module.name.clone(), module);
+    }
+
+    pub fn with(mut self, module: Module) -> Self {
+        self.insert(module);
+        self
+    }
+}
+
+impl ModuleResolver for MemoryResolver {
+    fn resolve(&self, name: &str) -> Result<Option<Module>, VMError> {
+        Ok(self.modules.get(name).cloned())
+    }
+}
+
+/// The VM's resolvers and the exports of every module loaded so far.
+#[derive(Default)]
+pub(crate) struct Modules {
+    resolvers: Vec<Box<dyn ModuleResolver>>,
+    loaded: HashMap<String, HashMap<String, Value>>,
+}
+
+impl Modules {
+    pub(crate) fn add_resolver(&mut self, resolver: Box<dyn ModuleResolver>) {
+        self.resolvers.push(resolver);
+    }
+
+    /// The exports of the module called `name`, if it is loaded.
+    pub(crate) fn exports(&self, name: &str) -> Option<&HashMap<String, Value>> {
+        self.loaded.get(name)
+    }
+
+    /// Records `module`, as the VM placed it, as the module called `name`.
+    pub(crate) fn insert(&mut self, name: &str, module: &Module) -> &HashMap<String, Value> {
+        let exports = module.functions.iter().map(|function| (function.name.clone(), Value::Function(function.clone()))).collect();
+        self.loaded.entry(name.to_string()).insert_entry(exports).into_mut()
+    }
+
+    pub(crate) fn resolve(&self, name: &str) -> Result<Module, VMError> {
+        for resolver in &self.resolvers {
+            if let Some(module) = resolver.resolve(name)? {
+                return Ok(module);
+            }
+        }
+        Err(VMError::Import(format!("No module named '{}'", name)))
+    }
+
+    /// Makes loaded modules export `new` wherever they exported `old`.
+    pub(crate) fn replace(&mut self, old: &Rc<Function>, new: &Rc<Function>) {
+        for export in self.loaded.values_mut().flat_map(HashMap::values_mut) {
+            if matches!(export, Value::Function(function) if Rc::ptr_eq(function, old)) {
+                *export = Value::Function(new.clone());
+            }
+        }
+    }
+
+    pub(crate) fn is_loaded(&self, name: &str) -> bool {
+        self.loaded.contains_key(name)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.loaded.clear();
+    }
+}
@@ -0,0 +1,205 @@
+//! Per-call-site caches for the name-based property and method opcodes.
+//!
+//! The property opcodes (`GetObjectProperty8/16`, `SetObjectProperty8/16` and their
+//! `WithInlineCache` forms) name a property, and `CallWithInlineCache` a method, by a string
+//! constant. Each instruction remembers what the
+//! name resolved to per receiver: property sites key on the instance's shape (see
+//! `vm::shape`) and remember the offset, or for a property being added the shape to move to;
+//! method sites key on the class and remember the method found along its class chain. One
+//! receiver kind makes the site monomorphic, up to `POLYMORPHIC_LIMIT` polymorphic. A site
+//! that sees more turns megamorphic and stops caching; method sites then fall back to the
+//! VM-wide table `MegamorphicMethodCall` always uses.
+//!
+//! Entries hold their shape or class weakly and match by identity, so a class redefined under
+//! the same name never hits the old entries; `IrisVM::invalidate_inline_caches` drops them
+//! early.
+
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use crate::vm::function::Function;
+use crate::vm::object::Class;
+use crate::vm::shape::Shape;
+
+/// Receiver shapes or classes a site caches before it turns megamorphic.
+pub const POLYMORPHIC_LIMIT: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheState {
+    Uninitialized,
+    Monomorphic,
+    Polymorphic,
+    Megamorphic,
+}
+
+#[derive(Clone)]
+pub(crate) enum Resolved {
+    Field(usize),
+    /// Adding the property: the instance moves to the shape, and the value goes at the offset.
+    Transition(Rc<Shape>, usize),
+    Method(Rc<Function>),
+}
+
+struct Entry<K> {
+    key: Weak<K>,
+    resolved: Resolved,
+}
+
+impl<K> Entry<K> {
+    fn matches(&self, key: &Rc<K>) -> bool {
+        // The weak reference keeps the allocation, so a live match is the same shape or class.
+        std::ptr::eq(self.key.as_ptr(), Rc::as_ptr(key))
+    }
+}
+
+struct SiteCache<K> {
+    name: Rc<str>,
+    entries: Vec<Entry<K>>,
+    megamorphic: bool,
+}
+
+impl<K> SiteCache<K> {
+    fn new(name: &Rc<str>) -> Self {
+        Self { name: name.clone(), entries: Vec::new(), megamorphic: false }
+    }
+
+    /// Whether the site gave up caching `name`. A different name means another function now
+    /// lives at this address, so the site starts over.
+    fn is_megamorphic(&mut self, name: &Rc<str>) -> bool {
+        if self.name != *name {
+            *self = SiteCache::new(name);
+        }
+        self.megamorphic
+    }
+
+    /// What `name` resolves to on `key`, calling `resolve` on a miss.
+    fn lookup(&mut self, key: &Rc<K>, resolve: impl FnOnce(&Rc<K>) -> Option<Resolved>) -> Option<Resolved> {
+        if let Some(entry) = self.entries.iter().find(|entry| entry.matches(key)) {
+            return Some(entry.resolved.clone());
+        }
+        let resolved = resolve(key)?;
+        if self.entries.len() == POLYMORPHIC_LIMIT {
+            self.entries.clear();
+            self.megamorphic = true;
+        } else {
+            self.entries.push(Entry { key: Rc::downgrade(key), resolved: resolved.clone() });
+        }
+        Some(resolved)
+    }
+
+    fn state(&self) -> CacheState {
+        match self.entries.len() {
+            _ if self.megamorphic => CacheState::Megamorphic,
+            0 => CacheState::Uninitialized,
+            1 => CacheState::Monomorphic,
+            _ => CacheState::Polymorphic,
+        }
+    }
+}
+
+/// A call site: the function's address and the offset just past the instruction.
+pub(crate) type Site = (*const Function, usize);
+
+#[derive(Default)]
+pub(crate) struct InlineCaches {
+    property_sites: HashMap<Site, SiteCache<Shape>>,
+    method_sites: HashMap<Site, SiteCache<Class>>,
+    megamorphic: HashMap<(*const Class, Rc<str>), Entry<Class>>,
+}
+
+impl InlineCaches {
+    /// What the property `name` resolves to on `shape` at `site`, calling `resolve` on a miss.
+    pub(crate) fn lookup_property(
+        &mut self,
+        site: Site,
+        name: &Rc<str>,
+        shape: &Rc<Shape>,
+        resolve: impl FnOnce(&Rc<Shape>) -> Option<Resolved>,
+    ) -> Option<Resolved> {
+        let cache = self.property_sites.entry(site).or_insert_with(|| SiteCache::new(name));
+        if cache.is_megamorphic(name) {
+            return resolve(shape);
+        }
+        cache.lookup(shape, resolve)
+    }
+
+    /// What the method `name` resolves to on `class` at `site`, calling `resolve` on a miss.
+    pub(crate) fn lookup_method(
+        &mut self,
+        site: Site,
+        name: &Rc<str>,
+        class: &Rc<Class>,
+        resolve: impl FnOnce(&Rc<Class>) -> Option<Resolved>,
+    ) -> Option<Resolved> {
+        let cache = self.method_sites.entry(site).or_insert_with(|| SiteCache::new(name));
+        if cache.is_megamorphic(name) {
+            return self.lookup_megamorphic(name, class, resolve);
+        }
+        cache.lookup(class, resolve)
+    }
+
+    /// Resolves through the table shared by all megamorphic sites.
+    pub(crate) fn lookup_megamorphic(
+        &mut self,
+        name: &Rc<str>,
+        class: &Rc<Class>,
+        resolve: impl FnOnce(&Rc<Class>) -> Option<Resolved>,
+    ) -> Option<Resolved> {
+        let key = (Rc::as_ptr(class), name.clone());
+        if let Some(entry) = self.megamorphic.get(&key).filter(|entry| entry.matches(class)) {
+            return Some(entry.resolved.clone());
+        }
+        let resolved = resolve(class)?;
+        self.megamorphic.insert(key, Entry { key: Rc::downgrade(class), resolved: resolved.clone() });
+        Some(resolved)
+    }
+
+    pub(crate) fn state(&self, site: Site) -> CacheState {
+        match (self.property_sites.get(&site), self.method_sites.get(&site)) {
+            (Some(cache), _) => cache.state(),
+            (_, Some(cache)) => cache.state(),
+            _ => CacheState::Uninitialized,
+        }
+    }
+
+    /// Drops everything cached for `class`. Its instances' shapes die with them.
+    pub(crate) fn invalidate(&mut self, class: &Rc<Class>) {
+        for cache in self.method_sites.values_mut() {
+            cache.entries.retain(|entry| !entry.matches(class));
+        }
+        self.megamorphic.retain(|_, entry| !entry.matches(class));
+    }
+
+    /// Drops every method entry that resolved to `function`.
+    pub(crate) fn invalidate_function(&mut self, function: &Rc<Function>) {
+        let stale = |entry: &Entry<Class>| matches!(&entry.resolved, Resolved::Method(method) if Rc::ptr_eq(method, function));
+        for cache in self.method_sites.values_mut() {
+            cache.entries.retain(|entry| !stale(entry));
+        }
+        self.megamorphic.retain(|_, entry| !stale(entry));
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.property_sites.clear();
+        self.method_sites.clear();
+        self.megamorphic.clear();
+    }
+}
+
+/// The offset of the property `name` on `shape`.
+pub(crate) fn resolve_field(shape: &Shape, name: &str) -> Option<Resolved> {
+    shape.offset(name).map(Resolved::Field)
+}
+
+/// Where to store the property `name` on `shape`, adding it if it's new and `extensible`.
+pub(crate) fn resolve_store(shape: &Rc<Shape>, name: &str, extensible: bool) -> Option<Resolved> {
+    match resolve_field(shape, name) {
+        Some(field) => Some(field),
+        None if extensible => Some(Resolved::Transition(shape.with_property(name), shape.len())),
+        None => None,
+    }
+}
+
+/// The method called `name` in `class`'s vtable, so inherited or overriding.
+pub(crate) fn resolve_method(class: &Class, name: &str) -> Option<Resolved> {
+    class.vtable().iter().find(|method| method.name == name).map(|method| Resolved::Method(method.clone()))
+}
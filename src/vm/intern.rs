@@ -0,0 +1,46 @@
+//! Per-thread string interner. Every string the VM loads or builds at runtime goes through
+//! `intern`, so equal strings share one allocation and copying a `Value::Str` is a reference
+//! count bump.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
+use serde::{Deserialize, Deserializer};
+
+thread_local! {
+    static STRINGS: RefCell<HashSet<Rc<str>>> = RefCell::new(HashSet::new());
+}
+
+/// The shared copy of `s`, added to the table if it isn't there yet.
+pub fn intern(s: &str) -> Rc<str> {
+    STRINGS.with(|strings| {
+        let mut strings = strings.borrow_mut();
+        if let Some(existing) = strings.get(s) {
+            return existing.clone();
+        }
+        let interned: Rc<str> = Rc::from(s);
+        strings.insert(interned.clone());
+        interned
+    })
+}
+
+/// Drops strings nothing but the table refers to. Returns how many were dropped.
+pub fn purge() -> usize {
+    STRINGS.with(|strings| {
+        let mut strings = strings.borrow_mut();
+        let before = strings.len();
+        strings.retain(|s| Rc::strong_count(s) > 1);
+        before - strings.len()
+    })
+}
+
+/// Strings currently in this thread's table.
+pub fn interned_count() -> usize {
+    STRINGS.with(|strings| strings.borrow().len())
+}
+
+/// Deserializes a string straight into the table, for `Value::Str`.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rc<str>, D::Error> {
+    let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+    Ok(intern(&s))
+}
@@ -0,0 +1,99 @@
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::rc::Rc;
+use crate::vm::closure::Upvalue;
+use crate::vm::gc::Gc;
+use crate::vm::typed_array::ElementType;
+use crate::vm::value::Value;
+
+/// Approximate heap bytes owned directly by `value`, not counting values it refers to.
+/// Only strings, byte buffers, arrays, tuples, typed arrays, maps, sets and instances are counted; everything else lives inline in
+/// the `Value` or is shared program data.
+pub fn shallow_size(value: &Value) -> usize {
+    match value {
+        Value::Str(s) => s.len(),
+        Value::Bytes(bytes) => size_of::<Vec<u8>>() + bytes.borrow().capacity(),
+        Value::Tuple(elements) => tuple_size(elements.len()),
+        Value::Array(array) => size_of::<Vec<Value>>() + array.borrow().capacity() * size_of::<Value>(),
+        Value::Map(map) => {
+            let map = map.borrow();
+            let keys: usize = map.keys().map(String::capacity).sum();
+            size_of::<Value>() + map.capacity() * (size_of::<String>() + size_of::<Value>()) + keys
+        }
+        Value::Object(instance) => size_of::<Value>() + instance.borrow().fields.capacity() * size_of::<Value>(),
+        Value::Set(set) => set_size(set.borrow().len()),
+        Value::Int32Array(array) => typed_array_size(ElementType::Int32, array.borrow().capacity()),
+        Value::Float64Array(array) => typed_array_size(ElementType::Float64, array.borrow().capacity()),
+        Value::ByteArray(array) => typed_array_size(ElementType::Byte, array.borrow().capacity()),
+        _ => 0,
+    }
+}
+
+/// Bytes a new array of `len` elements is charged.
+pub fn array_size(len: usize) -> usize {
+    size_of::<Vec<Value>>() + len * size_of::<Value>()
+}
+
+/// Bytes a new tuple of `len` elements is charged.
+pub fn tuple_size(len: usize) -> usize {
+    len * size_of::<Value>()
+}
+
+/// Bytes a set of `len` elements is charged: the elements plus their index entries.
+pub fn set_size(len: usize) -> usize {
+    size_of::<Vec<Value>>() + len * 2 * size_of::<Value>()
+}
+
+/// Bytes a typed array of `len` elements is charged.
+pub fn typed_array_size(element: ElementType, len: usize) -> usize {
+    size_of::<Vec<u8>>() + len * element.element_size()
+}
+
+/// Bytes a new map entry under `key` is charged.
+pub fn map_entry_size(key: &str) -> usize {
+    size_of::<String>() + size_of::<Value>() + key.len()
+}
+
+/// Sums `shallow_size` over everything reachable from `roots`, counting shared values once.
+pub fn reachable_size<'a>(roots: impl IntoIterator<Item = &'a Value>) -> usize {
+    let mut seen = HashSet::new();
+    let mut pending: Vec<Value> = roots.into_iter().cloned().collect();
+    let mut total = 0;
+    while let Some(value) = pending.pop() {
+        let identity = match &value {
+            Value::Array(array) => Some(Gc::addr(array)),
+            Value::Map(map) => Some(Gc::addr(map)),
+            Value::Object(instance) => Some(Gc::addr(instance)),
+            Value::Set(set) => Some(Gc::addr(set)),
+            Value::Closure(closure) => Some(Rc::as_ptr(closure) as *const ()),
+            Value::Coroutine(coroutine) => Some(Gc::addr(coroutine)),
+            Value::Fiber(fiber) => Some(Gc::addr(fiber)),
+            Value::Int32Array(array) => Some(Gc::addr(array)),
+            Value::Float64Array(array) => Some(Gc::addr(array)),
+            Value::ByteArray(array) => Some(Gc::addr(array)),
+            Value::Bytes(bytes) => Some(Gc::addr(bytes)),
+            Value::Tuple(elements) => Some(elements.as_ptr() as *const ()),
+            _ => None,
+        };
+        if identity.is_some_and(|identity| !seen.insert(identity)) {
+            continue;
+        }
+        total += shallow_size(&value);
+        match &value {
+            Value::Array(array) => pending.extend(array.borrow().iter().cloned()),
+            Value::Map(map) => pending.extend(map.borrow().values().cloned()),
+            Value::Object(instance) => pending.extend(instance.borrow().fields.iter().cloned()),
+            Value::Tuple(elements) => pending.extend(elements.iter().cloned()),
+            Value::Set(set) => pending.extend(set.borrow().iter().cloned()),
+            // Open upvalues refer to stack slots, which are roots already.
+            Value::Closure(closure) => pending.extend(closure.upvalues.iter().filter_map(|upvalue| match &*upvalue.borrow() {
+                Upvalue::Closed(value) => Some(value.clone()),
+                Upvalue::Open(_) => None,
+            })),
+            Value::Coroutine(coroutine) => pending.extend(coroutine.borrow().values().cloned()),
+            Value::Fiber(fiber) => pending.extend(fiber.borrow().values().cloned()),
+            _ => {}
+        }
+    }
+    total
+}
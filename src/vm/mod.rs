@@ -2,5 +2,39 @@ pub mod opcode;
 pub mod chunk;
 pub mod value;
 pub mod function;
+pub mod native;
 pub mod object;
+pub mod exception;
+pub mod config;
+pub mod verifier;
+pub mod decoded;
+pub mod register;
+pub mod builder;
+pub mod memory;
+#[cfg(feature = "nan-boxing")]
+pub mod packed;
+pub mod gc;
+pub mod intern;
+pub mod bigint;
+pub mod typed_array;
+pub mod set;
+pub mod range;
+pub mod closure;
+pub mod coroutine;
+pub mod future;
+pub mod fiber;
+pub mod channel;
+pub mod monitor;
+pub mod inline_cache;
+pub mod shape;
+pub mod interrupt;
+pub mod capability;
+pub(crate) mod dylib;
+pub mod extension;
+pub mod sandbox;
+pub mod clock;
+pub mod random;
+pub mod import;
+pub mod snapshot;
+#[allow(clippy::module_inception)]
 pub mod vm;
\ No newline at end of file
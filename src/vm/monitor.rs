@@ -0,0 +1,87 @@
+//! Re-entrant locks on objects for `EnterMonitor` and `ExitMonitor`.
+//!
+//! `EnterMonitor` pops an object and takes its monitor for the running fiber; taking it
+//! again from the same fiber just counts one more level. A fiber entering a monitor another
+//! fiber holds lets the others run and tries again when it is scheduled back. `ExitMonitor`
+//! gives up one level, and a fiber that finishes releases everything it still holds.
+//!
+//! With deadlock detection on, the default in debug builds, entering a monitor whose owner
+//! is itself waiting, directly or through other fibers, for one the entering fiber holds
+//! fails with `VMError::Deadlock` instead of spinning forever.
+
+use std::collections::HashMap;
+use crate::vm::value::Value;
+
+/// The main program's id; spawned fibers use their address.
+pub(crate) const MAIN_FIBER: usize = 0;
+
+struct Monitor {
+    /// Keeps the object, and so its address, alive while the monitor is held.
+    _object: Value,
+    owner: usize,
+    depth: usize,
+}
+
+/// What happened on `Monitors::enter`.
+pub(crate) enum Entry {
+    Acquired,
+    /// Another fiber holds the monitor.
+    Contended,
+    /// Waiting would never end: the fibers form this cycle of owners.
+    Deadlock(Vec<usize>),
+}
+
+#[derive(Default)]
+pub(crate) struct Monitors {
+    held: HashMap<*const (), Monitor>,
+    /// The monitor each blocked fiber is trying to enter.
+    waiting: HashMap<usize, *const ()>,
+}
+
+impl Monitors {
+    pub(crate) fn enter(&mut self, key: *const (), object: &Value, fiber: usize, detect_deadlocks: bool) -> Entry {
+        let monitor = self.held.entry(key).or_insert_with(|| Monitor { _object: object.clone(), owner: fiber, depth: 0 });
+        if monitor.owner == fiber {
+            monitor.depth += 1;
+            self.waiting.remove(&fiber);
+            return Entry::Acquired;
+        }
+        if detect_deadlocks {
+            if let Some(cycle) = self.wait_cycle(key, fiber) {
+                self.waiting.remove(&fiber);
+                return Entry::Deadlock(cycle);
+            }
+        }
+        self.waiting.insert(fiber, key);
+        Entry::Contended
+    }
+
+    /// Gives up one level of the monitor; `false` if `fiber` doesn't hold it.
+    pub(crate) fn exit(&mut self, key: *const (), fiber: usize) -> bool {
+        let Some(monitor) = self.held.get_mut(&key).filter(|monitor| monitor.owner == fiber) else { return false };
+        monitor.depth -= 1;
+        if monitor.depth == 0 {
+            self.held.remove(&key);
+        }
+        true
+    }
+
+    pub(crate) fn release_all(&mut self, fiber: usize) {
+        self.held.retain(|_, monitor| monitor.owner != fiber);
+        self.waiting.remove(&fiber);
+    }
+
+    /// Follows owners and the monitors they wait on from `key`, looking for `fiber`.
+    fn wait_cycle(&self, key: *const (), fiber: usize) -> Option<Vec<usize>> {
+        let mut cycle = vec![fiber];
+        let mut owner = self.held.get(&key)?.owner;
+        while owner != fiber {
+            if cycle.contains(&owner) {
+                return None;
+            }
+            cycle.push(owner);
+            owner = self.held.get(self.waiting.get(&owner)?)?.owner;
+        }
+        Some(cycle)
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::rc::Rc;
+use crate::vm::gc::Gc;
+use crate::vm::intern::intern;
+use crate::vm::value::Value;
+use crate::vm::vm::{IrisVM, VMError};
+
+type NativeBody = dyn Fn(&mut IrisVM, &[Value]) -> Result<Value, VMError>;
+
+/// Host code callable from the guest. The interpreter pops the arguments and the callee,
+/// passes the arguments in call order and pushes the returned value. An `Err` is raised
+/// like any other runtime error, so guest `try` blocks can catch it.
+#[derive(Clone)]
+pub struct NativeFn(Rc<NativeBody>);
+
+impl NativeFn {
+    pub fn new(body: impl Fn(&mut IrisVM, &[Value]) -> Result<Value, VMError> + 'static) -> Self {
+        Self(Rc::new(body))
+    }
+
+    pub fn call(&self, vm: &mut IrisVM, args: &[Value]) -> Result<Value, VMError> {
+        (self.0)(vm, args)
+    }
+}
+
+impl fmt::Debug for NativeFn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
+/// Rust types a typed native can take as an argument.
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, VMError>;
+}
+
+/// Rust types a typed native can return.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        let int = match value {
+            Value::I8(v) => Some(*v as i64),
+            Value::I16(v) => Some(*v as i64),
+            Value::I32(v) => Some(*v as i64),
+            Value::I64(v) => Some(*v),
+            Value::I128(v) => i64::try_from(*v).ok(),
+            Value::U8(v) => Some(*v as i64),
+            Value::U16(v) => Some(*v as i64),
+            Value::U32(v) => Some(*v as i64),
+            Value::U64(v) => i64::try_from(*v).ok(),
+            Value::U128(v) => i64::try_from(*v).ok(),
+            _ => None,
+        };
+        int.ok_or_else(|| VMError::TypeMismatch(format!("Expected an integer argument, got {:?}", value)))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        match value {
+            Value::F32(v) => Ok(*v as f64),
+            Value::F64(v) => Ok(*v),
+            other => i64::from_value(other).map(|v| v as f64)
+                .map_err(|_| VMError::TypeMismatch(format!("Expected a numeric argument, got {:?}", value))),
+        }
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(VMError::TypeMismatch(format!("Expected a boolean argument, got {:?}", value))),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        match value {
+            Value::Str(s) => Ok(s.to_string()),
+            _ => Err(VMError::TypeMismatch(format!("Expected a string argument, got {:?}", value))),
+        }
+    }
+}
+
+impl FromValue for char {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        match value {
+            Value::Char(c) => Ok(*c),
+            _ => Err(VMError::TypeMismatch(format!("Expected a char argument, got {:?}", value))),
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    fn from_value(value: &Value) -> Result<Self, VMError> {
+        match value {
+            Value::Bytes(bytes) => Ok(bytes.borrow().clone()),
+            _ => Err(VMError::TypeMismatch(format!("Expected a bytes argument, got {:?}", value))),
+        }
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Null
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::I64(self)
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::F64(self)
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::Str(intern(&self))
+    }
+}
+
+impl IntoValue for char {
+    fn into_value(self) -> Value {
+        Value::Char(self)
+    }
+}
+
+impl IntoValue for Vec<u8> {
+    fn into_value(self) -> Value {
+        Value::Bytes(Gc::new(self))
+    }
+}
+
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        self.map_or(Value::Null, IntoValue::into_value)
+    }
+}
+
+/// Closures that can be registered with `IrisVM::register_typed_native`. `Args` is the
+/// tuple of argument types, which fixes the arity.
+pub trait TypedNative<Args> {
+    fn arity(&self) -> usize;
+    fn into_native(self) -> NativeFn;
+}
+
+fn check_arity(expected: usize, args: &[Value]) -> Result<(), VMError> {
+    if args.len() != expected {
+        return Err(VMError::ArityMismatch { expected, found: args.len() });
+    }
+    Ok(())
+}
+
+macro_rules! typed_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> TypedNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Result<R, VMError> + 'static,
+            R: IntoValue,
+            $($arg: FromValue),*
+        {
+            fn arity(&self) -> usize {
+                <[&str]>::len(&[$(stringify!($arg)),*])
+            }
+
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self) -> NativeFn {
+                let arity = self.arity();
+                NativeFn::new(move |_, args| {
+                    check_arity(arity, args)?;
+                    let mut args = args.iter();
+                    $(let $arg = $arg::from_value(args.next().unwrap())?;)*
+                    self($($arg),*).map(IntoValue::into_value)
+                })
+            }
+        }
+    };
+}
+
+typed_native!();
+typed_native!(A);
+typed_native!(A, B);
+typed_native!(A, B, C);
+typed_native!(A, B, C, D);
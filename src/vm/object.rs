@@ -1,8 +1,22 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{cell::{OnceCell, RefCell}, collections::HashMap, rc::Rc};
 use crate::vm::function::Function;
+use crate::vm::shape::Shape;
 use crate::vm::value::Value;
 use serde::{Serialize, Deserialize};
 
+/// The method `AllocateObject` calls on new instances.
+pub const CONSTRUCTOR: &str = "init";
+/// The method `InitializeClass` runs once per class.
+pub const CLASS_INITIALIZER: &str = "clinit";
+
+/// A method together with the receiver it was bound to by `BindMethodHandle`. Calling it
+/// calls the method with the receiver as its first argument.
+#[derive(Debug, Clone)]
+pub struct BoundMethod {
+    pub receiver: Value,
+    pub method: Rc<Function>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Class {
     pub name: String,
@@ -10,8 +24,21 @@ pub struct Class {
     pub superclass: Option<Rc<Class>>,
     pub methods: Vec<Rc<Function>>,
     pub properties: HashMap<String, usize>,
+    /// Whether instances can gain properties `properties` doesn't declare. Not part of the
+    /// bytecode format, so loaded classes are extensible.
+    #[serde(skip, default = "extensible_by_default")]
+    pub extensible: bool,
+    /// Made from `properties` when the first instance is, see `vm::shape`.
+    #[serde(skip)]
+    shape: OnceCell<Rc<Shape>>,
+    /// Built on first dispatch, see `vtable`.
+    #[serde(skip)]
+    vtable: RefCell<Option<VTable>>,
 }
 
+/// Methods by `InvokeMethod` slot, inherited ones included.
+pub type VTable = Rc<[Rc<Function>]>;
+
 impl Class {
     pub fn new(name: String, type_id: usize, superclass: Option<Rc<Class>>) -> Self {
         Self {
@@ -20,38 +47,104 @@ impl Class {
             superclass,
             methods: Vec::new(),
             properties: HashMap::new(),
+            extensible: true,
+            shape: OnceCell::new(),
+            vtable: RefCell::new(None),
         }
     }
 
+    /// The shape new instances start with: this class's properties and its superclasses'.
+    pub fn initial_shape(&self) -> Rc<Shape> {
+        self.shape.get_or_init(|| Rc::new(Shape::new(self.all_properties()))).clone()
+    }
+
+    fn all_properties(&self) -> HashMap<String, usize> {
+        let mut properties = self.superclass.as_ref().map(|superclass| superclass.all_properties()).unwrap_or_default();
+        properties.extend(self.properties.iter().map(|(name, slot)| (name.clone(), *slot)));
+        properties
+    }
+
     pub fn add_method(&mut self, key: usize, method: Rc<Function>) {
         self.methods.insert(key, method);
+        self.vtable = RefCell::new(None);
     }
 
     pub fn find_method(&self, key: usize) -> Option<Rc<Function>> {
-        if let Some(method) = self.methods.get(key) {
-            Some(method.clone())
-        } else if let Some(ref super_cls) = self.superclass {
-            super_cls.find_method(key)
-        } else {
-            None
+        self.vtable().get(key).cloned()
+    }
+
+    /// The superclass's table with this class's methods after it. A method named like an
+    /// inherited one takes over its slot instead, so a slot means the same method, or an
+    /// override of it, all the way down the hierarchy.
+    pub fn vtable(&self) -> VTable {
+        if let Some(vtable) = self.vtable.borrow().as_ref() {
+            return vtable.clone();
+        }
+        let mut slots = self.superclass.as_ref().map(|superclass| superclass.vtable().to_vec()).unwrap_or_default();
+        for method in &self.methods {
+            match slots.iter().position(|slot| slot.name == method.name) {
+                Some(slot) => slots[slot] = method.clone(),
+                None => slots.push(method.clone()),
+            }
         }
+        let vtable: VTable = slots.into();
+        *self.vtable.borrow_mut() = Some(vtable.clone());
+        vtable
     }
+
+    /// Replaces the table `InvokeMethod` dispatches through. Subclasses that have built
+    /// theirs already keep the methods they inherited.
+    pub fn set_vtable(&self, vtable: VTable) {
+        *self.vtable.borrow_mut() = Some(vtable);
+    }
+}
+
+fn extensible_by_default() -> bool {
+    true
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Instance {
     pub class: Rc<Class>,
+    pub shape: Rc<Shape>,
     pub fields: Vec<Value>,
 }
 
 impl Instance {
     pub fn new(class: Rc<Class>) -> Self {
         Self {
+            shape: class.initial_shape(),
             class,
             fields: Vec::new(),
         }
     }
 
+    /// The named property; null if the shape has it but it was never set.
+    pub fn get_property(&self, name: &str) -> Option<Value> {
+        let offset = self.shape.offset(name)?;
+        Some(self.fields.get(offset).cloned().unwrap_or(Value::Null))
+    }
+
+    /// Sets the named property, adding it to the instance's shape if it's new.
+    pub fn set_property(&mut self, name: &str, value: Value) {
+        let offset = match self.shape.offset(name) {
+            Some(offset) => offset,
+            None => {
+                self.shape = self.shape.with_property(name);
+                self.shape.len() - 1
+            }
+        };
+        self.set_at(offset, value);
+    }
+
+    /// Stores `value` at `offset`, growing the fields to reach it.
+    pub fn set_at(&mut self, offset: usize, value: Value) {
+        if offset >= self.fields.len() {
+            self.fields.resize(offset + 1, Value::Null);
+        }
+        self.fields[offset] = value;
+    }
+
     pub fn get_method(&self, key: usize) -> Option<Rc<Function>> {
         self.class.find_method(key)
     }
@@ -1,6 +1,12 @@
+use std::ops::RangeInclusive;
+
 /// IRIS VM - High-Performance OpCodes (No GC)
 /// Optimized for interpreter-only speed, no garbage collection.
-#[repr(u8)]
+///
+/// Opcodes up to 0xFF are encoded as a single byte. The rest live on the extended page:
+/// `EXTENDED_PREFIX` followed by the low byte of the discriminant, so `opcode as u16` is
+/// always the big-endian encoding.
+#[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum OpCode {
     Unknown = 0,
@@ -238,6 +244,8 @@ pub enum OpCode {
     AllocateSlice = 210,
 
     // == Atomics and Concurrency ==
+    // The atomics read-modify-write one `Int32Array` slot and push its old value. Fibers only
+    // switch between instructions, so no other fiber sees the slot mid-update.
     AtomicAddInt32 = 211,
     AtomicSubtractInt32 = 212,
     AtomicCompareAndSwapInt32 = 213,
@@ -257,8 +265,84 @@ pub enum OpCode {
     // == Miscellaneous ==
     PrintTopOfStack = 224,
     NoOperation = 225,
+
+    // == Weak References ==
+    CreateWeakRef = 226,
+    UpgradeWeakRef = 227,
+
+    // == Typed Arrays ==
+    NewTypedArray = 228,
+    TypedArrayGet = 229,
+    TypedArraySet = 230,
+    TypedArrayLength = 231,
+    TypedArrayFill = 232,
+
+    // == Big Integers ==
+    ConvertToBigInt = 233,
+    ConvertBigIntToInt64 = 234,
+    AddInt64Promoting = 235,
+    SubtractInt64Promoting = 236,
+    MultiplyInt64Promoting = 237,
+
+    // == Tuples (extended page) ==
+    CreateTuple = 0xFF01,
+    GetTupleElement = 0xFF02,
+    UnpackTuple = 0xFF03,
+
+    // == Sets (extended page) ==
+    CreateSet = 0xFF04,
+    SetInsert = 0xFF05,
+    SetContains = 0xFF06,
+    SetRemove = 0xFF07,
+    SetUnion = 0xFF08,
+    SetIntersection = 0xFF09,
+    SetToArray = 0xFF0A,
+    SetLength = 0xFF0B,
+
+    // == Ranges (extended page), see `vm::range` ==
+    CreateRange = 0xFF0C,
+    RangeHasNext = 0xFF0D,
+    RangeNext = 0xFF0E,
+
+    // == Closures (extended page), see `vm::closure` ==
+    CaptureUpvalue = 0xFF0F,
+    GetUpvalue = 0xFF10,
+    SetUpvalue = 0xFF11,
+    CloseUpvalue = 0xFF12,
+
+    // == Coroutines (extended page), see `vm::coroutine` ==
+    CreateCoroutine = 0xFF13,
+    ResumeCoroutine = 0xFF14,
+    Yield = 0xFF15,
+
+    // == Async (extended page), see `vm::future` ==
+    Await = 0xFF16,
+
+    // == Fibers (extended page), see `vm::fiber` ==
+    SpawnFiber = 0xFF17,
+    YieldFiber = 0xFF18,
+    JoinFiber = 0xFF19,
+
+    // == Channels (extended page), see `vm::channel` ==
+    CreateChannel = 0xFF1A,
+    ChannelSend = 0xFF1B,
+    ChannelReceive = 0xFF1C,
+    ChannelTryReceive = 0xFF1D,
+
+    // == Superinstructions (extended page), see `optimize::quicken` ==
+    /// `GetLocalVariable8 slot; AddInt32WithConstant value; SetLocalVariable8 slot`.
+    AddLocalInt32WithConstant = 0xFF1E,
+    /// `GetLocalVariable8 a; GetLocalVariable8 b; LessThanInt32; JumpIfFalse offset`.
+    JumpIfLocalsNotLessInt32 = 0xFF1F,
+
+    // == Modules (extended page), see `vm::import` ==
+    LoadModule = 0xFF20,
+    ImportSymbol = 0xFF21,
 }
 
+/// First byte of every extended-page instruction, see `OpCode`.
+pub const EXTENDED_PREFIX: u8 = 0xFF;
+
 impl From<u8> for OpCode {
     fn from(byte: u8) -> Self {
         match byte {
@@ -487,7 +571,353 @@ impl From<u8> for OpCode {
             223 => OpCode::MegamorphicMethodCall,
             224 => OpCode::PrintTopOfStack,
             225 => OpCode::NoOperation,
+            226 => OpCode::CreateWeakRef,
+            227 => OpCode::UpgradeWeakRef,
+            228 => OpCode::NewTypedArray,
+            229 => OpCode::TypedArrayGet,
+            230 => OpCode::TypedArraySet,
+            231 => OpCode::TypedArrayLength,
+            232 => OpCode::TypedArrayFill,
+            233 => OpCode::ConvertToBigInt,
+            234 => OpCode::ConvertBigIntToInt64,
+            235 => OpCode::AddInt64Promoting,
+            236 => OpCode::SubtractInt64Promoting,
+            237 => OpCode::MultiplyInt64Promoting,
+            _ => OpCode::Unknown,
+        }
+    }
+}
+impl OpCode {
+    /// The extended-page opcode whose second byte is `byte`.
+    pub fn extended(byte: u8) -> OpCode {
+        match byte {
+            0x01 => OpCode::CreateTuple,
+            0x02 => OpCode::GetTupleElement,
+            0x03 => OpCode::UnpackTuple,
+            0x04 => OpCode::CreateSet,
+            0x05 => OpCode::SetInsert,
+            0x06 => OpCode::SetContains,
+            0x07 => OpCode::SetRemove,
+            0x08 => OpCode::SetUnion,
+            0x09 => OpCode::SetIntersection,
+            0x0A => OpCode::SetToArray,
+            0x0B => OpCode::SetLength,
+            0x0C => OpCode::CreateRange,
+            0x0D => OpCode::RangeHasNext,
+            0x0E => OpCode::RangeNext,
+            0x0F => OpCode::CaptureUpvalue,
+            0x10 => OpCode::GetUpvalue,
+            0x11 => OpCode::SetUpvalue,
+            0x12 => OpCode::CloseUpvalue,
+            0x13 => OpCode::CreateCoroutine,
+            0x14 => OpCode::ResumeCoroutine,
+            0x15 => OpCode::Yield,
+            0x16 => OpCode::Await,
+            0x17 => OpCode::SpawnFiber,
+            0x18 => OpCode::YieldFiber,
+            0x19 => OpCode::JoinFiber,
+            0x1A => OpCode::CreateChannel,
+            0x1B => OpCode::ChannelSend,
+            0x1C => OpCode::ChannelReceive,
+            0x1D => OpCode::ChannelTryReceive,
+            0x1E => OpCode::AddLocalInt32WithConstant,
+            0x1F => OpCode::JumpIfLocalsNotLessInt32,
+            0x20 => OpCode::LoadModule,
+            0x21 => OpCode::ImportSymbol,
             _ => OpCode::Unknown,
         }
     }
-}
\ No newline at end of file
+
+    /// Decodes the opcode starting at `offset`, one or two bytes. Custom opcodes decode as
+    /// `Unknown`; returns `None` if an extended opcode is cut off.
+    pub fn decode(code: &[u8], offset: usize) -> Option<OpCode> {
+        match *code.get(offset)? {
+            EXTENDED_PREFIX => Some(OpCode::extended(*code.get(offset + 1)?)),
+            byte => Some(OpCode::from(byte)),
+        }
+    }
+
+    /// Bytes taken by the opcode itself: 2 on the extended page, 1 otherwise.
+    pub fn opcode_len(self) -> usize {
+        if self as u16 > 0xFF { 2 } else { 1 }
+    }
+
+    /// Looks an opcode up by its variant name, ignoring case (`PushConstant8`, `pushconstant8`).
+    pub fn from_mnemonic(name: &str) -> Option<OpCode> {
+        (1..=u8::MAX)
+            .map(OpCode::from)
+            .chain((1..=u8::MAX).map(OpCode::extended))
+            .find(|opcode| *opcode != OpCode::Unknown && format!("{:?}", opcode).eq_ignore_ascii_case(name))
+    }
+
+    /// Number of operand bytes that follow this opcode, or `None` for the switch
+    /// instructions whose length depends on their own operands.
+    pub fn operand_len(self) -> Option<usize> {
+        use OpCode::*;
+        let len = match self {
+            PushConstant8 | PickStackItem | RollStackItems | PeekStack | DropMultiple
+            | DuplicateMultiple | SwapMultiple | LoadImmediateI8 | GetLocalVariable8
+            | SetLocalVariable8 | GetGlobalVariable8 | DefineGlobalVariable8 | SetGlobalVariable8
+            | GetObjectProperty8 | SetObjectProperty8 | GetSuperClassMethod8 | DefineClass8
+            | UnconditionalJump | ShortJump | CallFunction | TailCallFunction | BeginTryBlock | FinallyBlock
+            | AddInt32WithConstant | AddInt64WithConstant | MultiplyInt32WithConstant
+            | MultiplyInt64WithConstant | CreateNewArray8 | CreateNewMap8 | GetObjectField8
+            | SetObjectField8 | NewTypedArray | CreateTuple | GetTupleElement | UnpackTuple
+            | CreateSet | GetUpvalue | SetUpvalue | AllocateObject => 1,
+
+            PushConstant16 | LoadImmediateI16 | GetLocalVariable16 | SetLocalVariable16
+            | GetObjectProperty16 | SetObjectProperty16 | GetSuperClassMethod16 | DefineClass16
+            | InvokeMethod8 | CaptureUpvalue | JumpIfTrue | JumpIfFalse | JumpIfNull | JumpIfNonNull | LoopJump
+            | CatchException | CompareAndBranchEqualInt32 | CompareAndBranchNotEqualInt32
+            | CompareAndBranchLessThanInt32 | CompareAndBranchGreaterThanInt32 | CreateNewArray16
+            | CreateNewMap16 | GetObjectField16 | SetObjectField16 | GetPropertyWithInlineCache
+            | SetPropertyWithInlineCache | LoadMethodHandle | AddLocalInt32WithConstant | LoadModule
+            | ImportSymbol => 2,
+
+            InvokeMethod16 | CallWithInlineCache | MegamorphicMethodCall => 3,
+            JumpIfLocalsNotLessInt32 => 4,
+            LoadImmediateI32 | LoadImmediateF32 => 4,
+            LoadImmediateI64 | LoadImmediateF64 => 8,
+
+            TableSwitch | LookupSwitch | RangeSwitch => return None,
+            _ => 0,
+        };
+        Some(len)
+    }
+
+    /// Sizes in bytes of the operands, in order, each read as one big-endian number. Empty for
+    /// the switch instructions, whose tables are read in place.
+    pub fn operand_widths(self) -> &'static [usize] {
+        use OpCode::*;
+        match self {
+            InvokeMethod8 | CaptureUpvalue | AddLocalInt32WithConstant => &[1, 1],
+            InvokeMethod16 | CallWithInlineCache | MegamorphicMethodCall => &[2, 1],
+            JumpIfLocalsNotLessInt32 => &[1, 1, 2],
+            _ => match self.operand_len() {
+                Some(1) => &[1],
+                Some(2) => &[2],
+                Some(4) => &[4],
+                Some(8) => &[8],
+                _ => &[],
+            },
+        }
+    }
+}
+
+/// Bytes reserved for embedder-defined instructions registered through
+/// `IrisVM::register_custom_opcode`. The form is fixed by range so bytecode stays decodable
+/// without knowing which handlers a VM has: 0xF0–0xF7 take no operand, 0xF8–0xFE take one byte.
+pub const CUSTOM_OPCODES: RangeInclusive<u8> = 0xF0..=0xFE;
+pub const CUSTOM_OPCODES_WITH_OPERAND: RangeInclusive<u8> = 0xF8..=0xFE;
+
+pub fn is_custom_opcode(byte: u8) -> bool {
+    CUSTOM_OPCODES.contains(&byte)
+}
+
+/// Length in bytes of the instruction starting at `offset`, including the opcode byte.
+/// Returns `None` if the instruction is truncated or its operands are malformed.
+pub fn instruction_len(code: &[u8], offset: usize) -> Option<usize> {
+    let byte = *code.get(offset)?;
+    if is_custom_opcode(byte) {
+        let len = if CUSTOM_OPCODES_WITH_OPERAND.contains(&byte) { 2 } else { 1 };
+        return (offset + len <= code.len()).then_some(len);
+    }
+    let opcode = OpCode::decode(code, offset)?;
+    if byte == EXTENDED_PREFIX && opcode == OpCode::Unknown {
+        return Some(2);
+    }
+    let read_u16 = |at: usize| -> Option<usize> {
+        Some(u16::from_be_bytes([*code.get(at)?, *code.get(at + 1)?]) as usize)
+    };
+    let read_i32 = |at: usize| -> Option<i32> {
+        let bytes = code.get(at..at + 4)?;
+        Some(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+
+    let len = match opcode.operand_len() {
+        Some(operands) => opcode.opcode_len() + operands,
+        None => match opcode {
+            // default:u16 low:i32 high:i32 offsets:u16[high - low + 1]
+            OpCode::TableSwitch => {
+                let low = read_i32(offset + 3)?;
+                let high = read_i32(offset + 7)?;
+                if low > high {
+                    return None;
+                }
+                11 + (high as i64 - low as i64 + 1) as usize * 2
+            }
+            // default:u16 count:u16 (key:i32 offset:u16)[count]
+            OpCode::LookupSwitch => 5 + read_u16(offset + 3)? * 6,
+            // default:u16 count:u16 (start:i32 end:i32 offset:u16)[count]
+            OpCode::RangeSwitch => 5 + read_u16(offset + 3)? * 10,
+            _ => unreachable!(),
+        },
+    };
+
+    if offset + len > code.len() {
+        return None;
+    }
+    Some(len)
+}
+
+/// Offsets execution can continue at after the instruction starting at `offset`,
+/// including the fall-through and any try handler it installs. Returns `None` if the
+/// instruction is malformed or jumps before the start of the code.
+pub fn successors(code: &[u8], offset: usize) -> Option<Vec<usize>> {
+    let len = instruction_len(code, offset)?;
+    let next = offset + len;
+    let u8_at = |at: usize| code[at] as usize;
+    let u16_at = |at: usize| u16::from_be_bytes([code[at], code[at + 1]]) as usize;
+
+    let targets = match OpCode::decode(code, offset)? {
+        OpCode::ReturnFromFunction | OpCode::ThrowException | OpCode::TailCallFunction => vec![],
+        OpCode::UnconditionalJump => vec![next + u8_at(offset + 1)],
+        OpCode::ShortJump => {
+            let target = next as isize + code[offset + 1] as i8 as isize;
+            vec![usize::try_from(target).ok()?]
+        }
+        OpCode::LoopJump => vec![next.checked_sub(u16_at(offset + 1))?],
+        OpCode::JumpIfTrue | OpCode::JumpIfFalse | OpCode::JumpIfNull | OpCode::JumpIfNonNull
+        | OpCode::CompareAndBranchEqualInt32 | OpCode::CompareAndBranchNotEqualInt32
+        | OpCode::CompareAndBranchLessThanInt32 | OpCode::CompareAndBranchGreaterThanInt32 => {
+            vec![next, next + u16_at(offset + 1)]
+        }
+        OpCode::BeginTryBlock | OpCode::FinallyBlock => vec![next, next + u8_at(offset + 1)],
+        OpCode::JumpIfLocalsNotLessInt32 => vec![next, next + u16_at(offset + 4)],
+        OpCode::TableSwitch => {
+            let mut targets = vec![offset + u16_at(offset + 1)];
+            let mut at = offset + 11;
+            while at < next {
+                targets.push(offset + u16_at(at));
+                at += 2;
+            }
+            targets
+        }
+        OpCode::LookupSwitch => {
+            let mut targets = vec![offset + u16_at(offset + 1)];
+            let mut at = offset + 5;
+            while at < next {
+                targets.push(offset + u16_at(at + 4));
+                at += 6;
+            }
+            targets
+        }
+        OpCode::RangeSwitch => {
+            let mut targets = vec![offset + u16_at(offset + 1)];
+            let mut at = offset + 5;
+            while at < next {
+                targets.push(offset + u16_at(at + 8));
+                at += 10;
+            }
+            targets
+        }
+        _ => vec![next],
+    };
+    Some(targets)
+}
+
+/// Number of values `(popped, pushed)` by the instruction starting at `offset`. Returns
+/// `None` for custom opcodes and for instructions whose stack behaviour isn't defined yet.
+pub fn stack_effect(code: &[u8], offset: usize) -> Option<(usize, usize)> {
+    use OpCode::*;
+    instruction_len(code, offset)?;
+    if is_custom_opcode(code[offset]) {
+        return None;
+    }
+    let u8_at = |at: usize| code[offset + at] as usize;
+    let u16_at = |at: usize| u16::from_be_bytes([code[offset + at], code[offset + at + 1]]) as usize;
+
+    let effect = match OpCode::decode(code, offset)? {
+        PushConstant8 | PushConstant16 | PushNull | PushTrue | PushFalse | LoadImmediateI8
+        | LoadImmediateI16 | LoadImmediateI32 | LoadImmediateI64 | LoadImmediateF32 | LoadImmediateF64
+        | GetLocalVariable8 | GetLocalVariable16 | GetGlobalVariable8 | DefineClass8 | DefineClass16 => (0, 1),
+
+        NoOperation | UnconditionalJump | ShortJump | LoopJump | LoopStartMarker | LoopEndMarker
+        | BeginTryBlock | FinallyBlock | EndTryBlock | JumpIfLocalsNotLessInt32 => (0, 0),
+
+        PopStack | DefineGlobalVariable8 | InitializeClass | FreeObject | PrintTopOfStack | JumpIfTrue | JumpIfFalse | JumpIfNull
+        | JumpIfNonNull | ThrowException | UnwindStack | ReturnFromFunction | TableSwitch | LookupSwitch | RangeSwitch => (1, 0),
+
+        DuplicateTop => (1, 2),
+        SwapTopTwo => (2, 2),
+        RotateTopThree => (3, 3),
+        SwapTopTwoPairs => (4, 4),
+        PeekStack | PickStackItem => (u8_at(1) + 1, u8_at(1) + 2),
+        RollStackItems => (u8_at(1), u8_at(1)),
+        DropMultiple => (u8_at(1), 0),
+        DuplicateMultiple => (u8_at(1), u8_at(1) * 2),
+        SwapMultiple => (u8_at(1) * 2, u8_at(1) * 2),
+
+        SetLocalVariable8 | SetLocalVariable16 | SetGlobalVariable8 | CreateNewInstance | GetObjectProperty8
+        | GetObjectProperty16 | GetPropertyWithInlineCache | GetObjectField8 | GetObjectField16 | GetArrayLength | LogicalNotOperation
+        | BitwiseNotInt32 | BitwiseNotInt64 | NegateInt32 | NegateInt64 | NegateFloat32 | NegateFloat64
+        | IncrementInt32 | DecrementInt32 | IncrementInt64 | DecrementInt64 | AddInt32WithConstant
+        | AddInt64WithConstant | MultiplyInt32WithConstant | MultiplyInt64WithConstant | AbsoluteInt32
+        | AbsoluteInt64 | AbsoluteFloat32 | AbsoluteFloat64 | FloorFloat32 | CeilFloat32 | RoundFloat32
+        | TruncateFloat32 | SquareRootFloat32 | SquareRootFloat64 | ConvertInt32ToInt64 | ConvertInt32ToFloat32
+        | ConvertInt32ToFloat64 | ConvertInt64ToInt32 | ConvertInt64ToFloat32 | ConvertInt64ToFloat64
+        | ConvertFloat32ToInt32 | ConvertFloat32ToInt64 | ConvertFloat32ToFloat64 | ConvertFloat64ToInt32
+        | ConvertFloat64ToInt64 | ConvertFloat64ToFloat32 | CreateWeakRef | UpgradeWeakRef
+        | NewTypedArray | TypedArrayLength | ConvertToBigInt | ConvertBigIntToInt64
+        | SetToArray | SetLength | LoadMethodHandle => (1, 1),
+
+        LogicalAndOperation | LogicalOrOperation | BooleanAndOperation | BooleanOrOperation | BitwiseAndInt32
+        | BitwiseAndInt64 | BitwiseOrInt32 | BitwiseOrInt64 | BitwiseXorInt32 | BitwiseXorInt64 | LeftShiftInt32
+        | LeftShiftInt64 | RightShiftInt32 | RightShiftInt64 | UnsignedRightShiftInt32 | UnsignedRightShiftInt64
+        | RotateLeftInt32 | RotateRightInt32 | AddInt32 | AddInt64 | AddFloat32 | AddFloat64 | SubtractInt32
+        | SubtractInt64 | SubtractFloat32 | SubtractFloat64 | MultiplyInt32 | MultiplyInt64 | MultiplyFloat32
+        | MultiplyFloat64 | DivideInt32 | DivideInt64 | DivideFloat32 | DivideFloat64 | ModuloInt32 | ModuloInt64
+        | EqualInt32 | EqualInt64 | EqualFloat32 | EqualFloat64 | NotEqualInt32 | NotEqualInt64 | NotEqualFloat32
+        | NotEqualFloat64 | GreaterThanInt32 | GreaterThanInt64 | GreaterThanFloat32 | GreaterThanFloat64
+        | LessThanInt32 | LessThanInt64 | LessThanFloat32 | LessThanFloat64 | GreaterOrEqualInt32
+        | GreaterOrEqualInt64 | GreaterOrEqualFloat32 | GreaterOrEqualFloat64 | LessOrEqualInt32 | LessOrEqualInt64
+        | LessOrEqualFloat32 | LessOrEqualFloat64 | GreaterUnsigned8 | GreaterUnsigned16 | GreaterUnsigned32
+        | GreaterUnsigned64 | LessUnsigned8 | LessUnsigned16 | LessUnsigned32 | LessUnsigned64
+        | GreaterOrEqualUnsigned8 | GreaterOrEqualUnsigned16 | GreaterOrEqualUnsigned32 | GreaterOrEqualUnsigned64
+        | LessOrEqualUnsigned8 | LessOrEqualUnsigned16 | LessOrEqualUnsigned32 | LessOrEqualUnsigned64
+        | GetArrayIndexInt32 | GetArrayIndexFloat32 | GetArrayIndexFastInt32 | MapContainsKey | MapRemoveKey
+        | CheckCastObject | InstanceOfCheck | GetSuperClassMethod8 | GetSuperClassMethod16 | TypedArrayGet
+        | AddInt64Promoting | SubtractInt64Promoting | MultiplyInt64Promoting | SetContains | SetRemove
+        | SetUnion | SetIntersection => (2, 1),
+        CreateRange => (3, 1),
+        RangeHasNext | RangeNext | ChannelTryReceive => (1, 2),
+        CaptureUpvalue | SetUpvalue | CreateCoroutine | Yield | Await | SpawnFiber
+        | JoinFiber | ChannelReceive => (1, 1),
+        YieldFiber | YieldCurrentThread => (0, 0),
+        EnterMonitor | ExitMonitor => (1, 0),
+        ChannelSend => (2, 0),
+        ResumeCoroutine => (2, 1),
+        GetUpvalue | CreateChannel | LoadModule => (0, 1),
+        ImportSymbol => (1, 1),
+        CloseUpvalue => (1, 0),
+
+        SetObjectProperty8 | SetObjectProperty16 | SetPropertyWithInlineCache | SetObjectField8 | SetObjectField16 | ResizeArray
+        | CompareAndBranchEqualInt32 | CompareAndBranchNotEqualInt32 | CompareAndBranchLessThanInt32
+        | CompareAndBranchGreaterThanInt32 | TypedArrayFill | SetInsert => (2, 0),
+
+        BindMethodHandle => (2, 1),
+        GetVirtualTable | CatchException => (1, 1),
+        AddLocalInt32WithConstant => (0, 1),
+        SetVirtualTable => (2, 0),
+        FusedMultiplyAddFloat32 | FusedMultiplyAddFloat64 | MapGetOrDefaultValue | AllocateSlice
+        | AtomicAddInt32 | AtomicSubtractInt32 => (3, 1),
+        AtomicCompareAndSwapInt32 => (4, 1),
+        SetArrayIndexInt32 | SetArrayIndexFloat32 | SetArrayIndexFastInt32 | TypedArraySet => (3, 0),
+
+        CreateNewArray8 => (u8_at(1), 1),
+        CreateNewArray16 => (u16_at(1), 1),
+        CreateNewMap8 => (u8_at(1) * 2, 1),
+        CreateNewMap16 => (u16_at(1) * 2, 1),
+        CallFunction | AllocateObject => (u8_at(1) + 1, 1),
+        TailCallFunction => (u8_at(1) + 1, 0),
+        InvokeMethod8 => (u8_at(2) + 1, 1),
+        InvokeMethod16 | CallWithInlineCache | MegamorphicMethodCall => (u8_at(3) + 1, 1),
+        CreateTuple => (u8_at(2), 1),
+        GetTupleElement => (1, 1),
+        UnpackTuple => (1, u8_at(2)),
+        CreateSet => (u8_at(2), 1),
+
+        _ => return None,
+    };
+    Some(effect)
+}
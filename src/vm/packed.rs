@@ -0,0 +1,192 @@
+//! A one-word encoding of `Value`, enabled with the `nan-boxing` feature.
+//!
+//! Doubles are stored as their own bits, with every NaN canonicalized to a single quiet NaN.
+//! That frees the other NaN bit patterns with the sign and the top mantissa bit set, whose
+//! low 51 bits carry a 3-bit tag and a 48-bit payload:
+//!
+//! * `NULL`: null, `false` and `true` as payload 0, 1 and 2.
+//! * `SMALL_INT`: `I8` to `U32`, the kind in bits 32..40 and the integer in the low 32 bits.
+//! * `F32`: the float's bits.
+//! * `BOXED`: an `Rc<Value>` pointer, for everything wider or heap allocated.
+
+use std::fmt;
+use std::rc::Rc;
+use crate::vm::value::Value;
+
+const TAGGED: u64 = 0xFFF8_0000_0000_0000;
+const CANONICAL_NAN: u64 = 0x7FF8_0000_0000_0000;
+const PAYLOAD: u64 = 0x0000_FFFF_FFFF_FFFF;
+const TAG_SHIFT: u32 = 48;
+
+const TAG_NULL: u64 = 0;
+const TAG_SMALL_INT: u64 = 1;
+const TAG_F32: u64 = 2;
+const TAG_BOXED: u64 = 3;
+
+const KIND_I8: u64 = 0;
+const KIND_I16: u64 = 1;
+const KIND_I32: u64 = 2;
+const KIND_U8: u64 = 3;
+const KIND_U16: u64 = 4;
+const KIND_U32: u64 = 5;
+
+/// A `Value` packed into 64 bits, see the module docs.
+pub struct PackedValue(u64);
+
+const _: () = assert!(std::mem::size_of::<PackedValue>() == 8);
+
+impl PackedValue {
+    fn tagged(tag: u64, payload: u64) -> Self {
+        PackedValue(TAGGED | tag << TAG_SHIFT | payload & PAYLOAD)
+    }
+
+    fn small_int(kind: u64, bits: u32) -> Self {
+        Self::tagged(TAG_SMALL_INT, kind << 32 | bits as u64)
+    }
+
+    fn boxed(value: Value) -> Self {
+        let pointer = Rc::into_raw(Rc::new(value)) as u64;
+        assert_eq!(pointer & !PAYLOAD, 0, "pointer does not fit in a NaN-boxed payload");
+        Self::tagged(TAG_BOXED, pointer)
+    }
+
+    pub fn null() -> Self {
+        Self::tagged(TAG_NULL, 0)
+    }
+
+    pub fn from_f64(value: f64) -> Self {
+        PackedValue(if value.is_nan() { CANONICAL_NAN } else { value.to_bits() })
+    }
+
+    /// The raw encoding.
+    pub fn bits(&self) -> u64 {
+        self.0
+    }
+
+    fn tag(&self) -> Option<u64> {
+        (self.0 & TAGGED == TAGGED).then_some((self.0 >> TAG_SHIFT) & 0x7)
+    }
+
+    fn payload(&self) -> u64 {
+        self.0 & PAYLOAD
+    }
+
+    pub fn is_f64(&self) -> bool {
+        self.tag().is_none()
+    }
+
+    /// Whether the value lives behind a pointer rather than in the word itself.
+    pub fn is_boxed(&self) -> bool {
+        self.tag() == Some(TAG_BOXED)
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        self.is_f64().then(|| f64::from_bits(self.0))
+    }
+
+    fn as_boxed(&self) -> Option<*const Value> {
+        self.is_boxed().then(|| self.payload() as *const Value)
+    }
+
+    /// Unpacks a copy of the value.
+    pub fn to_value(&self) -> Value {
+        let payload = self.payload();
+        match self.tag() {
+            None => Value::F64(f64::from_bits(self.0)),
+            Some(TAG_NULL) => match payload {
+                0 => Value::Null,
+                flag => Value::Bool(flag == 2),
+            },
+            Some(TAG_SMALL_INT) => {
+                let bits = payload as u32;
+                match payload >> 32 {
+                    KIND_I8 => Value::I8(bits as i8),
+                    KIND_I16 => Value::I16(bits as i16),
+                    KIND_I32 => Value::I32(bits as i32),
+                    KIND_U8 => Value::U8(bits as u8),
+                    KIND_U16 => Value::U16(bits as u16),
+                    _ => Value::U32(bits),
+                }
+            }
+            Some(TAG_F32) => Value::F32(f32::from_bits(payload as u32)),
+            // SAFETY: boxed payloads come from `Rc::into_raw` and this word still owns its
+            // reference.
+            _ => unsafe { (*(payload as *const Value)).clone() },
+        }
+    }
+}
+
+impl From<Value> for PackedValue {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::null(),
+            Value::Bool(flag) => Self::tagged(TAG_NULL, 1 + flag as u64),
+            Value::I8(i) => Self::small_int(KIND_I8, i as u32),
+            Value::I16(i) => Self::small_int(KIND_I16, i as u32),
+            Value::I32(i) => Self::small_int(KIND_I32, i as u32),
+            Value::U8(i) => Self::small_int(KIND_U8, i as u32),
+            Value::U16(i) => Self::small_int(KIND_U16, i as u32),
+            Value::U32(i) => Self::small_int(KIND_U32, i),
+            Value::F32(f) => Self::tagged(TAG_F32, f.to_bits() as u64),
+            Value::F64(f) => Self::from_f64(f),
+            other => Self::boxed(other),
+        }
+    }
+}
+
+impl From<&Value> for PackedValue {
+    fn from(value: &Value) -> Self {
+        value.clone().into()
+    }
+}
+
+impl From<PackedValue> for Value {
+    fn from(packed: PackedValue) -> Self {
+        let Some(pointer) = packed.as_boxed() else { return packed.to_value() };
+        std::mem::forget(packed);
+        // SAFETY: ownership of the boxed reference moves out of the forgotten word.
+        let rc = unsafe { Rc::from_raw(pointer) };
+        Rc::try_unwrap(rc).unwrap_or_else(|rc| (*rc).clone())
+    }
+}
+
+impl Clone for PackedValue {
+    fn clone(&self) -> Self {
+        if let Some(pointer) = self.as_boxed() {
+            // SAFETY: the pointer is a live `Rc<Value>` owned by `self`.
+            unsafe { Rc::increment_strong_count(pointer) };
+        }
+        PackedValue(self.0)
+    }
+}
+
+impl Drop for PackedValue {
+    fn drop(&mut self) {
+        if let Some(pointer) = self.as_boxed() {
+            // SAFETY: releases the reference this word owns.
+            unsafe { Rc::decrement_strong_count(pointer) };
+        }
+    }
+}
+
+impl PartialEq for PackedValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_value() == other.to_value()
+    }
+}
+
+impl fmt::Debug for PackedValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Packed({:?})", self.to_value())
+    }
+}
+
+/// Packs every value of a stack or constant pool.
+pub fn pack(values: &[Value]) -> Vec<PackedValue> {
+    values.iter().map(PackedValue::from).collect()
+}
+
+/// The inverse of `pack`.
+pub fn unpack(values: &[PackedValue]) -> Vec<Value> {
+    values.iter().map(PackedValue::to_value).collect()
+}
@@ -0,0 +1,64 @@
+//! `Value::Range`: a half-open run of `i64`s from `start` towards `end` by `step`.
+//!
+//! A range is its own iterator: `RangeNext` pushes the first element and the range of the
+//! ones after it, so a numeric for-loop keeps a single value on the stack:
+//!
+//! ```text
+//!         <start> <end> <step>
+//!         CreateRange
+//! loop:   RangeHasNext
+//!         JumpIfFalse done
+//!         RangeNext          ; rest, i
+//!         <body, consuming i>
+//!         LoopJump loop
+//! done:   PopStack
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+    pub step: i64,
+}
+
+impl Range {
+    /// `None` if `step` is zero.
+    pub fn new(start: i64, end: i64, step: i64) -> Option<Range> {
+        (step != 0).then_some(Range { start, end, step })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        if self.step > 0 { self.start >= self.end } else { self.start <= self.end }
+    }
+
+    pub fn len(&self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        let distance = (self.end as i128 - self.start as i128).unsigned_abs();
+        let step = (self.step as i128).unsigned_abs();
+        distance.div_ceil(step) as u64
+    }
+
+    /// The first element and the range of the rest, or `None` once exhausted.
+    pub fn split_first(&self) -> Option<(i64, Range)> {
+        if self.is_empty() {
+            return None;
+        }
+        // Stepping past i64's range means there's nothing left.
+        let start = self.start.checked_add(self.step).unwrap_or(self.end);
+        Some((self.start, Range { start, ..*self }))
+    }
+}
+
+impl Iterator for Range {
+    type Item = i64;
+
+    fn next(&mut self) -> Option<i64> {
+        let (first, rest) = self.split_first()?;
+        *self = rest;
+        Some(first)
+    }
+}
@@ -0,0 +1,265 @@
+//! Register-form function bodies, translated from stack bytecode.
+//!
+//! Stack bytecode stays the interchange format: it is what gets serialized, verified and
+//! disassembled. `translate_function` gives a function a register-form body as well, which the
+//! interpreter runs in its place. The registers are the frame's stack slots, so arguments and
+//! locals are the lowest registers and the operand stack continues above them. Loading a
+//! local only to use it once becomes an operand that names the local's register, and an
+//! operation writes its result straight to the slot it would have been pushed to.
+//!
+//! Only Int32 code without calls translates: constants, locals, Int32 arithmetic and
+//! comparisons, jumps and returns. At every jump target the registers hold exactly what the
+//! operand stack would, so the VM can carry on with the bytecode from there; it does when
+//! interrupted or out of fuel on a backward jump. It works the other way round too: a frame
+//! running bytecode can switch to the register form at a jump target, which is how a loop
+//! that gets hot moves up a tier mid-call. Breakpoints, stats, coverage and profiling
+//! see every bytecode instruction, so while any of them is on functions run as bytecode.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use crate::vm::decoded::DecodedCode;
+use crate::vm::function::Function;
+use crate::vm::opcode::OpCode;
+use crate::vm::value::Value;
+use crate::vm::verifier::{verify, VerifiedFunction, VerifyError};
+
+/// A stack slot of the frame, counted from its base.
+pub type Register = usize;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegInstr {
+    LoadConstant { dst: Register, index: usize },
+    LoadValue { dst: Register, value: Value },
+    Move { dst: Register, src: Register },
+    /// `dst = a op b` for an Int32 arithmetic or comparison opcode.
+    Binary { op: OpCode, dst: Register, a: Register, b: Register },
+    AddConstant { dst: Register, src: Register, constant: i32 },
+    /// Jumps index `RegisterCode::blocks`.
+    Jump { target: usize },
+    JumpIfFalse { condition: Register, target: usize },
+    /// Jumps unless `a < b`, like `JumpIfLocalsNotLessInt32`.
+    JumpIfNotLess { a: Register, b: Register, target: usize },
+    Return { src: Register },
+}
+
+/// A jump target: where it starts in the register body and in the bytecode, and the stack
+/// depth there.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Block {
+    pub pc: usize,
+    pub offset: usize,
+    pub depth: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterCode {
+    pub instructions: Vec<RegInstr>,
+    pub blocks: Vec<Block>,
+    /// Registers the body uses: the function's maximum stack depth.
+    pub registers: usize,
+}
+
+impl RegisterCode {
+    /// The block starting at bytecode `offset`, where a frame running the bytecode can switch
+    /// to this body.
+    pub fn block_at(&self, offset: usize) -> Option<&Block> {
+        self.blocks.binary_search_by_key(&offset, |block| block.offset).ok().map(|index| &self.blocks[index])
+    }
+}
+
+#[derive(Debug)]
+pub enum TranslateError {
+    Verify(VerifyError),
+    Unsupported { offset: usize, opcode: OpCode },
+    /// Execution can run off the end of the code, which returns without a value.
+    FallsOffEnd,
+}
+
+impl fmt::Display for TranslateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranslateError::Verify(error) => write!(f, "{}", error),
+            TranslateError::Unsupported { offset, opcode } => write!(f, "{:?} at offset {} has no register form", opcode, offset),
+            TranslateError::FallsOffEnd => write!(f, "Execution can run off the end of the code"),
+        }
+    }
+}
+
+impl Error for TranslateError {}
+
+impl From<VerifyError> for TranslateError {
+    fn from(error: VerifyError) -> Self {
+        TranslateError::Verify(error)
+    }
+}
+
+/// Verifies `function` and gives it a register-form body. Returns `false`, leaving it alone,
+/// if the function is shared.
+pub fn translate_function(function: &mut Rc<Function>) -> Result<bool, TranslateError> {
+    let body = translate(&verify(function)?)?;
+    match Rc::get_mut(function) {
+        Some(function) => {
+            function.registers = Some(Rc::new(body));
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+pub fn translate(verified: &VerifiedFunction) -> Result<RegisterCode, TranslateError> {
+    let code = verified.function().bytecode.as_deref().unwrap_or_default();
+    if verified.stack_depth_at(code.len()).is_some() {
+        return Err(TranslateError::FallsOffEnd);
+    }
+    let decoded = DecodedCode::new(code);
+    let instructions: Vec<_> = decoded.instructions().iter()
+        .scan(0, |offset, instruction| Some((std::mem::replace(offset, instruction.next), instruction)))
+        .filter_map(|(offset, instruction)| Some((offset, verified.stack_depth_at(offset)?, instruction)))
+        .collect();
+
+    let mut blocks: BTreeMap<usize, usize> = instructions.iter()
+        .filter_map(|(_, _, instruction)| jump_target(instruction.opcode, instruction.next, instruction.operands()))
+        .map(|target| (target, 0))
+        .collect();
+    for (index, block) in blocks.values_mut().enumerate() {
+        *block = index;
+    }
+
+    let mut translator = Translator {
+        body: RegisterCode { instructions: Vec::new(), blocks: Vec::new(), registers: verified.max_stack_depth() },
+        aliases: vec![None; verified.max_stack_depth() + 1],
+    };
+    for (offset, depth, instruction) in instructions {
+        if blocks.contains_key(&offset) {
+            translator.materialize_all();
+            translator.body.blocks.push(Block { pc: translator.body.instructions.len(), offset, depth });
+        }
+        let target = jump_target(instruction.opcode, instruction.next, instruction.operands()).map(|target| blocks[&target]);
+        translator.translate(instruction.opcode, instruction.operands(), depth, target)
+            .ok_or(TranslateError::Unsupported { offset, opcode: instruction.opcode })?;
+    }
+    Ok(translator.body)
+}
+
+fn jump_target(opcode: OpCode, next: usize, operands: &[u64]) -> Option<usize> {
+    match opcode {
+        OpCode::UnconditionalJump | OpCode::JumpIfFalse => Some(next + operands[0] as usize),
+        OpCode::ShortJump => next.checked_add_signed(operands[0] as u8 as i8 as isize),
+        OpCode::LoopJump => next.checked_sub(operands[0] as usize),
+        OpCode::JumpIfLocalsNotLessInt32 => Some(next + operands[2] as usize),
+        _ => None,
+    }
+}
+
+struct Translator {
+    body: RegisterCode,
+    /// Stack slots whose value hasn't been copied in yet because it is still in the register
+    /// of the local it was loaded from.
+    aliases: Vec<Option<Register>>,
+}
+
+impl Translator {
+    /// The register holding the value of stack slot `slot`.
+    fn read(&self, slot: usize) -> Register {
+        self.aliases[slot].unwrap_or(slot)
+    }
+
+    fn materialize(&mut self, slot: usize) {
+        if let Some(src) = self.aliases[slot].take() {
+            self.body.instructions.push(RegInstr::Move { dst: slot, src });
+        }
+    }
+
+    fn materialize_all(&mut self) {
+        for slot in 0..self.aliases.len() {
+            self.materialize(slot);
+        }
+    }
+
+    /// Emits `instruction`, which writes `dst`, once no slot still relies on the old value.
+    fn write(&mut self, dst: Register, instruction: RegInstr) {
+        for slot in 0..self.aliases.len() {
+            if self.aliases[slot] == Some(dst) {
+                self.materialize(slot);
+            }
+        }
+        self.aliases[dst] = None;
+        self.body.instructions.push(instruction);
+    }
+
+    /// Translates one instruction entered at stack depth `depth`; `None` if it has no
+    /// register form.
+    fn translate(&mut self, opcode: OpCode, operands: &[u64], depth: usize, target: Option<usize>) -> Option<()> {
+        use OpCode::*;
+        let top = depth.wrapping_sub(1);
+        let value = match opcode {
+            LoadImmediateI8 => Some(Value::I8(operands[0] as u8 as i8)),
+            LoadImmediateI16 => Some(Value::I16(operands[0] as u16 as i16)),
+            LoadImmediateI32 => Some(Value::I32(operands[0] as u32 as i32)),
+            LoadImmediateI64 => Some(Value::I64(operands[0] as i64)),
+            PushNull => Some(Value::Null),
+            PushTrue => Some(Value::Bool(true)),
+            PushFalse => Some(Value::Bool(false)),
+            _ => None,
+        };
+        if let Some(value) = value {
+            self.write(depth, RegInstr::LoadValue { dst: depth, value });
+            return Some(());
+        }
+        match opcode {
+            NoOperation => {}
+            PushConstant8 | PushConstant16 => {
+                self.write(depth, RegInstr::LoadConstant { dst: depth, index: operands[0] as usize });
+            }
+            GetLocalVariable8 | GetLocalVariable16 => self.aliases[depth] = Some(self.read(operands[0] as usize)),
+            SetLocalVariable8 | SetLocalVariable16 => {
+                let (dst, src) = (operands[0] as usize, self.read(top));
+                if dst != src {
+                    self.write(dst, RegInstr::Move { dst, src });
+                }
+            }
+            PopStack => self.aliases[top] = None,
+            DuplicateTop => self.aliases[depth] = Some(self.read(top)),
+            AddInt32 | SubtractInt32 | MultiplyInt32 | LessThanInt32 | GreaterThanInt32 | GreaterOrEqualInt32
+            | LessOrEqualInt32 | EqualInt32 | NotEqualInt32 => {
+                let (a, b) = (self.read(top - 1), self.read(top));
+                self.aliases[top] = None;
+                self.write(top - 1, RegInstr::Binary { op: opcode, dst: top - 1, a, b });
+            }
+            AddInt32WithConstant => {
+                let src = self.read(top);
+                self.write(top, RegInstr::AddConstant { dst: top, src, constant: operands[0] as u8 as i8 as i32 });
+            }
+            AddLocalInt32WithConstant => {
+                let slot = operands[0] as usize;
+                let src = self.read(slot);
+                self.write(slot, RegInstr::AddConstant { dst: slot, src, constant: operands[1] as u8 as i8 as i32 });
+                self.aliases[depth] = Some(slot);
+            }
+            UnconditionalJump | ShortJump | LoopJump => {
+                self.materialize_all();
+                self.body.instructions.push(RegInstr::Jump { target: target? });
+            }
+            JumpIfFalse => {
+                let condition = self.read(top);
+                self.aliases[top] = None;
+                self.materialize_all();
+                self.body.instructions.push(RegInstr::JumpIfFalse { condition, target: target? });
+            }
+            JumpIfLocalsNotLessInt32 => {
+                let (a, b) = (self.read(operands[0] as usize), self.read(operands[1] as usize));
+                self.materialize_all();
+                self.body.instructions.push(RegInstr::JumpIfNotLess { a, b, target: target? });
+            }
+            ReturnFromFunction => {
+                let src = self.read(top);
+                self.body.instructions.push(RegInstr::Return { src });
+                self.aliases.fill(None);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
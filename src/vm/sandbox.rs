@@ -0,0 +1,95 @@
+//! What the `fs` natives may touch: a list of root directories, optionally read-only.
+//!
+//! With no roots, the default, every access is denied. Otherwise a path is allowed if it
+//! resolves inside one of the roots. Relative paths are taken from the first root, and
+//! paths are resolved through `..` and symbolic links before they are checked, so neither
+//! leads out of a root. A path that doesn't exist yet, such as a file about to be written,
+//! is checked through its parent directory; a symbolic link to such a path is denied, as
+//! writing through it would create its target wherever it points.
+//!
+//! ```
+//! use iris_vm::vm::sandbox::FsPolicy;
+//!
+//! let policy = FsPolicy::new().allow_root(std::env::temp_dir()).read_only(true);
+//! assert!(policy.check("/", false).is_err());
+//! assert!(policy.check(".", true).is_err());
+//! ```
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use crate::vm::vm::VMError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FsPolicy {
+    roots: Vec<PathBuf>,
+    read_only: bool,
+}
+
+/// Denies everything, whatever policy the host set; see `IrisVM::make_deterministic`.
+pub(crate) static DISABLED: FsPolicy = FsPolicy { roots: Vec::new(), read_only: false };
+
+impl FsPolicy {
+    /// Denies everything until a root is allowed.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    /// Denies writes, leaving reads to the roots.
+    pub fn read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.roots.is_empty()
+    }
+
+    /// Resolves `path` and checks it is inside a root, and that the policy allows writing
+    /// if `write`.
+    pub fn check(&self, path: &str, write: bool) -> Result<PathBuf, VMError> {
+        let first = self.roots.first().ok_or_else(|| VMError::AccessDenied("file system access is disabled".to_string()))?;
+        if write && self.read_only {
+            return Err(VMError::AccessDenied(format!("'{}' cannot be written, the file system is read-only", path)));
+        }
+        let resolved = resolve(&first.join(path)).map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => VMError::AccessDenied(format!("'{}': {}", path, e)),
+            _ => VMError::Io(format!("'{}': {}", path, e)),
+        })?;
+        let inside = self.roots.iter().any(|root| root.canonicalize().is_ok_and(|root| resolved.starts_with(root)));
+        if !inside {
+            return Err(VMError::AccessDenied(format!("'{}' is outside the allowed directories", path)));
+        }
+        Ok(resolved)
+    }
+}
+
+/// `path` without symbolic links or `..`; if it doesn't exist, its parent resolved with the
+/// file name appended. A dangling symbolic link fails with `PermissionDenied`.
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match path.canonicalize() {
+        Ok(resolved) => Ok(resolved),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            if path.symlink_metadata().is_ok() {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "a symbolic link to a missing file"));
+            }
+            let (Some(parent), Some(Component::Normal(name))) = (path.parent(), path.components().next_back()) else {
+                return Err(error);
+            };
+            Ok(parent.canonicalize()?.join(name))
+        }
+        Err(error) => Err(error),
+    }
+}
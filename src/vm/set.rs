@@ -0,0 +1,181 @@
+//! `Value::Set`: an insertion-ordered set of values.
+//!
+//! Membership follows `Value`'s equality. Scalars, strings, chars, big integers, bytes, ranges
+//! and tuples of those compare by content; arrays, maps, instances, sets, functions, closures, coroutines, futures, fibers and classes
+//! by identity. Weak references and bare native functions can't be stored.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::vm::bigint::BigInt;
+use crate::vm::gc::Gc;
+use crate::vm::range::Range;
+use crate::vm::value::Value;
+use crate::vm::vm::VMError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum SetKey {
+    Null,
+    Bool(bool),
+    Int(u8, i128),
+    UInt(u8, u128),
+    F32(u32),
+    F64(u64),
+    Str(Rc<str>),
+    Char(char),
+    BigInt(BigInt),
+    Bytes(Vec<u8>),
+    Tuple(Vec<SetKey>),
+    Range(Range),
+    Ref(*const ()),
+}
+
+impl SetKey {
+    fn of(value: &Value) -> Result<SetKey, VMError> {
+        let key = match value {
+            Value::Null => SetKey::Null,
+            Value::Bool(b) => SetKey::Bool(*b),
+            Value::I8(v) => SetKey::Int(8, *v as i128),
+            Value::I16(v) => SetKey::Int(16, *v as i128),
+            Value::I32(v) => SetKey::Int(32, *v as i128),
+            Value::I64(v) => SetKey::Int(64, *v as i128),
+            Value::I128(v) => SetKey::Int(128, *v),
+            Value::U8(v) => SetKey::UInt(8, *v as u128),
+            Value::U16(v) => SetKey::UInt(16, *v as u128),
+            Value::U32(v) => SetKey::UInt(32, *v as u128),
+            Value::U64(v) => SetKey::UInt(64, *v as u128),
+            Value::U128(v) => SetKey::UInt(128, *v),
+            Value::F32(v) => SetKey::F32(v.to_bits()),
+            Value::F64(v) => SetKey::F64(v.to_bits()),
+            Value::Str(s) => SetKey::Str(s.clone()),
+            Value::Char(c) => SetKey::Char(*c),
+            Value::BigInt(v) => SetKey::BigInt((**v).clone()),
+            Value::Bytes(bytes) => SetKey::Bytes(bytes.borrow().clone()),
+            Value::Tuple(elements) => SetKey::Tuple(elements.iter().map(SetKey::of).collect::<Result<_, _>>()?),
+            Value::Range(range) => SetKey::Range(*range),
+            Value::Object(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Array(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Map(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Set(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Int32Array(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Float64Array(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::ByteArray(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Function(rc) => SetKey::Ref(Rc::as_ptr(rc) as *const ()),
+            Value::Class(rc) => SetKey::Ref(Rc::as_ptr(rc) as *const ()),
+            Value::Closure(rc) => SetKey::Ref(Rc::as_ptr(rc) as *const ()),
+            Value::Coroutine(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Future(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Fiber(gc) => SetKey::Ref(Gc::addr(gc)),
+            Value::Channel(channel) => SetKey::Ref(channel.addr()),
+            Value::BoundMethod(rc) => SetKey::Ref(Rc::as_ptr(rc) as *const ()),
+            Value::NativeFunction(_) | Value::WeakRef(_) => {
+                return Err(VMError::TypeMismatch(format!("{:?} can't be stored in a set", value)))
+            }
+        };
+        Ok(key)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValueSet {
+    elements: Vec<Value>,
+    index: HashMap<SetKey, usize>,
+}
+
+impl ValueSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Adds `value`, returning `false` if it was already present.
+    pub fn insert(&mut self, value: Value) -> Result<bool, VMError> {
+        let key = SetKey::of(&value)?;
+        if self.index.contains_key(&key) {
+            return Ok(false);
+        }
+        self.index.insert(key, self.elements.len());
+        self.elements.push(value);
+        Ok(true)
+    }
+
+    pub fn contains(&self, value: &Value) -> Result<bool, VMError> {
+        Ok(self.index.contains_key(&SetKey::of(value)?))
+    }
+
+    /// Removes `value`, keeping the order of the rest. Returns whether it was present.
+    pub fn remove(&mut self, value: &Value) -> Result<bool, VMError> {
+        let Some(position) = self.index.remove(&SetKey::of(value)?) else { return Ok(false) };
+        self.elements.remove(position);
+        for slot in self.index.values_mut() {
+            if *slot > position {
+                *slot -= 1;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Elements in insertion order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
+        self.elements.iter()
+    }
+
+    /// Elements of `self`, then those of `other` not already in `self`.
+    pub fn union(&self, other: &ValueSet) -> ValueSet {
+        let mut union = self.clone();
+        for value in other.iter() {
+            let _ = union.insert(value.clone());
+        }
+        union
+    }
+
+    /// Elements of `self` that are also in `other`, in `self`'s order.
+    pub fn intersection(&self, other: &ValueSet) -> ValueSet {
+        let mut intersection = ValueSet::new();
+        for value in self.iter().filter(|value| other.contains(value).unwrap_or(false)) {
+            let _ = intersection.insert(value.clone());
+        }
+        intersection
+    }
+
+    /// A set of `values`, dropping duplicates.
+    pub fn from_values(values: impl IntoIterator<Item = Value>) -> Result<ValueSet, VMError> {
+        let mut set = ValueSet::new();
+        for value in values {
+            set.insert(value)?;
+        }
+        Ok(set)
+    }
+
+    /// Empties the set, handing back its elements.
+    pub(crate) fn take(&mut self) -> Vec<Value> {
+        self.index.clear();
+        std::mem::take(&mut self.elements)
+    }
+}
+
+impl PartialEq for ValueSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.index.keys().all(|key| other.index.contains_key(key))
+    }
+}
+
+impl Serialize for ValueSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.elements.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ValueSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elements = Vec::<Value>::deserialize(deserializer)?;
+        ValueSet::from_values(elements).map_err(|e| serde::de::Error::custom(e.to_string()))
+    }
+}
@@ -0,0 +1,64 @@
+//! Shapes: the layout of an instance's named properties, shared between instances.
+//!
+//! A shape maps property names to offsets in `Instance::fields`. Instances of a class start
+//! with the class's initial shape, made from its `properties` and those of its superclasses.
+//! Adding a property moves an instance to the shape with that property appended; each shape
+//! remembers the shapes it led to, so instances that gain the same properties in the same
+//! order end up sharing one shape. The inline caches key on shapes, so a named access is
+//! resolved once per shape and is a plain offset lookup after that.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Shape {
+    offsets: HashMap<String, usize>,
+    len: usize,
+    #[serde(skip)]
+    transitions: RefCell<HashMap<String, Weak<Shape>>>,
+}
+
+impl Shape {
+    /// A shape with the given `(name, offset)` properties.
+    pub fn new(properties: impl IntoIterator<Item = (String, usize)>) -> Self {
+        let offsets: HashMap<String, usize> = properties.into_iter().collect();
+        let len = offsets.values().map(|offset| offset + 1).max().unwrap_or(0);
+        Self { offsets, len, transitions: RefCell::new(HashMap::new()) }
+    }
+
+    pub fn offset(&self, name: &str) -> Option<usize> {
+        self.offsets.get(name).copied()
+    }
+
+    /// Fields an instance of this shape needs.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Property names by offset; `None` for offsets no property uses.
+    pub fn names(&self) -> Vec<Option<&str>> {
+        let mut names = vec![None; self.len];
+        for (name, offset) in &self.offsets {
+            names[*offset] = Some(name.as_str());
+        }
+        names
+    }
+
+    /// This shape with `name` appended, shared with every other instance that added it here.
+    pub fn with_property(self: &Rc<Self>, name: &str) -> Rc<Shape> {
+        if let Some(shape) = self.transitions.borrow().get(name).and_then(Weak::upgrade) {
+            return shape;
+        }
+        let mut offsets = self.offsets.clone();
+        offsets.insert(name.to_string(), self.len);
+        let shape = Rc::new(Shape { offsets, len: self.len + 1, transitions: RefCell::new(HashMap::new()) });
+        self.transitions.borrow_mut().insert(name.to_string(), Rc::downgrade(&shape));
+        shape
+    }
+}
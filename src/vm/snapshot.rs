@@ -0,0 +1,80 @@
+//! Checkpoints of a paused VM, see `IrisVM::snapshot`.
+//!
+//! A snapshot is the VM's operand stack, call frames, try blocks, globals and the classes
+//! whose initializer has run, written as one value graph with `data::valuecodec`, so values
+//! the stack and globals share stay shared after `IrisVM::restore`. Native functions are
+//! saved by name and looked up among the restoring VM's globals, so it must register the
+//! same natives first.
+
+use std::collections::HashSet;
+use std::io;
+use std::rc::Rc;
+use crate::vm::closure::Upvalue;
+use crate::vm::gc::Gc;
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    bytes: Vec<u8>,
+}
+
+impl Snapshot {
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    /// A snapshot read back from `as_bytes`; `IrisVM::restore` checks it.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, &self.bytes)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        std::fs::read(path).map(Self::from_bytes)
+    }
+}
+
+/// The arrays, maps, sets and instances reachable from `roots`, for the restoring VM's
+/// garbage collector to track.
+pub(crate) fn heap_objects<'a>(roots: impl IntoIterator<Item = &'a Value>) -> Vec<Value> {
+    let mut seen = HashSet::new();
+    let mut objects = Vec::new();
+    let mut pending: Vec<Value> = roots.into_iter().cloned().collect();
+    while let Some(value) = pending.pop() {
+        let identity = match &value {
+            Value::Array(array) => Gc::addr(array),
+            Value::Map(map) => Gc::addr(map),
+            Value::Set(set) => Gc::addr(set),
+            Value::Object(instance) => Gc::addr(instance),
+            Value::Closure(closure) => Rc::as_ptr(closure) as *const (),
+            Value::Tuple(elements) => elements.as_ptr() as *const (),
+            _ => continue,
+        };
+        if !seen.insert(identity) {
+            continue;
+        }
+        match &value {
+            Value::Array(array) => pending.extend(array.borrow().iter().cloned()),
+            Value::Map(map) => pending.extend(map.borrow().values().cloned()),
+            Value::Set(set) => pending.extend(set.borrow().iter().cloned()),
+            Value::Object(instance) => pending.extend(instance.borrow().fields.iter().cloned()),
+            Value::Tuple(elements) => pending.extend(elements.iter().cloned()),
+            Value::Closure(closure) => pending.extend(closure.upvalues.iter().filter_map(|upvalue| match &*upvalue.borrow() {
+                Upvalue::Closed(value) => Some(value.clone()),
+                Upvalue::Open(_) => None,
+            })),
+            _ => {}
+        }
+        if !matches!(value, Value::Closure(_) | Value::Tuple(_)) {
+            objects.push(value);
+        }
+    }
+    objects
+}
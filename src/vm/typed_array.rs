@@ -0,0 +1,131 @@
+//! Homogeneous arrays that store their elements unboxed: `Value::Int32Array`,
+//! `Value::Float64Array` and `Value::ByteArray`.
+
+use std::mem::size_of;
+use crate::vm::gc::Gc;
+use crate::vm::value::Value;
+
+/// Element type of a typed array, the operand of `NewTypedArray`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ElementType {
+    Int32 = 0,
+    Float64 = 1,
+    Byte = 2,
+}
+
+impl ElementType {
+    pub fn from_byte(byte: u8) -> Option<ElementType> {
+        match byte {
+            0 => Some(ElementType::Int32),
+            1 => Some(ElementType::Float64),
+            2 => Some(ElementType::Byte),
+            _ => None,
+        }
+    }
+
+    pub fn element_size(self) -> usize {
+        match self {
+            ElementType::Int32 => size_of::<i32>(),
+            ElementType::Float64 => size_of::<f64>(),
+            ElementType::Byte => size_of::<u8>(),
+        }
+    }
+
+    /// A zero-filled array of `len` elements.
+    pub fn new_array(self, len: usize) -> Value {
+        match self {
+            ElementType::Int32 => Value::Int32Array(Gc::new(vec![0; len])),
+            ElementType::Float64 => Value::Float64Array(Gc::new(vec![0.0; len])),
+            ElementType::Byte => Value::ByteArray(Gc::new(vec![0; len])),
+        }
+    }
+
+    pub fn of(value: &Value) -> Option<ElementType> {
+        match value {
+            Value::Int32Array(_) => Some(ElementType::Int32),
+            Value::Float64Array(_) => Some(ElementType::Float64),
+            Value::ByteArray(_) => Some(ElementType::Byte),
+            _ => None,
+        }
+    }
+}
+
+/// Number of elements, or `None` if `value` isn't a typed array.
+pub fn len(value: &Value) -> Option<usize> {
+    match value {
+        Value::Int32Array(array) => Some(array.borrow().len()),
+        Value::Float64Array(array) => Some(array.borrow().len()),
+        Value::ByteArray(array) => Some(array.borrow().len()),
+        _ => None,
+    }
+}
+
+/// The element at `index`, boxed as `I32`, `F64` or `U8`.
+pub fn get(value: &Value, index: usize) -> Option<Value> {
+    match value {
+        Value::Int32Array(array) => array.borrow().get(index).map(|x| Value::I32(*x)),
+        Value::Float64Array(array) => array.borrow().get(index).map(|x| Value::F64(*x)),
+        Value::ByteArray(array) => array.borrow().get(index).map(|x| Value::U8(*x)),
+        _ => None,
+    }
+}
+
+/// Stores `element` at `index`. Integer elements wrap to the array's width; returns `false`
+/// if `index` is out of bounds.
+pub fn set(value: &Value, index: usize, element: Element) -> bool {
+    match value {
+        Value::Int32Array(array) => array.borrow_mut().get_mut(index).map(|slot| *slot = element.as_i64() as i32).is_some(),
+        Value::Float64Array(array) => array.borrow_mut().get_mut(index).map(|slot| *slot = element.as_f64()).is_some(),
+        Value::ByteArray(array) => array.borrow_mut().get_mut(index).map(|slot| *slot = element.as_i64() as u8).is_some(),
+        _ => false,
+    }
+}
+
+/// Sets every element to `element`.
+pub fn fill(value: &Value, element: Element) {
+    match value {
+        Value::Int32Array(array) => array.borrow_mut().fill(element.as_i64() as i32),
+        Value::Float64Array(array) => array.borrow_mut().fill(element.as_f64()),
+        Value::ByteArray(array) => array.borrow_mut().fill(element.as_i64() as u8),
+        _ => {}
+    }
+}
+
+/// A numeric value being stored into a typed array.
+#[derive(Debug, Clone, Copy)]
+pub enum Element {
+    Int(i64),
+    Float(f64),
+}
+
+impl Element {
+    pub fn from_value(value: &Value) -> Option<Element> {
+        Some(match value {
+            Value::I8(v) => Element::Int(*v as i64),
+            Value::I16(v) => Element::Int(*v as i64),
+            Value::I32(v) => Element::Int(*v as i64),
+            Value::I64(v) => Element::Int(*v),
+            Value::U8(v) => Element::Int(*v as i64),
+            Value::U16(v) => Element::Int(*v as i64),
+            Value::U32(v) => Element::Int(*v as i64),
+            Value::U64(v) => Element::Int(*v as i64),
+            Value::F32(v) => Element::Float(*v as f64),
+            Value::F64(v) => Element::Float(*v),
+            _ => return None,
+        })
+    }
+
+    fn as_i64(self) -> i64 {
+        match self {
+            Element::Int(v) => v,
+            Element::Float(v) => v as i64,
+        }
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Element::Int(v) => v as f64,
+            Element::Float(v) => v,
+        }
+    }
+}
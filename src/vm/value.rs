@@ -1,6 +1,15 @@
-use std::{rc::Rc, collections::HashMap, cell::RefCell};
-use crate::vm::object::{Instance, Class};
+use std::{rc::Rc, collections::HashMap};
+use crate::vm::object::{Instance, Class, BoundMethod};
 use crate::vm::function::Function;
+use crate::vm::bigint::BigInt;
+use crate::vm::set::ValueSet;
+use crate::vm::range::Range;
+use crate::vm::closure::Closure;
+use crate::vm::coroutine::Coroutine;
+use crate::vm::future::HostFuture;
+use crate::vm::fiber::Fiber;
+use crate::vm::channel::Channel;
+use crate::vm::gc::{Gc, WeakRef};
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,14 +32,49 @@ pub enum Value {
     F32(f32),
     F64(f64),
     // Other types
-    Str(String),
-    Object(Rc<Instance>),
+    Str(#[serde(deserialize_with = "crate::vm::intern::deserialize")] Rc<str>),
+    Object(Gc<Instance>),
     Function(Rc<Function>),
+    Class(Rc<Class>),
+    Array(Gc<Vec<Value>>),
+    Map(Gc<HashMap<String, Value>>),
+    // Typed arrays, see `vm::typed_array`
+    Int32Array(Gc<Vec<i32>>),
+    Float64Array(Gc<Vec<f64>>),
+    ByteArray(Gc<Vec<u8>>),
+    BigInt(Rc<BigInt>),
+    /// A byte buffer, see `stdlib::bytes`.
+    Bytes(Gc<Vec<u8>>),
+    Char(char),
+    /// An immutable fixed-size sequence.
+    Tuple(Rc<[Value]>),
+    /// An insertion-ordered set, see `vm::set`.
+    Set(Gc<ValueSet>),
+    Range(Range),
+    // Skipped variants must stay last: serde numbers variants differently when
+    // serializing and deserializing once a skipped variant sits in the middle.
     #[serde(skip)]
     NativeFunction(fn(Vec<Value>) -> Value),
-    Class(Rc<Class>),
-    Array(Rc<RefCell<Vec<Value>>>),
-    Map(Rc<RefCell<HashMap<String, Value>>>),
+    #[serde(skip)]
+    WeakRef(WeakRef),
+    /// A function with captured upvalues, see `vm::closure`.
+    #[serde(skip)]
+    Closure(Rc<Closure>),
+    /// A suspendable call, see `vm::coroutine`.
+    #[serde(skip)]
+    Coroutine(Gc<Coroutine>),
+    /// A host future, see `vm::future`.
+    #[serde(skip)]
+    Future(Gc<HostFuture>),
+    /// A green thread, see `vm::fiber`.
+    #[serde(skip)]
+    Fiber(Gc<Fiber>),
+    /// A message queue, see `vm::channel`.
+    #[serde(skip)]
+    Channel(Rc<Channel>),
+    /// A method bound to its receiver, see `BindMethodHandle`.
+    #[serde(skip)]
+    BoundMethod(Rc<BoundMethod>),
 }
 
 impl PartialEq for Value {
@@ -52,7 +96,7 @@ impl PartialEq for Value {
             (F32(a), F32(b)) => a == b,
             (F64(a), F64(b)) => a == b,
             (Str(a), Str(b)) => a == b,
-            (Object(a), Object(b)) => Rc::ptr_eq(a, b),
+            (Object(a), Object(b)) => Gc::ptr_eq(a, b),
             (Function(a), Function(b)) => Rc::ptr_eq(a, b),
             (NativeFunction(a), NativeFunction(b)) => {
                 let a_ptr: usize = *a as usize;
@@ -60,8 +104,24 @@ impl PartialEq for Value {
                 a_ptr == b_ptr
             }
             (Class(a), Class(b)) => Rc::ptr_eq(a, b),
-            (Array(a), Array(b)) => Rc::ptr_eq(a, b),
-            (Map(a), Map(b)) => Rc::ptr_eq(a, b),
+            (Array(a), Array(b)) => Gc::ptr_eq(a, b),
+            (Map(a), Map(b)) => Gc::ptr_eq(a, b),
+            (Int32Array(a), Int32Array(b)) => Gc::ptr_eq(a, b),
+            (Float64Array(a), Float64Array(b)) => Gc::ptr_eq(a, b),
+            (ByteArray(a), ByteArray(b)) => Gc::ptr_eq(a, b),
+            (BigInt(a), BigInt(b)) => a == b,
+            (Bytes(a), Bytes(b)) => *a.borrow() == *b.borrow(),
+            (Char(a), Char(b)) => a == b,
+            (Tuple(a), Tuple(b)) => a == b,
+            (Set(a), Set(b)) => Gc::ptr_eq(a, b),
+            (Range(a), Range(b)) => a == b,
+            (Closure(a), Closure(b)) => Rc::ptr_eq(a, b),
+            (Coroutine(a), Coroutine(b)) => Gc::ptr_eq(a, b),
+            (Future(a), Future(b)) => Gc::ptr_eq(a, b),
+            (Fiber(a), Fiber(b)) => Gc::ptr_eq(a, b),
+            (Channel(a), Channel(b)) => a == b,
+            (BoundMethod(a), BoundMethod(b)) => Rc::ptr_eq(a, b),
+            (WeakRef(a), WeakRef(b)) => crate::vm::gc::WeakRef::ptr_eq(a, b),
             _ => false,
         }
     }
@@ -87,6 +147,12 @@ impl Value {
             Value::Str(s) => !s.is_empty(),
             Value::Array(a) => !a.borrow().is_empty(),
             Value::Map(m) => !m.borrow().is_empty(),
+            Value::Bytes(b) => !b.borrow().is_empty(),
+            Value::BigInt(i) => !i.is_zero(),
+            Value::Tuple(t) => !t.is_empty(),
+            Value::Set(s) => !s.borrow().is_empty(),
+            Value::Range(r) => !r.is_empty(),
+            Value::Int32Array(_) | Value::Float64Array(_) | Value::ByteArray(_) => crate::vm::typed_array::len(self) != Some(0),
             _ => true, // Objects, Functions, Classes are always truthy
         }
     }
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use crate::debug::liveness::LivenessTable;
+use crate::vm::function::Function;
+use crate::vm::opcode::{instruction_len, is_custom_opcode, stack_effect, successors, OpCode};
+use crate::vm::value::Value;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    NoBytecode,
+    Truncated { offset: usize },
+    UnknownOpCode { offset: usize, byte: u8 },
+    UnsupportedOpCode { offset: usize, byte: u8 },
+    JumpBeforeStart { offset: usize },
+    JumpOutOfBounds { offset: usize, target: usize },
+    JumpIntoInstruction { offset: usize, target: usize },
+    MissingConstant { offset: usize, index: usize },
+    InvalidConstant { offset: usize, index: usize, expected: &'static str },
+    InvalidLocal { offset: usize, slot: usize },
+    StackUnderflow { offset: usize, depth: usize, needed: usize },
+    InconsistentStackDepth { offset: usize, expected: usize, found: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::NoBytecode => write!(f, "Function has no bytecode"),
+            VerifyError::Truncated { offset } => write!(f, "Truncated instruction at {}", offset),
+            VerifyError::UnknownOpCode { offset, byte } => write!(f, "Unknown opcode {:#04x} at {}", byte, offset),
+            VerifyError::UnsupportedOpCode { offset, byte } => {
+                write!(f, "Opcode {:#04x} at {} has no verifiable stack effect", byte, offset)
+            }
+            VerifyError::JumpBeforeStart { offset } => write!(f, "Jump at {} targets before the start of the function", offset),
+            VerifyError::JumpOutOfBounds { offset, target } => {
+                write!(f, "Jump at {} targets {}, outside the function", offset, target)
+            }
+            VerifyError::JumpIntoInstruction { offset, target } => {
+                write!(f, "Jump at {} targets {}, inside another instruction", offset, target)
+            }
+            VerifyError::MissingConstant { offset, index } => write!(f, "Constant {} used at {} does not exist", index, offset),
+            VerifyError::InvalidConstant { offset, index, expected } => {
+                write!(f, "Constant {} used at {} is not a {}", index, offset, expected)
+            }
+            VerifyError::InvalidLocal { offset, slot } => write!(f, "Local slot {} used at {} is not on the stack", slot, offset),
+            VerifyError::StackUnderflow { offset, depth, needed } => {
+                write!(f, "Stack underflow at {}: depth {}, instruction needs {}", offset, depth, needed)
+            }
+            VerifyError::InconsistentStackDepth { offset, expected, found } => {
+                write!(f, "Stack depth at {} is {} on one path and {} on another", offset, expected, found)
+            }
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+/// A function whose bytecode passed `verify`, along with what the pass learned about it.
+#[derive(Debug, Clone)]
+pub struct VerifiedFunction {
+    function: Rc<Function>,
+    max_stack_depth: usize,
+    depths: Vec<Option<usize>>,
+    liveness: LivenessTable,
+}
+
+impl VerifiedFunction {
+    pub fn function(&self) -> &Rc<Function> {
+        &self.function
+    }
+
+    /// Deepest operand stack reached relative to the frame base, arguments included.
+    pub fn max_stack_depth(&self) -> usize {
+        self.max_stack_depth
+    }
+
+    /// Stack depth relative to the frame base on entry to the instruction at `offset`, or
+    /// `None` if no path reaches it. The end of the code counts as an offset.
+    pub fn stack_depth_at(&self, offset: usize) -> Option<usize> {
+        self.depths.get(offset).copied().flatten()
+    }
+
+    pub fn liveness(&self) -> &LivenessTable {
+        &self.liveness
+    }
+}
+
+/// Checks that every instruction decodes, jumps land on instruction boundaries, constant and
+/// local operands exist, and the stack depth is consistent and never underflows on any path.
+pub fn verify(function: &Rc<Function>) -> Result<VerifiedFunction, VerifyError> {
+    verify_with_custom(function, &HashMap::new())
+}
+
+/// Like `verify`, taking the (pops, pushes) of custom opcodes from `custom_effects`. Custom
+/// opcodes missing from it fail as `UnsupportedOpCode`.
+pub fn verify_with_custom(function: &Rc<Function>, custom_effects: &HashMap<u8, (usize, usize)>) -> Result<VerifiedFunction, VerifyError> {
+    let code = function.bytecode.as_ref().ok_or(VerifyError::NoBytecode)?;
+
+    let mut boundaries = vec![false; code.len() + 1];
+    let mut offset = 0;
+    while offset < code.len() {
+        let len = instruction_len(code, offset).ok_or(VerifyError::Truncated { offset })?;
+        let byte = code[offset];
+        if !is_custom_opcode(byte) && OpCode::decode(code, offset) == Some(OpCode::Unknown) {
+            return Err(VerifyError::UnknownOpCode { offset, byte });
+        }
+        boundaries[offset] = true;
+        offset += len;
+    }
+    // Running off the end returns from the frame, so the end is a valid target too.
+    boundaries[code.len()] = true;
+
+    let mut depths: Vec<Option<usize>> = vec![None; code.len() + 1];
+    let mut max_stack_depth = function.arity;
+    let mut worklist = vec![(0, function.arity)];
+    while let Some((offset, depth)) = worklist.pop() {
+        match depths[offset] {
+            Some(expected) if expected == depth => continue,
+            Some(expected) => return Err(VerifyError::InconsistentStackDepth { offset, expected, found: depth }),
+            None => depths[offset] = Some(depth),
+        }
+        if offset == code.len() {
+            continue;
+        }
+
+        check_operands(function, code, offset, depth)?;
+        let effect = if is_custom_opcode(code[offset]) {
+            custom_effects.get(&code[offset]).copied()
+        } else {
+            stack_effect(code, offset)
+        };
+        let (pops, pushes) = effect.ok_or(VerifyError::UnsupportedOpCode { offset, byte: code[offset] })?;
+        if pops > depth {
+            return Err(VerifyError::StackUnderflow { offset, depth, needed: pops });
+        }
+        let after = depth - pops + pushes;
+        max_stack_depth = max_stack_depth.max(after);
+
+        let targets = successors(code, offset).ok_or(VerifyError::JumpBeforeStart { offset })?;
+        let handler = matches!(OpCode::from(code[offset]), OpCode::BeginTryBlock | OpCode::FinallyBlock).then(|| targets[1]);
+        for target in targets {
+            if target > code.len() {
+                return Err(VerifyError::JumpOutOfBounds { offset, target });
+            }
+            if !boundaries[target] {
+                return Err(VerifyError::JumpIntoInstruction { offset, target });
+            }
+            // A handler starts with the stack as it was at BeginTryBlock plus the exception.
+            let target_depth = if Some(target) == handler { depth + 1 } else { after };
+            max_stack_depth = max_stack_depth.max(target_depth);
+            worklist.push((target, target_depth));
+        }
+    }
+
+    let liveness = LivenessTable::compute(function).unwrap_or_default();
+    Ok(VerifiedFunction { function: function.clone(), max_stack_depth, depths, liveness })
+}
+
+fn check_operands(function: &Function, code: &[u8], offset: usize, depth: usize) -> Result<(), VerifyError> {
+    use OpCode::*;
+    let u8_at = |at: usize| code[offset + at] as usize;
+    let u16_at = |at: usize| u16::from_be_bytes([code[offset + at], code[offset + at + 1]]) as usize;
+    if is_custom_opcode(code[offset]) {
+        return Ok(());
+    }
+
+    let opcode = OpCode::decode(code, offset).unwrap_or(Unknown);
+    let (index, expected) = match opcode {
+        PushConstant8 => (u8_at(1), None),
+        PushConstant16 => (u16_at(1), None),
+        DefineClass8 | GetObjectField8 | SetObjectField8 | GetObjectProperty8 | SetObjectProperty8 => {
+            (u8_at(1), Some("string"))
+        }
+        DefineClass16 | GetObjectField16 | SetObjectField16 | GetObjectProperty16 | SetObjectProperty16
+        | GetPropertyWithInlineCache | SetPropertyWithInlineCache | CallWithInlineCache | MegamorphicMethodCall
+        | LoadMethodHandle | CatchException => {
+            (u16_at(1), Some("string"))
+        }
+        LoadModule | ImportSymbol => (u16_at(2), Some("string")),
+        GetLocalVariable8 | SetLocalVariable8 | GetLocalVariable16 | SetLocalVariable16 => {
+            let slot = if opcode.operand_len() == Some(1) { u8_at(1) } else { u16_at(1) };
+            if slot >= depth {
+                return Err(VerifyError::InvalidLocal { offset, slot });
+            }
+            return Ok(());
+        }
+        AddLocalInt32WithConstant | JumpIfLocalsNotLessInt32 => {
+            let last = if opcode == AddLocalInt32WithConstant { 2 } else { 3 };
+            return match (2..=last).map(u8_at).find(|slot| *slot >= depth) {
+                Some(slot) => Err(VerifyError::InvalidLocal { offset, slot }),
+                None => Ok(()),
+            };
+        }
+        _ => return Ok(()),
+    };
+
+    match (function.constants.get(index), expected) {
+        (None, _) => Err(VerifyError::MissingConstant { offset, index }),
+        (Some(Value::Str(_)), Some(_)) | (Some(_), None) => Ok(()),
+        (Some(_), Some(expected)) => Err(VerifyError::InvalidConstant { offset, index, expected }),
+    }
+}
@@ -1,12 +1,17 @@
-use crate::vm::{object::{Instance, Class}, opcode::OpCode, value::Value, function::Function};
-use std::{rc::Rc, collections::HashMap, cell::RefCell, error::Error, fmt};
+use crate::data::module::Module;
+use crate::data::package::Package;
+use crate::data::valuecodec;
+use crate::disasm::disassemble_instruction;
+use crate::debug::{backtrace::Backtrace, coverage::Coverage, cycles::{find_cycles, Cycle}, breakpoints::{BreakpointAction, Breakpoints}, lines::SourceLocation, profiler::Profile, stats::ExecutionStats};
+use crate::vm::{builder::{IrisVMBuilder, VMLimits}, gc::{Gc, Heap, WeakRef}, intern::{self, intern}, interrupt::InterruptHandle, memory, native::TypedNative, set::ValueSet, range::Range, closure::{Closure, Upvalue, UpvalueRef}, coroutine::{Coroutine, CoroutineState}, future::HostFuture, fiber::{Fiber, FiberState, Scheduler}, channel::Channel, monitor::{Entry, Monitors, MAIN_FIBER}, inline_cache::{self, CacheState, InlineCaches, Resolved}, bigint::BigInt, object::{Instance, Class, BoundMethod, CONSTRUCTOR, CLASS_INITIALIZER}, typed_array::{self, Element, ElementType}, opcode::{OpCode, CUSTOM_OPCODES, CUSTOM_OPCODES_WITH_OPERAND, is_custom_opcode}, value::Value, function::{Function, FunctionKind}, exception::{self, CatchPolicy, ExceptionClasses}, config::ConfigStore, verifier::{verify, verify_with_custom, VerifiedFunction, VerifyError}, decoded::{decode_instruction, DecodedCode, DecodedInstr}, register::{translate, RegInstr, RegisterCode}, capability::Capability, extension, sandbox::{self, FsPolicy}, clock::{Clock, SystemClock, VirtualClock}, random::Rng, import::{ModuleResolver, Modules}, snapshot::{self, Snapshot}};
+use std::{rc::{Rc, Weak}, cell::RefCell, cmp::Ordering, collections::{HashMap, HashSet}, error::Error, fmt, time::Instant};
 
 #[derive(Debug)]
 pub enum VMError {
     StackUnderflow,
     TypeMismatch(String),
     UndefinedVariable(String),
-    UndefinedProperty(usize),
+    UndefinedProperty(String),
     MethodNotFound(usize),
     NonCallableValue,
     NonObjectValue,
@@ -14,11 +19,47 @@ pub enum VMError {
     NonStringKey,
     IndexOutOfBounds,
     DivisionByZero,
+    IntegerOverflow,
+    /// `ResumeCoroutine` or `IrisVM::resume` on a coroutine that has returned.
+    CoroutineFinished,
     UnknownOpCode,
     InvalidOperand(String),
     UnhandledException(Value),
     NoActiveCallFrame,
     NoTryFrame,
+    ReadOnlyGlobal(String),
+    BytecodeVersionError { expected: String, found: String },
+    VerificationFailed(VerifyError),
+    ArityMismatch { expected: usize, found: usize },
+    StackOverflow { limit: usize },
+    CallDepthExceeded { limit: usize },
+    /// The fuel budget ran out before the next instruction. Add fuel and call `run()` again
+    /// to continue where execution stopped.
+    OutOfFuel,
+    OutOfMemory { limit: usize },
+    /// An `InterruptHandle` asked the VM to stop. Call `run()` again to resume.
+    Interrupted,
+    /// `run()` reached a breakpoint and paused before executing it. Call `run()` again to resume.
+    Breakpoint(SourceLocation),
+    /// Bytecode awaited a host future that hasn't completed. `run_async()` waits for it;
+    /// otherwise complete it and call `run()` again to resume at the `Await`.
+    Pending,
+    /// `EnterMonitor` would wait forever: this many fibers each wait for a monitor another holds.
+    Deadlock { fibers: usize },
+    /// A native needs a capability the host hasn't granted, see `vm::capability`.
+    CapabilityDenied(Capability),
+    /// The VM's `FsPolicy` doesn't allow the access, see `vm::sandbox`.
+    AccessDenied(String),
+    /// A file system operation failed.
+    Io(String),
+    /// Guest code asked to end the program with this status, see `stdlib::process`.
+    Exit(i32),
+    /// `LoadModule` or `ImportSymbol` failed, see `vm::import`.
+    Import(String),
+    /// `IrisVM::snapshot` or `IrisVM::restore` failed, see `vm::snapshot`.
+    Snapshot(String),
+    /// An error that escaped `run()`, with the call frames that were active when it was raised.
+    At { error: Box<VMError>, backtrace: Backtrace },
 }
 
 impl fmt::Display for VMError {
@@ -35,23 +76,232 @@ impl fmt::Display for VMError {
             VMError::NonStringKey => write!(f, "Map keys must be strings"),
             VMError::IndexOutOfBounds => write!(f, "Array index out of bounds"),
             VMError::DivisionByZero => write!(f, "Division by zero"),
+            VMError::IntegerOverflow => write!(f, "Integer overflow"),
+            VMError::CoroutineFinished => write!(f, "Cannot resume a finished coroutine"),
             VMError::UnknownOpCode => write!(f, "Unknown opcode encountered"),
             VMError::InvalidOperand(msg) => write!(f, "Invalid operand: {}", msg),
             VMError::UnhandledException(val) => write!(f, "Unhandled exception: {:?}", val),
             VMError::NoActiveCallFrame => write!(f, "No active call frame"),
             VMError::NoTryFrame => write!(f, "No try frame to end"),
+            VMError::ReadOnlyGlobal(name) => write!(f, "Cannot assign to read-only global '{}'", name),
+            VMError::BytecodeVersionError { expected, found } => {
+                write!(f, "Incompatible bytecode file: expected {}, found {}", expected, found)
+            }
+            VMError::VerificationFailed(error) => write!(f, "Bytecode verification failed: {}", error),
+            VMError::ArityMismatch { expected, found } => {
+                write!(f, "Expected {} argument(s) but got {}", expected, found)
+            }
+            VMError::StackOverflow { limit } => write!(f, "Operand stack exceeded its limit of {} values", limit),
+            VMError::CallDepthExceeded { limit } => write!(f, "Call depth exceeded its limit of {} frames", limit),
+            VMError::OutOfFuel => write!(f, "Out of fuel"),
+            VMError::OutOfMemory { limit } => write!(f, "Heap limit of {} bytes exceeded", limit),
+            VMError::Interrupted => write!(f, "Interrupted"),
+            VMError::Breakpoint(location) => write!(f, "Paused at breakpoint {}", location),
+            VMError::Pending => write!(f, "Awaiting a pending future"),
+            VMError::Deadlock { fibers } => write!(f, "Deadlock: {} fibers wait for each other's monitors", fibers),
+            VMError::CapabilityDenied(capability) => write!(f, "The '{}' capability has not been granted", capability),
+            VMError::AccessDenied(msg) => write!(f, "Access denied: {}", msg),
+            VMError::Io(msg) => write!(f, "I/O error: {}", msg),
+            VMError::Exit(status) => write!(f, "Exited with status {}", status),
+            VMError::Import(msg) => write!(f, "Import failed: {}", msg),
+            VMError::Snapshot(msg) => write!(f, "Snapshot failed: {}", msg),
+            VMError::At { error, backtrace } => match backtrace.frames().first() {
+                Some(location) => write!(f, "{} {}", error, location),
+                None => write!(f, "{}", error),
+            },
         }
     }
 }
 
 impl Error for VMError {}
 
+/// Field-less mirror of `VMError`, used to decide how an error may be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VMErrorKind {
+    StackUnderflow,
+    TypeMismatch,
+    UndefinedVariable,
+    UndefinedProperty,
+    MethodNotFound,
+    NonCallableValue,
+    NonObjectValue,
+    NonClassValue,
+    NonStringKey,
+    IndexOutOfBounds,
+    DivisionByZero,
+    IntegerOverflow,
+    CoroutineFinished,
+    UnknownOpCode,
+    InvalidOperand,
+    UnhandledException,
+    NoActiveCallFrame,
+    NoTryFrame,
+    ReadOnlyGlobal,
+    BytecodeVersionError,
+    VerificationFailed,
+    ArityMismatch,
+    StackOverflow,
+    CallDepthExceeded,
+    OutOfFuel,
+    OutOfMemory,
+    Interrupted,
+    Breakpoint,
+    Pending,
+    Deadlock,
+    CapabilityDenied,
+    AccessDenied,
+    Io,
+    Exit,
+    Import,
+    Snapshot,
+}
+
+impl VMErrorKind {
+    /// Fatal kinds describe a broken program or a host decision to stop the guest.
+    /// They always abort `run()` and can never be turned into guest exceptions.
+    pub fn is_fatal(self) -> bool {
+        matches!(
+            self,
+            VMErrorKind::UnknownOpCode
+                | VMErrorKind::InvalidOperand
+                | VMErrorKind::UnhandledException
+                | VMErrorKind::NoActiveCallFrame
+                | VMErrorKind::NoTryFrame
+                | VMErrorKind::BytecodeVersionError
+                | VMErrorKind::VerificationFailed
+                | VMErrorKind::StackOverflow
+                | VMErrorKind::CallDepthExceeded
+                | VMErrorKind::OutOfFuel
+                | VMErrorKind::Interrupted
+                | VMErrorKind::Breakpoint
+                | VMErrorKind::Pending
+                | VMErrorKind::Deadlock
+                | VMErrorKind::Exit
+                | VMErrorKind::Snapshot
+        )
+    }
+}
+
+impl VMError {
+    pub fn kind(&self) -> VMErrorKind {
+        match self {
+            VMError::StackUnderflow => VMErrorKind::StackUnderflow,
+            VMError::TypeMismatch(_) => VMErrorKind::TypeMismatch,
+            VMError::UndefinedVariable(_) => VMErrorKind::UndefinedVariable,
+            VMError::UndefinedProperty(_) => VMErrorKind::UndefinedProperty,
+            VMError::MethodNotFound(_) => VMErrorKind::MethodNotFound,
+            VMError::NonCallableValue => VMErrorKind::NonCallableValue,
+            VMError::NonObjectValue => VMErrorKind::NonObjectValue,
+            VMError::NonClassValue => VMErrorKind::NonClassValue,
+            VMError::NonStringKey => VMErrorKind::NonStringKey,
+            VMError::IndexOutOfBounds => VMErrorKind::IndexOutOfBounds,
+            VMError::DivisionByZero => VMErrorKind::DivisionByZero,
+            VMError::IntegerOverflow => VMErrorKind::IntegerOverflow,
+            VMError::CoroutineFinished => VMErrorKind::CoroutineFinished,
+            VMError::UnknownOpCode => VMErrorKind::UnknownOpCode,
+            VMError::InvalidOperand(_) => VMErrorKind::InvalidOperand,
+            VMError::UnhandledException(_) => VMErrorKind::UnhandledException,
+            VMError::NoActiveCallFrame => VMErrorKind::NoActiveCallFrame,
+            VMError::NoTryFrame => VMErrorKind::NoTryFrame,
+            VMError::ReadOnlyGlobal(_) => VMErrorKind::ReadOnlyGlobal,
+            VMError::BytecodeVersionError { .. } => VMErrorKind::BytecodeVersionError,
+            VMError::VerificationFailed(_) => VMErrorKind::VerificationFailed,
+            VMError::ArityMismatch { .. } => VMErrorKind::ArityMismatch,
+            VMError::StackOverflow { .. } => VMErrorKind::StackOverflow,
+            VMError::CallDepthExceeded { .. } => VMErrorKind::CallDepthExceeded,
+            VMError::OutOfFuel => VMErrorKind::OutOfFuel,
+            VMError::OutOfMemory { .. } => VMErrorKind::OutOfMemory,
+            VMError::Interrupted => VMErrorKind::Interrupted,
+            VMError::Breakpoint(_) => VMErrorKind::Breakpoint,
+            VMError::Pending => VMErrorKind::Pending,
+            VMError::Deadlock { .. } => VMErrorKind::Deadlock,
+            VMError::CapabilityDenied(_) => VMErrorKind::CapabilityDenied,
+            VMError::AccessDenied(_) => VMErrorKind::AccessDenied,
+            VMError::Io(_) => VMErrorKind::Io,
+            VMError::Exit(_) => VMErrorKind::Exit,
+            VMError::Import(_) => VMErrorKind::Import,
+            VMError::Snapshot(_) => VMErrorKind::Snapshot,
+            VMError::At { error, .. } => error.kind(),
+        }
+    }
+
+    /// The error without any location attached.
+    pub fn root(&self) -> &VMError {
+        match self {
+            VMError::At { error, .. } => error.root(),
+            error => error,
+        }
+    }
+
+    /// Where the error was raised, if it escaped `run()`.
+    pub fn location(&self) -> Option<&SourceLocation> {
+        self.backtrace()?.frames().first()
+    }
+
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            VMError::At { backtrace, .. } => Some(backtrace),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 enum Numeric {
     Int(i64),
     Float(f64),
 }
 
+/// The error for a typed array instruction that failed on `array`: out of bounds if it is a
+/// typed array, a type mismatch if not.
+fn typed_array_error(array: &Value) -> VMError {
+    match ElementType::of(array) {
+        Some(_) => VMError::IndexOutOfBounds,
+        None => VMError::TypeMismatch("Expected a typed array".to_string()),
+    }
+}
+
+/// An operand of the Int64 instructions, which also take big integers.
+enum Integer {
+    Small(i64),
+    Big(BigInt),
+}
+
+impl Integer {
+    fn to_big(&self) -> BigInt {
+        match self {
+            Integer::Small(value) => BigInt::from(*value),
+            Integer::Big(value) => value.clone(),
+        }
+    }
+}
+
+fn value_to_integer(value: &Value) -> Option<Integer> {
+    let wide = match value {
+        Value::I8(v) => *v as i128,
+        Value::I16(v) => *v as i128,
+        Value::I32(v) => *v as i128,
+        Value::I64(v) => return Some(Integer::Small(*v)),
+        Value::I128(v) => *v,
+        Value::U8(v) => *v as i128,
+        Value::U16(v) => *v as i128,
+        Value::U32(v) => *v as i128,
+        Value::U64(v) => *v as i128,
+        Value::U128(v) => return Some(Integer::Big(BigInt::from(*v))),
+        Value::BigInt(v) => return Some(Integer::Big((**v).clone())),
+        _ => return None,
+    };
+    Some(i64::try_from(wide).map_or_else(|_| Integer::Big(BigInt::from(wide)), Integer::Small))
+}
+
+/// `value` as an I64 if it fits, a BigInt otherwise.
+fn integer_value(value: BigInt) -> Value {
+    match value.to_i64() {
+        Some(small) => Value::I64(small),
+        None => Value::BigInt(Rc::new(value)),
+    }
+}
+
 fn value_to_numeric(value: &Value) -> Option<Numeric> {
     match value {
         Value::I8(v) => Some(Numeric::Int(*v as i64)),
@@ -70,34 +320,259 @@ fn value_to_numeric(value: &Value) -> Option<Numeric> {
     }
 }
 
+/// The Int32 arithmetic and comparison instructions other than division, shared by the
+/// stack interpreter and register-form bodies (see `vm::register`).
+pub(crate) fn binary_int32(op: OpCode, a: Value, b: Value) -> Result<Value, VMError> {
+    let numeric = |what: &str| -> Result<(Numeric, Numeric), VMError> {
+        let num_a = value_to_numeric(&a)
+            .ok_or_else(|| VMError::TypeMismatch(format!("Operand 'a' must be numeric for {}.", what)))?;
+        let num_b = value_to_numeric(&b)
+            .ok_or_else(|| VMError::TypeMismatch(format!("Operand 'b' must be numeric for {}.", what)))?;
+        Ok((num_a, num_b))
+    };
+    let arithmetic = |(num_a, num_b), int: fn(i64, i64) -> i64, float: fn(f64, f64) -> f64| match (num_a, num_b) {
+        (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::I64(int(val_a, val_b)),
+        (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::F64(float(val_a, val_b)),
+        (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::F64(float(val_a, val_b as f64)),
+        (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::F64(float(val_a as f64, val_b)),
+    };
+    let compare = |(num_a, num_b), holds: fn(Ordering) -> bool| {
+        let ordering = match (num_a, num_b) {
+            (Numeric::Int(val_a), Numeric::Int(val_b)) => Some(val_a.cmp(&val_b)),
+            (Numeric::Float(val_a), Numeric::Float(val_b)) => val_a.partial_cmp(&val_b),
+            (Numeric::Float(val_a), Numeric::Int(val_b)) => val_a.partial_cmp(&(val_b as f64)),
+            (Numeric::Int(val_a), Numeric::Float(val_b)) => (val_a as f64).partial_cmp(&val_b),
+        };
+        Value::Bool(ordering.is_some_and(holds))
+    };
+    Ok(match op {
+        OpCode::AddInt32 => match (a, b) {
+            (Value::I32(a_val), Value::I32(b_val)) => Value::I32(a_val.wrapping_add(b_val)),
+            (Value::Str(a_val), Value::Str(b_val)) => Value::Str(intern(&[&*a_val, &*b_val].concat())),
+            _ => return Err(VMError::TypeMismatch("Operands for AddInt32 must both be I32 or both be Str".to_string())),
+        },
+        OpCode::SubtractInt32 => arithmetic(numeric("subtraction")?, i64::wrapping_sub, |a, b| a - b),
+        OpCode::MultiplyInt32 => arithmetic(numeric("multiplication")?, i64::wrapping_mul, |a, b| a * b),
+        OpCode::LessThanInt32 => compare(numeric("comparison")?, Ordering::is_lt),
+        OpCode::GreaterThanInt32 => compare(numeric("comparison")?, Ordering::is_gt),
+        OpCode::GreaterOrEqualInt32 => compare(numeric("comparison")?, Ordering::is_ge),
+        OpCode::LessOrEqualInt32 => compare(numeric("comparison")?, Ordering::is_le),
+        OpCode::EqualInt32 => Value::Bool(a == b),
+        OpCode::NotEqualInt32 => Value::Bool(a != b),
+        _ => return Err(VMError::UnknownOpCode),
+    })
+}
+
+/// What a single `IrisVM::step` did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepOutcome {
+    /// The opcode that ran, as its byte or extended-page number (see `OpCode`), or `None` if
+    /// there was nothing left to run.
+    pub opcode: Option<u16>,
+    /// Function and offset of the instruction that ran.
+    pub location: Option<SourceLocation>,
+    /// Call frames active after the step.
+    pub depth: usize,
+    /// Whether the program has run to completion.
+    pub finished: bool,
+}
+
+impl StepOutcome {
+    /// The decoded opcode, if it was a built-in one.
+    pub fn op(&self) -> Option<OpCode> {
+        match self.opcode? {
+            number @ 0x100.. => Some(OpCode::extended(number as u8)),
+            byte if is_custom_opcode(byte as u8) => None,
+            byte => Some(OpCode::from(byte as u8)),
+        }
+    }
+}
+
+/// Calls plus loop back edges after which a function tiers up by default.
+pub const DEFAULT_TIER_UP_THRESHOLD: u32 = 1000;
+
+/// The most the value stack and call frames have held, see `IrisVM::high_water`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HighWater {
+    pub stack: usize,
+    pub frames: usize,
+}
+
+/// Handler for an embedder-defined opcode. Receives the operand byte for opcodes in
+/// `CUSTOM_OPCODES_WITH_OPERAND` and `None` otherwise.
+pub type CustomOpcodeHandler = Rc<dyn Fn(&mut IrisVM, Option<u8>) -> Result<(), VMError>>;
+
 #[repr(C)]
 pub struct IrisVM {
     pub stack: Vec<Value>,
     frames: Vec<CallFrame>,
     globals: Vec<Value>,
     try_frames: Vec<TryFrame>,
+    /// Frames of the innermost `execute`; try blocks below them belong to an outer one.
+    base_depth: usize,
+    catch_policy: CatchPolicy,
+    exception_classes: ExceptionClasses,
+    config: ConfigStore,
+    custom_opcodes: HashMap<u8, CustomOpcodeHandler>,
+    custom_effects: HashMap<u8, (usize, usize)>,
+    global_names: HashMap<String, usize>,
+    require_verification: bool,
+    verified: HashMap<*const Function, Rc<Function>>,
+    /// Functions decoded by `push_frame`.
+    decoded: HashMap<*const Function, Decoded>,
+    /// Calls plus back edges after which a function runs in register form, if it can.
+    tier_up_threshold: Option<u32>,
+    /// The instruction executing, and how many of its operands the handler has read.
+    instruction: DecodedInstr,
+    next_operand: usize,
+    limits: VMLimits,
+    /// Values and frames each stack is reserved for, see `IrisVM::preallocate`.
+    capacity: (usize, usize),
+    high_water: HighWater,
+    /// Argument vectors for native calls, reused so a call doesn't allocate.
+    native_args: Vec<Vec<Value>>,
+    jit_enabled: bool,
+    fuel: Option<u64>,
+    heap_bytes: usize,
+    interrupt: InterruptHandle,
+    breakpoints: Breakpoints,
+    resuming: bool,
+    profile: Option<Profile>,
+    stats: Option<ExecutionStats>,
+    coverage: Option<Coverage>,
+    heap: Heap,
+    report_cycles_on_drop: bool,
+    /// Upvalues still referring to stack slots, see `vm::closure`.
+    open_upvalues: Vec<UpvalueRef>,
+    /// Running coroutines, innermost last.
+    coroutines: Vec<ActiveCoroutine>,
+    /// The pending future the last `Await` stopped at.
+    awaiting: Option<Gc<HostFuture>>,
+    scheduler: Scheduler,
+    /// `execute` calls in progress; more than one means a host call is running bytecode.
+    execute_depth: usize,
+    monitors: Monitors,
+    detect_deadlocks: bool,
+    capabilities: HashSet<Capability>,
+    fs_policy: FsPolicy,
+    clock: Rc<dyn Clock>,
+    rng: Rng,
+    /// See `make_deterministic`.
+    deterministic: bool,
+    args: Vec<String>,
+    modules: Modules,
+    /// Resource files of the packages loaded, see `data::package`.
+    resources: HashMap<String, Vec<u8>>,
+    inline_caches: InlineCaches,
+    /// Classes `InitializeClass` has run the initializer of.
+    initialized_classes: HashMap<*const Class, Weak<Class>>,
 }
 
-struct CallFrame {
+pub(crate) struct CallFrame {
     function: Rc<Function>,
+    code: Rc<DecodedCode>,
+    /// Run in place of `code` from the start or a jump target, see `vm::register`.
+    registers: Option<Rc<RegisterCode>>,
+    /// Whether a back edge has already tried to tier the function up.
+    tier_checked: bool,
     ip: usize,
     stack_base: usize,
+    closure: Option<Rc<Closure>>,
+    /// Constructors and class initializers return nothing; see `AllocateObject`.
+    discard_result: bool,
+}
+
+/// A function's decoded code and the register form it tiered up to.
+struct Decoded {
+    /// Keeps the address from being reused while the entry exists.
+    function: Weak<Function>,
+    code: Rc<DecodedCode>,
+    tier: Tier,
+}
+
+enum Tier {
+    Bytecode,
+    Registers(Rc<RegisterCode>),
+    /// Hot, but it has no register form.
+    Untranslatable,
+}
+
+/// A coroutine that is running, with where its frames and stack start.
+struct ActiveCoroutine {
+    coroutine: Gc<Coroutine>,
+    depth: usize,
+    stack_base: usize,
 }
 
 impl CallFrame {
         #[allow(dead_code)]
     pub fn new(function: Rc<Function>, stack_base: usize) -> Self {
         CallFrame {
+            code: Rc::new(DecodedCode::new(function.bytecode.as_deref().unwrap_or_default())),
+            registers: function.registers.clone(),
+            tier_checked: false,
             function,
             ip: 0,
             stack_base,
+            closure: None,
+            discard_result: false,
         }
     }
 }
 
-struct TryFrame {
+/// Read-only view of an active call frame, see `IrisVM::frames`.
+pub struct FrameView<'a> {
+    frame: &'a CallFrame,
+    locals: &'a [Value],
+}
+
+impl<'a> FrameView<'a> {
+    pub fn function(&self) -> &'a Rc<Function> {
+        &self.frame.function
+    }
+
+    pub fn name(&self) -> &'a str {
+        &self.frame.function.name
+    }
+
+    /// Offset of the next instruction this frame will execute.
+    pub fn ip(&self) -> usize {
+        self.frame.ip
+    }
+
+    /// Index of the frame's first slot in `IrisVM::stack`.
+    pub fn stack_base(&self) -> usize {
+        self.frame.stack_base
+    }
+
+    /// The frame's slots of the operand stack: its arguments and locals followed by its
+    /// temporaries, up to where the next frame starts.
+    pub fn locals(&self) -> &'a [Value] {
+        self.locals
+    }
+}
+
+pub(crate) struct TryFrame {
     ip: usize,
     stack_size: usize,
+    /// Call frames when the block began; unwinding to it pops those above.
+    depth: usize,
+}
+
+impl Drop for IrisVM {
+    fn drop(&mut self) {
+        if self.report_cycles_on_drop {
+            for cycle in self.find_cycles() {
+                eprint!("{}", cycle);
+            }
+        }
+    }
+}
+
+impl Default for IrisVM {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IrisVM {
@@ -107,7 +582,352 @@ impl IrisVM {
             frames: vec![], // Initial call frame will be pushed when a function is called
             globals: Vec::new(),
             try_frames: Vec::new(),
+            base_depth: 0,
+            catch_policy: CatchPolicy::default(),
+            exception_classes: ExceptionClasses::new(),
+            config: ConfigStore::default(),
+            custom_opcodes: HashMap::new(),
+            custom_effects: HashMap::new(),
+            global_names: HashMap::new(),
+            require_verification: false,
+            verified: HashMap::new(),
+            decoded: HashMap::new(),
+            tier_up_threshold: Some(DEFAULT_TIER_UP_THRESHOLD),
+            instruction: DecodedInstr::default(),
+            next_operand: 0,
+            limits: VMLimits::default(),
+            capacity: (0, 0),
+            high_water: HighWater::default(),
+            native_args: Vec::new(),
+            jit_enabled: false,
+            fuel: None,
+            heap_bytes: 0,
+            interrupt: InterruptHandle::default(),
+            breakpoints: Breakpoints::default(),
+            resuming: false,
+            profile: None,
+            stats: None,
+            coverage: None,
+            heap: Heap::default(),
+            report_cycles_on_drop: false,
+            open_upvalues: Vec::new(),
+            coroutines: Vec::new(),
+            awaiting: None,
+            scheduler: Scheduler::default(),
+            execute_depth: 0,
+            monitors: Monitors::default(),
+            detect_deadlocks: cfg!(debug_assertions),
+            capabilities: HashSet::new(),
+            fs_policy: FsPolicy::default(),
+            clock: Rc::new(SystemClock::new()),
+            rng: Rng::from_entropy(),
+            deterministic: false,
+            args: Vec::new(),
+            modules: Modules::default(),
+            resources: HashMap::new(),
+            inline_caches: InlineCaches::default(),
+            initialized_classes: HashMap::new(),
+        }
+    }
+
+    pub fn builder() -> IrisVMBuilder {
+        IrisVMBuilder::new()
+    }
+
+    /// Reserves room for `stack` values and `frames` call frames, so running within them
+    /// doesn't reallocate. Fibers get the same room when they first run.
+    pub fn preallocate(&mut self, stack: usize, frames: usize) {
+        self.capacity = (stack, frames);
+        self.reserve_stacks();
+    }
+
+    fn reserve_stacks(&mut self) {
+        let (stack, frames) = self.capacity;
+        self.stack.reserve(stack.saturating_sub(self.stack.len()));
+        self.frames.reserve(frames.saturating_sub(self.frames.len()));
+    }
+
+    /// The most values and frames held at once since the VM started or `reset_high_water`.
+    pub fn high_water(&self) -> HighWater {
+        self.high_water
+    }
+
+    pub fn reset_high_water(&mut self) {
+        self.high_water = HighWater { stack: self.stack.len(), frames: self.frames.len() };
+    }
+
+    pub fn limits(&self) -> VMLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: VMLimits) {
+        self.limits = limits;
+    }
+
+    /// Remaining instruction budget, or `None` when execution is unmetered.
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    /// Meters execution: every instruction costs one unit and `run()` stops with `OutOfFuel`
+    /// when none is left. `None` turns metering off.
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.fuel = fuel;
+    }
+
+    /// Tops up the budget of a metered VM; does nothing when execution is unmetered.
+    pub fn add_fuel(&mut self, amount: u64) {
+        if let Some(fuel) = &mut self.fuel {
+            *fuel = fuel.saturating_add(amount);
+        }
+    }
+
+    /// Approximate bytes held by guest strings, arrays, maps and instances. Allocations add
+    /// to it as they happen; frees are only noticed by `recount_heap`.
+    pub fn heap_usage(&self) -> usize {
+        self.heap_bytes
+    }
+
+    /// Recomputes `heap_usage` from what the stack and globals can still reach.
+    pub fn recount_heap(&mut self) -> usize {
+        self.heap_bytes = memory::reachable_size(self.stack.iter().chain(self.globals.iter()));
+        self.heap_bytes
+    }
+
+    /// Registers a new array, map or instance with the garbage collector, collecting first if
+    /// enough has been allocated since the last collection.
+    fn allocate(&mut self, value: Value) -> Value {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+        self.heap.track(&value);
+        value
+    }
+
+    /// Frees unreachable cycles of arrays, maps and instances, see `vm::gc`. Returns how many
+    /// objects were swept. Also runs on its own as the guest allocates, and drops interned
+    /// strings nothing refers to any more.
+    pub fn collect_garbage(&mut self) -> usize {
+        let swept = self.heap.collect();
+        intern::purge();
+        swept
+    }
+
+    pub fn heap(&self) -> &Heap {
+        &self.heap
+    }
+
+    /// Reference cycles among the arrays, maps and instances the stack, globals or heap hold.
+    pub fn find_cycles(&self) -> Vec<Cycle> {
+        find_cycles(self.stack.iter().chain(&self.globals), self.heap.objects())
+    }
+
+    /// Prints `find_cycles` to stderr when the VM is dropped, to find script-level leaks.
+    pub fn set_report_cycles_on_drop(&mut self, enabled: bool) {
+        self.report_cycles_on_drop = enabled;
+    }
+
+    /// Accounts for `bytes` about to be allocated, failing with `OutOfMemory` if that would
+    /// exceed the heap limit even after recounting what is still reachable.
+    fn charge_heap(&mut self, bytes: usize) -> Result<(), VMError> {
+        if let Some(limit) = self.limits.max_heap_bytes {
+            if self.heap_bytes.saturating_add(bytes) > limit {
+                self.collect_garbage();
+                if self.recount_heap().saturating_add(bytes) > limit {
+                    return Err(VMError::OutOfMemory { limit });
+                }
+            }
+        }
+        self.heap_bytes += bytes;
+        Ok(())
+    }
+
+    /// A handle that can stop this VM from another thread, see `InterruptHandle`.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Pauses `run()` before the instruction at `offset` in every function named `function`.
+    /// Returns `false` if the breakpoint was already set.
+    pub fn add_breakpoint(&mut self, function: &str, offset: usize) -> bool {
+        self.breakpoints.insert(function, offset)
+    }
+
+    pub fn remove_breakpoint(&mut self, function: &str, offset: usize) -> bool {
+        self.breakpoints.remove(function, offset)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+
+    /// Decides what happens at each breakpoint. Without a handler `run()` always pauses.
+    pub fn set_breakpoint_handler(&mut self, handler: impl Fn(&mut IrisVM, &SourceLocation) -> BreakpointAction + 'static) {
+        self.breakpoints.handler = Some(Rc::new(handler));
+    }
+
+    /// Samples the call stack every `interval` instructions until `stop_profiling`.
+    /// Replaces any profile in progress.
+    pub fn start_profiling(&mut self, interval: u64) {
+        self.profile = Some(Profile::new(interval));
+    }
+
+    pub fn stop_profiling(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    fn sample_profile(&mut self) {
+        if !self.profile.as_mut().is_some_and(Profile::tick) {
+            return;
+        }
+        let frames: Vec<(String, usize)> = self.call_stack().frames().iter().map(|at| (at.function.clone(), at.offset)).collect();
+        if let Some(profile) = &mut self.profile {
+            profile.record(&frames);
+        }
+    }
+
+    /// Counts and times every instruction executed from now on. Timing makes dispatch
+    /// noticeably slower, so this is off by default. Disabling drops the collected stats.
+    pub fn set_stats_enabled(&mut self, enabled: bool) {
+        match (enabled, &self.stats) {
+            (true, None) => self.stats = Some(ExecutionStats::default()),
+            (false, _) => self.stats = None,
+            _ => {}
+        }
+    }
+
+    pub fn stats(&self) -> Option<&ExecutionStats> {
+        self.stats.as_ref()
+    }
+
+    /// Zeroes the collected stats, if enabled.
+    pub fn reset_stats(&mut self) {
+        if let Some(stats) = &mut self.stats {
+            *stats = ExecutionStats::default();
+        }
+    }
+
+    /// Records which instructions execute from now on. Disabling drops the collected coverage.
+    pub fn set_coverage_enabled(&mut self, enabled: bool) {
+        match (enabled, &self.coverage) {
+            (true, None) => self.coverage = Some(Coverage::default()),
+            (false, _) => self.coverage = None,
+            _ => {}
+        }
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// The instruction the top frame will execute next.
+    pub fn current_location(&self) -> Option<SourceLocation> {
+        let frame = self.frames.last()?;
+        Some(SourceLocation {
+            function: frame.function.name.clone(),
+            offset: frame.ip,
+            span: frame.function.lines.span_at(frame.ip),
+        })
+    }
+
+    /// The active frames, innermost first. The top frame points at the instruction it will
+    /// execute next, the others at their pending call.
+    pub fn call_stack(&self) -> Backtrace {
+        let top = self.frames.len().saturating_sub(1);
+        let frames = self.frames.iter().enumerate().rev().map(|(index, frame)| {
+            let offset = if index == top { frame.ip } else { frame.ip.saturating_sub(1) };
+            SourceLocation {
+                function: frame.function.name.clone(),
+                offset,
+                span: frame.function.lines.span_at(offset),
+            }
+        });
+        Backtrace::new(frames.collect())
+    }
+
+    /// The active call frames, innermost first.
+    pub fn frames(&self) -> impl DoubleEndedIterator<Item = FrameView<'_>> + ExactSizeIterator + '_ {
+        self.frames.iter().enumerate().rev().map(|(index, frame)| {
+            let end = self.frames.get(index + 1).map_or(self.stack.len(), |callee| callee.stack_base);
+            let locals = self.stack.get(frame.stack_base..end.max(frame.stack_base)).unwrap_or_default();
+            FrameView { frame, locals }
+        })
+    }
+
+    pub(crate) fn global_names(&self) -> &HashMap<String, usize> {
+        &self.global_names
+    }
+
+    /// Whether this build has a JIT compiler to honor `set_jit_enabled`. None does yet: the
+    /// fastest tier is the register form, which every build has.
+    pub fn jit_available() -> bool {
+        false
+    }
+
+    pub fn jit_enabled(&self) -> bool {
+        self.jit_enabled
+    }
+
+    pub fn set_jit_enabled(&mut self, enabled: bool) {
+        self.jit_enabled = enabled;
+    }
+
+    /// When set, every bytecode function must pass `verify` before a frame is pushed for it.
+    /// Results are cached per function, so each one is only checked once.
+    pub fn set_require_verification(&mut self, required: bool) {
+        self.require_verification = required;
+    }
+
+    pub fn push_verified_frame(&mut self, verified: &VerifiedFunction, arg_count: usize) -> Result<(), VMError> {
+        let function = verified.function().clone();
+        self.verified.insert(Rc::as_ptr(&function), function.clone());
+        self.push_frame(function, arg_count)
+    }
+
+    /// Installs a handler for one of the reserved opcode bytes (`CUSTOM_OPCODES`). `effect` is
+    /// the (pops, pushes) the handler leaves on the operand stack; the verifier trusts it.
+    pub fn register_custom_opcode(
+        &mut self,
+        byte: u8,
+        effect: (usize, usize),
+        handler: impl Fn(&mut IrisVM, Option<u8>) -> Result<(), VMError> + 'static,
+    ) -> Result<(), VMError> {
+        if !CUSTOM_OPCODES.contains(&byte) {
+            return Err(VMError::InvalidOperand(format!("Opcode {:#04x} is outside the reserved custom range", byte)));
         }
+        self.custom_opcodes.insert(byte, Rc::new(handler));
+        self.custom_effects.insert(byte, effect);
+        Ok(())
+    }
+
+    fn verify_function(&self, function: &Rc<Function>) -> Result<VerifiedFunction, VMError> {
+        verify_with_custom(function, &self.custom_effects).map_err(VMError::VerificationFailed)
+    }
+
+    fn dispatch_custom(&mut self, byte: u8) -> Result<(), VMError> {
+        let handler = self.custom_opcodes.get(&byte).cloned().ok_or(VMError::UnknownOpCode)?;
+        let operand = if CUSTOM_OPCODES_WITH_OPERAND.contains(&byte) {
+            Some(self.read_byte()?)
+        } else {
+            None
+        };
+        handler(self, operand)
+    }
+
+    pub fn catch_policy(&self) -> &CatchPolicy {
+        &self.catch_policy
+    }
+
+    pub fn set_catch_policy(&mut self, policy: CatchPolicy) {
+        self.catch_policy = policy;
     }
 
     pub fn current_frame_stack_offset(&self) -> usize {
@@ -117,15 +937,99 @@ impl IrisVM {
     // ... rest of the impl IrisVM block ...
 
         pub fn push_frame(&mut self, function: Rc<Function>, arg_count: usize) -> Result<(), VMError> {
+        if self.require_verification && function.bytecode.is_some() && !self.verified.contains_key(&Rc::as_ptr(&function)) {
+            self.verify_function(&function)?;
+            self.verified.insert(Rc::as_ptr(&function), function.clone());
+        }
+        if let Some(limit) = self.limits.max_call_depth.filter(|limit| self.frames.len() >= *limit) {
+            return Err(VMError::CallDepthExceeded { limit });
+        }
+        function.hotness.record_invocation();
         let frame = CallFrame {
+            code: self.decode(&function),
+            registers: self.tier_up(&function),
+            tier_checked: false,
             function,
             ip: 0,
             stack_base: self.stack.len() - arg_count,
+            closure: None,
+            discard_result: false,
         };
         self.frames.push(frame);
+        self.high_water.frames = self.high_water.frames.max(self.frames.len());
+        Ok(())
+    }
+
+    /// `function`'s decoded code, decoding it the first time it runs.
+    fn decode(&mut self, function: &Rc<Function>) -> Rc<DecodedCode> {
+        if let Some(decoded) = self.decoded.get(&Rc::as_ptr(function)) {
+            return decoded.code.clone();
+        }
+        // Forget functions that have been dropped once they could make up half the cache.
+        if self.decoded.len() >= 64 && self.decoded.len().is_power_of_two() {
+            self.decoded.retain(|_, decoded| decoded.function.strong_count() > 0);
+        }
+        let code = Rc::new(DecodedCode::new(function.bytecode.as_deref().unwrap_or_default()));
+        let decoded = Decoded { function: Rc::downgrade(function), code: code.clone(), tier: Tier::Bytecode };
+        self.decoded.insert(Rc::as_ptr(function), decoded);
+        code
+    }
+
+    /// The register form `function` runs in: its own, or the one it is translated to once
+    /// it gets hot. Functions that don't translate stay bytecode.
+    fn tier_up(&mut self, function: &Rc<Function>) -> Option<Rc<RegisterCode>> {
+        if function.registers.is_some() {
+            return function.registers.clone();
+        }
+        let threshold = self.tier_up_threshold?;
+        let decoded = self.decoded.get_mut(&Rc::as_ptr(function))?;
+        match &decoded.tier {
+            Tier::Registers(registers) => return Some(registers.clone()),
+            Tier::Untranslatable => return None,
+            Tier::Bytecode if function.hotness.total() < threshold || function.bytecode.is_none() => return None,
+            Tier::Bytecode => {}
+        }
+        decoded.tier = match verify(function).ok().and_then(|verified| translate(&verified).ok()) {
+            Some(registers) => Tier::Registers(Rc::new(registers)),
+            None => Tier::Untranslatable,
+        };
+        match &decoded.tier {
+            Tier::Registers(registers) => Some(registers.clone()),
+            _ => None,
+        }
+    }
+
+    /// Counts a back edge of the running frame. Once its function is hot, the frame gets the
+    /// register form to carry on in from the loop header: on-stack replacement.
+    fn back_edge(&mut self) -> Result<(), VMError> {
+        let frame = self.frames.last_mut().ok_or(VMError::NoActiveCallFrame)?;
+        frame.function.hotness.record_back_edge();
+        let hot = self.tier_up_threshold.is_some_and(|threshold| frame.function.hotness.total() >= threshold);
+        if !hot || frame.registers.is_some() || frame.tier_checked {
+            return Ok(());
+        }
+        frame.tier_checked = true;
+        let function = frame.function.clone();
+        let registers = self.tier_up(&function);
+        self.current_frame_mut()?.registers = registers;
         Ok(())
     }
 
+    /// Calls plus loop back edges after which a function runs in register form, or `None`
+    /// to only run functions given one by `register::translate_function` that way.
+    pub fn tier_up_threshold(&self) -> Option<u32> {
+        self.tier_up_threshold
+    }
+
+    pub fn set_tier_up_threshold(&mut self, threshold: Option<u32>) {
+        self.tier_up_threshold = threshold;
+    }
+
+    /// Whether `function` has tiered up to register form.
+    pub fn is_tiered_up(&self, function: &Rc<Function>) -> bool {
+        self.decoded.get(&Rc::as_ptr(function)).is_some_and(|decoded| matches!(decoded.tier, Tier::Registers(_)))
+    }
+
     fn current_frame_mut(&mut self) -> Result<&mut CallFrame, VMError> {
         self.frames.last_mut().ok_or(VMError::NoActiveCallFrame)
     }
@@ -134,30 +1038,25 @@ impl IrisVM {
         self.frames.last().ok_or(VMError::NoActiveCallFrame)
     }
 
+    /// The executing instruction's next operand; see `vm::decoded`.
+    fn read_operand(&mut self) -> Result<u64, VMError> {
+        let operand = *self.instruction.operands().get(self.next_operand)
+            .ok_or_else(|| VMError::InvalidOperand("Instruction pointer out of bounds".to_string()))?;
+        self.next_operand += 1;
+        Ok(operand)
+    }
+
     fn read_byte(&mut self) -> Result<u8, VMError> {
-        let frame = self.current_frame_mut()?;
-        let bytecode = frame.function.bytecode.as_ref().ok_or(VMError::InvalidOperand("Bytecode not found".to_string()))?;
-        if frame.ip >= bytecode.len() {
-            return Err(VMError::InvalidOperand("Instruction pointer out of bounds".to_string()));
-        }
-        let byte = bytecode[frame.ip];
-        frame.ip += 1;
-        Ok(byte)
+        self.read_operand().map(|operand| operand as u8)
     }
 
     fn read_u16(&mut self) -> Result<u16, VMError> {
-        let byte1 = self.read_byte()?;
-        let byte2 = self.read_byte()?;
-        Ok(u16::from_be_bytes([byte1, byte2]))
+        self.read_operand().map(|operand| operand as u16)
     }
 
     #[allow(dead_code)]
     fn read_u32(&mut self) -> Result<u32, VMError> {
-        let byte1 = self.read_byte()?;
-        let byte2 = self.read_byte()?;
-        let byte3 = self.read_byte()?;
-        let byte4 = self.read_byte()?;
-        Ok(((byte1 as u32) << 24) | ((byte2 as u32) << 16) | ((byte3 as u32) << 8) | (byte4 as u32))
+        self.read_operand().map(|operand| operand as u32)
     }
 
     fn read_i8(&mut self) -> Result<i8, VMError> {
@@ -165,39 +1064,23 @@ impl IrisVM {
     }
 
     fn read_i16(&mut self) -> Result<i16, VMError> {
-        let byte1 = self.read_byte()?;
-        let byte2 = self.read_byte()?;
-        Ok(i16::from_be_bytes([byte1, byte2]))
+        self.read_u16().map(|operand| operand as i16)
     }
 
     fn read_i32(&mut self) -> Result<i32, VMError> {
-        let bytes = [
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-        ];
-        Ok(i32::from_be_bytes(bytes))
+        self.read_operand().map(|operand| operand as u32 as i32)
     }
 
     fn read_i64(&mut self) -> Result<i64, VMError> {
-        let bytes = [
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-        ];
-        Ok(i64::from_be_bytes(bytes))
+        self.read_operand().map(|operand| operand as i64)
     }
 
     fn read_f32(&mut self) -> Result<f32, VMError> {
-        let bytes = [
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-        ];
-        Ok(f32::from_be_bytes(bytes))
+        self.read_operand().map(|operand| f32::from_bits(operand as u32))
     }
 
     fn read_f64(&mut self) -> Result<f64, VMError> {
-        let bytes = [
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-            self.read_byte()?, self.read_byte()?, self.read_byte()?, self.read_byte()?,
-        ];
-        Ok(f64::from_be_bytes(bytes))
+        self.read_operand().map(f64::from_bits)
     }
 
     fn read_constant8(&mut self) -> Result<Value, VMError> {
@@ -306,8 +1189,33 @@ impl IrisVM {
         todo!()
     }
 
+    /// Runs the class's `clinit` method, once per class. Superclasses aren't initialized
+    /// along with it.
     fn handle_initialize_class(&mut self) -> Result<(), VMError> {
-        todo!()
+        let Value::Class(class) = self.pop_stack()? else { return Err(VMError::NonClassValue) };
+        let key = Rc::as_ptr(&class);
+        if self.initialized_classes.get(&key).is_some_and(|initialized| initialized.strong_count() > 0) {
+            return Ok(());
+        }
+        self.initialized_classes.insert(key, Rc::downgrade(&class));
+        let Some(initializer) = class.methods.iter().find(|method| method.name == CLASS_INITIALIZER).cloned() else {
+            return Ok(());
+        };
+        if initializer.arity != 0 {
+            return Err(VMError::ArityMismatch { expected: 0, found: initializer.arity });
+        }
+        match initializer.kind {
+            crate::vm::function::FunctionKind::Native => {
+                self.stack.push(Value::Class(class));
+                self.call_native(&initializer, 0, false)?;
+                self.pop_stack()?;
+            }
+            crate::vm::function::FunctionKind::Bytecode => {
+                self.push_frame(initializer, 0)?;
+                self.current_frame_mut()?.discard_result = true;
+            }
+        }
+        Ok(())
     }
 
     fn handle_check_cast_object(&mut self) -> Result<(), VMError> {
@@ -318,32 +1226,115 @@ impl IrisVM {
         todo!()
     }
 
+    /// Pops a class and pushes its method, or its superclasses', named by the u16 constant.
+    /// The handle is a plain function taking the receiver as its first argument.
     fn handle_load_method_handle(&mut self) -> Result<(), VMError> {
-        todo!()
+        let (name_index, name) = self.read_member_name()?;
+        let Value::Class(class) = self.pop_stack()? else { return Err(VMError::NonClassValue) };
+        match inline_cache::resolve_method(&class, &name) {
+            Some(Resolved::Method(method)) => {
+                self.stack.push(Value::Function(method));
+                Ok(())
+            }
+            _ => Err(VMError::MethodNotFound(name_index)),
+        }
     }
 
+    /// Pops a receiver and a method handle and pushes the method bound to the receiver.
     fn handle_bind_method_handle(&mut self) -> Result<(), VMError> {
-        todo!()
+        let receiver = self.pop_stack()?;
+        let Value::Function(method) = self.pop_stack()? else { return Err(VMError::NonCallableValue) };
+        self.stack.push(Value::BoundMethod(Rc::new(BoundMethod { receiver, method })));
+        Ok(())
     }
 
+    /// Pops a class or an instance and pushes its class's methods by slot, as a tuple.
     fn handle_get_virtual_table(&mut self) -> Result<(), VMError> {
-        todo!()
+        let class = match self.pop_stack()? {
+            Value::Class(class) => class,
+            Value::Object(instance) => instance.borrow().class.clone(),
+            _ => return Err(VMError::NonClassValue),
+        };
+        let methods: Vec<Value> = class.vtable().iter().map(|method| Value::Function(method.clone())).collect();
+        self.stack.push(Value::Tuple(methods.into()));
+        Ok(())
     }
 
+    /// Pops a tuple or array of functions and a class, and makes the functions the class's
+    /// table.
     fn handle_set_virtual_table(&mut self) -> Result<(), VMError> {
-        todo!()
+        let table = self.pop_stack()?;
+        let Value::Class(class) = self.pop_stack()? else { return Err(VMError::NonClassValue) };
+        let entries = match &table {
+            Value::Tuple(elements) => elements.to_vec(),
+            Value::Array(array) => array.borrow().clone(),
+            _ => return Err(VMError::TypeMismatch("SetVirtualTable expects a tuple or array of functions".to_string())),
+        };
+        let vtable = entries.into_iter()
+            .map(|entry| match entry {
+                Value::Function(method) => Ok(method),
+                _ => Err(VMError::TypeMismatch("Virtual table entries must be functions".to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        class.set_vtable(vtable.into());
+        self.inline_caches.invalidate(&class);
+        Ok(())
     }
 
+    /// Pops a class and `arg_count` arguments, and pushes a new instance with a slot for each
+    /// of the class's properties, after calling its `init` method, if any, on it with the
+    /// arguments. A bytecode `init` gets the instance as local 0; what it returns is dropped.
     fn handle_allocate_object(&mut self) -> Result<(), VMError> {
-        todo!()
-    }
-
-    fn handle_free_object(&mut self) -> Result<(), VMError> {
-        todo!()
-    }
+        let arg_count = self.read_byte()? as usize;
+        let Value::Class(class) = self.peek_stack(arg_count)?.clone() else { return Err(VMError::NonClassValue) };
+        let mut instance = Instance::new(class.clone());
+        instance.fields = vec![Value::Null; instance.shape.len()];
+        self.charge_heap(std::mem::size_of::<Value>() * (instance.fields.len() + 1))?;
+        let instance = self.allocate(Value::Object(Gc::new(instance)));
+        let receiver = self.stack.len() - 1 - arg_count;
+        self.stack[receiver] = instance.clone();
+
+        let init = match inline_cache::resolve_method(&class, CONSTRUCTOR) {
+            Some(Resolved::Method(init)) => init,
+            _ if arg_count == 0 => return Ok(()),
+            _ => return Err(VMError::ArityMismatch { expected: 0, found: arg_count }),
+        };
+        self.stack.insert(receiver + 1, instance);
+        match init.kind {
+            crate::vm::function::FunctionKind::Native => {
+                self.call_native(&init, arg_count, true)?;
+                self.pop_stack()?;
+            }
+            crate::vm::function::FunctionKind::Bytecode => {
+                if init.arity != arg_count + 1 {
+                    return Err(VMError::ArityMismatch { expected: init.arity, found: arg_count + 1 });
+                }
+                self.push_frame(init, arg_count + 1)?;
+                self.current_frame_mut()?.discard_result = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Pops an object and drops everything it refers to. The instance itself lives on, with
+    /// no properties, until nothing refers to it; the collector owns deallocation.
+    fn handle_free_object(&mut self) -> Result<(), VMError> {
+        let Value::Object(object) = self.pop_stack()? else { return Err(VMError::NonObjectValue) };
+        let mut instance = object.borrow_mut();
+        instance.shape = instance.class.initial_shape();
+        instance.fields.clear();
+        Ok(())
+    }
 
     fn handle_short_jump(&mut self) -> Result<(), VMError> {
-        todo!()
+        let offset = self.read_byte()? as i8;
+        let frame = self.current_frame_mut()?;
+        frame.ip = frame.ip.checked_add_signed(offset as isize)
+            .ok_or_else(|| VMError::InvalidOperand("Short jump before start of function".to_string()))?;
+        if offset < 0 {
+            self.back_edge()?;
+        }
+        Ok(())
     }
 
     fn handle_jump_if_true(&mut self) -> Result<(), VMError> {
@@ -382,16 +1373,35 @@ impl IrisVM {
         todo!()
     }
 
+    /// Starts a handler that only takes exceptions of the class named by the u16 constant or
+    /// a subclass; anything else is thrown on.
     fn handle_catch_exception(&mut self) -> Result<(), VMError> {
-        todo!()
+        let (_, name) = self.read_member_name()?;
+        let matches = match self.peek_stack(0)? {
+            Value::Object(exception) => exception::is_named_or_extends(&exception.borrow().class, &name),
+            _ => false,
+        };
+        if !matches {
+            let exception = self.pop_stack()?;
+            self.unwind(exception)?;
+        }
+        Ok(())
     }
 
+    /// Begins a block whose handler runs for any exception leaving it, then passes the
+    /// exception on with `UnwindStack`. The normal path ends the block with `EndTryBlock`,
+    /// pushes null and falls into the same handler.
     fn handle_finally_block(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.handle_begin_try_block()
     }
 
+    /// Ends a finally handler: pops what the handler started with and, unless it's null,
+    /// throws it on to the next enclosing try block, keeping the unwinding going.
     fn handle_unwind_stack(&mut self) -> Result<(), VMError> {
-        todo!()
+        match self.pop_stack()? {
+            Value::Null => Ok(()),
+            exception => self.unwind(exception),
+        }
     }
 
     fn handle_boolean_and_operation(&mut self) -> Result<(), VMError> {
@@ -443,7 +1453,7 @@ impl IrisVM {
     }
 
     fn handle_add_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_arithmetic("AddInt64", |a, b| Some(a.wrapping_add(b)), |a, b| a + b, false)
     }
 
     fn handle_add_float32(&mut self) -> Result<(), VMError> {
@@ -455,7 +1465,7 @@ impl IrisVM {
     }
 
     fn handle_subtract_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_arithmetic("SubtractInt64", |a, b| Some(a.wrapping_sub(b)), |a, b| a - b, false)
     }
 
     fn handle_subtract_float32(&mut self) -> Result<(), VMError> {
@@ -467,7 +1477,7 @@ impl IrisVM {
     }
 
     fn handle_multiply_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_arithmetic("MultiplyInt64", |a, b| Some(a.wrapping_mul(b)), |a, b| a * b, false)
     }
 
     fn handle_multiply_float32(&mut self) -> Result<(), VMError> {
@@ -479,7 +1489,7 @@ impl IrisVM {
     }
 
     fn handle_divide_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_division("DivideInt64", false)
     }
 
     fn handle_divide_float32(&mut self) -> Result<(), VMError> {
@@ -491,11 +1501,74 @@ impl IrisVM {
     }
 
     fn handle_modulo_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_division("ModuloInt64", true)
     }
 
     fn handle_negate_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        let result = match value_to_integer(&self.pop_stack()?) {
+            Some(Integer::Small(value)) => Value::I64(value.wrapping_neg()),
+            Some(Integer::Big(value)) => Value::BigInt(Rc::new(-&value)),
+            None => return Err(VMError::TypeMismatch("Operand for NegateInt64 must be I64 or BigInt".to_string())),
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    /// Pops the two operands of an Int64 instruction, see `Integer`.
+    fn pop_integer_pair(&mut self, name: &str) -> Result<(Integer, Integer), VMError> {
+        let b = self.pop_stack()?;
+        let a = self.pop_stack()?;
+        match (value_to_integer(&a), value_to_integer(&b)) {
+            (Some(a), Some(b)) => Ok((a, b)),
+            _ => Err(VMError::TypeMismatch(format!("Operands for {} must be I64 or BigInt", name))),
+        }
+    }
+
+    /// Two I64s give `small`'s result, or with `promote` the big integer result once it
+    /// overflows. A BigInt operand makes the result a BigInt, narrowed back to I64 if it fits
+    /// when promoting.
+    fn int64_arithmetic(
+        &mut self,
+        name: &str,
+        small: fn(i64, i64) -> Option<i64>,
+        big: fn(&BigInt, &BigInt) -> BigInt,
+        promote: bool,
+    ) -> Result<(), VMError> {
+        let (a, b) = self.pop_integer_pair(name)?;
+        let result = match (&a, &b) {
+            (Integer::Small(x), Integer::Small(y)) => match small(*x, *y) {
+                Some(result) => Value::I64(result),
+                None => integer_value(big(&a.to_big(), &b.to_big())),
+            },
+            _ if promote => integer_value(big(&a.to_big(), &b.to_big())),
+            _ => Value::BigInt(Rc::new(big(&a.to_big(), &b.to_big()))),
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn int64_division(&mut self, name: &str, remainder: bool) -> Result<(), VMError> {
+        let (a, b) = self.pop_integer_pair(name)?;
+        let result = match (&a, &b) {
+            (_, Integer::Small(0)) => return Err(VMError::DivisionByZero),
+            (Integer::Small(x), Integer::Small(y)) => Value::I64(if remainder { x.wrapping_rem(*y) } else { x.wrapping_div(*y) }),
+            _ => {
+                let (quotient, rest) = a.to_big().div_rem(&b.to_big()).ok_or(VMError::DivisionByZero)?;
+                Value::BigInt(Rc::new(if remainder { rest } else { quotient }))
+            }
+        };
+        self.stack.push(result);
+        Ok(())
+    }
+
+    fn int64_comparison(&mut self, name: &str, test: fn(Ordering) -> bool) -> Result<(), VMError> {
+        let (a, b) = self.pop_integer_pair(name)?;
+        let ordering = match (&a, &b) {
+            (Integer::Small(x), Integer::Small(y)) => x.cmp(y),
+            _ => a.to_big().cmp(&b.to_big()),
+        };
+        self.stack.push(Value::Bool(test(ordering)));
+        Ok(())
     }
 
     fn handle_negate_float32(&mut self) -> Result<(), VMError> {
@@ -523,7 +1596,42 @@ impl IrisVM {
     }
 
     fn handle_add_int32_with_constant(&mut self) -> Result<(), VMError> {
-        todo!()
+        let constant = self.read_byte()? as i8 as i32;
+        match self.pop_stack()? {
+            Value::I32(x) => self.stack.push(Value::I32(x.wrapping_add(constant))),
+            _ => return Err(VMError::TypeMismatch("Operand for AddInt32WithConstant must be I32".to_string())),
+        }
+        Ok(())
+    }
+
+    fn handle_add_local_int32_with_constant(&mut self) -> Result<(), VMError> {
+        let slot = self.read_byte()? as usize;
+        let constant = self.read_byte()? as i8 as i32;
+        let local = self.current_frame()?.stack_base + slot;
+        match self.stack.get(local) {
+            Some(Value::I32(x)) => {
+                let sum = Value::I32(x.wrapping_add(constant));
+                self.stack[local] = sum.clone();
+                self.stack.push(sum);
+            }
+            _ => return Err(VMError::TypeMismatch("Operand for AddInt32WithConstant must be I32".to_string())),
+        }
+        Ok(())
+    }
+
+    fn handle_jump_if_locals_not_less_int32(&mut self) -> Result<(), VMError> {
+        let a = self.read_byte()? as usize;
+        let b = self.read_byte()? as usize;
+        let offset = self.read_u16()? as usize;
+        let stack_base = self.current_frame()?.stack_base;
+        let (Some(a), Some(b)) = (self.stack.get(stack_base + a), self.stack.get(stack_base + b)) else {
+            return Err(VMError::StackUnderflow);
+        };
+        let less = binary_int32(OpCode::LessThanInt32, a.clone(), b.clone())?;
+        if !less.is_truthy() {
+            self.current_frame_mut()?.ip += offset;
+        }
+        Ok(())
     }
 
     fn handle_add_int64_with_constant(&mut self) -> Result<(), VMError> {
@@ -531,7 +1639,9 @@ impl IrisVM {
     }
 
     fn handle_multiply_int32_with_constant(&mut self) -> Result<(), VMError> {
-        todo!()
+        let constant = self.read_byte()? as i8;
+        self.stack.push(Value::I8(constant));
+        self.handle_multiply_int32()
     }
 
     fn handle_multiply_int64_with_constant(&mut self) -> Result<(), VMError> {
@@ -587,7 +1697,7 @@ impl IrisVM {
     }
 
     fn handle_equal_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("EqualInt64", Ordering::is_eq)
     }
 
     fn handle_equal_float32(&mut self) -> Result<(), VMError> {
@@ -599,7 +1709,7 @@ impl IrisVM {
     }
 
     fn handle_not_equal_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("NotEqualInt64", Ordering::is_ne)
     }
 
     fn handle_not_equal_float32(&mut self) -> Result<(), VMError> {
@@ -611,7 +1721,7 @@ impl IrisVM {
     }
 
     fn handle_greater_than_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("GreaterThanInt64", Ordering::is_gt)
     }
 
     fn handle_greater_than_float32(&mut self) -> Result<(), VMError> {
@@ -623,7 +1733,7 @@ impl IrisVM {
     }
 
     fn handle_less_than_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("LessThanInt64", Ordering::is_lt)
     }
 
     fn handle_less_than_float32(&mut self) -> Result<(), VMError> {
@@ -635,7 +1745,7 @@ impl IrisVM {
     }
 
     fn handle_greater_or_equal_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("GreaterOrEqualInt64", Ordering::is_ge)
     }
 
     fn handle_greater_or_equal_float32(&mut self) -> Result<(), VMError> {
@@ -647,7 +1757,7 @@ impl IrisVM {
     }
 
     fn handle_less_or_equal_int64(&mut self) -> Result<(), VMError> {
-        todo!()
+        self.int64_comparison("LessOrEqualInt64", Ordering::is_le)
     }
 
     fn handle_less_or_equal_float32(&mut self) -> Result<(), VMError> {
@@ -827,55 +1937,160 @@ impl IrisVM {
     }
 
     fn handle_atomic_add_int32(&mut self) -> Result<(), VMError> {
-        todo!()
+        let delta = self.pop_atomic_operand()?;
+        self.update_atomic_slot(|old| old.wrapping_add(delta))
     }
 
     fn handle_atomic_subtract_int32(&mut self) -> Result<(), VMError> {
-        todo!()
+        let delta = self.pop_atomic_operand()?;
+        self.update_atomic_slot(|old| old.wrapping_sub(delta))
     }
 
+    /// Stores the new value only if the slot holds the expected one; the old value pushed
+    /// tells which happened.
     fn handle_atomic_compare_and_swap_int32(&mut self) -> Result<(), VMError> {
-        todo!()
+        let new = self.pop_atomic_operand()?;
+        let expected = self.pop_atomic_operand()?;
+        self.update_atomic_slot(|old| if old == expected { new } else { old })
+    }
+
+    fn pop_atomic_operand(&mut self) -> Result<i32, VMError> {
+        match Element::from_value(&self.pop_stack()?) {
+            Some(Element::Int(value)) => i32::try_from(value).map_err(|_| VMError::IntegerOverflow),
+            _ => Err(VMError::TypeMismatch("Atomic operands must be integers".to_string())),
+        }
+    }
+
+    /// Pops an `Int32Array` and index, applies `update` to that slot and pushes its old value.
+    fn update_atomic_slot(&mut self, update: impl FnOnce(i32) -> i32) -> Result<(), VMError> {
+        let index = self.pop_typed_array_index()?;
+        let array = match self.pop_stack()? {
+            Value::Int32Array(array) => array,
+            _ => return Err(VMError::TypeMismatch("Atomic opcodes expect an Int32Array".to_string())),
+        };
+        let mut elements = array.borrow_mut();
+        let slot = elements.get_mut(index).ok_or(VMError::IndexOutOfBounds)?;
+        let old = *slot;
+        *slot = update(old);
+        drop(elements);
+        self.stack.push(Value::I32(old));
+        Ok(())
     }
 
     fn handle_enter_monitor(&mut self) -> Result<(), VMError> {
-        todo!()
+        let object = self.pop_stack()?;
+        let Value::Object(instance) = &object else { return Err(VMError::NonObjectValue) };
+        let fiber = self.current_fiber_id();
+        match self.monitors.enter(Gc::addr(instance), &object, fiber, self.detect_deadlocks) {
+            Entry::Acquired => Ok(()),
+            Entry::Deadlock(cycle) => Err(VMError::Deadlock { fibers: cycle.len() }),
+            Entry::Contended if self.can_switch_fiber() => {
+                // Try again once the other fibers have had a turn.
+                self.stack.push(object);
+                self.current_frame_mut()?.ip -= OpCode::EnterMonitor.opcode_len();
+                self.switch_fiber()
+            }
+            Entry::Contended => {
+                Err(VMError::InvalidOperand("EnterMonitor can't wait inside a host call or coroutine".to_string()))
+            }
+        }
     }
 
     fn handle_exit_monitor(&mut self) -> Result<(), VMError> {
-        todo!()
+        let Value::Object(instance) = self.pop_stack()? else { return Err(VMError::NonObjectValue) };
+        if !self.monitors.exit(Gc::addr(&instance), self.current_fiber_id()) {
+            return Err(VMError::InvalidOperand("ExitMonitor on a monitor the fiber doesn't hold".to_string()));
+        }
+        Ok(())
     }
 
+    /// The same as `YieldFiber`: fibers are the VM's threads.
     fn handle_yield_current_thread(&mut self) -> Result<(), VMError> {
-        todo!()
+        if self.can_switch_fiber() {
+            self.switch_fiber()?;
+        }
+        Ok(())
     }
 
     fn handle_call_with_inline_cache(&mut self) -> Result<(), VMError> {
-        todo!()
+        let (name_index, name) = self.read_member_name()?;
+        let arg_count = self.read_byte()? as usize;
+        let class = self.receiver_class(arg_count)?;
+        let site = self.cache_site()?;
+        match self.inline_caches.lookup_method(site, &name, &class, |class| inline_cache::resolve_method(class, &name)) {
+            Some(Resolved::Method(method)) => self.invoke_resolved_method(method, arg_count),
+            _ => Err(VMError::MethodNotFound(name_index)),
+        }
     }
 
     fn handle_call_with_inline_cache_inline(&mut self) -> Result<(), VMError> {
         todo!()
     }
 
-    fn handle_get_property_with_inline_cache(&mut self) -> Result<(), VMError> {
+    fn handle_get_property_with_inline_cache_inline(&mut self) -> Result<(), VMError> {
         todo!()
     }
 
-    fn handle_get_property_with_inline_cache_inline(&mut self) -> Result<(), VMError> {
+    fn handle_load_method_inline_cache(&mut self) -> Result<(), VMError> {
         todo!()
     }
 
-    fn handle_set_property_with_inline_cache(&mut self) -> Result<(), VMError> {
-        todo!()
+    fn handle_megamorphic_method_call(&mut self) -> Result<(), VMError> {
+        let (name_index, name) = self.read_member_name()?;
+        let arg_count = self.read_byte()? as usize;
+        let class = self.receiver_class(arg_count)?;
+        match self.inline_caches.lookup_megamorphic(&name, &class, |class| inline_cache::resolve_method(class, &name)) {
+            Some(Resolved::Method(method)) => self.invoke_resolved_method(method, arg_count),
+            _ => Err(VMError::MethodNotFound(name_index)),
+        }
     }
 
-    fn handle_load_method_inline_cache(&mut self) -> Result<(), VMError> {
-        todo!()
+    /// Reads the u16 constant index naming a method, and the name.
+    fn read_member_name(&mut self) -> Result<(usize, Rc<str>), VMError> {
+        let index = self.read_u16()? as usize;
+        Ok((index, self.member_name(index)?))
     }
 
-    fn handle_megamorphic_method_call(&mut self) -> Result<(), VMError> {
-        todo!()
+    /// The property or method name in constant `index`.
+    fn member_name(&self, index: usize) -> Result<Rc<str>, VMError> {
+        match self.current_frame()?.function.constants().get(index) {
+            Some(Value::Str(name)) => Ok(name.clone()),
+            Some(_) => Err(VMError::TypeMismatch("Member name is not a string".to_string())),
+            None => Err(VMError::InvalidOperand("Member name constant not found".to_string())),
+        }
+    }
+
+    /// The class of the object `depth` slots below the top of the stack.
+    fn receiver_class(&self, depth: usize) -> Result<Rc<Class>, VMError> {
+        match self.peek_stack(depth)? {
+            Value::Object(instance) => Ok(instance.borrow().class.clone()),
+            _ => Err(VMError::NonObjectValue),
+        }
+    }
+
+    /// The instruction just read, as an inline cache key.
+    fn cache_site(&self) -> Result<inline_cache::Site, VMError> {
+        let frame = self.current_frame()?;
+        Ok((Rc::as_ptr(&frame.function), frame.ip))
+    }
+
+    /// The state of the inline cache of the instruction at `offset` in `function`.
+    pub fn inline_cache_state(&self, function: &Rc<Function>, offset: usize) -> CacheState {
+        let end = function.bytecode.as_deref().and_then(|code| crate::vm::opcode::instruction_len(code, offset));
+        end.map_or(CacheState::Uninitialized, |len| self.inline_caches.state((Rc::as_ptr(function), offset + len)))
+    }
+
+    /// Drops every inline cache entry; needed only to reclaim their memory.
+    pub fn invalidate_inline_caches(&mut self) {
+        self.inline_caches.clear();
+    }
+
+    /// Drops inline cache entries for the class in `slot` before the global is overwritten.
+    fn forget_global_class(&mut self, slot: usize) {
+        if let Some(Value::Class(class)) = self.globals.get(slot) {
+            let class = class.clone();
+            self.inline_caches.invalidate(&class);
+        }
     }
 
         #[allow(dead_code)]
@@ -885,9 +2100,8 @@ impl IrisVM {
 
         // Handle string concatenation separately
         if let (Value::Str(s1), Value::Str(s2)) = (&a, &b) {
-            let mut new_s = s1.clone();
-            new_s.push_str(s2);
-            self.stack.push(Value::Str(new_s));
+            let joined = [&**s1, &**s2].concat();
+            self.stack.push(Value::Str(intern(&joined)));
             return Ok(());
         }
 
@@ -907,41 +2121,14 @@ impl IrisVM {
         Ok(())
     }
 
-    fn handle_subtract_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for subtraction.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for subtraction.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::I64(val_a.wrapping_sub(val_b)),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::F64(val_a - val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::F64(val_a - val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::F64(val_a as f64 - val_b),
-        };
-
-        self.stack.push(result);
-        Ok(())
+    fn handle_multiply_int32(&mut self) -> Result<(), VMError> {
+        self.handle_binary_int32(OpCode::MultiplyInt32)
     }
 
-    fn handle_multiply_int32(&mut self) -> Result<(), VMError> {
+    fn handle_binary_int32(&mut self, op: OpCode) -> Result<(), VMError> {
         let b = self.pop_stack()?;
         let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for multiplication.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for multiplication.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::I64(val_a.wrapping_mul(val_b)),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::F64(val_a * val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::F64(val_a * val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::F64(val_a as f64 * val_b),
-        };
-
-        self.stack.push(result);
+        self.stack.push(binary_int32(op, a, b)?);
         Ok(())
     }
 
@@ -1008,97 +2195,6 @@ impl IrisVM {
         Ok(())
     }
 
-    fn handle_equal_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        self.stack.push(Value::Bool(a == b));
-        Ok(())
-    }
-
-    fn handle_not_equal_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        self.stack.push(Value::Bool(a != b));
-        Ok(())
-    }
-
-    fn handle_greater_than_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for comparison.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for comparison.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::Bool(val_a > val_b),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::Bool(val_a > val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::Bool(val_a > val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::Bool((val_a as f64) > val_b),
-        };
-
-        self.stack.push(result);
-        Ok(())
-    }
-
-        #[allow(dead_code)]
-    fn handle_less_than_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for comparison.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for comparison.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::Bool(val_a < val_b),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::Bool(val_a < val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::Bool(val_a < val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::Bool((val_a as f64) < val_b),
-        };
-
-        self.stack.push(result);
-        Ok(())
-    }
-
-    fn handle_greater_or_equal_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for comparison.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for comparison.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::Bool(val_a >= val_b),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::Bool(val_a >= val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::Bool(val_a >= val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::Bool(val_a as f64 >= val_b),
-        };
-
-        self.stack.push(result);
-        Ok(())
-    }
-
-    fn handle_less_or_equal_int32(&mut self) -> Result<(), VMError> {
-        let b = self.pop_stack()?;
-        let a = self.pop_stack()?;
-        let num_a = value_to_numeric(&a)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'a' must be numeric for comparison.".to_string()))?;
-        let num_b = value_to_numeric(&b)
-            .ok_or_else(|| VMError::TypeMismatch("Operand 'b' must be numeric for comparison.".to_string()))?;
-
-        let result = match (num_a, num_b) {
-            (Numeric::Int(val_a), Numeric::Int(val_b)) => Value::Bool(val_a <= val_b),
-            (Numeric::Float(val_a), Numeric::Float(val_b)) => Value::Bool(val_a <= val_b),
-            (Numeric::Float(val_a), Numeric::Int(val_b)) => Value::Bool(val_a <= val_b as f64),
-            (Numeric::Int(val_a), Numeric::Float(val_b)) => Value::Bool(val_a as f64 <= val_b),
-        };
-
-        self.stack.push(result);
-        Ok(())
-    }
-
     fn handle_logical_and_operation(&mut self) -> Result<(), VMError> {
         let b = self.pop_stack()?;
         let a = self.pop_stack()?;
@@ -1211,50 +2307,68 @@ impl IrisVM {
         let offset = self.read_u16()? as usize;
         let frame = self.current_frame_mut()?;
         frame.ip -= offset;
-        Ok(())
+        self.back_edge()
     }
 
         fn handle_call_function(&mut self) -> Result<(), VMError> {
         let arg_count = self.read_byte()? as usize;
+        self.call_value(arg_count)
+    }
+
+    /// Calls the callee below the top `arg_count` values.
+    fn call_value(&mut self, arg_count: usize) -> Result<(), VMError> {
         let callee_pos = self.stack.len() - 1 - arg_count;
         let callee = self.stack[callee_pos].clone();
 
         match callee {
             Value::Function(func) => {
                 match func.kind {
-                    crate::vm::function::FunctionKind::Native => {
-                        // The native function now takes *mut IrisVM and returns ().
-                        // We need to pass the vm_ptr directly.
-                        (func.native.unwrap())(self as *mut IrisVM);
-                    }
+                    crate::vm::function::FunctionKind::Native => self.call_native(&func, arg_count, false)?,
                     crate::vm::function::FunctionKind::Bytecode => {
                         self.stack.remove(callee_pos);
                         self.push_frame(func, arg_count)?;
                     }
                 }
             }
+            Value::Closure(closure) => {
+                self.stack.remove(callee_pos);
+                self.push_frame(closure.function.clone(), arg_count)?;
+                self.current_frame_mut()?.closure = Some(closure);
+            }
+            Value::BoundMethod(bound) => {
+                self.stack[callee_pos] = Value::Function(bound.method.clone());
+                self.stack.insert(callee_pos + 1, bound.receiver.clone());
+                return self.call_value(arg_count + 1);
+            }
             _ => return Err(VMError::NonCallableValue),
         }
         Ok(())
     }
 
+    /// Pops `arg_count` arguments and the callee or receiver below them, calls the native
+    /// and pushes its result. Methods get their receiver as the first argument.
+    fn call_native(&mut self, function: &Function, arg_count: usize, with_receiver: bool) -> Result<(), VMError> {
+        let native = function.native.clone()
+            .ok_or_else(|| VMError::InvalidOperand(format!("Native function '{}' has no body", function.name)))?;
+        let start = self.stack.len().checked_sub(arg_count + 1).ok_or(VMError::StackUnderflow)?;
+        let mut args = self.native_args.pop().unwrap_or_default();
+        args.extend(self.stack.drain(start..));
+        let result = native.call(self, if with_receiver { &args } else { &args[1..] });
+        args.clear();
+        self.native_args.push(args);
+        self.stack.push(result?);
+        Ok(())
+    }
+
     fn handle_invoke_method(&mut self, method_index: usize, arg_count: usize) -> Result<(), VMError> {
         let _instance_index = self.stack.len() - 1 - arg_count;
         let instance_value = self.peek_stack(arg_count)?.clone();
 
         match instance_value {
             Value::Object(instance_rc) => {
-                if let Some(method) = instance_rc.get_method(method_index) {
-                    match method.kind {
-                        crate::vm::function::FunctionKind::Native => {
-                            // The native function now takes *mut IrisVM and returns ().
-                            // We need to pass the vm_ptr directly.
-                            (method.native.unwrap())(self as *mut IrisVM);
-                        }
-                                                crate::vm::function::FunctionKind::Bytecode => {
-                            self.push_frame(method, arg_count)?;
-                        }
-                    }
+                let method = instance_rc.borrow().get_method(method_index);
+                if let Some(method) = method {
+                    self.invoke_resolved_method(method, arg_count)?;
                 } else {
                     return Err(VMError::MethodNotFound(method_index));
                 }
@@ -1264,6 +2378,14 @@ impl IrisVM {
         Ok(())
     }
 
+    /// Calls `method` on the receiver below the top `arg_count` values.
+    fn invoke_resolved_method(&mut self, method: Rc<Function>, arg_count: usize) -> Result<(), VMError> {
+        match method.kind {
+            crate::vm::function::FunctionKind::Native => self.call_native(&method, arg_count, true),
+            crate::vm::function::FunctionKind::Bytecode => self.push_frame(method, arg_count),
+        }
+    }
+
     fn handle_get_local_variable(&mut self, slot: usize) -> Result<(), VMError> {
         let stack_base = self.current_frame()?.stack_base;
         let value = self.stack[stack_base + slot].clone();
@@ -1287,8 +2409,17 @@ impl IrisVM {
         Ok(())
     }
 
+    fn check_global_writable(&self, slot: usize) -> Result<(), VMError> {
+        if let Some(entry) = self.config.entries().iter().find(|entry| entry.slot == slot) {
+            return Err(VMError::ReadOnlyGlobal(entry.name.clone()));
+        }
+        Ok(())
+    }
+
     fn handle_define_global_variable(&mut self, slot: usize) -> Result<(), VMError> {
+        self.check_global_writable(slot)?;
         let value = self.pop_stack()?;
+        self.forget_global_class(slot);
         if slot >= self.globals.len() {
             self.globals.resize(slot + 1, Value::Null);
         }
@@ -1297,37 +2428,54 @@ impl IrisVM {
     }
 
     fn handle_set_global_variable(&mut self, slot: usize) -> Result<(), VMError> {
+        self.check_global_writable(slot)?;
         let value = self.peek_stack(0)?.clone();
         if slot >= self.globals.len() {
             return Err(VMError::UndefinedVariable(format!("Global variable at slot {} not found for setting", slot)));
         }
+        self.forget_global_class(slot);
         self.globals[slot] = value;
         Ok(())
     }
 
-    fn handle_get_object_property(&mut self, index: usize) -> Result<(), VMError> {
-        let instance = self.pop_stack()?;
-        match instance {
-            Value::Object(obj) => {
-                if let Some(value) = obj.get_field(index) {
-                    self.stack.push(value.clone());
-                } else {
-                    return Err(VMError::UndefinedProperty(index));
-                }
+    /// Reads the property named by constant `name_index`, resolved through the instance's
+    /// shape and cached per instruction, see `vm::inline_cache`.
+    fn handle_get_object_property(&mut self, name_index: usize) -> Result<(), VMError> {
+        let name = self.member_name(name_index)?;
+        let Value::Object(instance) = self.pop_stack()? else { return Err(VMError::NonObjectValue) };
+        let shape = instance.borrow().shape.clone();
+        let site = self.cache_site()?;
+        match self.inline_caches.lookup_property(site, &name, &shape, |shape| inline_cache::resolve_field(shape, &name)) {
+            Some(Resolved::Field(offset)) => {
+                let value = instance.borrow().fields.get(offset).cloned().unwrap_or(Value::Null);
+                self.stack.push(value);
+                Ok(())
             }
-            _ => return Err(VMError::NonObjectValue),
+            _ => Err(VMError::UndefinedProperty(name.to_string())),
         }
-        Ok(())
     }
 
-    fn handle_set_object_property(&mut self, index: usize) -> Result<(), VMError> {
+    /// Stores the property named by constant `name_index`, adding it to the instance's shape
+    /// if it's new and the class is extensible.
+    fn handle_set_object_property(&mut self, name_index: usize) -> Result<(), VMError> {
+        let name = self.member_name(name_index)?;
         let value = self.pop_stack()?;
-        let instance_val = self.pop_stack()?;
-        match instance_val {
-            Value::Object(mut obj) => {
-                Rc::get_mut(&mut obj).ok_or(VMError::InvalidOperand("Could not get mutable reference to object".to_string()))?.set_field(index, value);
+        let Value::Object(instance) = self.pop_stack()? else { return Err(VMError::NonObjectValue) };
+        let (shape, extensible) = {
+            let instance = instance.borrow();
+            (instance.shape.clone(), instance.class.extensible)
+        };
+        let site = self.cache_site()?;
+        let resolved = self.inline_caches.lookup_property(site, &name, &shape, |shape| inline_cache::resolve_store(shape, &name, extensible));
+        match resolved {
+            Some(Resolved::Field(offset)) => instance.borrow_mut().set_at(offset, value),
+            Some(Resolved::Transition(shape, offset)) => {
+                self.charge_heap(std::mem::size_of::<Value>())?;
+                let mut instance = instance.borrow_mut();
+                instance.shape = shape;
+                instance.set_at(offset, value);
             }
-            _ => return Err(VMError::NonObjectValue),
+            _ => return Err(VMError::UndefinedProperty(name.to_string())),
         }
         Ok(())
     }
@@ -1336,8 +2484,10 @@ impl IrisVM {
         let class_val = self.pop_stack()?;
         match class_val {
             Value::Class(class_rc) => {
+                self.charge_heap(std::mem::size_of::<Value>())?;
                 let instance = Instance::new(class_rc.clone());
-                self.stack.push(Value::Object(Rc::new(instance)));
+                let instance = self.allocate(Value::Object(Gc::new(instance)));
+                self.stack.push(instance);
             }
             _ => return Err(VMError::NonClassValue),
         }
@@ -1362,11 +2512,11 @@ impl IrisVM {
     }
 
     fn handle_define_class(&mut self, name_index: usize) -> Result<(), VMError> {
-        let name = match self.current_frame()?.function.constants().get(name_index).ok_or(VMError::InvalidOperand("Class name constant not found".to_string()))? {
+        let name = match self.current_frame()?.function.constants().get(name_index).ok_or_else(|| VMError::InvalidOperand("Class name constant not found".to_string()))? {
             Value::Str(s) => s.clone(),
             _ => return Err(VMError::TypeMismatch("Class name is not a string".to_string())),
         };
-        let class = Rc::new(Class::new(name, 0, None));
+        let class = Rc::new(Class::new(name.to_string(), 0, None));
         self.stack.push(Value::Class(class));
         Ok(())
     }
@@ -1375,8 +2525,10 @@ impl IrisVM {
         if self.stack.len() < num_elements {
             return Err(VMError::StackUnderflow);
         }
+        self.charge_heap(memory::array_size(num_elements))?;
         let elements: Vec<Value> = self.stack.drain(self.stack.len() - num_elements..).collect();
-        self.stack.push(Value::Array(Rc::new(RefCell::new(elements))));
+        let array = self.allocate(Value::Array(Gc::new(elements)));
+        self.stack.push(array);
         Ok(())
     }
 
@@ -1405,8 +2557,17 @@ impl IrisVM {
 
         match (array_val, index_val) {
             (Value::Array(arr), Value::I64(idx)) => {
-                let mut array = arr.borrow_mut();
+                if idx < 0 {
+                    return Err(VMError::IndexOutOfBounds);
+                }
                 let u_idx = idx as usize;
+                let len = arr.borrow().len();
+                if u_idx >= len {
+                    let bytes = (u_idx - len).checked_add(1).and_then(|grown| grown.checked_mul(std::mem::size_of::<Value>()));
+                    let limit = self.limits.max_heap_bytes.unwrap_or(usize::MAX);
+                    self.charge_heap(bytes.ok_or(VMError::OutOfMemory { limit })?)?;
+                }
+                let mut array = arr.borrow_mut();
                 if u_idx >= array.len() {
                     array.resize(u_idx + 1, Value::Null);
                 }
@@ -1417,26 +2578,77 @@ impl IrisVM {
         Ok(())
     }
 
+    fn handle_new_typed_array(&mut self) -> Result<(), VMError> {
+        let element = self.read_byte()?;
+        let element = ElementType::from_byte(element).ok_or_else(|| VMError::InvalidOperand(format!("Unknown typed array element type {}", element)))?;
+        let len = self.pop_typed_array_index()?;
+        self.charge_heap(memory::typed_array_size(element, len))?;
+        self.stack.push(element.new_array(len));
+        Ok(())
+    }
+
+    /// Pops a non-negative integer index or length.
+    fn pop_typed_array_index(&mut self) -> Result<usize, VMError> {
+        match Element::from_value(&self.pop_stack()?) {
+            Some(Element::Int(index)) if index >= 0 => Ok(index as usize),
+            Some(Element::Int(_)) => Err(VMError::IndexOutOfBounds),
+            _ => Err(VMError::TypeMismatch("Typed array index must be an integer".to_string())),
+        }
+    }
+
+    fn pop_typed_array_element(&mut self) -> Result<Element, VMError> {
+        Element::from_value(&self.pop_stack()?).ok_or_else(|| VMError::TypeMismatch("Typed array elements must be numeric".to_string()))
+    }
+
+    fn pop_set(&mut self, opcode: &str) -> Result<Gc<ValueSet>, VMError> {
+        match self.pop_stack()? {
+            Value::Set(set) => Ok(set),
+            _ => Err(VMError::TypeMismatch(format!("{} expects a set", opcode))),
+        }
+    }
+
+    fn pop_range_bound(&mut self) -> Result<i64, VMError> {
+        match value_to_integer(&self.pop_stack()?) {
+            Some(Integer::Small(value)) => Ok(value),
+            Some(Integer::Big(_)) => Err(VMError::IntegerOverflow),
+            None => Err(VMError::TypeMismatch("Range bounds must be integers".to_string())),
+        }
+    }
+
+    fn push_new_set(&mut self, set: ValueSet) -> Result<(), VMError> {
+        self.charge_heap(memory::set_size(set.len()))?;
+        let set = self.allocate(Value::Set(Gc::new(set)));
+        self.stack.push(set);
+        Ok(())
+    }
+
     fn handle_create_new_map(&mut self, num_entries: usize) -> Result<(), VMError> {
         if self.stack.len() < num_entries * 2 {
             return Err(VMError::StackUnderflow);
         }
+        let keys = self.stack[self.stack.len() - num_entries * 2..].iter().step_by(2);
+        let bytes: usize = keys.map(|key| match key {
+            Value::Str(key) => memory::map_entry_size(key),
+            _ => 0,
+        }).sum();
+        self.charge_heap(std::mem::size_of::<Value>() + bytes)?;
         let mut map = HashMap::with_capacity(num_entries);
         for _ in 0..num_entries {
             let value = self.pop_stack()?;
             let key_val = self.pop_stack()?;
             if let Value::Str(key) = key_val {
-                map.insert(key, value);
+                map.insert(key.to_string(), value);
             } else {
                 return Err(VMError::NonStringKey);
             }
         }
-        self.stack.push(Value::Map(Rc::new(RefCell::new(map))));
+        let map = self.allocate(Value::Map(Gc::new(map)));
+        self.stack.push(map);
         Ok(())
     }
 
     fn handle_get_object_field(&mut self, name_index: usize) -> Result<(), VMError> {
-        let name = match self.current_frame()?.function.constants().get(name_index).ok_or(VMError::InvalidOperand("Field name constant not found".to_string()))? {
+        let name = match self.current_frame()?.function.constants().get(name_index).ok_or_else(|| VMError::InvalidOperand("Field name constant not found".to_string()))? {
             Value::Str(s) => s.clone(),
             _ => return Err(VMError::TypeMismatch("Field name is not a string".to_string())),
         };
@@ -1444,7 +2656,7 @@ impl IrisVM {
         match map_val {
             Value::Map(map_rc) => {
                 let map = map_rc.borrow();
-                let value = map.get(&name).cloned().unwrap_or(Value::Null);
+                let value = map.get(&*name).cloned().unwrap_or(Value::Null);
                 self.stack.push(value);
             }
             _ => return Err(VMError::TypeMismatch("GetField can only operate on maps.".to_string())),
@@ -1453,7 +2665,7 @@ impl IrisVM {
     }
 
     fn handle_set_object_field(&mut self, name_index: usize) -> Result<(), VMError> {
-        let name = match self.current_frame()?.function.constants().get(name_index).ok_or(VMError::InvalidOperand("Field name constant not found".to_string()))? {
+        let name = match self.current_frame()?.function.constants().get(name_index).ok_or_else(|| VMError::InvalidOperand("Field name constant not found".to_string()))? {
             Value::Str(s) => s.clone(),
             _ => return Err(VMError::TypeMismatch("Field name is not a string".to_string())),
         };
@@ -1462,7 +2674,10 @@ impl IrisVM {
 
         match map_val {
             Value::Map(map_rc) => {
-                map_rc.borrow_mut().insert(name, value);
+                if !map_rc.borrow().contains_key(&*name) {
+                    self.charge_heap(memory::map_entry_size(&name))?;
+                }
+                map_rc.borrow_mut().insert(name.to_string(), value);
             }
             _ => return Err(VMError::TypeMismatch("SetField can only operate on maps.".to_string())),
         }
@@ -1471,36 +2686,691 @@ impl IrisVM {
 
     fn handle_throw_exception(&mut self) -> Result<(), VMError> {
         let exception = self.pop_stack()?;
-        if let Some(try_frame) = self.try_frames.pop() {
-            self.current_frame_mut()?.ip = try_frame.ip;
-            self.stack.truncate(try_frame.stack_size);
-            self.stack.push(exception);
-        } else {
-            return Err(VMError::UnhandledException(exception));
+        if let Value::Object(object) = &exception {
+            let mut object = object.borrow_mut();
+            if self.exception_classes.is_exception_class(&object.class) && object.get_property("stack_trace").is_none_or(|trace| trace == Value::Null) {
+                object.set_property("stack_trace", Value::Str(intern(&self.call_stack().to_string())));
+            }
         }
-        Ok(())
+        self.unwind(exception)
     }
 
-    fn handle_begin_try_block(&mut self) -> Result<(), VMError> {
-        let offset = self.read_byte()? as usize;
-        self.try_frames.push(TryFrame {
+    /// The built-in exception class called `name`, e.g. `TypeError`.
+    pub fn exception_class(&self, name: &str) -> Option<Rc<Class>> {
+        self.exception_classes.get(name)
+    }
+
+    pub fn exception_classes(&self) -> &ExceptionClasses {
+        &self.exception_classes
+    }
+
+    /// Whether a try block of the running `execute` can take an exception.
+    fn has_handler(&self) -> bool {
+        self.try_frames.last().is_some_and(|try_frame| try_frame.depth > self.base_depth)
+    }
+
+    /// Pops call frames down to the innermost try block and continues at its handler with
+    /// `exception` pushed.
+    fn unwind(&mut self, exception: Value) -> Result<(), VMError> {
+        if !self.has_handler() {
+            return Err(VMError::UnhandledException(exception));
+        }
+        let try_frame = self.try_frames.pop().ok_or(VMError::NoTryFrame)?;
+        while self.frames.len() > try_frame.depth {
+            self.frames.pop();
+            self.finish_coroutine();
+        }
+        self.current_frame_mut()?.ip = try_frame.ip;
+        self.close_upvalues(try_frame.stack_size);
+        self.stack.truncate(try_frame.stack_size);
+        self.stack.push(exception);
+        Ok(())
+    }
+
+    /// Drops the try blocks of frames that have returned.
+    fn end_try_blocks_above(&mut self, depth: usize) {
+        while self.try_frames.last().is_some_and(|try_frame| try_frame.depth > depth) {
+            self.try_frames.pop();
+        }
+    }
+
+    /// Attaches a backtrace to an error leaving `execute`. If the failing instruction
+    /// changed the frame stack, the top frame's current instruction is reported instead.
+    fn locate(&self, error: VMError, start: usize, depth: usize) -> VMError {
+        if matches!(error, VMError::At { .. }) || self.frames.is_empty() {
+            return error;
+        }
+        let frames = self.frames.iter().enumerate().rev().map(|(index, frame)| {
+            let offset = if index + 1 == depth && self.frames.len() == depth { start } else { frame.ip.saturating_sub(1) };
+            SourceLocation {
+                function: frame.function.name.clone(),
+                offset,
+                span: frame.function.lines.span_at(offset),
+            }
+        });
+        VMError::At { error: Box::new(error), backtrace: Backtrace::new(frames.collect()) }
+    }
+
+    /// Hands a runtime error to the guest's innermost try block, as an instance of the
+    /// matching built-in exception class, when the catch policy allows it. Otherwise returns
+    /// it so `run()` aborts.
+    fn raise_runtime_error(&mut self, error: VMError) -> Result<(), VMError> {
+        if !self.has_handler() || !self.catch_policy.is_catchable(error.kind()) {
+            return Err(error);
+        }
+        let class = self.exception_classes.for_kind(error.kind());
+        let mut exception = exception::new_exception(class, &error.root().to_string());
+        if let Some(backtrace) = error.backtrace() {
+            // Raised in a host call; its frames are gone by now.
+            exception.set_property("stack_trace", Value::Str(intern(&backtrace.to_string())));
+        }
+        let exception = Gc::new(exception);
+        let exception = self.allocate(Value::Object(exception));
+        self.stack.push(exception);
+        self.handle_throw_exception()
+    }
+
+    fn handle_begin_try_block(&mut self) -> Result<(), VMError> {
+        let offset = self.read_byte()? as usize;
+        self.try_frames.push(TryFrame {
             ip: self.current_frame()?.ip + offset,
             stack_size: self.stack.len(),
+            depth: self.frames.len(),
+        });
+        Ok(())
+    }
+
+    /// The open upvalue for absolute stack `slot`, creating it if no closure captured the
+    /// slot yet so that every closure shares it.
+    fn capture_upvalue(&mut self, slot: usize) -> UpvalueRef {
+        let existing = self.open_upvalues.iter().find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Closes every open upvalue at or above stack slot `from`, before those slots are dropped.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let mut upvalue = upvalue.borrow_mut();
+            match *upvalue {
+                Upvalue::Open(slot) if slot >= from => {
+                    *upvalue = Upvalue::Closed(stack.get(slot).cloned().unwrap_or(Value::Null));
+                    false
+                }
+                _ => true,
+            }
         });
+    }
+
+    /// Closes the running fiber's open upvalues so it can be parked, returning them with their
+    /// slots for `reopen_upvalues`.
+    fn park_upvalues(&mut self) -> Vec<(UpvalueRef, usize)> {
+        let stack = &self.stack;
+        self.open_upvalues.drain(..).filter_map(|upvalue| {
+            let Upvalue::Open(slot) = *upvalue.borrow() else { return None };
+            *upvalue.borrow_mut() = Upvalue::Closed(stack.get(slot).cloned().unwrap_or(Value::Null));
+            Some((upvalue, slot))
+        }).collect()
+    }
+
+    /// Copies what parked upvalues hold back onto the resumed fiber's stack and opens them
+    /// there again.
+    fn reopen_upvalues(&mut self, upvalues: Vec<(UpvalueRef, usize)>) {
+        for (upvalue, slot) in upvalues {
+            if let Upvalue::Closed(value) = std::mem::replace(&mut *upvalue.borrow_mut(), Upvalue::Open(slot)) {
+                if let Some(local) = self.stack.get_mut(slot) {
+                    *local = value;
+                }
+            }
+            self.open_upvalues.push(upvalue);
+        }
+    }
+
+    fn current_upvalue(&self, index: usize) -> Result<UpvalueRef, VMError> {
+        self.current_frame()?.closure.as_ref()
+            .and_then(|closure| closure.upvalues.get(index).cloned())
+            .ok_or_else(|| VMError::InvalidOperand(format!("Upvalue {} is not captured by the running function", index)))
+    }
+
+    fn handle_capture_upvalue(&mut self) -> Result<(), VMError> {
+        let local = self.read_byte()? != 0;
+        let index = self.read_byte()? as usize;
+        let upvalue = if local {
+            let slot = self.current_frame()?.stack_base + index;
+            if slot >= self.stack.len() {
+                return Err(VMError::InvalidOperand(format!("Local {} is out of range", index)));
+            }
+            self.capture_upvalue(slot)
+        } else {
+            self.current_upvalue(index)?
+        };
+        let mut closure = match self.pop_stack()? {
+            Value::Function(function) if function.bytecode.is_some() => Closure::new(function),
+            Value::Closure(closure) => Rc::unwrap_or_clone(closure),
+            _ => return Err(VMError::TypeMismatch("CaptureUpvalue expects a bytecode function or closure".to_string())),
+        };
+        closure.upvalues.push(upvalue);
+        self.stack.push(Value::Closure(Rc::new(closure)));
         Ok(())
     }
 
+    fn handle_get_upvalue(&mut self) -> Result<(), VMError> {
+        let index = self.read_byte()? as usize;
+        let value = match &*self.current_upvalue(index)?.borrow() {
+            Upvalue::Open(slot) => self.stack[*slot].clone(),
+            Upvalue::Closed(value) => value.clone(),
+        };
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn handle_set_upvalue(&mut self) -> Result<(), VMError> {
+        let index = self.read_byte()? as usize;
+        let value = self.peek_stack(0)?.clone();
+        let upvalue = self.current_upvalue(index)?;
+        let mut upvalue = upvalue.borrow_mut();
+        match &mut *upvalue {
+            Upvalue::Open(slot) => self.stack[*slot] = value,
+            Upvalue::Closed(closed) => *closed = value,
+        }
+        Ok(())
+    }
+
+    fn handle_close_upvalue(&mut self) -> Result<(), VMError> {
+        let top = self.stack.len().checked_sub(1).ok_or(VMError::StackUnderflow)?;
+        self.close_upvalues(top);
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn handle_create_coroutine(&mut self) -> Result<(), VMError> {
+        let callee = self.pop_stack()?;
+        let function = match &callee {
+            Value::Function(function) if function.bytecode.is_some() => function.clone(),
+            Value::Closure(closure) => closure.function.clone(),
+            _ => return Err(VMError::TypeMismatch("CreateCoroutine expects a bytecode function or closure".to_string())),
+        };
+        if function.arity > 1 {
+            return Err(VMError::ArityMismatch { expected: 1, found: function.arity });
+        }
+        self.stack.push(Value::Coroutine(Gc::new(Coroutine::new(callee))));
+        Ok(())
+    }
+
+    /// Switches to `coroutine`, starting it or restoring its frames above the current ones.
+    fn resume_coroutine(&mut self, coroutine: Gc<Coroutine>, value: Value) -> Result<(), VMError> {
+        let depth = self.frames.len();
+        let stack_base = self.stack.len();
+        let mut suspended = coroutine.borrow_mut();
+        match suspended.state {
+            CoroutineState::Finished => return Err(VMError::CoroutineFinished),
+            CoroutineState::Running => return Err(VMError::InvalidOperand("Coroutine is already running".to_string())),
+            CoroutineState::Suspended => {}
+        }
+        match suspended.callee.take() {
+            Some(callee) => {
+                let (function, closure) = match callee {
+                    Value::Closure(closure) => (closure.function.clone(), Some(closure)),
+                    Value::Function(function) => (function, None),
+                    _ => return Err(VMError::NonCallableValue),
+                };
+                let arg_count = function.arity;
+                if arg_count == 1 {
+                    self.stack.push(value);
+                }
+                self.push_frame(function, arg_count)?;
+                self.current_frame_mut()?.closure = closure;
+            }
+            None => {
+                self.stack.append(&mut suspended.stack);
+                self.frames.extend(suspended.frames.drain(..).map(|mut frame| {
+                    frame.stack_base += stack_base;
+                    frame
+                }));
+                self.stack.push(value);
+            }
+        }
+        suspended.state = CoroutineState::Running;
+        drop(suspended);
+        self.coroutines.push(ActiveCoroutine { coroutine, depth, stack_base });
+        Ok(())
+    }
+
+    fn handle_resume_coroutine(&mut self) -> Result<(), VMError> {
+        let value = self.pop_stack()?;
+        match self.pop_stack()? {
+            Value::Coroutine(coroutine) => self.resume_coroutine(coroutine, value),
+            _ => Err(VMError::TypeMismatch("ResumeCoroutine expects a coroutine".to_string())),
+        }
+    }
+
+    /// Saves the running coroutine's frames and stack and hands `value` to its resumer.
+    fn handle_yield(&mut self) -> Result<(), VMError> {
+        let value = self.pop_stack()?;
+        let active = self.coroutines.pop().ok_or_else(|| VMError::InvalidOperand("Yield outside a coroutine".to_string()))?;
+        self.close_upvalues(active.stack_base);
+        let mut coroutine = active.coroutine.borrow_mut();
+        coroutine.stack = self.stack.split_off(active.stack_base);
+        coroutine.frames = self.frames.split_off(active.depth).into_iter().map(|mut frame| {
+            frame.stack_base -= active.stack_base;
+            frame
+        }).collect();
+        coroutine.state = CoroutineState::Suspended;
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// Marks the innermost running coroutine finished once its first frame has returned.
+    fn finish_coroutine(&mut self) {
+        if self.coroutines.last().is_some_and(|active| active.depth == self.frames.len()) {
+            if let Some(active) = self.coroutines.pop() {
+                active.coroutine.borrow_mut().state = CoroutineState::Finished;
+            }
+        }
+    }
+
+    fn handle_await(&mut self) -> Result<(), VMError> {
+        let future = match self.pop_stack()? {
+            Value::Future(future) => future,
+            _ => return Err(VMError::TypeMismatch("Await expects a future".to_string())),
+        };
+        let result = future.borrow().result().cloned();
+        match result {
+            Some(Ok(value)) => self.stack.push(value),
+            Some(Err(rejection)) => {
+                self.stack.push(rejection);
+                self.handle_throw_exception()?;
+            }
+            None => {
+                // Stop before the Await so that resuming runs it again.
+                self.stack.push(Value::Future(future.clone()));
+                self.current_frame_mut()?.ip -= OpCode::Await.opcode_len();
+                self.awaiting = Some(future);
+                return Err(VMError::Pending);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_spawn_fiber(&mut self) -> Result<(), VMError> {
+        let callee = self.pop_stack()?;
+        let function = match &callee {
+            Value::Function(function) if function.bytecode.is_some() => function.clone(),
+            Value::Closure(closure) => closure.function.clone(),
+            _ => return Err(VMError::TypeMismatch("SpawnFiber expects a bytecode function or closure".to_string())),
+        };
+        if function.arity != 0 {
+            return Err(VMError::ArityMismatch { expected: 0, found: function.arity });
+        }
+        let fiber = Gc::new(Fiber::new(Some(callee)));
+        self.scheduler.spawn(fiber.clone());
+        self.stack.push(Value::Fiber(fiber));
+        Ok(())
+    }
+
+    fn handle_join_fiber(&mut self) -> Result<(), VMError> {
+        let fiber = match self.pop_stack()? {
+            Value::Fiber(fiber) => fiber,
+            _ => return Err(VMError::TypeMismatch("JoinFiber expects a fiber".to_string())),
+        };
+        if let Some(result) = fiber.borrow().result() {
+            self.stack.push(result.clone());
+            return Ok(());
+        }
+        if self.scheduler.current.as_ref().is_some_and(|current| Gc::ptr_eq(current, &fiber)) {
+            return Err(VMError::InvalidOperand("A fiber cannot join itself".to_string()));
+        }
+        if !self.can_switch_fiber() {
+            return Err(VMError::InvalidOperand("JoinFiber can't wait inside a host call or coroutine".to_string()));
+        }
+        // Run the JoinFiber again once this fiber is scheduled back.
+        self.stack.push(Value::Fiber(fiber));
+        self.current_frame_mut()?.ip -= OpCode::JoinFiber.opcode_len();
+        self.switch_fiber()
+    }
+
+    fn pop_channel(&mut self, opcode: &str) -> Result<Rc<Channel>, VMError> {
+        match self.pop_stack()? {
+            Value::Channel(channel) => Ok(channel),
+            _ => Err(VMError::TypeMismatch(format!("{} expects a channel", opcode))),
+        }
+    }
+
+    fn handle_channel_receive(&mut self) -> Result<(), VMError> {
+        let channel = self.pop_channel("ChannelReceive")?;
+        if let Some(message) = channel.try_receive() {
+            self.stack.push(message);
+            return Ok(());
+        }
+        if self.can_switch_fiber() && !self.scheduler.ready.is_empty() {
+            // Let the other fibers run, then try again.
+            self.stack.push(Value::Channel(channel));
+            self.current_frame_mut()?.ip -= OpCode::ChannelReceive.opcode_len();
+            return self.switch_fiber();
+        }
+        let message = channel.receive()?;
+        self.stack.push(message);
+        Ok(())
+    }
+
+    /// Whether the scheduler may switch fibers now: only once something was spawned, and
+    /// not inside a host call or a coroutine.
+    fn can_switch_fiber(&self) -> bool {
+        self.scheduler.current.is_some() && self.coroutines.is_empty() && self.execute_depth == 1
+    }
+
+    /// Parks the running fiber, unless it has finished, and runs the next ready one. Does
+    /// nothing when no other fiber is ready.
+    fn switch_fiber(&mut self) -> Result<(), VMError> {
+        self.scheduler.ticks = 0;
+        let Some(next) = self.scheduler.ready.pop_front() else { return Ok(()) };
+        let current = self.scheduler.current.take().ok_or(VMError::NoActiveCallFrame)?;
+        let upvalues = self.park_upvalues();
+        let mut parked = current.borrow_mut();
+        parked.upvalues = upvalues;
+        parked.frames = std::mem::take(&mut self.frames);
+        parked.stack = std::mem::take(&mut self.stack);
+        parked.try_frames = std::mem::take(&mut self.try_frames);
+        if parked.state == FiberState::Running {
+            parked.state = FiberState::Ready;
+            self.scheduler.ready.push_back(current.clone());
+        }
+        drop(parked);
+
+        let mut resumed = next.borrow_mut();
+        resumed.state = FiberState::Running;
+        self.frames = std::mem::take(&mut resumed.frames);
+        self.stack = std::mem::take(&mut resumed.stack);
+        self.try_frames = std::mem::take(&mut resumed.try_frames);
+        let upvalues = std::mem::take(&mut resumed.upvalues);
+        self.reserve_stacks();
+        let callee = resumed.callee.take();
+        drop(resumed);
+        self.reopen_upvalues(upvalues);
+        self.scheduler.current = Some(next);
+        let (function, closure) = match callee {
+            Some(Value::Function(function)) => (function, None),
+            Some(Value::Closure(closure)) => (closure.function.clone(), Some(closure)),
+            _ => return Ok(()),
+        };
+        self.push_frame(function, 0)?;
+        self.current_frame_mut()?.closure = closure;
+        Ok(())
+    }
+
+    /// Records the result of a spawned fiber that ran to completion and switches to the next
+    /// one. Returns `false` if it was the main fiber that finished.
+    fn finish_fiber(&mut self) -> Result<bool, VMError> {
+        if self.scheduler.in_main() {
+            return Ok(false);
+        }
+        let Some(current) = self.scheduler.current.clone() else { return Ok(false) };
+        let result = self.stack.pop().unwrap_or(Value::Null);
+        self.stack.clear();
+        self.try_frames.clear();
+        self.monitors.release_all(Gc::addr(&current) as usize);
+        let mut finished = current.borrow_mut();
+        finished.state = FiberState::Finished;
+        finished.result = Some(result);
+        drop(finished);
+        self.switch_fiber()?;
+        Ok(true)
+    }
+
+    /// Identifies the running fiber as a monitor owner.
+    fn current_fiber_id(&self) -> usize {
+        match &self.scheduler.current {
+            Some(current) if !self.scheduler.in_main() => Gc::addr(current) as usize,
+            _ => MAIN_FIBER,
+        }
+    }
+
+    /// Whether `EnterMonitor` checks for deadlocks, see `vm::monitor`. On by default in
+    /// debug builds.
+    pub fn set_deadlock_detection(&mut self, enabled: bool) {
+        self.detect_deadlocks = enabled;
+    }
+
+    /// Lets guest code do what `capability` covers, see `vm::capability`.
+    pub fn grant(&mut self, capability: Capability) {
+        self.capabilities.insert(capability);
+    }
+
+    pub fn revoke(&mut self, capability: Capability) {
+        self.capabilities.remove(&capability);
+    }
+
+    /// Whether `capability` has been granted. Never for the ones that aren't deterministic
+    /// once the VM is, see `make_deterministic`.
+    pub fn has_capability(&self, capability: Capability) -> bool {
+        self.capabilities.contains(&capability) && (capability.is_deterministic() || !self.deterministic)
+    }
+
+    /// Fails with `CapabilityDenied` unless `capability` has been granted.
+    pub fn require_capability(&self, capability: Capability) -> Result<(), VMError> {
+        if self.has_capability(capability) {
+            Ok(())
+        } else {
+            Err(VMError::CapabilityDenied(capability))
+        }
+    }
+
+    /// What the `fs` natives may touch, see `vm::sandbox`. Nothing by default.
+    pub fn set_fs_policy(&mut self, policy: FsPolicy) {
+        self.fs_policy = policy;
+    }
+
+    pub fn fs_policy(&self) -> &FsPolicy {
+        if self.deterministic {
+            return &sandbox::DISABLED;
+        }
+        &self.fs_policy
+    }
+
+    /// Where the `time` natives read the time, see `vm::clock`. The host's clocks by default.
+    pub fn set_clock(&mut self, clock: Rc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    pub fn clock(&self) -> &Rc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Restarts the generator behind the `random` natives from `seed`, see `vm::random`.
+    pub fn seed_random(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Makes every run of the same program with the same inputs behave the same, for
+    /// lockstep simulations and consensus. The `time` natives read a `VirtualClock` that
+    /// only moves when guest code sleeps, the `random` natives start from `seed`, and code
+    /// runs one bytecode instruction at a time, never in register form, so fuel and fiber
+    /// time slices run out at the same instruction every run. Natives whose results come
+    /// from the host fail: the `Ffi` and `Env` capabilities count as not granted and the
+    /// `fs` natives are denied. Maps need nothing extra, as whatever lists their entries,
+    /// like `json_stringify` or the value codec, sorts them by key. There is no way back.
+    pub fn make_deterministic(&mut self, seed: u64) {
+        self.deterministic = true;
+        self.clock = Rc::new(VirtualClock::new());
+        self.seed_random(seed);
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// The program arguments `process_args` returns, see `stdlib::process`.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Adds a place `LoadModule` looks for modules, after the ones added before, see
+    /// `vm::import`.
+    pub fn add_module_resolver(&mut self, resolver: impl ModuleResolver + 'static) {
+        self.modules.add_resolver(Box::new(resolver));
+    }
+
+    /// What `LoadModule name` pushes: a new map of the module's exports, loading the module
+    /// the first time.
+    pub fn import_module(&mut self, name: &str) -> Result<Value, VMError> {
+        let exports = match self.modules.exports(name) {
+            Some(exports) => exports.clone(),
+            None => {
+                let module = self.modules.resolve(name)?;
+                let (_, placed) = self.place_module(&module)?;
+                self.modules.insert(name, &placed).clone()
+            }
+        };
+        Ok(Value::Map(Gc::new(exports)))
+    }
+
+    fn handle_load_module(&mut self) -> Result<(), VMError> {
+        let (_, name) = self.read_member_name()?;
+        let module = self.import_module(&name)?;
+        self.stack.push(module);
+        Ok(())
+    }
+
+    /// Pops a module's exports and pushes the one named by the operand.
+    fn handle_import_symbol(&mut self) -> Result<(), VMError> {
+        let (_, name) = self.read_member_name()?;
+        let Value::Map(exports) = self.pop_stack()? else {
+            return Err(VMError::TypeMismatch("ImportSymbol expects a module".to_string()));
+        };
+        let symbol = exports.borrow().get(&*name).cloned();
+        let symbol = symbol.ok_or_else(|| VMError::Import(format!("The module has no export '{}'", name)))?;
+        self.stack.push(symbol);
+        Ok(())
+    }
+
+    pub fn is_module_loaded(&self, name: &str) -> bool {
+        self.modules.is_loaded(name)
+    }
+
+    /// Forgets every loaded module, so the next import of each resolves it again.
+    pub fn clear_module_cache(&mut self) {
+        self.modules.clear();
+    }
+
+    /// Makes `package`'s modules importable and its resources readable, and loads its entry
+    /// module like `load_module`, returning the entry point. Fails if the package requires
+    /// a feature this VM lacks. See `data::package`.
+    pub fn load_package(&mut self, package: &Package) -> Result<Option<Rc<Function>>, VMError> {
+        let missing = package.missing_features();
+        if !missing.is_empty() {
+            return Err(VMError::Import(format!("Package '{}' requires {}, which this VM lacks", package.name, missing.join(", "))));
+        }
+        self.add_module_resolver(package.resolver());
+        self.resources.extend(package.resources.clone());
+        let Some(entry) = package.entry_module() else { return Ok(None) };
+        Ok(self.load_module(entry)?.entry())
+    }
+
+    /// A resource file of a loaded package.
+    pub fn resource(&self, name: &str) -> Option<&[u8]> {
+        self.resources.get(name).map(Vec::as_slice)
+    }
+
+    /// Opens the native extension at `path` and lets it register its natives and classes,
+    /// see `vm::extension`.
+    pub fn load_extension(&mut self, path: &str) -> Result<(), VMError> {
+        extension::load(self, path)
+    }
+
+    /// Instructions a fiber runs before the scheduler switches to the next one.
+    pub fn set_fiber_time_slice(&mut self, instructions: u32) {
+        self.scheduler.time_slice = instructions.max(1);
+    }
+
     fn handle_end_try_block(&mut self) -> Result<(), VMError> {
         self.try_frames.pop().ok_or(VMError::NoTryFrame)?;
         Ok(())
     }
 
+    /// Runs the active frame's register-form body from `pc` through to its return. Interrupted
+    /// or out of fuel on a backward jump, it leaves the frame at the jump target, where either
+    /// form can go on.
+    fn run_register_body(&mut self, body: &RegisterCode, mut pc: usize) -> Result<bool, VMError> {
+        let frame = self.current_frame()?;
+        let (base, function) = (frame.stack_base, frame.function.clone());
+        self.stack.resize(base + body.registers, Value::Null);
+        self.high_water.stack = self.high_water.stack.max(self.stack.len());
+        loop {
+            let instruction = &body.instructions[pc];
+            pc += 1;
+            if let Some(fuel) = &mut self.fuel {
+                *fuel = fuel.saturating_sub(1);
+            }
+            let target = match instruction {
+                RegInstr::LoadConstant { dst, index } => {
+                    self.stack[base + dst] = function.constants[*index].clone();
+                    None
+                }
+                RegInstr::LoadValue { dst, value } => {
+                    self.stack[base + dst] = value.clone();
+                    None
+                }
+                RegInstr::Move { dst, src } => {
+                    self.stack[base + dst] = self.stack[base + src].clone();
+                    None
+                }
+                RegInstr::Binary { op, dst, a, b } => {
+                    self.stack[base + dst] = binary_int32(*op, self.stack[base + a].clone(), self.stack[base + b].clone())?;
+                    None
+                }
+                RegInstr::AddConstant { dst, src, constant } => match self.stack[base + src] {
+                    Value::I32(x) => {
+                        self.stack[base + dst] = Value::I32(x.wrapping_add(*constant));
+                        None
+                    }
+                    _ => return Err(VMError::TypeMismatch("Operand for AddInt32WithConstant must be I32".to_string())),
+                },
+                RegInstr::Jump { target } => Some(*target),
+                RegInstr::JumpIfFalse { condition, target } => (!self.stack[base + condition].is_truthy()).then_some(*target),
+                RegInstr::JumpIfNotLess { a, b, target } => {
+                    let less = binary_int32(OpCode::LessThanInt32, self.stack[base + a].clone(), self.stack[base + b].clone())?;
+                    (!less.is_truthy()).then_some(*target)
+                }
+                RegInstr::Return { src } => {
+                    let result = self.stack[base + src].clone();
+                    self.stack.push(result);
+                    return self.handle_return_from_function();
+                }
+            };
+            let Some(block) = target.map(|target| body.blocks[target]) else { continue };
+            if block.pc < pc && (self.fuel == Some(0) || self.interrupt.take()) {
+                self.current_frame_mut()?.ip = block.offset;
+                self.stack.truncate(base + block.depth);
+                return Err(if self.fuel == Some(0) { VMError::OutOfFuel } else { VMError::Interrupted });
+            }
+            pc = block.pc;
+        }
+    }
+
     fn handle_return_from_function(&mut self) -> Result<bool, VMError> {
         let result = self.pop_stack()?;
         let frame = self.frames.pop().ok_or(VMError::NoActiveCallFrame)?;
+        self.end_try_blocks_above(self.frames.len());
 
+        self.close_upvalues(frame.stack_base);
         self.stack.truncate(frame.stack_base);
-        self.stack.push(result);
+        if !frame.discard_result {
+            self.stack.push(result);
+        }
+        self.finish_coroutine();
 
         Ok(self.frames.is_empty())
     }
@@ -1517,402 +3387,1100 @@ impl IrisVM {
         Ok(())
     }
 
+    /// Defines every function of `module` as a global, in module order, starting at the lowest
+    /// run of free slots, and returns the module as loaded; names can be resolved with
+    /// `global_slot`. Module bytecode refers to its functions by index, so away from slot 0
+    /// they are relocated, see `Module::relocated`.
+    pub fn load_module(&mut self, module: &Module) -> Result<Module, VMError> {
+        let (base, loaded) = self.place_module(module)?;
+        for (offset, function) in loaded.functions.iter().enumerate() {
+            self.global_names.insert(function.name.clone(), base + offset);
+        }
+        Ok(loaded)
+    }
+
+    /// Defines the functions of `module`, relocated, in the lowest run of free slots.
+    fn place_module(&mut self, module: &Module) -> Result<(usize, Module), VMError> {
+        let base = self.first_free_global_run(module.functions.len());
+        let placed = module.relocated(base).map_err(|e| VMError::Import(format!("Cannot load module '{}': {}", module.name, e)))?;
+        for (offset, function) in placed.functions.iter().enumerate() {
+            self.define_global(base + offset, Value::Function(function.clone()));
+        }
+        Ok((base, placed))
+    }
+
+    /// Replaces the function defined as the global `name` with `function`, for editing a
+    /// program while it runs. Every global and module export holding the old function gets
+    /// the new one, and its decoded and register-form code and the method caches that found
+    /// it are dropped. Calls already running finish in the old function, and values made from
+    /// it, like closures, keep it. Returns the old function.
+    pub fn redefine_function(&mut self, name: &str, function: Rc<Function>) -> Result<Rc<Function>, VMError> {
+        let old = match self.global_slot(name).and_then(|slot| self.globals.get(slot)) {
+            Some(Value::Function(old)) if matches!(old.kind, FunctionKind::Bytecode) => old.clone(),
+            Some(_) => return Err(VMError::TypeMismatch(format!("Global '{}' is not a bytecode function", name))),
+            None => return Err(VMError::UndefinedVariable(name.to_string())),
+        };
+        if self.require_verification {
+            self.verify_function(&function)?;
+            self.verified.insert(Rc::as_ptr(&function), function.clone());
+        }
+        for global in self.globals.iter_mut() {
+            if matches!(global, Value::Function(defined) if Rc::ptr_eq(defined, &old)) {
+                *global = Value::Function(function.clone());
+            }
+        }
+        self.modules.replace(&old, &function);
+        self.inline_caches.invalidate_function(&old);
+        self.verified.remove(&Rc::as_ptr(&old));
+        self.decoded.remove(&Rc::as_ptr(&old));
+        Ok(old)
+    }
+
+    /// Defines a host function as a global named `name` and returns its slot. The closure
+    /// gets the arguments of each call and its result is pushed for the caller.
+    pub fn register_native(
+        &mut self,
+        name: &str,
+        native: impl Fn(&[Value]) -> Result<Value, VMError> + 'static,
+    ) -> usize {
+        let function = Function::new_native(name.to_string(), 0, move |_, args| native(args));
+        self.define_named_global(name, Value::Function(Rc::new(function)))
+    }
+
+    /// Like `register_native`, but arguments and the result are converted from and to Rust
+    /// types, and calls with the wrong number of arguments fail with `ArityMismatch`.
+    pub fn register_typed_native<Args>(&mut self, name: &str, native: impl TypedNative<Args>) -> usize {
+        let function = Function::from_native(name.to_string(), native.arity(), native.into_native());
+        self.define_named_global(name, Value::Function(Rc::new(function)))
+    }
+
+    /// Defines `value` as the global `name`, reusing the slot if the name is already taken.
+    pub fn define_named_global(&mut self, name: &str, value: Value) -> usize {
+        let slot = self.global_slot(name).unwrap_or_else(|| self.first_free_global_run(1));
+        self.define_global(slot, value);
+        self.global_names.insert(name.to_string(), slot);
+        slot
+    }
+
+    pub fn global_slot(&self, name: &str) -> Option<usize> {
+        self.global_names.get(name).copied()
+    }
+
+    fn first_free_global_run(&self, count: usize) -> usize {
+        let is_free = |slot: usize| {
+            !self.config.is_reserved(slot) && self.globals.get(slot).is_none_or(|value| matches!(value, Value::Null))
+        };
+        (0..=self.globals.len())
+            .find(|&base| (base..base + count).all(is_free))
+            .unwrap_or(self.globals.len())
+    }
+
+    pub fn globals(&self) -> &[Value] {
+        &self.globals
+    }
+
+    pub(crate) fn replace_globals(&mut self, globals: Vec<Value>) {
+        self.globals = globals;
+    }
+
+    /// Drops all transient execution state (operand stack, call frames and try frames).
+    /// Globals, config values and policies are kept.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.frames.clear();
+        self.try_frames.clear();
+    }
+
+    /// Captures the stack, call frames, try blocks and globals of a VM that isn't running,
+    /// for `restore` to continue from later, here or in another process. Fails while a
+    /// coroutine, fiber, future or native call is in progress, or if a value can't be
+    /// encoded. See `vm::snapshot`.
+    pub fn snapshot(&self) -> Result<Snapshot, VMError> {
+        if self.execute_depth > 0 || !self.coroutines.is_empty() || self.scheduler.current.is_some() || self.awaiting.is_some() {
+            return Err(VMError::Snapshot("The VM is running a coroutine, fiber, future or native call".to_string()));
+        }
+        let int = |n: usize| Value::I64(n as i64);
+        let frames = self.frames.iter().map(|frame| {
+            let closure = frame.closure.clone().map_or(Value::Null, Value::Closure);
+            let fields = [Value::Function(frame.function.clone()), closure, int(frame.ip), int(frame.stack_base), Value::Bool(frame.discard_result)];
+            Value::Tuple(Rc::from(fields))
+        });
+        let try_frames = self.try_frames.iter().map(|block| Value::Tuple(Rc::from([int(block.ip), int(block.stack_size), int(block.depth)])));
+        let names = self.global_names.iter().map(|(name, &slot)| (name.clone(), int(slot))).collect();
+        // Open upvalues are kept by a closure of their own, so they stay shared with the
+        // closures that captured them.
+        let mut upvalues = Closure::new(Rc::new(Function::new_bytecode(String::new(), 0, Vec::new(), Vec::new())));
+        upvalues.upvalues = self.open_upvalues.clone();
+        let initialized = self.initialized_classes.values().filter_map(Weak::upgrade).map(Value::Class);
+        let state = Value::Array(Gc::new(vec![
+            Value::Array(Gc::new(self.stack.clone())),
+            Value::Array(Gc::new(self.globals.clone())),
+            Value::Map(Gc::new(names)),
+            Value::Array(Gc::new(frames.collect())),
+            Value::Array(Gc::new(try_frames.collect())),
+            Value::Closure(Rc::new(upvalues)),
+            Value::Array(Gc::new(initialized.collect())),
+        ]));
+        let bytes = valuecodec::encode_with_natives(&state).map_err(|e| VMError::Snapshot(e.to_string()))?;
+        Ok(Snapshot::new(bytes))
+    }
+
+    /// Replaces the stack, call frames, try blocks and globals with `snapshot`'s; `run()` then
+    /// continues where the snapshot was taken. Natives are looked up by name among this VM's
+    /// globals, so register them first. On error the VM is left as it was.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), VMError> {
+        let malformed = || VMError::Snapshot("Malformed snapshot".to_string());
+        let natives = |name: &str| {
+            let native = self.global_slot(name).and_then(|slot| self.globals.get(slot))?;
+            matches!(native, Value::Function(function) if matches!(function.kind, FunctionKind::Native)).then(|| native.clone())
+        };
+        let state = valuecodec::decode_with_natives(snapshot.as_bytes(), &natives).map_err(|e| VMError::Snapshot(e.to_string()))?;
+        let Value::Array(state) = state else { return Err(malformed()) };
+        let state = state.borrow().clone();
+        let [Value::Array(stack), Value::Array(globals), Value::Map(names), Value::Array(frames), Value::Array(try_frames), Value::Closure(upvalues), Value::Array(initialized)] = state.as_slice() else {
+            return Err(malformed());
+        };
+        let int = |value: &Value| match value {
+            Value::I64(n) => usize::try_from(*n).map_err(|_| malformed()),
+            _ => Err(malformed()),
+        };
+        let stack = stack.borrow().clone();
+        let globals = globals.borrow().clone();
+        let names = names.borrow().iter().map(|(name, slot)| Ok((name.clone(), int(slot)?))).collect::<Result<HashMap<_, _>, VMError>>()?;
+        let mut restored = Vec::new();
+        for frame in frames.borrow().iter() {
+            let Value::Tuple(fields) = frame else { return Err(malformed()) };
+            let [Value::Function(function), closure, ip, stack_base, Value::Bool(discard_result)] = &fields[..] else { return Err(malformed()) };
+            let closure = match closure {
+                Value::Closure(closure) => Some(closure.clone()),
+                Value::Null => None,
+                _ => return Err(malformed()),
+            };
+            if self.require_verification && function.bytecode.is_some() {
+                self.verify_function(function)?;
+            }
+            restored.push((function.clone(), closure, int(ip)?, int(stack_base)?, *discard_result));
+        }
+        let try_frames = try_frames.borrow().iter().map(|block| match block {
+            Value::Tuple(fields) if fields.len() == 3 => Ok(TryFrame { ip: int(&fields[0])?, stack_size: int(&fields[1])?, depth: int(&fields[2])? }),
+            _ => Err(malformed()),
+        }).collect::<Result<Vec<_>, VMError>>()?;
+        let initialized: Vec<Rc<Class>> = initialized.borrow().iter().map(|class| match class {
+            Value::Class(class) => Ok(class.clone()),
+            _ => Err(malformed()),
+        }).collect::<Result<_, VMError>>()?;
+
+        self.frames = restored.into_iter().map(|(function, closure, ip, stack_base, discard_result)| CallFrame {
+            code: self.decode(&function),
+            registers: function.registers.clone(),
+            tier_checked: false,
+            function,
+            ip,
+            stack_base,
+            closure,
+            discard_result,
+        }).collect();
+        for object in snapshot::heap_objects(stack.iter().chain(&globals)) {
+            self.heap.track(&object);
+        }
+        self.stack = stack;
+        self.globals = globals;
+        self.global_names = names;
+        self.try_frames = try_frames;
+        self.base_depth = 0;
+        self.open_upvalues = upvalues.upvalues.clone();
+        self.initialized_classes = initialized.iter().map(|class| (Rc::as_ptr(class), Rc::downgrade(class))).collect();
+        self.inline_caches.clear();
+        Ok(())
+    }
+
     pub fn define_global(&mut self, index: usize, value: Value) {
+        self.forget_global_class(index);
         if index >= self.globals.len() {
             self.globals.resize(index + 1, Value::Null);
         }
         self.globals[index] = value;
     }
 
-    pub fn run(&mut self) -> Result<(), VMError> {
-        while let Some(frame) = self.frames.last_mut() {
-            let bytecode = frame.function.bytecode.as_ref().ok_or(VMError::InvalidOperand("Bytecode not found".to_string()))?;
-            if frame.ip >= bytecode.len() {
-                self.frames.pop();
-                continue;
-            }
+    /// Publishes a read-only config value into its reserved global slot and returns the slot.
+    /// Updating an existing value schedules its watchers for the next safepoint.
+    pub fn publish_config(&mut self, name: &str, value: Value) -> Result<usize, VMError> {
+        let slot = self.config.publish(name, value.clone())?;
+        self.define_global(slot, value);
+        Ok(slot)
+    }
 
-            let opcode: OpCode = bytecode[frame.ip].into();
-            frame.ip += 1;
+    pub fn config(&self) -> &ConfigStore {
+        &self.config
+    }
 
-            match opcode {
-                OpCode::Unknown => return Err(VMError::UnknownOpCode),
-                OpCode::NoOperation => {},
+    /// Registers a function to be called as `callback(name, value)` whenever the host updates `name`.
+    pub fn watch_config(&mut self, name: &str, callback: Value) -> Result<(), VMError> {
+        self.config.watch(name, callback)
+    }
 
-                OpCode::PushConstant8 => {
-                    let constant = self.read_constant8()?;
-                    self.stack.push(constant);
-                }
-                OpCode::PushConstant16 => {
-                    let constant = self.read_constant16()?;
-                    self.stack.push(constant);
-                }
-                OpCode::PushNull => self.stack.push(Value::Null),
-                OpCode::PushTrue => self.stack.push(Value::Bool(true)),
-                OpCode::PushFalse => self.stack.push(Value::Bool(false)),
-                OpCode::PopStack => {
-                    self.pop_stack()?;
-                }
-                OpCode::DuplicateTop => {
-                    let value = self.peek_stack(0)?.clone();
-                    self.stack.push(value);
-                }
-                OpCode::SwapTopTwo => {
-                    let a = self.pop_stack()?;
-                    let b = self.pop_stack()?;
-                    self.stack.push(a);
-                    self.stack.push(b);
-                }
-                OpCode::RotateTopThree => self.handle_rotate_top_three()?,
-                OpCode::PickStackItem => self.handle_peek_stack()?,
-                OpCode::RollStackItems => self.handle_roll_stack_items()?,
-                OpCode::PeekStack => self.handle_peek_stack()?,
-                OpCode::DropMultiple => self.handle_drop_multiple()?,
-                OpCode::DuplicateMultiple => self.handle_duplicate_multiple()?,
-                OpCode::SwapTopTwoPairs => self.handle_swap_top_two_pairs()?,
-                OpCode::SwapMultiple => self.handle_swap_multiple()?,
-
-                OpCode::LoadImmediateI8 => {
-                    let value = self.read_i8()?;
-                    self.stack.push(Value::I8(value));
-                }
-                OpCode::LoadImmediateI16 => {
-                    let value = self.read_i16()?;
-                    self.stack.push(Value::I16(value));
-                }
-                OpCode::LoadImmediateI32 => {
-                    let value = self.read_i32()?;
-                    self.stack.push(Value::I32(value));
-                }
-                OpCode::LoadImmediateI64 => {
-                    let value = self.read_i64()?;
-                    self.stack.push(Value::I64(value));
-                }
-                OpCode::LoadImmediateF32 => {
-                    let value = self.read_f32()?;
-                    self.stack.push(Value::F32(value));
-                }
-                OpCode::LoadImmediateF64 => {
-                    let value = self.read_f64()?;
-                    self.stack.push(Value::F64(value));
-                }
+    fn notify_config_watchers(&mut self) -> Result<(), VMError> {
+        for notification in self.config.take_pending() {
+            if let Value::Function(callback) = notification.callback {
+                self.invoke_nested(callback, vec![Value::Str(intern(&notification.name)), notification.value])?;
+            }
+        }
+        Ok(())
+    }
 
-                OpCode::GetLocalVariable8 => {
-                    let slot = self.read_byte()? as usize;
-                    self.handle_get_local_variable(slot)?;
-                }
-                OpCode::GetLocalVariable16 => {
-                    let slot = self.read_u16()? as usize;
-                    self.handle_get_local_variable(slot)?
-                }
-                OpCode::SetLocalVariable8 => {
-                    let slot = self.read_byte()? as usize;
-                    self.handle_set_local_variable(slot)?;
-                }
-                OpCode::SetLocalVariable16 => {
-                    let slot = self.read_u16()? as usize;
-                    self.handle_set_local_variable(slot)?
-                }
-                OpCode::GetGlobalVariable8 => {
-                    let slot = self.read_byte()? as usize;
-                    self.handle_get_global_variable(slot)?
-                }
-                OpCode::DefineGlobalVariable8 => {
-                    let slot = self.read_byte()? as usize;
-                    self.handle_define_global_variable(slot)?
-                }
-                OpCode::SetGlobalVariable8 => {
-                    let slot = self.read_byte()? as usize;
-                    self.handle_set_global_variable(slot)?
-                }
+    /// Runs `function` to completion on top of the current frames and discards its result,
+    /// leaving the interrupted frame's operand stack untouched.
+    fn invoke_nested(&mut self, function: Rc<Function>, args: Vec<Value>) -> Result<(), VMError> {
+        self.call(function, &args).map(|_| ())
+    }
 
-                OpCode::GetObjectProperty8 => {
-                    let name_index = self.read_byte()? as usize;
-                    self.handle_get_object_property(name_index)?
-                }
-                OpCode::GetObjectProperty16 => {
-                    let name_index = self.read_u16()? as usize;
-                    self.handle_get_object_property(name_index)?
-                }
-                OpCode::SetObjectProperty8 => {
-                    let name_index = self.read_byte()? as usize;
-                    self.handle_set_object_property(name_index)?
-                }
-                OpCode::SetObjectProperty16 => {
-                    let name_index = self.read_u16()? as usize;
-                    self.handle_set_object_property(name_index)?
-                }
-                OpCode::CreateNewInstance => self.handle_create_new_instance()?,
-                OpCode::InvokeMethod8 => {
-                    let method_name_index = self.read_byte()? as usize;
-                    let arg_count = self.read_byte()? as usize;
-                    self.handle_invoke_method(method_name_index, arg_count)?
-                }
-                OpCode::InvokeMethod16 => {
-                    let method_name_index = self.read_u16()? as usize;
-                    let arg_count = self.read_byte()? as usize;
-                    self.handle_invoke_method(method_name_index, arg_count)?
-                }
-                OpCode::CallDynamicMethod => self.handle_call_dynamic_method()?,
-                OpCode::GetSuperClassMethod8 => {
-                    let method_name_index = self.read_byte()? as usize;
-                    self.handle_get_super_class_method(method_name_index)?
-                }
-                OpCode::GetSuperClassMethod16 => {
-                    let method_name_index = self.read_u16()? as usize;
-                    self.handle_get_super_class_method(method_name_index)?
-                }
-                OpCode::DefineClass8 => {
-                    let name_index = self.read_byte()? as usize;
-                    self.handle_define_class(name_index)?
-                }
-                OpCode::DefineClass16 => {
-                    let name_index = self.read_u16()? as usize;
-                    self.handle_define_class(name_index)?
-                }
-                OpCode::InitializeClass => self.handle_initialize_class()?,
-                OpCode::CheckCastObject => self.handle_check_cast_object()?,
-                OpCode::InstanceOfCheck => self.handle_instance_of_check()?,
-                OpCode::LoadMethodHandle => self.handle_load_method_handle()?,
-                OpCode::BindMethodHandle => self.handle_bind_method_handle()?,
-                OpCode::GetVirtualTable => self.handle_get_virtual_table()?,
-                OpCode::SetVirtualTable => self.handle_set_virtual_table()?,
-                OpCode::AllocateObject => self.handle_allocate_object()?,
-                OpCode::FreeObject => self.handle_free_object()?,
-
-                OpCode::UnconditionalJump => self.handle_unconditional_jump()?,
-                OpCode::ShortJump => self.handle_short_jump()?,
-                OpCode::JumpIfTrue => self.handle_jump_if_true()?,
-                OpCode::JumpIfFalse => {
-                    self.handle_jump_if_false()?;
-                },
-                OpCode::JumpIfNull => self.handle_jump_if_null()?,
-                OpCode::JumpIfNonNull => self.handle_jump_if_non_null()?,
-                OpCode::LoopJump => {
-                    self.handle_loop_jump()?;
-                },
-                OpCode::LoopStartMarker => self.handle_loop_start_marker()?,
-                OpCode::LoopEndMarker => self.handle_loop_end_marker()?,
-                OpCode::CallFunction => self.handle_call_function()?,
-                OpCode::ReturnFromFunction => {
-                    if self.handle_return_from_function()? {
-                        break;
-                    }
-                }
-                OpCode::TailCallFunction => self.handle_tail_call_function()?,
-                OpCode::TableSwitch => self.handle_table_switch()?,
-                OpCode::LookupSwitch => self.handle_lookup_switch()?,
-                OpCode::RangeSwitch => self.handle_range_switch()?,
-                OpCode::ThrowException => self.handle_throw_exception()?,
-                OpCode::BeginTryBlock => self.handle_begin_try_block()?,
-                OpCode::CatchException => self.handle_catch_exception()?,
-                OpCode::FinallyBlock => self.handle_finally_block()?,
-                OpCode::EndTryBlock => self.handle_end_try_block()?,
-                OpCode::UnwindStack => self.handle_unwind_stack()?,
-
-                OpCode::EqualInt32 => self.handle_equal_int32()?,
-                OpCode::EqualInt64 => self.handle_equal_int64()?,
-                OpCode::EqualFloat32 => self.handle_equal_float32()?,
-                OpCode::EqualFloat64 => self.handle_equal_float64()?,
-                OpCode::NotEqualInt32 => self.handle_not_equal_int32()?,
-                OpCode::NotEqualInt64 => self.handle_not_equal_int64()?,
-                OpCode::NotEqualFloat32 => self.handle_not_equal_float32()?,
-                OpCode::NotEqualFloat64 => self.handle_not_equal_float64()?,
-                OpCode::GreaterThanInt32 => self.handle_greater_than_int32()?,
-                OpCode::LessThanInt32 => {
-                    let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                    let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                    match (a, b) {
-                        (Value::I32(a_val), Value::I32(b_val)) => self.stack.push(Value::Bool(a_val < b_val)),
-                        _ => return Err(VMError::TypeMismatch("Operands for LessThanInt32 must be I32".to_string())),
-                    }
-                },
-                OpCode::GreaterThanInt64 => self.handle_greater_than_int64()?,
-                OpCode::GreaterThanFloat32 => self.handle_greater_than_float32()?,
-                OpCode::GreaterThanFloat64 => self.handle_greater_than_float64()?,
-                OpCode::LessThanInt64 => self.handle_less_than_int64()?,
-                OpCode::LessThanFloat32 => self.handle_less_than_float32()?,
-                OpCode::LessThanFloat64 => self.handle_less_than_float64()?,
-                OpCode::GreaterOrEqualInt32 => self.handle_greater_or_equal_int32()?,
-                OpCode::GreaterOrEqualInt64 => self.handle_greater_or_equal_int64()?,
-                OpCode::GreaterOrEqualFloat32 => self.handle_greater_or_equal_float32()?,
-                OpCode::GreaterOrEqualFloat64 => self.handle_greater_or_equal_float64()?,
-                OpCode::LessOrEqualInt32 => self.handle_less_or_equal_int32()?,
-                OpCode::LessOrEqualInt64 => self.handle_less_or_equal_int64()?,
-                OpCode::LessOrEqualFloat32 => self.handle_less_or_equal_float32()?,
-                OpCode::LessOrEqualFloat64 => self.handle_less_or_equal_float64()?,
-                OpCode::CompareAndBranchEqualInt32 => self.handle_compare_and_branch_equal_int32()?,
-                OpCode::CompareAndBranchNotEqualInt32 => self.handle_compare_and_branch_not_equal_int32()?,
-                OpCode::CompareAndBranchLessThanInt32 => self.handle_compare_and_branch_less_than_int32()?,
-                OpCode::CompareAndBranchGreaterThanInt32 => self.handle_compare_and_branch_greater_than_int32()?,
-
-                OpCode::GreaterUnsigned8 => self.handle_greater_unsigned8()?,
-                OpCode::GreaterUnsigned16 => self.handle_greater_unsigned16()?,
-                OpCode::GreaterUnsigned32 => self.handle_greater_unsigned32()?,
-                OpCode::GreaterUnsigned64 => self.handle_greater_unsigned64()?,
-                OpCode::LessUnsigned8 => self.handle_less_unsigned8()?,
-                OpCode::LessUnsigned16 => self.handle_less_unsigned16()?,
-                OpCode::LessUnsigned32 => self.handle_less_unsigned32()?,
-                OpCode::LessUnsigned64 => self.handle_less_unsigned64()?,
-                OpCode::GreaterOrEqualUnsigned8 => self.handle_greater_or_equal_unsigned8()?,
-                OpCode::GreaterOrEqualUnsigned16 => self.handle_greater_or_equal_unsigned16()?,
-                OpCode::GreaterOrEqualUnsigned32 => self.handle_greater_or_equal_unsigned32()?,
-                OpCode::GreaterOrEqualUnsigned64 => self.handle_greater_or_equal_unsigned64()?,
-                OpCode::LessOrEqualUnsigned8 => self.handle_less_or_equal_unsigned8()?,
-                OpCode::LessOrEqualUnsigned16 => self.handle_less_or_equal_unsigned16()?,
-                OpCode::LessOrEqualUnsigned32 => self.handle_less_or_equal_unsigned32()?,
-                OpCode::LessOrEqualUnsigned64 => self.handle_less_or_equal_unsigned64()?,
-                OpCode::ConvertInt32ToInt64 => self.handle_convert_int32_to_int64()?,
-                OpCode::ConvertInt32ToFloat32 => self.handle_convert_int32_to_float32()?,
-                OpCode::ConvertInt32ToFloat64 => self.handle_convert_int32_to_float64()?,
-                OpCode::ConvertInt64ToInt32 => self.handle_convert_int64_to_int32()?,
-                OpCode::ConvertInt64ToFloat32 => self.handle_convert_int64_to_float32()?,
-                OpCode::ConvertInt64ToFloat64 => self.handle_convert_int64_to_float64()?,
-                OpCode::ConvertFloat32ToInt32 => self.handle_convert_float32_to_int32()?,
-                OpCode::ConvertFloat32ToInt64 => self.handle_convert_float32_to_int64()?,
-                OpCode::ConvertFloat32ToFloat64 => self.handle_convert_float32_to_float64()?,
-                OpCode::ConvertFloat64ToInt32 => self.handle_convert_float64_to_int32()?,
-                OpCode::ConvertFloat64ToInt64 => self.handle_convert_float64_to_int64()?,
-                OpCode::ConvertFloat64ToFloat32 => self.handle_convert_float64_to_float32()?,
-
-                OpCode::LogicalAndOperation => self.handle_logical_and_operation()?,
-                OpCode::LogicalOrOperation => self.handle_logical_or_operation()?,
-                OpCode::LogicalNotOperation => self.handle_logical_not_operation()?,
-                OpCode::BooleanAndOperation => self.handle_boolean_and_operation()?,
-                OpCode::BooleanOrOperation => self.handle_boolean_or_operation()?,
-
-                OpCode::AddInt32 => {
-                    let b = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                    let a = self.stack.pop().ok_or(VMError::StackUnderflow)?;
-                    match (a, b) {
-                        (Value::I32(a_val), Value::I32(b_val)) => self.stack.push(Value::I32(a_val + b_val)),
-                        _ => return Err(VMError::TypeMismatch("Operands for AddInt32 must be I32".to_string())),
-                    }
-                },
-                OpCode::AddInt64 => self.handle_add_int64()?,
-                OpCode::AddFloat32 => self.handle_add_float32()?,
-                OpCode::AddFloat64 => self.handle_add_float64()?,
-                OpCode::SubtractInt32 => self.handle_subtract_int32()?,
-                OpCode::SubtractInt64 => self.handle_subtract_int64()?,
-                OpCode::SubtractFloat32 => self.handle_subtract_float32()?,
-                OpCode::SubtractFloat64 => self.handle_subtract_float64()?,
-                OpCode::MultiplyInt32 => self.handle_multiply_int32()?,
-                OpCode::MultiplyInt64 => self.handle_multiply_int64()?,
-                OpCode::MultiplyFloat32 => self.handle_multiply_float32()?,
-                OpCode::MultiplyFloat64 => self.handle_multiply_float64()?,
-                OpCode::DivideInt32 => self.handle_divide_int32()?,
-                OpCode::DivideInt64 => self.handle_divide_int64()?,
-                OpCode::DivideFloat32 => self.handle_divide_float32()?,
-                OpCode::DivideFloat64 => self.handle_divide_float64()?,
-                OpCode::ModuloInt32 => self.handle_modulo_int32()?,
-                OpCode::ModuloInt64 => self.handle_modulo_int64()?,
-                OpCode::NegateInt32 => self.handle_negate_int32()?,
-                OpCode::NegateInt64 => self.handle_negate_int64()?,
-                OpCode::NegateFloat32 => self.handle_negate_float32()?,
-                OpCode::NegateFloat64 => self.handle_negate_float64()?,
-                OpCode::IncrementInt32 => self.handle_increment_int32()?,
-                OpCode::DecrementInt32 => self.handle_decrement_int32()?,
-                OpCode::IncrementInt64 => self.handle_increment_int64()?,
-                OpCode::DecrementInt64 => self.handle_decrement_int64()?,
-                OpCode::AddInt32WithConstant => self.handle_add_int32_with_constant()?,
-                OpCode::AddInt64WithConstant => self.handle_add_int64_with_constant()?,
-                OpCode::MultiplyInt32WithConstant => self.handle_multiply_int32_with_constant()?,
-                OpCode::MultiplyInt64WithConstant => self.handle_multiply_int64_with_constant()?,
-                OpCode::FusedMultiplyAddFloat32 => self.handle_fused_multiply_add_float32()?,
-                OpCode::FusedMultiplyAddFloat64 => self.handle_fused_multiply_add_float64()?,
-                OpCode::AbsoluteInt32 => self.handle_absolute_int32()?,
-                OpCode::AbsoluteInt64 => self.handle_absolute_int64()?,
-                OpCode::AbsoluteFloat32 => self.handle_absolute_float32()?,
-                OpCode::AbsoluteFloat64 => self.handle_absolute_float64()?,
-                OpCode::FloorFloat32 => self.handle_floor_float32()?,
-                OpCode::CeilFloat32 => self.handle_ceil_float32()?,
-                OpCode::RoundFloat32 => self.handle_round_float32()?,
-                OpCode::TruncateFloat32 => self.handle_truncate_float32()?,
-                OpCode::SquareRootFloat32 => self.handle_square_root_float32()?,
-                OpCode::SquareRootFloat64 => self.handle_square_root_float64()?,
-
-                OpCode::BitwiseAndInt32 => self.handle_bitwise_and_int32()?,
-                OpCode::BitwiseOrInt32 => self.handle_bitwise_or_int32()?,
-                OpCode::BitwiseXorInt32 => self.handle_bitwise_xor_int32()?,
-                OpCode::BitwiseNotInt32 => self.handle_bitwise_not_int32()?,
-                OpCode::BitwiseAndInt64 => self.handle_bitwise_and_int64()?,
-                OpCode::BitwiseOrInt64 => self.handle_bitwise_or_int64()?,
-                OpCode::BitwiseXorInt64 => self.handle_bitwise_xor_int64()?,
-                OpCode::BitwiseNotInt64 => self.handle_bitwise_not_int64()?,
-                OpCode::LeftShiftInt32 => self.handle_left_shift_int32()?,
-                OpCode::LeftShiftInt64 => self.handle_left_shift_int64()?,
-                OpCode::RightShiftInt32 => self.handle_right_shift_int32()?,
-                OpCode::RightShiftInt64 => self.handle_right_shift_int64()?,
-                OpCode::UnsignedRightShiftInt32 => self.handle_unsigned_right_shift_int32()?,
-                OpCode::UnsignedRightShiftInt64 => self.handle_unsigned_right_shift_int64()?,
-                OpCode::RotateLeftInt32 => self.handle_rotate_left_int32()?,
-                OpCode::RotateRightInt32 => self.handle_rotate_right_int32()?,
-
-                OpCode::CreateNewArray8 => {
-                    let num_elements = self.read_byte()? as usize;
-                    self.handle_create_new_array(num_elements)?
-                }
-                OpCode::CreateNewArray16 => {
-                    let num_elements = self.read_u16()? as usize;
-                    self.handle_create_new_array(num_elements)?
-                }
-                OpCode::GetArrayLength => self.handle_get_array_length()?,
-                OpCode::ResizeArray => self.handle_resize_array()?,
-                OpCode::GetArrayIndexInt32 => self.handle_get_array_index()?,
-                OpCode::SetArrayIndexInt32 => self.handle_set_array_index()?,
-                OpCode::GetArrayIndexFloat32 => self.handle_get_array_index_float32()?,
-                OpCode::SetArrayIndexFloat32 => self.handle_set_array_index_float32()?,
-                OpCode::GetArrayIndexFastInt32 => self.handle_get_array_index_fast_int32()?,
-                OpCode::SetArrayIndexFastInt32 => self.handle_set_array_index_fast_int32()?,
-                OpCode::CreateNewMap8 => {
-                    let num_entries = self.read_byte()? as usize;
-                    self.handle_create_new_map(num_entries)?
-                }
-                OpCode::CreateNewMap16 => {
-                    let num_entries = self.read_u16()? as usize;
-                    self.handle_create_new_map(num_entries)?
-                }
-                OpCode::MapContainsKey => self.handle_map_contains_key()?,
-                OpCode::MapRemoveKey => self.handle_map_remove_key()?,
-                OpCode::MapGetOrDefaultValue => self.handle_map_get_or_default_value()?,
-                OpCode::GetObjectField8 => {
-                    let name_index = self.read_byte()? as usize;
-                    self.handle_get_object_field(name_index)?
-                }
-                OpCode::GetObjectField16 => {
-                    let name_index = self.read_u16()? as usize;
-                    self.handle_get_object_field(name_index)?
-                }
-                OpCode::SetObjectField8 => {
-                    let name_index = self.read_byte()? as usize;
-                    self.handle_set_object_field(name_index)?
+    /// Calls `function` with `args` and runs it until it returns, on top of whatever is
+    /// already executing. The stack and frames are restored afterwards, even on error.
+    /// A function that runs off its end without `ReturnFromFunction` returns the value left
+    /// on top of its stack, or `Null`.
+    pub fn call(&mut self, function: Rc<Function>, args: &[Value]) -> Result<Value, VMError> {
+        if function.bytecode.is_some() && args.len() != function.arity {
+            return Err(VMError::ArityMismatch { expected: function.arity, found: args.len() });
+        }
+        let stack_len = self.stack.len();
+        let depth = self.frames.len();
+        let try_depth = self.try_frames.len();
+        self.stack.push(Value::Function(function.clone()));
+        self.stack.extend_from_slice(args);
+        let result = match function.kind {
+            crate::vm::function::FunctionKind::Native => self.call_native(&function, args.len(), false),
+            crate::vm::function::FunctionKind::Bytecode => {
+                self.stack.remove(stack_len);
+                self.push_frame(function, args.len()).and_then(|_| self.execute(depth, false))
+            }
+        };
+        let value = if self.stack.len() > stack_len { self.stack.pop().unwrap_or(Value::Null) } else { Value::Null };
+        self.close_upvalues(stack_len);
+        self.stack.truncate(stack_len);
+        self.frames.truncate(depth);
+        self.try_frames.truncate(try_depth);
+        result.map(|_| value)
+    }
+
+    /// Resumes `coroutine` with `value` and runs it until it yields or returns, on top of
+    /// whatever is already executing. Returns the yielded or returned value. A coroutine
+    /// that fails is finished, and the stack and frames are restored as with `call`.
+    pub fn resume(&mut self, coroutine: &Value, value: Value) -> Result<Value, VMError> {
+        let Value::Coroutine(coroutine) = coroutine else {
+            return Err(VMError::TypeMismatch("resume expects a coroutine".to_string()));
+        };
+        let stack_len = self.stack.len();
+        let depth = self.frames.len();
+        let try_depth = self.try_frames.len();
+        let coroutine_depth = self.coroutines.len();
+        let result = self.resume_coroutine(coroutine.clone(), value).and_then(|_| self.execute(depth, false));
+        let value = if self.stack.len() > stack_len { self.stack.pop().unwrap_or(Value::Null) } else { Value::Null };
+        for active in self.coroutines.drain(coroutine_depth..) {
+            active.coroutine.borrow_mut().state = CoroutineState::Finished;
+        }
+        self.close_upvalues(stack_len);
+        self.stack.truncate(stack_len);
+        self.frames.truncate(depth);
+        self.try_frames.truncate(try_depth);
+        result.map(|_| value)
+    }
+
+    /// Like `run()`, but when bytecode awaits a pending host future, waits for it without
+    /// blocking the executor and then continues. See `vm::future`.
+    pub async fn run_async(&mut self) -> Result<(), VMError> {
+        loop {
+            match self.run() {
+                Err(error) if matches!(error.root(), VMError::Pending) => {
+                    let future = self.awaiting.take().ok_or(error)?;
+                    std::future::poll_fn(|cx| future.borrow_mut().poll(cx)).await;
                 }
-                OpCode::SetObjectField16 => {
-                    let name_index = self.read_u16()? as usize;
-                    self.handle_set_object_field(name_index)?
+                result => return result,
+            }
+        }
+    }
+
+    /// Runs until the program finishes. Breakpoints only pause here, not inside `call`,
+    /// since a nested call cannot be resumed once it returns.
+    pub fn run(&mut self) -> Result<(), VMError> {
+        self.execute(0, true)
+    }
+
+    /// Like `run()`, but writes each instruction executed to `out` with the top of the stack
+    /// after it ran, or the error it raised. Write errors are ignored.
+    pub fn run_traced(&mut self, out: &mut impl std::io::Write) -> Result<(), VMError> {
+        const TRACED_SLOTS: usize = 3;
+        loop {
+            self.pop_finished_frames(0);
+            let Some(frame) = self.frames.last() else { return Ok(()) };
+            let (function, offset) = (frame.function.clone(), frame.ip);
+            let result = self.step_above(0, true, false);
+            let text = disassemble_instruction(&function, offset).unwrap_or_else(|| "<truncated>".to_string());
+            let _ = match &result {
+                Ok(_) => writeln!(out, "{}@{:04} {:<36} {:?}", function.name, offset, text, &self.stack[self.stack.len().saturating_sub(TRACED_SLOTS)..]),
+                Err(VMError::Breakpoint(_) | VMError::Interrupted | VMError::OutOfFuel | VMError::Pending) => Ok(()),
+                Err(error) => writeln!(out, "{}@{:04} {:<36} error: {}", function.name, offset, text, error.root()),
+            };
+            if result?.finished {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes exactly one instruction and reports what ran, ignoring breakpoints. Errors are
+    /// handled as in `run()`, so a step that throws into a try block succeeds and leaves the VM
+    /// at the handler.
+    pub fn step(&mut self) -> Result<StepOutcome, VMError> {
+        self.step_above(0, false, true)
+    }
+
+    /// Runs until the frame count drops back to `base_depth`.
+    fn execute(&mut self, base_depth: usize, breakpoints: bool) -> Result<(), VMError> {
+        self.execute_depth += 1;
+        let outer_depth = std::mem::replace(&mut self.base_depth, base_depth);
+        // A host call made by a handler runs instructions of its own; the handler may still
+        // have operands to read once it returns.
+        let (instruction, next_operand) = (self.instruction, self.next_operand);
+        let result = self.execute_fibers(base_depth, breakpoints);
+        (self.instruction, self.next_operand) = (instruction, next_operand);
+        self.base_depth = outer_depth;
+        self.execute_depth -= 1;
+        result
+    }
+
+    /// Runs until the frames above `base_depth` finish, moving on to the next fiber whenever
+    /// a spawned one finishes.
+    fn execute_fibers(&mut self, base_depth: usize, breakpoints: bool) -> Result<(), VMError> {
+        loop {
+            while !self.step_above(base_depth, breakpoints, false)?.finished {}
+            if base_depth > 0 || !self.can_switch_fiber() || !self.finish_fiber()? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Checks for a breakpoint at the next instruction. The instruction a paused `run()`
+    /// stopped at is let through once, so calling `run()` again resumes there.
+    fn check_breakpoint(&mut self) -> Result<(), VMError> {
+        if self.resuming || self.breakpoints.is_empty() {
+            return Ok(());
+        }
+        let frame = self.frames.last().ok_or(VMError::NoActiveCallFrame)?;
+        if !self.breakpoints.contains(&frame.function.name, frame.ip) {
+            return Ok(());
+        }
+        let location = self.current_location().ok_or(VMError::NoActiveCallFrame)?;
+        let action = match self.breakpoints.handler.clone() {
+            Some(handler) => handler(self, &location),
+            None => BreakpointAction::Pause,
+        };
+        match action {
+            BreakpointAction::Continue => Ok(()),
+            BreakpointAction::Pause => {
+                self.resuming = true;
+                Err(VMError::Breakpoint(location))
+            }
+        }
+    }
+
+    /// Drops frames above `base_depth` that have run off the end of their bytecode.
+    fn pop_finished_frames(&mut self, base_depth: usize) {
+        while self.frames.len() > base_depth {
+            let frame = &self.frames[self.frames.len() - 1];
+            if frame.function.bytecode.as_ref().is_some_and(|code| frame.ip < code.len()) {
+                break;
+            }
+            self.frames.pop();
+            self.end_try_blocks_above(self.frames.len());
+            self.finish_coroutine();
+        }
+    }
+
+    /// Executes one instruction in the frames above `base_depth`. The outcome only has the
+    /// instruction's location when `locate` is set, since building it allocates.
+    fn step_above(&mut self, base_depth: usize, breakpoints: bool, locate: bool) -> Result<StepOutcome, VMError> {
+        if self.config.has_pending() {
+            self.notify_config_watchers()?;
+        }
+        self.pop_finished_frames(base_depth);
+        if self.frames.len() <= base_depth {
+            return Ok(StepOutcome { opcode: None, location: None, depth: self.frames.len(), finished: true });
+        }
+        if base_depth == 0 && self.can_switch_fiber() {
+            self.scheduler.ticks += 1;
+            if self.scheduler.ticks >= self.scheduler.time_slice {
+                self.switch_fiber()?;
+            }
+        }
+        if breakpoints {
+            self.check_breakpoint()?;
+        }
+        if self.interrupt.take() {
+            return Err(VMError::Interrupted);
+        }
+        if let Some(fuel) = &mut self.fuel {
+            if *fuel == 0 {
+                return Err(VMError::OutOfFuel);
+            }
+            *fuel -= 1;
+        }
+        self.sample_profile();
+
+        let stepwise = !self.breakpoints.is_empty() || self.stats.is_some() || self.coverage.is_some() || self.profile.is_some() || self.deterministic;
+        let frame = self.frames.last_mut().ok_or(VMError::NoActiveCallFrame)?;
+        let start = frame.ip;
+        let register_entry = match &frame.registers {
+            Some(body) if !stepwise => match start {
+                0 => Some((body.clone(), 0)),
+                _ => body.block_at(start).map(|block| (body.clone(), block.pc)),
+            },
+            _ => None,
+        };
+        let instruction = match frame.code.at(start) {
+            Some(instruction) => *instruction,
+            None => frame.function.bytecode.as_deref().and_then(|code| decode_instruction(code, start))
+                .ok_or_else(|| VMError::InvalidOperand("Bytecode not found".to_string()))?,
+        };
+        let (byte, opcode) = (instruction.byte, instruction.opcode);
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(&frame.function, start);
+        }
+        let location = locate.then(|| SourceLocation {
+            function: frame.function.name.clone(),
+            offset: start,
+            span: frame.function.lines.span_at(start),
+        });
+        frame.ip = instruction.next;
+        self.instruction = instruction;
+        self.next_operand = 0;
+        self.resuming = false;
+
+        let depth = self.frames.len();
+        let started = self.stats.is_some().then(Instant::now);
+        let result = if let Some((body, pc)) = register_entry {
+            match self.run_register_body(&body, pc) {
+                Err(error @ (VMError::Interrupted | VMError::OutOfFuel)) => return Err(error),
+                result => result,
+            }
+        } else if is_custom_opcode(byte) {
+            self.dispatch_custom(byte).map(|_| false)
+        } else {
+            self.dispatch(opcode)
+        };
+        let number = if is_custom_opcode(byte) { byte as u16 } else { opcode as u16 };
+        if let (Some(stats), Some(started)) = (&mut self.stats, started) {
+            stats.record(number, started.elapsed());
+        }
+        self.high_water.stack = self.high_water.stack.max(self.stack.len());
+        let result = result.and_then(|done| match self.limits.max_stack_size {
+            Some(limit) if self.stack.len() > limit => Err(VMError::StackOverflow { limit }),
+            _ => Ok(done),
+        });
+        let returned = match result {
+            Ok(done) => done,
+            Err(error) => {
+                if let Err(error) = self.raise_runtime_error(error) {
+                    return Err(self.locate(error, start, depth));
                 }
-                OpCode::AllocateSlice => self.handle_allocate_slice()?,
-
-                OpCode::AtomicAddInt32 => self.handle_atomic_add_int32()?,
-                OpCode::AtomicSubtractInt32 => self.handle_atomic_subtract_int32()?,
-                OpCode::AtomicCompareAndSwapInt32 => self.handle_atomic_compare_and_swap_int32()?,
-                OpCode::EnterMonitor => self.handle_enter_monitor()?,
-                OpCode::ExitMonitor => self.handle_exit_monitor()?,
-                OpCode::YieldCurrentThread => self.handle_yield_current_thread()?,
-
-                OpCode::CallWithInlineCache => self.handle_call_with_inline_cache()?,
-                OpCode::CallWithInlineCacheInline => self.handle_call_with_inline_cache_inline()?,
-                OpCode::GetPropertyWithInlineCache => self.handle_get_property_with_inline_cache()?,
-                OpCode::GetPropertyWithInlineCacheInline => self.handle_get_property_with_inline_cache_inline()?,
-                OpCode::SetPropertyWithInlineCache => self.handle_set_property_with_inline_cache()?,
-                OpCode::LoadMethodInlineCache => self.handle_load_method_inline_cache()?,
-                OpCode::MegamorphicMethodCall => self.handle_megamorphic_method_call()?,
-
-                OpCode::PrintTopOfStack => {
-                    self.handle_print_top_of_stack()?;
-                },
+                false
             }
+        };
+        self.pop_finished_frames(base_depth);
+        Ok(StepOutcome {
+            opcode: Some(number),
+            location,
+            depth: self.frames.len(),
+            finished: returned || self.frames.len() <= base_depth,
+        })
+    }
+
+    /// Executes a single decoded opcode. Returns `true` once the outermost frame has returned.
+    fn dispatch(&mut self, opcode: OpCode) -> Result<bool, VMError> {
+        match HANDLERS[dispatch_index(opcode)] {
+            Some(handler) => handler(self),
+            None => Err(VMError::UnknownOpCode),
         }
-        Ok(())
+    }
+
+    /// Whether the interpreter has a handler for `opcode`; custom opcodes aside, executing one it
+    /// doesn't fails with `VMError::UnknownOpCode`.
+    pub fn handles(opcode: OpCode) -> bool {
+        HANDLERS[dispatch_index(opcode)].is_some()
     }
 }
+
+/// Runs one instruction whose opcode and prefix have been read; `Ok(true)` once the outermost
+/// frame has returned.
+type Handler = fn(&mut IrisVM) -> Result<bool, VMError>;
+
+/// The primary page, then the extended page.
+const DISPATCH_TABLE_LEN: usize = 512;
+
+/// Where `opcode`'s handler sits in `HANDLERS`.
+const fn dispatch_index(opcode: OpCode) -> usize {
+    match opcode as u16 {
+        number @ 0xFF00.. => 256 + (number & 0xFF) as usize,
+        number => number as usize,
+    }
+}
+
+/// Builds the handler table. A handler's body runs with `vm` bound to the VM and returns
+/// `Ok(false)` when it doesn't return anything itself.
+macro_rules! handlers {
+    ($($($op:ident)|+ => |$vm:ident| $body:expr,)*) => {{
+        let mut table: [Option<Handler>; DISPATCH_TABLE_LEN] = [None; DISPATCH_TABLE_LEN];
+        $($(table[dispatch_index(OpCode::$op)] = Some(|$vm| {
+            $body;
+            Ok(false)
+        });)+)*
+        table
+    }};
+}
+
+/// Every opcode's handler, indexed by `dispatch_index`, so dispatch is one indexed call rather
+/// than a branch through a match on all opcodes.
+static HANDLERS: [Option<Handler>; DISPATCH_TABLE_LEN] = handlers! {
+    NoOperation => |_vm| {},
+
+    PushConstant8 => |vm| {
+        let constant = vm.read_constant8()?;
+        vm.stack.push(constant);
+    },
+    PushConstant16 => |vm| {
+        let constant = vm.read_constant16()?;
+        vm.stack.push(constant);
+    },
+    PushNull => |vm| vm.stack.push(Value::Null),
+    PushTrue => |vm| vm.stack.push(Value::Bool(true)),
+    PushFalse => |vm| vm.stack.push(Value::Bool(false)),
+    PopStack => |vm| {
+        vm.pop_stack()?;
+    },
+    DuplicateTop => |vm| {
+        let value = vm.peek_stack(0)?.clone();
+        vm.stack.push(value);
+    },
+    SwapTopTwo => |vm| {
+        let a = vm.pop_stack()?;
+        let b = vm.pop_stack()?;
+        vm.stack.push(a);
+        vm.stack.push(b);
+    },
+    RotateTopThree => |vm| vm.handle_rotate_top_three()?,
+    PickStackItem => |vm| vm.handle_peek_stack()?,
+    RollStackItems => |vm| vm.handle_roll_stack_items()?,
+    PeekStack => |vm| vm.handle_peek_stack()?,
+    DropMultiple => |vm| vm.handle_drop_multiple()?,
+    DuplicateMultiple => |vm| vm.handle_duplicate_multiple()?,
+    SwapTopTwoPairs => |vm| vm.handle_swap_top_two_pairs()?,
+    SwapMultiple => |vm| vm.handle_swap_multiple()?,
+
+    LoadImmediateI8 => |vm| {
+        let value = vm.read_i8()?;
+        vm.stack.push(Value::I8(value));
+    },
+    LoadImmediateI16 => |vm| {
+        let value = vm.read_i16()?;
+        vm.stack.push(Value::I16(value));
+    },
+    LoadImmediateI32 => |vm| {
+        let value = vm.read_i32()?;
+        vm.stack.push(Value::I32(value));
+    },
+    LoadImmediateI64 => |vm| {
+        let value = vm.read_i64()?;
+        vm.stack.push(Value::I64(value));
+    },
+    LoadImmediateF32 => |vm| {
+        let value = vm.read_f32()?;
+        vm.stack.push(Value::F32(value));
+    },
+    LoadImmediateF64 => |vm| {
+        let value = vm.read_f64()?;
+        vm.stack.push(Value::F64(value));
+    },
+
+    GetLocalVariable8 => |vm| {
+        let slot = vm.read_byte()? as usize;
+        vm.handle_get_local_variable(slot)?;
+    },
+    GetLocalVariable16 => |vm| {
+        let slot = vm.read_u16()? as usize;
+        vm.handle_get_local_variable(slot)?
+    },
+    SetLocalVariable8 => |vm| {
+        let slot = vm.read_byte()? as usize;
+        vm.handle_set_local_variable(slot)?;
+    },
+    SetLocalVariable16 => |vm| {
+        let slot = vm.read_u16()? as usize;
+        vm.handle_set_local_variable(slot)?
+    },
+    GetGlobalVariable8 => |vm| {
+        let slot = vm.read_byte()? as usize;
+        vm.handle_get_global_variable(slot)?
+    },
+    DefineGlobalVariable8 => |vm| {
+        let slot = vm.read_byte()? as usize;
+        vm.handle_define_global_variable(slot)?
+    },
+    SetGlobalVariable8 => |vm| {
+        let slot = vm.read_byte()? as usize;
+        vm.handle_set_global_variable(slot)?
+    },
+
+    GetObjectProperty8 => |vm| {
+        let name_index = vm.read_byte()? as usize;
+        vm.handle_get_object_property(name_index)?
+    },
+    GetObjectProperty16 | GetPropertyWithInlineCache => |vm| {
+        let name_index = vm.read_u16()? as usize;
+        vm.handle_get_object_property(name_index)?
+    },
+    SetObjectProperty8 => |vm| {
+        let name_index = vm.read_byte()? as usize;
+        vm.handle_set_object_property(name_index)?
+    },
+    SetObjectProperty16 | SetPropertyWithInlineCache => |vm| {
+        let name_index = vm.read_u16()? as usize;
+        vm.handle_set_object_property(name_index)?
+    },
+    CreateNewInstance => |vm| vm.handle_create_new_instance()?,
+    InvokeMethod8 => |vm| {
+        let method_name_index = vm.read_byte()? as usize;
+        let arg_count = vm.read_byte()? as usize;
+        vm.handle_invoke_method(method_name_index, arg_count)?
+    },
+    InvokeMethod16 => |vm| {
+        let method_name_index = vm.read_u16()? as usize;
+        let arg_count = vm.read_byte()? as usize;
+        vm.handle_invoke_method(method_name_index, arg_count)?
+    },
+    CallDynamicMethod => |vm| vm.handle_call_dynamic_method()?,
+    GetSuperClassMethod8 => |vm| {
+        let method_name_index = vm.read_byte()? as usize;
+        vm.handle_get_super_class_method(method_name_index)?
+    },
+    GetSuperClassMethod16 => |vm| {
+        let method_name_index = vm.read_u16()? as usize;
+        vm.handle_get_super_class_method(method_name_index)?
+    },
+    DefineClass8 => |vm| {
+        let name_index = vm.read_byte()? as usize;
+        vm.handle_define_class(name_index)?
+    },
+    DefineClass16 => |vm| {
+        let name_index = vm.read_u16()? as usize;
+        vm.handle_define_class(name_index)?
+    },
+    InitializeClass => |vm| vm.handle_initialize_class()?,
+    CheckCastObject => |vm| vm.handle_check_cast_object()?,
+    InstanceOfCheck => |vm| vm.handle_instance_of_check()?,
+    LoadMethodHandle => |vm| vm.handle_load_method_handle()?,
+    BindMethodHandle => |vm| vm.handle_bind_method_handle()?,
+    GetVirtualTable => |vm| vm.handle_get_virtual_table()?,
+    SetVirtualTable => |vm| vm.handle_set_virtual_table()?,
+    AllocateObject => |vm| vm.handle_allocate_object()?,
+    FreeObject => |vm| vm.handle_free_object()?,
+
+    UnconditionalJump => |vm| vm.handle_unconditional_jump()?,
+    ShortJump => |vm| vm.handle_short_jump()?,
+    JumpIfTrue => |vm| vm.handle_jump_if_true()?,
+    JumpIfFalse => |vm| {
+        vm.handle_jump_if_false()?;
+    },
+    JumpIfNull => |vm| vm.handle_jump_if_null()?,
+    JumpIfNonNull => |vm| vm.handle_jump_if_non_null()?,
+    LoopJump => |vm| {
+        vm.handle_loop_jump()?;
+    },
+    LoopStartMarker => |vm| vm.handle_loop_start_marker()?,
+    LoopEndMarker => |vm| vm.handle_loop_end_marker()?,
+    CallFunction => |vm| vm.handle_call_function()?,
+    ReturnFromFunction => |vm| {
+        if vm.handle_return_from_function()? {
+            return Ok(true);
+        }
+    },
+    TailCallFunction => |vm| vm.handle_tail_call_function()?,
+    TableSwitch => |vm| vm.handle_table_switch()?,
+    LookupSwitch => |vm| vm.handle_lookup_switch()?,
+    RangeSwitch => |vm| vm.handle_range_switch()?,
+    ThrowException => |vm| vm.handle_throw_exception()?,
+    BeginTryBlock => |vm| vm.handle_begin_try_block()?,
+    CatchException => |vm| vm.handle_catch_exception()?,
+    FinallyBlock => |vm| vm.handle_finally_block()?,
+    EndTryBlock => |vm| vm.handle_end_try_block()?,
+    UnwindStack => |vm| vm.handle_unwind_stack()?,
+
+    EqualInt32 => |vm| vm.handle_binary_int32(OpCode::EqualInt32)?,
+    EqualInt64 => |vm| vm.handle_equal_int64()?,
+    EqualFloat32 => |vm| vm.handle_equal_float32()?,
+    EqualFloat64 => |vm| vm.handle_equal_float64()?,
+    NotEqualInt32 => |vm| vm.handle_binary_int32(OpCode::NotEqualInt32)?,
+    NotEqualInt64 => |vm| vm.handle_not_equal_int64()?,
+    NotEqualFloat32 => |vm| vm.handle_not_equal_float32()?,
+    NotEqualFloat64 => |vm| vm.handle_not_equal_float64()?,
+    GreaterThanInt32 => |vm| vm.handle_binary_int32(OpCode::GreaterThanInt32)?,
+    LessThanInt32 => |vm| vm.handle_binary_int32(OpCode::LessThanInt32)?,
+    GreaterThanInt64 => |vm| vm.handle_greater_than_int64()?,
+    GreaterThanFloat32 => |vm| vm.handle_greater_than_float32()?,
+    GreaterThanFloat64 => |vm| vm.handle_greater_than_float64()?,
+    LessThanInt64 => |vm| vm.handle_less_than_int64()?,
+    LessThanFloat32 => |vm| vm.handle_less_than_float32()?,
+    LessThanFloat64 => |vm| vm.handle_less_than_float64()?,
+    GreaterOrEqualInt32 => |vm| vm.handle_binary_int32(OpCode::GreaterOrEqualInt32)?,
+    GreaterOrEqualInt64 => |vm| vm.handle_greater_or_equal_int64()?,
+    GreaterOrEqualFloat32 => |vm| vm.handle_greater_or_equal_float32()?,
+    GreaterOrEqualFloat64 => |vm| vm.handle_greater_or_equal_float64()?,
+    LessOrEqualInt32 => |vm| vm.handle_binary_int32(OpCode::LessOrEqualInt32)?,
+    LessOrEqualInt64 => |vm| vm.handle_less_or_equal_int64()?,
+    LessOrEqualFloat32 => |vm| vm.handle_less_or_equal_float32()?,
+    LessOrEqualFloat64 => |vm| vm.handle_less_or_equal_float64()?,
+    CompareAndBranchEqualInt32 => |vm| vm.handle_compare_and_branch_equal_int32()?,
+    CompareAndBranchNotEqualInt32 => |vm| vm.handle_compare_and_branch_not_equal_int32()?,
+    CompareAndBranchLessThanInt32 => |vm| vm.handle_compare_and_branch_less_than_int32()?,
+    CompareAndBranchGreaterThanInt32 => |vm| vm.handle_compare_and_branch_greater_than_int32()?,
+
+    GreaterUnsigned8 => |vm| vm.handle_greater_unsigned8()?,
+    GreaterUnsigned16 => |vm| vm.handle_greater_unsigned16()?,
+    GreaterUnsigned32 => |vm| vm.handle_greater_unsigned32()?,
+    GreaterUnsigned64 => |vm| vm.handle_greater_unsigned64()?,
+    LessUnsigned8 => |vm| vm.handle_less_unsigned8()?,
+    LessUnsigned16 => |vm| vm.handle_less_unsigned16()?,
+    LessUnsigned32 => |vm| vm.handle_less_unsigned32()?,
+    LessUnsigned64 => |vm| vm.handle_less_unsigned64()?,
+    GreaterOrEqualUnsigned8 => |vm| vm.handle_greater_or_equal_unsigned8()?,
+    GreaterOrEqualUnsigned16 => |vm| vm.handle_greater_or_equal_unsigned16()?,
+    GreaterOrEqualUnsigned32 => |vm| vm.handle_greater_or_equal_unsigned32()?,
+    GreaterOrEqualUnsigned64 => |vm| vm.handle_greater_or_equal_unsigned64()?,
+    LessOrEqualUnsigned8 => |vm| vm.handle_less_or_equal_unsigned8()?,
+    LessOrEqualUnsigned16 => |vm| vm.handle_less_or_equal_unsigned16()?,
+    LessOrEqualUnsigned32 => |vm| vm.handle_less_or_equal_unsigned32()?,
+    LessOrEqualUnsigned64 => |vm| vm.handle_less_or_equal_unsigned64()?,
+    ConvertInt32ToInt64 => |vm| vm.handle_convert_int32_to_int64()?,
+    ConvertInt32ToFloat32 => |vm| vm.handle_convert_int32_to_float32()?,
+    ConvertInt32ToFloat64 => |vm| vm.handle_convert_int32_to_float64()?,
+    ConvertInt64ToInt32 => |vm| vm.handle_convert_int64_to_int32()?,
+    ConvertInt64ToFloat32 => |vm| vm.handle_convert_int64_to_float32()?,
+    ConvertInt64ToFloat64 => |vm| vm.handle_convert_int64_to_float64()?,
+    ConvertFloat32ToInt32 => |vm| vm.handle_convert_float32_to_int32()?,
+    ConvertFloat32ToInt64 => |vm| vm.handle_convert_float32_to_int64()?,
+    ConvertFloat32ToFloat64 => |vm| vm.handle_convert_float32_to_float64()?,
+    ConvertFloat64ToInt32 => |vm| vm.handle_convert_float64_to_int32()?,
+    ConvertFloat64ToInt64 => |vm| vm.handle_convert_float64_to_int64()?,
+    ConvertFloat64ToFloat32 => |vm| vm.handle_convert_float64_to_float32()?,
+
+    LogicalAndOperation => |vm| vm.handle_logical_and_operation()?,
+    LogicalOrOperation => |vm| vm.handle_logical_or_operation()?,
+    LogicalNotOperation => |vm| vm.handle_logical_not_operation()?,
+    BooleanAndOperation => |vm| vm.handle_boolean_and_operation()?,
+    BooleanOrOperation => |vm| vm.handle_boolean_or_operation()?,
+
+    AddInt32 => |vm| vm.handle_binary_int32(OpCode::AddInt32)?,
+    AddInt64 => |vm| vm.handle_add_int64()?,
+    AddFloat32 => |vm| vm.handle_add_float32()?,
+    AddFloat64 => |vm| vm.handle_add_float64()?,
+    SubtractInt32 => |vm| vm.handle_binary_int32(OpCode::SubtractInt32)?,
+    SubtractInt64 => |vm| vm.handle_subtract_int64()?,
+    SubtractFloat32 => |vm| vm.handle_subtract_float32()?,
+    SubtractFloat64 => |vm| vm.handle_subtract_float64()?,
+    MultiplyInt32 => |vm| vm.handle_multiply_int32()?,
+    MultiplyInt64 => |vm| vm.handle_multiply_int64()?,
+    MultiplyFloat32 => |vm| vm.handle_multiply_float32()?,
+    MultiplyFloat64 => |vm| vm.handle_multiply_float64()?,
+    DivideInt32 => |vm| vm.handle_divide_int32()?,
+    DivideInt64 => |vm| vm.handle_divide_int64()?,
+    DivideFloat32 => |vm| vm.handle_divide_float32()?,
+    DivideFloat64 => |vm| vm.handle_divide_float64()?,
+    ModuloInt32 => |vm| vm.handle_modulo_int32()?,
+    ModuloInt64 => |vm| vm.handle_modulo_int64()?,
+    NegateInt32 => |vm| vm.handle_negate_int32()?,
+    NegateInt64 => |vm| vm.handle_negate_int64()?,
+    NegateFloat32 => |vm| vm.handle_negate_float32()?,
+    NegateFloat64 => |vm| vm.handle_negate_float64()?,
+    IncrementInt32 => |vm| vm.handle_increment_int32()?,
+    DecrementInt32 => |vm| vm.handle_decrement_int32()?,
+    IncrementInt64 => |vm| vm.handle_increment_int64()?,
+    DecrementInt64 => |vm| vm.handle_decrement_int64()?,
+    AddInt32WithConstant => |vm| vm.handle_add_int32_with_constant()?,
+    AddInt64WithConstant => |vm| vm.handle_add_int64_with_constant()?,
+    MultiplyInt32WithConstant => |vm| vm.handle_multiply_int32_with_constant()?,
+    MultiplyInt64WithConstant => |vm| vm.handle_multiply_int64_with_constant()?,
+    FusedMultiplyAddFloat32 => |vm| vm.handle_fused_multiply_add_float32()?,
+    FusedMultiplyAddFloat64 => |vm| vm.handle_fused_multiply_add_float64()?,
+    AbsoluteInt32 => |vm| vm.handle_absolute_int32()?,
+    AbsoluteInt64 => |vm| vm.handle_absolute_int64()?,
+    AbsoluteFloat32 => |vm| vm.handle_absolute_float32()?,
+    AbsoluteFloat64 => |vm| vm.handle_absolute_float64()?,
+    FloorFloat32 => |vm| vm.handle_floor_float32()?,
+    CeilFloat32 => |vm| vm.handle_ceil_float32()?,
+    RoundFloat32 => |vm| vm.handle_round_float32()?,
+    TruncateFloat32 => |vm| vm.handle_truncate_float32()?,
+    SquareRootFloat32 => |vm| vm.handle_square_root_float32()?,
+    SquareRootFloat64 => |vm| vm.handle_square_root_float64()?,
+
+    BitwiseAndInt32 => |vm| vm.handle_bitwise_and_int32()?,
+    BitwiseOrInt32 => |vm| vm.handle_bitwise_or_int32()?,
+    BitwiseXorInt32 => |vm| vm.handle_bitwise_xor_int32()?,
+    BitwiseNotInt32 => |vm| vm.handle_bitwise_not_int32()?,
+    BitwiseAndInt64 => |vm| vm.handle_bitwise_and_int64()?,
+    BitwiseOrInt64 => |vm| vm.handle_bitwise_or_int64()?,
+    BitwiseXorInt64 => |vm| vm.handle_bitwise_xor_int64()?,
+    BitwiseNotInt64 => |vm| vm.handle_bitwise_not_int64()?,
+    LeftShiftInt32 => |vm| vm.handle_left_shift_int32()?,
+    LeftShiftInt64 => |vm| vm.handle_left_shift_int64()?,
+    RightShiftInt32 => |vm| vm.handle_right_shift_int32()?,
+    RightShiftInt64 => |vm| vm.handle_right_shift_int64()?,
+    UnsignedRightShiftInt32 => |vm| vm.handle_unsigned_right_shift_int32()?,
+    UnsignedRightShiftInt64 => |vm| vm.handle_unsigned_right_shift_int64()?,
+    RotateLeftInt32 => |vm| vm.handle_rotate_left_int32()?,
+    RotateRightInt32 => |vm| vm.handle_rotate_right_int32()?,
+
+    CreateNewArray8 => |vm| {
+        let num_elements = vm.read_byte()? as usize;
+        vm.handle_create_new_array(num_elements)?
+    },
+    CreateNewArray16 => |vm| {
+        let num_elements = vm.read_u16()? as usize;
+        vm.handle_create_new_array(num_elements)?
+    },
+    GetArrayLength => |vm| vm.handle_get_array_length()?,
+    ResizeArray => |vm| vm.handle_resize_array()?,
+    GetArrayIndexInt32 => |vm| vm.handle_get_array_index()?,
+    SetArrayIndexInt32 => |vm| vm.handle_set_array_index()?,
+    GetArrayIndexFloat32 => |vm| vm.handle_get_array_index_float32()?,
+    SetArrayIndexFloat32 => |vm| vm.handle_set_array_index_float32()?,
+    GetArrayIndexFastInt32 => |vm| vm.handle_get_array_index_fast_int32()?,
+    SetArrayIndexFastInt32 => |vm| vm.handle_set_array_index_fast_int32()?,
+    CreateNewMap8 => |vm| {
+        let num_entries = vm.read_byte()? as usize;
+        vm.handle_create_new_map(num_entries)?
+    },
+    CreateNewMap16 => |vm| {
+        let num_entries = vm.read_u16()? as usize;
+        vm.handle_create_new_map(num_entries)?
+    },
+    MapContainsKey => |vm| vm.handle_map_contains_key()?,
+    MapRemoveKey => |vm| vm.handle_map_remove_key()?,
+    MapGetOrDefaultValue => |vm| vm.handle_map_get_or_default_value()?,
+    GetObjectField8 => |vm| {
+        let name_index = vm.read_byte()? as usize;
+        vm.handle_get_object_field(name_index)?
+    },
+    GetObjectField16 => |vm| {
+        let name_index = vm.read_u16()? as usize;
+        vm.handle_get_object_field(name_index)?
+    },
+    SetObjectField8 => |vm| {
+        let name_index = vm.read_byte()? as usize;
+        vm.handle_set_object_field(name_index)?
+    },
+    SetObjectField16 => |vm| {
+        let name_index = vm.read_u16()? as usize;
+        vm.handle_set_object_field(name_index)?
+    },
+    AllocateSlice => |vm| vm.handle_allocate_slice()?,
+
+    AtomicAddInt32 => |vm| vm.handle_atomic_add_int32()?,
+    AtomicSubtractInt32 => |vm| vm.handle_atomic_subtract_int32()?,
+    AtomicCompareAndSwapInt32 => |vm| vm.handle_atomic_compare_and_swap_int32()?,
+    EnterMonitor => |vm| vm.handle_enter_monitor()?,
+    ExitMonitor => |vm| vm.handle_exit_monitor()?,
+    YieldCurrentThread => |vm| vm.handle_yield_current_thread()?,
+
+    CallWithInlineCache => |vm| vm.handle_call_with_inline_cache()?,
+    CallWithInlineCacheInline => |vm| vm.handle_call_with_inline_cache_inline()?,
+    GetPropertyWithInlineCacheInline => |vm| vm.handle_get_property_with_inline_cache_inline()?,
+    LoadMethodInlineCache => |vm| vm.handle_load_method_inline_cache()?,
+    MegamorphicMethodCall => |vm| vm.handle_megamorphic_method_call()?,
+
+    PrintTopOfStack => |vm| {
+        vm.handle_print_top_of_stack()?;
+    },
+
+    CreateWeakRef => |vm| {
+        let value = vm.pop_stack()?;
+        let weak = WeakRef::new(&value).ok_or_else(|| VMError::TypeMismatch("CreateWeakRef expects an array, map, set or object".to_string()))?;
+        vm.stack.push(Value::WeakRef(weak));
+    },
+    UpgradeWeakRef => |vm| match vm.pop_stack()? {
+        Value::WeakRef(weak) => vm.stack.push(weak.upgrade().unwrap_or(Value::Null)),
+        _ => return Err(VMError::TypeMismatch("UpgradeWeakRef expects a weak reference".to_string())),
+    },
+
+    ConvertToBigInt => |vm| {
+        let value = value_to_integer(&vm.pop_stack()?)
+            .ok_or_else(|| VMError::TypeMismatch("ConvertToBigInt expects an integer".to_string()))?;
+        vm.stack.push(Value::BigInt(Rc::new(value.to_big())));
+    },
+    ConvertBigIntToInt64 => |vm| {
+        let value = match value_to_integer(&vm.pop_stack()?) {
+            Some(Integer::Small(value)) => value,
+            Some(Integer::Big(value)) => value.to_i64().ok_or(VMError::IntegerOverflow)?,
+            None => return Err(VMError::TypeMismatch("ConvertBigIntToInt64 expects an integer".to_string())),
+        };
+        vm.stack.push(Value::I64(value));
+    },
+    AddInt64Promoting => |vm| vm.int64_arithmetic("AddInt64Promoting", i64::checked_add, |a, b| a + b, true)?,
+    SubtractInt64Promoting => |vm| vm.int64_arithmetic("SubtractInt64Promoting", i64::checked_sub, |a, b| a - b, true)?,
+    MultiplyInt64Promoting => |vm| vm.int64_arithmetic("MultiplyInt64Promoting", i64::checked_mul, |a, b| a * b, true)?,
+
+    CreateTuple => |vm| {
+        let len = vm.read_byte()? as usize;
+        if vm.stack.len() < len {
+            return Err(VMError::StackUnderflow);
+        }
+        vm.charge_heap(memory::tuple_size(len))?;
+        let elements: Rc<[Value]> = vm.stack.drain(vm.stack.len() - len..).collect();
+        vm.stack.push(Value::Tuple(elements));
+    },
+    GetTupleElement => |vm| {
+        let index = vm.read_byte()? as usize;
+        match vm.pop_stack()? {
+            Value::Tuple(elements) => vm.stack.push(elements.get(index).cloned().ok_or(VMError::IndexOutOfBounds)?),
+            _ => return Err(VMError::TypeMismatch("GetTupleElement expects a tuple".to_string())),
+        }
+    },
+    UnpackTuple => |vm| {
+        let len = vm.read_byte()? as usize;
+        match vm.pop_stack()? {
+            Value::Tuple(elements) if elements.len() == len => vm.stack.extend(elements.iter().cloned()),
+            Value::Tuple(elements) => return Err(VMError::ArityMismatch { expected: len, found: elements.len() }),
+            _ => return Err(VMError::TypeMismatch("UnpackTuple expects a tuple".to_string())),
+        }
+    },
+
+    CreateSet => |vm| {
+        let len = vm.read_byte()? as usize;
+        if vm.stack.len() < len {
+            return Err(VMError::StackUnderflow);
+        }
+        let set = ValueSet::from_values(vm.stack.drain(vm.stack.len() - len..).collect::<Vec<_>>())?;
+        vm.push_new_set(set)?;
+    },
+    SetInsert => |vm| {
+        let value = vm.pop_stack()?;
+        let set = vm.pop_set("SetInsert")?;
+        if set.borrow_mut().insert(value)? {
+            vm.charge_heap(memory::set_size(1) - memory::set_size(0))?;
+        }
+    },
+    SetContains => |vm| {
+        let value = vm.pop_stack()?;
+        let contains = vm.pop_set("SetContains")?.borrow().contains(&value)?;
+        vm.stack.push(Value::Bool(contains));
+    },
+    SetRemove => |vm| {
+        let value = vm.pop_stack()?;
+        let removed = vm.pop_set("SetRemove")?.borrow_mut().remove(&value)?;
+        vm.stack.push(Value::Bool(removed));
+    },
+    SetUnion => |vm| {
+        let b = vm.pop_set("SetUnion")?;
+        let union = vm.pop_set("SetUnion")?.borrow().union(&b.borrow());
+        vm.push_new_set(union)?;
+    },
+    SetIntersection => |vm| {
+        let b = vm.pop_set("SetIntersection")?;
+        let intersection = vm.pop_set("SetIntersection")?.borrow().intersection(&b.borrow());
+        vm.push_new_set(intersection)?;
+    },
+    SetToArray => |vm| {
+        let elements: Vec<Value> = vm.pop_set("SetToArray")?.borrow().iter().cloned().collect();
+        vm.charge_heap(memory::array_size(elements.len()))?;
+        let array = vm.allocate(Value::Array(Gc::new(elements)));
+        vm.stack.push(array);
+    },
+    SetLength => |vm| {
+        let len = vm.pop_set("SetLength")?.borrow().len();
+        vm.stack.push(Value::I64(len as i64));
+    },
+
+    CreateRange => |vm| {
+        let step = vm.pop_range_bound()?;
+        let end = vm.pop_range_bound()?;
+        let start = vm.pop_range_bound()?;
+        let range = Range::new(start, end, step).ok_or_else(|| VMError::InvalidOperand("Range step must not be zero".to_string()))?;
+        vm.stack.push(Value::Range(range));
+    },
+    RangeHasNext => |vm| match vm.peek_stack(0)? {
+        Value::Range(range) => {
+            let has_next = !range.is_empty();
+            vm.stack.push(Value::Bool(has_next));
+        }
+        _ => return Err(VMError::TypeMismatch("RangeHasNext expects a range".to_string())),
+    },
+    RangeNext => |vm| match vm.pop_stack()? {
+        Value::Range(range) => {
+            let (first, rest) = range.split_first().ok_or(VMError::IndexOutOfBounds)?;
+            vm.stack.push(Value::Range(rest));
+            vm.stack.push(Value::I64(first));
+        }
+        _ => return Err(VMError::TypeMismatch("RangeNext expects a range".to_string())),
+    },
+
+    CaptureUpvalue => |vm| vm.handle_capture_upvalue()?,
+    GetUpvalue => |vm| vm.handle_get_upvalue()?,
+    SetUpvalue => |vm| vm.handle_set_upvalue()?,
+    CloseUpvalue => |vm| vm.handle_close_upvalue()?,
+
+    CreateCoroutine => |vm| vm.handle_create_coroutine()?,
+    ResumeCoroutine => |vm| vm.handle_resume_coroutine()?,
+    Yield => |vm| vm.handle_yield()?,
+    Await => |vm| vm.handle_await()?,
+    LoadModule => |vm| vm.handle_load_module()?,
+    ImportSymbol => |vm| vm.handle_import_symbol()?,
+
+    SpawnFiber => |vm| vm.handle_spawn_fiber()?,
+    YieldFiber => |vm| {
+        if vm.can_switch_fiber() {
+            vm.switch_fiber()?;
+        }
+    },
+    JoinFiber => |vm| vm.handle_join_fiber()?,
+
+    CreateChannel => |vm| vm.stack.push(Value::Channel(Rc::new(Channel::new()))),
+    ChannelSend => |vm| {
+        let message = vm.pop_stack()?;
+        vm.pop_channel("ChannelSend")?.send(&message)?;
+    },
+    ChannelReceive => |vm| vm.handle_channel_receive()?,
+    ChannelTryReceive => |vm| {
+        let message = vm.pop_channel("ChannelTryReceive")?.try_receive();
+        let received = message.is_some();
+        vm.stack.push(message.unwrap_or(Value::Null));
+        vm.stack.push(Value::Bool(received));
+    },
+
+    AddLocalInt32WithConstant => |vm| vm.handle_add_local_int32_with_constant()?,
+    JumpIfLocalsNotLessInt32 => |vm| vm.handle_jump_if_locals_not_less_int32()?,
+
+    NewTypedArray => |vm| vm.handle_new_typed_array()?,
+    TypedArrayGet => |vm| {
+        let index = vm.pop_typed_array_index()?;
+        let array = vm.pop_stack()?;
+        let element = typed_array::get(&array, index).ok_or_else(|| typed_array_error(&array))?;
+        vm.stack.push(element);
+    },
+    TypedArraySet => |vm| {
+        let element = vm.pop_typed_array_element()?;
+        let index = vm.pop_typed_array_index()?;
+        let array = vm.pop_stack()?;
+        if !typed_array::set(&array, index, element) {
+            return Err(typed_array_error(&array));
+        }
+    },
+    TypedArrayLength => |vm| {
+        let array = vm.pop_stack()?;
+        let len = typed_array::len(&array).ok_or_else(|| typed_array_error(&array))?;
+        vm.stack.push(Value::I64(len as i64));
+    },
+    TypedArrayFill => |vm| {
+        let element = vm.pop_typed_array_element()?;
+        let array = vm.pop_stack()?;
+        if ElementType::of(&array).is_none() {
+            return Err(typed_array_error(&array));
+        }
+        typed_array::fill(&array, element);
+    },
+};
@@ -0,0 +1,82 @@
+use iris_vm::actors::{self, ActorSystem};
+use iris_vm::asm::assemble;
+use iris_vm::vm::channel::Channel;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+/// Replies to `(channel, n)` with `n * 2`.
+const DOUBLE: &str = "
+    .function double 1
+            GetLocalVariable8 0
+            GetTupleElement 0
+            GetLocalVariable8 0
+            GetTupleElement 1
+            LoadImmediateI32 2
+            MultiplyInt32
+            ChannelSend
+            PushNull
+            ReturnFromFunction
+";
+
+fn function(source: &str) -> Value {
+    Value::Function(Rc::new(assemble(source).unwrap()))
+}
+
+#[test]
+fn test_actor_handles_messages_in_order() {
+    let system = ActorSystem::new();
+    let actor = system.spawn(&function(DOUBLE)).unwrap();
+    let replies = Channel::new();
+    for n in 1..=3 {
+        let message = Value::Tuple(Rc::from(vec![Value::Channel(Rc::new(replies.clone())), Value::I32(n)]));
+        system.send(actor, &message).unwrap();
+    }
+    let received: Vec<Value> = (0..3).map(|_| replies.receive().unwrap()).collect();
+    assert_eq!(received, vec![Value::I64(2), Value::I64(4), Value::I64(6)]);
+    system.join(actor).unwrap();
+    assert!(matches!(system.send(actor, &Value::Null), Err(VMError::InvalidOperand(_))));
+}
+
+#[test]
+fn test_scripts_spawn_send_and_stop_actors() {
+    let system = ActorSystem::new();
+    let replies = Channel::new();
+    let mut vm = IrisVM::new();
+    actors::register(&mut vm, &system);
+    vm.define_named_global("double", function(DOUBLE));
+    vm.define_named_global("replies", Value::Channel(Rc::new(replies.clone())));
+    vm.push_frame(Rc::new(assemble("
+        .function main 0
+                GetGlobalVariable8 0
+                GetGlobalVariable8 3
+                CallFunction 1
+                DuplicateTop
+                GetGlobalVariable8 1
+                SwapTopTwo
+                GetGlobalVariable8 4
+                LoadImmediateI32 5
+                CreateTuple 2
+                CallFunction 2
+                PopStack
+                GetGlobalVariable8 2
+                SwapTopTwo
+                CallFunction 1
+                PopStack
+    ").unwrap()), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(replies.receive().unwrap(), Value::I64(10));
+    system.shutdown().unwrap();
+}
+
+#[test]
+fn test_actor_errors() {
+    let system = ActorSystem::new();
+    assert!(matches!(system.spawn(&Value::I32(1)), Err(VMError::TypeMismatch(_))));
+    assert!(matches!(system.send(7, &Value::Null), Err(VMError::InvalidOperand(_))));
+
+    let actor = system.spawn(&function(DOUBLE)).unwrap();
+    system.send(actor, &Value::I32(1)).unwrap();
+    let error = system.join(actor).unwrap_err();
+    assert!(error.contains("Type mismatch"), "{}", error);
+}
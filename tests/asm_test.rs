@@ -0,0 +1,63 @@
+use iris_vm::asm::{assemble, assemble_chunk};
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+use std::rc::Rc;
+
+#[test]
+fn test_assemble_and_run_loop() {
+    let function = assemble(r#"
+        .function countdown 0
+        .const greeting str "liftoff"
+
+                LoadImmediateI32 3
+        loop:   DuplicateTop
+                JumpIfFalse done        ; stop once the counter reaches zero
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:   PopStack
+                PushConstant8 greeting
+                ReturnFromFunction
+    "#).unwrap();
+
+    assert_eq!(function.name, "countdown");
+    assert_eq!(function.constants.len(), 1);
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::Str("liftoff".into())]);
+}
+
+#[test]
+fn test_assemble_operand_encoding() {
+    let chunk = assemble_chunk(r#"
+        start:  pushconstant8 "inline"
+                LoadImmediateI8 -2
+                InvokeMethod16 0x0102, 3
+                ShortJump start
+                TableSwitch end, 0, 1, end, end
+        end:    .byte 0xF8 7
+    "#).unwrap();
+
+    assert_eq!(chunk.constants.len(), 1);
+    assert_eq!(chunk.code, vec![
+        OpCode::PushConstant8 as u8, 0,
+        OpCode::LoadImmediateI8 as u8, 0xFE,
+        OpCode::InvokeMethod16 as u8, 1, 2, 3,
+        OpCode::ShortJump as u8, (-10i8) as u8,
+        OpCode::TableSwitch as u8, 0, 15, 0, 0, 0, 0, 0, 0, 0, 1, 0, 15, 0, 15,
+        0xF8, 7,
+    ]);
+}
+
+#[test]
+fn test_assemble_reports_line_numbers() {
+    let err = assemble("PushNull\nFrobnicate 3\n").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(err.message.contains("Frobnicate"));
+
+    let err = assemble("JumpIfFalse nowhere").unwrap_err();
+    assert!(err.message.contains("undefined label 'nowhere'"));
+}
@@ -0,0 +1,104 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_atomic_add_and_subtract_push_old_value() {
+    let mut vm = IrisVM::new();
+    let cells = Gc::new(vec![10, i32::MAX]);
+    vm.define_named_global("cells", Value::Int32Array(cells.clone()));
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                LoadImmediateI32 5
+                AtomicAddInt32
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                LoadImmediateI32 3
+                AtomicSubtractInt32
+                GetGlobalVariable8 0
+                LoadImmediateI32 1
+                LoadImmediateI32 1
+                AtomicAddInt32
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(10), Value::I32(15), Value::I32(i32::MAX)]);
+    assert_eq!(*cells.borrow(), vec![12, i32::MIN]);
+}
+
+#[test]
+fn test_compare_and_swap_only_stores_on_match() {
+    let mut vm = IrisVM::new();
+    let cells = Gc::new(vec![1]);
+    vm.define_named_global("cells", Value::Int32Array(cells.clone()));
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                LoadImmediateI32 2
+                LoadImmediateI32 9
+                AtomicCompareAndSwapInt32
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                LoadImmediateI32 1
+                LoadImmediateI32 7
+                AtomicCompareAndSwapInt32
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(1), Value::I32(1)]);
+    assert_eq!(*cells.borrow(), vec![7]);
+
+    let error = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 3
+                LoadImmediateI32 1
+                AtomicAddInt32
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::IndexOutOfBounds));
+}
+
+#[test]
+fn test_fibers_share_an_atomic_counter() {
+    let mut vm = IrisVM::new();
+    vm.set_fiber_time_slice(3);
+    vm.define_named_global("counter", Value::Int32Array(Gc::new(vec![0])));
+    let worker = assemble("
+        .function worker 0
+                LoadImmediateI32 100
+        top:    DuplicateTop
+                JumpIfFalse done
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                LoadImmediateI32 1
+                AtomicAddInt32
+                PopStack
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump top
+        done:   ReturnFromFunction
+    ").unwrap();
+    vm.define_named_global("worker", Value::Function(Rc::new(worker)));
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 1
+                SpawnFiber
+                GetGlobalVariable8 1
+                SpawnFiber
+                JoinFiber
+                PopStack
+                JoinFiber
+                PopStack
+                GetGlobalVariable8 0
+                LoadImmediateI32 0
+                TypedArrayGet
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(200)]);
+}
@@ -0,0 +1,49 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn divide_by_zero() -> Function {
+    let mut chunk = Chunk::new();
+    chunk.set_line(5);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(0i32);
+    chunk.set_line(6);
+    chunk.write(OpCode::DivideInt32);
+    chunk.write(OpCode::ReturnFromFunction);
+    Function::new_bytecode("divide".to_string(), 0, chunk.code, chunk.constants).with_lines(chunk.lines)
+}
+
+#[test]
+fn test_backtrace_lists_active_frames() {
+    let mut chunk = Chunk::new();
+    chunk.set_line(1);
+    chunk.write(OpCode::PushNull);
+    chunk.set_line(2);
+    chunk.write_constant(Value::Function(Rc::new(divide_by_zero())));
+    chunk.write(OpCode::CallFunction); chunk.write(0u8);
+    chunk.write(OpCode::ReturnFromFunction);
+    let main = Function::new_bytecode("main".to_string(), 0, chunk.code, chunk.constants).with_lines(chunk.lines);
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(main), 0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.root(), VMError::DivisionByZero));
+
+    let frames = err.backtrace().unwrap().frames();
+    let summary: Vec<(&str, usize, Option<u32>)> = frames.iter()
+        .map(|frame| (frame.function.as_str(), frame.offset, frame.span.map(|span| span.line)))
+        .collect();
+    assert_eq!(summary, vec![("divide", 10, Some(6)), ("main", 4, Some(2))]);
+    assert_eq!(err.backtrace().unwrap().to_string(), "   0: in divide at line 6\n   1: in main at line 2\n");
+}
+
+#[test]
+fn test_errors_outside_run_have_no_backtrace() {
+    let vm = IrisVM::new();
+    let err = vm.get_global(99).unwrap_err();
+    assert!(err.backtrace().is_none());
+    assert!(err.location().is_none());
+}
@@ -0,0 +1,72 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::bigint::BigInt;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(source: &str) -> Result<Vec<Value>, VMError> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn big(text: &str) -> Value {
+    Value::BigInt(Rc::new(text.parse().unwrap()))
+}
+
+#[test]
+fn test_bigint_arithmetic_and_formatting() {
+    let a: BigInt = "123456789012345678901234567890".parse().unwrap();
+    let b: BigInt = "-987654321987654321".parse().unwrap();
+    assert_eq!((&a * &b).to_string(), "-121932631246761163237311385323609205901126352690");
+    assert_eq!((&a + &b).to_string(), "123456789011358024579246913569");
+    let (quotient, remainder) = a.div_rem(&b).unwrap();
+    assert_eq!(quotient.to_string(), "-124999998748");
+    assert_eq!(remainder.to_string(), "432099904777777782");
+    assert_eq!(&(&quotient * &b) + &remainder, a);
+    assert!(b < BigInt::zero() && a > b);
+    assert_eq!(BigInt::from(i64::MIN).to_i64(), Some(i64::MIN));
+}
+
+#[test]
+fn test_promoting_ops_overflow_into_bigint() {
+    let stack = run(r#"
+        .function promote 0
+                LoadImmediateI64 9223372036854775807
+                LoadImmediateI64 2
+                MultiplyInt64Promoting
+                DuplicateTop
+                LoadImmediateI64 -9223372036854775807
+                AddInt64Promoting
+                LoadImmediateI64 3
+                LoadImmediateI64 4
+                MultiplyInt64Promoting
+    "#).unwrap();
+    assert_eq!(stack, vec![big("18446744073709551614"), Value::I64(9223372036854775807), Value::I64(12)]);
+}
+
+#[test]
+fn test_int64_ops_and_conversions_take_bigints() {
+    let stack = run(r#"
+        .function mixed 0
+        .const huge bigint 100000000000000000000
+                PushConstant8 huge
+                LoadImmediateI64 7
+                ModuloInt64
+                PushConstant8 huge
+                LoadImmediateI64 1
+                GreaterThanInt64
+                LoadImmediateI32 5
+                ConvertToBigInt
+    "#).unwrap();
+    assert_eq!(stack, vec![big("2"), Value::Bool(true), big("5")]);
+
+    let error = run(r#"
+        .function narrow 0
+        .const huge bigint -100000000000000000000
+                PushConstant8 huge
+                ConvertBigIntToInt64
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::IntegerOverflow));
+}
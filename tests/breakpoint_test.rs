@@ -0,0 +1,52 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::debug::breakpoints::BreakpointAction;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn program() -> Rc<iris_vm::vm::function::Function> {
+    Rc::new(assemble("
+        .function main 0
+        LoadImmediateI32 1
+        LoadImmediateI32 2
+        AddInt32
+    ").unwrap())
+}
+
+#[test]
+fn test_run_pauses_and_resumes_at_breakpoint() {
+    let mut vm = IrisVM::new();
+    assert!(vm.add_breakpoint("main", 10));
+    assert!(!vm.add_breakpoint("main", 10));
+    vm.push_frame(program(), 0).unwrap();
+
+    match vm.run() {
+        Err(VMError::Breakpoint(location)) => assert_eq!((location.function.as_str(), location.offset), ("main", 10)),
+        other => panic!("expected a breakpoint, got {:?}", other),
+    }
+    assert_eq!(vm.stack, vec![Value::I32(1), Value::I32(2)]);
+    assert_eq!(vm.current_location().unwrap().offset, 10);
+
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(3)]);
+    assert!(vm.remove_breakpoint("main", 10));
+    assert!(vm.breakpoints().is_empty());
+}
+
+#[test]
+fn test_handler_sees_paused_state_and_can_continue() {
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let mut vm = IrisVM::new();
+    vm.add_breakpoint("main", 5);
+    vm.add_breakpoint("main", 10);
+    let log = seen.clone();
+    vm.set_breakpoint_handler(move |vm, location| {
+        log.borrow_mut().push((location.offset, vm.stack.len()));
+        BreakpointAction::Continue
+    });
+    vm.push_frame(program(), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*seen.borrow(), [(5, 1), (10, 2)]);
+    assert_eq!(vm.stack, vec![Value::I32(3)]);
+}
@@ -0,0 +1,54 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_builder_predefines_globals() {
+    let mut vm = IrisVM::builder()
+        .initial_stack_capacity(64)
+        .global("base", Value::I64(40))
+        .native("plus_two", |args| match args {
+            [Value::I64(x)] => Ok(Value::I64(x + 2)),
+            _ => Err(VMError::TypeMismatch("plus_two expects an I64".to_string())),
+        })
+        .jit(true)
+        .build();
+    assert!(vm.stack.capacity() >= 64);
+    assert!(vm.jit_enabled());
+    assert!(!IrisVM::jit_available());
+
+    let source = format!("
+        GetGlobalVariable8 {}
+        GetGlobalVariable8 {}
+        CallFunction 1
+    ", vm.global_slot("plus_two").unwrap(), vm.global_slot("base").unwrap());
+    vm.push_frame(Rc::new(assemble(&source).unwrap()), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I64(42)]);
+}
+
+#[test]
+fn test_limits_stop_runaway_programs() {
+    let mut vm = IrisVM::builder().max_stack_size(16).build();
+    let grow = assemble("
+        top:    PushNull
+                LoopJump top
+    ").unwrap();
+    vm.push_frame(Rc::new(grow), 0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.root(), VMError::StackOverflow { limit: 16 }));
+    assert_eq!(vm.stack.len(), 17);
+
+    // Calls itself through global 0 forever.
+    let recurse = Rc::new(assemble("
+        .function recurse 0
+        GetGlobalVariable8 0
+        CallFunction 0
+        ReturnFromFunction
+    ").unwrap());
+    let mut vm = IrisVM::builder().max_call_depth(8).global("recurse", Value::Function(recurse.clone())).build();
+    let err = vm.call(recurse, &[]).unwrap_err();
+    assert!(matches!(err.root(), VMError::CallDepthExceeded { limit: 8 }));
+    assert_eq!(err.backtrace().unwrap().frames().len(), 8);
+}
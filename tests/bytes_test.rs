@@ -0,0 +1,44 @@
+use iris_vm::stdlib::bytes;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+#[test]
+fn test_bytes_round_trip_through_str() {
+    let mut vm = IrisVM::new();
+    bytes::register(&mut vm);
+    let buffer = call(&mut vm, "bytes_from_str", &[Value::Str("héllo".into())]).unwrap();
+    assert_eq!(call(&mut vm, "bytes_len", std::slice::from_ref(&buffer)).unwrap(), Value::I64(6));
+    assert_eq!(call(&mut vm, "bytes_get", &[buffer.clone(), Value::I32(1)]).unwrap(), Value::U8(0xC3));
+    assert_eq!(call(&mut vm, "bytes_to_str", &[buffer]).unwrap(), Value::Str("héllo".into()));
+}
+
+#[test]
+fn test_bytes_slice_concat_and_set() {
+    let mut vm = IrisVM::new();
+    bytes::register(&mut vm);
+    let buffer = call(&mut vm, "bytes_from_str", &[Value::Str("protocol".into())]).unwrap();
+    let head = call(&mut vm, "bytes_slice", &[buffer.clone(), Value::I32(0), Value::I32(3)]).unwrap();
+    let tail = call(&mut vm, "bytes_new", &[Value::I32(2)]).unwrap();
+    call(&mut vm, "bytes_set", &[tail.clone(), Value::I32(1), Value::I32(0x21)]).unwrap();
+    let joined = call(&mut vm, "bytes_concat", &[head, tail]).unwrap();
+    assert_eq!(joined, Value::Bytes(iris_vm::vm::gc::Gc::new(b"pro\0!".to_vec())));
+}
+
+#[test]
+fn test_bytes_errors() {
+    let mut vm = IrisVM::new();
+    bytes::register(&mut vm);
+    let buffer = call(&mut vm, "bytes_new", &[Value::I32(2)]).unwrap();
+    let error = call(&mut vm, "bytes_slice", &[buffer.clone(), Value::I32(1), Value::I32(5)]).unwrap_err();
+    assert!(matches!(error.root(), VMError::IndexOutOfBounds));
+    call(&mut vm, "bytes_set", &[buffer.clone(), Value::I32(0), Value::I32(0xFF)]).unwrap();
+    let error = call(&mut vm, "bytes_to_str", &[buffer]).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(message) if message.contains("UTF-8")));
+}
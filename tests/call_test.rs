@@ -0,0 +1,42 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn add() -> Rc<Function> {
+    Rc::new(assemble("
+        .function add 2
+        GetLocalVariable8 0
+        GetLocalVariable8 1
+        AddInt32
+        ReturnFromFunction
+    ").unwrap())
+}
+
+#[test]
+fn test_call_returns_result() {
+    let mut vm = IrisVM::new();
+    vm.stack.push(Value::Str("untouched".into()));
+    let result = vm.call(add(), &[Value::I32(40), Value::I32(2)]).unwrap();
+    assert_eq!(result, Value::I32(42));
+    assert_eq!(vm.stack, vec![Value::Str("untouched".into())]);
+
+    let err = vm.call(add(), &[Value::I32(1)]).unwrap_err();
+    assert!(matches!(err, VMError::ArityMismatch { expected: 2, found: 1 }));
+    let err = vm.call(add(), &[Value::I32(1), Value::Null]).unwrap_err();
+    assert!(matches!(err.root(), VMError::TypeMismatch(_)));
+    assert_eq!(vm.stack, vec![Value::Str("untouched".into())]);
+}
+
+#[test]
+fn test_natives_can_call_back_into_the_vm() {
+    let mut vm = IrisVM::new();
+    let apply = Function::new_native("apply".to_string(), 3, |vm, args| match args {
+        [Value::Function(callee), rest @ ..] => vm.call(callee.clone(), rest),
+        _ => Err(VMError::NonCallableValue),
+    });
+    let result = vm.call(Rc::new(apply), &[Value::Function(add()), Value::I32(5), Value::I32(6)]).unwrap();
+    assert_eq!(result, Value::I32(11));
+    assert!(vm.stack.is_empty());
+}
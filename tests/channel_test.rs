@@ -0,0 +1,107 @@
+use iris_vm::asm::assemble;
+use iris_vm::stdlib::channel;
+use iris_vm::vm::channel::Channel;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+use std::thread;
+
+fn define(vm: &mut IrisVM, source: &str) {
+    let function = assemble(source).unwrap();
+    vm.define_named_global(&function.name.clone(), Value::Function(Rc::new(function)));
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_receive_waits_for_another_fiber() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("inbox", Value::Channel(Rc::new(Channel::new())));
+    define(&mut vm, "
+        .function producer 0
+        .const done str \"done\"
+                YieldFiber
+                GetGlobalVariable8 0
+                LoadImmediateI32 7
+                ChannelSend
+                GetGlobalVariable8 0
+                PushConstant8 done
+                ChannelSend
+                PushNull
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 1
+                SpawnFiber
+                PopStack
+                GetGlobalVariable8 0
+                ChannelReceive
+                GetGlobalVariable8 0
+                ChannelReceive
+                GetGlobalVariable8 0
+                ChannelTryReceive
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(7), Value::Str("done".into()), Value::Null, Value::Bool(false)]);
+}
+
+#[test]
+fn test_vms_on_different_threads_exchange_messages() {
+    let requests = Channel::new();
+    let replies = Channel::new();
+    thread::scope(|scope| {
+        let (worker_requests, worker_replies) = (requests.clone(), replies.clone());
+        scope.spawn(move || {
+            let mut vm = IrisVM::new();
+            vm.define_named_global("requests", Value::Channel(Rc::new(worker_requests)));
+            vm.define_named_global("replies", Value::Channel(Rc::new(worker_replies)));
+            run(&mut vm, "
+                .function worker 0
+                        GetGlobalVariable8 1
+                        GetGlobalVariable8 0
+                        ChannelReceive
+                        LoadImmediateI32 2
+                        MultiplyInt32
+                        ChannelSend
+            ").unwrap();
+        });
+
+        let mut vm = IrisVM::new();
+        channel::register(&mut vm);
+        vm.define_named_global("requests", Value::Channel(Rc::new(requests.clone())));
+        vm.define_named_global("replies", Value::Channel(Rc::new(replies.clone())));
+        let send = vm.globals()[vm.global_slot("channel_send").unwrap()].clone();
+        let receive = vm.globals()[vm.global_slot("channel_receive").unwrap()].clone();
+        let (Value::Function(send), Value::Function(receive)) = (send, receive) else { panic!("natives not registered") };
+        let requests = vm.globals()[vm.global_slot("requests").unwrap()].clone();
+        let replies = vm.globals()[vm.global_slot("replies").unwrap()].clone();
+        vm.call(send, &[requests, Value::I32(21)]).unwrap();
+        assert_eq!(vm.call(receive, &[replies]).unwrap(), Value::I64(42));
+    });
+}
+
+#[test]
+fn test_channel_errors() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("inbox", Value::Channel(Rc::new(Channel::new())));
+    let error = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 1
+                CreateNewArray8 1
+                ChannelSend
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+
+    let mut vm = IrisVM::new();
+    let error = run(&mut vm, "
+        .function main 0
+                CreateChannel
+                ChannelReceive
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(_)));
+}
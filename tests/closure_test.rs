@@ -0,0 +1,104 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+/// A VM with `increment` as global 0: adds one to its first upvalue and returns it.
+fn vm_with_increment() -> IrisVM {
+    let increment = assemble("
+        .function increment 0
+                GetUpvalue 0
+                LoadImmediateI32 1
+                AddInt32
+                SetUpvalue 0
+                ReturnFromFunction
+    ").unwrap();
+    let mut vm = IrisVM::new();
+    vm.define_named_global("increment", Value::Function(Rc::new(increment)));
+    vm
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_open_upvalue_shares_the_local() {
+    let mut vm = vm_with_increment();
+    let stack = run(&mut vm, "
+        .function outer 0
+                LoadImmediateI32 10
+                GetGlobalVariable8 0
+                CaptureUpvalue 1, 0
+                GetLocalVariable8 1
+                CallFunction 0
+                PopStack
+                GetLocalVariable8 1
+                CallFunction 0
+                GetLocalVariable8 0
+    ").unwrap();
+    assert_eq!(stack[0], Value::I32(12));
+    assert_eq!(stack[2..], [Value::I32(12), Value::I32(12)]);
+}
+
+#[test]
+fn test_closed_upvalue_outlives_its_slot() {
+    let mut vm = vm_with_increment();
+    let make = assemble("
+        .function make 0
+                LoadImmediateI32 5
+                GetGlobalVariable8 0
+                CaptureUpvalue 1, 0
+                ReturnFromFunction
+    ").unwrap();
+    vm.define_named_global("make", Value::Function(Rc::new(make)));
+    let stack = run(&mut vm, "
+        .function returned 0
+                GetGlobalVariable8 1
+                CallFunction 0
+                DuplicateTop
+                CallFunction 0
+                PopStack
+                DuplicateTop
+                CallFunction 0
+    ").unwrap();
+    assert!(matches!(stack[0], Value::Closure(_)));
+    assert_eq!(stack[1], Value::I32(7));
+
+    let stack = run(&mut vm, "
+        .function closed 0
+                PushNull
+                LoadImmediateI32 1
+                GetGlobalVariable8 0
+                CaptureUpvalue 1, 1
+                SetLocalVariable8 0
+                PopStack
+                CloseUpvalue
+                LoadImmediateI32 100
+                GetLocalVariable8 0
+                CallFunction 0
+    ").unwrap();
+    assert_eq!(stack[1..], [Value::I32(100), Value::I32(2)]);
+}
+
+#[test]
+fn test_upvalue_errors() {
+    let mut vm = vm_with_increment();
+    let error = run(&mut vm, "
+        .function plain 0
+                GetGlobalVariable8 0
+                CallFunction 0
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(_)));
+
+    let mut vm = vm_with_increment();
+    let error = run(&mut vm, "
+        .function not_a_function 0
+                PushNull
+                PushNull
+                CaptureUpvalue 1, 0
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
@@ -0,0 +1,62 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::config::watch_config_native;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_config_values_are_read_only_globals() {
+    let mut vm = IrisVM::new();
+    let slot = vm.publish_config("max_speed", Value::I64(10)).unwrap();
+    assert_eq!(vm.get_global(slot).unwrap(), Value::I64(10));
+
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(99i32);
+    chunk.write(OpCode::SetGlobalVariable8); chunk.write(slot as u8);
+    let function = Rc::new(Function::new_bytecode(String::from("overwrite"), 0, chunk.code, chunk.constants));
+    vm.push_frame(function, 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::ReadOnlyGlobal(name) if name == "max_speed"));
+}
+
+#[test]
+fn test_guest_watcher_runs_at_safepoint() {
+    let mut vm = IrisVM::new();
+    let flag_slot = vm.publish_config("feature_flag", Value::Bool(false)).unwrap();
+
+    // on_change(name, value): globals[0] = value
+    let mut callback = Chunk::new();
+    callback.write(OpCode::GetLocalVariable8); callback.write(1u8);
+    callback.write(OpCode::DefineGlobalVariable8); callback.write(0u8);
+    callback.write(OpCode::PushNull);
+    callback.write(OpCode::ReturnFromFunction);
+    let callback = Function::new_bytecode(String::from("on_change"), 2, callback.code, callback.constants);
+
+    // watch_config("feature_flag", on_change)
+    let mut chunk = Chunk::new();
+    let watch = chunk.add_constant(Value::Function(Rc::new(watch_config_native())));
+    let name = chunk.add_constant(Value::Str("feature_flag".into()));
+    let on_change = chunk.add_constant(Value::Function(Rc::new(callback)));
+    chunk.write(OpCode::PushConstant8); chunk.write(watch);
+    chunk.write(OpCode::PushConstant8); chunk.write(name);
+    chunk.write(OpCode::PushConstant8); chunk.write(on_change);
+    chunk.write(OpCode::CallFunction); chunk.write(2u8);
+    let main = Rc::new(Function::new_bytecode(String::from("main"), 0, chunk.code, chunk.constants));
+    vm.push_frame(main, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack.pop(), Some(Value::Bool(true)));
+
+    vm.publish_config("feature_flag", Value::Bool(true)).unwrap();
+    assert!(vm.config().has_pending());
+
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::GetGlobalVariable8); chunk.write(flag_slot as u8);
+    let reader = Rc::new(Function::new_bytecode(String::from("reader"), 0, chunk.code, chunk.constants));
+    vm.push_frame(reader, 0).unwrap();
+    vm.run().unwrap();
+
+    assert!(!vm.config().has_pending());
+    assert_eq!(vm.get_global(0).unwrap(), Value::Bool(true));
+    assert_eq!(vm.stack, vec![Value::Bool(true)]);
+}
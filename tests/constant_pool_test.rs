@@ -0,0 +1,37 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::Chunk;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+
+#[test]
+fn test_equal_constants_share_an_index() {
+    let mut chunk = Chunk::new();
+    let hello = chunk.add_constant(Value::Str("hello".into()));
+    let answer = chunk.add_constant(Value::I64(42));
+    assert_eq!(chunk.add_constant(Value::Str("hello".into())), hello);
+    assert_eq!(chunk.add_constant(Value::I64(42)), answer);
+    assert_eq!(chunk.constants.len(), 2);
+
+    for _ in 0..1000 {
+        chunk.write_constant(Value::Str("hello".into()));
+    }
+    assert_eq!(chunk.constants.len(), 2);
+    assert_eq!(chunk.code[..2], [OpCode::PushConstant8 as u8, hello]);
+}
+
+#[test]
+fn test_distinct_constants_are_not_merged() {
+    let mut chunk = Chunk::new();
+    chunk.add_constant(Value::I64(1));
+    chunk.add_constant(Value::I32(1));
+    chunk.add_constant(Value::F64(0.0));
+    chunk.add_constant(Value::F64(-0.0));
+    chunk.add_constant(Value::F64(f64::NAN));
+    chunk.add_constant(Value::F64(f64::NAN));
+
+    let function = Rc::new(Function::new_bytecode("f".to_string(), 0, vec![], vec![]));
+    chunk.add_constant(Value::Function(function.clone()));
+    chunk.add_constant(Value::Function(function));
+    assert_eq!(chunk.constants.len(), 7);
+}
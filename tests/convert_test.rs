@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use serde::{Deserialize, Serialize};
+use iris_vm::data::convert::{from_value, to_value, ConvertError};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Shape {
+    Point,
+    Circle(f64),
+    Rect { width: u32, height: u32 },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Scene {
+    name: String,
+    shapes: Vec<Shape>,
+    origin: (i32, i32),
+    parent: Option<Box<Scene>>,
+}
+
+fn entry(value: &Value, key: &str) -> Value {
+    let Value::Map(map) = value else { panic!("expected a map, got {:?}", value) };
+    let entry = map.borrow()[key].clone();
+    entry
+}
+
+#[test]
+fn test_structs_round_trip_through_maps() {
+    let scene = Scene {
+        name: "demo".to_string(),
+        shapes: vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { width: 2, height: 3 }],
+        origin: (-1, 4),
+        parent: None,
+    };
+    let value = to_value(&scene).unwrap();
+    assert_eq!(entry(&value, "name"), Value::Str("demo".into()));
+    assert_eq!(entry(&value, "parent"), Value::Null);
+    let Value::Array(shapes) = entry(&value, "shapes") else { panic!() };
+    assert_eq!(shapes.borrow()[0], Value::Str("Point".into()));
+    assert_eq!(entry(&shapes.borrow()[1], "Circle"), Value::F64(1.5));
+    assert_eq!(entry(&entry(&shapes.borrow()[2], "Rect"), "height"), Value::U32(3));
+
+    assert_eq!(from_value::<Scene>(&value).unwrap(), scene);
+}
+
+#[test]
+fn test_vm_values_read_into_rust_types() {
+    let map = Value::Map(Gc::new(HashMap::from([
+        ("a".to_string(), Value::Tuple(Rc::from(vec![Value::I8(1), Value::I64(2)]))),
+        ("b".to_string(), Value::Int32Array(Gc::new(vec![3, 4]))),
+    ])));
+    let read: HashMap<String, Vec<i64>> = from_value(&map).unwrap();
+    assert_eq!(read, HashMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![3, 4])]));
+    assert_eq!(from_value::<f64>(&Value::I32(7)).unwrap(), 7.0);
+    assert!(from_value::<u8>(&Value::I32(300)).is_err());
+    assert_eq!(entry(&to_value(&HashMap::from([(1, true)])).unwrap(), "1"), Value::Bool(true));
+}
+
+#[test]
+fn test_runtime_values_and_cycles_fail() {
+    let function = Value::Function(Rc::new(Function::new_native("f".to_string(), 0, |_, _| Ok(Value::Null))));
+    assert_eq!(from_value::<Vec<i32>>(&function).unwrap_err(), ConvertError("A function has no data to convert".to_string()));
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Nested(Vec<Nested>);
+    let cycle = Gc::new(Vec::new());
+    cycle.borrow_mut().push(Value::Array(cycle.clone()));
+    let error = from_value::<Nested>(&Value::Array(cycle.clone())).unwrap_err();
+    assert_eq!(error.to_string(), "Value nested more than 128 deep");
+    cycle.borrow_mut().clear();
+}
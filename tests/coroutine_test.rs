@@ -0,0 +1,91 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::coroutine::CoroutineState;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn define(vm: &mut IrisVM, source: &str) {
+    let function = assemble(source).unwrap();
+    vm.define_named_global(&function.name.clone(), Value::Function(Rc::new(function)));
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_host_resumes_generator() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, "
+        .function count 0
+                LoadImmediateI32 1
+                Yield
+                PopStack
+                LoadImmediateI32 2
+                Yield
+                PopStack
+                LoadImmediateI32 3
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                CreateCoroutine
+    ").unwrap();
+    let coroutine = &stack[0];
+    for expected in 1..=3 {
+        assert_eq!(vm.resume(coroutine, Value::Null).unwrap(), Value::I32(expected));
+    }
+    match coroutine {
+        Value::Coroutine(coroutine) => assert_eq!(coroutine.borrow().state(), CoroutineState::Finished),
+        other => panic!("expected a coroutine, got {:?}", other),
+    }
+    let error = vm.resume(coroutine, Value::Null).unwrap_err();
+    assert!(matches!(error.root(), VMError::CoroutineFinished));
+}
+
+#[test]
+fn test_resume_passes_values_both_ways() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, "
+        .function accumulate 1
+                GetLocalVariable8 0
+                Yield
+                GetLocalVariable8 0
+                AddInt32
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                CreateCoroutine
+                DuplicateTop
+                LoadImmediateI32 5
+                ResumeCoroutine
+                SwapTopTwo
+                LoadImmediateI32 10
+                ResumeCoroutine
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(5), Value::I32(15)]);
+}
+
+#[test]
+fn test_coroutine_errors() {
+    let mut vm = IrisVM::new();
+    let error = run(&mut vm, "
+        .function main 0
+                PushNull
+                Yield
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(_)));
+
+    let mut vm = IrisVM::new();
+    let error = run(&mut vm, "
+        .function main 0
+                PushNull
+                CreateCoroutine
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
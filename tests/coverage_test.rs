@@ -0,0 +1,47 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::vm::IrisVM;
+
+#[test]
+fn test_coverage_marks_executed_offsets() {
+    let program = Rc::new(assemble("
+        .function main 0
+                PushFalse
+                JumpIfFalse skip
+                PushNull
+                PushNull
+        skip:   PushTrue
+    ").unwrap());
+    let mut vm = IrisVM::builder().coverage(true).build();
+    vm.push_frame(program, 0).unwrap();
+    vm.run().unwrap();
+
+    let coverage = vm.coverage().unwrap();
+    let main = coverage.function("main").unwrap();
+    assert_eq!(main.instructions(), [0, 1, 4, 5, 6]);
+    let executed: Vec<_> = main.instructions().into_iter().filter(|offset| main.is_executed(*offset)).collect();
+    assert_eq!(executed, [0, 1, 6]);
+    assert_eq!(main.executed_instructions(), 3);
+    assert_eq!(main.bitmap(), [0b100_0011]);
+}
+
+#[test]
+fn test_lcov_export_uses_line_tables() {
+    let program = Rc::new(assemble("
+        .function main 0
+        .line 1
+                PushFalse
+                JumpIfFalse done
+        .line 2
+                PushNull
+        .line 3
+        done:   PushFalse
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.set_coverage_enabled(true);
+    vm.push_frame(program, 0).unwrap();
+    vm.run().unwrap();
+
+    let lcov = vm.coverage().unwrap().to_lcov();
+    assert_eq!(lcov, "TN:\nSF:main\nFN:1,main\nFNDA:1,main\nFNF:1\nFNH:1\nDA:1,1\nDA:2,0\nDA:3,1\nLF:3\nLH:2\nend_of_record\n");
+}
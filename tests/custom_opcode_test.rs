@@ -0,0 +1,64 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::instruction_len;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::verifier::VerifyError;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_custom_opcodes_dispatch_to_host_handlers() {
+    let mut vm = IrisVM::new();
+    vm.register_custom_opcode(0xF0, (0, 1), |vm, operand| {
+        assert!(operand.is_none());
+        vm.stack.push(Value::I64(42));
+        Ok(())
+    }).unwrap();
+    vm.register_custom_opcode(0xF8, (0, 1), |vm, operand| {
+        vm.stack.push(Value::I64(operand.unwrap() as i64 * 2));
+        Ok(())
+    }).unwrap();
+
+    let mut chunk = Chunk::new();
+    chunk.write(0xF0u8);
+    chunk.write(0xF8u8); chunk.write(21u8);
+    assert_eq!(instruction_len(&chunk.code, 0), Some(1));
+    assert_eq!(instruction_len(&chunk.code, 1), Some(2));
+
+    let function = Rc::new(Function::new_bytecode(String::from("custom"), 0, chunk.code, chunk.constants));
+    vm.push_frame(function, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I64(42), Value::I64(42)]);
+}
+
+#[test]
+fn test_custom_opcode_registration_is_range_checked() {
+    let mut vm = IrisVM::new();
+    assert!(vm.register_custom_opcode(0x10, (0, 0), |_, _| Ok(())).is_err());
+    assert!(vm.register_custom_opcode(0xFF, (0, 0), |_, _| Ok(())).is_err());
+
+    let function = Rc::new(Function::new_bytecode(String::from("unregistered"), 0, vec![0xF1], Vec::new()));
+    vm.push_frame(function, 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::UnknownOpCode));
+}
+
+#[test]
+fn test_custom_opcodes_verify_with_declared_effect() {
+    let mut vm = IrisVM::new();
+    vm.set_require_verification(true);
+    vm.register_custom_opcode(0xF0, (1, 1), |vm, _| {
+        let value = vm.stack.pop().unwrap();
+        vm.stack.push(Value::Bool(matches!(value, Value::Null)));
+        Ok(())
+    }).unwrap();
+
+    let mut chunk = Chunk::new();
+    chunk.write(0xF0u8);
+    let underflow = Rc::new(Function::new_bytecode(String::from("underflow"), 0, chunk.code.clone(), Vec::new()));
+    assert!(matches!(vm.push_frame(underflow, 0), Err(VMError::VerificationFailed(VerifyError::StackUnderflow { .. }))));
+    let unregistered = Rc::new(Function::new_bytecode(String::from("unregistered"), 1, vec![0xF1], Vec::new()));
+    assert!(matches!(vm.push_frame(unregistered, 0), Err(VMError::VerificationFailed(VerifyError::UnsupportedOpCode { .. }))));
+
+    let is_null = Rc::new(Function::new_bytecode(String::from("is_null"), 1, chunk.code, chunk.constants));
+    assert_eq!(vm.call(is_null, &[Value::Null]).unwrap(), Value::Bool(true));
+}
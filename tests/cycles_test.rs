@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+#[test]
+fn test_reports_reachable_cycle_with_keys() {
+    let array = Gc::new(vec![Value::Null]);
+    let map = Gc::new(HashMap::from([("owner".to_string(), Value::Array(array.clone())), ("id".to_string(), Value::I64(1))]));
+    array.borrow_mut()[0] = Value::Map(map.clone());
+    let acyclic = Gc::new(vec![Value::Map(Gc::new(HashMap::new()))]);
+
+    let mut vm = IrisVM::new();
+    vm.define_named_global("root", Value::Array(array.clone()));
+    vm.define_named_global("plain", Value::Array(acyclic));
+    let cycles = vm.find_cycles();
+    assert_eq!(cycles.len(), 1);
+    let cycle = &cycles[0];
+    assert!(cycle.reachable);
+    assert_eq!(cycle.members.len(), 2);
+    assert_eq!(cycle.members[0].kind, "array");
+    assert_eq!(cycle.members[0].references, [("[0]".to_string(), 1)]);
+    assert_eq!(cycle.members[1].references, [(".owner".to_string(), 0)]);
+    assert!(cycle.to_string().contains("#1 map .owner -> #0 array"));
+
+    // Break the cycle so the test doesn't leak it.
+    map.borrow_mut().clear();
+}
+
+#[test]
+fn test_reports_leaked_cycles_until_collected() {
+    let program = Rc::new(assemble("
+        CreateNewArray8 0
+        DuplicateTop
+        LoadImmediateI64 0
+        GetLocalVariable8 0
+        SetArrayIndexInt32
+        PopStack
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    vm.run().unwrap();
+
+    let cycles = vm.find_cycles();
+    assert_eq!(cycles.len(), 1);
+    assert!(!cycles[0].reachable);
+    assert_eq!(cycles[0].members[0].references, [("[0]".to_string(), 0)]);
+    vm.collect_garbage();
+    assert!(vm.find_cycles().is_empty());
+}
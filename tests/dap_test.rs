@@ -0,0 +1,86 @@
+use std::io::Cursor;
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::json::Json;
+use iris_vm::debug::dap::{read_message, write_message, DapServer};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+fn request(seq: i64, command: &str, arguments: &str) -> Json {
+    Json::parse(&format!(r#"{{"seq":{},"type":"request","command":"{}","arguments":{}}}"#, seq, command, arguments)).unwrap()
+}
+
+fn events(messages: &[Json]) -> Vec<String> {
+    messages.iter().filter_map(|m| m.get("event").and_then(Json::as_str)).map(str::to_string).collect()
+}
+
+fn server() -> DapServer {
+    let program = Rc::new(assemble("
+        .function main 0
+        LoadImmediateI32 1
+        LoadImmediateI32 2
+        AddInt32
+    ").unwrap());
+    DapServer::with_program(IrisVM::new(), program)
+}
+
+#[test]
+fn test_json_round_trip() {
+    let text = r#"{"a":[1,2.5,-3e2,true,null],"b":"line\n\"quoted\" é"}"#;
+    let json = Json::parse(text).unwrap();
+    assert_eq!(json.get("b").and_then(Json::as_str), Some("line\n\"quoted\" é"));
+    assert_eq!(json.get("a").and_then(Json::as_array).unwrap()[2].as_i64(), Some(-300));
+    assert_eq!(Json::parse(&json.to_string()).unwrap(), json);
+    assert!(Json::parse("[1,]").is_err());
+}
+
+#[test]
+fn test_breakpoint_stack_and_variables() {
+    let mut dap = server();
+    assert_eq!(events(&dap.handle(&request(1, "initialize", "{}"))), ["initialized"]);
+    dap.handle(&request(2, "launch", "{}"));
+
+    // Line 4 of main's listing is the second LoadImmediateI32, at offset 5.
+    let response = &dap.handle(&request(3, "setBreakpoints", r#"{"source":{"name":"main"},"breakpoints":[{"line":4}]}"#))[0];
+    let breakpoint = &response.get("body").unwrap().get("breakpoints").and_then(Json::as_array).unwrap()[0];
+    assert_eq!(breakpoint.get("verified"), Some(&Json::Bool(true)));
+    assert_eq!(dap.vm().breakpoints().iter().collect::<Vec<_>>(), [("main", 5)]);
+
+    let messages = dap.handle(&request(4, "configurationDone", "{}"));
+    assert_eq!(messages[1].get("body").unwrap().get("reason").and_then(Json::as_str), Some("breakpoint"));
+
+    let trace = &dap.handle(&request(5, "stackTrace", r#"{"threadId":1}"#))[0];
+    let frame = &trace.get("body").unwrap().get("stackFrames").and_then(Json::as_array).unwrap()[0];
+    assert_eq!(frame.get("name").and_then(Json::as_str), Some("main"));
+    assert_eq!(frame.get("line").and_then(Json::as_i64), Some(4));
+
+    let variables = &dap.handle(&request(6, "variables", r#"{"variablesReference":1}"#))[0];
+    let locals = variables.get("body").unwrap().get("variables").and_then(Json::as_array).unwrap();
+    assert_eq!(locals.len(), 1);
+    assert_eq!(locals[0].get("value").and_then(Json::as_str), Some("I32(1)"));
+
+    assert_eq!(events(&dap.handle(&request(7, "stepIn", r#"{"threadId":1}"#))), ["stopped"]);
+    assert_eq!(events(&dap.handle(&request(8, "continue", r#"{"threadId":1}"#))), ["output", "terminated"]);
+    assert_eq!(dap.vm().stack, vec![Value::I32(3)]);
+}
+
+#[test]
+fn test_serve_over_framed_stream() {
+    let mut input = Vec::new();
+    write_message(&mut input, &request(1, "initialize", "{}")).unwrap();
+    write_message(&mut input, &request(2, "source", r#"{"sourceReference":1}"#)).unwrap();
+    write_message(&mut input, &request(3, "disconnect", "{}")).unwrap();
+    write_message(&mut input, &request(4, "threads", "{}")).unwrap();
+
+    let mut output = Vec::new();
+    server().serve(&mut Cursor::new(input), &mut output).unwrap();
+    let mut output = Cursor::new(output);
+    let mut messages = Vec::new();
+    while let Some(message) = read_message(&mut output).unwrap() {
+        messages.push(message);
+    }
+    let commands: Vec<_> = messages.iter().filter_map(|m| m.get("command").and_then(Json::as_str)).collect();
+    assert_eq!(commands, ["initialize", "source", "disconnect"]);
+    let content = messages[2].get("body").unwrap().get("content").and_then(Json::as_str).unwrap();
+    assert!(content.starts_with(".function main 0"));
+}
@@ -0,0 +1,50 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble_chunk;
+use iris_vm::vm::decoded::DecodedCode;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_decode_reads_operands_once() {
+    let chunk = assemble_chunk(r#"
+                LoadImmediateI32 -5
+                InvokeMethod16 0x0102, 3
+                JumpIfLocalsNotLessInt32 1, 2, end
+        end:
+    "#).unwrap();
+    let decoded = DecodedCode::new(&chunk.code);
+    let instructions = decoded.instructions();
+    assert_eq!(instructions.len(), 3);
+    assert_eq!(instructions[0].opcode, OpCode::LoadImmediateI32);
+    assert_eq!(instructions[0].operands(), [(-5i32) as u32 as u64]);
+    assert_eq!(instructions[1].operands(), [0x0102, 3]);
+    assert_eq!(instructions[1].next, 9);
+    assert_eq!(instructions[2].opcode, OpCode::JumpIfLocalsNotLessInt32);
+    assert_eq!(instructions[2].operands(), [1, 2, 0]);
+    assert_eq!(decoded.at(5).map(|instruction| instruction.opcode), Some(OpCode::InvokeMethod16));
+    assert!(decoded.at(6).is_none());
+}
+
+#[test]
+fn test_jump_into_an_instruction_decodes_it_there() {
+    // The jump lands on LoadImmediateI8's operand, which is PushTrue.
+    let code = vec![
+        OpCode::UnconditionalJump as u8, 1,
+        OpCode::LoadImmediateI8 as u8, OpCode::PushTrue as u8,
+        OpCode::ReturnFromFunction as u8,
+    ];
+    let function = Rc::new(Function::new_bytecode("f".to_string(), 0, code, Vec::new()));
+    let mut vm = IrisVM::new();
+    assert_eq!(vm.call(function, &[]).unwrap(), Value::Bool(true));
+}
+
+#[test]
+fn test_truncated_instruction_fails_on_its_operand() {
+    let code = vec![OpCode::PushNull as u8, OpCode::LoadImmediateI32 as u8, 0, 0];
+    let function = Rc::new(Function::new_bytecode("f".to_string(), 0, code, Vec::new()));
+    let mut vm = IrisVM::new();
+    vm.push_frame(function, 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::InvalidOperand(_)));
+}
@@ -0,0 +1,99 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::stdlib::{fs, process, random, time};
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::capability::Capability;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::register::translate_function;
+use iris_vm::vm::sandbox::FsPolicy;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn triangle() -> Function {
+    assemble("
+        .function triangle 1
+                LoadImmediateI32 0
+                LoadImmediateI32 0
+        loop:   GetLocalVariable8 2
+                GetLocalVariable8 0
+                LessOrEqualInt32
+                JumpIfFalse done
+                GetLocalVariable8 1
+                GetLocalVariable8 2
+                AddInt32
+                SetLocalVariable8 1
+                PopStack
+                GetLocalVariable8 2
+                AddInt32WithConstant 1
+                SetLocalVariable8 2
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 1
+                ReturnFromFunction
+    ").unwrap()
+}
+
+/// Fuel `triangle(20)` burns in `vm`.
+fn fuel_used(mut vm: IrisVM, function: Rc<Function>) -> u64 {
+    vm.set_fuel(Some(1_000_000));
+    assert_eq!(vm.call(function, &[Value::I32(20)]).unwrap(), Value::I32(210));
+    1_000_000 - vm.fuel().unwrap()
+}
+
+#[test]
+fn test_runs_repeat_exactly() {
+    let observe = || {
+        let mut vm = IrisVMBuilder::new().deterministic(7).build();
+        time::register(&mut vm);
+        random::register(&mut vm);
+        let mut seen = vec![call(&mut vm, "time_now", &[]).unwrap(), call(&mut vm, "time_wall", &[]).unwrap()];
+        call(&mut vm, "time_sleep", &[Value::I64(2)]).unwrap();
+        seen.push(call(&mut vm, "time_now", &[]).unwrap());
+        for _ in 0..3 {
+            seen.push(call(&mut vm, "random_f64", &[]).unwrap());
+            seen.push(call(&mut vm, "random_range", &[Value::I64(0), Value::I64(1000)]).unwrap());
+        }
+        assert!(vm.is_deterministic());
+        seen
+    };
+    let first = observe();
+    assert_eq!(first[..3], [Value::F64(0.0), Value::F64(0.0), Value::F64(2.0)]);
+    assert_eq!(first, observe());
+}
+
+#[test]
+fn test_host_dependent_natives_are_disabled() {
+    let root = std::env::temp_dir();
+    let mut vm = IrisVMBuilder::new()
+        .grant(Capability::Env)
+        .grant(Capability::Ffi)
+        .grant(Capability::Args)
+        .fs_policy(FsPolicy::new().allow_root(&root))
+        .args(["input"])
+        .deterministic(1)
+        .build();
+    process::register(&mut vm);
+    fs::register(&mut vm);
+    assert!(!vm.has_capability(Capability::Env) && !vm.has_capability(Capability::Ffi));
+    assert!(matches!(call(&mut vm, "env_get", &[Value::Str("PATH".into())]), Err(VMError::CapabilityDenied(Capability::Env))));
+    let Value::Array(args) = call(&mut vm, "process_args", &[]).unwrap() else { panic!("args are an array") };
+    assert_eq!(*args.borrow(), vec![Value::Str("input".into())]);
+    assert!(matches!(call(&mut vm, "fs_exists", &[Value::Str(".".into())]), Err(VMError::AccessDenied(_))));
+    assert!(!IrisVM::new().is_deterministic());
+}
+
+#[test]
+fn test_fuel_counts_bytecode_instructions() {
+    let mut translated = Rc::new(triangle());
+    assert!(translate_function(&mut translated).unwrap());
+    let bytecode = fuel_used(IrisVMBuilder::new().tiering(false).build(), Rc::new(triangle()));
+    assert_eq!(fuel_used(IrisVMBuilder::new().deterministic(0).build(), translated.clone()), bytecode);
+    assert_eq!(fuel_used(IrisVMBuilder::new().deterministic(0).tier_up_threshold(1).build(), Rc::new(triangle())), bytecode);
+}
@@ -12,7 +12,7 @@ use iris_vm::vm::opcode::OpCode;
 fn test_invoke_method() {
     let mut chunk = Chunk::new();
 
-    let hello_world = chunk.add_constant(Value::Str("Hello World".to_string()));
+    let hello_world = chunk.add_constant(Value::Str("Hello World".into()));
 
     chunk.write(OpCode::PushConstant8);
     chunk.write(hello_world);
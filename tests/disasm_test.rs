@@ -0,0 +1,57 @@
+use iris_vm::asm::assemble;
+use iris_vm::disasm::disassemble;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use std::rc::Rc;
+
+const COUNTDOWN: &str = r#"
+.function countdown 0
+.const greeting str "lift\"off"
+        LoadImmediateI32 3
+loop:   DuplicateTop
+        JumpIfFalse done
+        LoadImmediateI32 1
+        SubtractInt32
+        LoopJump loop
+done:   PopStack
+        PushConstant8 greeting
+        ReturnFromFunction
+"#;
+
+#[test]
+fn test_disassemble_listing() {
+    let listing = disassemble(&assemble(COUNTDOWN).unwrap());
+    let lines: Vec<&str> = listing.lines().map(str::trim_end).collect();
+    assert_eq!(lines[0], ".function countdown 0");
+    assert_eq!(lines[1], r#".const k0 str "lift\"off""#);
+    assert!(lines.contains(&"L0:     DuplicateTop                        ; 0005"));
+    assert!(lines.contains(&"        JumpIfFalse L1                      ; 0006 -> 0018"));
+    assert!(lines.contains(&"        LoopJump L0                         ; 0015 -> 0005"));
+    assert!(lines.contains(&r#"        PushConstant8 k0                    ; 0019 "lift\"off""#));
+}
+
+#[test]
+fn test_disassembly_reassembles_to_same_bytecode() {
+    let mut inner = Chunk::new();
+    inner.write(OpCode::PushNull);
+    let mut chunk = Chunk::new();
+    chunk.write_constant(Value::F64(0.1));
+    chunk.write_constant(Value::Function(Rc::new(Function::new_bytecode("inner".to_string(), 0, inner.code, inner.constants))));
+    chunk.write(OpCode::LoadImmediateI8); chunk.write(0x80u8);
+    chunk.write(OpCode::InvokeMethod8); chunk.write(1u8); chunk.write(2u8);
+    chunk.write(OpCode::BeginTryBlock); chunk.write(1u8);
+    chunk.write(OpCode::EndTryBlock);
+    chunk.write(OpCode::LookupSwitch); chunk.write(8u16); chunk.write(1u16); chunk.write(-5i32); chunk.write(11u16);
+    chunk.write(0xF8u8); chunk.write(3u8);
+    chunk.write(OpCode::ReturnFromFunction);
+    let original = Function::new_bytecode("mixed".to_string(), 2, chunk.code, chunk.constants);
+
+    let listing = disassemble(&original);
+    assert!(listing.contains("; k1 = <fn inner/0>"), "{}", listing);
+    let reassembled = assemble(&listing).unwrap_or_else(|e| panic!("{}\n{}", e, listing));
+    assert_eq!(reassembled.name, original.name);
+    assert_eq!(reassembled.arity, original.arity);
+    assert_eq!(reassembled.bytecode, original.bytecode, "{}", listing);
+}
@@ -0,0 +1,44 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::{OpCode, EXTENDED_PREFIX};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_every_opcode_has_a_handler() {
+    let primary = (0..EXTENDED_PREFIX).map(OpCode::from);
+    let extended = (0..=u8::MAX).map(OpCode::extended);
+    for opcode in primary.chain(extended).filter(|opcode| *opcode != OpCode::Unknown) {
+        assert!(IrisVM::handles(opcode), "no handler for {:?}", opcode);
+    }
+    assert!(!IrisVM::handles(OpCode::Unknown));
+}
+
+#[test]
+fn test_unassigned_extended_opcode_is_unknown() {
+    let function = Function::new_bytecode("f".to_string(), 0, vec![EXTENDED_PREFIX, 0xFE], Vec::new());
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::UnknownOpCode));
+}
+
+#[test]
+fn test_table_dispatch_runs_returns_and_jumps() {
+    let function = Rc::new(assemble(r#"
+                LoadImmediateI32 0
+                LoadImmediateI32 4
+        loop:   DuplicateTop
+                JumpIfFalse done
+                SwapTopTwo
+                AddInt32WithConstant 3
+                SwapTopTwo
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:   PopStack
+                ReturnFromFunction
+    "#).unwrap());
+    let mut vm = IrisVM::new();
+    assert_eq!(vm.call(function, &[]).unwrap(), Value::I32(12));
+}
@@ -0,0 +1,105 @@
+use iris_vm::asm::assemble;
+use iris_vm::stdlib;
+use iris_vm::vm::exception::CatchPolicy;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_runtime_errors_are_caught_by_class() {
+    let mut vm = IrisVM::new();
+    vm.set_catch_policy(CatchPolicy::all_recoverable());
+    let stack = run(&mut vm, r#"
+        .function main 0
+                BeginTryBlock outer
+                BeginTryBlock inner
+                LoadImmediateI32 1
+                LoadImmediateI32 0
+                DivideInt32
+                EndTryBlock
+                EndTryBlock
+                UnconditionalJump done
+        inner:  CatchException "IndexError"
+                EndTryBlock
+                UnconditionalJump done
+        outer:  CatchException "RuntimeError"
+                GetObjectProperty8 "message"
+        done:   NoOperation
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::Str("Division by zero".into())]);
+}
+
+#[test]
+fn test_thrown_exceptions_capture_a_stack_trace() {
+    let mut vm = IrisVM::new();
+    stdlib::errors::register(&mut vm);
+    let type_error = vm.global_slot("TypeError").unwrap();
+    let stack = run(&mut vm, &format!(r#"
+        .function main 0
+                BeginTryBlock handler
+                GetGlobalVariable8 {type_error}
+                AllocateObject 0
+                DuplicateTop
+                PushConstant8 "bad input"
+                SetObjectProperty8 "message"
+                ThrowException
+        handler: CatchException "TypeError"
+                DuplicateTop
+                GetObjectProperty8 "message"
+                SwapTopTwo
+                GetObjectProperty8 "stack_trace"
+    "#)).unwrap();
+    assert_eq!(stack[0], Value::Str("bad input".into()));
+    let Value::Str(trace) = &stack[1] else { panic!("expected a stack trace, got {:?}", stack[1]) };
+    assert!(trace.contains("in main at offset"), "{}", trace);
+}
+
+#[test]
+fn test_user_exception_classes_extend_built_in_ones() {
+    let mut vm = IrisVM::new();
+    let index_error = vm.exception_class("IndexError").unwrap();
+    assert!(vm.exception_classes().is_exception_class(&index_error));
+    let custom = Rc::new(Class::new("MissingKey".to_string(), 0, Some(index_error)));
+    vm.define_named_global("MissingKey", Value::Class(custom));
+    let error = run(&mut vm, r#"
+        .function main 0
+                BeginTryBlock handler
+                GetGlobalVariable8 0
+                AllocateObject 0
+                ThrowException
+        handler: CatchException "TypeError"
+    "#).unwrap_err();
+    let VMError::UnhandledException(Value::Object(exception)) = error.root() else { panic!("{:?}", error) };
+    assert_eq!(exception.borrow().class.name, "MissingKey");
+    assert!(exception.borrow().get_property("stack_trace").is_some_and(|trace| trace != Value::Null));
+}
+
+#[test]
+fn test_runtime_errors_in_callees_reach_the_callers_handler() {
+    let mut vm = IrisVM::new();
+    let callee = assemble(r#"
+        .function index_null 0
+                PushNull
+                LoadImmediateI64 0
+                GetArrayIndexInt32
+    "#).unwrap();
+    vm.define_named_global("index_null", Value::Function(Rc::new(callee)));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                BeginTryBlock handler
+                GetGlobalVariable8 0
+                CallFunction 0
+                EndTryBlock
+        handler: CatchException "TypeError"
+                GetObjectProperty8 "stack_trace"
+    "#).unwrap();
+    let [Value::Str(trace)] = &stack[..] else { panic!("expected a stack trace, got {:?}", stack) };
+    assert!(trace.contains("in index_null") && trace.contains("in main"), "{}", trace);
+}
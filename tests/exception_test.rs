@@ -0,0 +1,65 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::exception::CatchPolicy;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError, VMErrorKind};
+
+// try { 1 / 0 } catch { <the exception is left on the stack> }
+fn divide_by_zero_in_try() -> Rc<Function> {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::BeginTryBlock); chunk.write(14u8);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(0i32);
+    chunk.write(OpCode::DivideInt32);
+    chunk.write(OpCode::EndTryBlock);
+    chunk.write(OpCode::UnconditionalJump); chunk.write(1u8);
+    chunk.write(OpCode::NoOperation);
+    Rc::new(Function::new_bytecode(String::from("guarded_divide"), 0, chunk.code, chunk.constants))
+}
+
+#[test]
+fn test_runtime_errors_are_caught_by_default() {
+    let mut vm = IrisVM::new();
+    vm.push_frame(divide_by_zero_in_try(), 0).unwrap();
+    vm.run().unwrap();
+    assert!(matches!(&vm.stack[..], [Value::Object(_)]));
+
+    let mut vm = IrisVM::new();
+    vm.set_catch_policy(CatchPolicy::none());
+    vm.push_frame(divide_by_zero_in_try(), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::DivisionByZero));
+
+    // Outside any try block the error still aborts, unchanged.
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(0i32);
+    chunk.write(OpCode::DivideInt32);
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(Function::new_bytecode(String::from("divide"), 0, chunk.code, chunk.constants)), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::DivisionByZero));
+}
+
+#[test]
+fn test_catch_policy_bridges_allowed_kinds() {
+    let mut vm = IrisVM::new();
+    vm.set_catch_policy(CatchPolicy::none().allow(VMErrorKind::DivisionByZero));
+    vm.push_frame(divide_by_zero_in_try(), 0).unwrap();
+    vm.run().unwrap();
+    let Some(Value::Object(exception)) = vm.stack.pop() else { panic!("expected an exception object") };
+    assert_eq!(exception.borrow().class.name, "DivisionByZero");
+    assert_eq!(exception.borrow().get_property("message"), Some(Value::Str("Division by zero".into())));
+
+    let mut vm = IrisVM::new();
+    vm.set_catch_policy(CatchPolicy::all_recoverable().deny(VMErrorKind::DivisionByZero));
+    vm.push_frame(divide_by_zero_in_try(), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::DivisionByZero));
+}
+
+#[test]
+fn test_fatal_kinds_are_never_catchable() {
+    let policy = CatchPolicy::all_recoverable().allow(VMErrorKind::UnknownOpCode);
+    assert!(!policy.is_catchable(VMErrorKind::UnknownOpCode));
+    assert!(policy.is_catchable(VMErrorKind::TypeMismatch));
+}
@@ -0,0 +1,98 @@
+use std::ffi::{c_char, c_void};
+use iris_vm::vm::extension::{self, IrisCall, IrisMethod, IrisRegistrar, IrisValue, IRIS_ABI_VERSION, IRIS_INT};
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::object::Instance;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+unsafe extern "C" fn add(_: *mut IrisCall, args: *const IrisValue, _: usize, result: *mut IrisValue) -> i32 {
+    let (a, b) = (*args, *args.add(1));
+    *result = IrisValue::int(a.int + b.int);
+    0
+}
+
+unsafe extern "C" fn greet(call: *mut IrisCall, args: *const IrisValue, _: usize, result: *mut IrisValue) -> i32 {
+    let name = std::slice::from_raw_parts((*args).ptr as *const u8, (*args).len);
+    let greeting = format!("hello, {}", std::str::from_utf8(name).unwrap());
+    ((*call).new_string)(call, greeting.as_ptr() as *const c_char, greeting.len(), result);
+    0
+}
+
+unsafe extern "C" fn fail(_: *mut IrisCall, _: *const IrisValue, _: usize, result: *mut IrisValue) -> i32 {
+    *result = IrisValue::str("out of widgets");
+    1
+}
+
+/// `Counter.increment(by)`: adds `by` to the `count` property and returns it.
+unsafe extern "C" fn increment(call: *mut IrisCall, args: *const IrisValue, _: usize, result: *mut IrisValue) -> i32 {
+    let name = c"count".as_ptr();
+    let mut count = IrisValue::NULL;
+    ((*call).get_property)(call, args, name, &mut count);
+    let total = IrisValue::int(if count.tag == IRIS_INT { count.int } else { 0 } + (*args.add(1)).int);
+    ((*call).set_property)(call, args, name, &total);
+    *result = total;
+    0
+}
+
+unsafe extern "C" fn init(registrar: *mut IrisRegistrar) -> i32 {
+    if (*registrar).abi_version != IRIS_ABI_VERSION {
+        return 1;
+    }
+    let natives: [(&std::ffi::CStr, u32, extension::IrisNative); 3] = [(c"ext_add", 2, add), (c"ext_greet", 1, greet), (c"ext_fail", 0, fail)];
+    for (name, arity, native) in natives {
+        if ((*registrar).register_native)(registrar, name.as_ptr(), arity, native, std::ptr::null_mut()) != 0 {
+            return 1;
+        }
+    }
+    let properties = [c"count".as_ptr()];
+    let methods = [IrisMethod { name: c"increment".as_ptr(), arity: 1, native: increment, data: std::ptr::null_mut::<c_void>() }];
+    ((*registrar).register_class)(registrar, c"Counter".as_ptr(), properties.as_ptr(), 1, methods.as_ptr(), 1)
+}
+
+unsafe extern "C" fn refuse(_: *mut IrisRegistrar) -> i32 {
+    7
+}
+
+fn global(vm: &IrisVM, name: &str) -> Value {
+    vm.globals()[vm.global_slot(name).unwrap()].clone()
+}
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match global(vm, name) {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("expected a function, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_extension_natives() {
+    let mut vm = IrisVM::new();
+    extension::init(&mut vm, init).unwrap();
+    assert_eq!(call(&mut vm, "ext_add", &[Value::I32(2), Value::I64(40)]).unwrap(), Value::I64(42));
+    assert_eq!(call(&mut vm, "ext_greet", &[Value::Str("iris".into())]).unwrap(), Value::Str("hello, iris".into()));
+    assert!(matches!(call(&mut vm, "ext_add", &[]).unwrap_err().root(), VMError::ArityMismatch { expected: 2, found: 0 }));
+    assert!(call(&mut vm, "ext_fail", &[]).unwrap_err().to_string().contains("'ext_fail' failed: out of widgets"));
+}
+
+#[test]
+fn test_extension_classes() {
+    let mut vm = IrisVM::new();
+    extension::init(&mut vm, init).unwrap();
+    let Value::Class(class) = global(&vm, "Counter") else { panic!("expected a class") };
+    let method = class.find_method(0).unwrap();
+    assert_eq!(method.name, "increment");
+
+    let counter = Gc::new(Instance::new(class));
+    vm.call(method.clone(), &[Value::Object(counter.clone()), Value::I64(3)]).unwrap();
+    assert_eq!(vm.call(method, &[Value::Object(counter.clone()), Value::I64(4)]).unwrap(), Value::I64(7));
+    assert_eq!(counter.borrow().get_property("count"), Some(Value::I64(7)));
+}
+
+#[test]
+fn test_extension_load_failures() {
+    let mut vm = IrisVM::new();
+    assert!(vm.load_extension("/no/such/extension.so").unwrap_err().to_string().contains("Cannot open library"));
+    assert!(extension::init(&mut vm, refuse).unwrap_err().to_string().contains("status 7"));
+    #[cfg(target_os = "linux")]
+    assert!(vm.load_extension("libc.so.6").unwrap_err().to_string().contains("Cannot find 'iris_module_init'"));
+}
@@ -0,0 +1,70 @@
+use iris_vm::stdlib::ffi;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::capability::Capability;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, function: &Value, args: &[Value]) -> Result<Value, VMError> {
+    match function {
+        Value::Function(function) => vm.call(function.clone(), args),
+        other => panic!("expected a function, got {:?}", other),
+    }
+}
+
+fn declare(vm: &mut IrisVM, symbol: &str, params: &[&str], returns: &str) -> Result<Value, VMError> {
+    let declare = vm.globals()[vm.global_slot("ffi_function").unwrap()].clone();
+    let params = params.iter().map(|param| Value::Str((*param).into())).collect();
+    call(vm, &declare, &[Value::Null, Value::Str(symbol.into()), Value::Array(Gc::new(params)), Value::Str(returns.into())])
+}
+
+#[test]
+fn test_ffi_needs_the_capability() {
+    let mut vm = IrisVM::new();
+    ffi::register(&mut vm);
+    assert!(matches!(declare(&mut vm, "strlen", &["str"], "i64").unwrap_err().root(), VMError::CapabilityDenied(Capability::Ffi)));
+
+    vm.grant(Capability::Ffi);
+    assert!(vm.has_capability(Capability::Ffi));
+    vm.revoke(Capability::Ffi);
+    assert!(declare(&mut vm, "strlen", &["str"], "i64").is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn test_ffi_calls_c_functions() {
+    let mut vm = IrisVMBuilder::new().grant(Capability::Ffi).build();
+    ffi::register(&mut vm);
+
+    let strlen = declare(&mut vm, "strlen", &["str"], "i64").unwrap();
+    assert_eq!(call(&mut vm, &strlen, &[Value::Str("hello".into())]).unwrap(), Value::I64(5));
+
+    let abs = declare(&mut vm, "abs", &["i32"], "i32").unwrap();
+    assert_eq!(call(&mut vm, &abs, &[Value::I32(-12)]).unwrap(), Value::I32(12));
+
+    // Integers and doubles interleaved: ldexp(double, int).
+    let ldexp = declare(&mut vm, "ldexp", &["f64", "i32"], "f64").unwrap();
+    assert_eq!(call(&mut vm, &ldexp, &[Value::F64(1.5), Value::I32(3)]).unwrap(), Value::F64(12.0));
+
+    let strncmp = declare(&mut vm, "strncmp", &["str", "str", "i64"], "i32").unwrap();
+    assert_eq!(call(&mut vm, &strncmp, &[Value::Str("iris".into()), Value::Str("irk".into()), Value::I64(2)]).unwrap(), Value::I32(0));
+
+    let memset = declare(&mut vm, "memset", &["bytes", "i32", "i64"], "void").unwrap();
+    let buffer = Gc::new(vec![0u8; 4]);
+    call(&mut vm, &memset, &[Value::Bytes(buffer.clone()), Value::I32(7), Value::I64(3)]).unwrap();
+    assert_eq!(*buffer.borrow(), vec![7, 7, 7, 0]);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_ffi_declaration_errors() {
+    let mut vm = IrisVMBuilder::new().grant(Capability::Ffi).build();
+    ffi::register(&mut vm);
+    assert!(declare(&mut vm, "no_such_symbol_anywhere", &[], "void").unwrap_err().to_string().contains("Cannot find"));
+    assert!(declare(&mut vm, "strlen", &["char"], "i64").unwrap_err().to_string().contains("'char' is not an FFI parameter type"));
+    let error = declare(&mut vm, "strlen", &["i64"; ffi::MAX_ARGS + 1], "i64").unwrap_err();
+    assert_eq!(error.to_string(), format!("Invalid operand: 'strlen' takes more than {} argument(s)", ffi::MAX_ARGS));
+
+    let strlen = declare(&mut vm, "strlen", &["str"], "i64").unwrap();
+    assert!(matches!(call(&mut vm, &strlen, &[]).unwrap_err().root(), VMError::ArityMismatch { expected: 1, found: 0 }));
+}
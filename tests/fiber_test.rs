@@ -0,0 +1,109 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn define(vm: &mut IrisVM, source: &str) {
+    let function = assemble(source).unwrap();
+    vm.define_named_global(&function.name.clone(), Value::Function(Rc::new(function)));
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_join_returns_fiber_result() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, "
+        .function worker 0
+                LoadImmediateI32 40
+                YieldFiber
+                LoadImmediateI32 2
+                AddInt32
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                SpawnFiber
+                DuplicateTop
+                JoinFiber
+                SwapTopTwo
+                JoinFiber
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(42), Value::I32(42)]);
+}
+
+#[test]
+fn test_spinning_fiber_is_preempted() {
+    let mut vm = IrisVM::new();
+    vm.set_fiber_time_slice(10);
+    define(&mut vm, "
+        .function spin 0
+        top:    NoOperation
+                LoopJump top
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                SpawnFiber
+                PopStack
+                LoadImmediateI32 7
+                YieldFiber
+                LoadImmediateI32 8
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(7), Value::I32(8)]);
+}
+
+#[test]
+fn test_fiber_errors() {
+    let mut vm = IrisVM::new();
+    let error = run(&mut vm, "
+        .function main 0
+                PushNull
+                SpawnFiber
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+
+    let mut vm = IrisVM::new();
+    let error = run(&mut vm, "
+        .function main 0
+                PushNull
+                JoinFiber
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
+
+#[test]
+fn test_captured_locals_stay_shared_across_switches() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, "
+        .function increment 0
+                GetUpvalue 0
+                LoadImmediateI32 1
+                AddInt32
+                SetUpvalue 0
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                LoadImmediateI32 10
+                GetGlobalVariable8 0
+                CaptureUpvalue 1, 0
+                GetLocalVariable8 1
+                SpawnFiber
+                YieldFiber
+                GetLocalVariable8 0
+                LoadImmediateI32 20
+                SetLocalVariable8 0
+                PopStack
+                GetLocalVariable8 1
+                CallFunction 0
+    ").unwrap();
+    // The fiber's write reaches main's local, and main's later write reaches the closure.
+    assert_eq!(stack[3..], [Value::I32(11), Value::I32(21)]);
+    assert_eq!(stack[0], Value::I32(21));
+}
@@ -0,0 +1,92 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn define(vm: &mut IrisVM, source: &str) {
+    let function = assemble(source).unwrap();
+    vm.define_named_global(&function.name.clone(), Value::Function(Rc::new(function)));
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+const THROWER: &str = r#"
+    .function thrower 0
+            LoadImmediateI32 7
+            ThrowException
+"#;
+
+#[test]
+fn test_exceptions_unwind_through_call_frames() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, THROWER);
+    let stack = run(&mut vm, r#"
+        .function main 0
+                BeginTryBlock handler
+                GetGlobalVariable8 0
+                CallFunction 0
+                EndTryBlock
+                UnconditionalJump done
+        handler: LoadImmediateI32 1
+                AddInt32
+        done:   NoOperation
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::I32(8)]);
+    assert_eq!(vm.frames().count(), 0);
+}
+
+#[test]
+fn test_finally_runs_and_passes_the_exception_on() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, THROWER);
+    vm.define_named_global("finally_ran", Value::I32(0));
+    vm.define_named_global("caught", Value::I32(0));
+    let finally = r#"
+        .function main 0
+                BeginTryBlock outer
+                FinallyBlock finally
+                {BODY}
+                EndTryBlock
+                PushNull
+        finally: LoadImmediateI32 100
+                SetGlobalVariable8 1
+                PopStack
+                UnwindStack
+                EndTryBlock
+                UnconditionalJump done
+        outer:  SetGlobalVariable8 2
+                PopStack
+        done:   GetGlobalVariable8 1
+                GetGlobalVariable8 2
+    "#;
+    let stack = run(&mut vm, &finally.replace("{BODY}", "GetGlobalVariable8 0\nCallFunction 0")).unwrap();
+    assert_eq!(stack, vec![Value::I32(100), Value::I32(7)]);
+
+    vm.set_global(1, Value::I32(0)).unwrap();
+    vm.set_global(2, Value::I32(0)).unwrap();
+    let stack = run(&mut vm, &finally.replace("{BODY}", "NoOperation")).unwrap();
+    assert_eq!(stack, vec![Value::I32(100), Value::I32(0)]);
+}
+
+#[test]
+fn test_returning_ends_the_frames_try_blocks() {
+    let mut vm = IrisVM::new();
+    define(&mut vm, r#"
+        .function leaky 0
+                BeginTryBlock handler
+                LoadImmediateI32 1
+                ReturnFromFunction
+        handler: ReturnFromFunction
+    "#);
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                CallFunction 0
+                ThrowException
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::UnhandledException(Value::I32(1))));
+}
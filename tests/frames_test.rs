@@ -0,0 +1,42 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_frames_show_callee_and_caller() {
+    // `inner` reaches a breakpoint with its argument and one temporary on the stack.
+    let inner = Rc::new(assemble("
+        .function inner 1
+        PushTrue
+        PushNull
+        ReturnFromFunction
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    let slot = vm.define_named_global("inner", Value::Function(inner));
+    let outer = Rc::new(assemble(&format!("
+        .function outer 0
+        PushFalse
+        GetGlobalVariable8 {}
+        LoadImmediateI32 7
+        CallFunction 1
+    ", slot)).unwrap());
+    vm.add_breakpoint("inner", 1);
+    vm.push_frame(outer, 0).unwrap();
+    assert!(matches!(vm.run(), Err(VMError::Breakpoint(_))));
+
+    let frames: Vec<_> = vm.frames().collect();
+    assert_eq!(frames.len(), 2);
+    assert_eq!((frames[0].name(), frames[0].ip()), ("inner", 1));
+    assert_eq!(frames[0].locals(), [Value::I32(7), Value::Bool(true)]);
+    assert_eq!(frames[1].name(), "outer");
+    assert_eq!(frames[1].stack_base(), 0);
+    assert_eq!(frames[1].locals().first(), Some(&Value::Bool(false)));
+    assert_eq!(frames[0].stack_base(), vm.stack.len() - 2);
+}
+
+#[test]
+fn test_no_frames_when_idle() {
+    let vm = IrisVM::new();
+    assert_eq!(vm.frames().len(), 0);
+}
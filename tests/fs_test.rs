@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use iris_vm::stdlib::fs;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::sandbox::FsPolicy;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::Str(s.into())
+}
+
+/// A fresh directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("iris_fs_test_{}_{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("sub")).unwrap();
+    dir
+}
+
+fn vm(policy: FsPolicy) -> IrisVM {
+    let mut vm = IrisVMBuilder::new().fs_policy(policy).build();
+    fs::register(&mut vm);
+    vm
+}
+
+#[test]
+fn test_fs_is_disabled_by_default() {
+    let mut vm = IrisVM::new();
+    fs::register(&mut vm);
+    assert!(!vm.fs_policy().is_enabled());
+    for (name, args) in [("fs_read_file", vec![text("Cargo.toml")]), ("fs_exists", vec![text(".")]), ("fs_list_dir", vec![text(".")])] {
+        assert!(matches!(call(&mut vm, name, &args).unwrap_err().root(), VMError::AccessDenied(_)), "{}", name);
+    }
+}
+
+#[test]
+fn test_fs_reads_and_writes_inside_the_root() {
+    let dir = scratch("rw");
+    let mut vm = vm(FsPolicy::new().allow_root(&dir));
+    call(&mut vm, "fs_write_file", &[text("sub/note.txt"), text("hello")]).unwrap();
+    call(&mut vm, "fs_write_file", &[text("raw.bin"), Value::Bytes(Gc::new(vec![0, 1]))]).unwrap();
+    assert_eq!(call(&mut vm, "fs_read_file", &[text("sub/note.txt")]).unwrap(), text("hello"));
+    let absolute = dir.join("sub/note.txt").to_string_lossy().into_owned();
+    assert_eq!(call(&mut vm, "fs_exists", &[text(&absolute)]).unwrap(), Value::Bool(true));
+    assert_eq!(call(&mut vm, "fs_exists", &[text("missing/file")]).unwrap(), Value::Bool(false));
+    let Value::Array(names) = call(&mut vm, "fs_list_dir", &[text(".")]).unwrap() else { panic!("expected an array") };
+    assert_eq!(*names.borrow(), vec![text("raw.bin"), text("sub")]);
+    assert!(matches!(call(&mut vm, "fs_read_file", &[text("absent.txt")]).unwrap_err().root(), VMError::Io(_)));
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_fs_policy_keeps_access_inside_roots() {
+    let dir = scratch("policy");
+    std::fs::write(dir.join("sub/data.txt"), "data").unwrap();
+    let mut vm = vm(FsPolicy::new().allow_root(dir.join("sub")).read_only(true));
+    assert_eq!(call(&mut vm, "fs_read_file", &[text("data.txt")]).unwrap(), text("data"));
+    for path in ["../sub/../../etc", "/", "../"] {
+        assert!(matches!(call(&mut vm, "fs_list_dir", &[text(path)]).unwrap_err().root(), VMError::AccessDenied(_)), "{}", path);
+    }
+    assert!(matches!(call(&mut vm, "fs_write_file", &[text("data.txt"), text("x")]).unwrap_err().root(), VMError::AccessDenied(_)));
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink("/", dir.join("sub/escape")).unwrap();
+        assert!(matches!(call(&mut vm, "fs_list_dir", &[text("escape")]).unwrap_err().root(), VMError::AccessDenied(_)));
+
+        // A dangling link would have write_file create its target outside the root.
+        std::os::unix::fs::symlink(dir.join("outside.txt"), dir.join("sub/dangling")).unwrap();
+        let mut vm = self::vm(FsPolicy::new().allow_root(dir.join("sub")));
+        let error = call(&mut vm, "fs_write_file", &[text("dangling"), text("x")]).unwrap_err();
+        assert!(matches!(error.root(), VMError::AccessDenied(_)), "{}", error);
+        assert!(!dir.join("outside.txt").exists());
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
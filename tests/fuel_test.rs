@@ -0,0 +1,49 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError, VMErrorKind};
+
+fn countdown() -> Rc<Function> {
+    Rc::new(assemble("
+                LoadImmediateI32 3
+        loop:   DuplicateTop
+                JumpIfFalse done
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:
+    ").unwrap())
+}
+
+#[test]
+fn test_out_of_fuel_is_resumable() {
+    let mut vm = IrisVM::builder().fuel(5).build();
+    vm.push_frame(countdown(), 0).unwrap();
+    assert!(matches!(vm.run(), Err(VMError::OutOfFuel)));
+    assert_eq!(vm.fuel(), Some(0));
+
+    let mut stops = 1;
+    loop {
+        vm.add_fuel(5);
+        match vm.run() {
+            Ok(()) => break,
+            Err(VMError::OutOfFuel) => stops += 1,
+            Err(other) => panic!("unexpected error: {}", other),
+        }
+    }
+    // 1 + 5 per iteration (3 of them) + 2 for the final test = 18 instructions.
+    assert_eq!(stops, 3);
+    assert_eq!(vm.fuel(), Some(2));
+    assert_eq!(vm.stack, vec![Value::I64(0)]);
+}
+
+#[test]
+fn test_unmetered_by_default_and_fuel_is_not_catchable() {
+    let mut vm = IrisVM::new();
+    vm.add_fuel(10);
+    assert_eq!(vm.fuel(), None);
+    vm.push_frame(countdown(), 0).unwrap();
+    vm.run().unwrap();
+    assert!(VMErrorKind::OutOfFuel.is_fatal());
+}
@@ -0,0 +1,79 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::future::HostFuture;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+const AWAIT_GLOBAL: &str = "
+    .function main 0
+            GetGlobalVariable8 0
+            Await
+            LoadImmediateI32 1
+            AddInt32
+";
+
+fn start(future: HostFuture) -> IrisVM {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("future", Value::Future(Gc::new(future)));
+    vm.push_frame(Rc::new(assemble(AWAIT_GLOBAL).unwrap()), 0).unwrap();
+    vm
+}
+
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+/// Completes with `value` on its second poll.
+async fn later(value: Value) -> Result<Value, Value> {
+    let mut polled = false;
+    std::future::poll_fn(|cx| {
+        if polled {
+            return Poll::Ready(());
+        }
+        polled = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }).await;
+    Ok(value)
+}
+
+#[test]
+fn test_await_completed_future() {
+    let mut vm = start(HostFuture::ready(Ok(Value::I32(41))));
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(42)]);
+
+    let mut vm = start(HostFuture::ready(Err(Value::Str("refused".into()))));
+    let error = vm.run().unwrap_err();
+    assert!(matches!(error.root(), VMError::UnhandledException(Value::Str(message)) if &**message == "refused"));
+}
+
+#[test]
+fn test_pending_future_suspends_run() {
+    let mut vm = start(HostFuture::new(later(Value::I32(1))));
+    let error = vm.run().unwrap_err();
+    assert!(matches!(error.root(), VMError::Pending));
+    match vm.get_global(0).unwrap() {
+        Value::Future(future) => future.borrow_mut().complete(Ok(Value::I32(9))),
+        other => panic!("expected a future, got {:?}", other),
+    }
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(10)]);
+}
+
+#[test]
+fn test_run_async_waits_for_future() {
+    let mut vm = start(HostFuture::new(later(Value::I32(99))));
+    block_on(vm.run_async()).unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(100)]);
+}
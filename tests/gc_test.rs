@@ -0,0 +1,58 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+/// Builds `count` arrays that each contain themselves, keeping the last one in global 0
+/// when `keep` is set.
+fn cycles(count: i32, keep: bool) -> Rc<iris_vm::vm::function::Function> {
+    let keep = if keep { "DuplicateTop\nSetGlobalVariable8 0\nPopStack" } else { "" };
+    Rc::new(assemble(&format!("
+                PushNull
+                DefineGlobalVariable8 0
+                LoadImmediateI32 {}
+        loop:   DuplicateTop
+                JumpIfFalse done
+                CreateNewArray8 0
+                DuplicateTop
+                LoadImmediateI64 0
+                GetLocalVariable8 1
+                SetArrayIndexInt32
+                {}
+                PopStack
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:
+    ", count, keep)).unwrap())
+}
+
+#[test]
+fn test_collect_frees_unreachable_cycles() {
+    let mut vm = IrisVM::new();
+    vm.push_frame(cycles(3, true), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.heap().live_objects(), 3);
+
+    assert_eq!(vm.collect_garbage(), 2);
+    assert_eq!(vm.heap().live_objects(), 1);
+    let Value::Array(kept) = vm.get_global(0).unwrap() else { panic!("expected an array") };
+    assert!(matches!(&kept.borrow()[0], Value::Array(inner) if Gc::ptr_eq(inner, &kept)));
+
+    // A handle held by the embedder is a root too.
+    vm.define_global(0, Value::Null);
+    assert_eq!(vm.collect_garbage(), 0);
+    drop(kept);
+    assert_eq!(vm.collect_garbage(), 1);
+    assert_eq!(vm.heap().live_objects(), 0);
+}
+
+#[test]
+fn test_allocation_pressure_triggers_collection() {
+    let mut vm = IrisVM::new();
+    vm.push_frame(cycles(25_000, false), 0).unwrap();
+    vm.run().unwrap();
+    assert!(vm.heap().collections() >= 2);
+    assert!(vm.heap().live_objects() < 10_000);
+}
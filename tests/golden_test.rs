@@ -0,0 +1,295 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use iris_vm::data::bytecode::{decode_function, encode_function, BYTECODE_VERSION};
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::VMError;
+
+// Golden fixtures live in `tests/golden/<format version>/`. The directory for the
+// current format is compared byte-for-byte against freshly encoded functions; older
+// directories are kept around so we keep proving that files written by earlier
+// releases still load. Run with `IRIS_BLESS=1` to regenerate the current fixtures.
+const CURRENT_FORMAT_DIR: &str = "v1";
+const LEGACY_FORMAT_DIRS: &[&str] = &["v0"];
+
+fn golden_dir(version: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(version)
+}
+
+fn function(name: &str, arity: usize, chunk: Chunk) -> Function {
+    Function::new_bytecode(name.to_string(), arity, chunk.code, chunk.constants)
+}
+
+fn stack_ops() -> Function {
+    let mut chunk = Chunk::new();
+    let hello = chunk.add_constant(Value::Str("hello".into()));
+    chunk.write(OpCode::PushConstant8); chunk.write(hello);
+    chunk.write(OpCode::PushNull);
+    chunk.write(OpCode::PushTrue);
+    chunk.write(OpCode::PushFalse);
+    chunk.write(OpCode::SwapTopTwo);
+    chunk.write(OpCode::RotateTopThree);
+    chunk.write(OpCode::DuplicateTop);
+    chunk.write(OpCode::PeekStack); chunk.write(1u8);
+    chunk.write(OpCode::DropMultiple); chunk.write(5u8);
+    chunk.write(OpCode::PopStack);
+    function("stack_ops", 0, chunk)
+}
+
+fn immediates() -> Function {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::LoadImmediateI8); chunk.write(0xFEu8);
+    chunk.write(OpCode::LoadImmediateI16); chunk.write(0x1234u16);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(-123_456i32);
+    chunk.write(OpCode::LoadImmediateI64);
+    for b in i64::MIN.to_be_bytes() { chunk.write(b); }
+    chunk.write(OpCode::LoadImmediateF32);
+    for b in 1.5f32.to_be_bytes() { chunk.write(b); }
+    chunk.write(OpCode::LoadImmediateF64);
+    for b in std::f64::consts::PI.to_be_bytes() { chunk.write(b); }
+    function("immediates", 0, chunk)
+}
+
+fn variables() -> Function {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::GetLocalVariable8); chunk.write(0u8);
+    chunk.write(OpCode::SetLocalVariable8); chunk.write(1u8);
+    chunk.write(OpCode::GetLocalVariable16); chunk.write(300u16);
+    chunk.write(OpCode::SetLocalVariable16); chunk.write(301u16);
+    chunk.write(OpCode::DefineGlobalVariable8); chunk.write(0u8);
+    chunk.write(OpCode::GetGlobalVariable8); chunk.write(0u8);
+    chunk.write(OpCode::SetGlobalVariable8); chunk.write(0u8);
+    function("variables", 2, chunk)
+}
+
+fn arithmetic() -> Function {
+    let mut chunk = Chunk::new();
+    chunk.write_constant(Value::I64(40));
+    chunk.write_constant(Value::I64(2));
+    for op in [
+        OpCode::AddInt32, OpCode::SubtractInt32, OpCode::MultiplyInt32, OpCode::DivideInt32,
+        OpCode::ModuloInt32, OpCode::NegateInt32, OpCode::AddFloat64, OpCode::SquareRootFloat64,
+    ] {
+        chunk.write(op);
+    }
+    function("arithmetic", 0, chunk)
+}
+
+fn comparison_and_logic() -> Function {
+    let mut chunk = Chunk::new();
+    for op in [
+        OpCode::EqualInt32, OpCode::NotEqualInt32, OpCode::GreaterThanInt32, OpCode::LessThanInt32,
+        OpCode::GreaterOrEqualInt32, OpCode::LessOrEqualInt32, OpCode::LogicalNotOperation,
+        OpCode::LogicalAndOperation, OpCode::LogicalOrOperation,
+    ] {
+        chunk.write(op);
+    }
+    function("comparison_and_logic", 0, chunk)
+}
+
+fn bitwise() -> Function {
+    let mut chunk = Chunk::new();
+    for op in [
+        OpCode::BitwiseAndInt32, OpCode::BitwiseOrInt32, OpCode::BitwiseXorInt32, OpCode::BitwiseNotInt32,
+        OpCode::LeftShiftInt32, OpCode::RightShiftInt32, OpCode::UnsignedRightShiftInt64,
+    ] {
+        chunk.write(op);
+    }
+    function("bitwise", 0, chunk)
+}
+
+fn conversions() -> Function {
+    let mut chunk = Chunk::new();
+    for op in [
+        OpCode::ConvertInt32ToInt64, OpCode::ConvertInt64ToFloat64, OpCode::ConvertFloat64ToFloat32,
+        OpCode::ConvertFloat32ToInt32,
+    ] {
+        chunk.write(op);
+    }
+    function("conversions", 0, chunk)
+}
+
+fn control_flow() -> Function {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::PushTrue);
+    chunk.write(OpCode::JumpIfFalse); chunk.write(3u16);
+    chunk.write(OpCode::UnconditionalJump); chunk.write(1u8);
+    chunk.write(OpCode::NoOperation);
+    chunk.write(OpCode::LoopJump); chunk.write(0u16);
+    chunk.write(OpCode::BeginTryBlock); chunk.write(2u8);
+    chunk.write(OpCode::EndTryBlock);
+    chunk.write(OpCode::PushNull);
+    chunk.write(OpCode::ReturnFromFunction);
+    function("control_flow", 0, chunk)
+}
+
+fn data_structures() -> Function {
+    let mut chunk = Chunk::new();
+    let key = chunk.add_constant(Value::Str("key".into()));
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(2i32);
+    chunk.write(OpCode::CreateNewArray8); chunk.write(2u8);
+    chunk.write(OpCode::PushConstant8); chunk.write(key);
+    chunk.write(OpCode::PushNull);
+    chunk.write(OpCode::CreateNewMap8); chunk.write(1u8);
+    chunk.write(OpCode::GetObjectField8); chunk.write(key);
+    chunk.write(OpCode::GetArrayIndexInt32);
+    function("data_structures", 0, chunk)
+}
+
+fn classes() -> Function {
+    let mut method_chunk = Chunk::new();
+    method_chunk.write(OpCode::GetLocalVariable8); method_chunk.write(0u8);
+    method_chunk.write(OpCode::ReturnFromFunction);
+
+    let mut base = Class::new("Base".to_string(), 1, None);
+    base.add_method(0, Rc::new(function("describe", 1, method_chunk)));
+    base.properties.insert("id".to_string(), 0);
+    let derived = Class::new("Derived".to_string(), 2, Some(Rc::new(base)));
+
+    let mut chunk = Chunk::new();
+    let name = chunk.add_constant(Value::Str("Derived".into()));
+    let class = chunk.add_constant(Value::Class(Rc::new(derived)));
+    chunk.write(OpCode::DefineClass8); chunk.write(name);
+    chunk.write(OpCode::PushConstant8); chunk.write(class);
+    chunk.write(OpCode::CreateNewInstance);
+    chunk.write(OpCode::InvokeMethod8); chunk.write(0u8); chunk.write(0u8);
+    function("classes", 0, chunk)
+}
+
+fn nested_functions() -> Function {
+    let mut inner_chunk = Chunk::new();
+    inner_chunk.write(OpCode::LoadImmediateI32); inner_chunk.write(7i32);
+    inner_chunk.write(OpCode::ReturnFromFunction);
+    let inner = function("inner", 0, inner_chunk);
+
+    let mut middle_chunk = Chunk::new();
+    let inner_index = middle_chunk.add_constant(Value::Function(Rc::new(inner)));
+    middle_chunk.write(OpCode::PushConstant8); middle_chunk.write(inner_index);
+    middle_chunk.write(OpCode::CallFunction); middle_chunk.write(0u8);
+    middle_chunk.write(OpCode::ReturnFromFunction);
+    let middle = function("middle", 0, middle_chunk);
+
+    let mut chunk = Chunk::new();
+    let middle_index = chunk.add_constant(Value::Function(Rc::new(middle)));
+    chunk.write(OpCode::PushConstant8); chunk.write(middle_index);
+    chunk.write(OpCode::CallFunction); chunk.write(0u8);
+    chunk.write(OpCode::PrintTopOfStack);
+    function("nested_functions", 0, chunk)
+}
+
+fn big_pool() -> Function {
+    let mut chunk = Chunk::new();
+    for i in 0..300i64 {
+        match i % 4 {
+            0 => chunk.write_constant(Value::I64(i)),
+            1 => chunk.write_constant(Value::F64(i as f64 / 4.0)),
+            2 => chunk.write_constant(Value::Str(format!("constant_{}", i).into())),
+            _ => chunk.write_constant(Value::U32(i as u32)),
+        }
+    }
+    chunk.write(OpCode::DropMultiple); chunk.write(255u8);
+    function("big_pool", 0, chunk)
+}
+
+fn all_constant_types() -> Function {
+    let mut chunk = Chunk::new();
+    for value in [
+        Value::Null, Value::Bool(true), Value::I8(-8), Value::I16(-16), Value::I32(-32), Value::I64(-64),
+        Value::I128(-128), Value::U8(8), Value::U16(16), Value::U32(32), Value::U64(64), Value::U128(128),
+        Value::F32(0.5), Value::F64(-0.25), Value::Str("".into()),
+    ] {
+        chunk.write_constant(value);
+    }
+    function("all_constant_types", 0, chunk)
+}
+
+fn fixtures() -> Vec<Function> {
+    vec![
+        stack_ops(),
+        immediates(),
+        variables(),
+        arithmetic(),
+        comparison_and_logic(),
+        bitwise(),
+        conversions(),
+        control_flow(),
+        data_structures(),
+        classes(),
+        nested_functions(),
+        big_pool(),
+        all_constant_types(),
+    ]
+}
+
+#[test]
+fn test_golden_files_match_encoder() {
+    let dir = golden_dir(CURRENT_FORMAT_DIR);
+    let bless = std::env::var_os("IRIS_BLESS").is_some();
+    if bless {
+        std::fs::create_dir_all(&dir).unwrap();
+    }
+
+    for function in fixtures() {
+        let path = dir.join(format!("{}.ic", function.name));
+        let encoded = encode_function(&function).unwrap();
+        if bless {
+            std::fs::write(&path, &encoded).unwrap();
+            continue;
+        }
+        let golden = std::fs::read(&path)
+            .unwrap_or_else(|e| panic!("missing golden file {}: {} (run with IRIS_BLESS=1)", path.display(), e));
+        assert_eq!(encoded, golden, "encoding of '{}' no longer matches {}", function.name, path.display());
+    }
+}
+
+#[test]
+fn test_golden_files_round_trip() {
+    let dir = golden_dir(CURRENT_FORMAT_DIR);
+    for expected in fixtures() {
+        let path = dir.join(format!("{}.ic", expected.name));
+        let golden = std::fs::read(&path).unwrap();
+
+        let loaded = decode_function(&golden).unwrap();
+        assert_eq!(loaded.name, expected.name);
+        assert_eq!(loaded.arity, expected.arity);
+        assert_eq!(loaded.bytecode, expected.bytecode);
+        assert_eq!(format!("{:?}", loaded.constants), format!("{:?}", expected.constants));
+
+        let re_encoded = encode_function(&loaded).unwrap();
+        assert_eq!(re_encoded, golden, "save -> load -> save of '{}' is not stable", expected.name);
+    }
+}
+
+#[test]
+fn test_legacy_golden_files_still_load() {
+    for version in LEGACY_FORMAT_DIRS {
+        let dir = golden_dir(version);
+        for expected in fixtures() {
+            let path = dir.join(format!("{}.ic", expected.name));
+            let golden = std::fs::read(&path).unwrap();
+            let loaded = decode_function(&golden)
+                .unwrap_or_else(|e| panic!("{} ({}) no longer loads: {}", expected.name, version, e));
+            assert_eq!(loaded.name, expected.name);
+            assert_eq!(loaded.arity, expected.arity);
+            assert_eq!(loaded.bytecode, expected.bytecode);
+            assert_eq!(format!("{:?}", loaded.constants), format!("{:?}", expected.constants));
+        }
+    }
+}
+
+#[test]
+fn test_newer_format_versions_are_rejected() {
+    let mut encoded = encode_function(&stack_ops()).unwrap();
+    encoded[4..6].copy_from_slice(&(BYTECODE_VERSION + 1).to_be_bytes());
+    let err = decode_function(&encoded).unwrap_err();
+    assert!(matches!(err.downcast_ref::<VMError>(), Some(VMError::BytecodeVersionError { .. })));
+
+    let mut encoded = encode_function(&stack_ops()).unwrap();
+    encoded[6..8].copy_from_slice(&0x8000u16.to_be_bytes());
+    let err = decode_function(&encoded).unwrap_err();
+    assert!(err.to_string().contains("flags 0x8000"), "{}", err);
+}
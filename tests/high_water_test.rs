@@ -0,0 +1,80 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{HighWater, IrisVM, VMError};
+
+// Counts its argument down to zero, calling itself through global 0.
+fn count_down() -> Rc<Function> {
+    Rc::new(assemble("
+        .function count_down 1
+        .const minus_one i32 -1
+                GetLocalVariable8 0
+                LoadImmediateI32 0
+                EqualInt32
+                JumpIfFalse recurse
+                LoadImmediateI32 0
+                ReturnFromFunction
+        recurse: GetGlobalVariable8 0
+                GetLocalVariable8 0
+                PushConstant8 minus_one
+                AddInt32
+                CallFunction 1
+                ReturnFromFunction
+    ").unwrap())
+}
+
+fn vm_with(builder: IrisVMBuilder, function: &Rc<Function>) -> IrisVM {
+    builder.global("count_down", Value::Function(function.clone())).build()
+}
+
+#[test]
+fn test_high_water_tracks_deepest_recursion() {
+    let function = count_down();
+    let mut vm = vm_with(IrisVM::builder(), &function);
+    assert_eq!(vm.call(function.clone(), &[Value::I32(10)]).unwrap(), Value::I32(0));
+    let high_water = vm.high_water();
+    assert_eq!(high_water.frames, 11);
+    assert!(high_water.stack >= 11);
+
+    vm.reset_high_water();
+    assert_eq!(vm.high_water(), HighWater::default());
+    vm.call(function, &[Value::I32(3)]).unwrap();
+    assert_eq!(vm.high_water().frames, 4);
+}
+
+#[test]
+fn test_preallocated_stack_is_not_reallocated() {
+    let function = count_down();
+    let mut vm = vm_with(IrisVM::builder().initial_stack_capacity(256).initial_frame_capacity(64), &function);
+    let (stack, capacity) = (vm.stack.as_ptr(), vm.stack.capacity());
+    assert!(capacity >= 256);
+
+    vm.call(function, &[Value::I32(50)]).unwrap();
+    assert!(vm.high_water().stack > 50);
+    assert_eq!(vm.stack.as_ptr(), stack);
+    assert_eq!(vm.stack.capacity(), capacity);
+}
+
+#[test]
+fn test_failing_native_leaves_the_stack_usable() {
+    let mut vm = IrisVM::builder()
+        .native("fail", |_| Err(VMError::TypeMismatch("nope".to_string())))
+        .native("sum", |args| Ok(Value::I64(args.iter().map(|arg| if let Value::I64(x) = arg { *x } else { 0 }).sum())))
+        .build();
+    let call = |slot: usize| Rc::new(assemble(&format!("
+        GetGlobalVariable8 {}
+        LoadImmediateI64 2
+        LoadImmediateI64 3
+        CallFunction 2
+        ReturnFromFunction
+    ", slot)).unwrap());
+
+    let err = vm.call(call(vm.global_slot("fail").unwrap()), &[]).unwrap_err();
+    assert!(matches!(err.root(), VMError::TypeMismatch(_)));
+    vm.reset();
+    for _ in 0..3 {
+        assert_eq!(vm.call(call(vm.global_slot("sum").unwrap()), &[]).unwrap(), Value::I64(5));
+    }
+}
@@ -0,0 +1,137 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::module::{save_module, Module};
+use iris_vm::disasm::disassemble;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::import::{FsResolver, MemoryResolver, ModuleResolver};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::verifier::verify;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn geometry() -> Module {
+    let mut module = Module::new("geometry".to_string());
+    module.add_function(assemble("
+        .function area 2
+                GetLocalVariable8 0
+                GetLocalVariable8 1
+                MultiplyInt32
+                ReturnFromFunction
+    ").unwrap());
+    // Reaches area() through its own module rather than a global slot.
+    module.add_function(assemble(r#"
+        .function square 1
+                LoadModule "geometry"
+                ImportSymbol "area"
+                GetLocalVariable8 0
+                GetLocalVariable8 0
+                CallFunction 2
+                ReturnFromFunction
+    "#).unwrap());
+    module
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0)?;
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+const MAIN: &str = r#"
+    .function main 0
+            LoadModule "geometry"
+            ImportSymbol "square"
+            LoadImmediateI32 7
+            CallFunction 1
+"#;
+
+#[test]
+fn test_import_from_memory() {
+    let resolved = Rc::new(Cell::new(0));
+    let counter = resolved.clone();
+    let memory = MemoryResolver::new().with(geometry());
+    let mut vm = IrisVMBuilder::new()
+        .module_resolver(move |name: &str| {
+            counter.set(counter.get() + 1);
+            memory.resolve(name)
+        })
+        .build();
+    assert!(!vm.is_module_loaded("geometry"));
+    assert_eq!(run(&mut vm, MAIN).unwrap(), vec![Value::I64(49)]);
+    assert_eq!(run(&mut vm, MAIN).unwrap(), vec![Value::I64(49)]);
+    assert!(vm.is_module_loaded("geometry"));
+    assert_eq!(resolved.get(), 1);
+    assert!(vm.global_slot("square").is_none());
+
+    vm.clear_module_cache();
+    run(&mut vm, MAIN).unwrap();
+    assert_eq!(resolved.get(), 2);
+
+    let function = Rc::new(assemble(MAIN).unwrap());
+    verify(&function).unwrap();
+    let listing = disassemble(&function);
+    assert!(listing.contains("LoadModule") && listing.contains("\"geometry\""), "{}", listing);
+}
+
+#[test]
+fn test_import_from_files() {
+    let root = std::env::temp_dir().join(format!("iris_import_test_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("shapes")).unwrap();
+    let mut module = geometry();
+    module.name = "shapes.geometry".to_string();
+    save_module(&module, &root.join("shapes/geometry.icm").to_string_lossy()).unwrap();
+
+    let mut vm = IrisVM::new();
+    vm.add_module_resolver(|_: &str| Ok(None));
+    vm.add_module_resolver(FsResolver::new(&root));
+    let Value::Map(exports) = vm.import_module("shapes.geometry").unwrap() else { panic!("expected a map") };
+    let mut names: Vec<String> = exports.borrow().keys().cloned().collect();
+    names.sort();
+    assert_eq!(names, ["area", "square"]);
+    assert!(matches!(vm.import_module("shapes.circle").unwrap_err(), VMError::Import(_)));
+    assert!(matches!(vm.import_module("../shapes").unwrap_err(), VMError::Import(message) if message.contains("not a valid module name")));
+    std::fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_import_errors() {
+    let mut vm = IrisVMBuilder::new().module_resolver(MemoryResolver::new().with(geometry())).build();
+    let error = run(&mut vm, r#"
+        .function main 0
+                LoadModule "missing"
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::Import(message) if message.contains("'missing'")));
+    let error = run(&mut vm, r#"
+        .function main 0
+                LoadModule "geometry"
+                ImportSymbol "volume"
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::Import(message) if message.contains("'volume'")));
+    assert!(error.kind() == iris_vm::vm::vm::VMErrorKind::Import && !error.kind().is_fatal());
+}
+
+#[test]
+fn test_imported_functions_reach_siblings_by_slot() {
+    let mut module = Module::new("twice".to_string());
+    module.add_function(assemble(".function double 1\nGetLocalVariable8 0\nGetLocalVariable8 0\nAddInt32\nReturnFromFunction").unwrap());
+    // double() is slot 0 of its module, wherever the importing VM puts it.
+    module.add_function(assemble(r#"
+        .function quadruple 1
+                GetGlobalVariable8 0
+                GetGlobalVariable8 0
+                GetLocalVariable8 0
+                CallFunction 1
+                CallFunction 1
+                ReturnFromFunction
+    "#).unwrap());
+    let mut vm = IrisVMBuilder::new().module_resolver(MemoryResolver::new().with(module)).build();
+    vm.register_native("native", |_| Ok(Value::Null));
+    assert_eq!(run(&mut vm, r#"
+        .function main 0
+                LoadModule "twice"
+                ImportSymbol "quadruple"
+                LoadImmediateI32 5
+                CallFunction 1
+    "#).unwrap(), vec![Value::I32(20)]);
+    assert!(vm.global_slot("double").is_none());
+}
@@ -0,0 +1,100 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::inline_cache::CacheState;
+use iris_vm::vm::object::{Class, Instance};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+/// A class whose `kind` method returns `kind`.
+fn class(name: &str, kind: i32) -> Rc<Class> {
+    let mut class = Class::new(name.to_string(), 0, None);
+    class.methods.push(Rc::new(Function::new_native("kind".to_string(), 0, move |_, _| Ok(Value::I32(kind)))));
+    class.properties.insert("x".to_string(), 0);
+    Rc::new(class)
+}
+
+fn instance(class: &Rc<Class>) -> Value {
+    Value::Object(Gc::new(Instance::new(class.clone())))
+}
+
+const CALL_KIND: &str = "
+    .function call_kind 1
+            GetLocalVariable8 0
+            CallWithInlineCache \"kind\", 0
+            ReturnFromFunction
+";
+
+#[test]
+fn test_call_site_goes_polymorphic_then_megamorphic() {
+    let mut vm = IrisVM::new();
+    let call_kind = Rc::new(assemble(CALL_KIND).unwrap());
+    assert_eq!(vm.inline_cache_state(&call_kind, 2), CacheState::Uninitialized);
+    let classes: Vec<Rc<Class>> = (0..6).map(|kind| class(&format!("C{}", kind), kind)).collect();
+    let mut states = Vec::new();
+    for (kind, class) in classes.iter().enumerate() {
+        assert_eq!(vm.call(call_kind.clone(), &[instance(class)]).unwrap(), Value::I32(kind as i32));
+        states.push(vm.inline_cache_state(&call_kind, 2));
+    }
+    assert_eq!(states, vec![
+        CacheState::Monomorphic,
+        CacheState::Polymorphic,
+        CacheState::Polymorphic,
+        CacheState::Polymorphic,
+        CacheState::Megamorphic,
+        CacheState::Megamorphic,
+    ]);
+    assert_eq!(vm.call(call_kind.clone(), &[instance(&classes[0])]).unwrap(), Value::I32(0));
+
+    let megamorphic = Rc::new(assemble("
+        .function call_kind 1
+                GetLocalVariable8 0
+                MegamorphicMethodCall \"kind\", 0
+                ReturnFromFunction
+    ").unwrap());
+    assert_eq!(vm.call(megamorphic, &[instance(&classes[3])]).unwrap(), Value::I32(3));
+}
+
+#[test]
+fn test_properties_resolve_by_name_through_superclasses() {
+    let mut vm = IrisVM::new();
+    let base = class("Base", 0);
+    let derived = Rc::new(Class::new("Derived".to_string(), 1, Some(base.clone())));
+    let access = Rc::new(assemble("
+        .function access 1
+                GetLocalVariable8 0
+                LoadImmediateI32 7
+                SetPropertyWithInlineCache \"x\"
+                GetLocalVariable8 0
+                GetPropertyWithInlineCache \"x\"
+                ReturnFromFunction
+    ").unwrap());
+    assert_eq!(vm.call(access.clone(), &[instance(&base)]).unwrap(), Value::I32(7));
+    assert_eq!(vm.call(access.clone(), &[instance(&derived)]).unwrap(), Value::I32(7));
+    assert_eq!(vm.inline_cache_state(&access, 7), CacheState::Polymorphic);
+
+    let missing = Rc::new(assemble("
+        .function missing 1
+                GetLocalVariable8 0
+                GetPropertyWithInlineCache \"y\"
+                ReturnFromFunction
+    ").unwrap());
+    let error = vm.call(missing, &[instance(&base)]).unwrap_err();
+    assert!(matches!(error.root(), VMError::UndefinedProperty(_)));
+}
+
+#[test]
+fn test_redefining_a_class_invalidates_its_entries() {
+    let mut vm = IrisVM::new();
+    let old = class("Shape", 1);
+    vm.define_named_global("Shape", Value::Class(old.clone()));
+    let call_kind = Rc::new(assemble(CALL_KIND).unwrap());
+    assert_eq!(vm.call(call_kind.clone(), &[instance(&old)]).unwrap(), Value::I32(1));
+    assert_eq!(vm.inline_cache_state(&call_kind, 2), CacheState::Monomorphic);
+
+    let new = class("Shape", 2);
+    vm.define_named_global("Shape", Value::Class(new.clone()));
+    assert_eq!(vm.inline_cache_state(&call_kind, 2), CacheState::Uninitialized);
+    assert_eq!(vm.call(call_kind.clone(), &[instance(&new)]).unwrap(), Value::I32(2));
+}
@@ -0,0 +1,54 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::intern::{intern, interned_count, purge};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+#[test]
+fn test_intern_shares_allocation() {
+    let a = intern("shared");
+    let b = intern(&String::from("shared"));
+    assert!(Rc::ptr_eq(&a, &b));
+    assert!(!Rc::ptr_eq(&a, &intern("other")));
+}
+
+#[test]
+fn test_concatenation_is_interned() {
+    let function = assemble(r#"
+        .function greet 0
+                PushConstant8 "hello "
+                PushConstant8 "world"
+                AddInt32
+                ReturnFromFunction
+    "#).unwrap();
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    vm.run().unwrap();
+    match &vm.stack[..] {
+        [Value::Str(joined)] => {
+            assert_eq!(&**joined, "hello world");
+            assert!(Rc::ptr_eq(joined, &intern("hello world")));
+        }
+        other => panic!("unexpected stack {:?}", other),
+    }
+}
+
+#[test]
+fn test_add_int32_wraps_and_rejects_mixed_operands() {
+    let mut vm = IrisVM::new();
+    let add = Rc::new(assemble(".function add 2\nGetLocalVariable8 0\nGetLocalVariable8 1\nAddInt32\nReturnFromFunction").unwrap());
+    assert_eq!(vm.call(add.clone(), &[Value::I32(i32::MAX), Value::I32(1)]).unwrap(), Value::I32(i32::MIN));
+    let error = vm.call(add, &[Value::Str(intern("a")), Value::I32(1)]).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(message) if message.contains("both be Str")), "{}", error);
+}
+
+#[test]
+fn test_purge_drops_unused_strings() {
+    let kept = intern("kept by the test");
+    drop(intern("dropped by the test"));
+    let before = interned_count();
+    assert!(purge() >= 1);
+    assert!(interned_count() < before);
+    assert!(Rc::ptr_eq(&kept, &intern("kept by the test")));
+}
@@ -0,0 +1,41 @@
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError, VMErrorKind};
+
+#[test]
+fn test_interrupt_from_another_thread() {
+    let spin = Rc::new(assemble("
+        top:    LoopJump top
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    let handle = vm.interrupt_handle();
+    vm.push_frame(spin, 0).unwrap();
+
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+    assert!(matches!(vm.run(), Err(VMError::Interrupted)));
+    interrupter.join().unwrap();
+    assert!(!vm.interrupt_handle().is_interrupted());
+    assert!(VMErrorKind::Interrupted.is_fatal());
+}
+
+#[test]
+fn test_interrupted_run_resumes() {
+    let program = Rc::new(assemble("
+        LoadImmediateI32 1
+        LoadImmediateI32 2
+        AddInt32
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    vm.interrupt_handle().interrupt();
+    assert!(matches!(vm.run(), Err(VMError::Interrupted)));
+    assert!(vm.stack.is_empty());
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(3)]);
+}
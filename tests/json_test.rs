@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use iris_vm::data::json::Json;
+use iris_vm::stdlib::json;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn vm() -> IrisVM {
+    let mut vm = IrisVM::new();
+    json::register(&mut vm);
+    vm
+}
+
+#[test]
+fn test_json_parse_builds_maps_and_arrays() {
+    let mut vm = vm();
+    let parsed = call(&mut vm, "json_parse", &[Value::Str(r#"{"name": "iris", "tags": [1, 2.5, null, true]}"#.into())]).unwrap();
+    let Value::Map(map) = parsed else { panic!("expected a map, got {:?}", parsed) };
+    assert_eq!(map.borrow()["name"], Value::Str("iris".into()));
+    let Value::Array(tags) = map.borrow()["tags"].clone() else { panic!() };
+    assert_eq!(*tags.borrow(), vec![Value::I64(1), Value::F64(2.5), Value::Null, Value::Bool(true)]);
+
+    let error = call(&mut vm, "json_parse", &[Value::Str("[1,".into())]).unwrap_err();
+    assert_eq!(error.root().to_string(), "Invalid operand: Invalid JSON: unexpected end of input at byte 3");
+}
+
+#[test]
+fn test_json_stringify_compact_and_pretty() {
+    let mut vm = vm();
+    let value = Value::Map(Gc::new(HashMap::from([
+        ("b".to_string(), Value::Array(Gc::new(vec![Value::I32(1), Value::Char('x')]))),
+        ("a".to_string(), Value::F64(0.5)),
+        ("c".to_string(), Value::Array(Gc::new(Vec::new()))),
+    ])));
+    let compact = call(&mut vm, "json_stringify", std::slice::from_ref(&value)).unwrap();
+    assert_eq!(compact, Value::Str(r#"{"a":0.5,"b":[1,"x"],"c":[]}"#.into()));
+    let pretty = call(&mut vm, "json_stringify", &[value, Value::I32(2)]).unwrap();
+    assert_eq!(pretty, Value::Str("{\n  \"a\": 0.5,\n  \"b\": [\n    1,\n    \"x\"\n  ],\n  \"c\": []\n}".into()));
+}
+
+#[test]
+fn test_json_max_depth_stops_deep_and_cyclic_values() {
+    let mut vm = vm();
+    let nested = Value::Str("[[[1]]]".into());
+    assert!(call(&mut vm, "json_parse", &[nested.clone(), Value::I32(3)]).is_ok());
+    assert!(call(&mut vm, "json_parse", &[nested, Value::I32(2)]).is_err());
+    assert!(Json::parse(&"[".repeat(100_000)).is_err());
+
+    let cycle = Gc::new(Vec::new());
+    cycle.borrow_mut().push(Value::Array(cycle.clone()));
+    let error = call(&mut vm, "json_stringify", &[Value::Array(cycle.clone()), Value::Null, Value::I32(10)]).unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(message) if message.contains("nested too deeply")));
+    cycle.borrow_mut().clear();
+}
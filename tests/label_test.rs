@@ -0,0 +1,102 @@
+use std::rc::Rc;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter, JumpError};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::{successors, OpCode};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+#[test]
+fn test_labels_drive_a_loop() {
+    let mut chunk = Chunk::new();
+    let top = chunk.create_label();
+    let done = chunk.create_label();
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(3i32);
+    chunk.bind_label(top).unwrap();
+    chunk.write(OpCode::DuplicateTop);
+    chunk.emit_jump(OpCode::JumpIfFalse, done).unwrap();
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::SubtractInt32);
+    chunk.emit_jump(OpCode::LoopJump, top).unwrap();
+    chunk.bind_label(done).unwrap();
+    chunk.write(OpCode::ReturnFromFunction);
+
+    let function = Rc::new(Function::new_bytecode("countdown".to_string(), 0, chunk.code, chunk.constants));
+    let mut vm = IrisVM::new();
+    vm.push_frame(function, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I64(0)]);
+}
+
+#[test]
+fn test_jumps_pick_encoding_by_distance() {
+    let mut chunk = Chunk::new();
+    let back = chunk.create_label();
+    chunk.bind_label(back).unwrap();
+    chunk.write(OpCode::NoOperation);
+    chunk.emit_jump(OpCode::UnconditionalJump, back).unwrap();
+    assert_eq!(chunk.code[1..], [OpCode::ShortJump as u8, (-3i8) as u8]);
+
+    let near = chunk.create_label();
+    chunk.emit_jump(OpCode::ShortJump, near).unwrap();
+    chunk.write(OpCode::NoOperation);
+    chunk.bind_label(near).unwrap();
+    assert_eq!(chunk.code[3..5], [OpCode::UnconditionalJump as u8, 1]);
+
+    let far = chunk.create_label();
+    let start = chunk.code.len();
+    chunk.emit_jump(OpCode::UnconditionalJump, far).unwrap();
+    for _ in 0..300 {
+        chunk.write(OpCode::NoOperation);
+    }
+    chunk.bind_label(far).unwrap();
+    assert_eq!(chunk.code[start..start + 4], [OpCode::PushFalse as u8, OpCode::JumpIfFalse as u8, 1, 44]);
+
+    let at = chunk.code.len();
+    chunk.emit_jump(OpCode::ShortJump, back).unwrap();
+    assert_eq!(chunk.code[at], OpCode::LoopJump as u8);
+    assert_eq!(successors(&chunk.code, at), Some(vec![0]));
+}
+
+#[test]
+fn test_growing_jump_shifts_other_jumps() {
+    let mut chunk = Chunk::new();
+    let top = chunk.create_label();
+    let far = chunk.create_label();
+    chunk.bind_label(top).unwrap();
+    chunk.emit_jump(OpCode::UnconditionalJump, far).unwrap();
+    for _ in 0..124 {
+        chunk.write(OpCode::NoOperation);
+    }
+    // Fits a ShortJump until the forward jump above grows to four bytes.
+    let back = chunk.code.len();
+    chunk.emit_jump(OpCode::ShortJump, top).unwrap();
+    for _ in 0..200 {
+        chunk.write(OpCode::NoOperation);
+    }
+    chunk.bind_label(far).unwrap();
+    chunk.write(OpCode::ReturnFromFunction);
+
+    let back = back + 2;
+    assert_eq!(chunk.code[back], OpCode::LoopJump as u8);
+    assert_eq!(successors(&chunk.code, back), Some(vec![0]));
+    assert_eq!(successors(&chunk.code, 0), Some(vec![1]));
+    assert_eq!(successors(&chunk.code, 1), Some(vec![4, chunk.code.len() - 1]));
+}
+
+#[test]
+fn test_unreachable_jumps_are_errors() {
+    let mut chunk = Chunk::new();
+    let top = chunk.create_label();
+    chunk.bind_label(top).unwrap();
+    assert_eq!(chunk.bind_label(top), Err(JumpError::LabelBoundTwice));
+    assert_eq!(chunk.emit_jump(OpCode::JumpIfFalse, top), Err(JumpError::Backward(OpCode::JumpIfFalse)));
+    assert_eq!(chunk.emit_jump(OpCode::AddInt32, top), Err(JumpError::NotAJump(OpCode::AddInt32)));
+    assert!(chunk.code.is_empty());
+
+    let handler = chunk.create_label();
+    chunk.emit_jump(OpCode::BeginTryBlock, handler).unwrap();
+    for _ in 0..300 {
+        chunk.write(OpCode::NoOperation);
+    }
+    assert_eq!(chunk.bind_label(handler), Err(JumpError::OutOfRange { opcode: OpCode::BeginTryBlock, at: 0, target: 302 }));
+}
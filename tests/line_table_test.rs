@@ -0,0 +1,79 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::bytecode::{decode_function, encode_function, FLAG_LINE_TABLES};
+use iris_vm::debug::lines::Span;
+use iris_vm::disasm::disassemble;
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_runtime_error_reports_source_line() {
+    let mut chunk = Chunk::new();
+    chunk.set_line(41);
+    chunk.write(OpCode::LoadImmediateI32); chunk.write(1i32);
+    chunk.write(OpCode::PushTrue);
+    chunk.set_line(42);
+    chunk.write(OpCode::AddInt32);
+    chunk.write(OpCode::ReturnFromFunction);
+    let function = Function::new_bytecode("test_func".to_string(), 0, chunk.code, chunk.constants)
+        .with_lines(chunk.lines);
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.root(), VMError::TypeMismatch(_)));
+    assert_eq!(err.location().unwrap().offset, 6);
+    assert!(err.to_string().ends_with("in test_func at line 42"), "{}", err);
+}
+
+#[test]
+fn test_line_tables_survive_encoding() {
+    let mut inner = Chunk::new();
+    inner.set_span(Span { line: 7, column: 3 });
+    inner.write(OpCode::PushNull);
+    inner.write(OpCode::ReturnFromFunction);
+    let inner = Function::new_bytecode("inner".to_string(), 0, inner.code, inner.constants).with_lines(inner.lines);
+
+    let mut outer = Chunk::new();
+    outer.write_constant(Value::Function(Rc::new(inner)));
+    outer.set_line(2);
+    outer.write(OpCode::ReturnFromFunction);
+    let outer = Function::new_bytecode("outer".to_string(), 0, outer.code, outer.constants).with_lines(outer.lines);
+
+    let encoded = encode_function(&outer).unwrap();
+    assert_eq!(u16::from_be_bytes([encoded[6], encoded[7]]), FLAG_LINE_TABLES);
+    let decoded = decode_function(&encoded).unwrap();
+    assert_eq!(decoded.lines.line_at(0), None);
+    assert_eq!(decoded.lines.line_at(2), Some(2));
+    let Value::Function(inner) = &decoded.constants[0] else { panic!("expected a function constant") };
+    assert_eq!(inner.lines.span_at(1), Some(Span { line: 7, column: 3 }));
+
+    let plain = Function::new_bytecode("plain".to_string(), 0, vec![OpCode::PushNull as u8], Vec::new());
+    assert_eq!(encode_function(&plain).unwrap()[6..8], [0, 0]);
+}
+
+#[test]
+fn test_line_directives_round_trip_through_disassembly() {
+    let function = assemble(r#"
+        .function lines 0
+        .line 10
+                LoadImmediateI32 3
+        loop:   DuplicateTop
+                JumpIfFalse done
+        .line 11 5
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:   ReturnFromFunction
+    "#).unwrap();
+    assert_eq!(function.lines.line_at(5), Some(10));
+    assert_eq!(function.lines.span_at(9), Some(Span { line: 11, column: 5 }));
+
+    let listing = disassemble(&function);
+    assert!(listing.contains(".line 11 5"), "{}", listing);
+    let reassembled = assemble(&listing).unwrap();
+    assert_eq!(reassembled.lines, function.lines);
+}
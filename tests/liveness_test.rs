@@ -0,0 +1,43 @@
+use iris_vm::debug::liveness::{LiveRange, LivenessTable};
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::opcode::OpCode;
+
+#[test]
+fn test_liveness_ranges_straight_line() {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::GetLocalVariable8); chunk.write(0u8);   // 0: read argument
+    chunk.write(OpCode::SetLocalVariable8); chunk.write(1u8);   // 2: x = arg
+    chunk.write(OpCode::PopStack);                              // 4
+    chunk.write(OpCode::GetLocalVariable8); chunk.write(1u8);   // 5: read x
+    chunk.write(OpCode::PrintTopOfStack);                       // 7
+    chunk.write(OpCode::PushNull);                              // 8
+
+    let table = LivenessTable::compute_code(&chunk.code).unwrap();
+    assert_eq!(table.ranges(), &[
+        LiveRange { slot: 0, start: 0, end: 2 },
+        LiveRange { slot: 1, start: 4, end: 7 },
+    ]);
+    assert_eq!(table.live_locals_at(0), vec![0]);
+    assert!(table.live_locals_at(2).is_empty());
+    assert_eq!(table.live_locals_at(5), vec![1]);
+    assert!(table.live_locals_at(8).is_empty());
+}
+
+#[test]
+fn test_liveness_follows_loop_back_edges() {
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::GetLocalVariable8); chunk.write(0u8);   // 0: loop head reads counter
+    chunk.write(OpCode::JumpIfFalse); chunk.write(7u16);        // 2: exit to 12
+    chunk.write(OpCode::GetLocalVariable8); chunk.write(1u8);   // 5
+    chunk.write(OpCode::PopStack);                              // 7
+    chunk.write(OpCode::LoopJump); chunk.write(11u16);          // 8: back to 0
+    chunk.write(OpCode::PushNull);                              // 11
+    chunk.write(OpCode::ReturnFromFunction);                    // 12
+
+    let table = LivenessTable::compute_code(&chunk.code).unwrap();
+    // Both slots are read on every iteration, so they stay live across the back edge.
+    assert!(table.is_live(0, 8));
+    assert!(table.is_live(1, 8));
+    assert!(table.is_live(1, 2));
+    assert!(!table.is_live(0, 12));
+}
@@ -0,0 +1,57 @@
+use iris_vm::stdlib::math;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn vm() -> IrisVM {
+    let mut vm = IrisVM::new();
+    math::register(&mut vm);
+    vm
+}
+
+fn member(vm: &IrisVM, name: &str) -> Value {
+    let Value::Map(math) = vm.globals()[vm.global_slot("math").unwrap()].clone() else { panic!("math is not a map") };
+    let member = math.borrow()[name].clone();
+    member
+}
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match member(vm, name) {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("math.{} is not a function: {:?}", name, other),
+    }
+}
+
+#[test]
+fn test_math_functions_and_constants() {
+    let mut vm = vm();
+    assert_eq!(member(&vm, "PI"), Value::F64(std::f64::consts::PI));
+    assert_eq!(member(&vm, "E"), Value::F64(std::f64::consts::E));
+    assert_eq!(call(&mut vm, "sin", &[Value::I32(0)]).unwrap(), Value::F64(0.0));
+    assert_eq!(call(&mut vm, "cos", &[Value::F64(0.0)]).unwrap(), Value::F64(1.0));
+    assert_eq!(call(&mut vm, "exp", &[Value::I64(0)]).unwrap(), Value::F64(1.0));
+    let e = member(&vm, "E");
+    assert_eq!(call(&mut vm, "log", &[e]).unwrap(), Value::F64(1.0));
+    assert_eq!(call(&mut vm, "pow", &[Value::I32(2), Value::F64(10.0)]).unwrap(), Value::F64(1024.0));
+    assert_eq!(call(&mut vm, "atan2", &[Value::I32(1), Value::I32(1)]).unwrap(), Value::F64(std::f64::consts::FRAC_PI_4));
+    assert!(matches!(call(&mut vm, "tan", &[]).unwrap_err().root(), VMError::ArityMismatch { expected: 1, found: 0 }));
+}
+
+#[test]
+fn test_min_max_keep_integers() {
+    let mut vm = vm();
+    assert_eq!(call(&mut vm, "min", &[Value::I32(3), Value::I32(-2), Value::I32(7)]).unwrap(), Value::I32(-2));
+    assert_eq!(call(&mut vm, "max", &[Value::I32(3), Value::I64(9)]).unwrap(), Value::I64(9));
+    assert_eq!(call(&mut vm, "max", &[Value::I32(3), Value::F64(2.5)]).unwrap(), Value::F64(3.0));
+    assert_eq!(call(&mut vm, "min", &[Value::U8(4)]).unwrap(), Value::U8(4));
+    assert!(call(&mut vm, "min", &[]).is_err());
+    assert!(call(&mut vm, "max", &[Value::Str("a".into())]).is_err());
+}
+
+#[test]
+fn test_clamp() {
+    let mut vm = vm();
+    assert_eq!(call(&mut vm, "clamp", &[Value::I32(15), Value::I32(0), Value::I32(10)]).unwrap(), Value::I32(10));
+    assert_eq!(call(&mut vm, "clamp", &[Value::I32(-1), Value::I32(0), Value::I32(10)]).unwrap(), Value::I32(0));
+    assert_eq!(call(&mut vm, "clamp", &[Value::F64(0.5), Value::I32(0), Value::I32(1)]).unwrap(), Value::F64(0.5));
+    assert!(call(&mut vm, "clamp", &[Value::I32(1), Value::I32(5), Value::I32(0)]).is_err());
+}
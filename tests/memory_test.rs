@@ -0,0 +1,82 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::exception::CatchPolicy;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_live_allocations_hit_the_heap_limit() {
+    // Keeps growing the array in global 0, one slot per iteration.
+    let grow = Rc::new(assemble(r#"
+                CreateNewArray8 0
+                DefineGlobalVariable8 0
+                LoadImmediateI64 0
+        loop:   GetGlobalVariable8 0
+                GetLocalVariable8 0
+                PushConstant8 "item"
+                SetArrayIndexInt32
+                GetLocalVariable8 0
+                LoadImmediateI64 -1
+                SubtractInt32
+                SetLocalVariable8 0
+                PopStack
+                LoopJump loop
+    "#).unwrap());
+
+    let mut vm = IrisVM::builder().max_heap_bytes(4096).build();
+    vm.push_frame(grow, 0).unwrap();
+    let err = vm.run().unwrap_err();
+    assert!(matches!(err.root(), VMError::OutOfMemory { limit: 4096 }));
+    assert!(vm.heap_usage() > 2048);
+}
+
+#[test]
+fn test_garbage_is_recounted_and_errors_are_catchable() {
+    let churn = Rc::new(assemble("
+                LoadImmediateI32 200
+        loop:   DuplicateTop
+                JumpIfFalse done
+                CreateNewArray8 0
+                PopStack
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:
+    ").unwrap());
+    let mut vm = IrisVM::builder().max_heap_bytes(256).build();
+    vm.push_frame(churn, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.recount_heap(), 0);
+
+    let hog = Rc::new(assemble("
+                BeginTryBlock handler
+                CreateNewArray8 0
+                LoadImmediateI64 1000
+                PushNull
+                SetArrayIndexInt32
+                EndTryBlock
+        handler:
+    ").unwrap());
+    let mut vm = IrisVM::builder().max_heap_bytes(256).catch_policy(CatchPolicy::all_recoverable()).build();
+    vm.push_frame(hog, 0).unwrap();
+    vm.run().unwrap();
+    let [Value::Object(exception)] = &vm.stack[..] else { panic!("expected an exception, got {:?}", vm.stack) };
+    assert!(matches!(exception.borrow().get_property("message"), Some(Value::Str(message)) if message.contains("Heap limit")));
+}
+
+#[test]
+fn test_array_store_rejects_negative_and_huge_indexes() {
+    let store = |index: i64| Rc::new(assemble(&format!("
+                CreateNewArray8 0
+                LoadImmediateI64 {index}
+                PushNull
+                SetArrayIndexInt32
+    ")).unwrap());
+    let mut vm = IrisVM::builder().max_heap_bytes(4096).build();
+    vm.push_frame(store(-1), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::IndexOutOfBounds));
+
+    let mut vm = IrisVM::builder().max_heap_bytes(4096).build();
+    vm.push_frame(store(i64::MAX), 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::OutOfMemory { limit: 4096 }));
+}
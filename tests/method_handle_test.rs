@@ -0,0 +1,84 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn define_point(vm: &mut IrisVM) {
+    let mut base = Class::new("Base".to_string(), 0, None);
+    base.methods.push(Rc::new(assemble(r#"
+        .function scaled 2
+                GetLocalVariable8 0
+                GetObjectProperty8 "x"
+                GetLocalVariable8 1
+                MultiplyInt32
+                ReturnFromFunction
+    "#).unwrap()));
+    let point = Class::new("Point".to_string(), 1, Some(Rc::new(base)));
+    vm.define_named_global("Point", Value::Class(Rc::new(point)));
+}
+
+const NEW_POINT: &str = r#"
+                GetGlobalVariable8 0
+                CreateNewInstance
+                DuplicateTop
+                LoadImmediateI32 5
+                SetObjectProperty8 "x"
+"#;
+
+#[test]
+fn test_bound_method_is_called_with_its_receiver() {
+    let mut vm = IrisVM::new();
+    define_point(&mut vm);
+    let stack = run(&mut vm, &format!(r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadMethodHandle "scaled"
+                {NEW_POINT}
+                BindMethodHandle
+                LoadImmediateI32 3
+                CallFunction 1
+    "#)).unwrap();
+    assert_eq!(stack, vec![Value::I64(15)]);
+}
+
+#[test]
+fn test_unbound_handle_takes_the_receiver_first() {
+    let mut vm = IrisVM::new();
+    define_point(&mut vm);
+    let stack = run(&mut vm, &format!(r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadMethodHandle "scaled"
+                {NEW_POINT}
+                LoadImmediateI32 2
+                CallFunction 2
+    "#)).unwrap();
+    assert_eq!(stack, vec![Value::I64(10)]);
+}
+
+#[test]
+fn test_method_handle_errors() {
+    let mut vm = IrisVM::new();
+    define_point(&mut vm);
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadMethodHandle "missing"
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::MethodNotFound(_)));
+
+    let error = run(&mut vm, r#"
+        .function main 0
+                LoadImmediateI32 1
+                PushNull
+                BindMethodHandle
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::NonCallableValue));
+}
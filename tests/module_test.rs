@@ -0,0 +1,80 @@
+use std::rc::Rc;
+use iris_vm::data::module::{decode_module, encode_module, load_module, save_module, Module};
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn program() -> Module {
+    let mut module = Module::new(String::from("program"));
+
+    let mut answer = Chunk::new();
+    answer.write(OpCode::LoadImmediateI32); answer.write(42i32);
+    answer.write(OpCode::ReturnFromFunction);
+    module.add_function(Function::new_bytecode(String::from("answer"), 0, answer.code, answer.constants));
+
+    // main() calls answer() through its global slot (slot 0 on a fresh VM).
+    let mut main = Chunk::new();
+    main.write(OpCode::GetGlobalVariable8); main.write(0u8);
+    main.write(OpCode::CallFunction); main.write(0u8);
+    main.write(OpCode::ReturnFromFunction);
+    module.add_function(Function::new_bytecode(String::from("main"), 0, main.code, main.constants));
+
+    module.set_entry_point("main");
+    module
+}
+
+#[test]
+fn test_module_file_round_trip() {
+    save_module(&program(), "program.icm").unwrap();
+    let loaded = load_module("program.icm").unwrap();
+    std::fs::remove_file("program.icm").unwrap();
+
+    assert_eq!(loaded.name, "program");
+    assert_eq!(loaded.functions.len(), 2);
+    assert_eq!(loaded.entry().unwrap().name, "main");
+    assert_eq!(loaded.function("answer").unwrap().bytecode, program().function("answer").unwrap().bytecode);
+}
+
+#[test]
+fn test_module_rejects_missing_entry_point() {
+    let mut module = program();
+    module.set_entry_point("missing");
+    let encoded = encode_module(&module).unwrap();
+    assert!(decode_module(&encoded).is_err());
+}
+
+#[test]
+fn test_vm_load_module_registers_globals() {
+    let module = program();
+    let mut vm = IrisVM::new();
+    vm.load_module(&module).unwrap();
+    assert_eq!(vm.global_slot("main"), Some(1));
+
+    vm.push_frame(module.entry().unwrap(), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(42)]);
+    assert!(matches!(vm.get_global(0).unwrap(), Value::Function(f) if Rc::ptr_eq(&f, &module.functions[0])));
+}
+
+#[test]
+fn test_vm_load_module_relocates_after_natives() {
+    let mut vm = IrisVM::new();
+    vm.register_native("native", |_| Ok(Value::Null));
+    let first = vm.load_module(&program()).unwrap();
+    let second = vm.load_module(&program()).unwrap();
+    assert_eq!(vm.global_slot("answer"), Some(3));
+    assert_eq!(vm.global_slot("main"), Some(4));
+
+    for loaded in [first, second] {
+        assert_eq!(vm.call(loaded.entry().unwrap(), &[]).unwrap(), Value::I32(42));
+    }
+    let mut far = Module::new(String::from("far"));
+    far.add_function(Function::new_bytecode(String::from("own"), 0, vec![OpCode::GetGlobalVariable8 as u8, 0], Vec::new()));
+    // Free slots from 256 on can't be named by a one-byte operand.
+    for slot in 5..256 {
+        vm.define_global(slot, Value::Bool(true));
+    }
+    assert!(matches!(vm.load_module(&far), Err(VMError::Import(_))));
+}
@@ -0,0 +1,127 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::object::{Class, Instance};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn object() -> Value {
+    Value::Object(Gc::new(Instance::new(Rc::new(Class::new("Lock".to_string(), 0, None)))))
+}
+
+fn define(vm: &mut IrisVM, source: &str) {
+    let function = assemble(source).unwrap();
+    vm.define_named_global(&function.name.clone(), Value::Function(Rc::new(function)));
+}
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_monitor_is_reentrant() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("lock", object());
+    run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                EnterMonitor
+                GetGlobalVariable8 0
+                EnterMonitor
+                GetGlobalVariable8 0
+                ExitMonitor
+                GetGlobalVariable8 0
+                ExitMonitor
+    ").unwrap();
+
+    let error = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 0
+                ExitMonitor
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(_)));
+}
+
+#[test]
+fn test_monitor_excludes_other_fibers() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("lock", object());
+    vm.define_named_global("counter", Value::Int32Array(Gc::new(vec![0])));
+    // Reads the counter, lets the other fibers run, then writes it back incremented.
+    define(&mut vm, "
+        .function worker 0
+                GetGlobalVariable8 0
+                EnterMonitor
+                GetGlobalVariable8 1
+                LoadImmediateI32 0
+                GetGlobalVariable8 1
+                LoadImmediateI32 0
+                TypedArrayGet
+                YieldFiber
+                LoadImmediateI32 1
+                AddInt32
+                TypedArraySet
+                GetGlobalVariable8 0
+                ExitMonitor
+                PushNull
+                ReturnFromFunction
+    ");
+    let stack = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 2
+                SpawnFiber
+                GetGlobalVariable8 2
+                SpawnFiber
+                JoinFiber
+                PopStack
+                JoinFiber
+                PopStack
+                GetGlobalVariable8 1
+                LoadImmediateI32 0
+                TypedArrayGet
+    ").unwrap();
+    assert_eq!(stack, vec![Value::I32(2)]);
+}
+
+#[test]
+fn test_deadlock_is_detected() {
+    let mut vm = IrisVMBuilder::new()
+        .deadlock_detection(true)
+        .global("first", object())
+        .global("second", object())
+        .build();
+    define(&mut vm, "
+        .function forward 0
+                GetGlobalVariable8 0
+                EnterMonitor
+                YieldFiber
+                GetGlobalVariable8 1
+                EnterMonitor
+                PushNull
+                ReturnFromFunction
+    ");
+    define(&mut vm, "
+        .function backward 0
+                GetGlobalVariable8 1
+                EnterMonitor
+                YieldFiber
+                GetGlobalVariable8 0
+                EnterMonitor
+                PushNull
+                ReturnFromFunction
+    ");
+    let error = run(&mut vm, "
+        .function main 0
+                GetGlobalVariable8 2
+                SpawnFiber
+                GetGlobalVariable8 3
+                SpawnFiber
+                JoinFiber
+                SwapTopTwo
+                JoinFiber
+    ").unwrap_err();
+    assert!(matches!(error.root(), VMError::Deadlock { fibers: 2 }));
+}
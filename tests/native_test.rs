@@ -0,0 +1,75 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::exception::CatchPolicy;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0)?;
+    vm.run()?;
+    Ok(vm.stack.clone())
+}
+
+#[test]
+fn test_register_native_receives_arguments() {
+    let mut vm = IrisVM::new();
+    let slot = vm.register_native("count_args", |args| Ok(Value::I64(args.len() as i64)));
+    assert_eq!(vm.global_slot("count_args"), Some(slot));
+
+    let stack = run(&mut vm, &format!("
+        GetGlobalVariable8 {}
+        PushNull
+        PushTrue
+        LoadImmediateI8 7
+        CallFunction 3
+        ReturnFromFunction
+    ", slot)).unwrap();
+    assert_eq!(stack, vec![Value::I64(3)]);
+}
+
+#[test]
+fn test_typed_natives_convert_arguments() {
+    let mut vm = IrisVM::new();
+    let scale = vm.register_typed_native("scale", |x: i64, factor: f64| Ok(x as f64 * factor));
+    let shout = vm.register_typed_native("shout", |text: String| Ok(text.to_uppercase()));
+
+    let stack = run(&mut vm, &format!(r#"
+        GetGlobalVariable8 {scale}
+        LoadImmediateI32 4
+        LoadImmediateF64 0.5
+        CallFunction 2
+        GetGlobalVariable8 {shout}
+        PushConstant8 "hi"
+        CallFunction 1
+    "#)).unwrap();
+    assert_eq!(stack, vec![Value::F64(2.0), Value::Str("HI".into())]);
+
+    vm.reset();
+    let err = run(&mut vm, &format!("GetGlobalVariable8 {shout}\nPushTrue\nPushTrue\nCallFunction 2")).unwrap_err();
+    assert!(matches!(err.root(), VMError::ArityMismatch { expected: 1, found: 2 }));
+}
+
+#[test]
+fn test_native_errors_are_catchable() {
+    let mut vm = IrisVM::new();
+    vm.set_catch_policy(CatchPolicy::all_recoverable());
+    let fail = vm.register_native("fail", |_| Err(VMError::InvalidOperand("bad input".to_string())));
+    let check = vm.register_typed_native("check", |flag: bool| Ok(flag));
+
+    let err = run(&mut vm, &format!("GetGlobalVariable8 {fail}\nCallFunction 0")).unwrap_err();
+    assert!(matches!(err.root(), VMError::InvalidOperand(_)));
+
+    vm.reset();
+    let stack = run(&mut vm, &format!("
+                BeginTryBlock handler
+                GetGlobalVariable8 {check}
+                LoadImmediateI8 1
+                CallFunction 1
+                EndTryBlock
+        handler:
+    ")).unwrap();
+    assert_eq!(stack.len(), 1);
+    let Value::Object(exception) = &stack[0] else { panic!("expected an exception, got {:?}", stack[0]) };
+    let message = exception.borrow().get_property("message");
+    assert!(matches!(&message, Some(Value::Str(text)) if text.contains("boolean")), "{:?}", message);
+}
@@ -0,0 +1,104 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn class_with(name: &str, methods: &[&str]) -> Value {
+    let mut class = Class::new(name.to_string(), 0, None);
+    class.properties.insert("x".to_string(), 0);
+    class.properties.insert("y".to_string(), 1);
+    class.methods = methods.iter().map(|source| Rc::new(assemble(source).unwrap())).collect();
+    Value::Class(Rc::new(class))
+}
+
+#[test]
+fn test_allocate_object_runs_init_with_arguments() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Point", class_with("Point", &[r#"
+        .function init 3
+                GetLocalVariable8 0
+                GetLocalVariable8 1
+                SetObjectProperty8 "x"
+                GetLocalVariable8 0
+                GetLocalVariable8 2
+                SetObjectProperty8 "y"
+                PushNull
+                ReturnFromFunction
+    "#]));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 3
+                LoadImmediateI32 4
+                AllocateObject 2
+                DuplicateTop
+                GetObjectProperty8 "x"
+                SwapTopTwo
+                GetObjectProperty8 "y"
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::I32(3), Value::I32(4)]);
+
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 3
+                AllocateObject 1
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::ArityMismatch { expected: 3, found: 2 }));
+}
+
+#[test]
+fn test_free_object_drops_properties() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Point", class_with("Point", &[]));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                AllocateObject 0
+                DuplicateTop
+                DuplicateTop
+                LoadImmediateI32 1
+                SetObjectProperty8 "z"
+                FreeObject
+                GetObjectProperty8 "x"
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::Null]);
+
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 1
+                AllocateObject 1
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::ArityMismatch { expected: 0, found: 1 }));
+}
+
+#[test]
+fn test_initialize_class_runs_clinit_once() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Counter", class_with("Counter", &[r#"
+        .function clinit 0
+                GetGlobalVariable8 1
+                LoadImmediateI32 1
+                AddInt32
+                SetGlobalVariable8 1
+                ReturnFromFunction
+    "#]));
+    vm.define_named_global("runs", Value::I32(0));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                InitializeClass
+                GetGlobalVariable8 0
+                InitializeClass
+                GetGlobalVariable8 1
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::I32(1)]);
+}
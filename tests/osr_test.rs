@@ -0,0 +1,70 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+// Sums 1 through its argument, plus 7 kept on the operand stack across the loop.
+fn triangle_plus_seven() -> Rc<Function> {
+    Rc::new(assemble(r#"
+        .function triangle_plus_seven 1
+                LoadImmediateI32 0
+                LoadImmediateI32 0
+                LoadImmediateI32 7
+        loop:   GetLocalVariable8 2
+                GetLocalVariable8 0
+                LessOrEqualInt32
+                JumpIfFalse done
+                GetLocalVariable8 1
+                GetLocalVariable8 2
+                AddInt32
+                SetLocalVariable8 1
+                PopStack
+                GetLocalVariable8 2
+                AddInt32WithConstant 1
+                SetLocalVariable8 2
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 1
+                AddInt32
+                ReturnFromFunction
+    "#).unwrap())
+}
+
+#[test]
+fn test_hot_loop_switches_to_register_form_mid_call() {
+    let function = triangle_plus_seven();
+    let mut vm = IrisVM::builder().tier_up_threshold(10).build();
+    assert_eq!(vm.call(function.clone(), &[Value::I32(1000)]).unwrap(), Value::I32(500_507));
+    assert!(vm.is_tiered_up(&function));
+    assert_eq!(function.hotness.invocations(), 1);
+    assert_eq!(function.hotness.back_edges(), 9);
+}
+
+#[test]
+fn test_switch_keeps_operand_stack_and_locals() {
+    let function = triangle_plus_seven();
+    let mut vm = IrisVM::builder().tier_up_threshold(3).fuel(200).build();
+    vm.stack.push(Value::I32(100));
+    vm.push_frame(function.clone(), 1).unwrap();
+    assert!(matches!(vm.run(), Err(VMError::OutOfFuel)));
+    assert!(vm.is_tiered_up(&function));
+    assert_eq!(vm.stack.len(), 4);
+    assert_eq!(vm.stack[3], Value::I32(7));
+
+    vm.add_fuel(1_000_000);
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(5057)]);
+}
+
+#[test]
+fn test_observed_frames_stay_bytecode() {
+    let function = triangle_plus_seven();
+    let mut plain = IrisVM::builder().tiering(false).stats(true).build();
+    plain.call(function.clone(), &[Value::I32(50)]).unwrap();
+    let instructions = plain.stats().unwrap().total_instructions();
+
+    let mut vm = IrisVM::builder().tier_up_threshold(3).stats(true).build();
+    assert_eq!(vm.call(function, &[Value::I32(50)]).unwrap(), Value::I32(1282));
+    assert_eq!(vm.stats().unwrap().total_instructions(), instructions);
+}
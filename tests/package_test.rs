@@ -0,0 +1,106 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::module::{save_module, Module};
+use iris_vm::data::package::{decode_package, encode_package, pack_manifest, Package};
+use iris_vm::stdlib::package;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn app() -> Package {
+    let mut geometry = Module::new("geometry".to_string());
+    geometry.add_function(assemble("
+        .function area 2
+                GetLocalVariable8 0
+                GetLocalVariable8 1
+                MultiplyInt32
+                ReturnFromFunction
+    ").unwrap());
+    let mut main = Module::new("main".to_string());
+    main.add_function(assemble(r#"
+        .function main 0
+                LoadModule "geometry"
+                ImportSymbol "area"
+                LoadImmediateI32 6
+                LoadImmediateI32 7
+                CallFunction 2
+                ReturnFromFunction
+    "#).unwrap());
+    main.set_entry_point("main");
+
+    let mut package = Package::new("app", "1.2.0");
+    package.add_module(geometry);
+    package.add_module(main);
+    package.add_resource("greeting.txt", b"hello".to_vec());
+    package.set_entry("main");
+    package.require("modules");
+    package
+}
+
+fn run(vm: &mut IrisVM, function: Rc<Function>) -> Vec<Value> {
+    vm.push_frame(function, 0).unwrap();
+    vm.run().unwrap();
+    std::mem::take(&mut vm.stack)
+}
+
+#[test]
+fn test_package_round_trip() {
+    let decoded = decode_package(&encode_package(&app()).unwrap()).unwrap();
+    assert_eq!((decoded.name.as_str(), decoded.version.as_str()), ("app", "1.2.0"));
+    assert_eq!(decoded.entry.as_deref(), Some("main"));
+    assert_eq!(decoded.requires, vec!["modules".to_string()]);
+    assert_eq!(decoded.modules.len(), 2);
+    assert_eq!(decoded.module("geometry").unwrap().functions[0].name, "area");
+    assert_eq!(decoded.resource("greeting.txt"), Some(&b"hello"[..]));
+    assert!(decoded.missing_features().is_empty());
+
+    let mut orphaned = app();
+    orphaned.set_entry("missing");
+    assert!(encode_package(&orphaned).is_err());
+    assert!(decode_package(b"not a zip").is_err());
+}
+
+#[test]
+fn test_load_package_runs_entry() {
+    let mut vm = IrisVM::new();
+    package::register(&mut vm);
+    assert!(vm.resource("greeting.txt").is_none());
+
+    let package = decode_package(&encode_package(&app()).unwrap()).unwrap();
+    let entry = vm.load_package(&package).unwrap().unwrap();
+    assert_eq!(run(&mut vm, entry), vec![Value::I64(42)]);
+    assert!(vm.is_module_loaded("geometry"));
+
+    let resource = vm.global_slot("package_resource").unwrap();
+    let read = assemble(&format!("GetGlobalVariable8 {resource}\nPushConstant8 \"greeting.txt\"\nCallFunction 1")).unwrap();
+    assert_eq!(run(&mut vm, Rc::new(read)), vec![Value::Bytes(Gc::new(b"hello".to_vec()))]);
+    let read = assemble(&format!("GetGlobalVariable8 {resource}\nPushConstant8 \"missing.txt\"\nCallFunction 1")).unwrap();
+    assert_eq!(run(&mut vm, Rc::new(read)), vec![Value::Null]);
+
+    let mut future = app();
+    future.require("teleportation");
+    assert!(matches!(IrisVM::new().load_package(&future), Err(VMError::Import(message)) if message.contains("teleportation")));
+}
+
+#[test]
+fn test_pack_manifest() {
+    let root = std::env::temp_dir().join(format!("iris_package_test_{}", std::process::id()));
+    std::fs::create_dir_all(root.join("data")).unwrap();
+    for module in app().modules {
+        save_module(&module, &root.join(format!("{}.icm", module.name)).to_string_lossy()).unwrap();
+    }
+    std::fs::write(root.join("data/greeting.txt"), "hello").unwrap();
+    let manifest = root.join("app.json");
+    std::fs::write(&manifest, r#"{"name": "app", "version": "1.2.0", "entry": "main",
+        "modules": ["geometry.icm", "main.icm"], "resources": ["data/greeting.txt"]}"#).unwrap();
+
+    let package = pack_manifest(&manifest.to_string_lossy()).unwrap();
+    assert_eq!(package.entry_module().map(|module| module.name.as_str()), Some("main"));
+    assert_eq!(package.resource("data/greeting.txt"), Some(&b"hello"[..]));
+    assert!(Rc::ptr_eq(&package.module("main").unwrap().entry().unwrap(), &package.module("main").unwrap().functions[0]));
+
+    std::fs::write(&manifest, r#"{"name": "app", "version": "1", "resources": ["../secret"]}"#).unwrap();
+    assert!(pack_manifest(&manifest.to_string_lossy()).is_err());
+    std::fs::remove_dir_all(&root).unwrap();
+}
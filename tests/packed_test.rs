@@ -0,0 +1,34 @@
+#![cfg(feature = "nan-boxing")]
+
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::packed::{pack, unpack, PackedValue};
+use iris_vm::vm::value::Value;
+
+#[test]
+fn test_immediates_round_trip() {
+    let values = vec![
+        Value::Null, Value::Bool(false), Value::Bool(true),
+        Value::I8(-3), Value::I16(-300), Value::I32(i32::MIN), Value::U8(255), Value::U16(65535), Value::U32(u32::MAX),
+        Value::F32(1.5), Value::F64(-0.25), Value::F64(f64::INFINITY), Value::F64(f64::NEG_INFINITY),
+    ];
+    let packed = pack(&values);
+    assert!(packed.iter().all(|value| !value.is_boxed()));
+    assert_eq!(unpack(&packed), values);
+    assert!(PackedValue::from(Value::F64(f64::NAN)).as_f64().unwrap().is_nan());
+    assert_eq!(std::mem::size_of::<PackedValue>(), 8);
+}
+
+#[test]
+fn test_boxed_values_share_and_release() {
+    let array = Gc::new(vec![Value::I64(1)]);
+    let packed = PackedValue::from(Value::Array(array.clone()));
+    assert!(packed.is_boxed());
+    let copy = packed.clone();
+    assert_eq!(copy.to_value(), Value::Array(array.clone()));
+    assert_eq!(Value::from(copy), Value::Array(array.clone()));
+    drop(packed);
+    assert_eq!(Gc::strong_count(&array), 1);
+
+    let wide = PackedValue::from(Value::I128(-1));
+    assert_eq!(Value::from(wide), Value::I128(-1));
+}
@@ -0,0 +1,86 @@
+use std::rc::Rc;
+use iris_vm::asm::{assemble, assemble_chunk};
+use iris_vm::optimize::{peephole, peephole_function};
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+#[test]
+fn test_peephole_fuses_small_constants() {
+    let mut function = assemble(r#"
+        .function scale 0
+        .const two i32 2
+        .const big i32 1000
+
+                LoadImmediateI32 5
+                PushConstant8 two
+                AddInt32
+                LoadImmediateI8 -3
+                MultiplyInt32
+                PushConstant8 big
+                MultiplyInt32
+                ReturnFromFunction
+    "#).unwrap();
+
+    let stats = peephole_function(&mut function);
+    assert_eq!(stats.fused, 2);
+    assert_eq!(function.bytecode.as_ref().unwrap()[5..], [
+        OpCode::AddInt32WithConstant as u8, 2,
+        OpCode::MultiplyInt32WithConstant as u8, (-3i8) as u8,
+        OpCode::PushConstant8 as u8, 1,
+        OpCode::MultiplyInt32 as u8,
+        OpCode::ReturnFromFunction as u8,
+    ]);
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I64(-21000)]);
+}
+
+#[test]
+fn test_peephole_removes_dead_pushes_and_fixes_jumps() {
+    let mut function = assemble(r#"
+        .function countdown 0
+                LoadImmediateI32 3
+        loop:   PushNull
+                PopStack
+                DuplicateTop
+                JumpIfFalse done
+                LoadImmediateI64 99
+                PopStack
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:   ReturnFromFunction
+    "#).unwrap();
+    let before = function.bytecode.as_ref().unwrap().len();
+
+    let stats = peephole_function(&mut function);
+    assert_eq!(stats.removed_pairs, 2);
+    assert_eq!(function.bytecode.as_ref().unwrap().len(), before - 12);
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(function), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I64(0)]);
+}
+
+#[test]
+fn test_peephole_threads_jump_chains() {
+    let mut chunk = assemble_chunk(r#"
+                PushTrue
+                JumpIfTrue first
+                PushFalse
+        first:  UnconditionalJump second
+                PushNull
+        second: UnconditionalJump end
+                PushNull
+        end:    ReturnFromFunction
+    "#).unwrap();
+
+    let stats = peephole(&mut chunk);
+    assert_eq!(stats.threaded_jumps, 2);
+    assert_eq!(chunk.code[1..4], [OpCode::JumpIfTrue as u8, 0, 7]);
+    assert_eq!(chunk.code[5..7], [OpCode::UnconditionalJump as u8, 4]);
+}
@@ -0,0 +1,83 @@
+use std::rc::Rc;
+use iris_vm::pool::{PoolConfig, VmPool};
+use iris_vm::vm::chunk::{Chunk, ChunkWriter};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+fn warm_vm() -> IrisVM {
+    let mut vm = IrisVM::new();
+    vm.define_global(0, Value::Str("stdlib".into()));
+    vm
+}
+
+fn function(name: &str, chunk: Chunk) -> Rc<Function> {
+    Rc::new(Function::new_bytecode(name.to_string(), 0, chunk.code, chunk.constants))
+}
+
+#[test]
+fn test_pool_resets_transient_state_on_return() {
+    let pool = VmPool::new(PoolConfig { size: 2, ..PoolConfig::default() }, warm_vm);
+    {
+        let mut vm = pool.get();
+        vm.define_global(0, Value::Null);
+        vm.define_global(1, Value::I64(5));
+        vm.stack.push(Value::Bool(true));
+    }
+
+    let vm = pool.get();
+    assert!(vm.stack.is_empty());
+    assert_eq!(vm.globals(), &[Value::Str("stdlib".into())]);
+    assert_eq!(pool.metrics().in_use, 1);
+    assert_eq!(pool.metrics().created, 2);
+}
+
+#[test]
+fn test_pool_recycles_unhealthy_vms() {
+    let config = PoolConfig { size: 1, max_errors: Some(1), ..PoolConfig::default() };
+    let pool = VmPool::new(config, warm_vm);
+
+    let mut chunk = Chunk::new();
+    chunk.write(OpCode::PopStack);
+    {
+        let mut vm = pool.get();
+        vm.push_frame(function("underflow", chunk), 0).unwrap();
+        assert!(vm.run().is_err());
+    }
+
+    let metrics = pool.metrics();
+    assert_eq!(metrics.recycled, 1);
+    assert_eq!(metrics.created, 2);
+    assert_eq!(metrics.idle, 1);
+    assert_eq!(metrics.checkouts, metrics.returns);
+}
+
+#[test]
+fn test_pool_restores_baseline_containers() {
+    let factory = || {
+        let mut vm = IrisVM::new();
+        let shared = Value::Array(iris_vm::vm::gc::Gc::new(vec![Value::I64(1)]));
+        vm.define_global(0, shared.clone());
+        vm.define_global(1, shared);
+        vm
+    };
+    let pool = VmPool::new(PoolConfig { size: 1, ..PoolConfig::default() }, factory);
+    {
+        let vm = pool.get();
+        let Value::Array(items) = &vm.globals()[0] else { panic!("expected an array") };
+        items.borrow_mut().push(Value::I64(2));
+    }
+
+    let vm = pool.get();
+    let (Value::Array(first), Value::Array(second)) = (&vm.globals()[0], &vm.globals()[1]) else { panic!("expected arrays") };
+    assert_eq!(*first.borrow(), vec![Value::I64(1)]);
+    first.borrow_mut().push(Value::I64(3));
+    assert_eq!(second.borrow().len(), 2);
+    drop(vm);
+
+    let config = PoolConfig { size: 1, max_heap_bytes: Some(1), ..PoolConfig::default() };
+    let pool = VmPool::new(config, factory);
+    drop(pool.get());
+    assert_eq!(pool.metrics().recycled, 1);
+}
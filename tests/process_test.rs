@@ -0,0 +1,52 @@
+use iris_vm::stdlib::process;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::capability::Capability;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+#[test]
+fn test_process_natives_need_capabilities() {
+    let mut vm = IrisVMBuilder::new().args(["a"]).build();
+    process::register(&mut vm);
+    for (name, args, capability) in [
+        ("process_args", vec![], Capability::Args),
+        ("env_get", vec![Value::Str("PATH".into())], Capability::Env),
+        ("env_vars", vec![], Capability::Env),
+        ("process_exit", vec![Value::I64(0)], Capability::Exit),
+    ] {
+        let error = call(&mut vm, name, &args).unwrap_err();
+        assert!(matches!(error.root(), VMError::CapabilityDenied(denied) if *denied == capability), "{}", name);
+    }
+}
+
+#[test]
+fn test_args_and_env() {
+    let mut vm = IrisVMBuilder::new().args(["input.txt", "--fast"]).grant(Capability::Args).grant(Capability::Env).build();
+    process::register(&mut vm);
+    let Value::Array(args) = call(&mut vm, "process_args", &[]).unwrap() else { panic!("expected an array") };
+    assert_eq!(*args.borrow(), vec![Value::Str("input.txt".into()), Value::Str("--fast".into())]);
+
+    std::env::set_var("IRIS_PROCESS_TEST", "set");
+    assert_eq!(call(&mut vm, "env_get", &[Value::Str("IRIS_PROCESS_TEST".into())]).unwrap(), Value::Str("set".into()));
+    assert_eq!(call(&mut vm, "env_get", &[Value::Str("IRIS_PROCESS_TEST_UNSET".into())]).unwrap(), Value::Null);
+    assert!(matches!(call(&mut vm, "env_get", &[Value::Str("A=B".into())]).unwrap_err().root(), VMError::InvalidOperand(_)));
+    let Value::Map(vars) = call(&mut vm, "env_vars", &[]).unwrap() else { panic!("expected a map") };
+    assert_eq!(vars.borrow().get("IRIS_PROCESS_TEST"), Some(&Value::Str("set".into())));
+}
+
+#[test]
+fn test_exit_is_fatal() {
+    let mut vm = IrisVMBuilder::new().grant(Capability::Exit).build();
+    process::register(&mut vm);
+    let error = call(&mut vm, "process_exit", &[Value::I32(3)]).unwrap_err();
+    assert!(matches!(error.root(), VMError::Exit(3)));
+    assert!(error.kind().is_fatal());
+    assert!(matches!(call(&mut vm, "process_exit", &[Value::I64(1 << 40)]).unwrap_err().root(), VMError::InvalidOperand(_)));
+}
@@ -0,0 +1,55 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+/// `main` calls `work`, which spins through a countdown of `n`.
+fn program(vm: &mut IrisVM, n: i32) -> Rc<Function> {
+    let work = Rc::new(assemble(&format!("
+        .function work 0
+                LoadImmediateI32 {}
+        loop:   DuplicateTop
+                JumpIfFalse done
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:   ReturnFromFunction
+    ", n)).unwrap());
+    let slot = vm.define_named_global("work", Value::Function(work));
+    Rc::new(assemble(&format!("
+        .function main 0
+        GetGlobalVariable8 {}
+        CallFunction 0
+    ", slot)).unwrap())
+}
+
+#[test]
+fn test_profile_attributes_samples_to_the_hot_function() {
+    let mut vm = IrisVM::new();
+    let main = program(&mut vm, 200);
+    vm.start_profiling(10);
+    vm.push_frame(main, 0).unwrap();
+    vm.run().unwrap();
+    let profile = vm.stop_profiling().unwrap();
+    assert!(vm.profile().is_none());
+
+    // 2 instructions in main and 4 + 5 * 200 in work, sampled every 10th.
+    assert_eq!(profile.samples(), 100);
+    let functions = profile.functions();
+    assert_eq!(functions[0].0, "work");
+    assert_eq!(functions[0].2, 100);
+    assert!(profile.to_string().contains("work"));
+}
+
+#[test]
+fn test_collapsed_stacks() {
+    let mut vm = IrisVM::new();
+    let main = program(&mut vm, 4);
+    vm.start_profiling(1);
+    vm.push_frame(main, 0).unwrap();
+    vm.run().unwrap();
+    let profile = vm.profile().unwrap();
+    assert_eq!(profile.collapsed(), "main 2\nmain;work 24\n");
+    assert_eq!(profile.hot_spots().iter().filter(|(function, _, _)| *function == "main").count(), 2);
+}
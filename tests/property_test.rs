@@ -0,0 +1,75 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::verifier::{verify, VerifyError};
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_properties_are_accessed_by_name() {
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Point", Value::Class(Rc::new(Class::new("Point".to_string(), 0, None))));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                CreateNewInstance
+                DuplicateTop
+                LoadImmediateI32 3
+                SetObjectProperty8 "x"
+                DuplicateTop
+                LoadImmediateI32 4
+                SetObjectProperty16 "y"
+                DuplicateTop
+                GetObjectProperty16 "x"
+                SwapTopTwo
+                GetObjectProperty8 "y"
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::I32(3), Value::I32(4)]);
+}
+
+#[test]
+fn test_sealed_class_rejects_new_properties() {
+    let mut vm = IrisVM::new();
+    let mut class = Class::new("Sealed".to_string(), 0, None);
+    class.properties.insert("x".to_string(), 0);
+    class.extensible = false;
+    vm.define_named_global("Sealed", Value::Class(Rc::new(class)));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                CreateNewInstance
+                DuplicateTop
+                GetObjectProperty8 "x"
+                SwapTopTwo
+                LoadImmediateI32 1
+                SetObjectProperty8 "x"
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::Null]);
+
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                CreateNewInstance
+                LoadImmediateI32 1
+                SetObjectProperty8 "y"
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::UndefinedProperty(name) if name == "y"));
+    assert!(error.to_string().contains("Undefined property: 'y'"));
+}
+
+#[test]
+fn test_verifier_requires_string_property_names() {
+    let function = Rc::new(assemble("
+        .function main 0
+        .const one i32 1
+                PushNull
+                GetObjectProperty8 one
+    ").unwrap());
+    assert!(matches!(verify(&function), Err(VerifyError::InvalidConstant { expected: "string", .. })));
+}
@@ -0,0 +1,106 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::optimize::quicken_function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+const SUM_BELOW: &str = r#"
+    .function sum_below 0
+    .const one i32 1
+
+            LoadImmediateI32 0          ; i
+            LoadImmediateI32 10         ; n
+            LoadImmediateI32 0          ; sum
+    loop:   GetLocalVariable8 0
+            GetLocalVariable8 1
+            LessThanInt32
+            JumpIfFalse done
+            GetLocalVariable8 2
+            GetLocalVariable8 0
+            AddInt32
+            SetLocalVariable8 2
+            PopStack
+            GetLocalVariable8 0
+            PushConstant8 one
+            AddInt32
+            SetLocalVariable8 0
+            PopStack
+            LoopJump loop
+    done:   GetLocalVariable8 2
+            ReturnFromFunction
+"#;
+
+fn run(function: Rc<iris_vm::vm::function::Function>) -> Vec<Value> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(function, 0).unwrap();
+    vm.run().unwrap();
+    std::mem::take(&mut vm.stack)
+}
+
+#[test]
+fn test_quicken_rewrites_loop_into_superinstructions() {
+    let mut function = Rc::new(assemble(SUM_BELOW).unwrap());
+    assert_eq!(quicken_function(&mut function).unwrap(), 2);
+
+    let [compare, compare_op] = (OpCode::JumpIfLocalsNotLessInt32 as u16).to_be_bytes();
+    let [add, add_op] = (OpCode::AddLocalInt32WithConstant as u16).to_be_bytes();
+    let code = function.bytecode.as_ref().unwrap();
+    assert_eq!(code[15..21], [compare, compare_op, 0, 1, 0, 16]);
+    assert_eq!(code[29..34], [add, add_op, 0, 1, OpCode::PopStack as u8]);
+}
+
+#[test]
+fn test_quickened_code_computes_the_same_result() {
+    let plain = Rc::new(assemble(SUM_BELOW).unwrap());
+    let mut quickened = Rc::new(assemble(SUM_BELOW).unwrap());
+    quicken_function(&mut quickened).unwrap();
+    assert!(quickened.bytecode.as_ref().unwrap().len() < plain.bytecode.as_ref().unwrap().len());
+    assert_eq!(run(plain), vec![Value::I32(45)]);
+    assert_eq!(run(quickened), vec![Value::I32(45)]);
+}
+
+#[test]
+fn test_quicken_rejects_unverifiable_code_and_skips_jump_targets() {
+    let mut broken = Rc::new(assemble("PopStack\nReturnFromFunction").unwrap());
+    assert!(quicken_function(&mut broken).is_err());
+
+    // `inside` lands between the GetLocalVariable8 and the add, so the add can't be fused.
+    let mut function = Rc::new(assemble(r#"
+                LoadImmediateI32 1
+                LoadImmediateI32 5
+                PushTrue
+                JumpIfTrue inside
+                PopStack
+                GetLocalVariable8 0
+        inside: AddInt32WithConstant 2
+                SetLocalVariable8 0
+                ReturnFromFunction
+    "#).unwrap());
+    let before = function.bytecode.clone();
+    assert_eq!(quicken_function(&mut function).unwrap(), 0);
+    assert_eq!(function.bytecode, before);
+}
+
+#[test]
+fn test_quickened_compare_accepts_widened_locals() {
+    let below = || assemble(r#"
+        .function below 2
+                GetLocalVariable8 0
+                GetLocalVariable8 1
+                LessThanInt32
+                JumpIfFalse no
+                LoadImmediateI32 1
+                ReturnFromFunction
+        no:     LoadImmediateI32 0
+                ReturnFromFunction
+    "#).unwrap();
+    let mut quickened = Rc::new(below());
+    assert_eq!(quicken_function(&mut quickened).unwrap(), 1);
+    let mut vm = IrisVM::new();
+    for function in [Rc::new(below()), quickened] {
+        assert_eq!(vm.call(function.clone(), &[Value::I64(1), Value::I64(2)]).unwrap(), Value::I32(1));
+        assert_eq!(vm.call(function.clone(), &[Value::F64(2.5), Value::I32(2)]).unwrap(), Value::I32(0));
+        assert!(matches!(vm.call(function, &[Value::Null, Value::I32(2)]).unwrap_err().root(), VMError::TypeMismatch(_)));
+    }
+}
@@ -0,0 +1,65 @@
+use iris_vm::stdlib::random;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::random::Rng;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn seeded(seed: u64) -> IrisVM {
+    let mut vm = IrisVMBuilder::new().random_seed(seed).build();
+    random::register(&mut vm);
+    vm
+}
+
+/// A few draws of each kind.
+fn draws(vm: &mut IrisVM) -> Vec<Value> {
+    let array = Gc::new((0..10).map(Value::I64).collect::<Vec<_>>());
+    call(vm, "shuffle", &[Value::Array(array.clone())]).unwrap();
+    let mut values = vec![call(vm, "random_f64", &[]).unwrap(), call(vm, "random_range", &[Value::I64(-5), Value::I64(5)]).unwrap()];
+    values.extend(array.borrow().iter().cloned());
+    values
+}
+
+#[test]
+fn test_seeded_vms_repeat() {
+    let (mut a, mut b, mut c) = (seeded(7), seeded(7), seeded(8));
+    let first = draws(&mut a);
+    assert_eq!(first, draws(&mut b));
+    assert_ne!(first, draws(&mut c));
+    a.seed_random(7);
+    assert_eq!(draws(&mut a), first);
+}
+
+#[test]
+fn test_random_ranges() {
+    let mut vm = seeded(1);
+    let mut seen = [false; 3];
+    for _ in 0..200 {
+        let Value::F64(x) = call(&mut vm, "random_f64", &[]).unwrap() else { panic!("expected an F64") };
+        assert!((0.0..1.0).contains(&x));
+        let Value::I64(n) = call(&mut vm, "random_range", &[Value::I32(-1), Value::I64(2)]).unwrap() else { panic!("expected an I64") };
+        seen[(n + 1) as usize] = true;
+    }
+    assert_eq!(seen, [true; 3]);
+    assert_eq!(Rng::new(3).range(i64::MIN, i64::MAX).map(|n| n < i64::MAX), Some(true));
+    assert!(matches!(call(&mut vm, "random_range", &[Value::I64(2), Value::I64(2)]).unwrap_err().root(), VMError::InvalidOperand(_)));
+}
+
+#[test]
+fn test_shuffle_permutes() {
+    let mut vm = seeded(42);
+    let array = Gc::new((0..50).map(Value::I64).collect::<Vec<_>>());
+    call(&mut vm, "shuffle", &[Value::Array(array.clone())]).unwrap();
+    let mut shuffled: Vec<i64> = array.borrow().iter().map(|v| match v { Value::I64(n) => *n, _ => unreachable!() }).collect();
+    assert_ne!(shuffled, (0..50).collect::<Vec<_>>());
+    shuffled.sort();
+    assert_eq!(shuffled, (0..50).collect::<Vec<_>>());
+    assert!(matches!(call(&mut vm, "shuffle", &[Value::I64(1)]).unwrap_err().root(), VMError::TypeMismatch(_)));
+}
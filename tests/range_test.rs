@@ -0,0 +1,62 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::range::Range;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(source: &str) -> Result<Vec<Value>, VMError> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn collect(start: i32, end: i32, step: i32) -> Result<Vec<Value>, VMError> {
+    // Each element is swapped under the range, so the loop leaves them in order.
+    run(&format!(r#"
+        .function count 0
+                LoadImmediateI32 {start}
+                LoadImmediateI32 {end}
+                LoadImmediateI32 {step}
+                CreateRange
+        loop:   RangeHasNext
+                JumpIfFalse done
+                RangeNext
+                SwapTopTwo
+                LoopJump loop
+        done:   PopStack
+    "#))
+}
+
+#[test]
+fn test_range_loop() {
+    let ints = |values: &[i64]| values.iter().map(|v| Value::I64(*v)).collect::<Vec<_>>();
+    assert_eq!(collect(0, 5, 1).unwrap(), ints(&[0, 1, 2, 3, 4]));
+    assert_eq!(collect(10, 0, -3).unwrap(), ints(&[10, 7, 4, 1]));
+    assert_eq!(collect(3, 3, 1).unwrap(), ints(&[]));
+}
+
+#[test]
+fn test_range_iterator() {
+    let range = Range::new(i64::MAX - 2, i64::MAX, 2).unwrap();
+    assert_eq!(range.len(), 1);
+    assert_eq!(range.collect::<Vec<_>>(), vec![i64::MAX - 2]);
+    assert_eq!(Range::new(0, 10, 3).unwrap().len(), 4);
+    assert!(Range::new(0, 10, 0).is_none());
+}
+
+#[test]
+fn test_range_errors() {
+    let error = collect(0, 5, 0).unwrap_err();
+    assert!(matches!(error.root(), VMError::InvalidOperand(_)));
+
+    let error = run(r#"
+        .function exhausted 0
+                LoadImmediateI32 0
+                LoadImmediateI32 0
+                LoadImmediateI32 1
+                CreateRange
+                RangeNext
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::IndexOutOfBounds));
+}
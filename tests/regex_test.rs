@@ -0,0 +1,69 @@
+#![cfg(feature = "regex")]
+
+use iris_vm::data::regex::Regex;
+use iris_vm::stdlib::regex;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[&str]) -> Result<Value, VMError> {
+    let args: Vec<Value> = args.iter().map(|arg| Value::Str((*arg).into())).collect();
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, &args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn find_all(pattern: &str, text: &str) -> Vec<String> {
+    Regex::new(pattern).unwrap().captures_all(text).into_iter().map(|captures| text[captures.range()].to_string()).collect()
+}
+
+#[test]
+fn test_regex_syntax() {
+    assert_eq!(find_all(r"\d+", "a1 b22 c333"), ["1", "22", "333"]);
+    assert_eq!(find_all(r"[a-c]+|x", "abxcz"), ["ab", "x", "c"]);
+    assert_eq!(find_all(r"\bcat\b", "cat concat cat."), ["cat", "cat"]);
+    assert_eq!(find_all("a*", "baa"), ["", "aa", ""]);
+    assert_eq!(find_all("<.+?>", "<a><b>"), ["<a>", "<b>"]);
+    assert_eq!(find_all("<.+>", "<a><b>"), ["<a><b>"]);
+    assert_eq!(find_all(r"x{2,3}", "xxxxxxx"), ["xxx", "xxx"]);
+    assert_eq!(find_all(r"[^\s,]+", "é, ü ,ß"), ["é", "ü", "ß"]);
+    assert_eq!(find_all(r"a{,", "a{,"), ["a{,"]);
+    assert!(Regex::new("^abc$").unwrap().is_match("abc"));
+    assert!(!Regex::new("^abc$").unwrap().is_match("abcd"));
+    // Linear time where a backtracking matcher would take forever.
+    assert!(!Regex::new("(a*)*b").unwrap().is_match(&"a".repeat(5000)));
+    for bad in ["(", "a)", "*a", "[z-a]", r"\q", "a**", "(?<1x>a)", "[abc"] {
+        assert!(Regex::new(bad).is_err(), "{}", bad);
+    }
+    assert!(Regex::new("a{100000}").unwrap_err().message.contains("too large"));
+    let error = Regex::new("a{3}{2}").unwrap_err();
+    assert_eq!((error.offset, error.message.as_str()), (4, "nothing to repeat"));
+    assert_eq!(find_all("a+{x", "aa{x"), ["aa{x"]);
+}
+
+#[test]
+fn test_regex_natives() {
+    let mut vm = IrisVM::new();
+    regex::register(&mut vm);
+    assert_eq!(call(&mut vm, "regex_match", &["o+", "foo"]).unwrap(), Value::Bool(true));
+    assert_eq!(call(&mut vm, "regex_match", &["^o", "foo"]).unwrap(), Value::Bool(false));
+    let Value::Array(words) = call(&mut vm, "regex_find_all", &[r"\w+", "to be, or"]).unwrap() else { panic!("expected an array") };
+    assert_eq!(*words.borrow(), ["to", "be", "or"].map(|w| Value::Str(w.into())));
+    assert_eq!(call(&mut vm, "regex_replace", &[r"(\w+)@(?<host>\w+)", "ann@a, bob@b", "${host}:$1 $$"]).unwrap(), Value::Str("a:ann $, b:bob $".into()));
+    assert!(matches!(call(&mut vm, "regex_match", &["(", ""]).unwrap_err().root(), VMError::InvalidOperand(_)));
+}
+
+#[test]
+fn test_regex_captures() {
+    let mut vm = IrisVM::new();
+    regex::register(&mut vm);
+    let Value::Map(groups) = call(&mut vm, "regex_captures", &[r"(?<year>\d{4})-(\d\d)(-(\d\d))?", "on 2024-06!"]).unwrap() else { panic!("expected a map") };
+    let groups = groups.borrow();
+    assert_eq!(groups["0"], Value::Str("2024-06".into()));
+    assert_eq!(groups["1"], Value::Str("2024".into()));
+    assert_eq!(groups["year"], Value::Str("2024".into()));
+    assert_eq!(groups["2"], Value::Str("06".into()));
+    assert_eq!((groups["3"].clone(), groups["4"].clone()), (Value::Null, Value::Null));
+    assert_eq!(groups.len(), 6);
+    assert_eq!(call(&mut vm, "regex_captures", &["z", "abc"]).unwrap(), Value::Null);
+}
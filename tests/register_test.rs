@@ -0,0 +1,87 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::register::{translate_function, RegInstr, TranslateError};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn triangle() -> Function {
+    assemble(r#"
+        .function triangle 1
+                LoadImmediateI32 0          ; sum
+                LoadImmediateI32 0          ; i
+        loop:   GetLocalVariable8 2
+                GetLocalVariable8 0
+                LessOrEqualInt32
+                JumpIfFalse done
+                GetLocalVariable8 1
+                GetLocalVariable8 2
+                AddInt32
+                SetLocalVariable8 1
+                PopStack
+                GetLocalVariable8 2
+                AddInt32WithConstant 1
+                SetLocalVariable8 2
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 1
+                ReturnFromFunction
+    "#).unwrap()
+}
+
+#[test]
+fn test_register_form_reads_locals_in_place() {
+    let mut function = Rc::new(triangle());
+    assert!(translate_function(&mut function).unwrap());
+    let body = function.registers.clone().unwrap();
+    assert_eq!(body.registers, 5);
+    assert_eq!(body.instructions[2], RegInstr::Binary { op: OpCode::LessOrEqualInt32, dst: 3, a: 2, b: 0 });
+    assert_eq!(body.instructions[4], RegInstr::Binary { op: OpCode::AddInt32, dst: 3, a: 1, b: 2 });
+    assert!(!body.instructions.iter().any(|instruction| matches!(instruction, RegInstr::Move { dst: 3, .. })));
+
+    let mut vm = IrisVM::new();
+    assert_eq!(vm.call(function.clone(), &[Value::I32(100)]).unwrap(), Value::I32(5050));
+    assert_eq!(vm.call(Rc::new(triangle()), &[Value::I32(100)]).unwrap(), Value::I32(5050));
+}
+
+#[test]
+fn test_out_of_fuel_continues_as_bytecode() {
+    let mut function = Rc::new(triangle());
+    translate_function(&mut function).unwrap();
+    let mut vm = IrisVM::builder().fuel(50).build();
+    vm.stack.push(Value::I32(100));
+    vm.push_frame(function, 1).unwrap();
+    assert!(matches!(vm.run(), Err(VMError::OutOfFuel)));
+    vm.add_fuel(1_000_000);
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(5050)]);
+}
+
+#[test]
+fn test_calls_have_no_register_form() {
+    let mut function = Rc::new(assemble(r#"
+                PushNull
+                CallFunction 0
+                ReturnFromFunction
+    "#).unwrap());
+    let error = translate_function(&mut function).unwrap_err();
+    assert!(matches!(error, TranslateError::Unsupported { offset: 1, opcode: OpCode::CallFunction }));
+    assert!(function.registers.is_none());
+
+    let mut falls_off = Rc::new(assemble("LoadImmediateI32 1").unwrap());
+    assert!(matches!(translate_function(&mut falls_off), Err(TranslateError::FallsOffEnd)));
+}
+
+#[test]
+fn test_less_than_compares_widened_results() {
+    let below = || Rc::new(assemble(".function below 2\nGetLocalVariable8 0\nGetLocalVariable8 1\nLessThanInt32\nReturnFromFunction").unwrap());
+    let mut registers = below();
+    assert!(translate_function(&mut registers).unwrap());
+    let mut vm = IrisVM::new();
+    for function in [below(), registers] {
+        assert_eq!(vm.call(function.clone(), &[Value::I64(1), Value::F64(1.5)]).unwrap(), Value::Bool(true));
+        assert_eq!(vm.call(function.clone(), &[Value::I32(2), Value::I64(2)]).unwrap(), Value::Bool(false));
+        assert!(matches!(vm.call(function, &[Value::Null, Value::I32(1)]).unwrap_err().root(), VMError::TypeMismatch(_)));
+    }
+}
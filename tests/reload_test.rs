@@ -0,0 +1,95 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::module::Module;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::import::MemoryResolver;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+/// `.function bump 1` adding `amount` to its argument.
+fn bump(amount: i32) -> Function {
+    assemble(&format!("
+        .function bump 1
+                GetLocalVariable8 0
+                LoadImmediateI32 {amount}
+                AddInt32
+                ReturnFromFunction
+    ")).unwrap()
+}
+
+fn call(vm: &mut IrisVM, name: &str, arg: i32) -> Value {
+    let Value::Function(function) = vm.get_global(vm.global_slot(name).unwrap()).unwrap() else { panic!("{} is not a function", name) };
+    vm.call(function, &[Value::I32(arg)]).unwrap()
+}
+
+#[test]
+fn test_redefined_function_is_called_and_retiered() {
+    let mut vm = IrisVM::new();
+    vm.set_tier_up_threshold(Some(2));
+    let mut module = Module::new("counter".to_string());
+    module.add_function(bump(1));
+    vm.load_module(&module).unwrap();
+    for _ in 0..3 {
+        assert_eq!(call(&mut vm, "bump", 1), Value::I32(2));
+    }
+    let old = module.functions[0].clone();
+    assert!(vm.is_tiered_up(&old));
+
+    let new = Rc::new(bump(100));
+    let replaced = vm.redefine_function("bump", new.clone()).unwrap();
+    assert!(Rc::ptr_eq(&replaced, &old));
+    assert!(!vm.is_tiered_up(&old));
+    assert_eq!(call(&mut vm, "bump", 1), Value::I32(101));
+    for _ in 0..3 {
+        call(&mut vm, "bump", 1);
+    }
+    assert!(vm.is_tiered_up(&new));
+}
+
+#[test]
+fn test_redefine_updates_globals_and_imports() {
+    let mut vm = IrisVM::new();
+    let mut module = Module::new("counter".to_string());
+    module.add_function(bump(1));
+    vm.load_module(&module).unwrap();
+    vm.add_module_resolver(MemoryResolver::new().with(module));
+    vm.import_module("counter").unwrap();
+    let slot = vm.global_slot("bump").unwrap();
+
+    // Calls bump, then stops before calling it again through the global.
+    let main = assemble(&format!("
+                GetGlobalVariable8 {slot}
+                LoadImmediateI32 0
+                CallFunction 1
+                GetGlobalVariable8 {slot}
+                LoadImmediateI32 0
+                CallFunction 1
+    ")).unwrap();
+    vm.set_fuel(Some(6));
+    vm.push_frame(Rc::new(main), 0).unwrap();
+    assert!(matches!(vm.run(), Err(VMError::OutOfFuel)));
+    vm.redefine_function("bump", Rc::new(bump(100))).unwrap();
+    vm.set_fuel(None);
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I32(1), Value::I32(100)]);
+
+    let Value::Map(exports) = vm.import_module("counter").unwrap() else { panic!("exports are a map") };
+    let Some(Value::Function(imported)) = exports.borrow().get("bump").cloned() else { panic!("bump is exported") };
+    assert_eq!(vm.call(imported, &[Value::I32(1)]).unwrap(), Value::I32(101));
+}
+
+#[test]
+fn test_redefine_needs_a_bytecode_global() {
+    let mut vm = IrisVM::new();
+    vm.register_native("native", |_| Ok(Value::Null));
+    assert!(matches!(vm.redefine_function("missing", Rc::new(bump(1))), Err(VMError::UndefinedVariable(_))));
+    assert!(matches!(vm.redefine_function("native", Rc::new(bump(1))), Err(VMError::TypeMismatch(_))));
+
+    let mut module = Module::new("counter".to_string());
+    module.add_function(bump(1));
+    vm.load_module(&module).unwrap();
+    vm.set_require_verification(true);
+    let broken = Rc::new(assemble(".function bump 1\nAddInt32\nReturnFromFunction").unwrap());
+    assert!(matches!(vm.redefine_function("bump", broken), Err(VMError::VerificationFailed(_))));
+    assert_eq!(call(&mut vm, "bump", 1), Value::I32(2));
+}
@@ -0,0 +1,90 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(source: &str) -> Result<Vec<Value>, VMError> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn elements(value: &Value) -> Vec<Value> {
+    match value {
+        Value::Array(array) => array.borrow().clone(),
+        other => panic!("expected an array, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_insert_contains_and_remove() {
+    let stack = run(r#"
+        .function membership 0
+                LoadImmediateI32 1
+                PushConstant8 "a"
+                LoadImmediateI32 1
+                CreateSet 3
+                DuplicateTop
+                PushConstant8 "b"
+                SetInsert
+                DuplicateTop
+                PushConstant8 "a"
+                SetRemove
+                SwapTopTwo
+                DuplicateTop
+                PushConstant8 "a"
+                SetContains
+                SwapTopTwo
+                DuplicateTop
+                SetLength
+                SwapTopTwo
+                SetToArray
+    "#).unwrap();
+    assert_eq!(stack[..3], [Value::Bool(true), Value::Bool(false), Value::I64(2)]);
+    assert_eq!(elements(&stack[3]), vec![Value::I32(1), Value::Str("b".into())]);
+}
+
+#[test]
+fn test_union_and_intersection() {
+    let stack = run(r#"
+        .function union 0
+                LoadImmediateI32 1
+                LoadImmediateI32 2
+                CreateSet 2
+                LoadImmediateI32 2
+                LoadImmediateI32 3
+                CreateSet 2
+                SetUnion
+                SetToArray
+                LoadImmediateI32 1
+                LoadImmediateI32 2
+                CreateSet 2
+                LoadImmediateI32 2
+                LoadImmediateI32 3
+                CreateSet 2
+                SetIntersection
+                SetToArray
+    "#).unwrap();
+    assert_eq!(elements(&stack[0]), vec![Value::I32(1), Value::I32(2), Value::I32(3)]);
+    assert_eq!(elements(&stack[1]), vec![Value::I32(2)]);
+}
+
+#[test]
+fn test_set_errors() {
+    let error = run(r#"
+        .function not_a_set 0
+                PushNull
+                PushNull
+                SetContains
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+
+    let error = run(r#"
+        .function unhashable 0
+                CreateNewArray8 0
+                CreateWeakRef
+                CreateSet 1
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
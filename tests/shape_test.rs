@@ -0,0 +1,87 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::inline_cache::CacheState;
+use iris_vm::vm::object::{Class, Instance};
+use iris_vm::vm::shape::Shape;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+use std::rc::Rc;
+
+/// Sets `a` then `b` on its argument and returns it.
+const SET_A_B: &str = "
+    .function set_a_b 1
+            GetLocalVariable8 0
+            LoadImmediateI32 1
+            SetPropertyWithInlineCache \"a\"
+            GetLocalVariable8 0
+            LoadImmediateI32 2
+            SetPropertyWithInlineCache \"b\"
+            GetLocalVariable8 0
+            ReturnFromFunction
+";
+
+fn new_instance(class: &Rc<Class>) -> Value {
+    Value::Object(Gc::new(Instance::new(class.clone())))
+}
+
+fn shape_of(value: &Value) -> Rc<Shape> {
+    match value {
+        Value::Object(instance) => instance.borrow().shape.clone(),
+        other => panic!("not an instance: {:?}", other),
+    }
+}
+
+#[test]
+fn test_shape_transitions_are_shared() {
+    let root = Rc::new(Shape::new([("x".to_string(), 0)]));
+    let a = root.with_property("a");
+    assert!(Rc::ptr_eq(&a, &root.with_property("a")));
+    assert_eq!(a.offset("a"), Some(1));
+    assert_eq!(a.names(), vec![Some("x"), Some("a")]);
+    let b_then_a = root.with_property("b").with_property("a");
+    assert!(!Rc::ptr_eq(&a.with_property("b"), &b_then_a));
+    assert_eq!(b_then_a.offset("a"), Some(2));
+}
+
+#[test]
+fn test_instances_built_alike_share_a_shape() {
+    let mut vm = IrisVM::new();
+    let class = Rc::new(Class::new("Point".to_string(), 0, None));
+    let set_a_b = Rc::new(assemble(SET_A_B).unwrap());
+    let first = vm.call(set_a_b.clone(), &[new_instance(&class)]).unwrap();
+    let second = vm.call(set_a_b.clone(), &[new_instance(&class)]).unwrap();
+    assert!(Rc::ptr_eq(&shape_of(&first), &shape_of(&second)));
+
+    let Value::Object(instance) = &first else { unreachable!() };
+    assert_eq!(instance.borrow().get_property("b"), Some(Value::I32(2)));
+    assert_eq!(instance.borrow().fields, vec![Value::I32(1), Value::I32(2)]);
+
+    let other = new_instance(&class);
+    let Value::Object(instance) = &other else { unreachable!() };
+    instance.borrow_mut().set_property("b", Value::Null);
+    instance.borrow_mut().set_property("a", Value::Null);
+    assert!(!Rc::ptr_eq(&shape_of(&first), &shape_of(&other)));
+}
+
+#[test]
+fn test_same_shape_keeps_sites_monomorphic() {
+    let mut vm = IrisVM::new();
+    let mut class = Class::new("Point".to_string(), 0, None);
+    class.properties.insert("a".to_string(), 0);
+    let class = Rc::new(class);
+    let set_a_b = Rc::new(assemble(SET_A_B).unwrap());
+    let get_b = Rc::new(assemble("
+        .function get_b 1
+                GetLocalVariable8 0
+                GetPropertyWithInlineCache \"b\"
+                ReturnFromFunction
+    ").unwrap());
+    for _ in 0..10 {
+        let instance = vm.call(set_a_b.clone(), &[new_instance(&class)]).unwrap();
+        assert_eq!(vm.call(get_b.clone(), &[instance]).unwrap(), Value::I32(2));
+    }
+    // `a` is declared by the class, so only `b` is added.
+    assert_eq!(vm.inline_cache_state(&set_a_b, 7), CacheState::Monomorphic);
+    assert_eq!(vm.inline_cache_state(&set_a_b, 17), CacheState::Monomorphic);
+    assert_eq!(vm.inline_cache_state(&get_b, 2), CacheState::Monomorphic);
+}
@@ -0,0 +1,69 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use iris_vm::asm::assemble;
+use iris_vm::data::module::Module;
+use iris_vm::data::shared::{SharedModule, SharedValue};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+fn program() -> Module {
+    let mut module = Module::new(String::from("program"));
+    // `greet` is global 0 on a fresh VM; `main` calls it and appends the worker's suffix.
+    module.add_function(assemble(r#"
+        .function greet 0
+                PushConstant8 "hello "
+                ReturnFromFunction
+    "#).unwrap());
+    module.add_function(assemble("
+        .function main 1
+                GetGlobalVariable8 0
+                CallFunction 0
+                GetLocalVariable8 0
+                AddInt32
+                ReturnFromFunction
+    ").unwrap());
+    module.set_entry_point("main");
+    module
+}
+
+#[test]
+fn test_vm_per_thread_shares_module() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<SharedModule>();
+
+    let shared = Arc::new(SharedModule::new(&program()).unwrap());
+    let results: Vec<String> = std::thread::scope(|scope| {
+        let workers: Vec<_> = ["alice", "bob"].into_iter().map(|name| {
+            let shared = shared.clone();
+            scope.spawn(move || {
+                let module = shared.instantiate();
+                let mut vm = IrisVM::new();
+                vm.load_module(&module).unwrap();
+                match vm.call(module.entry().unwrap(), &[Value::Str(name.into())]).unwrap() {
+                    Value::Str(s) => s.to_string(),
+                    other => panic!("expected a string, got {:?}", other),
+                }
+            })
+        }).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    });
+    assert_eq!(results, vec!["hello alice", "hello bob"]);
+}
+
+#[test]
+fn test_shared_constants_round_trip() {
+    let shared = SharedModule::new(&program()).unwrap();
+    assert_eq!(shared.functions[0].constants, vec![SharedValue::Str("hello ".into())]);
+    let module = shared.instantiate();
+    assert_eq!(module.entry().unwrap().name, "main");
+    assert_eq!(module.functions[0].constants, vec![Value::Str("hello ".into())]);
+    assert_eq!(SharedModule::new(&module).unwrap(), shared);
+}
+
+#[test]
+fn test_native_functions_are_not_shared() {
+    let mut module = program();
+    module.functions.push(Rc::new(Function::new_native(String::from("host"), 0, |_, _| Ok(Value::Null))));
+    assert!(SharedModule::new(&module).is_err());
+}
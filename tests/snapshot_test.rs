@@ -0,0 +1,102 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::snapshot::Snapshot;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn double(args: &[Value]) -> Result<Value, VMError> {
+    match args {
+        [Value::I32(n)] => Ok(Value::I32(n * 2)),
+        [Value::I64(n)] => Ok(Value::I32(*n as i32 * 2)),
+        _ => Err(VMError::TypeMismatch("double expects an integer".to_string())),
+    }
+}
+
+/// Sums `double(n)` for n from 5 down to 1.
+fn start(vm: &mut IrisVM) {
+    let double = vm.register_native("double", double);
+    let main = assemble(&format!("
+                LoadImmediateI32 0
+                LoadImmediateI32 5
+        loop:   GetLocalVariable8 1
+                JumpIfFalse done
+                GetLocalVariable8 0
+                GetGlobalVariable8 {double}
+                GetLocalVariable8 1
+                CallFunction 1
+                AddInt32
+                SetLocalVariable8 0
+                PopStack
+                GetLocalVariable8 1
+                LoadImmediateI32 1
+                SubtractInt32
+                SetLocalVariable8 1
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 0
+                ReturnFromFunction
+    ")).unwrap();
+    vm.push_frame(Rc::new(main), 0).unwrap();
+}
+
+#[test]
+fn test_restore_continues_where_snapshot_was_taken() {
+    let mut whole = IrisVM::new();
+    start(&mut whole);
+    whole.run().unwrap();
+    let expected = std::mem::take(&mut whole.stack);
+
+    let mut vm = IrisVM::builder().fuel(20).build();
+    start(&mut vm);
+    assert!(matches!(vm.run(), Err(VMError::OutOfFuel)));
+    let path = std::env::temp_dir().join(format!("iris_snapshot_test_{}.snap", std::process::id()));
+    let path = path.to_string_lossy();
+    vm.snapshot().unwrap().save(&path).unwrap();
+    drop(vm);
+
+    let mut resumed = IrisVM::new();
+    resumed.register_native("double", double);
+    resumed.restore(&Snapshot::load(&path).unwrap()).unwrap();
+    std::fs::remove_file(&*path).unwrap();
+    assert_eq!(resumed.frames().len(), 1);
+    resumed.run().unwrap();
+    assert_eq!(resumed.stack, expected);
+    assert_eq!(expected, vec![Value::I32(30)]);
+}
+
+#[test]
+fn test_restore_keeps_sharing_and_globals() {
+    let mut vm = IrisVM::new();
+    let shared = Value::Array(Gc::new(vec![Value::I64(1)]));
+    let slot = vm.define_named_global("items", shared.clone());
+    vm.stack.push(shared);
+    vm.stack.push(Value::Str("text".into()));
+    let snapshot = Snapshot::from_bytes(vm.snapshot().unwrap().as_bytes().to_vec());
+
+    let mut restored = IrisVM::new();
+    restored.restore(&snapshot).unwrap();
+    assert_eq!(restored.global_slot("items"), Some(slot));
+    assert_eq!(restored.stack[1], Value::Str("text".into()));
+    let (Value::Array(on_stack), Value::Array(global)) = (&restored.stack[0], &restored.globals()[slot]) else {
+        panic!("expected arrays, got {:?}", restored.stack);
+    };
+    on_stack.borrow_mut().push(Value::I64(2));
+    assert_eq!(global.borrow().len(), 2);
+    assert_eq!(restored.heap().live_objects(), 1);
+}
+
+#[test]
+fn test_restore_errors_leave_vm_unchanged() {
+    let mut vm = IrisVM::new();
+    start(&mut vm);
+    let snapshot = vm.snapshot().unwrap();
+
+    // The native isn't registered in this VM.
+    let mut other = IrisVM::new();
+    other.stack.push(Value::I64(7));
+    assert!(matches!(other.restore(&snapshot), Err(VMError::Snapshot(message)) if message.contains("double")));
+    assert!(matches!(other.restore(&Snapshot::from_bytes(b"garbage".to_vec())), Err(VMError::Snapshot(_))));
+    assert_eq!(other.stack, vec![Value::I64(7)]);
+    assert_eq!(other.frames().len(), 0);
+}
@@ -0,0 +1,45 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::vm::IrisVM;
+
+fn countdown() -> Rc<iris_vm::vm::function::Function> {
+    Rc::new(assemble("
+                LoadImmediateI32 3
+        loop:   DuplicateTop
+                JumpIfFalse done
+                LoadImmediateI32 1
+                SubtractInt32
+                LoopJump loop
+        done:
+    ").unwrap())
+}
+
+#[test]
+fn test_opcode_counts() {
+    let mut vm = IrisVM::builder().stats(true).build();
+    vm.push_frame(countdown(), 0).unwrap();
+    vm.run().unwrap();
+    let stats = vm.stats().unwrap();
+    assert_eq!(stats.count(OpCode::DuplicateTop), 4);
+    assert_eq!(stats.count(OpCode::LoadImmediateI32), 4);
+    assert_eq!(stats.count(OpCode::LoopJump), 3);
+    assert_eq!(stats.count(OpCode::AddInt32), 0);
+    assert_eq!(stats.total_instructions(), 18);
+    assert_eq!(stats.entries()[0].0, OpCode::DuplicateTop as u16);
+    assert!(stats.to_string().contains("SubtractInt32"));
+}
+
+#[test]
+fn test_stats_are_off_by_default_and_resettable() {
+    let mut vm = IrisVM::new();
+    assert!(vm.stats().is_none());
+    vm.set_stats_enabled(true);
+    vm.push_frame(countdown(), 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stats().unwrap().total_instructions(), 18);
+    vm.reset_stats();
+    assert_eq!(vm.stats().unwrap().total_instructions(), 0);
+    vm.set_stats_enabled(false);
+    assert!(vm.stats().is_none());
+}
@@ -0,0 +1,65 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+#[test]
+fn test_step_executes_one_instruction_at_a_time() {
+    let program = Rc::new(assemble("
+        .function main 0
+        LoadImmediateI32 1
+        LoadImmediateI32 2
+        AddInt32
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+
+    let first = vm.step().unwrap();
+    assert_eq!(first.op(), Some(OpCode::LoadImmediateI32));
+    let location = first.location.unwrap();
+    assert_eq!((location.function.as_str(), location.offset), ("main", 0));
+    assert_eq!(vm.stack, vec![Value::I32(1)]);
+    assert!(!first.finished);
+
+    assert_eq!(vm.step().unwrap().location.unwrap().offset, 5);
+    let last = vm.step().unwrap();
+    assert_eq!(last.op(), Some(OpCode::AddInt32));
+    assert!(last.finished);
+    assert_eq!(last.depth, 0);
+    assert_eq!(vm.stack, vec![Value::I32(3)]);
+
+    let idle = vm.step().unwrap();
+    assert!(idle.finished && idle.opcode.is_none());
+}
+
+#[test]
+fn test_step_follows_calls() {
+    let callee = Rc::new(assemble("
+        .function callee 0
+        PushTrue
+        ReturnFromFunction
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    let slot = vm.define_named_global("callee", Value::Function(callee));
+    let caller = Rc::new(assemble(&format!("
+        .function caller 0
+        GetGlobalVariable8 {}
+        CallFunction 0
+        PopStack
+    ", slot)).unwrap());
+    vm.push_frame(caller, 0).unwrap();
+
+    let mut trace = Vec::new();
+    loop {
+        let outcome = vm.step().unwrap();
+        if outcome.finished {
+            break;
+        }
+        let location = outcome.location.unwrap();
+        trace.push((location.function, outcome.depth));
+    }
+    let names: Vec<_> = trace.iter().map(|(name, depth)| (name.as_str(), *depth)).collect();
+    assert_eq!(names, [("caller", 1), ("caller", 2), ("callee", 2), ("callee", 1)]);
+    assert!(vm.stack.is_empty());
+}
@@ -0,0 +1,79 @@
+use iris_vm::stdlib::string::{self, graphemes};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn text(s: &str) -> Value {
+    Value::Str(s.into())
+}
+
+#[test]
+fn test_char_indexing_and_length() {
+    let mut vm = IrisVM::new();
+    string::register(&mut vm);
+    assert_eq!(call(&mut vm, "string_len", &[text("naïve")]).unwrap(), Value::I64(5));
+    assert_eq!(call(&mut vm, "string_char_at", &[text("naïve"), Value::I64(2)]).unwrap(), Value::Char('ï'));
+    assert_eq!(call(&mut vm, "string_char_at", &[text("naïve"), Value::I64(9)]).unwrap(), Value::Null);
+    assert_eq!(call(&mut vm, "char_code", &[Value::Char('€')]).unwrap(), Value::I64(0x20AC));
+    assert_eq!(call(&mut vm, "char_from_code", &[Value::I64(0x41)]).unwrap(), Value::Char('A'));
+    assert!(call(&mut vm, "char_from_code", &[Value::I64(0xD800)]).is_err());
+}
+
+#[test]
+fn test_case_conversion() {
+    let mut vm = IrisVM::new();
+    string::register(&mut vm);
+    assert_eq!(call(&mut vm, "string_to_upper", &[text("straße")]).unwrap(), text("STRASSE"));
+    assert_eq!(call(&mut vm, "string_to_lower", &[Value::Char('Ä')]).unwrap(), Value::Char('ä'));
+    assert_eq!(call(&mut vm, "string_to_upper", &[Value::Char('ß')]).unwrap(), text("SS"));
+}
+
+#[test]
+fn test_graphemes() {
+    assert_eq!(graphemes("e\u{301}a\r\nb"), vec!["e\u{301}", "a", "\r\n", "b"]);
+    assert_eq!(graphemes("👩\u{200D}💻🇫🇷🇩🇪👍🏽"), vec!["👩\u{200D}💻", "🇫🇷", "🇩🇪", "👍🏽"]);
+
+    let mut vm = IrisVM::new();
+    string::register(&mut vm);
+    match call(&mut vm, "string_graphemes", &[text("ño")]).unwrap() {
+        Value::Array(items) => assert_eq!(*items.borrow(), vec![text("ñ"), text("o")]),
+        other => panic!("expected an array, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_split_join_and_search() {
+    let mut vm = IrisVM::new();
+    string::register(&mut vm);
+    let Value::Array(parts) = call(&mut vm, "string_split", &[text("a,b,,c"), text(",")]).unwrap() else { panic!("expected an array") };
+    assert_eq!(*parts.borrow(), vec![text("a"), text("b"), text(""), text("c")]);
+    let Value::Array(words) = call(&mut vm, "string_split", &[text("  one two\tthree ")]).unwrap() else { panic!("expected an array") };
+    assert_eq!(words.borrow().len(), 3);
+    assert_eq!(call(&mut vm, "string_join", &[Value::Array(words), text("-")]).unwrap(), text("one-two-three"));
+    assert!(call(&mut vm, "string_split", &[text("abc"), text("")]).is_err());
+
+    assert_eq!(call(&mut vm, "string_trim", &[text(" \tpadded\n")]).unwrap(), text("padded"));
+    assert_eq!(call(&mut vm, "string_replace", &[text("a-b-c"), text("-"), text("+")]).unwrap(), text("a+b+c"));
+    assert_eq!(call(&mut vm, "string_find", &[text("naïve café"), text("café")]).unwrap(), Value::I64(6));
+    assert_eq!(call(&mut vm, "string_find", &[text("abc"), text("z")]).unwrap(), Value::Null);
+    assert_eq!(call(&mut vm, "string_starts_with", &[text("iris vm"), text("iris")]).unwrap(), Value::Bool(true));
+    assert_eq!(call(&mut vm, "string_ends_with", &[text("iris vm"), text("iris")]).unwrap(), Value::Bool(false));
+}
+
+#[test]
+fn test_format() {
+    let mut vm = IrisVM::new();
+    string::register(&mut vm);
+    let formatted = call(&mut vm, "string_format", &[text("{} has {1} items costing {2:.2} {{total}}"), text("cart"), Value::I32(3), Value::F64(9.5)]);
+    assert_eq!(formatted.unwrap(), text("cart has 3 items costing 9.50 {total}"));
+    assert_eq!(string::format("{0}{0}{}", &[Value::Char('x'), Value::Null]).unwrap(), "xxx");
+    assert!(string::format("{2}", &[]).is_err());
+    assert!(string::format("{", &[]).is_err());
+    assert!(string::format("{:x}", &[Value::I32(1)]).is_err());
+}
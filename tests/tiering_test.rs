@@ -0,0 +1,84 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+// Sums 1 through its argument with a loop.
+fn triangle() -> Rc<Function> {
+    Rc::new(assemble(r#"
+        .function triangle 1
+                LoadImmediateI32 0
+                LoadImmediateI32 0
+        loop:   GetLocalVariable8 2
+                GetLocalVariable8 0
+                LessOrEqualInt32
+                JumpIfFalse done
+                GetLocalVariable8 1
+                GetLocalVariable8 2
+                AddInt32
+                SetLocalVariable8 1
+                PopStack
+                GetLocalVariable8 2
+                AddInt32WithConstant 1
+                SetLocalVariable8 2
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 1
+                ReturnFromFunction
+    "#).unwrap())
+}
+
+#[test]
+fn test_hot_function_tiers_up_to_register_form() {
+    let function = triangle();
+    let mut vm = IrisVM::builder().tier_up_threshold(5).build();
+    assert_eq!(vm.call(function.clone(), &[Value::I32(1)]).unwrap(), Value::I32(1));
+    assert!(!vm.is_tiered_up(&function));
+
+    // The third back edge of the second call crosses the threshold; the loop carries on in
+    // register form, which doesn't count back edges, and so does the next call.
+    assert_eq!(vm.call(function.clone(), &[Value::I32(4)]).unwrap(), Value::I32(10));
+    assert_eq!(function.hotness.invocations(), 2);
+    assert_eq!(function.hotness.back_edges(), 3);
+    assert!(vm.is_tiered_up(&function));
+    assert_eq!(vm.call(function.clone(), &[Value::I32(100)]).unwrap(), Value::I32(5050));
+    assert_eq!(function.hotness.back_edges(), 3);
+}
+
+#[test]
+fn test_tiering_can_be_turned_off() {
+    let function = triangle();
+    let mut vm = IrisVM::builder().tiering(false).build();
+    assert_eq!(vm.tier_up_threshold(), None);
+    for _ in 0..10 {
+        assert_eq!(vm.call(function.clone(), &[Value::I32(200)]).unwrap(), Value::I32(20100));
+    }
+    assert!(!vm.is_tiered_up(&function));
+    assert_eq!(function.hotness.invocations(), 10);
+    assert_eq!(function.hotness.back_edges(), 2010);
+}
+
+#[test]
+fn test_untranslatable_hot_function_stays_bytecode() {
+    let double = Rc::new(assemble("
+        .function double 1
+        GetLocalVariable8 0
+        GetLocalVariable8 0
+        AddInt32
+        ReturnFromFunction
+    ").unwrap());
+    let calls_double = Rc::new(assemble("
+        .function calls_double 1
+        GetGlobalVariable8 0
+        GetLocalVariable8 0
+        CallFunction 1
+        ReturnFromFunction
+    ").unwrap());
+    let mut vm = IrisVM::builder().tier_up_threshold(2).global("double", Value::Function(double.clone())).build();
+    for i in 0..5 {
+        assert_eq!(vm.call(calls_double.clone(), &[Value::I32(i)]).unwrap(), Value::I32(i + i));
+    }
+    assert!(!vm.is_tiered_up(&calls_double));
+    assert!(vm.is_tiered_up(&double));
+}
@@ -0,0 +1,89 @@
+use std::future::Future;
+use std::pin::pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use iris_vm::stdlib::time;
+use iris_vm::vm::builder::IrisVMBuilder;
+use iris_vm::vm::clock::{Clock, VirtualClock};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn call(vm: &mut IrisVM, name: &str, args: &[Value]) -> Result<Value, VMError> {
+    match vm.globals()[vm.global_slot(name).unwrap()].clone() {
+        Value::Function(function) => vm.call(function, args),
+        other => panic!("{} is not a function: {:?}", name, other),
+    }
+}
+
+fn seconds(value: Value) -> f64 {
+    match value {
+        Value::F64(seconds) => seconds,
+        other => panic!("expected seconds, got {:?}", other),
+    }
+}
+
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+fn virtual_vm() -> (IrisVM, Rc<VirtualClock>) {
+    let clock = Rc::new(VirtualClock::starting_at(Duration::from_secs(1_700_000_000)));
+    let mut vm = IrisVMBuilder::new().clock(clock.clone()).build();
+    time::register(&mut vm);
+    (vm, clock)
+}
+
+#[test]
+fn test_virtual_clock_only_moves_when_told() {
+    let (mut vm, clock) = virtual_vm();
+    assert_eq!(call(&mut vm, "time_now", &[]).unwrap(), Value::F64(0.0));
+    assert_eq!(call(&mut vm, "time_wall", &[]).unwrap(), Value::F64(1_700_000_000.0));
+    clock.advance(Duration::from_millis(1500));
+    call(&mut vm, "time_sleep", &[Value::I64(2)]).unwrap();
+    assert_eq!(call(&mut vm, "time_now", &[]).unwrap(), Value::F64(3.5));
+    assert_eq!(call(&mut vm, "time_wall", &[]).unwrap(), Value::F64(1_700_000_003.5));
+    assert!(matches!(call(&mut vm, "time_sleep", &[Value::F64(-1.0)]).unwrap_err().root(), VMError::InvalidOperand(_)));
+}
+
+#[test]
+fn test_stopwatch() {
+    let (mut vm, clock) = virtual_vm();
+    clock.advance(Duration::from_secs(10));
+    let Value::Function(stopwatch) = call(&mut vm, "time_stopwatch", &[]).unwrap() else { panic!("expected a function") };
+    assert_eq!(vm.call(stopwatch.clone(), &[]).unwrap(), Value::F64(0.0));
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(vm.call(stopwatch, &[]).unwrap(), Value::F64(0.25));
+
+    let mut vm = IrisVM::new();
+    time::register(&mut vm);
+    let before = seconds(call(&mut vm, "time_now", &[]).unwrap());
+    let Value::Function(stopwatch) = call(&mut vm, "time_stopwatch", &[]).unwrap() else { panic!("expected a function") };
+    call(&mut vm, "time_sleep", &[Value::F64(0.01)]).unwrap();
+    assert!(seconds(vm.call(stopwatch, &[]).unwrap()) >= 0.01);
+    assert!(seconds(call(&mut vm, "time_now", &[]).unwrap()) - before >= 0.01);
+    assert!(seconds(call(&mut vm, "time_wall", &[]).unwrap()) > 1_600_000_000.0);
+}
+
+#[test]
+fn test_sleep_async() {
+    let (mut vm, clock) = virtual_vm();
+    let Value::Future(future) = call(&mut vm, "time_sleep_async", &[Value::I64(5)]).unwrap() else { panic!("expected a future") };
+    assert_eq!(clock.monotonic(), Duration::from_secs(5));
+    block_on(std::future::poll_fn(|cx| future.borrow_mut().poll(cx)));
+    assert_eq!(future.borrow().result(), Some(&Ok(Value::Null)));
+
+    let mut vm = IrisVM::new();
+    time::register(&mut vm);
+    // The timer counts from the call, not from the first poll.
+    let started = std::time::Instant::now();
+    let Value::Future(future) = call(&mut vm, "time_sleep_async", &[Value::F64(0.02)]).unwrap() else { panic!("expected a future") };
+    block_on(std::future::poll_fn(|cx| future.borrow_mut().poll(cx)));
+    assert!(started.elapsed() >= Duration::from_millis(20));
+}
@@ -0,0 +1,47 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::disasm::disassemble_instruction;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_trace_logs_instructions_and_stack() {
+    let program = Rc::new(assemble(r#"
+        .function main 0
+                LoadImmediateI32 2
+                PushConstant8 "x"
+                PopStack
+                JumpIfFalse end
+        end:
+    "#).unwrap());
+    assert_eq!(disassemble_instruction(&program, 5).as_deref(), Some(r#"PushConstant8 k0 ; "x""#));
+    assert_eq!(disassemble_instruction(&program, 8).as_deref(), Some("JumpIfFalse 0011"));
+
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    let mut out = Vec::new();
+    vm.run_traced(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("main@0000 LoadImmediateI32 2") && lines[0].ends_with("[I32(2)]"));
+    assert!(lines[1].ends_with(r#"[I32(2), Str("x")]"#));
+    assert!(lines[3].starts_with("main@0008 JumpIfFalse 0011") && lines[3].ends_with("[]"));
+}
+
+#[test]
+fn test_trace_records_the_failing_instruction() {
+    let program = Rc::new(assemble("
+        .function main 0
+        PushNull
+        PushTrue
+        AddInt32
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    let mut out = Vec::new();
+    let err = vm.run_traced(&mut out).unwrap_err();
+    assert!(matches!(err.root(), VMError::TypeMismatch(_)));
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.lines().last().unwrap().starts_with("main@0002 AddInt32"));
+    assert!(out.contains("error: "));
+}
@@ -0,0 +1,66 @@
+use iris_vm::asm::assemble;
+use iris_vm::disasm::disassemble;
+use iris_vm::vm::opcode::{instruction_len, stack_effect, OpCode, EXTENDED_PREFIX};
+use iris_vm::vm::value::Value;
+use iris_vm::vm::verifier::verify;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(source: &str) -> Result<Vec<Value>, VMError> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_create_get_and_unpack_tuple() {
+    let stack = run(r#"
+        .function pair 0
+                LoadImmediateI32 1
+                PushConstant8 "two"
+                CreateTuple 2
+                DuplicateTop
+                GetTupleElement 1
+                SwapTopTwo
+                UnpackTuple 2
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::Str("two".into()), Value::I32(1), Value::Str("two".into())]);
+}
+
+#[test]
+fn test_extended_page_encoding() {
+    let function = assemble(r#"
+        .function encode 0
+                PushNull
+                PushTrue
+                CreateTuple 2
+                ReturnFromFunction
+    "#).unwrap();
+    let code = function.bytecode.as_ref().unwrap();
+    assert_eq!(&code[2..5], &[EXTENDED_PREFIX, 0x01, 2]);
+    assert_eq!(OpCode::decode(code, 2), Some(OpCode::CreateTuple));
+    assert_eq!(instruction_len(code, 2), Some(3));
+    assert_eq!(stack_effect(code, 2), Some((2, 1)));
+    assert!(disassemble(&function).contains("CreateTuple 2"));
+    assert!(verify(&Rc::new(function)).is_ok());
+}
+
+#[test]
+fn test_tuple_errors() {
+    let error = run(r#"
+        .function mismatch 0
+                PushNull
+                CreateTuple 1
+                UnpackTuple 2
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::ArityMismatch { expected: 2, found: 1 }));
+
+    let error = run(r#"
+        .function out_of_range 0
+                PushNull
+                CreateTuple 1
+                GetTupleElement 1
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::IndexOutOfBounds));
+}
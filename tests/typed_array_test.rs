@@ -0,0 +1,81 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(source: &str) -> Result<Vec<Value>, VMError> {
+    let mut vm = IrisVM::new();
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+#[test]
+fn test_int32_array_fill_set_get() {
+    let stack = run(r#"
+        .function ints 0
+                LoadImmediateI32 4
+                NewTypedArray 0
+                DuplicateTop
+                LoadImmediateI32 7
+                TypedArrayFill
+                DuplicateTop
+                LoadImmediateI32 2
+                LoadImmediateI64 -5
+                TypedArraySet
+                DuplicateTop
+                LoadImmediateI32 2
+                TypedArrayGet
+                SwapTopTwo
+                DuplicateTop
+                LoadImmediateI32 0
+                TypedArrayGet
+                SwapTopTwo
+                TypedArrayLength
+    "#).unwrap();
+    assert_eq!(stack, vec![Value::I32(-5), Value::I32(7), Value::I64(4)]);
+}
+
+#[test]
+fn test_float64_and_byte_arrays_store_unboxed() {
+    let stack = run(r#"
+        .function mixed 0
+                LoadImmediateI32 2
+                NewTypedArray 1
+                DuplicateTop
+                LoadImmediateI32 1
+                LoadImmediateF64 2.5
+                TypedArraySet
+                LoadImmediateI32 3
+                NewTypedArray 2
+                DuplicateTop
+                LoadImmediateI32 300
+                TypedArrayFill
+    "#).unwrap();
+    match &stack[..] {
+        [Value::Float64Array(floats), Value::ByteArray(bytes)] => {
+            assert_eq!(*floats.borrow(), vec![0.0, 2.5]);
+            assert_eq!(*bytes.borrow(), vec![44, 44, 44]);
+        }
+        other => panic!("unexpected stack {:?}", other),
+    }
+}
+
+#[test]
+fn test_typed_array_errors() {
+    let error = run(r#"
+        .function oob 0
+                LoadImmediateI32 1
+                NewTypedArray 0
+                LoadImmediateI32 1
+                TypedArrayGet
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::IndexOutOfBounds));
+
+    let error = run(r#"
+        .function not_typed 0
+                PushNull
+                TypedArrayLength
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
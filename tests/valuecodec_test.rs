@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::data::valuecodec::{decode, encode};
+use iris_vm::vm::closure::{Closure, Upvalue};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::gc::Gc;
+use iris_vm::vm::range::Range;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::IrisVM;
+
+fn round_trip(value: &Value) -> Value {
+    decode(&encode(value).unwrap()).unwrap()
+}
+
+#[test]
+fn test_values_round_trip_with_sharing() {
+    let shared = Gc::new(vec![Value::I32(-7), Value::Str("shared".into())]);
+    let value = Value::Tuple(Rc::from(vec![
+        Value::Null,
+        Value::U128(u128::MAX),
+        Value::I64(i64::MIN),
+        Value::F64(0.25),
+        Value::Char('λ'),
+        Value::Range(Range::new(0, 10, 3).unwrap()),
+        Value::BigInt(Rc::new("123456789012345678901234567890".parse().unwrap())),
+        Value::Bytes(Gc::new(vec![1, 2, 3])),
+        Value::Map(Gc::new(HashMap::from([("key".to_string(), Value::Bool(true))]))),
+        Value::Array(shared.clone()),
+        Value::Array(shared),
+    ]));
+    let Value::Tuple(items) = round_trip(&value) else { panic!("expected a tuple") };
+    let Value::Tuple(original) = value else { unreachable!() };
+    assert_eq!(items[..8], original[..8]);
+    let Value::Map(map) = &items[8] else { panic!("expected a map") };
+    assert_eq!(map.borrow()["key"], Value::Bool(true));
+    let (Value::Array(a), Value::Array(b)) = (&items[9], &items[10]) else { panic!("expected arrays") };
+    assert!(Gc::ptr_eq(a, b));
+    assert_eq!(*a.borrow(), vec![Value::I32(-7), Value::Str("shared".into())]);
+}
+
+#[test]
+fn test_cycles_and_functions_round_trip() {
+    let array = Gc::new(vec![Value::I32(1)]);
+    array.borrow_mut().push(Value::Array(array.clone()));
+    let Value::Array(decoded) = round_trip(&Value::Array(array)) else { panic!("expected an array") };
+    assert_eq!(decoded.borrow()[1], Value::Array(decoded.clone()));
+
+    // A closure whose only upvalue holds the closure itself.
+    let function = assemble("
+        .function answer 0
+                LoadImmediateI32 42
+                ReturnFromFunction
+    ").unwrap();
+    let upvalue = Rc::new(RefCell::new(Upvalue::Closed(Value::Null)));
+    let closure = Rc::new(Closure { function: Rc::new(function), upvalues: vec![upvalue.clone()] });
+    *upvalue.borrow_mut() = Upvalue::Closed(Value::Closure(closure.clone()));
+    let Value::Closure(decoded) = round_trip(&Value::Closure(closure)) else { panic!("expected a closure") };
+    assert!(matches!(&*decoded.upvalues[0].borrow(), Upvalue::Closed(Value::Closure(inner)) if Rc::ptr_eq(inner, &decoded)));
+
+    let mut vm = IrisVM::new();
+    assert_eq!(vm.call(decoded.function.clone(), &[]).unwrap(), Value::I32(42));
+}
+
+#[test]
+fn test_unencodable_and_corrupt_values_fail() {
+    let native = Function::new_native("host".to_string(), 0, |_, _| Ok(Value::Null));
+    assert!(encode(&Value::Function(Rc::new(native))).unwrap_err().to_string().contains("native function 'host'"));
+
+    let array = Gc::new(Vec::new());
+    let tuple = Value::Tuple(Rc::from(vec![Value::Array(array.clone())]));
+    array.borrow_mut().push(tuple.clone());
+    assert!(encode(&tuple).unwrap_err().to_string().contains("cycle through a tuple"));
+    array.borrow_mut().clear();
+
+    let encoded = encode(&Value::Str("hello".into())).unwrap();
+    assert!(decode(&encoded[..encoded.len() - 1]).is_err());
+    assert!(decode(b"not encoded").is_err());
+}
@@ -0,0 +1,70 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::verifier::{verify, VerifyError};
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+fn function(source: &str) -> Rc<Function> {
+    Rc::new(assemble(source).unwrap())
+}
+
+#[test]
+fn test_verify_accepts_well_formed_function() {
+    let verified = verify(&function(r#"
+        .function sum 2
+                GetLocalVariable8 0
+                GetLocalVariable8 1
+                AddInt32
+                DuplicateTop
+                JumpIfFalse zero
+                ReturnFromFunction
+        zero:   BeginTryBlock handler
+                PushNull
+                ThrowException
+        handler:
+                ReturnFromFunction
+    "#)).unwrap();
+    assert_eq!(verified.max_stack_depth(), 4);
+}
+
+#[test]
+fn test_verify_rejects_malformed_bytecode() {
+    let underflow = function("PushNull\nAddInt32");
+    assert_eq!(verify(&underflow).unwrap_err(), VerifyError::StackUnderflow { offset: 1, depth: 1, needed: 2 });
+
+    let missing = function("PushConstant8 3");
+    assert_eq!(verify(&missing).unwrap_err(), VerifyError::MissingConstant { offset: 0, index: 3 });
+
+    let into_operand = Rc::new(Function::new_bytecode("j".to_string(), 0, vec![
+        OpCode::UnconditionalJump as u8, 1,
+        OpCode::LoadImmediateI16 as u8, 0, 0,
+    ], vec![]));
+    assert_eq!(verify(&into_operand).unwrap_err(), VerifyError::JumpIntoInstruction { offset: 0, target: 3 });
+
+    let unbalanced = function(r#"
+                PushTrue
+                JumpIfFalse skip
+                PushNull
+        skip:   PushNull
+    "#);
+    assert!(matches!(verify(&unbalanced).unwrap_err(), VerifyError::InconsistentStackDepth { .. }));
+
+    let truncated = Rc::new(Function::new_bytecode("t".to_string(), 0, vec![OpCode::LoadImmediateI32 as u8, 0, 0], vec![]));
+    assert_eq!(verify(&truncated).unwrap_err(), VerifyError::Truncated { offset: 0 });
+}
+
+#[test]
+fn test_vm_can_require_verification() {
+    let callee_missing = function("CallFunction 2");
+    let mut vm = IrisVM::new();
+    vm.set_require_verification(true);
+    let err = vm.push_frame(callee_missing, 0).unwrap_err();
+    assert!(matches!(err, VMError::VerificationFailed(VerifyError::StackUnderflow { .. })));
+
+    let ok = verify(&function("LoadImmediateI8 7\nReturnFromFunction")).unwrap();
+    vm.push_verified_frame(&ok, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::I8(7)]);
+}
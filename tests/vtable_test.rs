@@ -0,0 +1,82 @@
+use iris_vm::asm::assemble;
+use iris_vm::vm::object::Class;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+use std::rc::Rc;
+
+fn run(vm: &mut IrisVM, source: &str) -> Result<Vec<Value>, VMError> {
+    vm.push_frame(Rc::new(assemble(source).unwrap()), 0).unwrap();
+    vm.run()?;
+    Ok(std::mem::take(&mut vm.stack))
+}
+
+fn method(name: &str, result: i32) -> Rc<iris_vm::vm::function::Function> {
+    Rc::new(assemble(&format!(".function {} 0\n LoadImmediateI32 {}\n ReturnFromFunction", name, result)).unwrap())
+}
+
+fn hierarchy() -> (Rc<Class>, Rc<Class>) {
+    let mut base = Class::new("Base".to_string(), 0, None);
+    base.methods = vec![method("describe", 1), method("size", 10)];
+    let base = Rc::new(base);
+    let mut derived = Class::new("Derived".to_string(), 1, Some(base.clone()));
+    derived.methods = vec![method("extra", 3), method("describe", 2)];
+    (base, Rc::new(derived))
+}
+
+#[test]
+fn test_vtable_inherits_and_overrides_by_name() {
+    let (base, derived) = hierarchy();
+    let names = |class: &Class| class.vtable().iter().map(|method| method.name.clone()).collect::<Vec<_>>();
+    assert_eq!(names(&base), ["describe", "size"]);
+    assert_eq!(names(&derived), ["describe", "size", "extra"]);
+
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Derived", Value::Class(derived));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                CreateNewInstance
+                InvokeMethod8 0, 0
+                GetGlobalVariable8 0
+                CreateNewInstance
+                InvokeMethod8 1, 0
+    "#).unwrap();
+    assert_eq!(stack.last(), Some(&Value::I32(10)));
+    assert_eq!(stack[1], Value::I32(2));
+}
+
+#[test]
+fn test_set_virtual_table_replaces_dispatch() {
+    let (base, _) = hierarchy();
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Base", Value::Class(base));
+    let stack = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                GetGlobalVariable8 0
+                GetVirtualTable
+                UnpackTuple 2
+                SwapTopTwo
+                CreateTuple 2
+                SetVirtualTable
+                GetGlobalVariable8 0
+                CreateNewInstance
+                InvokeMethod8 0, 0
+    "#).unwrap();
+    assert_eq!(stack.last(), Some(&Value::I32(10)));
+}
+
+#[test]
+fn test_set_virtual_table_requires_functions() {
+    let (base, _) = hierarchy();
+    let mut vm = IrisVM::new();
+    vm.define_named_global("Base", Value::Class(base));
+    let error = run(&mut vm, r#"
+        .function main 0
+                GetGlobalVariable8 0
+                LoadImmediateI32 1
+                CreateTuple 1
+                SetVirtualTable
+    "#).unwrap_err();
+    assert!(matches!(error.root(), VMError::TypeMismatch(_)));
+}
@@ -0,0 +1,82 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::backend::wasm::{compile_function, compile_module, WasmError};
+use iris_vm::vm::function::Function;
+use iris_vm::vm::opcode::OpCode;
+use iris_vm::vm::register::TranslateError;
+use iris_vm::vm::value::Value;
+
+#[test]
+fn test_constant_function_compiles_to_expected_module() {
+    let answer = Rc::new(assemble("
+        .function answer 0
+        LoadImmediateI8 42
+        ReturnFromFunction
+    ").unwrap());
+    let expected: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6D, 0x01, 0x00, 0x00, 0x00,
+        // () -> i32
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7F,
+        0x03, 0x02, 0x01, 0x00,
+        0x07, 0x0A, 0x01, 0x06, b'a', b'n', b's', b'w', b'e', b'r', 0x00, 0x00,
+        0x0A, 0x19, 0x01, 0x17,
+        // Two i32 locals: the register and the block to run next.
+        0x01, 0x02, 0x7F,
+        0x03, 0x40, 0x02, 0x40, 0x20, 0x01, 0x0E, 0x00, 0x00, 0x0B,
+        0x41, 0x2A, 0x21, 0x00, 0x20, 0x00, 0x0F,
+        0x0B, 0x00, 0x0B,
+    ];
+    assert_eq!(compile_function(&answer).unwrap(), expected);
+}
+
+#[test]
+fn test_loops_compile_with_a_block_per_jump_target() {
+    let triangle = Rc::new(assemble(r#"
+        .function triangle 1
+                LoadImmediateI32 0
+                LoadImmediateI32 0
+        loop:   GetLocalVariable8 2
+                GetLocalVariable8 0
+                LessOrEqualInt32
+                JumpIfFalse done
+                GetLocalVariable8 1
+                GetLocalVariable8 2
+                AddInt32
+                SetLocalVariable8 1
+                PopStack
+                GetLocalVariable8 2
+                AddInt32WithConstant 1
+                SetLocalVariable8 2
+                PopStack
+                LoopJump loop
+        done:   GetLocalVariable8 1
+                ReturnFromFunction
+    "#).unwrap());
+    let answer = Rc::new(assemble(".function answer 0\nLoadImmediateI8 42\nReturnFromFunction").unwrap());
+    let module = compile_module(&[triangle, answer]).unwrap();
+    assert!(module.starts_with(b"\0asm\x01\0\0\0"));
+    // (i32) -> i32 and () -> i32.
+    assert!(module.windows(9).any(|window| window == [0x02, 0x60, 0x01, 0x7F, 0x01, 0x7F, 0x60, 0x00, 0x01]));
+    // The br_table picks between the entry code and the two jump targets.
+    assert!(module.windows(5).any(|window| window == [0x0E, 0x02, 0x00, 0x01, 0x02]));
+    assert!(module.windows(8).any(|window| window == b"triangle"));
+}
+
+#[test]
+fn test_functions_without_register_form_are_rejected() {
+    let null = Rc::new(assemble(".function null 0\nPushNull\nReturnFromFunction").unwrap());
+    assert!(matches!(compile_function(&null), Err(WasmError::NonInt32 { value: Value::Null, .. })));
+
+    let calls = Rc::new(assemble(".function calls 0\nPushNull\nCallFunction 0\nReturnFromFunction").unwrap());
+    let error = compile_function(&calls).unwrap_err();
+    assert!(matches!(error, WasmError::Translate { error: TranslateError::Unsupported { offset: 1, .. }, .. }));
+    assert_eq!(error.to_string(), "Cannot compile 'calls': CallFunction at offset 1 has no register form");
+
+    let product = Rc::new(assemble(".function product 2\nGetLocalVariable8 0\nGetLocalVariable8 1\nMultiplyInt32\nReturnFromFunction").unwrap());
+    assert!(matches!(compile_function(&product), Err(WasmError::Widening { opcode: OpCode::MultiplyInt32, .. })));
+
+    let native = Rc::new(Function::new_native("native".to_string(), 0, |_, _| Ok(Value::Null)));
+    assert!(matches!(compile_function(&native), Err(WasmError::Native(_))));
+    let answer = Rc::new(assemble(".function answer 0\nLoadImmediateI8 42\nReturnFromFunction").unwrap());
+    assert!(matches!(compile_module(&[answer.clone(), answer]), Err(WasmError::DuplicateName(_))));
+}
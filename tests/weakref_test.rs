@@ -0,0 +1,41 @@
+use std::rc::Rc;
+use iris_vm::asm::assemble;
+use iris_vm::vm::value::Value;
+use iris_vm::vm::vm::{IrisVM, VMError};
+
+#[test]
+fn test_weak_ref_upgrades_while_alive() {
+    // Keeps the array in global 0 and a weak reference to it on the stack.
+    let program = Rc::new(assemble("
+        CreateNewArray8 0
+        DuplicateTop
+        DefineGlobalVariable8 0
+        CreateWeakRef
+        DuplicateTop
+        UpgradeWeakRef
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    vm.run().unwrap();
+    assert!(matches!(&vm.stack[..], [Value::WeakRef(_), Value::Array(_)]));
+    assert_eq!(vm.stack[1], vm.get_global(0).unwrap());
+
+    // Once the last strong reference is gone the upgrade yields null.
+    vm.stack.pop();
+    vm.define_global(0, Value::Null);
+    let upgrade = Rc::new(assemble("UpgradeWeakRef").unwrap());
+    vm.push_frame(upgrade, 0).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack, vec![Value::Null]);
+}
+
+#[test]
+fn test_weak_ref_needs_a_heap_value() {
+    let program = Rc::new(assemble("
+        LoadImmediateI32 1
+        CreateWeakRef
+    ").unwrap());
+    let mut vm = IrisVM::new();
+    vm.push_frame(program, 0).unwrap();
+    assert!(matches!(vm.run().unwrap_err().root(), VMError::TypeMismatch(_)));
+}